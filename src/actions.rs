@@ -0,0 +1,881 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::components::ColorSlot;
+use crate::config;
+use winit::event::{ModifiersState, VirtualKeyCode};
+
+/// Key bindings of the user over the default ones
+pub const KEYMAP_FILE: &str = "keymap.toml";
+
+/// What the keymap file holds, a `[keys]` table of bindings like `Ctrl+Shift+S` to actions like
+/// `file.save`.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct KeymapFile {
+	#[serde(default)]
+	keys: BTreeMap<String, String>,
+}
+
+/// Every user-facing operation, with its parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+	Undo,
+	Redo,
+	ClearCanvas,
+	/// Picks a .pntr document or PNG to open in the window, replacing the document
+	OpenFile,
+	/// Saves the layers, brush and colors as a .pntr document, asking where the first time
+	SaveDocument,
+	/// Zips the brushes, palettes and other settings into a bundle for another machine
+	ExportSettings,
+	/// Replaces the settings with those of a bundle
+	ImportSettings,
+	/// Opens the preferences window, or tells it is open already
+	ShowPreferences,
+	/// Saves the canvas as PNG, reading the file back to check it when asked to
+	ExportPng { verify: bool },
+	/// Exports with the numbered preset from the config, counting from 1
+	ExportPreset(u8),
+	/// Exports the selection drawn again from its strokes this many times bigger
+	RenderSelection(u8),
+	AutocropCanvas,
+	CropToSelection,
+	ResizeCanvasMode,
+	/// Toggles typing the canvas width and height in
+	CanvasSizeMode,
+	PerspectiveMode,
+	/// Toggles moving, scaling and rotating the selection, or the active layer without one
+	TransformMode,
+	/// Switches to the next workspace profile
+	NextWorkspace,
+	/// Toggles dragging out a rectangular selection, or moving the selected pixels
+	SelectMode,
+	Deselect,
+	/// Copies the selected pixels of the active layer to the selection buffer and the clipboard,
+	/// or the whole canvas to the clipboard without a selection
+	CopySelection,
+	CutSelection,
+	DeleteSelection,
+	/// Widens (positive) or narrows the feathered selection edges by steps of 4 pixels
+	FeatherSelection(i32),
+	/// Blends the foreground color over the selected pixels of the active layer
+	FillSelection,
+	/// Paints a border of the foreground color along the selection edge
+	StrokeSelection,
+	/// Widens (positive) or narrows the selection stroke by a pixel per step
+	SelectionStrokeWidth(i32),
+	/// Pastes the selection buffer at the selection, or the document corner without one. An image
+	/// copied in another application is pasted on a new layer instead
+	PasteSelection,
+	/// Toggles flood filling with the foreground color
+	FillMode,
+	/// Raises (positive) or lowers the fill tolerance by steps of 8/255
+	FillTolerance(i32),
+	/// Whether fills only cover transparent pixels, painting under line art on the same layer
+	ToggleFillBehind,
+	/// Toggles typing text where the canvas is clicked
+	TextMode,
+	/// Toggles typing text along a path dragged on the canvas
+	TextPathMode,
+	/// Spreads (positive) or tightens the glyphs of text along a path by a pixel
+	TextSpacing(i32),
+	/// Raises (positive) or lowers text along a path by a pixel
+	TextBaseline(i32),
+	/// Grows (positive) or shrinks typed text by a step
+	TextSize(i32),
+	/// Toggles picking a color from the canvas
+	EyedropperMode(ColorSlot),
+	SwapColors,
+	/// Replaces the color under the cursor, with a per channel tolerance
+	ReplaceColor(f32),
+	/// Grows (positive) or shrinks the brush by steps of about a tenth of its size
+	BrushSize(i32),
+	/// Changes the most a stroke covers by steps of 10%
+	BrushOpacity(i32),
+	/// Changes how much each pass of a stroke adds by steps of 10%
+	BrushFlow(i32),
+	/// Lengthens (positive) or shortens the stabilizer string by steps of 4 pixels
+	Stabilizer(i32),
+	/// Turns the stamped brush tip clockwise (positive) or back by steps of 15 degrees
+	BrushRotation(i32),
+	/// Fixes the random dabs of the brush dynamics to a new pattern, the same for every stroke
+	ReseedBrush,
+	/// Stamps the next image of the brushes assets along strokes, and back to the round brush after the last
+	NextBrushTip,
+	/// Switches to the brush saved under the number key
+	BrushPreset(u8),
+	/// Saves the brush and its tip under the number key
+	SaveBrushPreset(u8),
+	/// Shows or hides a stroke painted with the current brush
+	ToggleBrushPreview,
+	/// Whether strokes are smoothed and spaced by screen pixels, following the zoom
+	ToggleZoomSmoothing,
+	ToggleLockPixels,
+	ToggleLockPosition,
+	/// Strokes on the active layer only recolor its pixels, keeping how opaque they are
+	ToggleLockAlpha,
+	/// Picks an image to add as a layer linked to the file, drawn again when the file changes
+	LinkImage,
+	/// Opens the file of the active linked layer in the application associated with it
+	EditLinkedSource,
+	/// Opens the pressure curve editor for the current input device
+	PressureCalibration,
+	/// Switches strokes between painting and erasing
+	ToggleEraser,
+	ToggleTouchPainting,
+	/// Whether the eraser end of the stylus erases or draws like the tip
+	ToggleEraserEnd,
+	NewLayer,
+	/// Adds a layer for notes above the active one, shown but left out of exports
+	NewAnnotationLayer,
+	/// Whether exports of the document include its annotation layers
+	ToggleAnnotationExport,
+	/// Whether the document is saved with its strokes, so regions can be drawn again from them after opening it
+	ToggleSaveHistory,
+	DeleteLayer,
+	/// Activates the layer above (positive) or below
+	SelectLayer(i32),
+	/// Activates the layer above (positive) or below, selecting every layer from the last one activated to it
+	ExtendLayerSelection(i32),
+	/// Moves the selected layers up (positive) or down the stack together
+	MoveLayer(i32),
+	/// Merges the selected layers, or the active one into the layer below it
+	MergeLayers,
+	/// Puts the selected layers in a new group, moved together under the topmost of them
+	GroupLayers,
+	/// Takes the selected layers out of their groups
+	UngroupLayers,
+	/// Tags the document with the next color, shown in the window title and icon
+	TagDocument,
+	/// Tags the selected layers with the color after that of the active one
+	TagLayer,
+	/// Types a new name for the active layer in the field of the layers panel
+	RenameLayer,
+	ToggleLayerVisibility,
+	/// Changes the active layer opacity by steps of 10%
+	LayerOpacity(i32),
+	/// Adds a white mask to the selected layers, or removes their masks when the active layer has one
+	ToggleLayerMask,
+	/// Switches strokes and clears between the pixels of the active layer and its mask
+	EditLayerMask,
+	/// Cycles the canvas through its recolored palette variants, and back to the original
+	PaletteSwapPreview,
+	/// Remaps the palette entry under the cursor to the foreground color, in the variant previewed
+	RemapPaletteEntry,
+	/// Picks where to export every recolored palette variant as PNG
+	ExportPaletteVariants,
+	/// Zooms the canvas in (positive) or out around the window center
+	Zoom(i32),
+	/// Toggles painting every stroke with turned and mirrored copies around a draggable center
+	Kaleidoscope,
+	/// Goes from no symmetry through mirroring left to right, top to bottom, both ways and turning around the center
+	CycleSymmetry,
+	/// Adds (positive) or removes copies turned around the symmetry center
+	SymmetryFolds(i32),
+	/// Shows or hides the mixer, a scratch canvas to mix and pick colors on
+	ToggleMixer,
+	/// Shows or hides a hue ring and saturation and value square picking the foreground color
+	ToggleColorPicker,
+	/// Opens or closes a hue ring with the recent colors and the eyedropper at the cursor
+	QuickColorPicker,
+	/// Shows or hides sliders and fields for the brush size and opacity and the zoom, with the
+	/// eraser and symmetry next to them
+	ToggleControls,
+	/// Shows or hides the GPU memory used by layers, tiles, undo, thumbnails and atlases, with
+	/// buttons freeing some
+	ToggleMemoryPanel,
+	/// Shows or hides the layers, clicked to activate them and with Ctrl or Shift to select several
+	ToggleLayersPanel,
+	/// Lists every action searched by name, running the one picked
+	CommandPalette,
+	/// Opens a window showing the document as it is painted, for a second monitor or a stream
+	LivePreview,
+	/// Shows or hides the recent and pinned colors, clicked to paint with
+	ToggleSwatches,
+	/// Pins the foreground color among the swatches, or unpins it
+	PinColor,
+	/// Pins the colors of the GIMP palettes in the palettes assets
+	ImportPalettes,
+	/// Shows or hides the aspect ratio and safe area guides
+	ToggleGuides,
+	/// Whether undo and redo scroll to what they changed when it's out of view
+	ToggleFollowUndo,
+	/// Cycles the viewport through the proofing targets, and back to showing the artwork as it is
+	CycleProof,
+	/// Cycles comparing the document with a snapshot of it, by swiping and by blending, and back to off
+	CompareMode,
+	/// Retakes the compared snapshot from the document as it is now
+	CompareSnapshot,
+	StartTour,
+	Confirm,
+	Cancel,
+	RecordMacro(u8),
+	PlayMacro(u8),
+}
+
+impl Action {
+	pub fn id(&self) -> &'static str {
+		use Action::*;
+		match self {
+			Undo => "history.undo",
+			Redo => "history.redo",
+			ClearCanvas => "canvas.clear",
+			OpenFile => "file.open",
+			SaveDocument => "file.save",
+			ExportSettings => "file.export_settings",
+			ImportSettings => "file.import_settings",
+			ShowPreferences => "file.preferences",
+			ExportPng { .. } => "file.export",
+			ExportPreset(_) => "file.export_preset",
+			RenderSelection(_) => "select.render",
+			AutocropCanvas => "canvas.autocrop",
+			CropToSelection => "canvas.crop_to_selection",
+			ResizeCanvasMode => "canvas.resize_mode",
+			CanvasSizeMode => "canvas.size_mode",
+			PerspectiveMode => "canvas.perspective_mode",
+			TransformMode => "canvas.transform_mode",
+			ReplaceColor(_) => "canvas.replace_color",
+			NextWorkspace => "workspace.next",
+			SelectMode => "select.mode",
+			Deselect => "select.none",
+			CopySelection => "select.copy",
+			CutSelection => "select.cut",
+			DeleteSelection => "select.delete",
+			FeatherSelection(_) => "select.feather",
+			FillSelection => "select.fill",
+			StrokeSelection => "select.stroke",
+			SelectionStrokeWidth(_) => "select.stroke_width",
+			PasteSelection => "select.paste",
+			FillMode => "fill.mode",
+			FillTolerance(_) => "fill.tolerance",
+			ToggleFillBehind => "fill.behind",
+			TextMode => "text.mode",
+			TextPathMode => "text.path_mode",
+			TextSpacing(_) => "text.spacing",
+			TextBaseline(_) => "text.baseline",
+			TextSize(_) => "text.size",
+			EyedropperMode(_) => "color.eyedropper_mode",
+			SwapColors => "color.swap",
+			BrushSize(_) => "brush.size",
+			BrushOpacity(_) => "brush.opacity",
+			BrushFlow(_) => "brush.flow",
+			Stabilizer(_) => "brush.stabilizer",
+			BrushRotation(_) => "brush.rotation",
+			ReseedBrush => "brush.reseed",
+			NextBrushTip => "brush.tip",
+			BrushPreset(_) => "brush.preset",
+			SaveBrushPreset(_) => "brush.save_preset",
+			ToggleBrushPreview => "brush.preview",
+			ToggleZoomSmoothing => "brush.zoom_smoothing",
+			ToggleLockPixels => "layer.lock_pixels",
+			ToggleLockPosition => "layer.lock_position",
+			ToggleLockAlpha => "layer.lock_alpha",
+			LinkImage => "layer.link_image",
+			EditLinkedSource => "layer.edit_linked",
+			PressureCalibration => "brush.pressure_calibration",
+			ToggleEraser => "brush.eraser",
+			ToggleTouchPainting => "input.touch_paints",
+			ToggleEraserEnd => "input.eraser_end",
+			NewLayer => "layer.new",
+			NewAnnotationLayer => "layer.new_annotation",
+			ToggleAnnotationExport => "file.export_annotations",
+			ToggleSaveHistory => "file.save_history",
+			DeleteLayer => "layer.delete",
+			SelectLayer(_) => "layer.select",
+			ExtendLayerSelection(_) => "layer.extend_selection",
+			MoveLayer(_) => "layer.move",
+			MergeLayers => "layer.merge",
+			GroupLayers => "layer.group",
+			UngroupLayers => "layer.ungroup",
+			TagDocument => "file.tag",
+			TagLayer => "layer.tag",
+			RenameLayer => "layer.rename",
+			ToggleLayerVisibility => "layer.visibility",
+			LayerOpacity(_) => "layer.opacity",
+			ToggleLayerMask => "layer.mask",
+			EditLayerMask => "layer.edit_mask",
+			PaletteSwapPreview => "palette.swap_preview",
+			RemapPaletteEntry => "palette.remap_entry",
+			ExportPaletteVariants => "palette.export_variants",
+			Zoom(_) => "view.zoom",
+			Kaleidoscope => "symmetry.kaleidoscope",
+			CycleSymmetry => "symmetry.cycle",
+			SymmetryFolds(_) => "symmetry.folds",
+			ToggleMixer => "palette.mixer",
+			ToggleColorPicker => "palette.picker",
+			QuickColorPicker => "palette.quick_picker",
+			ToggleControls => "view.controls",
+			ToggleMemoryPanel => "view.memory",
+			ToggleLayersPanel => "view.layers",
+			CommandPalette => "view.command_palette",
+			LivePreview => "view.live_preview",
+			ToggleSwatches => "palette.swatches",
+			PinColor => "palette.pin",
+			ImportPalettes => "palette.import",
+			ToggleGuides => "view.guides",
+			ToggleFollowUndo => "view.follow_undo",
+			CycleProof => "view.proof",
+			CompareMode => "view.compare",
+			CompareSnapshot => "view.compare_snapshot",
+			StartTour => "help.tour",
+			Confirm => "tool.confirm",
+			Cancel => "tool.cancel",
+			RecordMacro(_) => "macro.record",
+			PlayMacro(_) => "macro.play",
+		}
+	}
+
+	/// Changes what is saved in the document without going through an edit.
+	pub fn changes_document(&self) -> bool {
+		use Action::*;
+		matches!(self,
+			NewLayer | NewAnnotationLayer | DeleteLayer | MoveLayer(_) | MergeLayers | GroupLayers | UngroupLayers | TagDocument
+			| TagLayer | ToggleLayerVisibility | LayerOpacity(_) | ToggleLayerMask | ToggleLockPixels | ToggleLockPosition
+			| ToggleLockAlpha | ToggleAnnotationExport | ToggleSaveHistory
+		)
+	}
+
+	/// The same action with the argument written in a config file, None if it takes none or it
+	/// isn't one it takes.
+	fn with_argument(&self, argument: &str) -> Option<Action> {
+		use Action::*;
+		let steps = || argument.parse::<i32>().ok();
+		let slot = || argument.parse::<u8>().ok();
+		return Some(match self {
+			ExportPng { .. } => ExportPng { verify: argument.parse().ok()? },
+			ExportPreset(_) => ExportPreset(slot()?),
+			RenderSelection(_) => RenderSelection(slot()?),
+			ReplaceColor(_) => ReplaceColor(argument.parse::<f32>().ok().filter(|t| (0. ..=1.).contains(t))?),
+			FeatherSelection(_) => FeatherSelection(steps()?),
+			SelectionStrokeWidth(_) => SelectionStrokeWidth(steps()?),
+			FillTolerance(_) => FillTolerance(steps()?),
+			TextSpacing(_) => TextSpacing(steps()?),
+			TextBaseline(_) => TextBaseline(steps()?),
+			TextSize(_) => TextSize(steps()?),
+			EyedropperMode(_) => EyedropperMode(match argument {
+				"foreground" => ColorSlot::Foreground,
+				"background" => ColorSlot::Background,
+				_ => return None,
+			}),
+			BrushSize(_) => BrushSize(steps()?),
+			BrushOpacity(_) => BrushOpacity(steps()?),
+			BrushFlow(_) => BrushFlow(steps()?),
+			Stabilizer(_) => Stabilizer(steps()?),
+			BrushRotation(_) => BrushRotation(steps()?),
+			BrushPreset(_) => BrushPreset(slot()?),
+			SaveBrushPreset(_) => SaveBrushPreset(slot()?),
+			SelectLayer(_) => SelectLayer(steps()?),
+			ExtendLayerSelection(_) => ExtendLayerSelection(steps()?),
+			MoveLayer(_) => MoveLayer(steps()?),
+			LayerOpacity(_) => LayerOpacity(steps()?),
+			Zoom(_) => Zoom(steps()?),
+			SymmetryFolds(_) => SymmetryFolds(steps()?),
+			RecordMacro(_) => RecordMacro(slot()?),
+			PlayMacro(_) => PlayMacro(slot()?),
+			_ => return None,
+		});
+	}
+
+	/// Macro control actions are never recorded themselves, only what they expand to.
+	fn recordable(&self) -> bool {
+		!matches!(self, Action::RecordMacro(_) | Action::PlayMacro(_))
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+	pub key: VirtualKeyCode,
+	pub modifiers: ModifiersState,
+}
+
+impl KeyBinding {
+	pub fn new(key: VirtualKeyCode, modifiers: ModifiersState) -> Self {
+		Self { key, modifiers }
+	}
+
+	/// A binding written the way it is shown, like `Ctrl+Shift+Z`, in any case.
+	pub fn parse(text: &str) -> Option<Self> {
+		let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+		// The plus key itself isn't one that can be bound
+		let name = parts.pop().filter(|n| !n.is_empty())?;
+		let mut modifiers = ModifiersState::empty();
+		for part in parts {
+			modifiers |= match part.to_ascii_lowercase().as_str() {
+				"ctrl" => ModifiersState::CTRL,
+				"alt" => ModifiersState::ALT,
+				"shift" => ModifiersState::SHIFT,
+				"super" => ModifiersState::LOGO,
+				_ => return None,
+			};
+		}
+		let key = BINDABLE_KEYS.iter().find(|k| KeyBinding::new(**k, ModifiersState::empty()).to_string().eq_ignore_ascii_case(name))?;
+		return Some(Self::new(*key, modifiers));
+	}
+}
+
+impl std::fmt::Display for KeyBinding {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (modifier, name) in [(ModifiersState::CTRL, "Ctrl+"), (ModifiersState::ALT, "Alt+"), (ModifiersState::SHIFT, "Shift+"), (ModifiersState::LOGO, "Super+")] {
+			if self.modifiers.contains(modifier) {
+				write!(f, "{name}")?;
+			}
+		}
+
+		use VirtualKeyCode::*;
+		match self.key {
+			Key1 | Key2 | Key3 | Key4 | Key5 | Key6 | Key7 | Key8 | Key9 | Key0 => write!(f, "{}", &format!("{:?}", self.key)[3..]),
+			LBracket => write!(f, "["),
+			RBracket => write!(f, "]"),
+			Return => write!(f, "Enter"),
+			Escape => write!(f, "Esc"),
+			key => write!(f, "{key:?}"),
+		}
+	}
+}
+
+/// Keys bindings can be read with from config files.
+const BINDABLE_KEYS: [VirtualKeyCode; 74] = {
+	use VirtualKeyCode::*;
+	[
+		A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+		Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+		F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+		Apostrophe, Backslash, Comma, Equals, Grave, LBracket, Minus, Period, RBracket, Semicolon, Slash,
+		Back, Delete, Down, End, Escape, Home, Insert, Left, PageDown, PageUp, Return, Right, Space, Tab, Up,
+	]
+};
+
+pub struct ActionInfo {
+	pub description: &'static str,
+	/// As registered, what config files name by its id
+	action: Action,
+}
+
+const REPLACE_TOLERANCE: f32 = 8. / 255.;
+
+const MACRO_KEYS: [VirtualKeyCode; 9] = {
+	use VirtualKeyCode::*;
+	[Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9]
+};
+
+pub struct ActionRegistry {
+	actions: HashMap<&'static str, ActionInfo>,
+	bindings: HashMap<KeyBinding, Action>,
+	/// Bindings from `new`, restored before applying overrides
+	defaults: HashMap<KeyBinding, Action>,
+
+	macros: HashMap<u8, Vec<Action>>,
+	recording: Option<(u8, Vec<Action>)>,
+}
+
+impl ActionRegistry {
+	pub fn new() -> Self {
+		let mut registry = Self {
+			actions: HashMap::new(),
+			bindings: HashMap::new(),
+			defaults: HashMap::new(),
+			macros: HashMap::new(),
+			recording: None,
+		};
+
+		registry.register(Action::Undo, "Undo the last edit");
+		registry.register(Action::Redo, "Redo the last undone edit");
+		registry.register(Action::ClearCanvas, "Clear the canvas");
+		registry.register(Action::OpenFile, "Open a document or image");
+		registry.register(Action::SaveDocument, "Save the document");
+		registry.register(Action::ExportSettings, "Export the settings as a bundle");
+		registry.register(Action::ImportSettings, "Import the settings of a bundle");
+		registry.register(Action::ShowPreferences, "Open the preferences");
+		registry.register(Action::ExportPng { verify: false }, "Export the canvas as PNG");
+		registry.register(Action::ExportPreset(0), "Export the canvas with a preset");
+		registry.register(Action::RenderSelection(0), "Export the selection re-rendered from its strokes");
+		registry.register(Action::AutocropCanvas, "Crop the canvas to its content");
+		registry.register(Action::CropToSelection, "Crop the canvas to the selection");
+		registry.register(Action::ResizeCanvasMode, "Toggle dragging the canvas edges to resize it");
+		registry.register(Action::CanvasSizeMode, "Toggle typing in the canvas width and height");
+		registry.register(Action::PerspectiveMode, "Toggle dragging the canvas corners for a perspective transform");
+		registry.register(Action::TransformMode, "Toggle moving, scaling and rotating the selection or layer");
+		registry.register(Action::ReplaceColor(0.), "Replace the color under the cursor with the foreground color");
+		registry.register(Action::NextWorkspace, "Switch to the next workspace profile");
+		registry.register(Action::SelectMode, "Toggle selecting a rectangle, or moving the selection");
+		registry.register(Action::Deselect, "Select nothing");
+		registry.register(Action::CopySelection, "Copy the selected pixels, or the whole canvas");
+		registry.register(Action::CutSelection, "Cut the selected pixels");
+		registry.register(Action::DeleteSelection, "Clear the selected pixels");
+		registry.register(Action::FeatherSelection(0), "Widen/narrow the feathered selection edges");
+		registry.register(Action::FillSelection, "Fill the selection with the foreground color");
+		registry.register(Action::StrokeSelection, "Stroke the selection edge with the foreground color");
+		registry.register(Action::SelectionStrokeWidth(0), "Widen/narrow the selection stroke");
+		registry.register(Action::PasteSelection, "Paste the copied pixels, or the clipboard image as a new layer");
+		registry.register(Action::FillMode, "Toggle flood filling with the foreground color");
+		registry.register(Action::FillTolerance(0), "Raise/lower the fill tolerance");
+		registry.register(Action::ToggleFillBehind, "Toggle filling behind the layer pixels");
+		registry.register(Action::TextMode, "Toggle typing text on the canvas");
+		registry.register(Action::TextPathMode, "Toggle typing text along a path");
+		registry.register(Action::TextSpacing(0), "Change the spacing of text along a path");
+		registry.register(Action::TextBaseline(0), "Raise/lower text along a path");
+		registry.register(Action::TextSize(0), "Change the size of typed text");
+		registry.register(Action::EyedropperMode(ColorSlot::Foreground), "Toggle picking the foreground/background color from the canvas");
+		registry.register(Action::SwapColors, "Swap the foreground and background colors");
+		registry.register(Action::BrushSize(0), "Change the brush size");
+		registry.register(Action::BrushOpacity(0), "Change the brush opacity");
+		registry.register(Action::BrushFlow(0), "Change the brush flow");
+		registry.register(Action::Stabilizer(0), "Strengthen/weaken the stroke stabilizer");
+		registry.register(Action::BrushRotation(0), "Turn the brush tip");
+		registry.register(Action::ReseedBrush, "Pick new random dabs for the brush");
+		registry.register(Action::NextBrushTip, "Switch to the next brush tip image");
+		registry.register(Action::BrushPreset(0), "Switch to a saved brush");
+		registry.register(Action::SaveBrushPreset(0), "Save the brush as a preset");
+		registry.register(Action::ToggleBrushPreview, "Show/hide the brush preview");
+		registry.register(Action::ToggleZoomSmoothing, "Toggle smoothing strokes by the zoom");
+		registry.register(Action::ToggleLockPixels, "Lock/unlock the pixels of the active layer");
+		registry.register(Action::ToggleLockPosition, "Lock/unlock moving and transforming the active layer");
+		registry.register(Action::ToggleLockAlpha, "Lock/unlock the transparency of the active layer");
+		registry.register(Action::LinkImage, "Add a layer linked to an image file");
+		registry.register(Action::EditLinkedSource, "Edit the linked image of the active layer in its application");
+		registry.register(Action::PressureCalibration, "Calibrate the pressure curve of the input device");
+		registry.register(Action::ToggleEraser, "Toggle the eraser");
+		registry.register(Action::ToggleTouchPainting, "Toggle painting with touch input");
+		registry.register(Action::ToggleEraserEnd, "Toggle erasing with the eraser end of the stylus");
+		registry.register(Action::NewLayer, "Add a layer above the active one");
+		registry.register(Action::NewAnnotationLayer, "Add an annotation layer above the active one");
+		registry.register(Action::ToggleAnnotationExport, "Toggle exporting the annotation layers");
+		registry.register(Action::ToggleSaveHistory, "Toggle saving the strokes with the document");
+		registry.register(Action::DeleteLayer, "Delete the selected layers");
+		registry.register(Action::SelectLayer(0), "Activate the layer above/below");
+		registry.register(Action::ExtendLayerSelection(0), "Extend the layer selection up/down");
+		registry.register(Action::MoveLayer(0), "Move the selected layers up/down");
+		registry.register(Action::MergeLayers, "Merge the selected layers, or the active one down");
+		registry.register(Action::GroupLayers, "Group the selected layers");
+		registry.register(Action::UngroupLayers, "Take the selected layers out of their groups");
+		registry.register(Action::TagDocument, "Tag the document window with the next color");
+		registry.register(Action::TagLayer, "Tag the selected layers with the next color");
+		registry.register(Action::RenameLayer, "Rename the active layer");
+		registry.register(Action::ToggleLayerVisibility, "Show/hide the selected layers");
+		registry.register(Action::LayerOpacity(0), "Change the opacity of the selected layers");
+		registry.register(Action::ToggleLayerMask, "Add/remove a mask on the selected layers");
+		registry.register(Action::EditLayerMask, "Paint the mask of the active layer, or its pixels");
+		registry.register(Action::PaletteSwapPreview, "Preview the next recolored palette variant");
+		registry.register(Action::RemapPaletteEntry, "Remap the palette color under the cursor to the foreground color");
+		registry.register(Action::ExportPaletteVariants, "Export every recolored palette variant as PNG");
+		registry.register(Action::Zoom(0), "Zoom the canvas in/out");
+		registry.register(Action::Kaleidoscope, "Toggle the kaleidoscope");
+		registry.register(Action::CycleSymmetry, "Cycle the symmetry modes");
+		registry.register(Action::SymmetryFolds(0), "Add/remove symmetry copies");
+		registry.register(Action::ToggleMixer, "Show/hide the color mixer");
+		registry.register(Action::ToggleColorPicker, "Show/hide the color picker");
+		registry.register(Action::QuickColorPicker, "Show/hide a color picker with the recent colors at the cursor");
+		registry.register(Action::ToggleControls, "Show/hide the brush, zoom and symmetry controls");
+		registry.register(Action::ToggleMemoryPanel, "Show/hide the GPU memory usage");
+		registry.register(Action::ToggleLayersPanel, "Show/hide the layers panel");
+		registry.register(Action::CommandPalette, "Search the commands and run one");
+		registry.register(Action::LivePreview, "Open a live preview of the document in a window");
+		registry.register(Action::ToggleSwatches, "Show/hide the color swatches");
+		registry.register(Action::PinColor, "Pin/unpin the foreground color");
+		registry.register(Action::ImportPalettes, "Pin the colors of the GIMP palettes in the assets");
+		registry.register(Action::ToggleGuides, "Show/hide the crop guides");
+		registry.register(Action::ToggleFollowUndo, "Toggle scrolling to what undo/redo changed");
+		registry.register(Action::CycleProof, "Cycle the proofing targets");
+		registry.register(Action::CompareMode, "Compare with a snapshot: swipe, blend, off");
+		registry.register(Action::CompareSnapshot, "Take the compared snapshot again");
+		registry.register(Action::StartTour, "Start the guided tour");
+		registry.register(Action::Confirm, "Apply the active tool");
+		registry.register(Action::Cancel, "Leave the active tool");
+		registry.register(Action::RecordMacro(0), "Start/stop recording a macro");
+		registry.register(Action::PlayMacro(0), "Play a recorded macro");
+
+		let none = ModifiersState::empty();
+		registry.bind(KeyBinding::new(VirtualKeyCode::Z, ModifiersState::CTRL), Action::Undo);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Z, ModifiersState::CTRL | ModifiersState::SHIFT), Action::Redo);
+		registry.bind(KeyBinding::new(VirtualKeyCode::C, none), Action::ClearCanvas);
+		registry.bind(KeyBinding::new(VirtualKeyCode::O, ModifiersState::CTRL), Action::OpenFile);
+		registry.bind(KeyBinding::new(VirtualKeyCode::S, ModifiersState::CTRL | ModifiersState::SHIFT), Action::SaveDocument);
+		registry.bind(KeyBinding::new(VirtualKeyCode::E, ModifiersState::CTRL | ModifiersState::ALT), Action::ExportSettings);
+		registry.bind(KeyBinding::new(VirtualKeyCode::I, ModifiersState::CTRL | ModifiersState::ALT), Action::ImportSettings);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Comma, ModifiersState::CTRL), Action::ShowPreferences);
+		registry.bind(KeyBinding::new(VirtualKeyCode::S, ModifiersState::CTRL), Action::ExportPng { verify: false });
+		registry.bind(KeyBinding::new(VirtualKeyCode::S, ModifiersState::CTRL | ModifiersState::ALT), Action::ExportPng { verify: true });
+		registry.bind(KeyBinding::new(VirtualKeyCode::X, ModifiersState::CTRL | ModifiersState::SHIFT), Action::AutocropCanvas);
+		registry.bind(KeyBinding::new(VirtualKeyCode::C, ModifiersState::CTRL | ModifiersState::SHIFT), Action::CropToSelection);
+		registry.bind(KeyBinding::new(VirtualKeyCode::R, none), Action::ResizeCanvasMode);
+		registry.bind(KeyBinding::new(VirtualKeyCode::C, ModifiersState::CTRL | ModifiersState::ALT), Action::CanvasSizeMode);
+		registry.bind(KeyBinding::new(VirtualKeyCode::P, none), Action::PerspectiveMode);
+		registry.bind(KeyBinding::new(VirtualKeyCode::T, ModifiersState::CTRL), Action::TransformMode);
+		registry.bind(KeyBinding::new(VirtualKeyCode::R, ModifiersState::SHIFT), Action::ReplaceColor(REPLACE_TOLERANCE));
+		registry.bind(KeyBinding::new(VirtualKeyCode::W, none), Action::NextWorkspace);
+		registry.bind(KeyBinding::new(VirtualKeyCode::S, none), Action::SelectMode);
+		registry.bind(KeyBinding::new(VirtualKeyCode::D, ModifiersState::CTRL), Action::Deselect);
+		registry.bind(KeyBinding::new(VirtualKeyCode::C, ModifiersState::CTRL), Action::CopySelection);
+		registry.bind(KeyBinding::new(VirtualKeyCode::X, ModifiersState::CTRL), Action::CutSelection);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Delete, none), Action::DeleteSelection);
+		registry.bind(KeyBinding::new(VirtualKeyCode::RBracket, ModifiersState::CTRL | ModifiersState::ALT), Action::FeatherSelection(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::LBracket, ModifiersState::CTRL | ModifiersState::ALT), Action::FeatherSelection(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Delete, ModifiersState::ALT), Action::FillSelection);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Delete, ModifiersState::CTRL | ModifiersState::SHIFT), Action::StrokeSelection);
+		registry.bind(KeyBinding::new(VirtualKeyCode::RBracket, ModifiersState::CTRL | ModifiersState::SHIFT), Action::SelectionStrokeWidth(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::LBracket, ModifiersState::CTRL | ModifiersState::SHIFT), Action::SelectionStrokeWidth(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::V, ModifiersState::CTRL), Action::PasteSelection);
+		registry.bind(KeyBinding::new(VirtualKeyCode::G, none), Action::FillMode);
+		registry.bind(KeyBinding::new(VirtualKeyCode::G, ModifiersState::SHIFT), Action::ToggleFillBehind);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Period, none), Action::FillTolerance(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Comma, none), Action::FillTolerance(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::T, none), Action::TextMode);
+		registry.bind(KeyBinding::new(VirtualKeyCode::T, ModifiersState::SHIFT), Action::TextPathMode);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Right, ModifiersState::ALT), Action::TextSpacing(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Left, ModifiersState::ALT), Action::TextSpacing(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Up, ModifiersState::ALT), Action::TextBaseline(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Down, ModifiersState::ALT), Action::TextBaseline(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::PageUp, ModifiersState::ALT), Action::TextSize(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::PageDown, ModifiersState::ALT), Action::TextSize(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::I, none), Action::EyedropperMode(ColorSlot::Foreground));
+		registry.bind(KeyBinding::new(VirtualKeyCode::I, ModifiersState::SHIFT), Action::EyedropperMode(ColorSlot::Background));
+		registry.bind(KeyBinding::new(VirtualKeyCode::X, none), Action::SwapColors);
+		registry.bind(KeyBinding::new(VirtualKeyCode::LBracket, none), Action::BrushSize(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::RBracket, none), Action::BrushSize(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Apostrophe, ModifiersState::CTRL), Action::BrushOpacity(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Semicolon, ModifiersState::CTRL), Action::BrushOpacity(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Apostrophe, ModifiersState::SHIFT), Action::BrushFlow(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Semicolon, ModifiersState::SHIFT), Action::BrushFlow(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Apostrophe, none), Action::Stabilizer(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Semicolon, none), Action::Stabilizer(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Period, ModifiersState::ALT), Action::BrushRotation(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Comma, ModifiersState::ALT), Action::BrushRotation(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::J, ModifiersState::ALT), Action::ReseedBrush);
+		registry.bind(KeyBinding::new(VirtualKeyCode::O, none), Action::NextBrushTip);
+		registry.bind(KeyBinding::new(VirtualKeyCode::B, none), Action::ToggleBrushPreview);
+		registry.bind(KeyBinding::new(VirtualKeyCode::B, ModifiersState::CTRL), Action::ToggleZoomSmoothing);
+		registry.bind(KeyBinding::new(VirtualKeyCode::L, none), Action::ToggleLockPixels);
+		registry.bind(KeyBinding::new(VirtualKeyCode::L, ModifiersState::SHIFT), Action::ToggleLockPosition);
+		registry.bind(KeyBinding::new(VirtualKeyCode::L, ModifiersState::ALT), Action::ToggleLockAlpha);
+		registry.bind(KeyBinding::new(VirtualKeyCode::L, ModifiersState::CTRL | ModifiersState::SHIFT), Action::LinkImage);
+		registry.bind(KeyBinding::new(VirtualKeyCode::L, ModifiersState::CTRL | ModifiersState::ALT), Action::EditLinkedSource);
+		registry.bind(KeyBinding::new(VirtualKeyCode::P, ModifiersState::CTRL | ModifiersState::ALT), Action::PressureCalibration);
+		registry.bind(KeyBinding::new(VirtualKeyCode::E, none), Action::ToggleEraser);
+		registry.bind(KeyBinding::new(VirtualKeyCode::T, ModifiersState::CTRL | ModifiersState::SHIFT), Action::ToggleTouchPainting);
+		registry.bind(KeyBinding::new(VirtualKeyCode::E, ModifiersState::CTRL | ModifiersState::SHIFT), Action::ToggleEraserEnd);
+		registry.bind(KeyBinding::new(VirtualKeyCode::N, ModifiersState::CTRL | ModifiersState::SHIFT), Action::NewLayer);
+		registry.bind(KeyBinding::new(VirtualKeyCode::N, ModifiersState::ALT), Action::NewAnnotationLayer);
+		registry.bind(KeyBinding::new(VirtualKeyCode::N, ModifiersState::CTRL | ModifiersState::ALT), Action::ToggleAnnotationExport);
+		registry.bind(KeyBinding::new(VirtualKeyCode::H, ModifiersState::CTRL | ModifiersState::ALT), Action::ToggleSaveHistory);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Delete, ModifiersState::SHIFT), Action::DeleteLayer);
+		registry.bind(KeyBinding::new(VirtualKeyCode::RBracket, ModifiersState::ALT), Action::SelectLayer(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::LBracket, ModifiersState::ALT), Action::SelectLayer(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::RBracket, ModifiersState::ALT | ModifiersState::SHIFT), Action::ExtendLayerSelection(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::LBracket, ModifiersState::ALT | ModifiersState::SHIFT), Action::ExtendLayerSelection(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::E, ModifiersState::CTRL), Action::MergeLayers);
+		registry.bind(KeyBinding::new(VirtualKeyCode::G, ModifiersState::CTRL), Action::GroupLayers);
+		registry.bind(KeyBinding::new(VirtualKeyCode::G, ModifiersState::CTRL | ModifiersState::SHIFT), Action::UngroupLayers);
+		registry.bind(KeyBinding::new(VirtualKeyCode::G, ModifiersState::CTRL | ModifiersState::ALT), Action::TagDocument);
+		registry.bind(KeyBinding::new(VirtualKeyCode::G, ModifiersState::ALT), Action::TagLayer);
+		registry.bind(KeyBinding::new(VirtualKeyCode::F2, none), Action::RenameLayer);
+		registry.bind(KeyBinding::new(VirtualKeyCode::RBracket, ModifiersState::CTRL), Action::MoveLayer(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::LBracket, ModifiersState::CTRL), Action::MoveLayer(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::H, none), Action::ToggleLayerVisibility);
+		registry.bind(KeyBinding::new(VirtualKeyCode::RBracket, ModifiersState::SHIFT), Action::LayerOpacity(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::LBracket, ModifiersState::SHIFT), Action::LayerOpacity(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::M, ModifiersState::CTRL), Action::ToggleLayerMask);
+		registry.bind(KeyBinding::new(VirtualKeyCode::M, ModifiersState::ALT), Action::EditLayerMask);
+		registry.bind(KeyBinding::new(VirtualKeyCode::V, none), Action::PaletteSwapPreview);
+		registry.bind(KeyBinding::new(VirtualKeyCode::V, ModifiersState::SHIFT), Action::ExportPaletteVariants);
+		registry.bind(KeyBinding::new(VirtualKeyCode::V, ModifiersState::ALT), Action::RemapPaletteEntry);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Equals, none), Action::Zoom(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Minus, none), Action::Zoom(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Q, none), Action::Kaleidoscope);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Q, ModifiersState::SHIFT), Action::CycleSymmetry);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Equals, ModifiersState::ALT), Action::SymmetryFolds(1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::Minus, ModifiersState::ALT), Action::SymmetryFolds(-1));
+		registry.bind(KeyBinding::new(VirtualKeyCode::M, none), Action::ToggleMixer);
+		registry.bind(KeyBinding::new(VirtualKeyCode::M, ModifiersState::SHIFT), Action::ToggleColorPicker);
+		registry.bind(KeyBinding::new(VirtualKeyCode::A, none), Action::QuickColorPicker);
+		registry.bind(KeyBinding::new(VirtualKeyCode::B, ModifiersState::SHIFT), Action::ToggleControls);
+		registry.bind(KeyBinding::new(VirtualKeyCode::M, ModifiersState::CTRL | ModifiersState::SHIFT), Action::ToggleMemoryPanel);
+		registry.bind(KeyBinding::new(VirtualKeyCode::F7, none), Action::ToggleLayersPanel);
+		registry.bind(KeyBinding::new(VirtualKeyCode::P, ModifiersState::CTRL | ModifiersState::SHIFT), Action::CommandPalette);
+		registry.bind(KeyBinding::new(VirtualKeyCode::V, ModifiersState::CTRL | ModifiersState::SHIFT), Action::LivePreview);
+		registry.bind(KeyBinding::new(VirtualKeyCode::W, ModifiersState::SHIFT), Action::ToggleSwatches);
+		registry.bind(KeyBinding::new(VirtualKeyCode::W, ModifiersState::ALT), Action::PinColor);
+		registry.bind(KeyBinding::new(VirtualKeyCode::W, ModifiersState::CTRL | ModifiersState::SHIFT), Action::ImportPalettes);
+		registry.bind(KeyBinding::new(VirtualKeyCode::U, none), Action::ToggleGuides);
+		registry.bind(KeyBinding::new(VirtualKeyCode::F, ModifiersState::CTRL | ModifiersState::SHIFT), Action::ToggleFollowUndo);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Y, none), Action::CycleProof);
+		registry.bind(KeyBinding::new(VirtualKeyCode::K, none), Action::CompareMode);
+		registry.bind(KeyBinding::new(VirtualKeyCode::K, ModifiersState::SHIFT), Action::CompareSnapshot);
+		registry.bind(KeyBinding::new(VirtualKeyCode::F1, ModifiersState::SHIFT), Action::StartTour);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Return, none), Action::Confirm);
+		registry.bind(KeyBinding::new(VirtualKeyCode::Escape, none), Action::Cancel);
+
+		for (i, key) in MACRO_KEYS.iter().enumerate() {
+			let slot = i as u8 + 1;
+			registry.bind(KeyBinding::new(*key, ModifiersState::CTRL | ModifiersState::SHIFT), Action::RecordMacro(slot));
+			registry.bind(KeyBinding::new(*key, ModifiersState::CTRL), Action::PlayMacro(slot));
+			registry.bind(KeyBinding::new(*key, ModifiersState::ALT), Action::ExportPreset(slot));
+			registry.bind(KeyBinding::new(*key, ModifiersState::CTRL | ModifiersState::ALT), Action::RenderSelection(slot));
+			registry.bind(KeyBinding::new(*key, none), Action::BrushPreset(slot));
+			registry.bind(KeyBinding::new(*key, ModifiersState::SHIFT), Action::SaveBrushPreset(slot));
+		}
+
+		registry.defaults = registry.bindings.clone();
+		return registry;
+	}
+
+	pub fn register(&mut self, action: Action, description: &'static str) {
+		self.actions.insert(action.id(), ActionInfo { description, action });
+	}
+
+	pub fn bind(&mut self, binding: KeyBinding, action: Action) {
+		if !self.actions.contains_key(action.id()) {
+			panic!("Binding unregistered action {}", action.id());
+		}
+		self.bindings.insert(binding, action);
+	}
+
+	/// Back to the default bindings, with `overrides` on top.
+	pub fn set_overrides(&mut self, overrides: &[(KeyBinding, Action)]) {
+		self.bindings = self.defaults.clone();
+		for (binding, action) in overrides {
+			self.bind(*binding, action.clone());
+		}
+	}
+
+	/// An action written in a config file as its id, then its argument for those that take one,
+	/// like `canvas.replace_color 0`.
+	pub fn parse_action(&self, text: &str) -> Option<Action> {
+		let text = text.trim();
+		let (id, argument) = text.split_once(' ').map_or((text, None), |(id, a)| (id, Some(a.trim())));
+		let action = &self.actions.get(id)?.action;
+		return match argument {
+			Some(argument) => action.with_argument(argument),
+			None => Some(action.clone()),
+		};
+	}
+
+	/// Bindings of a config file table of key bindings to actions, logging the ones it can't read.
+	pub fn parse_keymap(&self, keys: &BTreeMap<String, String>) -> Vec<(KeyBinding, Action)> {
+		return keys.iter().filter_map(|(binding, action)| {
+			let parsed = KeyBinding::parse(binding).zip(self.parse_action(action));
+			if parsed.is_none() {
+				log::warn!("Ignoring invalid key binding {binding:?} = {action:?}");
+			}
+			return parsed;
+		}).collect();
+	}
+
+	/// Bindings of the keymap file in the config directory, none without one.
+	pub fn load_keymap(&self) -> Vec<(KeyBinding, Action)> {
+		let file: KeymapFile = config::read_toml(KEYMAP_FILE).unwrap_or_default();
+		return self.parse_keymap(&file.keys);
+	}
+
+	pub fn info(&self, action: &Action) -> Option<&ActionInfo> {
+		self.actions.get(action.id())
+	}
+
+	/// Current bindings grouped by category (the action id prefix) with the description of their action, sorted.
+	pub fn grouped_bindings(&self) -> Vec<(&'static str, Vec<(KeyBinding, &'static str)>)> {
+		let mut groups: Vec<(&'static str, Vec<(KeyBinding, &'static str)>)> = Vec::new();
+		for (binding, action) in &self.bindings {
+			let category = action.id().split('.').next().unwrap();
+			let description = self.info(action).map_or(action.id(), |i| i.description);
+			match groups.iter_mut().find(|(c, _)| *c == category) {
+				Some((_, entries)) => entries.push((*binding, description)),
+				None => groups.push((category, vec![(*binding, description)])),
+			}
+		}
+
+		groups.sort_by_key(|(c, _)| *c);
+		for (_, entries) in groups.iter_mut() {
+			entries.sort_by_key(|(b, d)| (*d, b.to_string()));
+		}
+		return groups;
+	}
+
+	pub fn lookup(&self, binding: KeyBinding) -> Option<Action> {
+		self.bindings.get(&binding).cloned()
+	}
+
+	/// Starts recording into `slot`, or finishes the recording in progress and stores it.
+	pub fn toggle_recording(&mut self, slot: u8) {
+		match self.recording.take() {
+			None => {
+				log::info!("Recording macro {slot}");
+				self.recording = Some((slot, Vec::new()));
+			}
+			Some((recorded_slot, actions)) => {
+				log::info!("Recorded macro {recorded_slot} with {} actions", actions.len());
+				self.macros.insert(recorded_slot, actions);
+			}
+		}
+	}
+
+	/// Called for every action that is executed, so it ends up in the macro being recorded.
+	pub fn record(&mut self, action: &Action) {
+		if let Some((_, actions)) = &mut self.recording {
+			if action.recordable() {
+				actions.push(action.clone());
+			}
+		}
+	}
+
+	pub fn macro_actions(&self, slot: u8) -> Vec<Action> {
+		self.macros.get(&slot).cloned().unwrap_or_default()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn bindings_are_grouped_by_category_and_sorted() {
+		let registry = ActionRegistry::new();
+		let groups = registry.grouped_bindings();
+		assert!(groups.windows(2).all(|w| w[0].0 < w[1].0));
+		for (category, entries) in &groups {
+			assert!(entries.windows(2).all(|w| w[0].1 <= w[1].1));
+			for (binding, _) in entries {
+				assert_eq!(registry.lookup(*binding).unwrap().id().split('.').next(), Some(*category));
+			}
+		}
+		assert_eq!(groups.iter().map(|(_, entries)| entries.len()).sum::<usize>(), registry.bindings.len());
+	}
+
+	#[test]
+	fn bindings_show_their_modifiers() {
+		let binding = KeyBinding::new(VirtualKeyCode::Z, ModifiersState::CTRL | ModifiersState::SHIFT);
+		assert_eq!(binding.to_string(), "Ctrl+Shift+Z");
+		assert_eq!(KeyBinding::new(VirtualKeyCode::Key1, ModifiersState::empty()).to_string(), "1");
+	}
+
+	#[test]
+	fn bindings_parse_as_they_are_shown() {
+		for key in BINDABLE_KEYS {
+			let binding = KeyBinding::new(key, ModifiersState::CTRL | ModifiersState::SHIFT);
+			assert_eq!(KeyBinding::parse(&binding.to_string()), Some(binding));
+		}
+		assert_eq!(KeyBinding::parse("shift+r"), Some(KeyBinding::new(VirtualKeyCode::R, ModifiersState::SHIFT)));
+		assert_eq!(KeyBinding::parse("Hyper+R"), None);
+		assert_eq!(KeyBinding::parse("Ctrl+"), None);
+	}
+
+	#[test]
+	fn actions_parse_by_id_and_argument() {
+		let registry = ActionRegistry::new();
+		assert_eq!(registry.parse_action("history.undo"), Some(Action::Undo));
+		assert_eq!(registry.parse_action("canvas.replace_color 0"), Some(Action::ReplaceColor(0.)));
+		assert_eq!(registry.parse_action("color.eyedropper_mode background"), Some(Action::EyedropperMode(ColorSlot::Background)));
+		assert_eq!(registry.parse_action("brush.size -2"), Some(Action::BrushSize(-2)));
+		assert_eq!(registry.parse_action("history.undo 1"), None);
+		assert_eq!(registry.parse_action("brush.size big"), None);
+		assert_eq!(registry.parse_action("nothing.here"), None);
+	}
+
+	#[test]
+	fn keymap_file_binds_keys_and_skips_invalid_ones() {
+		let registry = ActionRegistry::new();
+		let text = "[keys]\n\"Ctrl+Shift+S\" = \"file.save\"\n\"Alt+1\" = \"brush.preset 1\"\n\"Ctrl+Q\" = \"file.quit\"\n";
+		let file: KeymapFile = toml::from_str(text).unwrap();
+		let keymap = registry.parse_keymap(&file.keys);
+		assert_eq!(keymap, vec![
+			(KeyBinding::new(VirtualKeyCode::Key1, ModifiersState::ALT), Action::BrushPreset(1)),
+			(KeyBinding::new(VirtualKeyCode::S, ModifiersState::CTRL | ModifiersState::SHIFT), Action::SaveDocument),
+		]);
+	}
+}
@@ -0,0 +1,111 @@
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	time::{Duration, Instant, SystemTime},
+};
+
+/// How often the assets directory is rescanned for added, removed or edited files,
+/// at the next event after it elapses.
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AssetKind {
+	Brushes,
+	Palettes,
+	Stamps,
+	Scripts,
+}
+
+impl AssetKind {
+	pub const ALL: [AssetKind; 4] = [AssetKind::Brushes, AssetKind::Palettes, AssetKind::Stamps, AssetKind::Scripts];
+
+	pub fn dir_name(&self) -> &'static str {
+		match self {
+			AssetKind::Brushes => "brushes",
+			AssetKind::Palettes => "palettes",
+			AssetKind::Stamps => "stamps",
+			AssetKind::Scripts => "scripts",
+		}
+	}
+}
+
+/// `$PNTR_ASSETS`, or `pntr/assets` inside the XDG data directory.
+pub fn default_root() -> Option<PathBuf> {
+	if let Some(dir) = std::env::var_os("PNTR_ASSETS") {
+		return Some(PathBuf::from(dir));
+	}
+	let data = match std::env::var_os("XDG_DATA_HOME") {
+		Some(dir) => PathBuf::from(dir),
+		None => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+	};
+	return Some(data.join("pntr/assets"));
+}
+
+/// Files of one kind with their modification time, sorted by path.
+type Listing = Vec<(PathBuf, SystemTime)>;
+
+fn list(dir: &Path) -> Listing {
+	let Ok(read) = std::fs::read_dir(dir) else {
+		return Vec::new();
+	};
+
+	let mut files: Listing = read
+		.filter_map(Result::ok)
+		.filter_map(|entry| {
+			let meta = entry.metadata().ok()?;
+			if !meta.is_file() {
+				return None;
+			}
+			Some((entry.path(), meta.modified().unwrap_or(SystemTime::UNIX_EPOCH)))
+		})
+		.collect();
+	files.sort();
+	return files;
+}
+
+/// User assets dropped in the assets directory, one subdirectory per kind, picked up while running.
+pub struct AssetLibrary {
+	root: PathBuf,
+	listings: HashMap<AssetKind, Listing>,
+	last_scan: Option<Instant>,
+}
+
+impl AssetLibrary {
+	/// Creates the directory layout so users know where to drop their files.
+	pub fn new(root: PathBuf) -> Self {
+		for kind in AssetKind::ALL {
+			if let Err(e) = std::fs::create_dir_all(root.join(kind.dir_name())) {
+				log::warn!("Could not create assets directory {}: {e}", root.display());
+				break;
+			}
+		}
+
+		Self {
+			root,
+			listings: HashMap::new(),
+			last_scan: None,
+		}
+	}
+
+	/// Rescans when due, returns the kinds whose files changed since the last scan.
+	pub fn poll(&mut self) -> Vec<AssetKind> {
+		if matches!(self.last_scan, Some(t) if t.elapsed() < SCAN_INTERVAL) {
+			return Vec::new();
+		}
+		self.last_scan = Some(Instant::now());
+
+		let mut changed = Vec::new();
+		for kind in AssetKind::ALL {
+			let listing = list(&self.root.join(kind.dir_name()));
+			if self.listings.get(&kind) != Some(&listing) {
+				changed.push(kind);
+				self.listings.insert(kind, listing);
+			}
+		}
+		return changed;
+	}
+
+	pub fn files(&self, kind: AssetKind) -> impl Iterator<Item = &Path> {
+		self.listings.get(&kind).into_iter().flatten().map(|(path, _)| path.as_path())
+	}
+}
@@ -0,0 +1,244 @@
+use std::{
+	fs::File,
+	path::{Path, PathBuf},
+	sync::{atomic::{AtomicU32, Ordering}, OnceLock},
+	time::{Duration, Instant},
+};
+
+use crate::components::Context;
+use crate::config;
+use crate::document::{self, BackgroundSave, Document, Metadata, Snapshot};
+use crate::layers::Layer;
+
+const PREFIX: &str = "session-";
+
+/// Recovery files of this process, one per window
+static NEXT_FILE: AtomicU32 = AtomicU32::new(0);
+
+/// Locked for as long as this process runs, the system releases it however the process ends
+static LOCK: OnceLock<Option<File>> = OnceLock::new();
+
+/// Where documents are autosaved, None without a home directory.
+fn recovery_dir() -> Option<PathBuf> {
+	return config::data_dir().map(|d| d.join("recovery"));
+}
+
+/// Process a recovery file was written by, from its name.
+fn owner(path: &Path) -> Option<u32> {
+	let name = path.file_stem()?.to_str()?.strip_prefix(PREFIX)?;
+	return name.split('-').next()?.parse().ok();
+}
+
+/// Lock file of a process, held by it while it runs.
+fn lock_path(pid: u32) -> Option<PathBuf> {
+	return recovery_dir().map(|d| d.join(format!("{PREFIX}{pid}.lock")));
+}
+
+/// Locks the lock file of this process, so other instances know its recovery files are in use.
+fn hold_lock() {
+	LOCK.get_or_init(|| {
+		let path = lock_path(std::process::id())?;
+		let locked = std::fs::create_dir_all(path.parent()?)
+			.and_then(|_| File::create(&path))
+			.and_then(|file| file.try_lock().map_err(std::io::Error::from).map(|_| file));
+		return locked.map_err(|e| log::warn!("Could not lock {}: {e}", path.display())).ok();
+	});
+}
+
+/// Whether the process that wrote a recovery file is proven gone, its lock file can be locked. A
+/// process without a lock file, or that can't be checked, is taken as still running.
+fn abandoned(pid: u32) -> bool {
+	if pid == std::process::id() {
+		return false;
+	}
+	let Some(file) = lock_path(pid).and_then(|p| File::options().write(true).open(p).ok()) else {
+		return false;
+	};
+	return file.try_lock().is_ok();
+}
+
+/// Removes the lock files of processes that are gone and have no recovery files left.
+fn clear_locks() {
+	let Some(entries) = recovery_dir().and_then(|d| std::fs::read_dir(d).ok()) else {
+		return;
+	};
+	let paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+	let locks = paths.iter().filter(|p| p.extension().is_some_and(|e| e == "lock"));
+	for lock in locks {
+		let Some(pid) = owner(lock) else {
+			continue;
+		};
+		let in_use = paths.iter().any(|p| p.extension().is_some_and(|e| e == "pntr") && owner(p) == Some(pid));
+		if !in_use && abandoned(pid) {
+			if let Err(e) = std::fs::remove_file(lock) {
+				log::warn!("Could not remove {}: {e}", lock.display());
+			}
+		}
+	}
+}
+
+/// Recovery files left behind by sessions that didn't close their windows, oldest first.
+pub fn stale_files() -> Vec<PathBuf> {
+	let Some(entries) = recovery_dir().and_then(|d| std::fs::read_dir(d).ok()) else {
+		return Vec::new();
+	};
+	let mut files: Vec<(std::time::SystemTime, PathBuf)> = entries.flatten()
+		.map(|e| e.path())
+		.filter(|p| p.extension().is_some_and(|e| e == "pntr") && owner(p).is_some_and(abandoned))
+		.map(|p| (std::fs::metadata(&p).and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH), p))
+		.collect();
+	files.sort();
+	return files.into_iter().map(|(_, p)| p).collect();
+}
+
+/// Asks whether to restore the documents of a previous session that didn't close, the recovery
+/// files to open when so. Declining deletes them.
+pub fn ask_restore() -> Vec<PathBuf> {
+	clear_locks();
+	let files = stale_files();
+	if files.is_empty() {
+		return files;
+	}
+	let answer = rfd::MessageDialog::new()
+		.set_level(rfd::MessageLevel::Warning)
+		.set_title("Restore previous session?")
+		.set_description(format!("pntr didn't close properly last time. Restore {} unsaved document{}?", files.len(), if files.len() == 1 { "" } else { "s" }))
+		.set_buttons(rfd::MessageButtons::YesNo)
+		.show();
+	if answer == rfd::MessageDialogResult::Yes {
+		return files;
+	}
+	for file in files {
+		if let Err(e) = std::fs::remove_file(&file) {
+			log::warn!("Could not remove {}: {e}", file.display());
+		}
+	}
+	return Vec::new();
+}
+
+/// Metadata without the times that change with every snapshot.
+fn timeless(metadata: &Metadata) -> Metadata {
+	return Metadata { modified: 0, active_seconds: 0, ..*metadata };
+}
+
+/// Keeps a recovery copy of a window's document, written every `interval` while it changes. The
+/// layers are read back across frames and written to disk by a thread, so drawing never waits.
+pub struct Autosave {
+	/// Named after the process, whose lock file tells running instances to leave it alone
+	path: Option<PathBuf>,
+	interval: Duration,
+	last: Instant,
+	/// Changed since the last autosave
+	stale: bool,
+	/// Content hash and description of the document last snapshotted
+	written: Option<(u64, Metadata, Vec<Layer>)>,
+	saving: Option<BackgroundSave>,
+	/// Started while the last snapshot was still being saved, like on running out of memory
+	queued: Option<Snapshot>,
+}
+
+impl Autosave {
+	pub fn new(interval: Duration) -> Self {
+		hold_lock();
+		let name = format!("{PREFIX}{}-{}.pntr", std::process::id(), NEXT_FILE.fetch_add(1, Ordering::Relaxed));
+		return Self { path: recovery_dir().map(|d| d.join(name)), interval, last: Instant::now(), stale: false, written: None, saving: None, queued: None };
+	}
+
+	/// Whether there is somewhere to save to, there isn't without a home directory.
+	pub fn is_enabled(&self) -> bool {
+		self.path.is_some()
+	}
+
+	pub fn set_interval(&mut self, interval: Duration) {
+		self.interval = interval;
+	}
+
+	/// Takes over a recovery file from a previous session, so it stays until this document is saved.
+	pub fn adopt(&mut self, recovered: &Path) {
+		let Some(path) = &self.path else {
+			return;
+		};
+		if let Err(e) = std::fs::rename(recovered, path) {
+			log::warn!("Could not take over {}: {e}", recovered.display());
+		}
+	}
+
+	pub fn changed(&mut self) {
+		self.stale = true;
+	}
+
+	/// Whether a snapshot should be started, none is in progress.
+	pub fn due(&self) -> bool {
+		return self.stale && self.path.is_some() && self.saving.is_none() && self.queued.is_none() && self.last.elapsed() >= self.interval;
+	}
+
+	/// Whether `document`, its pixels hashing to `hash`, is the one last snapshotted, like after
+	/// painting and undoing. It is then left as it is until it changes again.
+	pub fn skip_unchanged(&mut self, hash: u64, document: &Document) -> bool {
+		let unchanged = self.written.as_ref().is_some_and(|(h, metadata, layers)| {
+			*h == hash && timeless(metadata) == timeless(&document.metadata) && *layers == document.layers
+		});
+		if unchanged {
+			self.stale = false;
+			self.last = Instant::now();
+		}
+		return unchanged;
+	}
+
+	/// Starts writing the document of `snapshot` once it is read back, `hash` is the content hash of
+	/// its layers.
+	pub fn start(&mut self, snapshot: Snapshot, hash: u64) {
+		let document = snapshot.document();
+		self.written = Some((hash, document.metadata, document.layers.clone()));
+		self.queued = Some(snapshot);
+		self.stale = false;
+		self.last = Instant::now();
+	}
+
+	/// Saves the snapshot once read back, and logs how the last save went.
+	pub fn poll(&mut self, ctx: &Context) {
+		if let Some(result) = self.saving.as_mut().and_then(|s| s.poll(ctx)) {
+			match result {
+				Ok(()) => log::debug!("Autosaved {}", self.path.as_ref().unwrap().display()),
+				Err(e) => log::error!("Could not autosave: {e}"),
+			}
+			self.saving = None;
+		}
+		if self.saving.is_none() {
+			if let Some(snapshot) = self.queued.take() {
+				self.saving = Some(BackgroundSave::start(self.path.clone().unwrap(), snapshot));
+			}
+		}
+	}
+
+	/// The document as last autosaved, to restore after losing the GPU, None before the first autosave.
+	/// A snapshot still being read back is dropped, it never arrives from a lost device.
+	pub fn last_saved(&mut self) -> Option<Document> {
+		self.queued = None;
+		if let Some(saving) = self.saving.take() {
+			saving.finish();
+		}
+		// Saved again from the new device
+		self.written = None;
+		self.stale = true;
+		let path = self.path.as_ref().filter(|p| p.exists())?;
+		return document::load(path).map_err(|e| log::error!("Could not read {}: {e}", path.display())).ok();
+	}
+
+	/// Removes the recovery file, once the document is saved or its window closes on purpose.
+	pub fn discard(&mut self) {
+		self.queued = None;
+		self.written = None;
+		if let Some(saving) = self.saving.take() {
+			saving.finish();
+		}
+		self.stale = false;
+		let Some(path) = &self.path else {
+			return;
+		};
+		match std::fs::remove_file(path) {
+			Err(e) if e.kind() != std::io::ErrorKind::NotFound => log::warn!("Could not remove {}: {e}", path.display()),
+			_ => (),
+		}
+	}
+}
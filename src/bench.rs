@@ -0,0 +1,152 @@
+use std::time::{Duration, Instant};
+
+use crate::components::{self, Component, Point, Rect, Size};
+
+/// Strokes replayed, one per line as `x,y,pressure` points in document pixels
+const RECORDING: &str = include_str!("../assets/bench.strokes");
+/// Points reaching the canvas between frames, about a 240 Hz stylus at 60 frames per second
+const POINTS_PER_FRAME: usize = 4;
+const TARGET_SIZE: Size = Size { w: 1280, h: 800 };
+/// Frames rendered after the last stroke at most, waiting for the canvas to catch up
+const MAX_SETTLE_FRAMES: usize = 600;
+const READBACKS: usize = 5;
+
+/// `pntr --bench [--adapter name] [--max-frame-ms ms]`, replays the bundled strokes without a
+/// window and reports how fast they were drawn and read back. Fails when the 95th percentile
+/// frame time is over `--max-frame-ms`.
+pub fn run(args: &[String]) -> Result<(), String> {
+	let value = |flag: &str| args.iter().position(|a| a == flag).and_then(|i| args.get(i + 1));
+	let adapter_name = value("--adapter");
+	let max_frame_ms = match value("--max-frame-ms") {
+		Some(ms) => Some(ms.parse::<f64>().map_err(|_| format!("invalid --max-frame-ms {ms:?}"))?),
+		None => None,
+	};
+	let strokes = parse_recording(RECORDING);
+
+	let instance = wgpu::Instance::new(wgpu::Backends::all());
+	let adapter = match adapter_name {
+		Some(name) => instance
+			.enumerate_adapters(wgpu::Backends::all())
+			.find(|a| a.get_info().name.to_lowercase().contains(&name.to_lowercase()))
+			.ok_or(format!("no adapter named like {name:?}"))?,
+		None => pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+			power_preference: wgpu::PowerPreference::default(),
+			compatible_surface: None,
+			force_fallback_adapter: false,
+		}))
+		.ok_or("no adapter available")?,
+	};
+	let info = adapter.get_info();
+	println!("adapter: {} ({:?}, {:?})", info.name, info.backend, info.device_type);
+
+	let (device, queue, tier) = pollster::block_on(crate::layout::request_device(&adapter));
+	if !tier.paints() {
+		return Err(format!("{} adapter, strokes can't be drawn", tier.name()));
+	}
+	let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+	let mut ctx = components::Context::new(device, queue, format, tier);
+	let target = ctx.device.create_texture(&wgpu::TextureDescriptor {
+		label: Some("Bench(Target)"),
+		size: wgpu::Extent3d { width: TARGET_SIZE.w, height: TARGET_SIZE.h, depth_or_array_layers: 1 },
+		mip_level_count: 1,
+		sample_count: 1,
+		dimension: wgpu::TextureDimension::D2,
+		format,
+		usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+	});
+	let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+	let mut canvas = components::Canvas::new(&mut ctx);
+
+	let mut frame_times = Vec::new();
+	let mut frame = |ctx: &mut components::Context, canvas: &mut components::Canvas| {
+		let start = Instant::now();
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Bench(Encoder)"),
+		});
+		let viewport = Rect { pos: Point { x: 0, y: 0 }, size: TARGET_SIZE };
+		canvas.render(&mut encoder, ctx, &view, viewport, None);
+		canvas.take_damage();
+		ctx.staging_belt.finish();
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+		ctx.staging_belt.recall();
+		ctx.buffer_pool.reset();
+		ctx.uniform_pool.reset();
+		ctx.device.poll(wgpu::Maintain::Wait);
+		frame_times.push(start.elapsed());
+	};
+
+	let replay = Instant::now();
+	let mut points = 0;
+	for stroke in &strokes {
+		let Some((&(first, pressure), rest)) = stroke.split_first() else {
+			continue;
+		};
+		canvas.mouse_pos(first, Some(pressure));
+		canvas.mouse_down();
+		for chunk in rest.chunks(POINTS_PER_FRAME) {
+			for &(p, pressure) in chunk {
+				canvas.mouse_pos(p, Some(pressure));
+			}
+			frame(&mut ctx, &mut canvas);
+		}
+		canvas.mouse_up();
+		points += stroke.len();
+	}
+	let mut settle = 0;
+	while !canvas.is_idle() && settle < MAX_SETTLE_FRAMES {
+		frame(&mut ctx, &mut canvas);
+		settle += 1;
+	}
+	let replay = replay.elapsed();
+
+	let mut readbacks = Vec::new();
+	for _ in 0..READBACKS {
+		let start = Instant::now();
+		canvas.read_pixels(&mut ctx);
+		readbacks.push(start.elapsed());
+	}
+
+	let seconds = replay.as_secs_f64();
+	println!("strokes: {} ({points} points) in {:.3} s, {:.1} strokes/s, {:.0} points/s", strokes.len(), seconds, strokes.len() as f64 / seconds, points as f64 / seconds);
+	let frames = summarize(&mut frame_times);
+	println!("frames: {} {frames}", frame_times.len());
+	println!("readback: {}", summarize(&mut readbacks));
+
+	if let Some(max) = max_frame_ms {
+		let p95 = percentile(&frame_times, 0.95).as_secs_f64() * 1000.;
+		if p95 > max {
+			return Err(format!("95th percentile frame time {p95:.2} ms is over {max} ms"));
+		}
+	}
+	return Ok(());
+}
+
+/// Points and pressures of each stroke, skipping `#` comments and points that don't parse.
+fn parse_recording(text: &str) -> Vec<Vec<(Point, f32)>> {
+	let point = |p: &str| {
+		let mut fields = p.split(',');
+		let mut next = || fields.next()?.parse::<f32>().ok();
+		let (x, y, pressure) = (next()?, next()?, next()?);
+		return Some((Point { x: x as i32, y: y as i32 }, pressure));
+	};
+	return text.lines().filter(|l| !l.is_empty() && !l.starts_with('#')).map(|l| l.split_whitespace().filter_map(point).collect()).collect();
+}
+
+/// Sorted `durations` at the fraction `q` of the way through.
+fn percentile(durations: &[Duration], q: f64) -> Duration {
+	let i = ((durations.len() as f64 - 1.) * q).round() as usize;
+	return durations.get(i).copied().unwrap_or_default();
+}
+
+/// Sorts `durations` and formats their mean, median, 95th percentile and maximum in milliseconds.
+fn summarize(durations: &mut [Duration]) -> String {
+	durations.sort();
+	let ms = |d: Duration| d.as_secs_f64() * 1000.;
+	let mean = durations.iter().map(|d| ms(*d)).sum::<f64>() / durations.len().max(1) as f64;
+	return format!(
+		"mean {mean:.2} ms, median {:.2} ms, p95 {:.2} ms, max {:.2} ms",
+		ms(percentile(durations, 0.5)),
+		ms(percentile(durations, 0.95)),
+		ms(durations.last().copied().unwrap_or_default()),
+	);
+}
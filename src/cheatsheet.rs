@@ -0,0 +1,59 @@
+use crate::actions::ActionRegistry;
+use crate::components::Size;
+use crate::export::Pixels;
+use crate::font;
+
+const SCALE: u32 = 2;
+const MARGIN: u32 = 16;
+const BACKGROUND: [u8; 4] = [24, 24, 28, 235];
+const HEADING: [u8; 4] = [240, 200, 90, 255];
+const TEXT: [u8; 4] = [230, 230, 230, 255];
+
+/// Lines of one category block, the heading first.
+fn blocks(actions: &ActionRegistry) -> Vec<Vec<String>> {
+	return actions.grouped_bindings().into_iter().map(|(category, entries)| {
+		let width = entries.iter().map(|(b, _)| b.to_string().len()).max().unwrap_or(0);
+		let mut lines = vec![category.to_uppercase()];
+		lines.extend(entries.iter().map(|(b, d)| format!("{:width$}  {d}", b.to_string())));
+		lines.push(String::new());
+		lines
+	}).collect();
+}
+
+/// Every current binding in columns no taller than `max_height`, regenerated from the registry so remapped keys show up.
+pub fn render(actions: &ActionRegistry, max_height: u32) -> Pixels {
+	let line_h = font::text_size("A", SCALE).h;
+	let lines_per_column = ((max_height.saturating_sub(2 * MARGIN)) / line_h).max(1) as usize;
+
+	// Categories are kept whole within a column unless they're taller than one
+	let mut columns: Vec<Vec<(String, bool)>> = vec![Vec::new()];
+	for block in blocks(actions) {
+		if columns.last().unwrap().len() + block.len() > lines_per_column && !columns.last().unwrap().is_empty() {
+			columns.push(Vec::new());
+		}
+		for (i, line) in block.into_iter().enumerate() {
+			if columns.last().unwrap().len() == lines_per_column {
+				columns.push(Vec::new());
+			}
+			columns.last_mut().unwrap().push((line, i == 0));
+		}
+	}
+
+	let widths: Vec<u32> = columns.iter().map(|c| c.iter().map(|(l, _)| font::text_size(l, SCALE).w).max().unwrap_or(0)).collect();
+	let height = columns.iter().map(|c| c.len() as u32).max().unwrap_or(0) * line_h;
+	let size = Size {
+		w: widths.iter().sum::<u32>() + MARGIN * (widths.len() as u32 + 1),
+		h: height + 2 * MARGIN,
+	};
+
+	let mut pixels = Pixels::new(size);
+	font::fill(&mut pixels, 0, 0, size.w, size.h, BACKGROUND);
+	let mut x = MARGIN;
+	for (column, width) in columns.iter().zip(widths) {
+		for (i, (line, heading)) in column.iter().enumerate() {
+			font::draw_text(&mut pixels, x, MARGIN + i as u32 * line_h, line, if *heading { HEADING } else { TEXT }, SCALE);
+		}
+		x += width + MARGIN;
+	}
+	return pixels;
+}
@@ -0,0 +1,59 @@
+use std::borrow::Cow;
+
+use crate::components::Size;
+use crate::export::Pixels;
+
+/// Images shared with other applications through the system clipboard. Without one (no display
+/// server, or a clipboard manager refusing the connection) copying and pasting does nothing.
+pub struct Clipboard {
+	/// Connected on first use, None after it failed
+	backend: Option<Option<arboard::Clipboard>>,
+}
+
+impl Clipboard {
+	pub fn new() -> Self {
+		Self { backend: None }
+	}
+
+	fn backend(&mut self) -> Option<&mut arboard::Clipboard> {
+		return self.backend.get_or_insert_with(|| match arboard::Clipboard::new() {
+			Ok(clipboard) => Some(clipboard),
+			Err(e) => {
+				log::warn!("No system clipboard: {e}");
+				None
+			}
+		}).as_mut();
+	}
+
+	/// Puts straight alpha `pixels` on the clipboard.
+	pub fn copy_image(&mut self, pixels: &Pixels) {
+		let Some(backend) = self.backend() else {
+			return;
+		};
+		let image = arboard::ImageData {
+			width: pixels.size.w as usize,
+			height: pixels.size.h as usize,
+			bytes: Cow::Borrowed(&pixels.data),
+		};
+		if let Err(e) = backend.set_image(image) {
+			log::warn!("Could not copy to the clipboard: {e}");
+		}
+	}
+
+	/// Image on the clipboard, with straight alpha. None when it holds something else.
+	pub fn paste_image(&mut self) -> Option<Pixels> {
+		let image = match self.backend()?.get_image() {
+			Ok(image) => image,
+			Err(arboard::Error::ContentNotAvailable) => return None,
+			Err(e) => {
+				log::warn!("Could not paste from the clipboard: {e}");
+				return None;
+			}
+		};
+		let size = Size { w: image.width as u32, h: image.height as u32 };
+		if size.w == 0 || size.h == 0 {
+			return None;
+		}
+		return Some(Pixels { size, data: image.bytes.into_owned() });
+	}
+}
@@ -0,0 +1,246 @@
+use crate::actions::{Action, ActionRegistry, KeyBinding};
+use crate::components::{Context, Point, Rect, Size, Widget};
+use crate::export::Pixels;
+use crate::font;
+
+const WIDTH: u32 = 640;
+const SCALE: u32 = 2;
+/// Rows listed at once, the list scrolls to keep the selected one in view
+const SHOWN: usize = 12;
+const ROW_PADDING: u32 = 6;
+const MARGIN: u32 = 8;
+const FIELD_HEIGHT: u32 = 32;
+const BACKGROUND: [u8; 4] = [24, 24, 28, 235];
+const SELECTED: [u8; 4] = [70, 70, 84, 245];
+const TEXT: [u8; 4] = [230, 230, 230, 255];
+const BINDING: [u8; 4] = [150, 150, 160, 255];
+
+/// Matched letters right after the previous one, or at the start of a word, count more
+const CONSECUTIVE_BONUS: i32 = 5;
+const WORD_START_BONUS: i32 = 8;
+
+struct Command {
+	action: Action,
+	description: &'static str,
+	binding: KeyBinding,
+}
+
+/// How well `query` matches `text`: its letters in order, not necessarily next to each other. None
+/// when some are missing.
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+	let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+	let mut score = 0;
+	let mut next = 0;
+	let mut previous: Option<usize> = None;
+	for q in query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()) {
+		let i = next + text[next..].iter().position(|c| *c == q)?;
+		score += 1;
+		if previous.is_some_and(|p| p + 1 == i) {
+			score += CONSECUTIVE_BONUS;
+		}
+		if i == 0 || !text[i - 1].is_alphanumeric() {
+			score += WORD_START_BONUS;
+		}
+		// Skipped letters count against it, a little
+		score -= (i - next).min(3) as i32;
+		previous = Some(i);
+		next = i + 1;
+	}
+	return Some(score);
+}
+
+/// What a press while the palette is open did, it closes either way.
+pub enum PaletteClick {
+	/// The row of the action was clicked
+	Run(Action),
+	/// Outside of the rows
+	Close,
+}
+
+/// Every bound action searched by its description or id, run with Enter or a click.
+pub struct CommandPalette {
+	commands: Vec<Command>,
+	/// Indices of the commands matching the query, best first
+	matches: Vec<usize>,
+	/// Of the matches
+	selected: usize,
+	/// First match in view
+	scroll: usize,
+	/// Of the last press, until the layout takes it
+	clicked: Option<PaletteClick>,
+}
+
+impl CommandPalette {
+	/// Lists the actions as they are bound now, the palette's own one left out.
+	pub fn new(actions: &ActionRegistry) -> Self {
+		let mut commands: Vec<Command> = Vec::new();
+		for (_, entries) in actions.grouped_bindings() {
+			for (binding, description) in entries {
+				let Some(action) = actions.lookup(binding) else {
+					continue;
+				};
+				if action == Action::CommandPalette || commands.iter().any(|c| c.action == action) {
+					continue;
+				}
+				commands.push(Command { action, description, binding });
+			}
+		}
+		commands.sort_by_key(|c| (c.description, c.binding.to_string()));
+		let mut palette = Self { commands, matches: Vec::new(), selected: 0, scroll: 0, clicked: None };
+		palette.set_query("");
+		return palette;
+	}
+
+	/// Top left corner of the palette in a window of `size`, centered near the top.
+	pub fn position(size: Size) -> Point {
+		return Point { x: (size.w as i32 - WIDTH as i32) / 2, y: size.h as i32 / 6 };
+	}
+
+	pub fn field_rect(pos: Point) -> Rect {
+		return Rect::new(pos.x, pos.y, WIDTH, FIELD_HEIGHT);
+	}
+
+	pub fn set_query(&mut self, query: &str) {
+		let mut scored: Vec<(i32, usize)> = self.commands.iter().enumerate().filter_map(|(i, c)| {
+			let score = fuzzy_score(query, c.description).max(fuzzy_score(query, c.action.id()))?;
+			Some((score, i))
+		}).collect();
+		// Stable, so equally good ones stay in alphabetical order
+		scored.sort_by_key(|(score, _)| -score);
+		self.matches = scored.into_iter().map(|(_, i)| i).collect();
+		self.selected = 0;
+		self.scroll = 0;
+	}
+
+	/// Moves the selection by `rows`, returns if it moved.
+	pub fn move_selection(&mut self, rows: i32) -> bool {
+		if self.matches.is_empty() {
+			return false;
+		}
+		let selected = (self.selected as i32 + rows).clamp(0, self.matches.len() as i32 - 1) as usize;
+		if selected == self.selected {
+			return false;
+		}
+		self.selected = selected;
+		self.scroll = self.scroll.clamp(selected.saturating_sub(SHOWN - 1), selected);
+		return true;
+	}
+
+	pub fn selected(&self) -> Option<Action> {
+		return self.matches.get(self.selected).map(|i| self.commands[*i].action.clone());
+	}
+
+	fn row_height() -> u32 {
+		return font::text_size("A", SCALE).h + 2 * ROW_PADDING;
+	}
+
+	/// Of the list under the query field, for a palette at `pos`.
+	pub fn list_rect(&self, pos: Point) -> Rect {
+		let rows = self.matches.len().clamp(1, SHOWN) as u32;
+		return Rect::new(pos.x, pos.y + FIELD_HEIGHT as i32, WIDTH, rows * Self::row_height() + 2 * MARGIN);
+	}
+
+	/// Match of the row under `p` in a list drawn at `list`.
+	fn row_at(&self, list: Rect, p: Point) -> Option<usize> {
+		if !list.inside(p) {
+			return None;
+		}
+		let y = (p.y - list.pos.y).checked_sub(MARGIN as i32)?;
+		let row = self.scroll + (y as u32 / Self::row_height()) as usize;
+		return (row < self.matches.len().min(self.scroll + SHOWN)).then_some(row);
+	}
+
+	/// Selects the row under `p`, returns if the selection changed.
+	fn hover(&mut self, list: Rect, p: Point) -> bool {
+		match self.row_at(list, p) {
+			Some(row) if row != self.selected => {
+				self.selected = row;
+				true
+			}
+			_ => false,
+		}
+	}
+
+	/// Action of the row clicked at `p`.
+	fn click(&self, list: Rect, p: Point) -> Option<Action> {
+		return self.row_at(list, p).map(|row| self.commands[self.matches[row]].action.clone());
+	}
+
+	pub fn take_clicked(&mut self) -> Option<PaletteClick> {
+		return self.clicked.take();
+	}
+
+	pub fn render(&self) -> Pixels {
+		let row_h = Self::row_height();
+		let size = self.list_rect(Point { x: 0, y: 0 }).size;
+		let mut pixels = Pixels::new(size);
+		font::fill(&mut pixels, 0, 0, size.w, size.h, BACKGROUND);
+		if self.matches.is_empty() {
+			font::draw_text(&mut pixels, MARGIN, MARGIN + ROW_PADDING, "No matching command", BINDING, SCALE);
+			return pixels;
+		}
+
+		for (row, i) in self.matches.iter().enumerate().skip(self.scroll).take(SHOWN) {
+			let command = &self.commands[*i];
+			let top = MARGIN + (row - self.scroll) as u32 * row_h;
+			if row == self.selected {
+				font::fill(&mut pixels, 0, top, size.w, row_h, SELECTED);
+			}
+			let binding = command.binding.to_string();
+			let binding_w = font::text_size(&binding, SCALE).w;
+			let binding_x = size.w - MARGIN - binding_w;
+			// Descriptions too long for the room left of the binding are cut
+			let room = (binding_x.saturating_sub(2 * MARGIN) / font::advance(SCALE)) as usize;
+			let description: String = command.description.chars().take(room).collect();
+			font::draw_text(&mut pixels, MARGIN, top + ROW_PADDING, &description, TEXT, SCALE);
+			font::draw_text(&mut pixels, binding_x, top + ROW_PADDING, &binding, BINDING, SCALE);
+		}
+		return pixels;
+	}
+}
+
+/// Laid out over the whole window, so it takes the presses outside of it too, with the query field above it.
+impl Widget for CommandPalette {
+	fn press(&mut self, _: &mut Context, viewport: Rect, p: Point) -> bool {
+		let list = self.list_rect(Self::position(viewport.size));
+		self.clicked = Some(self.click(list, p).map_or(PaletteClick::Close, PaletteClick::Run));
+		return true;
+	}
+
+	fn hover_at(&mut self, viewport: Rect, p: Point) -> bool {
+		let list = self.list_rect(Self::position(viewport.size));
+		return self.hover(list, p);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn missing_letters_do_not_match() {
+		assert_eq!(fuzzy_score("xyz", "Undo the last edit"), None);
+		// In order only
+		assert_eq!(fuzzy_score("ou", "Undo"), None);
+	}
+
+	#[test]
+	fn case_and_spaces_are_ignored() {
+		assert_eq!(fuzzy_score("UN DO", "undo"), fuzzy_score("undo", "Undo"));
+		assert!(fuzzy_score("", "anything").is_some());
+	}
+
+	#[test]
+	fn consecutive_letters_score_higher() {
+		let together = fuzzy_score("lay", "New layer").unwrap();
+		let apart = fuzzy_score("lay", "Fill the selection anyway").unwrap();
+		assert!(together > apart);
+	}
+
+	#[test]
+	fn word_starts_score_higher() {
+		let start = fuzzy_score("s", "Save the document").unwrap();
+		let middle = fuzzy_score("s", "Undo the last edit").unwrap();
+		assert!(start > middle);
+	}
+}
@@ -0,0 +1,85 @@
+use crate::components::{Component, Context, Image, Point, Rect, Size};
+use crate::export::Pixels;
+
+/// Opacity of the earlier version blended over the document
+const BLEND_OPACITY: f32 = 0.5;
+/// Presses this many window pixels from the divider grab it
+const DIVIDER_GRAB: f32 = 8.;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CompareMode {
+	/// The earlier version left of a divider, the document right of it
+	Swipe,
+	/// The earlier version half transparent over the document
+	Blend,
+}
+
+/// An earlier version of the document, or another image, shown over it for before/after checks.
+pub struct Compare {
+	image: Box<Image>,
+	pub mode: CompareMode,
+	/// Document column the divider is at
+	divider: i32,
+	dragging: bool,
+}
+
+impl Compare {
+	/// `pixels` are stretched over the document when their size differs.
+	pub fn new(ctx: &mut Context, pixels: &Pixels, document: Size) -> Self {
+		if pixels.size != document {
+			log::warn!("Comparing with a {}x{} image, stretched over the {}x{} document", pixels.size.w, pixels.size.h, document.w, document.h);
+		}
+		let tex = ctx.upload_texture("Compare(Snapshot Texture)", pixels, wgpu::TextureUsages::STORAGE_BINDING);
+		let mut image = Image::new(ctx);
+		image.set_texture(ctx, tex);
+		return Self { image, mode: CompareMode::Swipe, divider: document.w as i32 / 2, dragging: false };
+	}
+
+	/// Returns if the press grabbed the divider. `p` is a document point, `scale` the view zoom.
+	pub fn press(&mut self, p: Point, scale: f32) -> bool {
+		self.dragging = self.mode == CompareMode::Swipe && ((p.x - self.divider) as f32 * scale).abs() <= DIVIDER_GRAB;
+		return self.dragging;
+	}
+
+	/// Returns if the divider moved.
+	pub fn drag(&mut self, p: Point, document: Size) -> bool {
+		if !self.dragging {
+			return false;
+		}
+		self.divider = p.x.clamp(0, document.w as i32);
+		return true;
+	}
+
+	/// Returns if the divider was being dragged.
+	pub fn release(&mut self) -> bool {
+		return std::mem::replace(&mut self.dragging, false);
+	}
+
+	/// Window column of the divider, `document` being the window rect the document of size `size` is drawn over.
+	fn divider_x(&self, document: Rect, size: Size) -> i32 {
+		let divider = self.divider.clamp(0, size.w as i32);
+		return document.pos.x + (divider as f32 / size.w.max(1) as f32 * document.size.w as f32).round() as i32;
+	}
+
+	/// Divider line outlined over the window, None unless swiping.
+	pub fn divider_rect(&self, document: Rect, size: Size) -> Option<Rect> {
+		if self.mode != CompareMode::Swipe {
+			return None;
+		}
+		return Some(Rect::new(self.divider_x(document, size), document.pos.y, 1, document.size.h));
+	}
+
+	pub fn render(&mut self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, output: &wgpu::TextureView, document: Rect, size: Size, clip: Rect) {
+		let (shown, opacity) = match self.mode {
+			CompareMode::Swipe => {
+				let w = (self.divider_x(document, size) - document.pos.x).max(0) as u32;
+				(Rect { pos: document.pos, size: Size { w, h: document.size.h } }, 1.)
+			}
+			CompareMode::Blend => (document, BLEND_OPACITY),
+		};
+		if let Some(clip) = shown.intersection(clip) {
+			self.image.opacity = opacity;
+			self.image.render(encoder, ctx, output, document, Some(clip));
+		}
+	}
+}
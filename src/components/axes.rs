@@ -0,0 +1,121 @@
+use crate::components::{self, Rect, Context, Pipelines, RectViewportClipSpace, Uniforms};
+
+/// Faint rays evenly spread around a point, like the axes of a symmetry, drawn over the viewport it is rendered in.
+pub struct Axes {
+	pipelines: std::sync::Arc<Pipelines>,
+	uniforms: Uniforms<AxesInput>,
+	/// Where the rays start, in framebuffer pixels
+	pub center: [f32; 2],
+	pub rays: u32,
+	/// Degrees clockwise from up of the first ray
+	pub angle: f32,
+	pub opacity: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct AxesInput {
+	center: [f32; 2],
+	rays: u32,
+	angle: f32,
+	opacity: f32,
+	_pad: [f32; 3],
+}
+
+impl components::Component for Axes {
+	fn generate_pipelines(ctx: &Context) -> Pipelines {
+		let uniforms = Uniforms::<AxesInput>::new(ctx, wgpu::ShaderStages::FRAGMENT, 0);
+		let shader = ctx.create_shader("Axes(Shader)", include_str!("shaders/axes.wgsl"), uniforms.defines());
+
+		let render_pipeline_layout = ctx.device.create_pipeline_layout(
+			&wgpu::PipelineLayoutDescriptor {
+				label: Some("Axes(Pipeline Layout)"),
+				bind_group_layouts: &uniforms.bind_group_layout().into_iter().collect::<Vec<_>>(),
+				push_constant_ranges: &uniforms.push_constant_ranges(),
+			}
+		);
+
+		let render_pipeline = ctx.device.create_render_pipeline(
+			&wgpu::RenderPipelineDescriptor {
+				label: Some("Axes(Render Pipeline)"),
+				layout: Some(&render_pipeline_layout),
+				vertex: wgpu::VertexState {
+					module: &shader,
+					entry_point: "vs_main",
+					buffers: &[],
+				},
+				fragment: Some(wgpu::FragmentState {
+					module: &shader,
+					entry_point: "fs_main",
+					targets: &[Some(wgpu::ColorTargetState {
+						format: ctx.surface_format,
+						blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+						write_mask: wgpu::ColorWrites::ALL,
+					})],
+				}),
+				primitive: wgpu::PrimitiveState {
+					topology: wgpu::PrimitiveTopology::TriangleList,
+					strip_index_format: None,
+					front_face: wgpu::FrontFace::Ccw,
+					cull_mode: None,
+					polygon_mode: wgpu::PolygonMode::Fill,
+					unclipped_depth: false,
+					conservative: false,
+				},
+				depth_stencil: None,
+				multisample: wgpu::MultisampleState {
+					count: 1,
+					mask: !0,
+					alpha_to_coverage_enabled: false
+				},
+				multiview: None
+			}
+		);
+
+		Pipelines {
+			render: vec![render_pipeline],
+			compute: vec![],
+		}
+	}
+
+	fn new(ctx: &mut Context) -> Box<Self> {
+		Box::new(Self {
+			pipelines: ctx.get_pipelines::<Self>(),
+			uniforms: Uniforms::new(ctx, wgpu::ShaderStages::FRAGMENT, 0),
+			center: [0.; 2],
+			rays: 0,
+			angle: 0.,
+			opacity: 0.35,
+		})
+	}
+
+	fn render(&mut self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, output: &wgpu::TextureView, viewport: Rect, clip_space: Option<Rect>) {
+		let input = AxesInput {
+			center: self.center,
+			rays: self.rays,
+			angle: self.angle.to_radians(),
+			opacity: self.opacity,
+			_pad: [0.; 3],
+		};
+
+		let uniforms = self.uniforms.bind(ctx, &input);
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("Axes(Render Pass)"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: output,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Load,
+					store: true,
+				}
+			})],
+			depth_stencil_attachment: None,
+		});
+
+		render_pass.set_pipeline(&self.pipelines.render[0]);
+		render_pass.set_viewport_rect(viewport);
+		render_pass.set_clipspace_rect(clip_space);
+		uniforms.set(&mut render_pass);
+		render_pass.draw(0..6, 0..1);
+	}
+}
@@ -0,0 +1,42 @@
+use crate::components::Context;
+use crate::export::Pixels;
+
+/// Grayscale image stamped along strokes instead of the round brush, dark opaque pixels painting.
+/// Loaded through `Context::brush_tip` so the brushes using the same image share its texture.
+pub struct BrushTip {
+	view: wgpu::TextureView,
+	bytes: u64,
+}
+
+impl BrushTip {
+	pub fn new(ctx: &Context, label: &str, pixels: &Pixels) -> Self {
+		use wgpu::util::DeviceExt;
+		let coverage: Vec<u8> = pixels.data.chunks_exact(4).map(|p| {
+			let luma = (p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000;
+			((255 - luma) * p[3] as u32 / 255) as u8
+		}).collect();
+		let tex = ctx.device.create_texture_with_data(&ctx.queue, &wgpu::TextureDescriptor {
+			label: Some(label),
+			size: wgpu::Extent3d {
+				width: pixels.size.w,
+				height: pixels.size.h,
+				depth_or_array_layers: 1,
+			},
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: wgpu::TextureFormat::R8Unorm,
+			usage: wgpu::TextureUsages::TEXTURE_BINDING,
+		}, &coverage);
+		return Self { view: tex.create_view(&wgpu::TextureViewDescriptor::default()), bytes: coverage.len() as u64 };
+	}
+
+	pub fn view(&self) -> &wgpu::TextureView {
+		&self.view
+	}
+
+	/// GPU memory of the texture.
+	pub fn bytes(&self) -> u64 {
+		self.bytes
+	}
+}
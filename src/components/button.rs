@@ -0,0 +1,111 @@
+use winit::event::VirtualKeyCode;
+
+use crate::components::{widget_focus_ring, widget_frame, widget_text_top, Context, DrawnWidget, Event, Point, Rect, Size, Widget, WidgetImage, WIDGET_BACKGROUND, WIDGET_HOVERED, WIDGET_SCALE, WIDGET_TEXT};
+use crate::export::Pixels;
+use crate::font;
+
+const PRESSED: [u8; 4] = [70, 70, 84, 255];
+
+/// Labeled button emitting `Event::Pressed` with its message when released over it, or when Space
+/// is pressed while it is focused.
+pub struct Button {
+	image: WidgetImage<(Size, bool, bool, bool)>,
+	label: String,
+	message: &'static str,
+	hovered: bool,
+	pressed: bool,
+	focused: bool,
+}
+
+impl Button {
+	pub fn set_label(&mut self, label: &str, message: &'static str) {
+		self.label = label.to_string();
+		self.message = message;
+		self.image.invalidate();
+	}
+}
+
+impl Widget for Button {
+	fn press(&mut self, _: &mut Context, _: Rect, _: Point) -> bool {
+		self.pressed = true;
+		return true;
+	}
+
+	/// Pressed looking only while the pointer is over it.
+	fn drag(&mut self, _: &mut Context, viewport: Rect, p: Point) -> bool {
+		let hovered = viewport.inside(p);
+		let changed = hovered != self.hovered;
+		self.hovered = hovered;
+		return changed;
+	}
+
+	fn release(&mut self, ctx: &mut Context) {
+		if self.pressed && self.hovered {
+			ctx.emit(Event::Pressed(self.message));
+		}
+		self.pressed = false;
+	}
+
+	fn set_hovered(&mut self, hovered: bool) -> bool {
+		let changed = hovered != self.hovered;
+		self.hovered = hovered;
+		return changed;
+	}
+
+	fn focusable(&self) -> bool {
+		true
+	}
+
+	fn set_focused(&mut self, _: &mut Context, focused: bool) {
+		self.focused = focused;
+	}
+
+	fn key_input(&mut self, ctx: &mut Context, key: VirtualKeyCode) -> bool {
+		if key != VirtualKeyCode::Space {
+			return false;
+		}
+		ctx.emit(Event::Pressed(self.message));
+		return true;
+	}
+}
+
+impl DrawnWidget for Button {
+	/// Size, hover, press and focus state
+	type State = (Size, bool, bool, bool);
+	const LABEL: &'static str = "Button(Texture)";
+
+	fn with_image(image: WidgetImage<Self::State>) -> Self {
+		Self {
+			image,
+			label: String::new(),
+			message: "",
+			hovered: false,
+			pressed: false,
+			focused: false,
+		}
+	}
+
+	fn image(&mut self) -> &mut WidgetImage<Self::State> {
+		&mut self.image
+	}
+
+	fn state(&self, size: Size) -> Option<Self::State> {
+		// Pressed only looks pressed while over the button
+		return Some((size, self.hovered, self.pressed && self.hovered, self.focused));
+	}
+
+	fn draw(&self, size: Size, _: &Self::State) -> Pixels {
+		let fill = match (self.pressed && self.hovered, self.hovered) {
+			(true, _) => PRESSED,
+			(false, true) => WIDGET_HOVERED,
+			(false, false) => WIDGET_BACKGROUND,
+		};
+		let mut pixels = widget_frame(size, fill);
+		let text = font::text_size(&self.label, WIDGET_SCALE);
+		font::draw_text(&mut pixels, size.w.saturating_sub(text.w) / 2, widget_text_top(size.h), &self.label, WIDGET_TEXT, WIDGET_SCALE);
+		if self.focused {
+			widget_focus_ring(&mut pixels);
+		}
+		return pixels;
+	}
+}
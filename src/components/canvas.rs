@@ -1,374 +1,4188 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use crate::components::{self, Point, Rect, Size, Image, Context, Pipelines};
+use crate::components::{self, BoundUniforms, BrushTip, Component, Control, Damaged, Event, Invalidations, Point, Rect, Size, Image, Context, Pipelines, Subscription, Symmetry, SymmetryCopy, Uniforms};
+use crate::export::{PendingExport, Pixels};
+use crate::math::Mat3;
+use crate::pressure::PressureCurve;
 
 // TODO: Use renderBundle in conjunction with buffers to draw different lines in the canvas without reencoding the render pass.
 
 const BACKGROUND_COLOR: [f32; 3] = [0., 0., 0.];
-const BRUSH_RADIUS: u32 = 3;
+const BRUSH_SIZE: u32 = 6;
+pub const MIN_BRUSH_SIZE: u32 = 1;
+pub const MAX_BRUSH_SIZE: u32 = 500;
+/// Longest side of a document, new, resized or rendered
+pub const MAX_CANVAS_SIDE: u32 = 1 << 15;
+const MAX_STABILIZER: u32 = 128;
+/// Strokes with less flow would take forever to show
+const MIN_FLOW: f32 = 0.01;
+/// Stroke ids are kept in two bytes of the stroke masks
+const MAX_STROKE_ID: u32 = 0xffff;
+/// Set in the stroke of points from mirrored symmetry copies, above the id
+const MIRRORED_STROKE: u32 = 1 << 16;
+/// Bits of the line shader flags: the tip is stamped, strokes keep how opaque pixels are, and
+/// strokes smear what they go over
+const STAMPED: u32 = 1;
+const ALPHA_LOCKED: u32 = 2;
+const SMUDGED: u32 = 4;
+/// Stamped or jittered dabs closer than this fraction of their size would go over themselves for nothing
+const MIN_STAMP_SPACING: f32 = 0.05;
+/// Screen pixels between dabs below which zoomed out strokes space them further apart
+const MIN_SCREEN_DAB_SPACING: f32 = 1.;
+/// Most zoomed out strokes space dabs apart, as a fraction of the brush size, so they still overlap at 100%
+const MAX_ZOOM_SPACING: f32 = 0.25;
+/// Most degrees the hue or the tip rotation of dabs is jittered by, either way
+const MAX_ANGLE_JITTER: f32 = 180.;
 const TEX_SIZE: Size = Size { w: 2000, h: 2000 };
+const MIN_TEX_SIDE: u32 = 64;
+/// Bytes of the color smudging strokes carry, a vec4 and the stroke id padded to its alignment
+const PICKUP_SIZE: u64 = 32;
+const FOREGROUND_COLOR: [f32; 3] = [1., 1., 1.];
 
 const POINTS_PER_BUFF: usize = 100;
-const BUFF_SIZE: wgpu::BufferSize = match wgpu::BufferSize::new((POINTS_PER_BUFF * std::mem::size_of::<Point>()) as u64) {
-	None => panic!("Error on BUFF_SIZE const definition"),
-	Some(x) => x,
-};
+/// Points the stroke log keeps, the oldest strokes are forgotten past it
+const MAX_LOGGED_POINTS: usize = 1 << 20;
+/// Frames a replayed stroke gets to be drawn in, in case it never catches up
+const MAX_REPLAY_FRAMES: usize = 10_000;
+/// Distance between the points strokes are interpolated with, in screen pixels when it follows the zoom
+const SPLINE_STEP: f32 = 2.;
+/// Stroke points are whole document pixels, interpolating closer than that adds nothing
+const MIN_SPLINE_STEP: f32 = 1.;
+const MAX_SPLINE_STEPS: u32 = 32;
 
-pub struct Canvas {
-	pipelines: std::sync::Arc<Pipelines>,
+/// Fill sweeps (rows then columns) before giving up on a fill still growing
+const MAX_FILL_SWEEPS: u32 = 256;
+
+/// Side of the blocks hashed on their own, so only the damaged ones are hashed again
+const HASH_BLOCK: u32 = 64;
+/// Of the layer, composite, proof and stroke textures, rgba8 packed in a u32. It's the format GL can
+/// read and write in storage textures, and copies to and from buffers the same bytes as rgba8
+const TILE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// Side of the blocks saved for undo before an edit first touches them
+const JOURNAL_BLOCK: u32 = 256;
+/// Set in the layer id of saved blocks of a layer mask
+const MASK_BLOCK: u32 = 1 << 31;
+
+/// Longest side of the layer thumbnails
+const THUMB_SIDE: u32 = 128;
+/// Thumbnails are refreshed by square cells of this many thumbnail pixels
+const THUMB_CELL: u32 = 16;
+/// Cells downscaled per frame, the rest wait for the next ones so strokes don't lag
+const THUMB_CELLS_PER_FRAME: usize = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorSlot {
+	/// Painted with
+	Foreground,
+	/// What the canvas is cleared to
+	Background,
+}
+
+/// What the viewport shows the artwork as once quantized for, without changing or exporting it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProofTarget {
+	/// Nearest of a fixed 16 color palette
+	Palette16,
+	/// Dithered black on a grey panel
+	EInk,
+	/// Limited ink coverage on off-white paper
+	Print,
+}
+
+impl ProofTarget {
+	pub const ALL: [ProofTarget; 3] = [ProofTarget::Palette16, ProofTarget::EInk, ProofTarget::Print];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			ProofTarget::Palette16 => "16 colors",
+			ProofTarget::EInk => "1-bit e-ink",
+			ProofTarget::Print => "print",
+		}
+	}
+}
+
+/// Packs a color like WGSL's `unpack4x8unorm` expects it, fully opaque.
+fn pack_rgba8(c: [f32; 3]) -> u32 {
+	let [r, g, b] = c;
+	return pack_premultiplied([r, g, b, 1.]);
+}
+
+/// Document of size `new` in the coordinates of one of size `old`, placed so the point `anchor`
+/// is at (0 to 1 from the top left) is the same in both.
+pub fn anchored_rect(old: Size, new: Size, anchor: [f32; 2]) -> Rect {
+	let [x, y] = [(old.w, new.w, anchor[0]), (old.h, new.h, anchor[1])]
+		.map(|(old, new, a)| ((old as f32 - new as f32) * a).round() as i32);
+	return Rect { pos: Point { x, y }, size: new };
+}
+
+/// Smallest rect of whole pixels holding `r` once mapped through `transform`.
+pub fn transformed_bounds(r: Rect, transform: Mat3) -> Rect {
+	let (x0, y0) = (r.pos.x as f32, r.pos.y as f32);
+	let (x1, y1) = (x0 + r.size.w as f32, y0 + r.size.h as f32);
+	let corners = [[x0, y0], [x1, y0], [x1, y1], [x0, y1]].map(|p| transform.apply(p));
+	let (lo, hi) = corners.iter().fold(([f32::MAX; 2], [f32::MIN; 2]), |(lo, hi), p| ([lo[0].min(p[0]), lo[1].min(p[1])], [hi[0].max(p[0]), hi[1].max(p[1])]));
+	let (left, top) = (lo[0].floor() as i32, lo[1].floor() as i32);
+	return Rect::new(left, top, (hi[0].ceil() as i32 - left).max(1) as u32, (hi[1].ceil() as i32 - top).max(1) as u32);
+}
+
+/// Relative luminance of a linear color, what masks show their layer by.
+fn luminance(c: [f32; 3]) -> f32 {
+	return 0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2];
+}
+
+fn pack_premultiplied(c: [f32; 4]) -> u32 {
+	let [r, g, b, a] = c.map(|v| (v.clamp(0., 1.) * 255.).round() as u32);
+	return r | g << 8 | b << 16 | a << 24;
+}
+
+/// How strokes are painted, pushed to the line pipeline with every dispatch.
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Brush {
+	/// Diameter in pixels
+	pub size: u32,
+	/// 1 for a hard edge, 0 fades out from the center
+	pub hardness: f32,
+	/// Distance between dabs as a fraction of the size, 0 for a continuous stroke
+	pub spacing: f32,
+	/// How much stylus pressure scales the size, 0 to 1
+	pub pressure_size: f32,
+	/// How much stylus pressure scales the opacity, 0 to 1
+	pub pressure_opacity: f32,
+	/// Most a single stroke covers, however much it goes over itself
+	pub opacity: f32,
+	/// How much each pass of the stroke adds, building up to the opacity. 1 covers at once
+	pub flow: f32,
+	/// Length in pixels of the string the stroke is pulled along by, trailing the cursor. 0 follows it
+	pub stabilizer: u32,
+	/// Degrees the brush tip is turned clockwise, when stamping one
+	pub rotation: f32,
+	/// Most dabs are randomly moved across the stroke either way, as a fraction of the size
+	pub scatter: f32,
+	/// Most dabs randomly shrink, as a fraction of the size
+	pub size_jitter: f32,
+	/// Most degrees the hue of dabs randomly turns either way
+	pub hue_jitter: f32,
+	/// Most the lightness of dabs randomly changes either way, 0 to 1
+	pub lightness_jitter: f32,
+	/// Most degrees stamped tips randomly turn either way
+	pub rotation_jitter: f32,
+	/// Picks the random dabs, the same for every stroke. 0 for different ones each stroke
+	pub seed: u32,
+}
+
+impl Default for Brush {
+	fn default() -> Self {
+		Self {
+			size: BRUSH_SIZE,
+			hardness: 1.,
+			spacing: 0.,
+			pressure_size: 1.,
+			pressure_opacity: 0.,
+			opacity: 1.,
+			flow: 1.,
+			stabilizer: 0,
+			rotation: 0.,
+			scatter: 0.,
+			size_jitter: 0.,
+			hue_jitter: 0.,
+			lightness_jitter: 0.,
+			rotation_jitter: 0.,
+			seed: 0,
+		}
+	}
+}
+
+impl Brush {
+	fn radius(&self) -> f32 {
+		self.size as f32 / 2.
+	}
+
+	/// Pixels the stroke may reach past its points, stamped tips reaching further by their corners
+	fn reach(&self, stamped: bool) -> u32 {
+		let radius = if stamped { self.radius() * std::f32::consts::SQRT_2 } else { self.radius() };
+		(radius + self.scatter * self.size as f32).ceil() as u32 + 1
+	}
+
+	/// Whether dabs are randomly changed, painting the strokes as separate dabs
+	fn jittered(&self) -> bool {
+		return self.scatter > 0. || self.size_jitter > 0. || self.hue_jitter > 0. || self.lightness_jitter > 0. || self.rotation_jitter > 0.;
+	}
+}
+
+pub const MIN_ZOOM: f32 = 1. / 16.;
+pub const MAX_ZOOM: f32 = 32.;
+
+/// How the document is shown in the viewport: scaled by `scale`, then moved by `offset` screen pixels.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ViewTransform {
+	pub offset: [f32; 2],
+	pub scale: f32,
+}
+
+impl Default for ViewTransform {
+	fn default() -> Self {
+		Self { offset: [0., 0.], scale: 1. }
+	}
+}
+
+impl ViewTransform {
+	/// Document pixel under `p`, a point relative to the viewport.
+	pub fn to_document(self, p: Point) -> Point {
+		let [x, y] = [(p.x, self.offset[0]), (p.y, self.offset[1])].map(|(v, o)| ((v as f32 - o) / self.scale).floor() as i32);
+		return Point { x, y };
+	}
+
+	/// Where the document point `p` is shown, relative to the viewport.
+	pub fn to_screen(self, p: Point) -> Point {
+		let [x, y] = [(p.x, self.offset[0]), (p.y, self.offset[1])].map(|(v, o)| (v as f32 * self.scale + o).round() as i32);
+		return Point { x, y };
+	}
+
+	pub fn to_screen_rect(self, r: Rect) -> Rect {
+		let pos = self.to_screen(r.pos);
+		let end = self.to_screen(r.pos + Point { x: r.size.w as i32, y: r.size.h as i32 });
+		return Rect::new(pos.x, pos.y, (end.x - pos.x).max(1) as u32, (end.y - pos.y).max(1) as u32);
+	}
+
+	/// Zooms by `factor` keeping the document point under `anchor` in place.
+	pub fn zoom(&mut self, factor: f32, anchor: Point) {
+		let scale = (self.scale * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+		let ratio = scale / self.scale;
+		self.offset = [(anchor.x, self.offset[0]), (anchor.y, self.offset[1])].map(|(a, o)| a as f32 - (a as f32 - o) * ratio);
+		self.scale = scale;
+	}
+
+	pub fn pan(&mut self, delta: Point) {
+		self.offset[0] += delta.x as f32;
+		self.offset[1] += delta.y as f32;
+	}
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BrushInput {
+	radius: f32,
+	hardness: f32,
+	spacing: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PressureInput {
+	size: f32,
+	opacity: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct StrokeInput {
+	opacity: f32,
+	flow: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct StampInput {
+	/// Radians
+	rotation: f32,
+	/// Of `STAMPED`, `ALPHA_LOCKED` and `SMUDGED`
+	flags: u32,
+}
+
+/// What the line pipelines read, the brush part the same for every dispatch of a frame
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineInput {
+	/// Document pixel of the first invocation
+	reference: Point,
+	/// Points of the bundle drawn
+	start: u32,
+	end: u32,
+	/// Where the bound tile starts in the document
+	tile_origin: Point,
+	brush: BrushInput,
+	/// Premultiplied and packed as rgba8
+	color: u32,
+	pressure: PressureInput,
+	stroke: StrokeInput,
+	stamp: StampInput,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PointInput {
+	mouse: Point,
+	brush_rad: u32,
+	_pad: u32,
+}
+
+/// Stroke point as the line shader reads it
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct StrokePoint {
+	pos: Point,
+	/// After the pressure curve, 1 for devices without pressure
+	pressure: f32,
+	/// Stroke the point is part of, from 1
+	stroke: u32,
+	/// Length of the stroke up to the point, where stamps are spaced from
+	distance: f32,
+	/// Radians stamped tips are turned by on top of the brush rotation, for symmetry copies
+	turn: f32,
+}
+
+/// Brush dynamics as the line shader reads them
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct DynamicsInput {
+	scatter: f32,
+	size_jitter: f32,
+	/// Fraction of the hue wheel
+	hue_jitter: f32,
+	lightness_jitter: f32,
+	/// Radians
+	rotation_jitter: f32,
+	seed: u32,
+	jittered: u32,
+	_pad: u32,
+}
+
+impl StrokePoint {
+	fn new(pos: Point, pressure: f32, stroke: u32) -> Self {
+		Self { pos, pressure, stroke, distance: 0., turn: 0. }
+	}
+
+	fn copied(&self, copy: &SymmetryCopy) -> Self {
+		let stroke = if copy.mirrored { self.stroke | MIRRORED_STROKE } else { self.stroke };
+		Self { pos: copy.apply(self.pos), stroke, turn: copy.turn, ..*self }
+	}
+}
+
+/// Queues `point` at the end of `line`, measuring how far along the stroke it is.
+/// A change the stroke log can replay at another resolution.
+#[derive(Clone)]
+pub enum Logged {
+	/// Points after the stabilizer, with the pressure after the curve, and what they were painted with
+	Stroke {
+		points: Vec<(Point, f32)>,
+		brush: Brush,
+		tip: Option<Arc<BrushTip>>,
+		symmetry: Option<Symmetry>,
+		color: [f32; 3],
+		erasing: bool,
+	},
+	/// The whole layer was cleared to the premultiplied color
+	Clear([f32; 4]),
+}
+
+/// Stroke or clear of the log as documents keep it, see `Canvas::saved_log`.
+pub struct SavedEdit {
+	/// Index of the layer it changed
+	pub layer: usize,
+	/// It changed the mask of the layer
+	pub mask: bool,
+	pub logged: Logged,
+}
+
+struct LogEntry {
+	serial: u64,
+	/// Block layer id of what it changed
+	target: u32,
+	/// Left out of replays while the edit it is part of is undone
+	undone: bool,
+	logged: Logged,
+}
+
+fn queue_point(line: &mut VecDeque<StrokePoint>, mut point: StrokePoint) {
+	if let Some(last) = line.back() {
+		point.distance = last.distance + ((point.pos.x - last.pos.x) as f32).hypot((point.pos.y - last.pos.y) as f32);
+	}
+	line.push_back(point);
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClearInput {
+	color: [f32; 4],
+	/// Region of the texture cleared
+	origin: Point,
+	size: Size,
+	/// Where the texture starts in the document
+	tile_origin: Point,
+	/// Uniform buffers round the struct up to its vec4 alignment
+	_pad: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct WarpInput {
+	inverse: [[f32; 4]; 3],
+	src_origin: Point,
+	tile_origin: Point,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ReplaceInput {
+	from: [f32; 3],
+	tolerance: f32,
+	to: [f32; 3],
+	_pad: f32,
+	tile_origin: Point,
+	_pad2: [u32; 2],
+}
+
+/// Uniform buffers align the tile origin after the vec3 background
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BoundsInput {
+	background: [f32; 3],
+	_pad: f32,
+	tile_origin: Point,
+	_pad2: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineInput {
+	color: [f32; 4],
+	/// Region of the texture outlined
+	origin: Point,
+	size: Size,
+	/// Where the texture starts in the document
+	tile_origin: Point,
+	width: f32,
+	_pad: f32,
+}
+
+/// Selection as `selection.wgsl` weights fills and filters by
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SelectionInput {
+	origin: Point,
+	size: Size,
+	feather: f32,
+	selected: u32,
+	_pad: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FillInput {
+	seed_color: [f32; 4],
+	tile_origin: Point,
+	document: Size,
+	tolerance: f32,
+	color: u32,
+	behind: u32,
+	_pad: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct HashInput {
+	tile_origin: Point,
+	region_origin: Point,
+	region_blocks: Size,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ThumbnailInput {
+	tile_origin: Point,
+	cell_origin: [u32; 2],
+	cell_size: [u32; 2],
+	scale: u32,
+	_pad: u32,
+}
+
+/// Inputs of the compute pipelines, each in the group after their other bindings when they come
+/// from a uniform buffer.
+struct Inputs {
+	clear: Uniforms<ClearInput>,
+	clear_selected: Uniforms<ClearInput>,
+	outline: Uniforms<OutlineInput>,
+	point: Uniforms<PointInput>,
+	line: Uniforms<LineInput>,
+	bounds: Uniforms<BoundsInput>,
+	replace: Uniforms<ReplaceInput>,
+	warp: Uniforms<WarpInput>,
+	thumbnail: Uniforms<ThumbnailInput>,
+	hash: Uniforms<HashInput>,
+	composite: Uniforms<CompositeInput>,
+	masked_composite: Uniforms<CompositeInput>,
+	fill: Uniforms<FillInput>,
+	proof: Uniforms<ProofInput>,
+}
+
+impl Inputs {
+	fn new(ctx: &Context) -> Self {
+		let stages = wgpu::ShaderStages::COMPUTE;
+		return Self {
+			clear: Uniforms::new(ctx, stages, 1),
+			clear_selected: Uniforms::new(ctx, stages, 2),
+			outline: Uniforms::new(ctx, stages, 2),
+			point: Uniforms::new(ctx, stages, 1),
+			line: Uniforms::new(ctx, stages, 3),
+			bounds: Uniforms::new(ctx, stages, 2),
+			replace: Uniforms::new(ctx, stages, 2),
+			warp: Uniforms::new(ctx, stages, 2),
+			thumbnail: Uniforms::new(ctx, stages, 2),
+			hash: Uniforms::new(ctx, stages, 2),
+			composite: Uniforms::new(ctx, stages, 2),
+			masked_composite: Uniforms::new(ctx, stages, 3),
+			fill: Uniforms::new(ctx, stages, 3),
+			proof: Uniforms::new(ctx, stages, 2),
+		};
+	}
+
+	/// Uploads `input` for the composite pipeline `layer` is blended with, masked or not.
+	fn bind_composite(&self, ctx: &mut Context, layer: &CanvasLayer, input: &CompositeInput) -> BoundUniforms {
+		if layer.mask.is_some() {
+			return self.masked_composite.bind(ctx, input);
+		}
+		return self.composite.bind(ctx, input);
+	}
+}
+
+/// Layout of a compute pipeline binding `groups`, then what `uniforms` needs.
+fn compute_layout<T: bytemuck::Pod>(ctx: &Context, label: &str, groups: &[&wgpu::BindGroupLayout], uniforms: &Uniforms<T>) -> wgpu::PipelineLayout {
+	return ctx.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+		label: Some(label),
+		bind_group_layouts: &groups.iter().copied().chain(uniforms.bind_group_layout()).collect::<Vec<_>>(),
+		push_constant_ranges: &uniforms.push_constant_ranges(),
+	});
+}
+
+/// Block hashes of a layer, see `Canvas::content_hash`.
+struct LayerHashes {
+	/// Hash of every HASH_BLOCK block, row major
+	pixels: Vec<u32>,
+	mask: Option<Vec<u32>>,
+	/// Region changed since the blocks were last hashed
+	dirty: Option<Rect>,
+}
+
+/// Copy of a layer in memory, see `Canvas::backup`.
+struct LayerBackup {
+	/// Straight alpha, empty until read back whole, again after the document is resized
+	pixels: Pixels,
+	mask: Option<Pixels>,
+	/// Region changed since it was last read back
+	dirty: Option<Rect>,
+}
+
+/// Part of a layer on its way to its backup.
+struct BackupReadback {
+	id: u32,
+	region: Rect,
+	pixels: Readback,
+	mask: Option<Readback>,
+}
+
+fn add_damage(damage: &mut Option<Rect>, r: Rect) {
+	*damage = Some(match *damage {
+		None => r,
+		Some(d) => d.union(r),
+	});
+}
+
+/// Copy of part of a tile, the undo history keeps them from before and after edits.
+pub struct SavedBlock {
+	/// Id of the layer it belongs to, with `MASK_BLOCK` set when it is of its mask
+	layer: u32,
+	tile: usize,
+	/// In tile coordinates
+	rect: Rect,
+	pixels: BlockPixels,
+}
+
+enum BlockPixels {
+	Texture(wgpu::Texture),
+	/// Premultiplied rows without padding, deflated
+	Packed(Vec<u8>),
+}
+
+impl SavedBlock {
+	/// GPU memory held, none once packed.
+	pub fn bytes(&self) -> u64 {
+		match self.pixels {
+			BlockPixels::Texture(_) => self.rect.size.w as u64 * self.rect.size.h as u64 * 4,
+			BlockPixels::Packed(_) => 0,
+		}
+	}
+
+	pub fn is_packed(&self) -> bool {
+		matches!(self.pixels, BlockPixels::Packed(_))
+	}
+}
+
+/// Moves saved blocks off the GPU, deflated in memory, waiting for them to be read back. Restoring
+/// them uploads them again.
+pub fn pack_blocks(ctx: &Context, blocks: &mut [SavedBlock]) {
+	let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+	let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+		label: Some("Canvas(Pack Encoder)"),
+	});
+	let mut buffers = Vec::new();
+	for (i, block) in blocks.iter().enumerate() {
+		let BlockPixels::Texture(tex) = &block.pixels else {
+			continue;
+		};
+		let padded_row = (block.rect.size.w * 4).div_ceil(align) * align;
+		let readback = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Canvas(Block Readback)"),
+			size: (padded_row * block.rect.size.h) as u64,
+			usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+		encoder.copy_texture_to_buffer(
+			tex.as_image_copy(),
+			wgpu::ImageCopyBuffer {
+				buffer: &readback,
+				layout: wgpu::ImageDataLayout {
+					offset: 0,
+					bytes_per_row: std::num::NonZeroU32::new(padded_row),
+					rows_per_image: None,
+				},
+			},
+			wgpu::Extent3d { width: block.rect.size.w, height: block.rect.size.h, depth_or_array_layers: 1 },
+		);
+		buffers.push((i, padded_row, readback));
+	}
+	if buffers.is_empty() {
+		return;
+	}
+	ctx.queue.submit(std::iter::once(encoder.finish()));
+	for (_, _, readback) in &buffers {
+		readback.slice(..).map_async(wgpu::MapMode::Read, |r| r.expect("Could not read back saved block"));
+	}
+	ctx.device.poll(wgpu::Maintain::Wait);
+
+	for (i, padded_row, readback) in buffers {
+		let block = &mut blocks[i];
+		let row_bytes = (block.rect.size.w * 4) as usize;
+		let data = readback.slice(..).get_mapped_range();
+		let mut deflate = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+		for row in data.chunks(padded_row as usize) {
+			// Writing to a vector doesn't fail
+			std::io::Write::write_all(&mut deflate, &row[..row_bytes]).unwrap();
+		}
+		block.pixels = BlockPixels::Packed(deflate.finish().unwrap());
+	}
+}
+
+/// Every layer of the document before a resize or crop, to go back to it.
+pub struct DocumentTiles {
+	/// Where its top left corner is in the document it was taken from
+	origin: Point,
+	size: Size,
+	/// Id, tiles and mask tiles of each layer
+	layers: Vec<(u32, Vec<Tile>, Option<Vec<Tile>>)>,
+}
+
+impl DocumentTiles {
+	pub fn bytes(&self) -> u64 {
+		let tiles = |tiles: &[Tile]| tiles.iter().map(Tile::bytes).sum::<u64>();
+		return self.layers.iter().map(|(_, t, m)| tiles(t) + m.as_deref().map_or(0, tiles)).sum();
+	}
+}
+
+/// A layer taken out of the stack with its pixels, put back where it was by `Canvas::restore_layer`.
+pub struct RemovedLayer {
+	index: usize,
+	layer: CanvasLayer,
+}
+
+impl RemovedLayer {
+	pub fn id(&self) -> u32 {
+		self.layer.id
+	}
+
+	pub fn index(&self) -> usize {
+		self.index
+	}
+
+	pub fn bytes(&self) -> u64 {
+		let tiles = |tiles: &[Tile]| tiles.iter().map(Tile::bytes).sum::<u64>();
+		return tiles(&self.layer.tiles) + self.layer.mask.as_deref().map_or(0, tiles);
+	}
+}
+
+/// Layer pixels from before the stroke touched them, and how much it covers them so far. The mask
+/// holds the coverage in red, and the stroke id in green and blue, pixels with an older id aren't touched yet.
+struct StrokeTile {
+	rect: Rect,
+	base: wgpu::TextureView,
+	mask: wgpu::TextureView,
+	/// Of the base and mask, with the tip, its sampler, the dynamics and the pickup
+	binding: wgpu::BindGroup,
+}
+
+impl StrokeTile {
+	fn new(ctx: &Context, pipelines: &Pipelines, rect: Rect, tip: &[wgpu::BindingResource; 4]) -> Self {
+		let create = |label| ctx.device.create_texture(&wgpu::TextureDescriptor {
+			label: Some(label),
+			size: wgpu::Extent3d { width: rect.size.w, height: rect.size.h, depth_or_array_layers: 1 },
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: TILE_FORMAT,
+			usage: wgpu::TextureUsages::STORAGE_BINDING,
+		});
+		let [base, mask] = [create("Canvas(Stroke Base)"), create("Canvas(Stroke Mask)")].map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()));
+		let binding = Self::binding(ctx, pipelines, &base, &mask, tip);
+		return Self { rect, base, mask, binding };
+	}
+
+	/// Binds `tip` from now on, keeping the stroke built up so far.
+	fn set_tip(&mut self, ctx: &Context, pipelines: &Pipelines, tip: &[wgpu::BindingResource; 4]) {
+		self.binding = Self::binding(ctx, pipelines, &self.base, &self.mask, tip);
+	}
+
+	fn binding(ctx: &Context, pipelines: &Pipelines, base: &wgpu::TextureView, mask: &wgpu::TextureView, tip: &[wgpu::BindingResource; 4]) -> wgpu::BindGroup {
+		let textures = [wgpu::BindingResource::TextureView(base), wgpu::BindingResource::TextureView(mask)];
+		let entries: Vec<wgpu::BindGroupEntry> = textures.into_iter().chain(tip.iter().cloned()).enumerate().map(|(binding, resource)| {
+			wgpu::BindGroupEntry { binding: binding as u32, resource }
+		}).collect();
+		return ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some("Canvas(Binding group 2)"),
+			layout: &pipelines.compute[2].get_bind_group_layout(2),
+			entries: &entries,
+		});
+	}
+}
+
+/// Part of the document backed by its own texture
+struct Tile {
+	/// Position in the document
+	rect: Rect,
 	image: Box<Image>,
-	tex_size: Size,
-	brush_radius: u32,
-	backgroud: [f32; 3],
+	/// Storage binding used by the compute pipelines
+	binding: wgpu::BindGroup,
+}
+
+impl Tile {
+	fn new(ctx: &mut Context, pipelines: &Pipelines, rect: Rect) -> Self {
+		let tex = ctx.device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("Canvas(Texture)"),
+			size: wgpu::Extent3d {
+				width: rect.size.w,
+				height: rect.size.h,
+				depth_or_array_layers: 1,
+			},
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: TILE_FORMAT,
+			usage: wgpu::TextureUsages::STORAGE_BINDING
+				| wgpu::TextureUsages::COPY_SRC
+				| wgpu::TextureUsages::COPY_DST,
+		});
+
+		let tex_view = tex.create_view(&wgpu::TextureViewDescriptor::default());
+		let binding = ctx.device.create_bind_group(
+			&wgpu::BindGroupDescriptor {
+				label: Some("Canvas(Binding group 0)"),
+				layout: &pipelines.compute[0].get_bind_group_layout(0),
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: wgpu::BindingResource::TextureView(&tex_view),
+					},
+				],
+			}
+		);
+
+		let mut image = Image::new(ctx);
+		image.set_packed_texture(ctx, tex);
+
+		Self { rect, image, binding }
+	}
+
+	fn texture(&self) -> &wgpu::Texture {
+		self.image.get_texture().as_ref().unwrap()
+	}
+
+	fn bytes(&self) -> u64 {
+		self.rect.size.w as u64 * self.rect.size.h as u64 * 4
+	}
+
+	fn extent(&self) -> wgpu::Extent3d {
+		wgpu::Extent3d {
+			width: self.rect.size.w,
+			height: self.rect.size.h,
+			depth_or_array_layers: 1,
+		}
+	}
+}
+
+fn encode_clear(encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, pipelines: &Pipelines, inputs: &Inputs, color: &[f32; 4], tiles: &[Tile]) {
+	for tile in tiles.iter() {
+		encode_clear_rect(encoder, ctx, pipelines, inputs, color, std::slice::from_ref(tile), tile.rect);
+	}
+}
+
+/// Clears only `rect` of the tiles, in document coordinates.
+fn encode_clear_rect(encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, pipelines: &Pipelines, inputs: &Inputs, color: &[f32; 4], tiles: &[Tile], rect: Rect) {
+	// Bound before the pass, which holds on to them
+	let parts: Vec<(&Tile, Rect, BoundUniforms)> = tiles.iter().filter_map(|tile| {
+		let part = rect.intersection(tile.rect)?;
+		let input = ClearInput {
+			color: *color,
+			origin: part.pos - tile.rect.pos,
+			size: part.size,
+			tile_origin: tile.rect.pos,
+			_pad: [0; 2],
+		};
+		return Some((tile, part, inputs.clear.bind(ctx, &input)));
+	}).collect();
+
+	let mut clear_pass = encoder.begin_compute_pass(
+		&wgpu::ComputePassDescriptor {
+			label: Some("Canvas(Clear Pass)"),
+		}
+	);
+
+	clear_pass.set_pipeline(&pipelines.compute[0]);
+	for (tile, part, input) in parts.iter() {
+		input.set_compute(&mut clear_pass);
+		clear_pass.set_bind_group(0, &tile.binding, &[]);
+		clear_pass.dispatch_workgroups((part.size.w/8)+1, (part.size.h/8)+1, 1);
+	}
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CompositeInput {
+	origin: Point,
+	opacity: f32,
+	first: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ProofInput {
+	origin: Point,
+	tile_pos: Point,
+	target: u32,
+	_pad: u32,
+}
+
+/// Document pixels on their way from the GPU, see `Canvas::composite_readback`.
+pub struct Readback {
+	size: Size,
+	/// Tile rect, bytes per row and buffer for each tile
+	buffers: Vec<(Rect, u32, wgpu::Buffer)>,
+	/// Buffers mapped so far
+	mapped: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl Readback {
+	/// Whether every tile is mapped, `try_finish` returns the pixels then.
+	pub fn is_ready(&self, ctx: &Context) -> bool {
+		ctx.device.poll(wgpu::Maintain::Poll);
+		return self.mapped.load(std::sync::atomic::Ordering::Acquire) >= self.buffers.len();
+	}
+
+	/// Straight alpha pixels once every tile is mapped, None while they aren't.
+	pub fn try_finish(&self, ctx: &Context) -> Option<Pixels> {
+		if !self.is_ready(ctx) {
+			return None;
+		}
+
+		let mut pixels = Pixels::new(self.size);
+		for (rect, padded_row, buffer) in &self.buffers {
+			let row_bytes = (rect.size.w * 4) as usize;
+			let data = buffer.slice(..).get_mapped_range();
+			for y in 0..rect.size.h {
+				let src = (y * padded_row) as usize;
+				let dst = (((rect.pos.y as u32 + y) * self.size.w + rect.pos.x as u32) * 4) as usize;
+				pixels.data[dst..dst + row_bytes].copy_from_slice(&data[src..src + row_bytes]);
+			}
+		}
+		pixels.unpremultiply();
+		return Some(pixels);
+	}
+}
+
+/// Layer contents as stored in documents.
+pub struct LayerPixels {
+	/// Straight alpha, the size of the document
+	pub pixels: Pixels,
+	pub opacity: f32,
+	pub visible: bool,
+	/// Grayscale, None when the layer has no mask
+	pub mask: Option<Pixels>,
+}
+
+/// Pixels of one layer, premultiplied so strokes blend over transparent pixels correctly.
+struct CanvasLayer {
+	/// Stays the same when layers are reordered, saved blocks refer to it
+	id: u32,
+	tiles: Vec<Tile>,
+	opacity: f32,
+	visible: bool,
+	/// Cleared to the background color instead of transparent
+	opaque: bool,
+	/// Grayscale tiles the layer is multiplied by when composited, white where it shows
+	mask: Option<Vec<Tile>>,
+	/// Strokes only change the color of pixels, not how opaque they are
+	alpha_locked: bool,
+}
+
+/// Downscaled copy of a layer for the layers panel, kept up to date a few cells per frame.
+pub struct LayerThumbnail {
+	image: Box<Image>,
+	binding: wgpu::BindGroup,
+	/// Document size it was made for
+	document: Size,
+	/// Canvas pixels per thumbnail pixel
+	scale: u32,
+	size: Size,
+	/// Cells waiting to be downscaled, row major
+	dirty: Vec<bool>,
+	cells: Size,
+}
+
+impl LayerThumbnail {
+	/// Marks the cells covering `damage`, in document coordinates.
+	pub fn invalidate(&mut self, damage: Rect) {
+		let Some(r) = damage.intersection(Rect::new(0, 0, self.document.w, self.document.h)) else {
+			return;
+		};
+
+		let cell_px = self.scale * THUMB_CELL;
+		let (x0, y0) = (r.pos.x as u32 / cell_px, r.pos.y as u32 / cell_px);
+		let x1 = (r.pos.x as u32 + r.size.w - 1) / cell_px;
+		let y1 = (r.pos.y as u32 + r.size.h - 1) / cell_px;
+		for y in y0..=y1.min(self.cells.h - 1) {
+			for x in x0..=x1.min(self.cells.w - 1) {
+				self.dirty[(y * self.cells.w + x) as usize] = true;
+			}
+		}
+	}
+
+	pub fn document(&self) -> Size {
+		self.document
+	}
+
+	pub fn is_dirty(&self) -> bool {
+		self.dirty.contains(&true)
+	}
+
+	pub fn bytes(&self) -> u64 {
+		self.size.w as u64 * self.size.h as u64 * 4
+	}
+
+	pub fn size(&self) -> Size {
+		self.size
+	}
+
+	/// Drawn over the row of the layer in the layers panel
+	pub fn image(&mut self) -> &mut Image {
+		&mut self.image
+	}
+}
+
+pub struct Canvas {
+	pipelines: std::sync::Arc<Pipelines>,
+	inputs: Inputs,
+	/// Bottom first
+	layers: Vec<CanvasLayer>,
+	active: usize,
+	next_layer_id: u32,
+	/// Visible layers blended together, what is shown and read back
+	composite: Vec<Tile>,
+	tex_size: Size,
+	view: ViewTransform,
+	/// Region the selection tools act on, within the document
+	selection: Option<Rect>,
+	/// Pixels over which the selection edges fade in
+	selection_feather: f32,
+	/// `selection` as fills and filters read it, rewritten before each of them
+	selection_mask: wgpu::Buffer,
+	/// `selection_mask` for the filters and selection fills, and for flood fills
+	selection_binding: wgpu::BindGroup,
+	fill_selection_binding: wgpu::BindGroup,
+	brush: Brush,
+	/// Stamped along strokes instead of round dabs
+	tip: Option<Arc<BrushTip>>,
+	/// Copies strokes are painted with
+	symmetry: Option<Symmetry>,
+	/// What the stroke tiles bind with `dynamics` and `pickup`, `tip` or a blank one before the first
+	bound_tip: Arc<BrushTip>,
+	tip_sampler: Arc<wgpu::Sampler>,
+	/// Brush dynamics of the strokes, rewritten every frame painting them
+	dynamics: wgpu::Buffer,
+	/// Color the smudging stroke carries and its id, only the shaders read and write it
+	pickup: wgpu::Buffer,
+	/// Strokes smear the colors they go over, with the foreground mixed in, like wet paint
+	smudging: bool,
+	/// Strokes paint what the active layer is cleared to instead of the foreground color
+	erasing: bool,
+	/// Strokes and clears change the mask of the active layer, when it has one
+	editing_mask: bool,
+	/// Strokes are interpolated and spaced by screen pixels rather than document pixels
+	zoom_smoothing: bool,
+	/// Tiles composited instead of those of the layer with the id, transformed region and transform they show
+	transform_preview: Option<((u32, Rect, Mat3), Vec<Tile>)>,
+	/// Document from before the resizes since the last `take_resize`
+	resized: Option<DocumentTiles>,
+	/// Shown instead of the composite, kept up to date with it
+	proof: Option<(ProofTarget, Vec<Tile>)>,
+	/// Strokes and clears since the document was loaded, what `render_region` replays
+	log: VecDeque<LogEntry>,
+	logged_points: usize,
+	next_serial: u64,
+	/// Serials of the entries logged since the last `take_logged`
+	logged: Vec<u64>,
+	/// Response curve of the input device drawing, set from its calibration
+	pressure_curve: PressureCurve,
+	foreground: [f32; 3],
+	backgroud: [f32; 3],
+
+	line_points: VecDeque<VecDeque<StrokePoint>>,
+	mouse_pos: Option<Point>,
+	/// Pressure at `mouse_pos`
+	pressure: f32,
+	/// Last points of the stroke being drawn as they came in, the newest ones not queued yet
+	stroke_tail: Vec<StrokePoint>,
+	/// End of the stabilizer string the stroke follows, None while not drawing
+	pen: Option<[f32; 2]>,
+	/// Id of the last stroke begun
+	stroke_id: u32,
+	/// Where strokes build up before covering the active layer, one per tile and created with the first stroke
+	stroke_tiles: Vec<StrokeTile>,
+	mouse_down: bool,
+	clear: bool,
+	/// Region of the texture changed and not composited yet
+	damage: Option<Rect>,
+	/// Region composited since the last `take_damage`
+	composited: Option<Rect>,
+	/// What changed, for the caches of the layers and composite to take
+	invalidations: Invalidations,
+
+	/// Blocks as they were before the edit in progress changed them, by layer id, tile and block coordinates
+	journal: Option<HashMap<(u32, usize, u32, u32), SavedBlock>>,
+
+	/// By layer id
+	layer_hashes: HashMap<u32, LayerHashes>,
+	hash_readbacks: Vec<HashReadback>,
+	hash_subscription: Subscription,
+
+	/// By layer id
+	backups: HashMap<u32, LayerBackup>,
+	backup_readbacks: Vec<BackupReadback>,
+	backup_subscription: Subscription,
+}
+
+impl components::Component for Canvas {
+	fn generate_pipelines(ctx: &Context) -> Pipelines {
+		let inputs = Inputs::new(ctx);
+		// Every input comes the same way, from push constants or uniform buffers
+		let defines = inputs.clear.defines();
+		let shader = ctx.create_shader("Canvas(Shader)", include_str!("shaders/canvas.wgsl"), defines);
+		// Strokes of stamped or jittered brushes are painted dab by dab, the others as solid lines
+		let dab_shader = ctx.create_shader("Canvas(Dab Shader)", include_str!("shaders/canvas.wgsl"), &[defines, &["DABS"]].concat());
+
+		let binding_group_layout = ctx.bind_group_layout("Canvas(Binding Group Layout)", &[
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::StorageTexture {
+					access: wgpu::StorageTextureAccess::ReadWrite,
+					format: TILE_FORMAT,
+					view_dimension: wgpu::TextureViewDimension::D2
+				},
+				count: None,
+			}
+		]);
+
+		let line_list_layout = ctx.bind_group_layout("Canvas(Line List Layout)", &[
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Storage {
+						read_only: true,
+					},
+					has_dynamic_offset: false,
+					min_binding_size: core::num::NonZeroU64::new(std::mem::size_of::<StrokePoint>() as u64),
+
+				},
+				count: None,
+			}
+		]);
+
+		let point_pipeline_layout = compute_layout(ctx, "Canvas(Point Pipeline Layout)", &[&binding_group_layout], &inputs.point);
+
+		let point_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Point Pipeline)"),
+				layout: Some(&point_pipeline_layout),
+				module: &shader,
+				entry_point: "draw_point",
+			}
+		);
+
+		let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::COMPUTE,
+			ty: wgpu::BindingType::StorageTexture {
+				access: wgpu::StorageTextureAccess::ReadWrite,
+				format: TILE_FORMAT,
+				view_dimension: wgpu::TextureViewDimension::D2,
+			},
+			count: None,
+		};
+		// The stroke textures with the tip, its sampler, the dynamics and the pickup, so the inputs
+		// fit in the fourth group downlevel adapters have
+		let stroke_layout = ctx.bind_group_layout("Canvas(Stroke Layout)", &[
+			storage_entry(0),
+			storage_entry(1),
+			wgpu::BindGroupLayoutEntry {
+				binding: 2,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Texture {
+					sample_type: wgpu::TextureSampleType::Float { filterable: true },
+					view_dimension: wgpu::TextureViewDimension::D2,
+					multisampled: false,
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 3,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 4,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Uniform,
+					has_dynamic_offset: false,
+					min_binding_size: None,
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 5,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Storage { read_only: false },
+					has_dynamic_offset: false,
+					min_binding_size: None,
+				},
+				count: None,
+			},
+		]);
+
+		let line_pipeline_layout = compute_layout(ctx, "Canvas(Line Pipeline Layout)", &[&binding_group_layout, &line_list_layout, &stroke_layout], &inputs.line);
+
+		let line_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Line Pipeline)"),
+				layout: Some(&line_pipeline_layout),
+				module: &shader,
+				entry_point: "draw_line",
+			}
+		);
+
+		// Picks up the color under a bundle of stroke points before it's drawn smudging
+		let pickup_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Pickup Pipeline)"),
+				layout: Some(&line_pipeline_layout),
+				module: &shader,
+				entry_point: "pick_up",
+			}
+		);
+
+		let dab_line_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Dab Line Pipeline)"),
+				layout: Some(&line_pipeline_layout),
+				module: &dab_shader,
+				entry_point: "draw_line",
+			}
+		);
+
+		let clear_pipeline_layout = compute_layout(ctx, "Canvas(Compute Pipeline Layout)", &[&binding_group_layout], &inputs.clear);
+
+		let clear_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Compute Pipeline)"),
+				layout: Some(&clear_pipeline_layout),
+				module: &shader,
+				entry_point: "clear",
+			}
+		);
+
+		// Fills bind the selection in a group of their own, the other passes next to the tile
+		let [fill_selection_layout, selection_layout] = [0, 3].map(|binding| {
+			ctx.bind_group_layout("Canvas(Selection Layout)", &[
+				wgpu::BindGroupLayoutEntry {
+					binding,
+					visibility: wgpu::ShaderStages::COMPUTE,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: core::num::NonZeroU64::new(std::mem::size_of::<SelectionInput>() as u64),
+					},
+					count: None,
+				}
+			])
+		});
+
+		let clear_selected_pipeline_layout = compute_layout(ctx, "Canvas(Clear Selected Pipeline Layout)", &[&binding_group_layout, &selection_layout], &inputs.clear_selected);
+
+		let clear_selected_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Clear Selected Pipeline)"),
+				layout: Some(&clear_selected_pipeline_layout),
+				module: &shader,
+				entry_point: "clear_selected",
+			}
+		);
+
+		let stroke_selected_pipeline_layout = compute_layout(ctx, "Canvas(Stroke Selected Pipeline Layout)", &[&binding_group_layout, &selection_layout], &inputs.outline);
+
+		let stroke_selected_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Stroke Selected Pipeline)"),
+				layout: Some(&stroke_selected_pipeline_layout),
+				module: &shader,
+				entry_point: "stroke_selected",
+			}
+		);
+
+		let bounds_layout = ctx.bind_group_layout("Canvas(Bounds Layout)", &[
+			wgpu::BindGroupLayoutEntry {
+				binding: 1,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Storage {
+						read_only: false,
+					},
+					has_dynamic_offset: false,
+					min_binding_size: core::num::NonZeroU64::new(4*4),
+				},
+				count: None,
+			}
+		]);
+
+		let bounds_pipeline_layout = compute_layout(ctx, "Canvas(Bounds Pipeline Layout)", &[&binding_group_layout, &bounds_layout], &inputs.bounds);
+
+		let bounds_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Bounds Pipeline)"),
+				layout: Some(&bounds_pipeline_layout),
+				module: &shader,
+				entry_point: "content_bounds",
+			}
+		);
+
+		let replace_pipeline_layout = compute_layout(ctx, "Canvas(Replace Pipeline Layout)", &[&binding_group_layout, &selection_layout], &inputs.replace);
+
+		let replace_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Replace Pipeline)"),
+				layout: Some(&replace_pipeline_layout),
+				module: &shader,
+				entry_point: "replace_color",
+			}
+		);
+
+		let warp_shader = ctx.create_shader("Canvas(Warp Shader)", include_str!("shaders/warp.wgsl"), defines);
+
+		// Packed like the tiles, the shader filters it
+		let warp_source_layout = ctx.bind_group_layout("Canvas(Warp Source Layout)", &[
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Texture {
+					sample_type: wgpu::TextureSampleType::Uint,
+					view_dimension: wgpu::TextureViewDimension::D2,
+					multisampled: false,
+				},
+				count: None,
+			},
+		]);
+
+		let warp_pipeline_layout = compute_layout(ctx, "Canvas(Warp Pipeline Layout)", &[&binding_group_layout, &warp_source_layout], &inputs.warp);
+
+		let warp_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Warp Pipeline)"),
+				layout: Some(&warp_pipeline_layout),
+				module: &warp_shader,
+				entry_point: "warp",
+			}
+		);
+
+		let thumbnail_shader = ctx.create_shader("Canvas(Thumbnail Shader)", include_str!("shaders/thumbnail.wgsl"), defines);
+
+		let thumbnail_layout = ctx.bind_group_layout("Canvas(Thumbnail Layout)", &[
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::StorageTexture {
+					access: wgpu::StorageTextureAccess::WriteOnly,
+					format: wgpu::TextureFormat::Rgba8Unorm,
+					view_dimension: wgpu::TextureViewDimension::D2
+				},
+				count: None,
+			}
+		]);
+
+		let thumbnail_pipeline_layout = compute_layout(ctx, "Canvas(Thumbnail Pipeline Layout)", &[&binding_group_layout, &thumbnail_layout], &inputs.thumbnail);
+
+		let thumbnail_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Thumbnail Pipeline)"),
+				layout: Some(&thumbnail_pipeline_layout),
+				module: &thumbnail_shader,
+				entry_point: "downscale",
+			}
+		);
+
+		let hash_shader = ctx.create_shader("Canvas(Hash Shader)", include_str!("shaders/hash.wgsl"), defines);
+
+		let hash_layout = ctx.bind_group_layout("Canvas(Hash Layout)", &[
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Storage {
+						read_only: false,
+					},
+					has_dynamic_offset: false,
+					min_binding_size: core::num::NonZeroU64::new(4),
+				},
+				count: None,
+			}
+		]);
+
+		let hash_pipeline_layout = compute_layout(ctx, "Canvas(Hash Pipeline Layout)", &[&binding_group_layout, &hash_layout], &inputs.hash);
+
+		let hash_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Hash Pipeline)"),
+				layout: Some(&hash_pipeline_layout),
+				module: &hash_shader,
+				entry_point: "block_hash",
+			}
+		);
+
+		let fill_shader = ctx.create_shader("Canvas(Fill Shader)", include_str!("shaders/fill.wgsl"), defines);
+
+		let fill_layout = ctx.bind_group_layout("Canvas(Fill Layout)", &[
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Storage {
+						read_only: false,
+					},
+					has_dynamic_offset: false,
+					min_binding_size: core::num::NonZeroU64::new(4),
+				},
+				count: None,
+			},
+			wgpu::BindGroupLayoutEntry {
+				binding: 1,
+				visibility: wgpu::ShaderStages::COMPUTE,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Storage {
+						read_only: false,
+					},
+					has_dynamic_offset: false,
+					min_binding_size: core::num::NonZeroU64::new(4),
+				},
+				count: None,
+			},
+		]);
+
+		let fill_pipeline_layout = compute_layout(ctx, "Canvas(Fill Pipeline Layout)", &[&binding_group_layout, &fill_layout, &fill_selection_layout], &inputs.fill);
+
+		let [fill_rows_pipeline, fill_columns_pipeline, fill_apply_pipeline] = ["fill_rows", "fill_columns", "fill_apply"].map(|entry_point| {
+			ctx.device.create_compute_pipeline(
+				&wgpu::ComputePipelineDescriptor {
+					label: Some("Canvas(Fill Pipeline)"),
+					layout: Some(&fill_pipeline_layout),
+					module: &fill_shader,
+					entry_point,
+				}
+			)
+		});
+
+		// Layers with a mask bind its tile at group 2
+		let [composite_pipeline, masked_composite_pipeline] = [(&[][..], 2, &inputs.composite), (&["MASKED"][..], 3, &inputs.masked_composite)].map(|(masked, groups, uniforms)| {
+			let composite_shader = ctx.create_shader("Canvas(Composite Shader)", include_str!("shaders/composite.wgsl"), &[defines, masked].concat());
+			let composite_pipeline_layout = compute_layout(ctx, "Canvas(Composite Pipeline Layout)", &[&*binding_group_layout; 3][..groups], uniforms);
+
+			ctx.device.create_compute_pipeline(
+				&wgpu::ComputePipelineDescriptor {
+					label: Some("Canvas(Composite Pipeline)"),
+					layout: Some(&composite_pipeline_layout),
+					module: &composite_shader,
+					entry_point: "composite_layer",
+				}
+			)
+		});
+
+		// Reads a composite tile at group 0, writes its proof at group 1
+		let proof_shader = ctx.create_shader("Canvas(Proof Shader)", include_str!("shaders/proof.wgsl"), defines);
+		let proof_pipeline_layout = compute_layout(ctx, "Canvas(Proof Pipeline Layout)", &[&binding_group_layout, &binding_group_layout], &inputs.proof);
+		let proof_pipeline = ctx.device.create_compute_pipeline(
+			&wgpu::ComputePipelineDescriptor {
+				label: Some("Canvas(Proof Pipeline)"),
+				layout: Some(&proof_pipeline_layout),
+				module: &proof_shader,
+				entry_point: "proof_tile",
+			}
+		);
+
+		return Pipelines {
+			render: vec![],
+			compute: vec![clear_pipeline, point_pipeline, line_pipeline, bounds_pipeline, warp_pipeline, replace_pipeline, thumbnail_pipeline, hash_pipeline, composite_pipeline, fill_rows_pipeline, fill_columns_pipeline, fill_apply_pipeline, dab_line_pipeline, masked_composite_pipeline, proof_pipeline, clear_selected_pipeline, stroke_selected_pipeline, pickup_pipeline],
+		};
+	}
+	fn new(ctx: &mut Context) -> Box<Self> {
+		return Self::with_size(ctx, TEX_SIZE);
+	}
+
+	fn render(&mut self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, output: &wgpu::TextureView, viewport: Rect, clip_space: Option<Rect>) {
+		self.update(encoder, ctx);
+
+		// Only the texels in the viewport are drawn, zoomed in tiles could be past the viewport size limits
+		let visible = Rect {
+			pos: self.view.to_document(Point { x: 0, y: 0 }),
+			size: Size { w: (viewport.size.w as f32 / self.view.scale).ceil() as u32 + 2, h: (viewport.size.h as f32 / self.view.scale).ceil() as u32 + 2 },
+		};
+		let shown = match &mut self.proof {
+			Some((_, tiles)) => tiles,
+			None => &mut self.composite,
+		};
+		for tile in shown.iter_mut() {
+			let Some(source) = tile.rect.intersection(visible) else {
+				continue;
+			};
+			let tile_viewport = self.view.to_screen_rect(source) + viewport.pos;
+			if let Some(clip) = tile_viewport.intersection(clip_space.unwrap_or(viewport)).and_then(|c| c.intersection(viewport)) {
+				tile.image.set_source(Some(Rect { pos: source.pos - tile.rect.pos, size: source.size }));
+				tile.image.render(encoder, ctx, output, tile_viewport, Some(clip));
+			}
+		}
+	}
+}
+
+impl Canvas {
+	/// Canvas with a `size` document, the size is halved down to a minimum when it doesn't fit in GPU
+	/// memory. Scratch canvases are sized to what they show rather than the default document.
+	pub fn with_size(ctx: &mut Context, size: Size) -> Box<Self> {
+		let mut tex_size = size;
+		let pipelines = ctx.get_pipelines::<Self>();
+
+		// Shrink the document instead of failing when the textures don't fit in GPU memory
+		let (tiles, composite) = loop {
+			ctx.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+			let tiles = Self::create_tiles(ctx, &pipelines, tex_size);
+			let composite = Self::create_tiles(ctx, &pipelines, tex_size);
+			match pollster::block_on(ctx.device.pop_error_scope()) {
+				None => break (tiles, composite),
+				Some(e) if tex_size.w > MIN_TEX_SIDE && tex_size.h > MIN_TEX_SIDE => {
+					drop(tiles);
+					drop(composite);
+					tex_size = Size { w: tex_size.w / 2, h: tex_size.h / 2 };
+					log::error!("{e}, retrying with a {}x{} canvas", tex_size.w, tex_size.h);
+				}
+				Some(e) => panic!("Could not allocate the canvas: {e}"),
+			}
+		};
+
+		let tip_sampler = ctx.sampler(wgpu::AddressMode::ClampToEdge, wgpu::FilterMode::Linear);
+		let dynamics = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Canvas(Dynamics)"),
+			size: std::mem::size_of::<DynamicsInput>() as u64,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+		let pickup = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Canvas(Pickup)"),
+			size: PICKUP_SIZE,
+			usage: wgpu::BufferUsages::STORAGE,
+			mapped_at_creation: false,
+		});
+		let blank = Arc::new(BrushTip::new(ctx, "Canvas(Blank Tip)", &Pixels::new(Size { w: 1, h: 1 })));
+		let selection_mask = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Canvas(Selection Mask)"),
+			size: std::mem::size_of::<SelectionInput>() as u64,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+		let [selection_binding, fill_selection_binding] = [(15, 1, 3), (9, 2, 0)].map(|(pipeline, group, binding)| {
+			ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("Canvas(Selection Binding)"),
+				layout: &pipelines.compute[pipeline].get_bind_group_layout(group),
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding,
+						resource: selection_mask.as_entire_binding(),
+					},
+				],
+			})
+		});
+
+		let background = CanvasLayer {
+			id: 0,
+			tiles,
+			opacity: 1.,
+			visible: true,
+			opaque: true,
+			mask: None,
+			alpha_locked: false,
+		};
+
+		let mut invalidations = Invalidations::new();
+		let hash_subscription = invalidations.subscribe();
+		let backup_subscription = invalidations.subscribe();
+
+		Box::new(Self {
+			pipelines,
+			inputs: Inputs::new(ctx),
+			layers: vec![background],
+			active: 0,
+			next_layer_id: 1,
+			composite,
+			tex_size,
+			view: ViewTransform::default(),
+			selection: None,
+			selection_feather: 0.,
+			selection_mask,
+			selection_binding,
+			fill_selection_binding,
+
+			brush: Brush::default(),
+			tip: None,
+			symmetry: None,
+			bound_tip: blank,
+			pickup,
+			smudging: false,
+			tip_sampler,
+			dynamics,
+			erasing: false,
+			editing_mask: false,
+			zoom_smoothing: true,
+			transform_preview: None,
+			resized: None,
+			proof: None,
+			log: VecDeque::new(),
+			logged_points: 0,
+			next_serial: 0,
+			logged: Vec::new(),
+			pressure_curve: PressureCurve::default(),
+			foreground: FOREGROUND_COLOR,
+			backgroud: BACKGROUND_COLOR,
+			line_points: VecDeque::new(),
+			mouse_pos: None,
+			pressure: 1.,
+			stroke_tail: Vec::new(),
+			pen: None,
+			stroke_id: 0,
+			stroke_tiles: Vec::new(),
+			mouse_down: false,
+			clear: true,
+			damage: None,
+			composited: None,
+			invalidations,
+
+			journal: None,
+
+			layer_hashes: HashMap::new(),
+			hash_readbacks: Vec::new(),
+			hash_subscription,
+
+			backups: HashMap::new(),
+			backup_readbacks: Vec::new(),
+			backup_subscription,
+		})
+	}
+
+	/// Draws the queued strokes and composites what changed, `take_damage` tells where. Rendering
+	/// does it too, called before to know the damage ahead of drawing.
+	pub fn update(&mut self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context) {
+		if self.clear {
+			self.clear = false;
+			let document = Rect::new(0, 0, self.tex_size.w, self.tex_size.h);
+			self.journal_save(ctx, encoder, self.target_id(), document);
+			self.invalidate(Damaged::Layer(self.layers[self.active].id), document);
+			let color = self.target_clear_color();
+			self.log_entry(Logged::Clear(color));
+			encode_clear(encoder, ctx, &self.pipelines, &self.inputs, &color, self.target_tiles());
+		}
+
+		if !self.line_points.is_empty() && self.line_points[0].len() > 1 {
+
+			// Lines that ended
+			let mut points_computed = 0;
+			let mut i = 0;
+
+			// Points uploaded this frame live in a slice of the context pool, reused on the next frame
+			let mut points_to_upload = 0;
+			for line in self.line_points.iter() {
+				if line.len() <= 1 || points_to_upload >= POINTS_PER_BUFF {
+					break;
+				}
+				points_to_upload = std::cmp::min(POINTS_PER_BUFF, points_to_upload + line.len());
+			}
+
+			// Symmetry copies of the points go after them, a copy per block of `points_to_upload`
+			let copies = self.symmetry.map_or_else(Vec::new, |s| s.copies());
+			let blocks = copies.len().max(1);
+			let upload_size = wgpu::BufferSize::new((points_to_upload * blocks * std::mem::size_of::<StrokePoint>()) as u64).unwrap();
+			let slice = ctx.buffer_pool.alloc(&ctx.device, upload_size);
+
+			let mut mapped = ctx.staging_belt.write_buffer(encoder, ctx.buffer_pool.buffer(&slice), slice.offset, upload_size, &ctx.device);
+
+			// Bounds, start and end point, and whether the points are drained once drawn, which copies aren't
+			let mut bundles: VecDeque<(Rect, u32, u32, bool)> = VecDeque::new();
+
+			let mut min_point: Point = self.line_points[0][0].pos;
+			let mut max_point: Point = min_point;
+
+			while points_computed < POINTS_PER_BUFF && i < self.line_points.len() {
+				use std::cmp::{min, max};
+
+				let mut bundle: (Rect, u32, u32, bool) = (Rect::new(0, 0, 0, 0), 0, 0, true);
+
+				if self.line_points[i].len() <= 1 {
+					// Not a viable line
+					break;
+				}
+
+				bundle.1 = points_computed as u32;
+
+				const P_SIZE: usize = std::mem::size_of::<StrokePoint>();
+				for k in 0..min(POINTS_PER_BUFF - points_computed, self.line_points[i].len()) {
+					let point = &self.line_points[i][k];
+					let p = &point.pos;
+
+					mapped[points_computed*P_SIZE..(points_computed+1)*P_SIZE].copy_from_slice(bytemuck::bytes_of(point));
+					points_computed += 1;
+
+					min_point.x = min(min_point.x, p.x);
+					min_point.y = min(min_point.y, p.y);
+
+					max_point.x = max(max_point.x, p.x);
+					max_point.y = max(max_point.y, p.y);
+				}
+
+				let size = max_point - min_point;
+
+				bundle.0 = Rect { pos: min_point, size: size.try_into().unwrap() };
+				bundle.2 = points_computed as u32;
+
+				bundles.push_back(bundle);
+
+				for (c, copy) in copies.iter().enumerate().skip(1) {
+					let offset = c * points_to_upload;
+					let (start, end) = (bundle.1 as usize, bundle.2 as usize);
+					let mut bounds: Option<(Point, Point)> = None;
+					for (k, point) in self.line_points[i].iter().take(end - start).enumerate() {
+						let point = point.copied(copy);
+						let index = offset + start + k;
+						mapped[index*P_SIZE..(index+1)*P_SIZE].copy_from_slice(bytemuck::bytes_of(&point));
+						let (lo, hi) = bounds.get_or_insert((point.pos, point.pos));
+						*lo = Point { x: min(lo.x, point.pos.x), y: min(lo.y, point.pos.y) };
+						*hi = Point { x: max(hi.x, point.pos.x), y: max(hi.y, point.pos.y) };
+					}
+					let (lo, hi) = bounds.unwrap();
+					let rect = Rect { pos: lo, size: (hi - lo).try_into().unwrap() };
+					bundles.push_back((rect, (offset + start) as u32, (offset + end) as u32, false));
+				}
+
+				if points_computed == POINTS_PER_BUFF {
+					let f = file!();
+					eprintln!("Buffer filled on this frame. If there was aditional lines to draw they were postponed to the next frame -> Should increase FPS or {f}::POINTS_PER_BUFF");
+					break;
+				}
+
+				i += 1;
+			}
+
+			drop(mapped);
+
+			// Points start at the same few places of the pool every frame
+			let line_binding = ctx.bind_group("Canvas(Binding group 1)", &[slice.chunk() as u64, slice.offset], || ctx.device.create_bind_group(
+				&wgpu::BindGroupDescriptor {
+					label: Some("Canvas(Binding group 1)"),
+					layout: &self.pipelines.compute[2].get_bind_group_layout(1),
+					entries: &[
+						wgpu::BindGroupEntry {
+							binding: 0,
+							resource: ctx.buffer_pool.binding(&slice),
+						}
+					],
+				}
+			));
+
+			let reach = self.brush.reach(self.tip.is_some());
+			for bundle in bundles.iter() {
+				let pos = bundle.0.pos - Point { x: reach as i32, y: reach as i32 };
+				let size = Size { w: bundle.0.size.w + 2*reach, h: bundle.0.size.h + 2*reach };
+				self.journal_save(ctx, encoder, self.target_id(), Rect { pos, size });
+			}
+
+			let tiles = self.target_tiles();
+			if self.stroke_tiles.len() != tiles.len() || self.stroke_tiles.iter().zip(tiles).any(|(s, t)| s.rect != t.rect) {
+				let resources = Self::tip_resources(&self.bound_tip, &self.tip_sampler, &self.dynamics, &self.pickup);
+				self.stroke_tiles = tiles.iter().map(|t| StrokeTile::new(ctx, &self.pipelines, t.rect, &resources)).collect();
+			}
+
+			let dabs = self.tip.is_some() || self.brush.jittered();
+			let brush = BrushInput {
+				radius: self.brush.radius(),
+				hardness: self.brush.hardness,
+				spacing: if dabs { self.dab_spacing() } else { self.brush.spacing },
+			};
+			let color = match (self.erasing, self.painting_mask()) {
+				// Erasing the mask hides the layer, painting it shows the layer as much as the foreground is light
+				(true, true) => pack_premultiplied([0., 0., 0., 1.]),
+				(false, true) => pack_rgba8([luminance(self.foreground); 3]),
+				(true, false) => pack_premultiplied(self.clear_color(self.active)),
+				(false, false) => pack_rgba8(self.foreground),
+			};
+			let pressure = PressureInput { size: self.brush.pressure_size, opacity: self.brush.pressure_opacity };
+			let stroke = StrokeInput { opacity: self.brush.opacity, flow: self.brush.flow };
+			let alpha_locked = self.layers[self.active].alpha_locked && !self.painting_mask();
+			let flags = if self.tip.is_some() { STAMPED } else { 0 } | if alpha_locked { ALPHA_LOCKED } else { 0 } | if self.smudging { SMUDGED } else { 0 };
+			let stamp = StampInput { rotation: self.brush.rotation.to_radians(), flags };
+			let b = &self.brush;
+			// Erasing takes out what is under the dabs whatever their color
+			let (hue_jitter, lightness_jitter) = if self.erasing { (0., 0.) } else { (b.hue_jitter / 360., b.lightness_jitter) };
+			let dynamics = DynamicsInput {
+				scatter: b.scatter,
+				size_jitter: b.size_jitter,
+				hue_jitter,
+				lightness_jitter,
+				rotation_jitter: b.rotation_jitter.to_radians(),
+				seed: b.seed,
+				jittered: b.jittered() as u32,
+				_pad: 0,
+			};
+			ctx.queue.write_buffer(&self.dynamics, 0, bytemuck::bytes_of(&dynamics));
+			// Borrowed apart from the rest of the canvas, which changes as the bundles are drawn
+			let editing_mask = self.editing_mask;
+			let layer = &self.layers[self.active];
+			let target = layer.mask.as_ref().filter(|_| editing_mask).unwrap_or(&layer.tiles);
+
+			// Bound before the pass holds on to them, for every tile each bundle covers
+			let line_inputs: Vec<Vec<BoundUniforms>> = bundles.iter().map(|bundle| {
+				let reference = bundle.0.pos - Point { x: reach as i32, y: reach as i32 };
+				let drawing_rect = Rect { pos: reference, size: Size { w: bundle.0.size.w + 2*reach, h: bundle.0.size.h + 2*reach } };
+				return target.iter().filter(|t| t.rect.intersects(drawing_rect)).map(|tile| {
+					let input = LineInput { reference, start: bundle.1, end: bundle.2, tile_origin: tile.rect.pos, brush, color, pressure, stroke, stamp };
+					return self.inputs.line.bind(ctx, &input);
+				}).collect();
+			}).collect();
+
+			let mut compute_pass = encoder.begin_compute_pass(
+				&wgpu::ComputePassDescriptor {
+					label: Some("Canvas(Compute Pass)"),
+				}
+			);
+
+			compute_pass.set_pipeline(&self.pipelines.compute[if dabs { 12 } else { 2 }]);
+			compute_pass.set_bind_group(1, &line_binding, &[]);
+
+			let mut line_inputs = line_inputs.iter();
+			while !bundles.is_empty() {
+				let reference = bundles[0].0.pos - Point {x: reach as i32, y: reach as i32};
+
+				let mut drawing_area = bundles[0].0.size;
+				drawing_area.w += 2*reach;
+				drawing_area.h += 2*reach;
+
+
+				let drawing_rect = Rect { pos: reference, size: drawing_area };
+				let covered = target.iter().zip(&self.stroke_tiles).filter(|(t, _)| t.rect.intersects(drawing_rect));
+				for ((tile, stroke), input) in covered.zip(line_inputs.next().unwrap()) {
+					compute_pass.set_bind_group(0, &tile.binding, &[]);
+					compute_pass.set_bind_group(2, &stroke.binding, &[]);
+					input.set_compute(&mut compute_pass);
+					if self.smudging {
+						compute_pass.set_pipeline(&self.pipelines.compute[17]);
+						compute_pass.dispatch_workgroups(1, 1, 1);
+						compute_pass.set_pipeline(&self.pipelines.compute[if dabs { 12 } else { 2 }]);
+					}
+					compute_pass.dispatch_workgroups(drawing_area.w/8 + 1, drawing_area.h/8 + 1, 1);
+				}
+				// The pass still holds the pipelines
+				add_damage(&mut self.damage, drawing_rect);
+				self.invalidations.notify(Damaged::Layer(layer.id), drawing_rect);
+
+				if bundles[0].3 {
+					let mut to_be_removed = bundles[0].2 - bundles[0].1;
+
+					if self.line_points.len() == 1 && self.mouse_down {
+						to_be_removed -= 1;
+					}
+
+					self.line_points[0].drain(0..(to_be_removed.try_into().unwrap()));
+
+					if self.line_points[0].is_empty() {
+						self.line_points.pop_front();
+					}
+				}
+
+				bundles.pop_front();
+			}
+		}
+
+
+		if let Some(damage) = self.damage.take() {
+			self.encode_composite(encoder, ctx, &self.composite, damage, &[]);
+			self.encode_proof(encoder, ctx, damage);
+			add_damage(&mut self.composited, damage);
+		}
+	}
+
+	/// `tip`, its sampler, `dynamics` and `pickup` as the stroke tiles bind them after their textures.
+	fn tip_resources<'a>(tip: &'a BrushTip, sampler: &'a wgpu::Sampler, dynamics: &'a wgpu::Buffer, pickup: &'a wgpu::Buffer) -> [wgpu::BindingResource<'a>; 4] {
+		return [
+			wgpu::BindingResource::TextureView(tip.view()),
+			wgpu::BindingResource::Sampler(sampler),
+			dynamics.as_entire_binding(),
+			pickup.as_entire_binding(),
+		];
+	}
+
+	/// Documents bigger than what the adapter supports are split in several textures
+	/// Side of the largest tile, one a texture can hold and a buffer can read back whole. A multiple
+	/// of 64, so its rows need no padding to be copied.
+	fn max_tile(limits: &wgpu::Limits) -> u32 {
+		let readable = ((limits.max_buffer_size / 4) as f64).sqrt() as u32;
+		return limits.max_texture_dimension_2d.min(readable) / 64 * 64;
+	}
+
+	fn create_tiles(ctx: &mut Context, pipelines: &Pipelines, tex_size: Size) -> Vec<Tile> {
+		let max_tile = Self::max_tile(&ctx.device.limits());
+		let mut tiles = Vec::new();
+		for y in (0..tex_size.h).step_by(max_tile as usize) {
+			for x in (0..tex_size.w).step_by(max_tile as usize) {
+				use std::cmp::min;
+				let rect = Rect::new(x as i32, y as i32, min(max_tile, tex_size.w - x), min(max_tile, tex_size.h - y));
+				tiles.push(Tile::new(ctx, pipelines, rect));
+			}
+		}
+		if tiles.len() > 1 {
+			log::info!("Canvas of {}x{} split in {} textures", tex_size.w, tex_size.h, tiles.len());
+		}
+		return tiles;
+	}
+
+	/// Premultiplied color `layer` is cleared to.
+	fn clear_color(&self, layer: usize) -> [f32; 4] {
+		if self.layers[layer].opaque {
+			let [r, g, b] = self.backgroud;
+			return [r, g, b, 1.];
+		}
+		return [0.; 4];
+	}
+
+	/// Blends the visible layers into the composite tiles, only where `damage` is.
+	/// Blends the visible layers into `targets` over `damage`, leaving out those at the `excluded` indices.
+	fn encode_composite(&self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, targets: &[Tile], damage: Rect, excluded: &[usize]) {
+		// Target tile, its damaged region, and each layer blended over it with its input
+		let mut blends: Vec<(usize, Rect, &CanvasLayer, &Tile, BoundUniforms)> = Vec::new();
+		for (t, target) in targets.iter().enumerate() {
+			let Some(region) = damage.intersection(target.rect) else {
+				continue;
+			};
+
+			// The bottom layer always goes first, hidden as fully transparent, so the composite is reset
+			let mut first = true;
+			for (i, layer) in self.layers.iter().enumerate() {
+				let shown = layer.visible && !excluded.contains(&i);
+				if !shown && i != 0 {
+					continue;
+				}
+				let input = CompositeInput {
+					origin: region.pos - target.rect.pos,
+					opacity: if shown { layer.opacity } else { 0. },
+					first: first as u32,
+				};
+				first = false;
+				let tiles = match &self.transform_preview {
+					Some(((id, _, _), tiles)) if *id == layer.id => tiles,
+					_ => &layer.tiles,
+				};
+				blends.push((t, region, layer, &tiles[t], self.inputs.bind_composite(ctx, layer, &input)));
+			}
+		}
+
+		let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+			label: Some("Canvas(Composite Pass)"),
+		});
+		for (t, region, layer, tile, input) in blends.iter() {
+			Self::set_composite_layer(&mut pass, &self.pipelines, layer, tile, *t);
+			pass.set_bind_group(0, &targets[*t].binding, &[]);
+			input.set_compute(&mut pass);
+			pass.dispatch_workgroups(region.size.w.div_ceil(8), region.size.h.div_ceil(8), 1);
+		}
+	}
+
+	/// Quantizes the `damage` of the composite into the proof tiles, when proofing.
+	fn encode_proof(&self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, damage: Rect) {
+		let Some((target, tiles)) = &self.proof else {
+			return;
+		};
+		let parts: Vec<(&Tile, &Tile, Rect, BoundUniforms)> = self.composite.iter().zip(tiles).filter_map(|(composite, proof)| {
+			let region = damage.intersection(composite.rect)?;
+			let input = ProofInput {
+				origin: region.pos - composite.rect.pos,
+				tile_pos: composite.rect.pos,
+				target: *target as u32,
+				_pad: 0,
+			};
+			return Some((composite, proof, region, self.inputs.proof.bind(ctx, &input)));
+		}).collect();
+
+		let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+			label: Some("Canvas(Proof Pass)"),
+		});
+		pass.set_pipeline(&self.pipelines.compute[14]);
+		for (composite, proof, region, input) in parts.iter() {
+			pass.set_bind_group(0, &composite.binding, &[]);
+			pass.set_bind_group(1, &proof.binding, &[]);
+			input.set_compute(&mut pass);
+			pass.dispatch_workgroups(region.size.w.div_ceil(8), region.size.h.div_ceil(8), 1);
+		}
+	}
+
+	/// What the viewport simulates the artwork as, None showing it as it is.
+	pub fn proof(&self) -> Option<ProofTarget> {
+		return self.proof.as_ref().map(|(target, _)| *target);
+	}
+
+	/// Shows the artwork as quantized for `target`, or as it is with None.
+	pub fn set_proof(&mut self, ctx: &mut Context, target: Option<ProofTarget>) {
+		if self.proof() == target {
+			return;
+		}
+		self.proof = target.map(|target| (target, match self.proof.take() {
+			Some((_, tiles)) => tiles,
+			None => Self::create_tiles(ctx, &self.pipelines, self.tex_size),
+		}));
+		// Composited again so the proof covers all of it
+		add_damage(&mut self.damage, Rect::new(0, 0, self.tex_size.w, self.tex_size.h));
+	}
+
+	/// Whether strokes and clears go to the mask of the active layer.
+	fn painting_mask(&self) -> bool {
+		self.editing_mask && self.layers[self.active].mask.is_some()
+	}
+
+	/// Saved block layer id of what strokes and clears change.
+	fn target_id(&self) -> u32 {
+		self.layers[self.active].id | if self.painting_mask() { MASK_BLOCK } else { 0 }
+	}
+
+	/// Tiles strokes and clears change, the mask of the active layer while editing it.
+	fn target_tiles(&self) -> &[Tile] {
+		let layer = &self.layers[self.active];
+		match &layer.mask {
+			Some(mask) if self.editing_mask => mask,
+			_ => &layer.tiles,
+		}
+	}
+
+	/// Premultiplied color clears of the target tiles leave, white for masks so all the layer shows.
+	fn target_clear_color(&self) -> [f32; 4] {
+		if self.painting_mask() {
+			return [1.; 4];
+		}
+		return self.clear_color(self.active);
+	}
+
+	/// Tiles of a saved block layer id, None once the layer or its mask is removed.
+	fn block_tiles(&self, id: u32) -> Option<&[Tile]> {
+		let layer = &self.layers[self.layer_index(id & !MASK_BLOCK)?];
+		if id & MASK_BLOCK != 0 {
+			return layer.mask.as_deref();
+		}
+		return Some(&layer.tiles);
+	}
+
+	/// Sets the composite pipeline for `layer`, masked or not, and binds `tile`, its tile `t` or one in its place.
+	fn set_composite_layer<'a>(pass: &mut wgpu::ComputePass<'a>, pipelines: &'a Pipelines, layer: &'a CanvasLayer, tile: &'a Tile, t: usize) {
+		pass.set_pipeline(&pipelines.compute[if layer.mask.is_some() { 13 } else { 8 }]);
+		pass.set_bind_group(1, &tile.binding, &[]);
+		if let Some(mask) = &layer.mask {
+			pass.set_bind_group(2, &mask[t].binding, &[]);
+		}
+	}
+
+	/// Index of the layer `id` refers to, None once it is taken out.
+	pub fn layer_index(&self, id: u32) -> Option<usize> {
+		self.layers.iter().position(|l| l.id == id)
+	}
+
+	/// Grows the damage and notifies the subscribed caches.
+	fn invalidate(&mut self, damaged: Damaged, rect: Rect) {
+		add_damage(&mut self.damage, rect);
+		self.invalidations.notify(damaged, rect);
+	}
+
+	fn document_damage(&mut self) {
+		self.invalidate(Damaged::Document, Rect::new(0, 0, self.tex_size.w, self.tex_size.h));
+	}
+
+	/// Where caches of the layers and composite subscribe to what changes, rather than comparing
+	/// them to the canvas.
+	pub fn invalidations(&mut self) -> &mut Invalidations {
+		&mut self.invalidations
+	}
+
+	/// Id of the layer at `index`, kept as layers are added, removed and moved.
+	pub fn layer_id(&self, index: usize) -> u32 {
+		self.layers[index].id
+	}
+
+	/// New transparent layer above the active one, it becomes the active layer. Returns its index.
+	pub fn add_layer(&mut self, ctx: &mut Context) -> usize {
+		let tiles = Self::create_tiles(ctx, &self.pipelines, self.tex_size);
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Layer Encoder)"),
+		});
+		encode_clear(&mut encoder, ctx, &self.pipelines, &self.inputs, &[0.; 4], &tiles);
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+
+		let layer = CanvasLayer {
+			id: self.next_layer_id,
+			tiles,
+			opacity: 1.,
+			visible: true,
+			opaque: false,
+			mask: None,
+			alpha_locked: false,
+		};
+		self.next_layer_id += 1;
+		self.active += 1;
+		self.layers.insert(self.active, layer);
+		self.document_damage();
+		return self.active;
+	}
+
+	/// Takes the layer at `index` out, refusing to take the last layer.
+	pub fn take_layer(&mut self, index: usize) -> Option<RemovedLayer> {
+		if self.layers.len() == 1 || index >= self.layers.len() {
+			return None;
+		}
+		return Some(self.take_any_layer(index));
+	}
+
+	/// Leaves no layer when taking the last, for undo to put others back right after.
+	pub fn take_any_layer(&mut self, index: usize) -> RemovedLayer {
+		let layer = self.layers.remove(index);
+		if self.active >= index && self.active > 0 {
+			self.active -= 1;
+		}
+		self.document_damage();
+		return RemovedLayer { index, layer };
+	}
+
+	/// Puts a taken layer back at the index it had, the active layer stays the same layer.
+	pub fn restore_layer(&mut self, removed: RemovedLayer) {
+		let index = removed.index.min(self.layers.len());
+		self.layers.insert(index, removed.layer);
+		if index <= self.active && self.layers.len() > 1 {
+			self.active += 1;
+		}
+		self.document_damage();
+	}
+
+	/// Bottom one of the visible layers at `indices` merging them would merge into, None without two to merge.
+	pub fn merge_target(&self, indices: &[usize]) -> Option<usize> {
+		let visible: Vec<usize> = indices.iter().copied().filter(|i| self.layers.get(*i).is_some_and(|l| l.visible)).collect();
+		return (visible.len() > 1).then(|| *visible.iter().min().unwrap());
+	}
+
+	/// Blends the visible layers at `indices` into a new layer in place of the bottom one of them and
+	/// removes the others, the merged layer fully opaque so it looks as before. Hidden layers are left as
+	/// they are. Returns the layers taken out, the bottom one first, none without two layers to merge.
+	pub fn merge_layers(&mut self, ctx: &mut Context, indices: &[usize]) -> Vec<RemovedLayer> {
+		let mut indices: Vec<usize> = indices.iter().copied().filter(|i| self.layers.get(*i).is_some_and(|l| l.visible)).collect();
+		indices.sort_unstable();
+		indices.dedup();
+		if indices.len() < 2 {
+			return Vec::new();
+		}
+
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Merge Encoder)"),
+		});
+		// Blended in the composite tiles, recomposited from the layers right after
+		{
+			let inputs: Vec<BoundUniforms> = indices.iter().enumerate().map(|(k, i)| {
+				let input = CompositeInput { origin: Point { x: 0, y: 0 }, opacity: self.layers[*i].opacity, first: (k == 0) as u32 };
+				return self.inputs.bind_composite(ctx, &self.layers[*i], &input);
+			}).collect();
+			let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+				label: Some("Canvas(Merge Pass)"),
+			});
+			for (t, target) in self.composite.iter().enumerate() {
+				for (i, input) in indices.iter().zip(&inputs) {
+					Self::set_composite_layer(&mut pass, &self.pipelines, &self.layers[*i], &self.layers[*i].tiles[t], t);
+					pass.set_bind_group(0, &target.binding, &[]);
+					input.set_compute(&mut pass);
+					pass.dispatch_workgroups(target.rect.size.w.div_ceil(8), target.rect.size.h.div_ceil(8), 1);
+				}
+			}
+		}
+		// Into new tiles, the bottom layer is kept as it was for undo
+		let bottom = indices[0];
+		let tiles = Self::create_tiles(ctx, &self.pipelines, self.tex_size);
+		for (composite, tile) in self.composite.iter().zip(&tiles) {
+			encoder.copy_texture_to_texture(composite.texture().as_image_copy(), tile.texture().as_image_copy(), tile.extent());
+		}
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+
+		let active_id = self.layers[self.active].id;
+		// The masks are applied to the merged pixels
+		let merged = CanvasLayer {
+			id: self.next_layer_id,
+			tiles,
+			opacity: 1.,
+			visible: true,
+			opaque: self.layers[bottom].opaque,
+			mask: None,
+			alpha_locked: self.layers[bottom].alpha_locked,
+		};
+		self.next_layer_id += 1;
+		let mut removed: Vec<RemovedLayer> = indices[1..].iter().rev().map(|i| RemovedLayer { index: *i, layer: self.layers.remove(*i) }).collect();
+		removed.push(RemovedLayer { index: bottom, layer: std::mem::replace(&mut self.layers[bottom], merged) });
+		removed.reverse();
+		self.active = self.layer_index(active_id).unwrap_or(bottom);
+		self.document_damage();
+		return removed;
+	}
+
+	pub fn set_active(&mut self, index: usize) {
+		if index < self.layers.len() {
+			self.active = index;
+		}
+	}
+
+	pub fn active_layer(&self) -> usize {
+		self.active
+	}
+
+	pub fn layer_count(&self) -> usize {
+		self.layers.len()
+	}
+
+	/// Moves the layer at `from` to `to`, the active layer stays the same layer.
+	pub fn reorder(&mut self, from: usize, to: usize) {
+		if from >= self.layers.len() || to >= self.layers.len() || from == to {
+			return;
+		}
+		let active_id = self.layers[self.active].id;
+		let layer = self.layers.remove(from);
+		self.layers.insert(to, layer);
+		self.active = self.layer_index(active_id).unwrap();
+		self.document_damage();
+	}
+
+	pub fn layer_opacity(&self, index: usize) -> f32 {
+		self.layers[index].opacity
+	}
+
+	pub fn set_layer_opacity(&mut self, index: usize, opacity: f32) {
+		self.layers[index].opacity = opacity.clamp(0., 1.);
+		self.invalidate(Damaged::Composite, Rect::new(0, 0, self.tex_size.w, self.tex_size.h));
+	}
+
+	/// Adds a white mask to the layer at `index`, so all of it still shows. False if it already has one.
+	pub fn add_layer_mask(&mut self, ctx: &mut Context, index: usize) -> bool {
+		if self.layers[index].mask.is_some() {
+			return false;
+		}
+		let mask = Self::create_tiles(ctx, &self.pipelines, self.tex_size);
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Mask Encoder)"),
+		});
+		encode_clear(&mut encoder, ctx, &self.pipelines, &self.inputs, &[1.; 4], &mask);
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+		self.layers[index].mask = Some(mask);
+		self.invalidate(Damaged::Composite, Rect::new(0, 0, self.tex_size.w, self.tex_size.h));
+		return true;
+	}
+
+	/// Drops the mask of the layer at `index`, showing all of it again. False if it had none.
+	pub fn remove_layer_mask(&mut self, index: usize) -> bool {
+		if self.layers[index].mask.take().is_none() {
+			return false;
+		}
+		self.invalidate(Damaged::Composite, Rect::new(0, 0, self.tex_size.w, self.tex_size.h));
+		return true;
+	}
+
+	pub fn has_layer_mask(&self, index: usize) -> bool {
+		self.layers[index].mask.is_some()
+	}
+
+	/// Whether strokes and clears of a layer with a mask change the mask instead of its pixels.
+	pub fn set_editing_mask(&mut self, editing: bool) {
+		self.editing_mask = editing;
+	}
+
+	pub fn editing_mask(&self) -> bool {
+		self.editing_mask
+	}
+
+	/// Strokes on the layer at `index` only change the color of its pixels, not how opaque they are.
+	pub fn set_layer_alpha_locked(&mut self, index: usize, locked: bool) {
+		self.layers[index].alpha_locked = locked;
+	}
+
+	pub fn layer_visible(&self, index: usize) -> bool {
+		self.layers[index].visible
+	}
+
+	pub fn set_layer_visible(&mut self, index: usize, visible: bool) {
+		self.layers[index].visible = visible;
+		self.invalidate(Damaged::Composite, Rect::new(0, 0, self.tex_size.w, self.tex_size.h));
+	}
+
+	/// Document pixels between interpolated stroke points, closer zoomed in so curves stay smooth on screen.
+	fn spline_step(&self) -> f32 {
+		if !self.zoom_smoothing {
+			return SPLINE_STEP;
+		}
+		return (SPLINE_STEP / self.view.scale).max(MIN_SPLINE_STEP);
+	}
+
+	/// Spacing of stamped and jittered dabs, wider zoomed out where closer dabs would land on the same screen pixels.
+	fn dab_spacing(&self) -> f32 {
+		let spacing = self.brush.spacing.max(MIN_STAMP_SPACING);
+		if !self.zoom_smoothing {
+			return spacing;
+		}
+		let screen = MIN_SCREEN_DAB_SPACING / (self.view.scale * self.brush.size.max(1) as f32);
+		return spacing.max(screen.min(MAX_ZOOM_SPACING));
+	}
+
+	pub fn zoom_smoothing(&self) -> bool {
+		self.zoom_smoothing
+	}
+
+	/// Whether stroke interpolation and dab spacing follow the zoom, instead of being the same at every zoom.
+	pub fn set_zoom_smoothing(&mut self, zoom_smoothing: bool) {
+		self.zoom_smoothing = zoom_smoothing;
+	}
+
+	fn current_point(&self) -> StrokePoint {
+		StrokePoint::new(self.mouse_pos.unwrap(), self.pressure, self.stroke_id)
+	}
+
+	/// Queues the points of a Catmull-Rom spline from `b` to `c`, `a` and `d` being the points around them.
+	/// `b` is already queued, so fast cursor moves still get a smooth stroke instead of straight segments.
+	fn queue_spline(&mut self, a: StrokePoint, b: StrokePoint, c: StrokePoint, d: StrokePoint) {
+		let [pa, pb, pc, pd] = [a, b, c, d].map(|p| [p.pos.x as f32, p.pos.y as f32]);
+		let length = ((pc[0] - pb[0]).powi(2) + (pc[1] - pb[1]).powi(2)).sqrt();
+		let steps = ((length / self.spline_step()).ceil() as u32).clamp(1, MAX_SPLINE_STEPS);
+
+		let line = self.line_points.back_mut().unwrap();
+		for i in 1..steps {
+			let t = i as f32 / steps as f32;
+			let (t2, t3) = (t * t, t * t * t);
+			let [x, y] = [0, 1].map(|k| 0.5 * (
+				2. * pb[k]
+				+ (pc[k] - pa[k]) * t
+				+ (2. * pa[k] - 5. * pb[k] + 4. * pc[k] - pd[k]) * t2
+				+ (3. * pb[k] - pa[k] - 3. * pc[k] + pd[k]) * t3
+			));
+			let pressure = b.pressure + (c.pressure - b.pressure) * t;
+			queue_point(line, StrokePoint::new(Point { x: x.round() as i32, y: y.round() as i32 }, pressure, b.stroke));
+		}
+		queue_point(line, c);
+	}
+
+	/// Adds a point to the stroke being drawn, the segment before the previous one can be queued once the next is known.
+	fn stroke_to(&mut self, next: StrokePoint) {
+		let tail = &mut self.stroke_tail;
+		tail.push(next);
+		if tail.len() < 3 {
+			return;
+		}
+		if tail.len() > 4 {
+			tail.remove(0);
+		}
+
+		let n = tail.len();
+		let (a, b, c, d) = (tail[n.saturating_sub(4)], tail[n - 3], tail[n - 2], tail[n - 1]);
+		let a = if n == 3 { b } else { a };
+		self.queue_spline(a, b, c, d);
+	}
+
+	/// `pressure` is the raw device pressure from 0 to 1, put through the pressure curve. None for devices without one.
+	pub fn mouse_pos(&mut self, p: Point, pressure: Option<f32>) {
+		self.mouse_pos = Some(p);
+		self.pressure = pressure.map_or(1., |v| self.pressure_curve.apply(v));
+		if self.mouse_down && !self.line_points.is_empty() {
+			if let Some(pos) = self.pull_pen(p) {
+				self.stroke_to(StrokePoint::new(pos, self.pressure, self.stroke_id));
+				self.log_point(pos, self.pressure);
+			}
+		}
+	}
+
+	/// Moves the pen once the cursor is a string length away from it, dragging it behind. None while it stays.
+	fn pull_pen(&mut self, p: Point) -> Option<Point> {
+		let length = self.brush.stabilizer as f32;
+		let target = [p.x as f32, p.y as f32];
+		let pen = self.pen.get_or_insert(target);
+		let [dx, dy] = [target[0] - pen[0], target[1] - pen[1]];
+		let distance = dx.hypot(dy);
+		if distance <= length {
+			return (length == 0.).then_some(p);
+		}
+		let pull = (distance - length) / distance;
+		*pen = [pen[0] + dx * pull, pen[1] + dy * pull];
+		return Some(Point { x: pen[0].round() as i32, y: pen[1].round() as i32 });
+	}
+
+	pub fn mouse_up(&mut self) {
+		self.mouse_down = false;
+		self.pen = None;
+		if self.line_points.is_empty() {
+			return;
+		}
+
+		// The last segment has no point after it, it ends straight
+		let tail = std::mem::take(&mut self.stroke_tail);
+		match tail.len() {
+			0 => (),
+			1 => queue_point(self.line_points.back_mut().unwrap(), tail[0]),
+			n => {
+				let a = if n > 2 { tail[n - 3] } else { tail[n - 2] };
+				self.queue_spline(a, tail[n - 2], tail[n - 1], tail[n - 1]);
+			}
+		}
+	}
+
+	pub fn mouse_down(&mut self) {
+		self.mouse_down = true;
+		// Masks still holding ids from before the wrap would take the new strokes for old ones
+		if self.stroke_id == MAX_STROKE_ID {
+			self.stroke_tiles.clear();
+		}
+		self.stroke_id = self.stroke_id % MAX_STROKE_ID + 1;
+		let point = self.current_point();
+		self.pen = Some([point.pos.x as f32, point.pos.y as f32]);
+		self.line_points.push_back(VecDeque::from([point]));
+		self.stroke_tail = vec![point];
+		self.log_entry(Logged::Stroke {
+			points: Vec::new(),
+			brush: self.brush,
+			tip: self.tip.clone(),
+			symmetry: self.symmetry,
+			color: self.foreground,
+			erasing: self.erasing,
+		});
+		self.log_point(point.pos, point.pressure);
+	}
+
+	fn log_entry(&mut self, logged: Logged) {
+		self.log.push_back(LogEntry { serial: self.next_serial, target: self.target_id(), undone: false, logged });
+		self.logged.push(self.next_serial);
+		self.next_serial += 1;
+	}
+
+	/// Adds a point to the stroke logged last, forgetting the oldest strokes once there are too many.
+	fn log_point(&mut self, pos: Point, pressure: f32) {
+		if let Some(LogEntry { logged: Logged::Stroke { points, .. }, .. }) = self.log.back_mut() {
+			points.push((pos, pressure));
+			self.logged_points += 1;
+		}
+		while self.logged_points > MAX_LOGGED_POINTS && self.log.len() > 1 {
+			if let Some(LogEntry { logged: Logged::Stroke { points, .. }, .. }) = self.log.pop_front() {
+				self.logged_points -= points.len();
+			}
+		}
+	}
+
+	/// Serials of the strokes and clears logged since the last call, the edit just recorded made them.
+	pub fn take_logged(&mut self) -> Vec<u64> {
+		return std::mem::take(&mut self.logged);
+	}
+
+	/// Leaves the logged strokes and clears with the `serials` out of replays, or puts them back.
+	pub fn set_logged_undone(&mut self, serials: &[u64], undone: bool) {
+		for entry in self.log.iter_mut().filter(|e| serials.contains(&e.serial)) {
+			entry.undone = undone;
+		}
+	}
+
+	fn clear_log(&mut self) {
+		self.log.clear();
+		self.logged.clear();
+		self.logged_points = 0;
+	}
+
+	/// The strokes and clears `render_region` replays, for a document to keep. Undone ones are left
+	/// out, as are strokes stamped with a tip, which only lives on the GPU.
+	pub fn saved_log(&self) -> Vec<SavedEdit> {
+		return self.log.iter()
+			.filter(|e| !e.undone && !matches!(e.logged, Logged::Stroke { tip: Some(_), .. }))
+			.filter_map(|e| Some(SavedEdit {
+				layer: self.layer_index(e.target & !MASK_BLOCK)?,
+				mask: e.target & MASK_BLOCK != 0,
+				logged: e.logged.clone(),
+			}))
+			.collect();
+	}
 
-	line_buff: wgpu::Buffer,
-	line_binding: wgpu::BindGroup,
+	/// Takes over the log a document kept, once its layers are set.
+	pub fn restore_log(&mut self, edits: Vec<SavedEdit>) {
+		self.clear_log();
+		for edit in edits {
+			let Some(layer) = self.layers.get(edit.layer) else {
+				continue;
+			};
+			let target = layer.id | if edit.mask { MASK_BLOCK } else { 0 };
+			if let Logged::Stroke { points, .. } = &edit.logged {
+				self.logged_points += points.len();
+			}
+			self.log.push_back(LogEntry { serial: self.next_serial, target, undone: false, logged: edit.logged });
+			self.next_serial += 1;
+		}
+	}
 
-	line_points: VecDeque<VecDeque<Point>>,
-	mouse_pos: Option<Point>,
-	mouse_down: bool,
-	clear: bool,
-}
+	/// Draws the stroke through `points` right away, bypassing the stabilizer and pressure curve.
+	fn replay_stroke(&mut self, points: &[(Point, f32)]) {
+		let Some((&(first, pressure), rest)) = points.split_first() else {
+			return;
+		};
+		self.mouse_pos = Some(first);
+		self.pressure = pressure;
+		self.mouse_down();
+		for &(p, pressure) in rest {
+			self.stroke_to(StrokePoint::new(p, pressure, self.stroke_id));
+		}
+		self.mouse_up();
+	}
 
-impl components::Component for Canvas {
-	fn generate_pipelines(ctx: &Context) -> Pipelines {
-		let shader = ctx.device.create_shader_module(wgpu::include_wgsl!("shaders/canvas.wgsl"));
+	/// Runs frames until what is queued is drawn and composited, waiting for the GPU after each.
+	fn flush(&mut self, ctx: &mut Context) {
+		for _ in 0..MAX_REPLAY_FRAMES {
+			let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Canvas(Replay Encoder)"),
+			});
+			self.update(&mut encoder, ctx);
+			ctx.staging_belt.finish();
+			ctx.queue.submit(std::iter::once(encoder.finish()));
+			ctx.staging_belt.recall();
+			ctx.buffer_pool.reset();
+			ctx.uniform_pool.reset();
+			ctx.device.poll(wgpu::Maintain::Wait);
+			if self.is_idle() && self.damage.is_none() {
+				return;
+			}
+		}
+		log::warn!("Replayed strokes didn't finish drawing");
+	}
 
-		let binding_group_layout = ctx.device.create_bind_group_layout(
-			&wgpu::BindGroupLayoutDescriptor {
-				label: Some("Canvas(Binding Group Layout)"),
-				entries: &[
-					wgpu::BindGroupLayoutEntry {
-						binding: 0,
-						visibility: wgpu::ShaderStages::COMPUTE,
-						ty: wgpu::BindingType::StorageTexture {
-							access: wgpu::StorageTextureAccess::ReadWrite,
-							format: wgpu::TextureFormat::Rgba8Unorm,
-							view_dimension: wgpu::TextureViewDimension::D2
-						},
-						count: None,
+	/// Starts reading back `region` of the document drawn again `scale` times bigger, from the logged
+	/// strokes and clears that reach it instead of upscaling the pixels. What wasn't painted with
+	/// strokes (fills, pastes, transforms, opened images) and masks are left out, as are the layers
+	/// at the `excluded` indices.
+	pub fn render_region(&self, ctx: &mut Context, region: Rect, scale: u32, excluded: &[usize]) -> Readback {
+		let size = Size { w: region.size.w * scale, h: region.size.h * scale };
+		let mut hires = Canvas::with_size(ctx, size);
+		// Rendered at the size asked for even when it didn't fit at first
+		if hires.tex_size != size {
+			hires.resize_document(ctx, Rect::new(0, 0, size.w, size.h));
+			hires.resized = None;
+		}
+		hires.backgroud = self.backgroud;
+		for layer in &self.layers[1..] {
+			hires.add_layer(ctx);
+			hires.layers[hires.active].opaque = layer.opaque;
+		}
+		for (hires_layer, layer) in hires.layers.iter_mut().zip(&self.layers) {
+			hires_layer.opacity = layer.opacity;
+			hires_layer.visible = layer.visible;
+			hires_layer.alpha_locked = layer.alpha_locked;
+		}
+		hires.flush(ctx);
+
+		// Document pixels become `scale` pixels, strokes go through their centers
+		let to_hires = |p: Point| Point { x: (p.x - region.pos.x) * scale as i32 + scale as i32 / 2, y: (p.y - region.pos.y) * scale as i32 + scale as i32 / 2 };
+		let mut replayed = 0;
+		for entry in self.log.iter().filter(|e| !e.undone && e.target & MASK_BLOCK == 0) {
+			let Some(index) = self.layer_index(entry.target) else {
+				continue;
+			};
+			hires.active = index;
+			match &entry.logged {
+				Logged::Stroke { points, brush, tip, symmetry, color, erasing } => {
+					// Copies of symmetric strokes can be anywhere
+					let reach = (brush.size as f32 * (1. + brush.scatter) / 2.).ceil() as i32 + 1;
+					let near = |&(p, _): &(Point, f32)| p.x + reach >= region.pos.x && p.y + reach >= region.pos.y
+						&& p.x - reach < region.pos.x + region.size.w as i32 && p.y - reach < region.pos.y + region.size.h as i32;
+					if symmetry.is_none() && !points.iter().any(near) {
+						continue;
 					}
-				]
+					hires.set_brush(Brush { size: brush.size * scale, stabilizer: 0, ..*brush });
+					hires.set_brush_tip(ctx, tip.clone());
+					hires.symmetry = symmetry.map(|s| Symmetry {
+						center: [(s.center[0] - region.pos.x as f32) * scale as f32, (s.center[1] - region.pos.y as f32) * scale as f32],
+						..s
+					});
+					hires.foreground = *color;
+					hires.erasing = *erasing;
+					let points: Vec<(Point, f32)> = points.iter().map(|&(p, pressure)| (to_hires(p), pressure)).collect();
+					hires.replay_stroke(&points);
+				}
+				Logged::Clear(color) => {
+					let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+						label: Some("Canvas(Replay Encoder)"),
+					});
+					encode_clear(&mut encoder, ctx, &hires.pipelines, &hires.inputs, color, &hires.layers[index].tiles);
+					ctx.queue.submit(std::iter::once(encoder.finish()));
+					hires.document_damage();
+					add_damage(&mut hires.damage, Rect::new(0, 0, size.w, size.h));
+				}
 			}
-		);
+			hires.flush(ctx);
+			replayed += 1;
+		}
+		log::info!("Replayed {replayed} strokes and clears at {scale}x");
+		return hires.composite_readback_without(ctx, excluded);
+	}
 
-		let line_list_layout = ctx.device.create_bind_group_layout(
-			&wgpu::BindGroupLayoutDescriptor {
-				label: Some("Canvas(Line List Layout)"),
-				entries: &[
-					wgpu::BindGroupLayoutEntry {
-						binding: 0,
-						visibility: wgpu::ShaderStages::COMPUTE,
-						ty: wgpu::BindingType::Buffer {
-							ty: wgpu::BufferBindingType::Storage {
-								read_only: true,
-							},
-							has_dynamic_offset: false,
-							min_binding_size: core::num::NonZeroU64::new(4*2),
-
-						},
-						count: None,
-					}
-				]
+	pub fn clear(&mut self) {
+		self.clear = true;
+	}
+
+	pub fn get_color(&self, slot: ColorSlot) -> [f32; 3] {
+		match slot {
+			ColorSlot::Foreground => self.foreground,
+			ColorSlot::Background => self.backgroud,
+		}
+	}
+
+	/// The background color is used by the next clear, painted pixels keep their color.
+	pub fn set_color(&mut self, slot: ColorSlot, color: [f32; 3]) {
+		match slot {
+			ColorSlot::Foreground => self.foreground = color,
+			ColorSlot::Background => self.backgroud = color,
+		}
+	}
+
+	/// Follows what the other components changed.
+	pub fn handle_event(&mut self, event: &Event) {
+		match *event {
+			Event::ColorChanged(slot, color) => self.set_color(slot, color),
+			Event::ValueChanged(Control::BrushSize, size) => self.set_brush(Brush { size: size as u32, ..self.brush }),
+			Event::ValueChanged(Control::BrushOpacity, percent) => self.set_brush(Brush { opacity: percent / 100., ..self.brush }),
+			// Around the middle of the canvas rect, which the layout zooms
+			Event::ValueChanged(Control::Zoom, _) => (),
+			// Preferences, the preferences window saves them
+			Event::ValueChanged(Control::FpsCap | Control::CanvasWidth | Control::CanvasHeight | Control::AutosaveInterval, _) => (),
+			Event::Pressed(_) | Event::Toggled(..) | Event::Selected(..) | Event::ToolPicked(_) | Event::Refused(_) => (),
+		}
+	}
+
+	pub fn swap_colors(&mut self) {
+		std::mem::swap(&mut self.foreground, &mut self.backgroud);
+	}
+
+	pub fn brush(&self) -> Brush {
+		self.brush
+	}
+
+	pub fn set_brush(&mut self, brush: Brush) {
+		self.brush = Brush {
+			size: brush.size.clamp(MIN_BRUSH_SIZE, MAX_BRUSH_SIZE),
+			hardness: brush.hardness.clamp(0., 1.),
+			spacing: brush.spacing.max(0.),
+			pressure_size: brush.pressure_size.clamp(0., 1.),
+			pressure_opacity: brush.pressure_opacity.clamp(0., 1.),
+			opacity: brush.opacity.clamp(0., 1.),
+			flow: brush.flow.clamp(MIN_FLOW, 1.),
+			stabilizer: brush.stabilizer.min(MAX_STABILIZER),
+			rotation: brush.rotation.rem_euclid(360.),
+			scatter: brush.scatter.max(0.),
+			size_jitter: brush.size_jitter.clamp(0., 1.),
+			hue_jitter: brush.hue_jitter.clamp(0., MAX_ANGLE_JITTER),
+			lightness_jitter: brush.lightness_jitter.clamp(0., 1.),
+			rotation_jitter: brush.rotation_jitter.clamp(0., MAX_ANGLE_JITTER),
+			seed: brush.seed,
+		};
+	}
+
+	pub fn symmetry(&self) -> Option<Symmetry> {
+		self.symmetry
+	}
+
+	/// Paints the strokes from now on with the copies of `symmetry` too.
+	pub fn set_symmetry(&mut self, symmetry: Option<Symmetry>) {
+		self.symmetry = symmetry;
+	}
+
+	pub fn brush_tip(&self) -> Option<Arc<BrushTip>> {
+		self.tip.clone()
+	}
+
+	/// Stamps `tip` along the strokes from now on, round dabs without one.
+	pub fn set_brush_tip(&mut self, ctx: &Context, tip: Option<Arc<BrushTip>>) {
+		if let Some(tip) = &tip {
+			self.bound_tip = tip.clone();
+			let resources = Self::tip_resources(&self.bound_tip, &self.tip_sampler, &self.dynamics, &self.pickup);
+			for stroke in self.stroke_tiles.iter_mut() {
+				stroke.set_tip(ctx, &self.pipelines, &resources);
 			}
-		);
+		}
+		self.tip = tip;
+	}
+
+	pub fn is_erasing(&self) -> bool {
+		self.erasing
+	}
+
+	/// Also applies to stroke points still queued for the next frame.
+	pub fn set_erasing(&mut self, erasing: bool) {
+		self.erasing = erasing;
+	}
 
-		let point_pipeline_layout = ctx.device.create_pipeline_layout(
-			&wgpu::PipelineLayoutDescriptor {
-				label: Some("Canvas(Point Pipeline Layout)"),
-				bind_group_layouts: &[&binding_group_layout],
-				push_constant_ranges: &[
-					wgpu::PushConstantRange {
-						stages: wgpu::ShaderStages::COMPUTE,
-						range: (0..12),
+	pub fn pressure_curve(&self) -> &PressureCurve {
+		&self.pressure_curve
+	}
+
+	pub fn set_pressure_curve(&mut self, curve: PressureCurve) {
+		self.pressure_curve = curve;
+	}
+
+	/// Strokes from now on smear what they go over instead of covering it, what the mixer paints with.
+	pub fn set_smudging(&mut self, smudging: bool) {
+		self.smudging = smudging;
+	}
+
+	pub fn view(&self) -> ViewTransform {
+		self.view
+	}
+
+	pub fn set_view(&mut self, view: ViewTransform) {
+		self.view = view;
+	}
+
+	pub fn size(&self) -> Size {
+		self.tex_size
+	}
+
+	/// Bounding box of the pixels that differ from the background, computed on the GPU.
+	pub fn content_bounds(&self, ctx: &mut Context) -> Option<Rect> {
+		use wgpu::util::DeviceExt;
+
+		if self.clear {
+			return None;
+		}
+
+		let initial: [u32; 4] = [u32::MAX, u32::MAX, 0, 0];
+		let bounds_buff = ctx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+			label: Some("Canvas(Bounds Buffer)"),
+			contents: bytemuck::cast_slice(&initial),
+			usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+		});
+		let readback = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Canvas(Bounds Readback)"),
+			size: std::mem::size_of_val(&initial) as u64,
+			usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		let bounds_binding = ctx.device.create_bind_group(
+			&wgpu::BindGroupDescriptor {
+				label: Some("Canvas(Bounds Binding)"),
+				layout: &self.pipelines.compute[3].get_bind_group_layout(1),
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: bounds_buff.as_entire_binding(),
 					}
 				],
 			}
 		);
 
-		let point_pipeline = ctx.device.create_compute_pipeline(
-			&wgpu::ComputePipelineDescriptor {
-				label: Some("Canvas(Point Pipeline)"),
-				layout: Some(&point_pipeline_layout),
-				module: &shader,
-				entry_point: "draw_point",
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Bounds Encoder)"),
+		});
+
+		{
+			let inputs: Vec<BoundUniforms> = self.composite.iter().map(|tile| {
+				let input = BoundsInput { background: self.backgroud, _pad: 0., tile_origin: tile.rect.pos, _pad2: [0; 2] };
+				return self.inputs.bounds.bind(ctx, &input);
+			}).collect();
+			let mut bounds_pass = encoder.begin_compute_pass(
+				&wgpu::ComputePassDescriptor {
+					label: Some("Canvas(Bounds Pass)"),
+				}
+			);
+
+			bounds_pass.set_pipeline(&self.pipelines.compute[3]);
+			bounds_pass.set_bind_group(1, &bounds_binding, &[]);
+			for (tile, input) in self.composite.iter().zip(&inputs) {
+				bounds_pass.set_bind_group(0, &tile.binding, &[]);
+				input.set_compute(&mut bounds_pass);
+				bounds_pass.dispatch_workgroups((tile.rect.size.w/8)+1, (tile.rect.size.h/8)+1, 1);
 			}
-		);
+		}
 
-		let line_pipeline_layout = ctx.device.create_pipeline_layout(
-			&wgpu::PipelineLayoutDescriptor {
-				label: Some("Canvas(Line Pipeline Layout)"),
-				bind_group_layouts: &[&binding_group_layout, &line_list_layout],
-				push_constant_ranges: &[
-					wgpu::PushConstantRange {
-						stages: wgpu::ShaderStages::COMPUTE,
-						range: (0..9*4),
-					}
-				],
+		encoder.copy_buffer_to_buffer(&bounds_buff, 0, &readback, 0, readback.size());
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+
+		let slice = readback.slice(..);
+		slice.map_async(wgpu::MapMode::Read, |r| r.expect("Could not read back canvas bounds"));
+		ctx.device.poll(wgpu::Maintain::Wait);
+
+		let b: [u32; 4] = bytemuck::cast_slice(&slice.get_mapped_range()).try_into().unwrap();
+		if b[0] > b[2] || b[1] > b[3] {
+			return None;
+		}
+
+		return Some(Rect::new(b[0] as i32, b[1] as i32, b[2] - b[0] + 1, b[3] - b[1] + 1));
+	}
+
+	/// Tiles for a document `rect` of the old one, the old pixels copied where they overlap.
+	fn resized_tiles(&self, ctx: &mut Context, encoder: &mut wgpu::CommandEncoder, old_tiles: &[Tile], rect: Rect, clear: [f32; 4]) -> Vec<Tile> {
+		let tiles = Self::create_tiles(ctx, &self.pipelines, rect.size);
+		encode_clear(encoder, ctx, &self.pipelines, &self.inputs, &clear, &tiles);
+
+		for new in tiles.iter() {
+			for old in old_tiles.iter() {
+				let Some(overlap) = (new.rect + rect.pos).intersection(old.rect) else {
+					continue;
+				};
+
+				let src = overlap.pos - old.rect.pos;
+				let dst = overlap.pos - rect.pos - new.rect.pos;
+				encoder.copy_texture_to_texture(
+					wgpu::ImageCopyTexture {
+						texture: old.texture(),
+						mip_level: 0,
+						origin: wgpu::Origin3d { x: src.x as u32, y: src.y as u32, z: 0 },
+						aspect: wgpu::TextureAspect::All,
+					},
+					wgpu::ImageCopyTexture {
+						texture: new.texture(),
+						mip_level: 0,
+						origin: wgpu::Origin3d { x: dst.x as u32, y: dst.y as u32, z: 0 },
+						aspect: wgpu::TextureAspect::All,
+					},
+					wgpu::Extent3d {
+						width: overlap.size.w,
+						height: overlap.size.h,
+						depth_or_array_layers: 1,
+					},
+				);
+			}
+		}
+		return tiles;
+	}
+
+	/// Makes `rect` (in current document coordinates) the new document, keeping what overlaps
+	/// and filling the rest with the background. The old document is kept for `take_resize`.
+	pub fn resize_document(&mut self, ctx: &mut Context, rect: Rect) {
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Resize Encoder)"),
+		});
+
+		let mut old = Vec::new();
+		for i in 0..self.layers.len() {
+			let tiles = self.resized_tiles(ctx, &mut encoder, &self.layers[i].tiles, rect, self.clear_color(i));
+			let mask = self.layers[i].mask.as_ref().map(|mask| self.resized_tiles(ctx, &mut encoder, mask, rect, [1.; 4]));
+			let layer = &mut self.layers[i];
+			old.push((layer.id, std::mem::replace(&mut layer.tiles, tiles), std::mem::replace(&mut layer.mask, mask)));
+		}
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+
+		// Resizing twice before it's taken goes back to the first one
+		match &mut self.resized {
+			Some(resized) => resized.origin = resized.origin - rect.pos,
+			None => self.resized = Some(DocumentTiles { origin: Point { x: 0, y: 0 } - rect.pos, size: self.tex_size, layers: old }),
+		}
+		self.set_document_size(ctx, rect.size, rect.pos);
+	}
+
+	/// Document from before the resizes since the last call, to undo them with `swap_document`.
+	pub fn take_resize(&mut self) -> Option<DocumentTiles> {
+		self.resized.take()
+	}
+
+	/// Puts the layers of `document` back, returning the current ones to swap them again. Layers
+	/// added since are cleared.
+	pub fn swap_document(&mut self, ctx: &mut Context, document: DocumentTiles) -> DocumentTiles {
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Swap Encoder)"),
+		});
+
+		let mut saved = document.layers;
+		let mut current = Vec::new();
+		for i in 0..self.layers.len() {
+			let (tiles, mask) = match saved.iter().position(|(id, _, _)| *id == self.layers[i].id) {
+				Some(j) => {
+					let (_, tiles, mask) = saved.swap_remove(j);
+					(tiles, mask)
+				}
+				None => {
+					let tiles = Self::create_tiles(ctx, &self.pipelines, document.size);
+					encode_clear(&mut encoder, ctx, &self.pipelines, &self.inputs, &self.clear_color(i), &tiles);
+					(tiles, None)
+				}
+			};
+			// The mask follows the layer, white where it didn't have one
+			let mask = self.layers[i].mask.is_some().then(|| mask.unwrap_or_else(|| {
+				let mask = Self::create_tiles(ctx, &self.pipelines, document.size);
+				encode_clear(&mut encoder, ctx, &self.pipelines, &self.inputs, &[1.; 4], &mask);
+				mask
+			}));
+			let layer = &mut self.layers[i];
+			current.push((layer.id, std::mem::replace(&mut layer.tiles, tiles), std::mem::replace(&mut layer.mask, mask)));
+		}
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+
+		let swapped = DocumentTiles { origin: Point { x: 0, y: 0 } - document.origin, size: self.tex_size, layers: current };
+		self.set_document_size(ctx, document.size, document.origin);
+		return swapped;
+	}
+
+	/// Crops the document to the selection, returns the rect kept, None without a selection.
+	pub fn crop_to_selection(&mut self, ctx: &mut Context) -> Option<Rect> {
+		let selection = self.selection?.intersection(Rect::new(0, 0, self.tex_size.w, self.tex_size.h))?;
+		self.resize_document(ctx, selection);
+		return Some(selection);
+	}
+
+	/// Resizes the document to `size`, placed with `anchored_rect`.
+	pub fn resize_canvas(&mut self, ctx: &mut Context, size: Size, anchor: [f32; 2]) {
+		self.resize_document(ctx, anchored_rect(self.tex_size, size, anchor));
+	}
+
+	/// After the layers were resized, to `size` with the new top left corner at `origin` in the
+	/// old document: recreates what depends on the size and keeps the pixels in place on screen.
+	fn set_document_size(&mut self, ctx: &mut Context, size: Size, origin: Point) {
+		self.selection = None;
+		self.composite = Self::create_tiles(ctx, &self.pipelines, size);
+		if let Some((_, tiles)) = &mut self.proof {
+			*tiles = Self::create_tiles(ctx, &self.pipelines, size);
+		}
+		self.transform_preview = None;
+		self.tex_size = size;
+		self.view.offset[0] += origin.x as f32 * self.view.scale;
+		self.view.offset[1] += origin.y as f32 * self.view.scale;
+
+		// Keep strokes that are still being drawn attached to the same pixels
+		for line in self.line_points.iter_mut() {
+			for p in line.iter_mut() {
+				p.pos = p.pos - origin;
+			}
+		}
+		for p in self.stroke_tail.iter_mut() {
+			p.pos = p.pos - origin;
+		}
+		for entry in self.log.iter_mut() {
+			if let Logged::Stroke { points, symmetry, .. } = &mut entry.logged {
+				for (p, _) in points.iter_mut() {
+					*p = *p - origin;
+				}
+				if let Some(s) = symmetry {
+					s.center = [s.center[0] - origin.x as f32, s.center[1] - origin.y as f32];
+				}
 			}
+		}
+		if let Some(p) = self.mouse_pos.as_mut() {
+			*p = *p - origin;
+		}
+
+		self.invalidate(Damaged::Document, Rect::new(0, 0, size.w, size.h));
+	}
+
+	/// Reads back the color of a document pixel.
+	pub fn pixel_at(&self, ctx: &mut Context, p: Point) -> Option<[u8; 4]> {
+		return Self::read_pixel(ctx, &self.composite, p);
+	}
+
+	fn read_pixel(ctx: &mut Context, tiles: &[Tile], p: Point) -> Option<[u8; 4]> {
+		let tile = tiles.iter().find(|t| t.rect.intersects(Rect { pos: p, size: Size { w: 1, h: 1 } }))?;
+		let local = p - tile.rect.pos;
+
+		let readback = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Canvas(Pixel Readback)"),
+			size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+			usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Pixel Encoder)"),
+		});
+		encoder.copy_texture_to_buffer(
+			wgpu::ImageCopyTexture {
+				texture: tile.texture(),
+				mip_level: 0,
+				origin: wgpu::Origin3d { x: local.x as u32, y: local.y as u32, z: 0 },
+				aspect: wgpu::TextureAspect::All,
+			},
+			wgpu::ImageCopyBuffer {
+				buffer: &readback,
+				layout: wgpu::ImageDataLayout {
+					offset: 0,
+					bytes_per_row: std::num::NonZeroU32::new(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+					rows_per_image: None,
+				},
+			},
+			wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
 		);
+		ctx.queue.submit(std::iter::once(encoder.finish()));
 
-		let line_pipeline = ctx.device.create_compute_pipeline(
-			&wgpu::ComputePipelineDescriptor {
-				label: Some("Canvas(Line Pipeline)"),
-				layout: Some(&line_pipeline_layout),
-				module: &shader,
-				entry_point: "draw_line",
+		let slice = readback.slice(..);
+		slice.map_async(wgpu::MapMode::Read, |r| r.expect("Could not read back canvas pixel"));
+		ctx.device.poll(wgpu::Maintain::Wait);
+
+		let pixel: [u8; 4] = slice.get_mapped_range()[0..4].try_into().unwrap();
+		return Some(pixel);
+	}
+
+	/// Replaces the document with `pixels`, painted on the bottom layer, the other layers cleared.
+	pub fn load_image(&mut self, ctx: &mut Context, pixels: &Pixels) {
+		self.resize_document(ctx, Rect::new(0, 0, pixels.size.w, pixels.size.h));
+
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Load Encoder)"),
+		});
+		for layer in self.layers.iter_mut().skip(1) {
+			encode_clear(&mut encoder, ctx, &self.pipelines, &self.inputs, &[0.; 4], &layer.tiles);
+			layer.mask = None;
+		}
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+
+		Self::write_tiles(ctx, &self.layers[0].tiles, pixels);
+
+		// The first frame would clear it otherwise
+		self.clear = false;
+		self.journal = None;
+		self.resized = None;
+		self.clear_log();
+		self.document_damage();
+	}
+
+	/// Copies the composite into mappable buffers and starts mapping them, without waiting.
+	pub fn composite_readback(&self, ctx: &mut Context) -> Readback {
+		return self.readback(ctx, &self.composite);
+	}
+
+	/// Starts exporting the composite without the layers at the `excluded` indices to `path`, it is
+	/// written once `PendingExport::poll` finds it read back.
+	pub fn export(&self, ctx: &mut Context, path: PathBuf, excluded: &[usize]) -> PendingExport {
+		return PendingExport::new(path, self.composite_readback_without(ctx, excluded));
+	}
+
+	/// Like `composite_readback`, composited again without the layers at the `excluded` indices.
+	pub fn composite_readback_without(&self, ctx: &mut Context, excluded: &[usize]) -> Readback {
+		if excluded.is_empty() {
+			return self.composite_readback(ctx);
+		}
+		let tiles = Self::create_tiles(ctx, &self.pipelines, self.tex_size);
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Export Encoder)"),
+		});
+		self.encode_composite(&mut encoder, ctx, &tiles, Rect::new(0, 0, self.tex_size.w, self.tex_size.h), excluded);
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+		return self.readback(ctx, &tiles);
+	}
+
+	fn readback(&self, ctx: &mut Context, tiles: &[Tile]) -> Readback {
+		return Self::region_readback(ctx, tiles, Rect::new(0, 0, self.tex_size.w, self.tex_size.h));
+	}
+
+	/// Starts reading back the part of `tiles` under `region`, the pixels are the size of the region.
+	fn region_readback(ctx: &mut Context, tiles: &[Tile], region: Rect) -> Readback {
+		let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Readback Encoder)"),
+		});
+
+		let mut buffers = Vec::new();
+		for tile in tiles {
+			let Some(part) = tile.rect.intersection(region) else {
+				continue;
+			};
+			let padded_row = (part.size.w * 4).div_ceil(align) * align;
+			let readback = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+				label: Some("Canvas(Tile Readback)"),
+				size: (padded_row * part.size.h) as u64,
+				usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+				mapped_at_creation: false,
+			});
+
+			let local = part.pos - tile.rect.pos;
+			encoder.copy_texture_to_buffer(
+				wgpu::ImageCopyTexture {
+					texture: tile.texture(),
+					mip_level: 0,
+					origin: wgpu::Origin3d { x: local.x as u32, y: local.y as u32, z: 0 },
+					aspect: wgpu::TextureAspect::All,
+				},
+				wgpu::ImageCopyBuffer {
+					buffer: &readback,
+					layout: wgpu::ImageDataLayout {
+						offset: 0,
+						bytes_per_row: std::num::NonZeroU32::new(padded_row),
+						rows_per_image: None,
+					},
+				},
+				wgpu::Extent3d { width: part.size.w, height: part.size.h, depth_or_array_layers: 1 },
+			);
+			buffers.push((Rect { pos: part.pos - region.pos, size: part.size }, padded_row, readback));
+		}
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+
+		let mapped = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+		for (_, _, buffer) in &buffers {
+			let mapped = mapped.clone();
+			buffer.slice(..).map_async(wgpu::MapMode::Read, move |r| {
+				r.expect("Could not read back canvas");
+				mapped.fetch_add(1, std::sync::atomic::Ordering::Release);
+			});
+		}
+
+		return Readback { size: region.size, buffers, mapped };
+	}
+
+	/// Blocks until the document pixels are read back.
+	pub fn read_pixels(&self, ctx: &mut Context) -> Pixels {
+		let readback = self.composite_readback(ctx);
+		ctx.device.poll(wgpu::Maintain::Wait);
+		return readback.try_finish(ctx).unwrap();
+	}
+
+	/// Starts reading back the pixels of the layer at `index`, without waiting for them.
+	pub fn layer_readback(&self, ctx: &mut Context, index: usize) -> Readback {
+		return self.readback(ctx, &self.layers[index].tiles);
+	}
+
+	/// Starts reading back the mask of the layer at `index`, None without one.
+	pub fn layer_mask_readback(&self, ctx: &mut Context, index: usize) -> Option<Readback> {
+		return Some(self.readback(ctx, self.layers[index].mask.as_ref()?));
+	}
+
+	/// Replaces every layer at once, bottom first, like opening a document. The bottom layer is the only opaque one.
+	pub fn set_layers(&mut self, ctx: &mut Context, size: Size, layers: &[LayerPixels]) {
+		self.layers.truncate(1);
+		self.layers[0].opaque = true;
+		self.layers[0].mask = None;
+		self.layers[0].alpha_locked = false;
+		self.active = 0;
+		self.resize_document(ctx, Rect::new(0, 0, size.w, size.h));
+		for _ in 1..layers.len() {
+			self.add_layer(ctx);
+		}
+
+		for (i, data) in layers.iter().enumerate() {
+			Self::write_tiles(ctx, &self.layers[i].tiles, &data.pixels);
+			if let Some(mask) = &data.mask {
+				self.add_layer_mask(ctx, i);
+				Self::write_tiles(ctx, self.layers[i].mask.as_ref().unwrap(), mask);
+			}
+			self.layers[i].opacity = data.opacity;
+			self.layers[i].visible = data.visible;
+		}
+		self.active = 0;
+		self.clear = false;
+		self.journal = None;
+		self.resized = None;
+		self.clear_log();
+		self.document_damage();
+	}
+
+	/// Uploads straight alpha `pixels` of the document size into the tiles of a layer or mask.
+	fn write_tiles(ctx: &mut Context, tiles: &[Tile], pixels: &Pixels) {
+		let mut premultiplied = pixels.clone();
+		premultiplied.premultiply();
+		for tile in tiles {
+			let offset = (tile.rect.pos.y as u32 * pixels.size.w + tile.rect.pos.x as u32) * 4;
+			ctx.queue.write_texture(
+				tile.texture().as_image_copy(),
+				&premultiplied.data,
+				wgpu::ImageDataLayout {
+					offset: offset as u64,
+					bytes_per_row: std::num::NonZeroU32::new(pixels.size.w * 4),
+					rows_per_image: std::num::NonZeroU32::new(pixels.size.h),
+				},
+				tile.extent(),
+			);
+		}
+	}
+
+	pub fn selection(&self) -> Option<Rect> {
+		self.selection
+	}
+
+	/// Selects `r` clipped to the document, nothing if they don't overlap.
+	pub fn set_selection(&mut self, r: Option<Rect>) {
+		self.selection = r.and_then(|r| r.intersection(Rect::new(0, 0, self.tex_size.w, self.tex_size.h)));
+	}
+
+	pub fn selection_feather(&self) -> f32 {
+		self.selection_feather
+	}
+
+	/// Fades the selection edges in over `feather` pixels, 0 for sharp edges.
+	pub fn set_selection_feather(&mut self, feather: f32) {
+		self.selection_feather = feather.max(0.);
+	}
+
+	/// How much of the document pixel at `p` fills and filters change, 1 without a selection.
+	/// The same weight `selection.wgsl` gives it.
+	pub fn selection_weight(&self, p: Point) -> f32 {
+		let Some(selection) = self.selection else {
+			return 1.;
+		};
+		let smoothstep = |x: f32| {
+			let t = (x / self.selection_feather.max(1.)).clamp(0., 1.);
+			t * t * (3. - 2. * t)
+		};
+		let side = |p: i32, start: i32, len: u32| {
+			let center = p as f32 + 0.5;
+			smoothstep((center - start as f32).min(start as f32 + len as f32 - center) + 0.5)
+		};
+		return side(p.x, selection.pos.x, selection.size.w) * side(p.y, selection.pos.y, selection.size.h);
+	}
+
+	/// Writes the selection for the passes weighted by it, encoded after this.
+	fn write_selection_mask(&self, ctx: &Context) {
+		let (origin, size) = match self.selection {
+			Some(r) => (r.pos, r.size),
+			None => (Point { x: 0, y: 0 }, Size { w: 0, h: 0 }),
+		};
+		let input = SelectionInput {
+			origin,
+			size,
+			feather: self.selection_feather,
+			selected: self.selection.is_some() as u32,
+			_pad: [0; 2],
+		};
+		ctx.queue.write_buffer(&self.selection_mask, 0, bytemuck::bytes_of(&input));
+	}
+
+	/// Document pixels fills and filters can change, the selection or the whole document.
+	fn selected_region(&self) -> Rect {
+		return self.selection.unwrap_or(Rect::new(0, 0, self.tex_size.w, self.tex_size.h));
+	}
+
+	/// Blocks until the selected pixels of the active layer are read back, faded out as much as they
+	/// aren't selected along feathered edges. None without a selection.
+	pub fn read_selection(&self, ctx: &mut Context) -> Option<Pixels> {
+		let selection = self.selection?;
+		let mut pixels = self.read_region(ctx, selection);
+		if self.selection_feather > 0. {
+			for (i, px) in pixels.data.chunks_exact_mut(4).enumerate() {
+				let p = selection.pos + Point { x: (i as u32 % selection.size.w) as i32, y: (i as u32 / selection.size.w) as i32 };
+				px[3] = (px[3] as f32 * self.selection_weight(p)).round() as u8;
+			}
+		}
+		return Some(pixels);
+	}
+
+	/// Blends `color`, or what the active layer is cleared to without one, over the selected pixels of
+	/// the active layer as much as they are selected. Does nothing without a selection.
+	pub fn fill_selection(&mut self, ctx: &mut Context, color: Option<[f32; 3]>) {
+		let Some(selection) = self.selection else {
+			return;
+		};
+		let color = match color {
+			Some([r, g, b]) => [r, g, b, 1.],
+			None => self.clear_color(self.active),
+		};
+
+		self.write_selection_mask(ctx);
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Fill Selection Encoder)"),
+		});
+		self.journal_save(ctx, &mut encoder, self.layers[self.active].id, selection);
+		{
+			let parts: Vec<(&Tile, Rect, BoundUniforms)> = self.layers[self.active].tiles.iter().filter_map(|tile| {
+				let part = selection.intersection(tile.rect)?;
+				let input = ClearInput {
+					color,
+					origin: part.pos - tile.rect.pos,
+					size: part.size,
+					tile_origin: tile.rect.pos,
+					_pad: [0; 2],
+				};
+				return Some((tile, part, self.inputs.clear_selected.bind(ctx, &input)));
+			}).collect();
+			let mut pass = encoder.begin_compute_pass(
+				&wgpu::ComputePassDescriptor {
+					label: Some("Canvas(Fill Selection Pass)"),
+				}
+			);
+			pass.set_pipeline(&self.pipelines.compute[15]);
+			pass.set_bind_group(1, &self.selection_binding, &[]);
+			for (tile, part, input) in parts.iter() {
+				input.set_compute(&mut pass);
+				pass.set_bind_group(0, &tile.binding, &[]);
+				pass.dispatch_workgroups((part.size.w/8)+1, (part.size.h/8)+1, 1);
+			}
+		}
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+		self.invalidate(Damaged::Layer(self.layers[self.active].id), selection);
+	}
+
+	/// Paints a `width` pixels wide border of `color` along the selection edge on the active layer,
+	/// centered on it. Does nothing without a selection.
+	pub fn stroke_selection(&mut self, ctx: &mut Context, color: [f32; 3], width: f32) {
+		let Some(selection) = self.selection else {
+			return;
+		};
+		let document = Rect::new(0, 0, self.tex_size.w, self.tex_size.h);
+		// The edge lies within half the feather of the selection bounds, only pixels that close to it are searched
+		let reach = (width / 2.) as i32 + 1;
+		let inset = reach + (self.selection_feather / 2.).ceil() as i32 + 1;
+		let grown = |r: Rect, by: i32| {
+			let w = r.size.w as i32 + 2 * by;
+			let h = r.size.h as i32 + 2 * by;
+			(w > 0 && h > 0).then(|| Rect::new(r.pos.x - by, r.pos.y - by, w as u32, h as u32))
+		};
+		let Some(outer) = grown(selection, reach).and_then(|r| r.intersection(document)) else {
+			return;
+		};
+		let band = match grown(selection, -inset) {
+			Some(inner) => {
+				let (top, bottom) = (inner.pos.y - outer.pos.y, outer.pos.y + outer.size.h as i32 - inner.pos.y - inner.size.h as i32);
+				let left = inner.pos.x - outer.pos.x;
+				let right = outer.pos.x + outer.size.w as i32 - inner.pos.x - inner.size.w as i32;
+				vec![
+					Rect::new(outer.pos.x, outer.pos.y, outer.size.w, top as u32),
+					Rect::new(outer.pos.x, inner.pos.y + inner.size.h as i32, outer.size.w, bottom as u32),
+					Rect::new(outer.pos.x, inner.pos.y, left as u32, inner.size.h),
+					Rect::new(inner.pos.x + inner.size.w as i32, inner.pos.y, right as u32, inner.size.h),
+				]
+			}
+			None => vec![outer],
+		};
+
+		self.write_selection_mask(ctx);
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Stroke Selection Encoder)"),
+		});
+		self.journal_save(ctx, &mut encoder, self.layers[self.active].id, outer);
+		{
+			let mut parts: Vec<(&Tile, Rect, BoundUniforms)> = Vec::new();
+			for tile in self.layers[self.active].tiles.iter() {
+				for part in band.iter().filter_map(|r| r.intersection(tile.rect)) {
+					let input = OutlineInput {
+						color: [color[0], color[1], color[2], 1.],
+						origin: part.pos - tile.rect.pos,
+						size: part.size,
+						tile_origin: tile.rect.pos,
+						width,
+						_pad: 0.,
+					};
+					parts.push((tile, part, self.inputs.outline.bind(ctx, &input)));
+				}
 			}
-		);
-
-		let clear_pipeline_layout = ctx.device.create_pipeline_layout(
-			&wgpu::PipelineLayoutDescriptor {
-				label: Some("Canvas(Compute Pipeline Layout)"),
-				bind_group_layouts: &[&binding_group_layout],
-				push_constant_ranges: &[
-					wgpu::PushConstantRange {
-						stages: wgpu::ShaderStages::COMPUTE,
-						range: (0..12),
-					}
-				],
+			let mut pass = encoder.begin_compute_pass(
+				&wgpu::ComputePassDescriptor {
+					label: Some("Canvas(Stroke Selection Pass)"),
+				}
+			);
+			pass.set_pipeline(&self.pipelines.compute[16]);
+			pass.set_bind_group(1, &self.selection_binding, &[]);
+			for (tile, part, input) in parts.iter() {
+				pass.set_bind_group(0, &tile.binding, &[]);
+				input.set_compute(&mut pass);
+				pass.dispatch_workgroups((part.size.w/8)+1, (part.size.h/8)+1, 1);
 			}
-		);
+		}
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+		self.invalidate(Damaged::Layer(self.layers[self.active].id), outer);
+	}
 
-		let clear_pipeline = ctx.device.create_compute_pipeline(
-			&wgpu::ComputePipelineDescriptor {
-				label: Some("Canvas(Compute Pipeline)"),
-				layout: Some(&clear_pipeline_layout),
-				module: &shader,
-				entry_point: "clear",
+	/// Blocks until the active layer pixels under `r` are read back, with straight alpha. `r` has to be within the document.
+	pub fn read_region(&self, ctx: &mut Context, r: Rect) -> Pixels {
+		let readback = Self::region_readback(ctx, &self.layers[self.active].tiles, r);
+		ctx.device.poll(wgpu::Maintain::Wait);
+		return readback.try_finish(ctx).unwrap();
+	}
+
+	/// Replaces the active layer pixels from `pos` with straight alpha `pixels`, clipped to the document.
+	/// Opaque layers get them over the color they are cleared to.
+	pub fn write_region(&mut self, ctx: &mut Context, pos: Point, pixels: &Pixels) {
+		let r = Rect { pos, size: pixels.size };
+		let Some(document) = r.intersection(Rect::new(0, 0, self.tex_size.w, self.tex_size.h)) else {
+			return;
+		};
+
+		// Queued writes land before the next submission, the journal has to copy the blocks first
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Region Encoder)"),
+		});
+		self.journal_save(ctx, &mut encoder, self.layers[self.active].id, document);
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+
+		let mut premultiplied = pixels.clone();
+		premultiplied.premultiply();
+		if self.layers[self.active].opaque {
+			let background = self.clear_color(self.active).map(|c| c * 255.);
+			for px in premultiplied.data.chunks_exact_mut(4) {
+				let uncovered = 1. - px[3] as f32 / 255.;
+				for i in 0..3 {
+					px[i] = (px[i] as f32 + background[i] * uncovered).round() as u8;
+				}
+				px[3] = 255;
 			}
-		);
+		}
+		for tile in &self.layers[self.active].tiles {
+			let Some(part) = document.intersection(tile.rect) else {
+				continue;
+			};
+			let offset = ((part.pos.y - pos.y) as u32 * pixels.size.w + (part.pos.x - pos.x) as u32) * 4;
+			let local = part.pos - tile.rect.pos;
+			ctx.queue.write_texture(
+				wgpu::ImageCopyTexture {
+					texture: tile.texture(),
+					mip_level: 0,
+					origin: wgpu::Origin3d { x: local.x as u32, y: local.y as u32, z: 0 },
+					aspect: wgpu::TextureAspect::All,
+				},
+				&premultiplied.data,
+				wgpu::ImageDataLayout {
+					offset: offset as u64,
+					bytes_per_row: std::num::NonZeroU32::new(pixels.size.w * 4),
+					rows_per_image: std::num::NonZeroU32::new(pixels.size.h),
+				},
+				wgpu::Extent3d { width: part.size.w, height: part.size.h, depth_or_array_layers: 1 },
+			);
+		}
+		self.invalidate(Damaged::Layer(self.layers[self.active].id), document);
+	}
 
-		return Pipelines {
-			render: vec![],
-			compute: vec![clear_pipeline, point_pipeline, line_pipeline],
+	/// Clears the active layer pixels under `r` to what the layer is cleared to.
+	pub fn clear_region(&mut self, ctx: &mut Context, r: Rect) {
+		let [r_, g, b, a] = self.clear_color(self.active).map(|c| (c * 255.).round() as u8);
+		let mut pixels = Pixels::new(r.size);
+		for px in pixels.data.chunks_exact_mut(4) {
+			px.copy_from_slice(&[r_, g, b, a]);
+		}
+		self.write_region(ctx, r.pos, &pixels);
+	}
+
+	/// Moves the selected pixels of the active layer by `delta`, leaving cleared pixels behind. The selection follows them.
+	pub fn move_selection(&mut self, ctx: &mut Context, delta: Point) {
+		let Some(selection) = self.selection else {
+			return;
 		};
+		let pixels = self.read_region(ctx, selection);
+		self.clear_region(ctx, selection);
+		self.write_region(ctx, selection.pos + delta, &pixels);
+		self.set_selection(Some(selection + delta));
 	}
-	fn new(ctx: &mut Context) -> Box<Self> {
-		let tex_size = TEX_SIZE;
-		let tex = ctx.device.create_texture(&wgpu::TextureDescriptor {
-			label: Some("Canvas(Texture)"),
-			size: wgpu::Extent3d {
-				width: tex_size.w,
-				height: tex_size.h,
-				depth_or_array_layers: 1,
-			},
-			mip_level_count: 1,
-			sample_count: 1,
-			dimension: wgpu::TextureDimension::D2,
-			format: wgpu::TextureFormat::Rgba8Unorm,
-			usage: wgpu::TextureUsages::STORAGE_BINDING,
-		});
 
-		let pipelines = ctx.get_pipelines::<Self>();
+	/// Fills the pixels of the active layer connected to `seed` with the foreground color, those within
+	/// `tolerance` (per channel) of the seed pixel. Blocks until the fill stops growing. Filling `behind` goes under
+	/// the pixels instead, spreading through those less opaque than the tolerance so line art bounds it untouched.
+	/// The fill stays within the selection, fading out along its feathered edges.
+	pub fn flood_fill(&mut self, ctx: &mut Context, seed: Point, tolerance: f32, behind: bool) {
+		use wgpu::util::DeviceExt;
+
+		let document = Rect::new(0, 0, self.tex_size.w, self.tex_size.h);
+		let Some(seed_pixel) = Self::read_pixel(ctx, &self.layers[self.active].tiles, seed) else {
+			return;
+		};
+		if behind && seed_pixel[3] as f32 / 255. >= 1. - tolerance {
+			return;
+		}
+		if self.selection_weight(seed) <= 0. {
+			return;
+		}
+		// A bit per pixel, MAX_CANVAS_SIDE squared just fits the default binding size limit
+		let mask_size = (document.size.w as u64 * document.size.h as u64).div_ceil(32) * 4;
+		let limits = ctx.device.limits();
+		if mask_size > limits.max_storage_buffer_binding_size as u64 || mask_size > limits.max_buffer_size {
+			log::warn!("Fill mask of {mask_size} bytes is over the limits of the device");
+			ctx.emit(Event::Refused("The document is too big to flood fill on this GPU."));
+			return;
+		}
+		self.write_selection_mask(ctx);
 
-		let line_buff = ctx.device.create_buffer(&wgpu::BufferDescriptor {
-			label: Some("Canvas(Line Buffer)"),
-			size: BUFF_SIZE.into(),
+		let mask = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Canvas(Fill Mask)"),
+			size: mask_size,
 			usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
 			mapped_at_creation: false,
 		});
+		let seed_index = seed.y as u64 * document.size.w as u64 + seed.x as u64;
+		ctx.queue.write_buffer(&mask, seed_index / 32 * 4, bytemuck::bytes_of(&(1u32 << (seed_index % 32))));
+		let changed = ctx.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+			label: Some("Canvas(Fill Changed)"),
+			contents: bytemuck::bytes_of(&0u32),
+			usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+		});
+		let readback = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Canvas(Fill Readback)"),
+			size: 4,
+			usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
 
-		let line_binding = ctx.device.create_bind_group(
+		let fill_binding = ctx.device.create_bind_group(
 			&wgpu::BindGroupDescriptor {
-				label: Some("Canvas(Binding group 1)"),
-				layout: &pipelines.compute[2].get_bind_group_layout(1),
+				label: Some("Canvas(Fill Binding)"),
+				layout: &self.pipelines.compute[9].get_bind_group_layout(1),
 				entries: &[
 					wgpu::BindGroupEntry {
 						binding: 0,
-						resource: line_buff.as_entire_binding(),
-					}
+						resource: mask.as_entire_binding(),
+					},
+					wgpu::BindGroupEntry {
+						binding: 1,
+						resource: changed.as_entire_binding(),
+					},
 				],
 			}
 		);
 
-		let mut image = Image::new(ctx);
-		image.set_texture(ctx, tex);
+		let input = FillInput {
+			seed_color: seed_pixel.map(|c| c as f32 / 255.),
+			tile_origin: Point { x: 0, y: 0 },
+			document: document.size,
+			tolerance,
+			color: pack_rgba8(self.foreground),
+			behind: behind as u32,
+			_pad: 0,
+		};
+		// The same for every sweep and the apply pass
+		let inputs: Vec<BoundUniforms> = self.layers[self.active].tiles.iter().map(|tile| {
+			return self.inputs.fill.bind(ctx, &FillInput { tile_origin: tile.rect.pos, ..input });
+		}).collect();
 
-		Box::new(Self {
-			pipelines,
-			image,
-			tex_size,
+		// Every sweep spreads the fill along whole rows or columns, shapes with a few turns are done in a couple
+		for sweep in 1..=MAX_FILL_SWEEPS {
+			let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+				label: Some("Canvas(Fill Encoder)"),
+			});
+			encoder.clear_buffer(&changed, 0, None);
+			{
+				let mut fill_pass = encoder.begin_compute_pass(
+					&wgpu::ComputePassDescriptor {
+						label: Some("Canvas(Fill Pass)"),
+					}
+				);
+				fill_pass.set_bind_group(1, &fill_binding, &[]);
+				fill_pass.set_bind_group(2, &self.fill_selection_binding, &[]);
+				for (pipeline, rows) in [(9, true), (10, false)] {
+					fill_pass.set_pipeline(&self.pipelines.compute[pipeline]);
+					for (tile, input) in self.layers[self.active].tiles.iter().zip(&inputs) {
+						let lines = if rows { tile.rect.size.h } else { tile.rect.size.w };
+						fill_pass.set_bind_group(0, &tile.binding, &[]);
+						input.set_compute(&mut fill_pass);
+						fill_pass.dispatch_workgroups(lines.div_ceil(64), 1, 1);
+					}
+				}
+			}
+			encoder.copy_buffer_to_buffer(&changed, 0, &readback, 0, 4);
+			ctx.queue.submit(std::iter::once(encoder.finish()));
 
-			line_buff,
-			line_binding,
+			let slice = readback.slice(..);
+			slice.map_async(wgpu::MapMode::Read, |r| r.expect("Could not read back fill progress"));
+			ctx.device.poll(wgpu::Maintain::Wait);
+			let grown: u32 = bytemuck::pod_read_unaligned(&slice.get_mapped_range());
+			readback.unmap();
+			if grown == 0 {
+				break;
+			}
+			if sweep == MAX_FILL_SWEEPS {
+				log::warn!("Fill still growing after {MAX_FILL_SWEEPS} sweeps, it may stop short of the whole area");
+			}
+		}
 
-			brush_radius: BRUSH_RADIUS,
-			backgroud: BACKGROUND_COLOR,
-			line_points: VecDeque::new(),
-			mouse_pos: None,
-			mouse_down: false,
-			clear: true,
-		})
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Fill Encoder)"),
+		});
+		let filled = self.selected_region();
+		self.journal_save(ctx, &mut encoder, self.layers[self.active].id, filled);
+		{
+			let mut fill_pass = encoder.begin_compute_pass(
+				&wgpu::ComputePassDescriptor {
+					label: Some("Canvas(Fill Apply Pass)"),
+				}
+			);
+			fill_pass.set_pipeline(&self.pipelines.compute[11]);
+			fill_pass.set_bind_group(1, &fill_binding, &[]);
+			fill_pass.set_bind_group(2, &self.fill_selection_binding, &[]);
+			for (tile, input) in self.layers[self.active].tiles.iter().zip(&inputs) {
+				fill_pass.set_bind_group(0, &tile.binding, &[]);
+				input.set_compute(&mut fill_pass);
+				fill_pass.dispatch_workgroups((tile.rect.size.w/8)+1, (tile.rect.size.h/8)+1, 1);
+			}
+		}
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+		self.invalidate(Damaged::Layer(self.layers[self.active].id), filled);
 	}
 
-	fn render(&mut self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, output: &wgpu::TextureView, viewport: Rect, _clip_space: Option<Rect>) {
+	/// Swaps every pixel within `tolerance` (per channel) of `from` for `to`, in one pass. Only the
+	/// selection changes, as much as it is selected.
+	pub fn replace_color(&mut self, ctx: &mut Context, from: [f32; 3], to: [f32; 3], tolerance: f32) {
+		let replaced = self.selected_region();
+		self.write_selection_mask(ctx);
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Replace Encoder)"),
+		});
+		self.journal_save(ctx, &mut encoder, self.layers[self.active].id, replaced);
 
-		let tex_view = self.image
-			.get_texture()
-			.as_ref()
-			.unwrap()
-			.create_view(&wgpu::TextureViewDescriptor::default());
+		{
+			let tiles: Vec<(&Tile, BoundUniforms)> = self.layers[self.active].tiles.iter().filter(|t| t.rect.intersects(replaced)).map(|tile| {
+				let input = ReplaceInput { from, tolerance, to, _pad: 0., tile_origin: tile.rect.pos, _pad2: [0; 2] };
+				return (tile, self.inputs.replace.bind(ctx, &input));
+			}).collect();
+			let mut replace_pass = encoder.begin_compute_pass(
+				&wgpu::ComputePassDescriptor {
+					label: Some("Canvas(Replace Pass)"),
+				}
+			);
 
-		let binding_group = ctx.device.create_bind_group(
-			&wgpu::BindGroupDescriptor {
-				label: Some("Canvas(Binding group 0)"),
-				layout: &self.pipelines.compute[0].get_bind_group_layout(0),
-				entries: &[
-					wgpu::BindGroupEntry {
-						binding: 0,
-						resource: wgpu::BindingResource::TextureView(&tex_view),
-					},
-				],
+			replace_pass.set_pipeline(&self.pipelines.compute[5]);
+			replace_pass.set_bind_group(1, &self.selection_binding, &[]);
+			for (tile, input) in tiles.iter() {
+				input.set_compute(&mut replace_pass);
+				replace_pass.set_bind_group(0, &tile.binding, &[]);
+				replace_pass.dispatch_workgroups((tile.rect.size.w/8)+1, (tile.rect.size.h/8)+1, 1);
 			}
-		);
+		}
 
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+		self.invalidate(Damaged::Layer(self.layers[self.active].id), replaced);
+	}
 
-		if self.clear {
-			self.clear = false;
-			let mut clear_pass = encoder.begin_compute_pass(
-				&wgpu::ComputePassDescriptor {
-					label: Some("Canvas(Clear Pass)"),
-				}
-			);
+	/// Maps the corners of `source` (top left, top right, bottom right, bottom left) of the active
+	/// layer to `corners`, resampling it bilinearly, and selects where it lands. Returns false if the
+	/// corners don't make a valid quad.
+	pub fn warp_perspective(&mut self, ctx: &mut Context, source: Rect, corners: [Point; 4]) -> bool {
+		let quad = corners.map(|p| [p.x as f32, p.y as f32]);
+		let Some(square_to_quad) = Mat3::square_to_quad(quad) else {
+			return false;
+		};
+		let to_square = Mat3::scale(1. / source.size.w as f32, 1. / source.size.h as f32) * Mat3::translate(-source.pos.x as f32, -source.pos.y as f32);
+		return self.transform_region(ctx, source, square_to_quad * to_square);
+	}
 
-			clear_pass.set_pipeline(&self.pipelines.compute[0]);
-			clear_pass.set_bind_group(0, &binding_group, &[]);
-			clear_pass.set_push_constants(0, bytemuck::cast_slice(&self.backgroud));
-			clear_pass.dispatch_workgroups((self.tex_size.w/8)+1, (self.tex_size.h/8)+1, 1);
+	/// Moves `source` of the active layer through the affine `transform` of document positions, resampling it
+	/// bilinearly, and selects where it lands. Returns false if `transform` can't be inverted.
+	pub fn transform_region(&mut self, ctx: &mut Context, source: Rect, transform: Mat3) -> bool {
+		let document = Rect::new(0, 0, self.tex_size.w, self.tex_size.h);
+		let Some(landed) = transformed_bounds(source, transform).intersection(document) else {
+			return false;
+		};
+		if transform.inverse().is_none() {
+			return false;
 		}
 
-		if self.line_points.len() > 0 && self.line_points[0].len() > 1 {
+		let changed = landed.union(source);
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Transform Encoder)"),
+		});
+		self.journal_save(ctx, &mut encoder, self.layers[self.active].id, changed);
+		self.encode_transform(ctx, &mut encoder, source, transform, &self.layers[self.active].tiles);
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+		self.invalidate(Damaged::Layer(self.layers[self.active].id), changed);
+		self.selection = Some(landed);
+		return true;
+	}
 
-			// Lines that ended
-			let mut points_computed = 0;
-			let mut i = 0;
+	/// Shows the active layer as `transform_region` would leave it, until set to None. Encoded again only
+	/// when it changes, reusing the tiles of the previous preview.
+	pub fn set_transform_preview(&mut self, ctx: &mut Context, encoder: &mut wgpu::CommandEncoder, preview: Option<(Rect, Mat3)>) {
+		let key = preview.map(|(source, transform)| (self.layers[self.active].id, source, transform));
+		if self.transform_preview.as_ref().map(|(k, _)| *k) == key {
+			return;
+		}
+		let previous = self.transform_preview.take();
+		self.invalidate(Damaged::Composite, Rect::new(0, 0, self.tex_size.w, self.tex_size.h));
+		let Some((_, source, transform)) = key else {
+			return;
+		};
 
-			let mut mapped = ctx.staging_belt.write_buffer(encoder, &self.line_buff, 0, BUFF_SIZE, &ctx.device);
+		let layer = &self.layers[self.active].tiles;
+		let tiles = match previous {
+			Some((_, tiles)) if tiles.iter().map(|t| t.rect).eq(layer.iter().map(|t| t.rect)) => tiles,
+			_ => Self::create_tiles(ctx, &self.pipelines, self.tex_size),
+		};
+		for (tile, copy) in self.layers[self.active].tiles.iter().zip(&tiles) {
+			encoder.copy_texture_to_texture(tile.texture().as_image_copy(), copy.texture().as_image_copy(), tile.extent());
+		}
+		self.encode_transform(ctx, encoder, source, transform, &tiles);
+		self.transform_preview = Some((key.unwrap(), tiles));
+	}
 
-			let mut bundles: VecDeque<(Rect, u32, u32)> = VecDeque::new();
+	/// Resamples `source` of the active layer bilinearly into `targets`, mapping document positions through
+	/// `transform`. What `source` covered is cleared first, so it moves instead of being copied. Does nothing if
+	/// `transform` can't be inverted.
+	fn encode_transform(&self, ctx: &mut Context, encoder: &mut wgpu::CommandEncoder, source: Rect, transform: Mat3, targets: &[Tile]) {
+		let Some(inverse) = transform.inverse() else {
+			return;
+		};
 
-			let mut min_point: Point = self.line_points[0][0];
-			let mut max_point: Point = min_point.clone();
+		// The targets can be the tiles sampled from, so sample from copies of them
+		let sources: Vec<(Rect, wgpu::BindGroup)> = self.layers[self.active].tiles.iter().filter_map(|tile| {
+			let part = source.intersection(tile.rect)?;
+			let extent = wgpu::Extent3d { width: part.size.w, height: part.size.h, depth_or_array_layers: 1 };
+			let copy = ctx.device.create_texture(&wgpu::TextureDescriptor {
+				label: Some("Canvas(Warp Source)"),
+				size: extent,
+				mip_level_count: 1,
+				sample_count: 1,
+				dimension: wgpu::TextureDimension::D2,
+				format: TILE_FORMAT,
+				usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+			});
+			let local = part.pos - tile.rect.pos;
+			encoder.copy_texture_to_texture(
+				wgpu::ImageCopyTexture {
+					texture: tile.texture(),
+					mip_level: 0,
+					origin: wgpu::Origin3d { x: local.x as u32, y: local.y as u32, z: 0 },
+					aspect: wgpu::TextureAspect::All,
+				},
+				copy.as_image_copy(),
+				extent,
+			);
 
-			while points_computed < POINTS_PER_BUFF && i < self.line_points.len() {
-				use std::cmp::{min, max};
+			let view = copy.create_view(&wgpu::TextureViewDescriptor::default());
+			let binding = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+				label: Some("Canvas(Warp Source Binding)"),
+				layout: &self.pipelines.compute[4].get_bind_group_layout(1),
+				entries: &[
+					wgpu::BindGroupEntry {
+						binding: 0,
+						resource: wgpu::BindingResource::TextureView(&view),
+					},
+				],
+			});
+			Some((part, binding))
+		}).collect();
 
-				let mut bundle: (Rect, u32, u32) = (Rect::new(0, 0, 0, 0), 0, 0);
+		encode_clear_rect(encoder, ctx, &self.pipelines, &self.inputs, &self.clear_color(self.active), targets, source);
 
-				if self.line_points[i].len() <= 1 {
-					// Not a viable line
-					break;
-				}
+		// For every source each target tile samples
+		let inputs: Vec<BoundUniforms> = targets.iter().flat_map(|tile| sources.iter().map(move |(src_rect, _)| (tile, src_rect))).map(|(tile, src_rect)| {
+			let input = WarpInput {
+				inverse: inverse.wgsl_columns(),
+				src_origin: src_rect.pos,
+				tile_origin: tile.rect.pos,
+			};
+			return self.inputs.warp.bind(ctx, &input);
+		}).collect();
+		let mut inputs = inputs.iter();
 
-				bundle.1 = points_computed as u32;
+		let mut warp_pass = encoder.begin_compute_pass(
+			&wgpu::ComputePassDescriptor {
+				label: Some("Canvas(Warp Pass)"),
+			}
+		);
 
-				for k in 0..min(POINTS_PER_BUFF - points_computed, self.line_points[i].len()) {
-					let p = &self.line_points[i][k];
+		warp_pass.set_pipeline(&self.pipelines.compute[4]);
+		for tile in targets.iter() {
+			warp_pass.set_bind_group(0, &tile.binding, &[]);
+			for ((_, src_binding), input) in sources.iter().zip(&mut inputs) {
+				warp_pass.set_bind_group(1, src_binding, &[]);
+				input.set_compute(&mut warp_pass);
+				warp_pass.dispatch_workgroups((tile.rect.size.w/8)+1, (tile.rect.size.h/8)+1, 1);
+			}
+		}
+	}
 
-					const P_SIZE: usize = std::mem::size_of::<Point>();
+	/// Crops the document to its content, returns the new document rect if anything changed.
+	pub fn autocrop(&mut self, ctx: &mut Context) -> Option<Rect> {
+		let bounds = self.content_bounds(ctx)?;
+		if bounds == Rect::new(0, 0, self.tex_size.w, self.tex_size.h) {
+			return None;
+		}
 
-					mapped[points_computed*P_SIZE..(points_computed+1)*P_SIZE].copy_from_slice(bytemuck::bytes_of(p));
-					points_computed += 1;
+		self.resize_document(ctx, bounds);
+		return Some(bounds);
+	}
 
-					min_point.x = min(min_point.x, p.x);
-					min_point.y = min(min_point.y, p.y);
+	fn copy_block(&self, ctx: &Context, encoder: &mut wgpu::CommandEncoder, layer: u32, tile: usize, rect: Rect) -> SavedBlock {
+		let extent = wgpu::Extent3d { width: rect.size.w, height: rect.size.h, depth_or_array_layers: 1 };
+		let tex = ctx.device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("Canvas(Saved Block)"),
+			size: extent,
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: TILE_FORMAT,
+			usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+		});
+		encoder.copy_texture_to_texture(
+			wgpu::ImageCopyTexture {
+				texture: self.block_tiles(layer).unwrap()[tile].texture(),
+				mip_level: 0,
+				origin: wgpu::Origin3d { x: rect.pos.x as u32, y: rect.pos.y as u32, z: 0 },
+				aspect: wgpu::TextureAspect::All,
+			},
+			tex.as_image_copy(),
+			extent,
+		);
+		return SavedBlock { layer, tile, rect, pixels: BlockPixels::Texture(tex) };
+	}
 
-					max_point.x = max(max_point.x, p.x);
-					max_point.y = max(max_point.y, p.y);
+	/// Saves the blocks of `layer` (a saved block layer id) under `r` that the edit in progress hasn't touched yet,
+	/// before `encoder` changes them.
+	fn journal_save(&mut self, ctx: &Context, encoder: &mut wgpu::CommandEncoder, layer: u32, r: Rect) {
+		let Some(mut journal) = self.journal.take() else {
+			return;
+		};
+
+		for (i, tile) in self.block_tiles(layer).into_iter().flatten().enumerate() {
+			let Some(local) = r.intersection(tile.rect) else {
+				continue;
+			};
+			let local = Rect { pos: local.pos - tile.rect.pos, size: local.size };
+
+			let (x0, y0) = (local.pos.x as u32 / JOURNAL_BLOCK, local.pos.y as u32 / JOURNAL_BLOCK);
+			let x1 = (local.pos.x as u32 + local.size.w - 1) / JOURNAL_BLOCK;
+			let y1 = (local.pos.y as u32 + local.size.h - 1) / JOURNAL_BLOCK;
+			for by in y0..=y1 {
+				for bx in x0..=x1 {
+					if journal.contains_key(&(layer, i, bx, by)) {
+						continue;
+					}
+					let (x, y) = (bx * JOURNAL_BLOCK, by * JOURNAL_BLOCK);
+					let rect = Rect::new(x as i32, y as i32, JOURNAL_BLOCK.min(tile.rect.size.w - x), JOURNAL_BLOCK.min(tile.rect.size.h - y));
+					journal.insert((layer, i, bx, by), self.copy_block(ctx, encoder, layer, i, rect));
 				}
+			}
+		}
 
-				let size = max_point - min_point;
+		self.journal = Some(journal);
+	}
 
-				bundle.0 = Rect { pos: min_point, size: size.try_into().unwrap() };
-				bundle.2 = points_computed as u32;
+	/// Starts saving what the following strokes, clears and fills change, until `end_edit`.
+	/// GPU memory of the layers, composite and its proof, stroke tiles and the blocks saved by the edit in progress.
+	pub fn memory(&self) -> components::MemoryUsage {
+		let tiles = |tiles: &[Tile]| tiles.iter().map(Tile::bytes).sum::<u64>();
+		// Base and mask of each
+		let strokes: u64 = self.stroke_tiles.iter().map(|s| s.rect.size.w as u64 * s.rect.size.h as u64 * 8).sum();
+		return components::MemoryUsage {
+			layers: self.layers.iter().map(|l| tiles(&l.tiles) + l.mask.as_deref().map_or(0, tiles)).sum(),
+			tiles: tiles(&self.composite) + self.proof.as_ref().map_or(0, |(_, t)| tiles(t)) + strokes + self.transform_preview.as_ref().map_or(0, |(_, t)| tiles(t)),
+			undo: self.journal.iter().flat_map(|j| j.values()).map(SavedBlock::bytes).sum::<u64>() + self.resized.as_ref().map_or(0, DocumentTiles::bytes),
+			..Default::default()
+		};
+	}
 
-				bundles.push_back(bundle);
+	/// Drops the stroke tiles while no stroke needs them, the next stroke creates them again.
+	pub fn release_caches(&mut self) {
+		if self.is_idle() {
+			self.stroke_tiles.clear();
+		}
+	}
 
-				if points_computed == POINTS_PER_BUFF {
-					let f = file!();
-					eprintln!("Buffer filled on this frame. If there was aditional lines to draw they were postponed to the next frame -> Should increase FPS or {f}::POINTS_PER_BUFF");
-					break;
-				}
+	pub fn begin_edit(&mut self) {
+		if self.journal.is_none() {
+			self.journal = Some(HashMap::new());
+		}
+	}
 
-				i += 1;
-			}
+	/// A stroke is being drawn, its button still held.
+	pub fn is_drawing(&self) -> bool {
+		self.mouse_down
+	}
 
-			drop(mapped);
+	/// No stroke or clear is waiting to be drawn.
+	pub fn is_idle(&self) -> bool {
+		!self.mouse_down && !self.clear && self.line_points.is_empty()
+	}
 
-			let mut compute_pass = encoder.begin_compute_pass(
-				&wgpu::ComputePassDescriptor {
-					label: Some("Canvas(Compute Pass)"),
+	/// Finishes the edit, returns the changed blocks as they were before and as they are after
+	/// the commands already in `encoder`. None if nothing changed.
+	pub fn end_edit(&mut self, ctx: &Context, encoder: &mut wgpu::CommandEncoder) -> Option<(Vec<SavedBlock>, Vec<SavedBlock>)> {
+		// Without the blocks of masks removed during the edit
+		let before: Vec<SavedBlock> = self.journal.take()?.into_values().filter(|b| self.block_tiles(b.layer).is_some()).collect();
+		if before.is_empty() {
+			return None;
+		}
+		let after = before.iter().map(|b| self.copy_block(ctx, encoder, b.layer, b.tile, b.rect)).collect();
+		return Some((before, after));
+	}
+
+	/// Copies saved blocks back into the tiles they came from, skipping removed layers.
+	/// Returns the document area they cover, None if none were restored.
+	pub fn restore_blocks(&mut self, ctx: &mut Context, blocks: &[SavedBlock]) -> Option<Rect> {
+		let mut restored: Option<Rect> = None;
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Canvas(Restore Encoder)"),
+		});
+		for block in blocks {
+			let Some(tile) = self.block_tiles(block.layer).map(|tiles| &tiles[block.tile]) else {
+				continue;
+			};
+			let destination = wgpu::ImageCopyTexture {
+				texture: tile.texture(),
+				mip_level: 0,
+				origin: wgpu::Origin3d { x: block.rect.pos.x as u32, y: block.rect.pos.y as u32, z: 0 },
+				aspect: wgpu::TextureAspect::All,
+			};
+			let extent = wgpu::Extent3d { width: block.rect.size.w, height: block.rect.size.h, depth_or_array_layers: 1 };
+			match &block.pixels {
+				BlockPixels::Texture(tex) => encoder.copy_texture_to_texture(tex.as_image_copy(), destination, extent),
+				BlockPixels::Packed(deflated) => {
+					let mut data = Vec::with_capacity((block.rect.size.w * block.rect.size.h * 4) as usize);
+					if let Err(e) = std::io::Read::read_to_end(&mut flate2::read::DeflateDecoder::new(&deflated[..]), &mut data) {
+						log::error!("Could not unpack saved block: {e}");
+						continue;
+					}
+					let layout = wgpu::ImageDataLayout {
+						offset: 0,
+						bytes_per_row: std::num::NonZeroU32::new(block.rect.size.w * 4),
+						rows_per_image: None,
+					};
+					ctx.queue.write_texture(destination, &data, layout, extent);
 				}
-			);
+			}
+			let rect = block.rect + tile.rect.pos;
+			self.invalidate(Damaged::Layer(block.layer & !MASK_BLOCK), rect);
+			restored = Some(restored.map_or(rect, |r| r.union(rect)));
+		}
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+		return restored;
+	}
+
+	/// Thumbnail of the whole document, all of it waiting to be downscaled.
+	pub fn create_thumbnail(&self, ctx: &mut Context) -> LayerThumbnail {
+		let document = self.tex_size;
+		let scale = std::cmp::max(document.w, document.h).div_ceil(THUMB_SIDE).max(1);
+		let size = Size { w: document.w.div_ceil(scale), h: document.h.div_ceil(scale) };
+		let cells = Size { w: size.w.div_ceil(THUMB_CELL), h: size.h.div_ceil(THUMB_CELL) };
 
-			compute_pass.set_pipeline(&self.pipelines.compute[2]);
-			compute_pass.set_bind_group(0, &binding_group, &[]);
-			compute_pass.set_bind_group(1, &self.line_binding, &[]);
-			compute_pass.set_push_constants(4*4, bytemuck::bytes_of(&self.brush_radius));
+		let tex = ctx.device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("Canvas(Thumbnail Texture)"),
+			size: wgpu::Extent3d {
+				width: size.w,
+				height: size.h,
+				depth_or_array_layers: 1,
+			},
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: wgpu::TextureFormat::Rgba8Unorm,
+			usage: wgpu::TextureUsages::STORAGE_BINDING,
+		});
 
+		let binding = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some("Canvas(Thumbnail Binding)"),
+			layout: &self.pipelines.compute[6].get_bind_group_layout(1),
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: wgpu::BindingResource::TextureView(&tex.create_view(&wgpu::TextureViewDescriptor::default())),
+				},
+			],
+		});
 
-			while !bundles.is_empty() {
-				let reference = bundles[0].0.pos - Point {x: self.brush_radius as i32, y: self.brush_radius as i32};
+		let mut image = Image::new(ctx);
+		image.set_texture(ctx, tex);
 
-				compute_pass.set_push_constants(0, bytemuck::bytes_of(&reference));
-				compute_pass.set_push_constants(4*2, bytemuck::bytes_of(&bundles[0].1));
-				compute_pass.set_push_constants(4*3, bytemuck::bytes_of(&bundles[0].2));
+		LayerThumbnail {
+			image,
+			binding,
+			document,
+			scale,
+			size,
+			dirty: vec![true; (cells.w * cells.h) as usize],
+			cells,
+		}
+	}
 
-				let mut drawing_area = bundles[0].0.size.clone();
-				drawing_area.w += 2*self.brush_radius;
-				drawing_area.h += 2*self.brush_radius;
+	/// Downscales up to `THUMB_CELLS_PER_FRAME` dirty cells of the thumbnail of `layer`, after the strokes encoded so far.
+	pub fn update_thumbnail(&self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, layer: usize, thumb: &mut LayerThumbnail) {
+		// Tile, cell size and input of every dispatch, bound before the pass
+		let mut cells: Vec<(&Tile, [u32; 2], BoundUniforms)> = Vec::new();
+		let pending: Vec<usize> = (0..thumb.dirty.len()).filter(|i| thumb.dirty[*i]).take(THUMB_CELLS_PER_FRAME).collect();
+		for i in pending {
+			thumb.dirty[i] = false;
+			let (cx, cy) = (i as u32 % thumb.cells.w, i as u32 / thumb.cells.w);
+			let origin = [cx * THUMB_CELL, cy * THUMB_CELL];
+			let size = [THUMB_CELL.min(thumb.size.w - origin[0]), THUMB_CELL.min(thumb.size.h - origin[1])];
 
+			let cell_rect = Rect::new(
+				(origin[0] * thumb.scale) as i32,
+				(origin[1] * thumb.scale) as i32,
+				size[0] * thumb.scale,
+				size[1] * thumb.scale,
+			);
+			for tile in self.layers[layer].tiles.iter().filter(|t| t.rect.intersects(cell_rect)) {
+				let input = ThumbnailInput {
+					tile_origin: tile.rect.pos,
+					cell_origin: origin,
+					cell_size: size,
+					scale: thumb.scale,
+					_pad: 0,
+				};
+				cells.push((tile, size, self.inputs.thumbnail.bind(ctx, &input)));
+			}
+		}
 
-				compute_pass.dispatch_workgroups(drawing_area.w/8 + 1, drawing_area.h/8 + 1, 1);
+		let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+			label: Some("Canvas(Thumbnail Pass)"),
+		});
+		pass.set_pipeline(&self.pipelines.compute[6]);
+		pass.set_bind_group(1, &thumb.binding, &[]);
+		for (tile, size, input) in cells.iter() {
+			pass.set_bind_group(0, &tile.binding, &[]);
+			input.set_compute(&mut pass);
+			pass.dispatch_workgroups(size[0].div_ceil(8), size[1].div_ceil(8), 1);
+		}
+	}
 
-				let mut to_be_removed = bundles[0].2 - bundles[0].1;
+	pub fn take_damage(&mut self) -> Option<Rect> {
+		return self.composited.take();
+	}
 
-				if self.line_points.len() == 1 && self.mouse_down {
-					to_be_removed -= 1;
+	/// Hash of the layers as they are on the GPU, their pixels, masks, order, opacity and visibility.
+	/// Strokes still queued aren't part of it. Only blocks damaged since the last call are hashed
+	/// again, read back across frames: None until they arrive, ask again then.
+	pub fn content_hash(&mut self, ctx: &mut Context) -> Option<u64> {
+		let blocks = Size { w: self.tex_size.w.div_ceil(HASH_BLOCK), h: self.tex_size.h.div_ceil(HASH_BLOCK) };
+		let document = Rect::new(0, 0, self.tex_size.w, self.tex_size.h);
+		let count = (blocks.w * blocks.h) as usize;
+		if !self.hash_readbacks.is_empty() {
+			ctx.device.poll(wgpu::Maintain::Poll);
+			if !self.hash_readbacks.iter().all(HashReadback::is_ready) {
+				return None;
+			}
+			for readback in std::mem::take(&mut self.hash_readbacks) {
+				let Some(hashes) = self.layer_hashes.get_mut(&readback.layer) else {
+					continue;
+				};
+				let target = if readback.mask { hashes.mask.as_mut() } else { Some(&mut hashes.pixels) };
+				if let Some(target) = target.filter(|t| t.len() == count) {
+					readback.copy_to(target, blocks);
 				}
+			}
+		}
 
-				self.line_points[0].drain(0..(to_be_removed.try_into().unwrap()));
-
-				if self.line_points[0].is_empty() {
-					self.line_points.pop_front();
+		for (damaged, r) in self.invalidations.take(self.hash_subscription) {
+			for (_, hashes) in self.layer_hashes.iter_mut().filter(|(id, _)| damaged.covers_layer(**id)) {
+				add_damage(&mut hashes.dirty, r);
+			}
+		}
+		self.layer_hashes.retain(|id, _| self.layers.iter().any(|l| l.id == *id));
+		for layer in &self.layers {
+			let hashes = self.layer_hashes.entry(layer.id).or_insert(LayerHashes { pixels: Vec::new(), mask: None, dirty: None });
+			if hashes.pixels.len() != count || hashes.mask.as_ref().map(Vec::len) != layer.mask.as_ref().map(|_| count) {
+				hashes.pixels = vec![0; count];
+				hashes.mask = layer.mask.as_ref().map(|_| vec![0; count]);
+				hashes.dirty = Some(document);
+			}
+			if let Some(dirty) = hashes.dirty.take().and_then(|d| d.intersection(document)) {
+				self.hash_readbacks.push(hash_blocks(ctx, &self.pipelines, &self.inputs.hash, (layer.id, false), &layer.tiles, dirty));
+				if let Some(mask) = &layer.mask {
+					self.hash_readbacks.push(hash_blocks(ctx, &self.pipelines, &self.inputs.hash, (layer.id, true), mask, dirty));
 				}
+			}
+		}
+		if !self.hash_readbacks.is_empty() {
+			return None;
+		}
 
-				bundles.pop_front();
+		// FNV-1a over the size and, layer by layer, how it blends and its block hashes
+		let mut hash: u64 = 0xcbf29ce484222325;
+		let mut fold = |values: &[u32]| {
+			for byte in values.iter().flat_map(|v| v.to_le_bytes()) {
+				hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
 			}
+		};
+		fold(&[self.tex_size.w, self.tex_size.h]);
+		for layer in &self.layers {
+			let hashes = &self.layer_hashes[&layer.id];
+			fold(&[layer.opacity.to_bits(), layer.visible as u32, layer.mask.is_some() as u32]);
+			fold(&hashes.pixels);
+			fold(hashes.mask.as_deref().unwrap_or_default());
 		}
+		return Some(hash);
+	}
 
+	/// Keeps the copies of the layers in memory up to date, reading back what changed once the last
+	/// readback arrived. Never waits for the GPU.
+	pub fn update_backup(&mut self, ctx: &mut Context) {
+		let arrived = self.backup_readbacks.iter().all(|r| r.pixels.is_ready(ctx) && r.mask.as_ref().is_none_or(|m| m.is_ready(ctx)));
+		if !arrived {
+			return;
+		}
+		let document = Rect::new(0, 0, self.tex_size.w, self.tex_size.h);
+		for readback in std::mem::take(&mut self.backup_readbacks) {
+			let Some(backup) = self.backups.get_mut(&readback.id) else {
+				continue;
+			};
+			let pixels = readback.pixels.try_finish(ctx).unwrap();
+			let mask = readback.mask.as_ref().map(|m| m.try_finish(ctx).unwrap());
+			if readback.region == document {
+				(backup.pixels, backup.mask) = (pixels, mask);
+			} else if backup.pixels.size == self.tex_size {
+				backup.pixels.paste(readback.region.pos, &pixels);
+				if let (Some(backup), Some(mask)) = (&mut backup.mask, mask) {
+					backup.paste(readback.region.pos, &mask);
+				}
+			}
+		}
 
-		self.image.render(encoder, ctx, output, Rect::new(0, 0, self.tex_size.w, self.tex_size.h), Some(viewport));
+		for (damaged, r) in self.invalidations.take(self.backup_subscription) {
+			for (_, backup) in self.backups.iter_mut().filter(|(id, _)| damaged.covers_layer(**id)) {
+				add_damage(&mut backup.dirty, r);
+			}
+		}
+		self.backups.retain(|id, _| self.layers.iter().any(|l| l.id == *id));
+		for layer in &self.layers {
+			let backup = self.backups.entry(layer.id).or_insert(LayerBackup { pixels: Pixels::new(Size { w: 0, h: 0 }), mask: None, dirty: None });
+			if backup.pixels.size != self.tex_size || backup.mask.is_some() != layer.mask.is_some() {
+				backup.pixels = Pixels::new(Size { w: 0, h: 0 });
+				backup.mask = layer.mask.as_ref().map(|_| Pixels::new(Size { w: 0, h: 0 }));
+				backup.dirty = Some(document);
+			}
+			let Some(region) = backup.dirty.take().and_then(|d| d.intersection(document)) else {
+				continue;
+			};
+			self.backup_readbacks.push(BackupReadback {
+				id: layer.id,
+				region,
+				pixels: Self::region_readback(ctx, &layer.tiles, region),
+				mask: layer.mask.as_ref().map(|m| Self::region_readback(ctx, m, region)),
+			});
+		}
 	}
 
-	fn min_size() -> Option<components::Size> {
-		todo!()
+	/// The layers as last read back by `update_backup`, to rebuild the document from once the GPU
+	/// is lost. Edits still on their way back are missing, None before every layer arrived once.
+	pub fn backup(&self) -> Option<Vec<LayerPixels>> {
+		return self.layers.iter().map(|layer| {
+			let backup = self.backups.get(&layer.id).filter(|b| b.pixels.size == self.tex_size && b.mask.as_ref().is_none_or(|m| m.size == self.tex_size))?;
+			return Some(LayerPixels {
+				pixels: backup.pixels.clone(),
+				opacity: layer.opacity,
+				visible: layer.visible,
+				mask: backup.mask.clone(),
+			});
+		}).collect();
 	}
 }
 
-impl Canvas {
-	pub fn mouse_pos(&mut self, p: Point) {
-		if self.mouse_down && !self.line_points.is_empty() {
-			self.line_points.back_mut().unwrap().push_back(self.mouse_pos.unwrap());
-		}
-		self.mouse_pos = Some(p);
+/// Block hashes of part of a layer on their way from the GPU, see `hash_blocks`.
+struct HashReadback {
+	layer: u32,
+	/// Of the layer mask rather than its pixels
+	mask: bool,
+	/// Block coordinates of the first block, and how many there are across and down
+	first: Point,
+	region_blocks: Size,
+	buffer: wgpu::Buffer,
+	mapped: Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl HashReadback {
+	fn is_ready(&self) -> bool {
+		return self.mapped.load(std::sync::atomic::Ordering::Acquire);
 	}
 
-	pub fn mouse_up(&mut self) {
-		self.mouse_down = false;
-		if !self.line_points.is_empty() {
-			self.line_points.back_mut().unwrap().push_back(self.mouse_pos.unwrap());
+	/// Puts the hashes in their place in `hashes`, a row major hash of each of the `blocks` of the
+	/// document. Only once `is_ready`.
+	fn copy_to(&self, hashes: &mut [u32], blocks: Size) {
+		let data = self.buffer.slice(..).get_mapped_range();
+		let region_hashes: &[u32] = bytemuck::cast_slice(&data);
+		for y in 0..self.region_blocks.h {
+			let src = (y * self.region_blocks.w) as usize;
+			let dst = ((self.first.y as u32 + y) * blocks.w + self.first.x as u32) as usize;
+			hashes[dst..dst + self.region_blocks.w as usize].copy_from_slice(&region_hashes[src..src + self.region_blocks.w as usize]);
 		}
 	}
+}
 
-	pub fn mouse_down(&mut self) {
-		self.mouse_down = true;
-		self.line_points.push_back(VecDeque::new());
-		self.line_points.back_mut().unwrap().push_back(self.mouse_pos.unwrap());
-	}
+/// Starts hashing the blocks of `tiles` `dirty` touches, of the layer with the id and whether they
+/// are its mask in `layer`.
+fn hash_blocks(ctx: &mut Context, pipelines: &Pipelines, inputs: &Uniforms<HashInput>, layer: (u32, bool), tiles: &[Tile], dirty: Rect) -> HashReadback {
+	let first = Point { x: dirty.pos.x / HASH_BLOCK as i32, y: dirty.pos.y / HASH_BLOCK as i32 };
+	let last = Point {
+		x: (dirty.pos.x + dirty.size.w as i32 - 1) / HASH_BLOCK as i32,
+		y: (dirty.pos.y + dirty.size.h as i32 - 1) / HASH_BLOCK as i32,
+	};
+	let region_blocks: Size = (last - first + Point { x: 1, y: 1 }).try_into().unwrap();
+	let region = Rect {
+		pos: Point { x: first.x * HASH_BLOCK as i32, y: first.y * HASH_BLOCK as i32 },
+		size: Size { w: region_blocks.w * HASH_BLOCK, h: region_blocks.h * HASH_BLOCK },
+	};
 
-	pub fn clear(&mut self) {
-		self.clear = true;
+	let bytes = (region_blocks.w * region_blocks.h * 4) as u64;
+	let buffer = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+		label: Some("Canvas(Hash Buffer)"),
+		size: bytes,
+		usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+		mapped_at_creation: false,
+	});
+	let readback = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+		label: Some("Canvas(Hash Readback)"),
+		size: bytes,
+		usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+		mapped_at_creation: false,
+	});
+	let binding = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+		label: Some("Canvas(Hash Binding)"),
+		layout: &pipelines.compute[7].get_bind_group_layout(1),
+		entries: &[
+			wgpu::BindGroupEntry {
+				binding: 0,
+				resource: buffer.as_entire_binding(),
+			},
+		],
+	});
+
+	let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+		label: Some("Canvas(Hash Encoder)"),
+	});
+	{
+		let tiles: Vec<(&Tile, BoundUniforms)> = tiles.iter().filter(|t| t.rect.intersects(region)).map(|tile| {
+			let input = HashInput { tile_origin: tile.rect.pos, region_origin: region.pos, region_blocks };
+			return (tile, inputs.bind(ctx, &input));
+		}).collect();
+		let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+			label: Some("Canvas(Hash Pass)"),
+		});
+		pass.set_pipeline(&pipelines.compute[7]);
+		pass.set_bind_group(1, &binding, &[]);
+		for (tile, input) in tiles.iter() {
+			pass.set_bind_group(0, &tile.binding, &[]);
+			input.set_compute(&mut pass);
+			pass.dispatch_workgroups(region_blocks.w.div_ceil(8), region.size.h.div_ceil(8), 1);
+		}
 	}
+	encoder.copy_buffer_to_buffer(&buffer, 0, &readback, 0, bytes);
+	ctx.queue.submit(std::iter::once(encoder.finish()));
+
+	let mapped = Arc::new(std::sync::atomic::AtomicBool::new(false));
+	let done = mapped.clone();
+	readback.slice(..).map_async(wgpu::MapMode::Read, move |r| {
+		r.expect("Could not read back canvas hashes");
+		done.store(true, std::sync::atomic::Ordering::Release);
+	});
+	return HashReadback { layer: layer.0, mask: layer.1, first, region_blocks, buffer: readback, mapped };
 }
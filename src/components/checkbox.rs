@@ -0,0 +1,106 @@
+use winit::event::VirtualKeyCode;
+
+use crate::components::{widget_focus_ring, widget_frame, widget_text_top, Context, DrawnWidget, Event, Point, Rect, Size, Widget, WidgetImage, WIDGET_BACKGROUND, WIDGET_BORDER, WIDGET_HOVERED, WIDGET_SCALE, WIDGET_TEXT};
+use crate::export::Pixels;
+use crate::font;
+
+/// Labeled box ticked and unticked by clicking, or Space while focused, emitting `Event::Toggled`
+/// with its message.
+pub struct Checkbox {
+	image: WidgetImage<(Size, bool, bool, bool)>,
+	label: String,
+	message: &'static str,
+	checked: bool,
+	hovered: bool,
+	focused: bool,
+}
+
+impl Checkbox {
+	pub fn set_label(&mut self, label: &str, message: &'static str) {
+		self.label = label.to_string();
+		self.message = message;
+		self.image.invalidate();
+	}
+
+	/// Shows `checked` without emitting anything.
+	pub fn set_checked(&mut self, checked: bool) {
+		self.checked = checked;
+	}
+
+	fn toggle(&mut self, ctx: &mut Context) {
+		self.checked = !self.checked;
+		ctx.emit(Event::Toggled(self.message, self.checked));
+	}
+}
+
+impl Widget for Checkbox {
+	fn press(&mut self, ctx: &mut Context, _: Rect, _: Point) -> bool {
+		self.toggle(ctx);
+		return true;
+	}
+
+	fn set_hovered(&mut self, hovered: bool) -> bool {
+		let changed = hovered != self.hovered;
+		self.hovered = hovered;
+		return changed;
+	}
+
+	fn focusable(&self) -> bool {
+		true
+	}
+
+	fn set_focused(&mut self, _: &mut Context, focused: bool) {
+		self.focused = focused;
+	}
+
+	fn key_input(&mut self, ctx: &mut Context, key: VirtualKeyCode) -> bool {
+		if key != VirtualKeyCode::Space {
+			return false;
+		}
+		self.toggle(ctx);
+		return true;
+	}
+}
+
+impl DrawnWidget for Checkbox {
+	/// Size, checked, hover and focus state
+	type State = (Size, bool, bool, bool);
+	const LABEL: &'static str = "Checkbox(Texture)";
+
+	fn with_image(image: WidgetImage<Self::State>) -> Self {
+		Self {
+			image,
+			label: String::new(),
+			message: "",
+			checked: false,
+			hovered: false,
+			focused: false,
+		}
+	}
+
+	fn image(&mut self) -> &mut WidgetImage<Self::State> {
+		&mut self.image
+	}
+
+	fn state(&self, size: Size) -> Option<Self::State> {
+		return Some((size, self.checked, self.hovered, self.focused));
+	}
+
+	fn draw(&self, size: Size, _: &Self::State) -> Pixels {
+		let mut pixels = widget_frame(size, if self.hovered { WIDGET_HOVERED } else { WIDGET_BACKGROUND });
+		let side = font::GLYPH_H * WIDGET_SCALE;
+		let top = widget_text_top(size.h);
+		let left = top.max(2);
+		font::fill(&mut pixels, left, top, side, side, WIDGET_BORDER);
+		if self.checked {
+			font::fill(&mut pixels, left + 3, top + 3, side - 6, side - 6, WIDGET_TEXT);
+		} else {
+			font::fill(&mut pixels, left + 1, top + 1, side - 2, side - 2, WIDGET_BACKGROUND);
+		}
+		font::draw_text(&mut pixels, left + side + font::advance(WIDGET_SCALE), top, &self.label, WIDGET_TEXT, WIDGET_SCALE);
+		if self.focused {
+			widget_focus_ring(&mut pixels);
+		}
+		return pixels;
+	}
+}
@@ -0,0 +1,191 @@
+use crate::components::{self, ColorSlot, Context, Event, Pipelines, Point, Rect, RectViewportClipSpace, Uniforms, Widget};
+use crate::palette;
+
+/// Inner radius of the hue ring, as a fraction of the outer one
+const RING_INNER: f32 = 0.8;
+/// Half the side of the saturation and value square, as a fraction of the ring inner radius
+const SQUARE_HALF: f32 = 0.65;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Part {
+	Ring,
+	Square,
+}
+
+/// Hue ring around a saturation and value square, emitting `Event::ColorChanged` for the foreground
+/// color as it is dragged on.
+pub struct ColorPicker {
+	pipelines: std::sync::Arc<Pipelines>,
+	uniforms: Uniforms<PickerInput>,
+	/// Hue in degrees, saturation and value from 0 to 1
+	hsv: [f32; 3],
+	/// What the press started on, the pointer moves it until released
+	dragging: Option<Part>,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PickerInput {
+	center: [f32; 2],
+	radius: f32,
+	inner_radius: f32,
+	hsv: [f32; 3],
+	srgb_output: u32,
+}
+
+impl ColorPicker {
+	/// Shows `rgb` unless it is already, keeping the hue of grays.
+	pub fn set_color(&mut self, rgb: [f32; 3]) {
+		let shown = palette::hsv_to_rgb(self.hsv);
+		if shown.iter().zip(rgb).any(|(a, b)| (a - b).abs() > 0.5 / 255.) {
+			let hsv = palette::rgb_to_hsv(rgb);
+			self.hsv = if hsv[1] == 0. { [self.hsv[0], 0., hsv[2]] } else { hsv };
+		}
+	}
+
+	fn geometry(viewport: Rect) -> ([f32; 2], f32) {
+		let radius = viewport.size.w.min(viewport.size.h) as f32 / 2.;
+		let center = [viewport.pos.x as f32 + viewport.size.w as f32 / 2., viewport.pos.y as f32 + viewport.size.h as f32 / 2.];
+		return (center, radius);
+	}
+}
+
+impl Widget for ColorPicker {
+	/// Starts dragging the part of the picker under `p`.
+	fn press(&mut self, ctx: &mut Context, viewport: Rect, p: Point) -> bool {
+		let (center, radius) = Self::geometry(viewport);
+		let (dx, dy) = (p.x as f32 - center[0], p.y as f32 - center[1]);
+		let half = radius * RING_INNER * SQUARE_HALF;
+		let distance = dx.hypot(dy);
+		self.dragging = if (radius * RING_INNER..=radius).contains(&distance) {
+			Some(Part::Ring)
+		} else if dx.abs() <= half && dy.abs() <= half {
+			Some(Part::Square)
+		} else {
+			None
+		};
+		return self.drag(ctx, viewport, p);
+	}
+
+	fn drag(&mut self, ctx: &mut Context, viewport: Rect, p: Point) -> bool {
+		let Some(part) = self.dragging else {
+			return false;
+		};
+		let (center, radius) = Self::geometry(viewport);
+		let (dx, dy) = (p.x as f32 - center[0], p.y as f32 - center[1]);
+		let hsv = match part {
+			Part::Ring => [dx.atan2(-dy).to_degrees().rem_euclid(360.), self.hsv[1], self.hsv[2]],
+			Part::Square => {
+				let half = radius * RING_INNER * SQUARE_HALF;
+				[self.hsv[0], (dx / half * 0.5 + 0.5).clamp(0., 1.), (0.5 - dy / half * 0.5).clamp(0., 1.)]
+			}
+		};
+		if hsv == self.hsv {
+			return true;
+		}
+		self.hsv = hsv;
+		ctx.emit(Event::ColorChanged(ColorSlot::Foreground, palette::hsv_to_rgb(hsv)));
+		return true;
+	}
+
+	fn release(&mut self, _: &mut Context) {
+		self.dragging = None;
+	}
+}
+
+impl components::Component for ColorPicker {
+	fn generate_pipelines(ctx: &Context) -> Pipelines {
+		let uniforms = Uniforms::<PickerInput>::new(ctx, wgpu::ShaderStages::FRAGMENT, 0);
+		let shader = ctx.create_shader("ColorPicker(Shader)", include_str!("shaders/colorpicker.wgsl"), uniforms.defines());
+
+		let render_pipeline_layout = ctx.device.create_pipeline_layout(
+			&wgpu::PipelineLayoutDescriptor {
+				label: Some("ColorPicker(Pipeline Layout)"),
+				bind_group_layouts: &uniforms.bind_group_layout().into_iter().collect::<Vec<_>>(),
+				push_constant_ranges: &uniforms.push_constant_ranges(),
+			}
+		);
+
+		let render_pipeline = ctx.device.create_render_pipeline(
+			&wgpu::RenderPipelineDescriptor {
+				label: Some("ColorPicker(Render Pipeline)"),
+				layout: Some(&render_pipeline_layout),
+				vertex: wgpu::VertexState {
+					module: &shader,
+					entry_point: "vs_main",
+					buffers: &[],
+				},
+				fragment: Some(wgpu::FragmentState {
+					module: &shader,
+					entry_point: "fs_main",
+					targets: &[Some(wgpu::ColorTargetState {
+						format: ctx.surface_format,
+						blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+						write_mask: wgpu::ColorWrites::ALL,
+					})],
+				}),
+				primitive: wgpu::PrimitiveState {
+					topology: wgpu::PrimitiveTopology::TriangleList,
+					strip_index_format: None,
+					front_face: wgpu::FrontFace::Ccw,
+					cull_mode: None,
+					polygon_mode: wgpu::PolygonMode::Fill,
+					unclipped_depth: false,
+					conservative: false,
+				},
+				depth_stencil: None,
+				multisample: wgpu::MultisampleState {
+					count: 1,
+					mask: !0,
+					alpha_to_coverage_enabled: false
+				},
+				multiview: None
+			}
+		);
+
+		Pipelines {
+			render: vec![render_pipeline],
+			compute: vec![],
+		}
+	}
+
+	fn new(ctx: &mut Context) -> Box<Self> {
+		Box::new(Self {
+			pipelines: ctx.get_pipelines::<Self>(),
+			uniforms: Uniforms::new(ctx, wgpu::ShaderStages::FRAGMENT, 0),
+			hsv: [0., 0., 0.],
+			dragging: None,
+		})
+	}
+
+	fn render(&mut self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, output: &wgpu::TextureView, viewport: Rect, clip_space: Option<Rect>) {
+		let (center, radius) = Self::geometry(viewport);
+		let input = PickerInput {
+			center,
+			radius,
+			inner_radius: radius * RING_INNER,
+			hsv: [self.hsv[0] / 360., self.hsv[1], self.hsv[2]],
+			srgb_output: ctx.surface_format.describe().srgb as u32,
+		};
+
+		let uniforms = self.uniforms.bind(ctx, &input);
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("ColorPicker(Render Pass)"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: output,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Load,
+					store: true,
+				}
+			})],
+			depth_stencil_attachment: None,
+		});
+
+		render_pass.set_pipeline(&self.pipelines.render[0]);
+		render_pass.set_viewport_rect(viewport);
+		render_pass.set_clipspace_rect(clip_space);
+		uniforms.set(&mut render_pass);
+		render_pass.draw(0..6, 0..1);
+	}
+}
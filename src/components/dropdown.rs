@@ -0,0 +1,155 @@
+use winit::event::VirtualKeyCode;
+
+use crate::components::{widget_focus_ring, widget_frame, widget_text_top, Context, DrawnWidget, Event, Point, Rect, Size, Widget, WidgetImage, WIDGET_BACKGROUND, WIDGET_HOVERED, WIDGET_SCALE, WIDGET_TEXT};
+use crate::export::Pixels;
+use crate::font;
+
+const SELECTED: [u8; 4] = [70, 70, 84, 255];
+
+/// Shows the option picked, and the list of them under it once clicked or Space is pressed while
+/// focused. Picking one, or going up and down with the arrow keys, emits `Event::Selected` with its
+/// message and index; clicking elsewhere or Escape closes the list.
+pub struct Dropdown {
+	image: WidgetImage<(Size, usize, bool, bool, bool)>,
+	options: Vec<String>,
+	message: &'static str,
+	selected: usize,
+	open: bool,
+	hovered: bool,
+	focused: bool,
+}
+
+impl Dropdown {
+	pub fn set_options(&mut self, options: &[&str], message: &'static str) {
+		self.options = options.iter().map(|o| o.to_string()).collect();
+		self.message = message;
+		self.selected = self.selected.min(self.options.len().saturating_sub(1));
+		self.image.invalidate();
+	}
+
+	/// Shows the option at `index` without emitting anything.
+	pub fn set_selected(&mut self, index: usize) {
+		self.selected = index.min(self.options.len().saturating_sub(1));
+	}
+
+	/// Where the dropdown is drawn when closed at `header`: the header, and the options under it
+	/// while open. It is rendered at this rect.
+	pub fn rect(&self, header: Rect) -> Rect {
+		if !self.open {
+			return header;
+		}
+		return Rect::new(header.pos.x, header.pos.y, header.size.w, header.size.h * (self.options.len() as u32 + 1));
+	}
+}
+
+impl Widget for Dropdown {
+	fn press(&mut self, ctx: &mut Context, viewport: Rect, p: Point) -> bool {
+		if !self.open {
+			self.open = true;
+			return true;
+		}
+		let row_h = viewport.size.h / (self.options.len() as u32 + 1);
+		let row = ((p.y - viewport.pos.y) as u32 / row_h.max(1)) as usize;
+		if row > 0 && row <= self.options.len() {
+			self.selected = row - 1;
+			ctx.emit(Event::Selected(self.message, self.selected));
+		}
+		self.open = false;
+		return true;
+	}
+
+	fn set_hovered(&mut self, hovered: bool) -> bool {
+		let changed = hovered != self.hovered;
+		self.hovered = hovered;
+		return changed;
+	}
+
+	fn bounds(&self, rect: Rect) -> Rect {
+		self.rect(rect)
+	}
+
+	fn focusable(&self) -> bool {
+		true
+	}
+
+	fn set_focused(&mut self, _: &mut Context, focused: bool) {
+		if !focused {
+			self.open = false;
+		}
+		self.focused = focused;
+	}
+
+	fn key_input(&mut self, ctx: &mut Context, key: VirtualKeyCode) -> bool {
+		let selected = match key {
+			VirtualKeyCode::Escape if self.open => {
+				self.open = false;
+				return true;
+			}
+			VirtualKeyCode::Space => {
+				self.open = !self.open;
+				return true;
+			}
+			VirtualKeyCode::Up => self.selected.saturating_sub(1),
+			VirtualKeyCode::Down => (self.selected + 1).min(self.options.len().saturating_sub(1)),
+			_ => return false,
+		};
+		if selected != self.selected {
+			self.selected = selected;
+			ctx.emit(Event::Selected(self.message, selected));
+		}
+		return true;
+	}
+}
+
+impl DrawnWidget for Dropdown {
+	/// Size, selection, open, hover and focus state
+	type State = (Size, usize, bool, bool, bool);
+	const LABEL: &'static str = "Dropdown(Texture)";
+
+	fn with_image(image: WidgetImage<Self::State>) -> Self {
+		Self {
+			image,
+			options: Vec::new(),
+			message: "",
+			selected: 0,
+			open: false,
+			hovered: false,
+			focused: false,
+		}
+	}
+
+	fn image(&mut self) -> &mut WidgetImage<Self::State> {
+		&mut self.image
+	}
+
+	fn state(&self, size: Size) -> Option<Self::State> {
+		return Some((size, self.selected, self.open, self.hovered, self.focused));
+	}
+
+	fn draw(&self, size: Size, _: &Self::State) -> Pixels {
+		let rows = if self.open { self.options.len() as u32 + 1 } else { 1 };
+		let row_h = size.h / rows;
+		let mut pixels = widget_frame(size, WIDGET_BACKGROUND);
+		if self.hovered {
+			font::fill(&mut pixels, 1, 1, size.w.saturating_sub(2), row_h.saturating_sub(2), WIDGET_HOVERED);
+		}
+		let left = font::advance(WIDGET_SCALE) / 2;
+		let current = self.options.get(self.selected).map_or("", String::as_str);
+		font::draw_text(&mut pixels, left, widget_text_top(row_h), current, WIDGET_TEXT, WIDGET_SCALE);
+		let marker = if self.open { "^" } else { "V" };
+		font::draw_text(&mut pixels, size.w.saturating_sub(left + font::advance(WIDGET_SCALE)), widget_text_top(row_h), marker, WIDGET_TEXT, WIDGET_SCALE);
+		if self.open {
+			for (i, option) in self.options.iter().enumerate() {
+				let top = (i as u32 + 1) * row_h;
+				if i == self.selected {
+					font::fill(&mut pixels, 1, top, size.w.saturating_sub(2), row_h, SELECTED);
+				}
+				font::draw_text(&mut pixels, left, top + widget_text_top(row_h), option, WIDGET_TEXT, WIDGET_SCALE);
+			}
+		}
+		if self.focused {
+			widget_focus_ring(&mut pixels);
+		}
+		return pixels;
+	}
+}
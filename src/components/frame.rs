@@ -0,0 +1,202 @@
+use crate::components::{self, Context, Pipelines, Rect, RectViewportClipSpace, Size, Uniforms};
+
+/// What the window shows, kept in a texture of the surface format between frames so only the
+/// damaged parts are drawn again. Rendering it copies all of it to the surface, whose contents
+/// aren't kept.
+pub struct Frame {
+	pipelines: std::sync::Arc<Pipelines>,
+	/// Color of the fill pipeline
+	uniforms: Uniforms<[f32; 4]>,
+	/// None until the first `resize`
+	target: Option<(Size, wgpu::Texture, wgpu::BindGroup)>,
+	/// In window pixels, drawn again on the next frame
+	damage: Option<Rect>,
+}
+
+impl Frame {
+	/// Makes the texture again when `size` changed, everything in it is damaged then.
+	pub fn resize(&mut self, ctx: &Context, size: Size) {
+		if self.target.as_ref().is_some_and(|(s, _, _)| *s == size) {
+			return;
+		}
+		let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("Frame(Texture)"),
+			size: wgpu::Extent3d { width: size.w.max(1), height: size.h.max(1), depth_or_array_layers: 1 },
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: ctx.surface_format,
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+		});
+		let binding = ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some("Frame(Binding Group)"),
+			layout: &self.pipelines.render[0].get_bind_group_layout(0),
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::TextureView(&texture.create_view(&wgpu::TextureViewDescriptor::default())),
+				}
+			],
+		});
+		self.target = Some((size, texture, binding));
+		self.damage_all();
+	}
+
+	/// Where components are drawn, None before the first `resize`.
+	pub fn create_view(&self) -> Option<wgpu::TextureView> {
+		return self.target.as_ref().map(|(_, texture, _)| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+	}
+
+	/// Marks `rect` to be drawn again.
+	pub fn damage(&mut self, rect: Rect) {
+		self.damage = Some(self.damage.map_or(rect, |d| d.union(rect)));
+	}
+
+	pub fn damage_all(&mut self) {
+		let size = self.target.as_ref().map_or(Size { w: 0, h: 0 }, |(s, _, _)| *s);
+		self.damage(Rect { pos: components::Point { x: 0, y: 0 }, size });
+	}
+
+	/// What was damaged since the last call, inside the frame.
+	pub fn take_damage(&mut self) -> Option<Rect> {
+		let (size, _, _) = self.target.as_ref()?;
+		return self.damage.take()?.intersection(Rect { pos: components::Point { x: 0, y: 0 }, size: *size });
+	}
+
+	/// Fills `rect` of `output` with `color`, replacing what was there.
+	pub fn fill(&self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, output: &wgpu::TextureView, rect: Rect, color: wgpu::Color) {
+		let color = [color.r, color.g, color.b, color.a].map(|c| c as f32);
+		let uniforms = self.uniforms.bind(ctx, &color);
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("Frame(Fill Pass)"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: output,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Load,
+					store: true,
+				}
+			})],
+			depth_stencil_attachment: None,
+		});
+		render_pass.set_pipeline(&self.pipelines.render[1]);
+		render_pass.set_clipspace_rect(Some(rect));
+		uniforms.set(&mut render_pass);
+		render_pass.draw(0..6, 0..1);
+	}
+}
+
+impl components::Component for Frame {
+	fn generate_pipelines(ctx: &Context) -> Pipelines {
+		let uniforms = Uniforms::<[f32; 4]>::new(ctx, wgpu::ShaderStages::FRAGMENT, 0);
+		let shader = ctx.create_shader("Frame(Shader)", include_str!("shaders/frame.wgsl"), uniforms.defines());
+
+		// Binding 0 of group 0 is the fill color when it comes from a uniform buffer
+		let binding_group_layout = ctx.bind_group_layout("Frame(Binding Group Layout)", &[
+			wgpu::BindGroupLayoutEntry {
+				binding: 1,
+				visibility: wgpu::ShaderStages::FRAGMENT,
+				ty: wgpu::BindingType::Texture {
+					sample_type: wgpu::TextureSampleType::Float { filterable: false },
+					view_dimension: wgpu::TextureViewDimension::D2,
+					multisampled: false,
+				},
+				count: None,
+			}
+		]);
+
+		let copy_layout = ctx.device.create_pipeline_layout(
+			&wgpu::PipelineLayoutDescriptor {
+				label: Some("Frame(Copy Pipeline Layout)"),
+				bind_group_layouts: &[&binding_group_layout],
+				push_constant_ranges: &[],
+			}
+		);
+		let fill_layout = ctx.device.create_pipeline_layout(
+			&wgpu::PipelineLayoutDescriptor {
+				label: Some("Frame(Fill Pipeline Layout)"),
+				bind_group_layouts: &uniforms.bind_group_layout().into_iter().collect::<Vec<_>>(),
+				push_constant_ranges: &uniforms.push_constant_ranges(),
+			}
+		);
+
+		// Both replace what is under them
+		let pipeline = |label, layout, entry_point| ctx.device.create_render_pipeline(
+			&wgpu::RenderPipelineDescriptor {
+				label: Some(label),
+				layout: Some(layout),
+				vertex: wgpu::VertexState {
+					module: &shader,
+					entry_point: "vs_main",
+					buffers: &[],
+				},
+				fragment: Some(wgpu::FragmentState {
+					module: &shader,
+					entry_point,
+					targets: &[Some(wgpu::ColorTargetState {
+						format: ctx.surface_format,
+						blend: None,
+						write_mask: wgpu::ColorWrites::ALL,
+					})],
+				}),
+				primitive: wgpu::PrimitiveState {
+					topology: wgpu::PrimitiveTopology::TriangleList,
+					strip_index_format: None,
+					front_face: wgpu::FrontFace::Ccw,
+					cull_mode: None,
+					polygon_mode: wgpu::PolygonMode::Fill,
+					unclipped_depth: false,
+					conservative: false,
+				},
+				depth_stencil: None,
+				multisample: wgpu::MultisampleState {
+					count: 1,
+					mask: !0,
+					alpha_to_coverage_enabled: false
+				},
+				multiview: None
+			}
+		);
+
+		Pipelines {
+			render: vec![
+				pipeline("Frame(Copy Pipeline)", &copy_layout, "fs_copy"),
+				pipeline("Frame(Fill Pipeline)", &fill_layout, "fs_fill"),
+			],
+			compute: vec![],
+		}
+	}
+
+	fn new(ctx: &mut Context) -> Box<Self> {
+		Box::new(Self {
+			pipelines: ctx.get_pipelines::<Self>(),
+			uniforms: Uniforms::new(ctx, wgpu::ShaderStages::FRAGMENT, 0),
+			target: None,
+			damage: None,
+		})
+	}
+
+	/// Copies the whole frame to `output`, which has to be as big.
+	fn render(&mut self, encoder: &mut wgpu::CommandEncoder, _: &mut Context, output: &wgpu::TextureView, viewport: Rect, clip_space: Option<Rect>) {
+		let Some((_, _, binding)) = &self.target else {
+			return;
+		};
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("Frame(Copy Pass)"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: output,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Load,
+					store: true,
+				}
+			})],
+			depth_stencil_attachment: None,
+		});
+		render_pass.set_pipeline(&self.pipelines.render[0]);
+		render_pass.set_viewport_rect(viewport);
+		render_pass.set_clipspace_rect(clip_space);
+		render_pass.set_bind_group(0, binding, &[]);
+		render_pass.draw(0..6, 0..1);
+	}
+}
@@ -1,54 +1,128 @@
-use crate::components::{self, Rect, Context, Pipelines, RectViewportClipSpace};
+use crate::components::{self, Rect, Context, Pipelines, RectViewportClipSpace, Uniforms};
 
 pub struct Image {
 	pipelines: std::sync::Arc<Pipelines>,
+	uniforms: Uniforms<ImageInput>,
 	tex: Option<wgpu::Texture>,
+	/// The texture holds rgba8 packed in r32uint, as canvas tiles do
+	packed: bool,
 	binding_group: Option<wgpu::BindGroup>,
+	/// Part of the texture drawn, None for all of it
+	source: Option<Rect>,
+	/// Multiplies the texture alpha, 1 by default
+	pub opacity: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ImageInput {
+	source_origin: [i32; 2],
+	source_size: [u32; 2],
+	srgb_output: u32,
+	opacity: f32,
 }
 
 impl components::Component for Image {
 	fn generate_pipelines(ctx: &Context) -> Pipelines {
-		let shader = ctx.device.create_shader_module(wgpu::include_wgsl!("shaders/image.wgsl"));
+		let uniforms = Uniforms::<ImageInput>::new(ctx, wgpu::ShaderStages::FRAGMENT, 1);
+		// Rgba8 textures and packed ones
+		let render = [(wgpu::TextureFormat::Rgba8Unorm, &[][..]), (wgpu::TextureFormat::R32Uint, &["PACKED"][..])].map(|(format, packed)| {
+			let defines: Vec<&str> = uniforms.defines().iter().chain(packed).copied().collect();
+			let shader = ctx.create_shader("Image(Shader)", include_str!("shaders/image.wgsl"), &defines);
+			return Self::render_pipeline(ctx, &uniforms, &shader, format);
+		});
 
-		let binding_group_layout = ctx.device.create_bind_group_layout(
-			&wgpu::BindGroupLayoutDescriptor {
-				label: Some("Image(Binding Group Layout)"),
-				entries: &[
-					wgpu::BindGroupLayoutEntry {
-						binding: 0,
-						visibility: wgpu::ShaderStages::FRAGMENT,
-						ty: wgpu::BindingType::StorageTexture {
-							access: wgpu::StorageTextureAccess::ReadOnly,
-							format: wgpu::TextureFormat::Rgba8Unorm,
-							view_dimension: wgpu::TextureViewDimension::D2
-						},
-						count: None,
-					}
-				]
+		Pipelines {
+			render: render.into(),
+			compute: vec![],
+		}
+	}
+
+	fn new(ctx: &mut Context) -> Box<Self> {
+		Box::new(Self {
+			pipelines: ctx.get_pipelines::<Self>(),
+			uniforms: Uniforms::new(ctx, wgpu::ShaderStages::FRAGMENT, 1),
+			tex: None,
+			packed: false,
+			binding_group: None,
+			source: None,
+			opacity: 1.,
+		})
+
+	}
+
+	fn render(&mut self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, output: & wgpu::TextureView, viewport: Rect, clip_space: Option<Rect>) {
+		// Texture contents are sRGB encoded, sRGB surfaces expect linear values
+		let source = self.source.unwrap_or(Rect::new(0, 0, 0, 0));
+		let input = ImageInput {
+			source_origin: [source.pos.x, source.pos.y],
+			source_size: [source.size.w, source.size.h],
+			srgb_output: ctx.surface_format.describe().srgb as u32,
+			opacity: self.opacity,
+		};
+		let uniforms = self.uniforms.bind(ctx, &input);
+
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("Image(Render Pass)"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: output,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Load,
+					store: true,
+				}
+			})],
+			depth_stencil_attachment: None,
+		});
+
+		render_pass.set_pipeline(&self.pipelines.render[self.packed as usize]);
+		render_pass.set_viewport_rect(viewport);
+		render_pass.set_clipspace_rect(clip_space);
+		let binding = self.binding_group.as_ref().expect("Trying to render Image with no texture");
+		render_pass.set_bind_group(0, binding, &[]);
+		uniforms.set(&mut render_pass);
+		render_pass.draw(0..6, 0..1);
+
+		drop(render_pass)
+	}
+}
+
+impl Image {
+	/// Draws textures of `format` with `shader`, preprocessed for them.
+	fn render_pipeline(ctx: &Context, uniforms: &Uniforms<ImageInput>, shader: &wgpu::ShaderModule, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+		let binding_group_layout = ctx.bind_group_layout("Image(Binding Group Layout)", &[
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: wgpu::ShaderStages::FRAGMENT,
+				ty: wgpu::BindingType::StorageTexture {
+					access: wgpu::StorageTextureAccess::ReadOnly,
+					format,
+					view_dimension: wgpu::TextureViewDimension::D2
+				},
+				count: None,
 			}
-		);
+		]);
 
 		let render_pipeline_layout = ctx.device.create_pipeline_layout(
 			&wgpu::PipelineLayoutDescriptor {
 				label: Some("Image(Pipeline Layout)"),
-				bind_group_layouts: &[&binding_group_layout],
-
-				push_constant_ranges: &[],
+				bind_group_layouts: &std::iter::once(&*binding_group_layout).chain(uniforms.bind_group_layout()).collect::<Vec<_>>(),
+				push_constant_ranges: &uniforms.push_constant_ranges(),
 			}
 		);
 
 
-		let render_pipeline = ctx.device.create_render_pipeline(
+		return ctx.device.create_render_pipeline(
 			&wgpu::RenderPipelineDescriptor {
 				label: Some("Image(Render Pipeline)"),
 				layout: Some(&render_pipeline_layout),
 				vertex: wgpu::VertexState {
-					module: &shader,
+					module: shader,
 					entry_point: "vs_main",
 					buffers: &[],
 				},
 				fragment: Some(wgpu::FragmentState {
-					module: &shader,
+					module: shader,
 					entry_point: "fs_main",
 					targets: &[Some(wgpu::ColorTargetState {
 						format: ctx.surface_format,
@@ -74,60 +148,29 @@ impl components::Component for Image {
 				multiview: None
 			}
 		);
-
-		Pipelines {
-			render: vec![render_pipeline],
-			compute: vec![],
-		}
 	}
 
-
-	fn new(ctx: &mut Context) -> Box<Self> {
-		Box::new(Self {
-			pipelines: ctx.get_pipelines::<Self>(),
-			tex: None,
-			binding_group: None,
-		})
-
+	pub fn get_texture(&self) -> &Option<wgpu::Texture> {
+		&self.tex
 	}
 
-	fn render(&mut self, encoder: &mut wgpu::CommandEncoder, _: &mut Context, output: & wgpu::TextureView, viewport: Rect, clip_space: Option<Rect>) {
-		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-			label: Some("Image(Render Pass)"),
-			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-				view: &output,
-				resolve_target: None,
-				ops: wgpu::Operations {
-					load: wgpu::LoadOp::Load,
-					store: true,
-				}
-			})],
-			depth_stencil_attachment: None,
-		});
-
-		render_pass.set_pipeline(&self.pipelines.render[0]);
-		render_pass.set_viewport_rect(viewport);
-		render_pass.set_clipspace_rect(clip_space);
-		let binding = self.binding_group.as_ref().expect("Trying to render Image with no texture");
-		render_pass.set_bind_group(0, &binding, &[]);
-		render_pass.draw(0..6, 0..1);
-
-		drop(render_pass)
+	/// Draws only `source` of the texture over the viewport, all of it when None.
+	pub fn set_source(&mut self, source: Option<Rect>) {
+		self.source = source;
 	}
 
-
-	fn min_size() -> Option<components::Size> {
-		todo!()
+	pub fn set_texture(&mut self, ctx: &Context, tex: wgpu::Texture) {
+		self.bind_texture(ctx, tex, false);
 	}
-}
 
-impl Image {
-	pub fn get_texture(&self) -> &Option<wgpu::Texture> {
-		&self.tex
+	/// Like `set_texture`, for an r32uint texture holding rgba8 texels.
+	pub fn set_packed_texture(&mut self, ctx: &Context, tex: wgpu::Texture) {
+		self.bind_texture(ctx, tex, true);
 	}
 
-	pub fn set_texture(&mut self, ctx: &Context, tex: wgpu::Texture) {
+	fn bind_texture(&mut self, ctx: &Context, tex: wgpu::Texture, packed: bool) {
 		self.tex = Some(tex);
+		self.packed = packed;
 
 		let tex_view = self.tex
 			.as_ref()
@@ -137,7 +180,7 @@ impl Image {
 		let binding_group = ctx.device.create_bind_group(
 			&wgpu::BindGroupDescriptor {
 				label: Some("Image(Binding group 0)"),
-				layout: &self.pipelines.render[0].get_bind_group_layout(0),
+				layout: &self.pipelines.render[packed as usize].get_bind_group_layout(0),
 				entries: &[
 					wgpu::BindGroupEntry {
 						binding: 0,
@@ -0,0 +1,71 @@
+use crate::components::Rect;
+
+/// What an invalidation changed, rects are in document coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Damaged {
+	/// Pixels of the layer with this id, and the composite over them
+	Layer(u32),
+	/// Only how the layers blend, after an opacity or visibility change
+	Composite,
+	/// Every layer, after they were added, removed, reordered or replaced
+	Document,
+}
+
+impl Damaged {
+	/// Whether cached copies of the layer `id` are out of date.
+	pub fn covers_layer(&self, id: u32) -> bool {
+		match self {
+			Damaged::Layer(layer) => *layer == id,
+			Damaged::Composite => false,
+			Damaged::Document => true,
+		}
+	}
+}
+
+/// Handle of a cache subscribed to an `Invalidations`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Subscription(usize);
+
+/// Dirty notifications of the canvas, kept for each cache subscribed until it takes them. A rect
+/// per kind of damage, grown by the ones notified after it.
+pub struct Invalidations {
+	/// By subscription, None once unsubscribed
+	pending: Vec<Option<Vec<(Damaged, Rect)>>>,
+}
+
+impl Invalidations {
+	pub fn new() -> Self {
+		Self { pending: Vec::new() }
+	}
+
+	/// Starts keeping notifications for a new cache, it gets the ones after this.
+	pub fn subscribe(&mut self) -> Subscription {
+		if let Some(free) = self.pending.iter().position(Option::is_none) {
+			self.pending[free] = Some(Vec::new());
+			return Subscription(free);
+		}
+		self.pending.push(Some(Vec::new()));
+		return Subscription(self.pending.len() - 1);
+	}
+
+	/// Stops keeping notifications for a cache that is gone, its handle may be given out again.
+	pub fn unsubscribe(&mut self, subscription: Subscription) {
+		if let Some(pending) = self.pending.get_mut(subscription.0) {
+			*pending = None;
+		}
+	}
+
+	pub fn notify(&mut self, damaged: Damaged, rect: Rect) {
+		for pending in self.pending.iter_mut().flatten() {
+			match pending.iter_mut().find(|(d, _)| *d == damaged) {
+				Some((_, r)) => *r = r.union(rect),
+				None => pending.push((damaged, rect)),
+			}
+		}
+	}
+
+	/// What was notified since the last call with `subscription`.
+	pub fn take(&mut self, subscription: Subscription) -> Vec<(Damaged, Rect)> {
+		return self.pending.get_mut(subscription.0).and_then(Option::as_mut).map(std::mem::take).unwrap_or_default();
+	}
+}
@@ -1,14 +1,17 @@
 use bytemuck::{Pod, Zeroable};
 use core::ops;
+use crate::export::{ExportError, Pixels};
 
 use std::{
 	any::TypeId,
+	cell::RefCell,
 	collections::HashMap,
+	path::{Path, PathBuf},
 	sync::{Arc, Weak},
 };
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable)]
 pub struct Point {
 	pub x: i32,
 	pub y: i32,
@@ -24,7 +27,7 @@ impl<P: Into<f64>> From<winit::dpi::PhysicalPosition<P>> for Point {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable)]
 pub struct Size {
 	pub w: u32,
 	pub h: u32,
@@ -41,7 +44,7 @@ impl TryFrom<Point> for Size {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable)]
 pub struct Rect {
 	pub pos: Point,
 	pub size: Size,
@@ -64,6 +67,59 @@ impl Rect {
 		}
 		inside_dim!(x, w) && inside_dim!(y, h)
 	}
+
+	pub fn intersects(&self, other: Rect) -> bool {
+		macro_rules! overlap_dim {
+			($dimP:ident, $dimS:ident) => {
+				self.pos.$dimP < other.pos.$dimP + other.size.$dimS as i32
+					&& other.pos.$dimP < self.pos.$dimP + self.size.$dimS as i32
+			};
+		}
+		overlap_dim!(x, w) && overlap_dim!(y, h)
+	}
+
+	pub fn intersection(&self, other: Rect) -> Option<Rect> {
+		if !self.intersects(other) {
+			return None;
+		}
+
+		use std::cmp::{max, min};
+		let x = max(self.pos.x, other.pos.x);
+		let y = max(self.pos.y, other.pos.y);
+		let right = min(self.pos.x + self.size.w as i32, other.pos.x + other.size.w as i32);
+		let bottom = min(self.pos.y + self.size.h as i32, other.pos.y + other.size.h as i32);
+		return Some(Rect::new(x, y, (right - x) as u32, (bottom - y) as u32));
+	}
+
+	/// Smallest rect containing both
+	pub fn union(&self, other: Rect) -> Rect {
+		use std::cmp::{max, min};
+		let x = min(self.pos.x, other.pos.x);
+		let y = min(self.pos.y, other.pos.y);
+		let right = max(self.pos.x + self.size.w as i32, other.pos.x + other.size.w as i32);
+		let bottom = max(self.pos.y + self.size.h as i32, other.pos.y + other.size.h as i32);
+		Rect::new(x, y, (right - x) as u32, (bottom - y) as u32)
+	}
+
+	pub fn contains(&self, other: Rect) -> bool {
+		macro_rules! contains_dim {
+			($dimP:ident, $dimS:ident) => {
+				self.pos.$dimP <= other.pos.$dimP
+					&& other.pos.$dimP + other.size.$dimS as i32 <= self.pos.$dimP + self.size.$dimS as i32
+			};
+		}
+		contains_dim!(x, w) && contains_dim!(y, h)
+	}
+}
+
+/// A component at `rect` doesn't need rendering if it is outside of `bounds` or fully hidden
+/// behind one of the opaque `occluders` drawn on top of it.
+pub fn is_culled(rect: Rect, bounds: Rect, occluders: &[Rect]) -> bool {
+	if !rect.intersects(bounds) {
+		return true;
+	}
+
+	return occluders.iter().any(|o| o.contains(rect));
 }
 
 impl ops::AddAssign for Point {
@@ -76,7 +132,7 @@ impl ops::AddAssign for Point {
 impl ops::Add for Point {
 	type Output = Self;
 	fn add(self, other: Self) -> Self {
-		let mut r = self.clone();
+		let mut r = self;
 		r += other;
 		r
 	}
@@ -102,7 +158,7 @@ impl ops::Add<Point> for Rect {
 	type Output = Self;
 
 	fn add(self, other: Point) -> Self::Output {
-		let mut r = self.clone();
+		let mut r = self;
 		r += other;
 		r
 	}
@@ -128,10 +184,10 @@ impl RectViewportClipSpace for wgpu::RenderPass<'_> {
 	fn set_clipspace_rect(&mut self, or: Option<Rect>) {
 		if let Some(r) = or {
 			use std::cmp::{max, min};
-			let x = max(r.pos.x as u32, 0);
-			let y = max(r.pos.y as u32, 0);
-			let w = r.size.w + min(r.pos.x as u32, 0);
-			let h = r.size.h + min(r.pos.y as u32, 0);
+			let x = max(r.pos.x, 0) as u32;
+			let y = max(r.pos.y, 0) as u32;
+			let w = max(r.size.w as i32 + min(r.pos.x, 0), 0) as u32;
+			let h = max(r.size.h as i32 + min(r.pos.y, 0), 0) as u32;
 			self.set_scissor_rect(x, y, w, h);
 		}
 	}
@@ -142,10 +198,101 @@ pub struct Pipelines {
 	pub compute: Vec<wgpu::ComputePipeline>,
 }
 
+/// GPU memory held, in bytes by what it holds.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MemoryUsage {
+	/// Layer pixels
+	pub layers: u64,
+	/// Composite and stroke tiles
+	pub tiles: u64,
+	/// Saved blocks of the history and the edit in progress
+	pub undo: u64,
+	pub thumbnails: u64,
+	/// Brush tips and pooled buffers
+	pub atlases: u64,
+	/// Canvases of the mixer and the brush preview
+	pub scratch: u64,
+}
+
+impl MemoryUsage {
+	pub fn total(&self) -> u64 {
+		self.layers + self.tiles + self.undo + self.thumbnails + self.atlases + self.scratch
+	}
+}
+
+/// How much of what the components use an adapter has, best first.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tier {
+	/// Push constants and storage textures read from fragment and compute shaders
+	Full,
+	/// Compute shaders without those, `Uniforms` come from buffers and tiles are packed in `r32uint`
+	Downlevel,
+	/// Within WebGL2, no compute shaders
+	WebGl2,
+}
+
+impl Tier {
+	/// Features used when the adapter has them.
+	pub const FEATURES: wgpu::Features = wgpu::Features::PUSH_CONSTANTS.union(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+
+	pub fn of(adapter: &wgpu::Adapter) -> Self {
+		if !adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS) {
+			return Tier::WebGl2;
+		}
+		if !adapter.features().contains(Self::FEATURES) {
+			return Tier::Downlevel;
+		}
+		return Tier::Full;
+	}
+
+	/// Limits to ask the device for, the defaults when the adapter has them.
+	pub fn limits(&self, adapter: &wgpu::Adapter) -> wgpu::Limits {
+		let base = match self {
+			Tier::WebGl2 => wgpu::Limits::downlevel_webgl2_defaults(),
+			_ if !wgpu::Limits::default().check_limits(&adapter.limits()) => wgpu::Limits::downlevel_defaults(),
+			_ => wgpu::Limits::default(),
+		};
+		return wgpu::Limits {
+			max_push_constant_size: if adapter.features().contains(wgpu::Features::PUSH_CONSTANTS) { 64 } else { 0 },
+			max_texture_dimension_2d: adapter.limits().max_texture_dimension_2d,
+			..base
+		};
+	}
+
+	/// Whether the canvas can be drawn, its passes are compute shaders.
+	pub fn paints(&self) -> bool {
+		*self != Tier::WebGl2
+	}
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Tier::Full => "Full",
+			Tier::Downlevel => "Downlevel",
+			Tier::WebGl2 => "WebGL2",
+		}
+	}
+}
+
+/// What a component tells the others, queued in the `Context` until the layout hands it to them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+	ColorChanged(ColorSlot, [f32; 3]),
+	ValueChanged(Control, f32),
+	/// A button was clicked, with the message it was made with
+	Pressed(&'static str),
+	/// A checkbox was ticked (true) or unticked
+	Toggled(&'static str, bool),
+	/// An option of a dropdown, or a row of a list, was picked by index
+	Selected(&'static str, usize),
+	/// A toolbar button was clicked
+	ToolPicked(ToolbarItem),
+	/// An edit couldn't be done, with why, for the user
+	Refused(&'static str),
+}
+
 pub trait Component {
 	fn generate_pipelines(_: &Context) -> Pipelines;
 	fn new(_: &mut Context) -> Box<Self>;
-	fn min_size() -> Option<Size>;
 	fn render(
 		&mut self,
 		_: &mut wgpu::CommandEncoder,
@@ -156,23 +303,188 @@ pub trait Component {
 	);
 }
 
+/// GPU objects made once and handed to every component asking for the same one.
+#[derive(Default)]
+struct GpuCache {
+	samplers: HashMap<(wgpu::AddressMode, wgpu::FilterMode), Arc<wgpu::Sampler>>,
+	layouts: HashMap<Vec<wgpu::BindGroupLayoutEntry>, Arc<wgpu::BindGroupLayout>>,
+	/// By label and the numbers the caller tells their resources apart with
+	bind_groups: HashMap<(&'static str, Vec<u64>), Arc<wgpu::BindGroup>>,
+}
+
 const STAGING_BUFFER_BYTES: u64 = 10;
+const POOL_CHUNK_BYTES: u64 = 64 * 1024;
+/// Hundreds of draws, most uniforms are a few dozen bytes at 256 byte offsets
+const UNIFORM_CHUNK_BYTES: u64 = 64 * 1024;
 
 pub struct Context {
 	pub device: wgpu::Device,
+	pub queue: wgpu::Queue,
 	pub surface_format: wgpu::TextureFormat,
+	/// Of the adapter the device is from
+	pub tier: Tier,
 	pipeline_map: HashMap<TypeId, Weak<Pipelines>>,
+	/// Kept while no component uses them, see `warm_pipelines`
+	warm: HashMap<TypeId, Arc<Pipelines>>,
+	/// Tips by the image they were loaded from, alive while a brush holds them
+	brush_tips: HashMap<PathBuf, Weak<BrushTip>>,
 	pub staging_belt: wgpu::util::StagingBelt,
+	pub buffer_pool: BufferPool,
+	/// Of `Uniforms` read from a buffer, reset with `buffer_pool`
+	pub uniform_pool: BufferPool,
+	/// Behind a cell so pipelines can take layouts from it while being generated
+	cache: RefCell<GpuCache>,
+	events: Vec<Event>,
 }
 
 impl Context {
-	pub fn new(device: wgpu::Device, surface_format: wgpu::TextureFormat) -> Context {
+	pub fn new(device: wgpu::Device, queue: wgpu::Queue, surface_format: wgpu::TextureFormat, tier: Tier) -> Context {
 		Context {
 			device,
+			queue,
 			surface_format,
+			tier,
 			pipeline_map: HashMap::new(),
+			warm: HashMap::new(),
+			brush_tips: HashMap::new(),
 			staging_belt: wgpu::util::StagingBelt::new(4 * STAGING_BUFFER_BYTES),
+			buffer_pool: BufferPool::new(
+				"Context(Buffer Pool)",
+				wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+				POOL_CHUNK_BYTES,
+			),
+			uniform_pool: BufferPool::new(
+				"Context(Uniform Pool)",
+				wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+				UNIFORM_CHUNK_BYTES,
+			),
+			cache: RefCell::new(GpuCache::default()),
+			events: Vec::new(),
+		}
+	}
+
+	pub fn emit(&mut self, event: Event) {
+		self.events.push(event);
+	}
+
+	/// Events emitted since the last call, oldest first.
+	pub fn take_events(&mut self) -> Vec<Event> {
+		return std::mem::take(&mut self.events);
+	}
+
+	/// GPU memory of the brush tips in use and the buffer pools, counted as atlases.
+	pub fn atlas_bytes(&self) -> u64 {
+		let tips: u64 = self.brush_tips.values().filter_map(Weak::upgrade).map(|t| t.bytes()).sum();
+		return tips + self.buffer_pool.bytes() + self.uniform_pool.bytes();
+	}
+
+	/// Frees GPU memory that can be allocated again when needed.
+	pub fn release_caches(&mut self) {
+		self.trim_pools();
+		self.warm.clear();
+		self.pipeline_map.retain(|_, weak| weak.strong_count() > 0);
+		self.brush_tips.retain(|_, weak| weak.strong_count() > 0);
+		let cache = self.cache.get_mut();
+		cache.samplers.retain(|_, s| Arc::strong_count(s) > 1);
+		cache.layouts.retain(|_, l| Arc::strong_count(l) > 1);
+	}
+
+	/// Frees the chunks of the buffer pools, the next frame allocates what it needs again.
+	pub fn trim_pools(&mut self) {
+		self.buffer_pool.trim();
+		self.uniform_pool.trim();
+		// Bind groups may hold chunks the pool just let go of
+		self.cache.get_mut().bind_groups.clear();
+	}
+
+	/// Sampler with `address` on every axis and `filter` for magnifying and minifying.
+	pub fn sampler(&self, address: wgpu::AddressMode, filter: wgpu::FilterMode) -> Arc<wgpu::Sampler> {
+		let mut cache = self.cache.borrow_mut();
+		return cache.samplers.entry((address, filter)).or_insert_with(|| Arc::new(self.device.create_sampler(&wgpu::SamplerDescriptor {
+			label: Some("Context(Sampler)"),
+			address_mode_u: address,
+			address_mode_v: address,
+			address_mode_w: address,
+			mag_filter: filter,
+			min_filter: filter,
+			..Default::default()
+		}))).clone();
+	}
+
+	/// Layout with `entries`, `label` is the one of the first component asking for it.
+	pub fn bind_group_layout(&self, label: &str, entries: &[wgpu::BindGroupLayoutEntry]) -> Arc<wgpu::BindGroupLayout> {
+		let mut cache = self.cache.borrow_mut();
+		if let Some(layout) = cache.layouts.get(entries) {
+			return layout.clone();
+		}
+		let layout = Arc::new(self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { label: Some(label), entries }));
+		cache.layouts.insert(entries.to_vec(), layout.clone());
+		return layout;
+	}
+
+	/// Bind group `create` makes the first time it is asked for with `label` and `resources`, the
+	/// numbers telling apart what is bound. Kept until `release_caches`, so only for resources that
+	/// outlive it.
+	pub fn bind_group(&self, label: &'static str, resources: &[u64], create: impl FnOnce() -> wgpu::BindGroup) -> Arc<wgpu::BindGroup> {
+		let key = (label, resources.to_vec());
+		if let Some(bind_group) = self.cache.borrow().bind_groups.get(&key) {
+			return bind_group.clone();
 		}
+		let bind_group = Arc::new(create());
+		self.cache.borrow_mut().bind_groups.insert(key, bind_group.clone());
+		return bind_group;
+	}
+
+	/// Tip from the image at `path`, the one loaded already while a brush still uses it.
+	pub fn brush_tip(&mut self, path: &Path) -> Result<Arc<BrushTip>, ExportError> {
+		if let Some(tip) = self.brush_tips.get(path).and_then(Weak::upgrade) {
+			return Ok(tip);
+		}
+
+		let pixels = Pixels::load_image(path)?;
+		let tip = Arc::new(BrushTip::new(self, &format!("BrushTip({})", path.display()), &pixels));
+		self.brush_tips.insert(path.to_path_buf(), Arc::downgrade(&tip));
+		return Ok(tip);
+	}
+
+	/// Texture with `pixels` in it, in the format the components read and write.
+	pub fn upload_texture(&self, label: &str, pixels: &Pixels, usage: wgpu::TextureUsages) -> wgpu::Texture {
+		use wgpu::util::DeviceExt;
+		return self.device.create_texture_with_data(&self.queue, &wgpu::TextureDescriptor {
+			label: Some(label),
+			size: wgpu::Extent3d {
+				width: pixels.size.w,
+				height: pixels.size.h,
+				depth_or_array_layers: 1,
+			},
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: wgpu::TextureFormat::Rgba8Unorm,
+			usage,
+		}, &pixels.data);
+	}
+
+	/// Module of the WGSL `source` once `preprocess` expanded it with `defines`. Bad directives
+	/// panic, as shaders that don't compile do.
+	pub fn create_shader(&self, label: &str, source: &str, defines: &[&str]) -> wgpu::ShaderModule {
+		let source = preprocess(source, defines).unwrap_or_else(|e| panic!("{label}: {e}"));
+		return self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some(label),
+			source: wgpu::ShaderSource::Wgsl(source.into()),
+		});
+	}
+
+	/// Generates the pipelines of `T` ahead of its first component, and keeps them for when its last
+	/// one is dropped. Returns whether they had to be generated.
+	pub fn warm_pipelines<T: Component + 'static>(&mut self) -> bool {
+		if self.warm.contains_key(&TypeId::of::<T>()) {
+			return false;
+		}
+		let generated = self.pipeline_map.get(&TypeId::of::<T>()).is_none_or(|weak| weak.strong_count() == 0);
+		let pipelines = self.get_pipelines::<T>();
+		self.warm.insert(TypeId::of::<T>(), pipelines);
+		return generated;
 	}
 
 	pub fn get_pipelines<T: Component + 'static>(&mut self) -> Arc<Pipelines> {
@@ -189,6 +501,23 @@ impl Context {
 	}
 }
 
+mod pool;
+pub use pool::*;
+mod brushtip;
+pub use brushtip::*;
+mod symmetry;
+pub use symmetry::*;
+mod invalidation;
+pub use invalidation::*;
+mod widget;
+pub use widget::*;
+mod shader;
+pub use shader::*;
+mod tree;
+pub use tree::*;
+mod uniforms;
+pub use uniforms::*;
+
 macro_rules! add_component {
 	($x:ident) => {
 		mod $x;
@@ -198,3 +527,16 @@ macro_rules! add_component {
 
 add_component!(canvas);
 add_component!(image);
+add_component!(outline);
+add_component!(shadow);
+add_component!(axes);
+add_component!(toolbar);
+add_component!(colorpicker);
+add_component!(swatches);
+add_component!(slider);
+add_component!(numberfield);
+add_component!(button);
+add_component!(checkbox);
+add_component!(dropdown);
+add_component!(textfield);
+add_component!(frame);
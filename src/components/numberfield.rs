@@ -0,0 +1,122 @@
+use winit::event::VirtualKeyCode;
+
+use crate::components::{widget_focus_ring, widget_frame, widget_text_top, Context, Control, DrawnWidget, Event, Point, Rect, Size, Widget, WidgetImage, WIDGET_FOCUSED, WIDGET_SCALE, WIDGET_TEXT};
+use crate::export::Pixels;
+use crate::font;
+
+const BACKGROUND: [u8; 4] = [24, 24, 28, 235];
+
+/// Value of its control typed in with the keyboard once clicked on. Enter, or clicking elsewhere,
+/// emits `Event::ValueChanged` with it; Escape goes back to the value it had.
+pub struct NumberField {
+	image: WidgetImage<(Size, String)>,
+	control: Control,
+	value: f32,
+	focused: bool,
+	/// Text typed since it was focused, None until something is typed
+	editing: Option<String>,
+}
+
+impl NumberField {
+	pub fn set_control(&mut self, control: Control) {
+		self.control = control;
+		self.value = control.clamp(self.value);
+	}
+
+	/// Shows `value`, unless something is being typed in.
+	pub fn set_value(&mut self, value: f32) {
+		if self.editing.is_none() {
+			self.value = self.control.clamp(value);
+		}
+	}
+
+	/// Emits what was typed, when it is a number.
+	fn commit(&mut self, ctx: &mut Context) {
+		let Some(value) = self.editing.take().and_then(|t| t.parse::<f32>().ok()) else {
+			return;
+		};
+		self.value = self.control.clamp(value);
+		ctx.emit(Event::ValueChanged(self.control, self.value));
+	}
+
+	fn text(&self) -> String {
+		match &self.editing {
+			Some(text) => format!("{text}_"),
+			None if self.focused => format!("{}_", self.control.format(self.value)),
+			None => self.control.format(self.value),
+		}
+	}
+}
+
+impl Widget for NumberField {
+	fn press(&mut self, _: &mut Context, _: Rect, _: Point) -> bool {
+		return true;
+	}
+
+	fn focusable(&self) -> bool {
+		true
+	}
+
+	fn set_focused(&mut self, ctx: &mut Context, focused: bool) {
+		if !focused {
+			self.commit(ctx);
+		}
+		self.focused = focused;
+	}
+
+	/// The first character typed replaces the value.
+	fn text_input(&mut self, _: &mut Context, c: char) -> bool {
+		match c {
+			'\u{8}' => {
+				self.editing.get_or_insert_with(String::new).pop();
+			}
+			'0'..='9' | '.' | '-' => self.editing.get_or_insert_with(String::new).push(c),
+			_ => return false,
+		}
+		return true;
+	}
+
+	fn key_input(&mut self, ctx: &mut Context, key: VirtualKeyCode) -> bool {
+		match key {
+			VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => self.commit(ctx),
+			VirtualKeyCode::Escape => self.editing = None,
+			_ => return crate::hud::is_input_key(key),
+		}
+		return true;
+	}
+}
+
+impl DrawnWidget for NumberField {
+	/// Size and text
+	type State = (Size, String);
+	const LABEL: &'static str = "NumberField(Texture)";
+
+	fn with_image(image: WidgetImage<Self::State>) -> Self {
+		let control = Control::BrushSize;
+		Self {
+			image,
+			control,
+			value: control.range().0,
+			focused: false,
+			editing: None,
+		}
+	}
+
+	fn image(&mut self) -> &mut WidgetImage<Self::State> {
+		&mut self.image
+	}
+
+	fn state(&self, size: Size) -> Option<Self::State> {
+		return Some((size, self.text()));
+	}
+
+	fn draw(&self, size: Size, (_, text): &Self::State) -> Pixels {
+		let mut pixels = widget_frame(size, BACKGROUND);
+		let x = size.w.saturating_sub(font::text_size(text, WIDGET_SCALE).w + 2 * WIDGET_SCALE);
+		font::draw_text(&mut pixels, x, widget_text_top(size.h), text, if self.focused { WIDGET_FOCUSED } else { WIDGET_TEXT }, WIDGET_SCALE);
+		if self.focused {
+			widget_focus_ring(&mut pixels);
+		}
+		return pixels;
+	}
+}
@@ -0,0 +1,123 @@
+use crate::components::{self, Rect, Context, Pipelines, RectViewportClipSpace, Uniforms};
+
+/// Border drawn along the inside of the rect it is rendered in.
+pub struct Outline {
+	pipelines: std::sync::Arc<Pipelines>,
+	uniforms: Uniforms<OutlineInput>,
+	pub color: [f32; 4],
+	pub width: f32,
+	/// Length of the dashes alternating with the inverted color, 0 for a solid border
+	pub dash: f32,
+	/// Distance the dashes are shifted along the border, animated for marching ants
+	pub phase: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineInput {
+	color: [f32; 4],
+	rect: [f32; 4],
+	width: f32,
+	srgb_output: u32,
+	dash: f32,
+	phase: f32,
+}
+
+impl components::Component for Outline {
+	fn generate_pipelines(ctx: &Context) -> Pipelines {
+		let uniforms = Uniforms::<OutlineInput>::new(ctx, wgpu::ShaderStages::FRAGMENT, 0);
+		let shader = ctx.create_shader("Outline(Shader)", include_str!("shaders/outline.wgsl"), uniforms.defines());
+
+		let render_pipeline_layout = ctx.device.create_pipeline_layout(
+			&wgpu::PipelineLayoutDescriptor {
+				label: Some("Outline(Pipeline Layout)"),
+				bind_group_layouts: &uniforms.bind_group_layout().into_iter().collect::<Vec<_>>(),
+				push_constant_ranges: &uniforms.push_constant_ranges(),
+			}
+		);
+
+		let render_pipeline = ctx.device.create_render_pipeline(
+			&wgpu::RenderPipelineDescriptor {
+				label: Some("Outline(Render Pipeline)"),
+				layout: Some(&render_pipeline_layout),
+				vertex: wgpu::VertexState {
+					module: &shader,
+					entry_point: "vs_main",
+					buffers: &[],
+				},
+				fragment: Some(wgpu::FragmentState {
+					module: &shader,
+					entry_point: "fs_main",
+					targets: &[Some(wgpu::ColorTargetState {
+						format: ctx.surface_format,
+						blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+						write_mask: wgpu::ColorWrites::ALL,
+					})],
+				}),
+				primitive: wgpu::PrimitiveState {
+					topology: wgpu::PrimitiveTopology::TriangleList,
+					strip_index_format: None,
+					front_face: wgpu::FrontFace::Ccw,
+					cull_mode: None,
+					polygon_mode: wgpu::PolygonMode::Fill,
+					unclipped_depth: false,
+					conservative: false,
+				},
+				depth_stencil: None,
+				multisample: wgpu::MultisampleState {
+					count: 1,
+					mask: !0,
+					alpha_to_coverage_enabled: false
+				},
+				multiview: None
+			}
+		);
+
+		Pipelines {
+			render: vec![render_pipeline],
+			compute: vec![],
+		}
+	}
+
+	fn new(ctx: &mut Context) -> Box<Self> {
+		Box::new(Self {
+			pipelines: ctx.get_pipelines::<Self>(),
+			uniforms: Uniforms::new(ctx, wgpu::ShaderStages::FRAGMENT, 0),
+			color: [1., 1., 1., 1.],
+			width: 1.,
+			dash: 0.,
+			phase: 0.,
+		})
+	}
+
+	fn render(&mut self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, output: &wgpu::TextureView, viewport: Rect, clip_space: Option<Rect>) {
+		let input = OutlineInput {
+			color: self.color,
+			rect: [viewport.pos.x as f32, viewport.pos.y as f32, viewport.size.w as f32, viewport.size.h as f32],
+			width: self.width,
+			srgb_output: ctx.surface_format.describe().srgb as u32,
+			dash: self.dash,
+			phase: self.phase,
+		};
+
+		let uniforms = self.uniforms.bind(ctx, &input);
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("Outline(Render Pass)"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: output,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Load,
+					store: true,
+				}
+			})],
+			depth_stencil_attachment: None,
+		});
+
+		render_pass.set_pipeline(&self.pipelines.render[0]);
+		render_pass.set_viewport_rect(viewport);
+		render_pass.set_clipspace_rect(clip_space);
+		uniforms.set(&mut render_pass);
+		render_pass.draw(0..6, 0..1);
+	}
+}
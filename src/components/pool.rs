@@ -0,0 +1,93 @@
+/// Hands out slices of a few big GPU buffers that are reused every frame, so components that upload
+/// per-frame geometry don't create (and drop) a buffer each time.
+pub struct BufferPool {
+	label: &'static str,
+	usage: wgpu::BufferUsages,
+	chunk_size: u64,
+	chunks: Vec<Chunk>,
+}
+
+struct Chunk {
+	buffer: wgpu::Buffer,
+	used: u64,
+}
+
+/// A region of a pooled buffer, valid until the next `BufferPool::reset`.
+pub struct PooledSlice {
+	chunk: usize,
+	pub offset: u64,
+}
+
+impl PooledSlice {
+	/// Index of the chunk it is in, chunks keep theirs until `BufferPool::trim`.
+	pub fn chunk(&self) -> usize {
+		self.chunk
+	}
+}
+
+impl BufferPool {
+	pub fn new(label: &'static str, usage: wgpu::BufferUsages, chunk_size: u64) -> Self {
+		Self {
+			label,
+			usage,
+			chunk_size,
+			chunks: Vec::new(),
+		}
+	}
+
+	pub fn alloc(&mut self, device: &wgpu::Device, size: wgpu::BufferSize) -> PooledSlice {
+		let limits = device.limits();
+		let alignment = if self.usage.contains(wgpu::BufferUsages::UNIFORM) { limits.min_uniform_buffer_offset_alignment } else { limits.min_storage_buffer_offset_alignment } as u64;
+		let size_bytes: u64 = size.into();
+
+		for (i, chunk) in self.chunks.iter_mut().enumerate() {
+			let offset = wgpu::util::align_to(chunk.used, alignment);
+			if offset + size_bytes <= chunk.buffer.size() {
+				chunk.used = offset + size_bytes;
+				return PooledSlice { chunk: i, offset };
+			}
+		}
+
+		log::debug!("{}: growing pool to {} chunks", self.label, self.chunks.len() + 1);
+		let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some(self.label),
+			size: std::cmp::max(self.chunk_size, size_bytes),
+			usage: self.usage,
+			mapped_at_creation: false,
+		});
+
+		self.chunks.push(Chunk { buffer, used: size_bytes });
+		return PooledSlice { chunk: self.chunks.len() - 1, offset: 0 };
+	}
+
+	pub fn buffer(&self, slice: &PooledSlice) -> &wgpu::Buffer {
+		&self.chunks[slice.chunk].buffer
+	}
+
+	/// Binding from the start of `slice` to the end of its chunk, the same for every slice starting
+	/// there so its bind group can be cached.
+	pub fn binding(&self, slice: &PooledSlice) -> wgpu::BindingResource<'_> {
+		wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+			buffer: self.buffer(slice),
+			offset: slice.offset,
+			size: None,
+		})
+	}
+
+	/// GPU memory of the chunks, used or not.
+	pub fn bytes(&self) -> u64 {
+		self.chunks.iter().map(|c| c.buffer.size()).sum()
+	}
+
+	/// Frees every chunk, slices handed out before are no longer valid.
+	pub fn trim(&mut self) {
+		self.chunks.clear();
+	}
+
+	/// Makes every slice available again. Call once the frame using them has been submitted.
+	pub fn reset(&mut self) {
+		for chunk in self.chunks.iter_mut() {
+			chunk.used = 0;
+		}
+	}
+}
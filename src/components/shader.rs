@@ -0,0 +1,117 @@
+/// Files shaders can `#include`, by the name they are included with
+const INCLUDES: [(&str, &str); 2] = [
+	("color.wgsl", include_str!("shaders/include/color.wgsl")),
+	("selection.wgsl", include_str!("shaders/include/selection.wgsl")),
+];
+
+/// Expands the directives of WGSL `source`, one per line:
+/// - `#include "name"` pastes one of `INCLUDES`, only the first time it is included
+/// - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` keep the lines between them or not,
+///   depending on whether `NAME` is one of `defines`
+pub fn preprocess(source: &str, defines: &[&str]) -> Result<String, String> {
+	let mut output = String::with_capacity(source.len());
+	expand(source, defines, &mut Vec::new(), &mut output)?;
+	return Ok(output);
+}
+
+fn expand(source: &str, defines: &[&str], included: &mut Vec<&'static str>, output: &mut String) -> Result<(), String> {
+	// Whether the lines of each open #ifdef are kept, and if its #else was seen
+	let mut branches: Vec<(bool, bool)> = Vec::new();
+	for (i, line) in source.lines().enumerate() {
+		let Some(directive) = line.trim_start().strip_prefix('#') else {
+			if branches.iter().all(|(kept, _)| *kept) {
+				output.push_str(line);
+			}
+			output.push('\n');
+			continue;
+		};
+		let (name, argument) = directive.split_once(char::is_whitespace).map_or((directive.trim(), ""), |(n, a)| (n, a.trim()));
+		let kept = branches.iter().all(|(kept, _)| *kept);
+		match name {
+			"ifdef" | "ifndef" if argument.is_empty() => return Err(format!("line {}: #{name} needs a name", i + 1)),
+			"ifdef" => branches.push((defines.contains(&argument), false)),
+			"ifndef" => branches.push((!defines.contains(&argument), false)),
+			"else" => match branches.last_mut() {
+				Some((kept, seen @ false)) => (*kept, *seen) = (!*kept, true),
+				_ => return Err(format!("line {}: #else without #ifdef", i + 1)),
+			},
+			"endif" => {
+				if branches.pop().is_none() {
+					return Err(format!("line {}: #endif without #ifdef", i + 1));
+				}
+			}
+			"include" if kept => {
+				let file = argument.trim_matches('"');
+				let Some((file, included_source)) = INCLUDES.iter().find(|(n, _)| *n == file) else {
+					return Err(format!("line {}: no shader include {argument}", i + 1));
+				};
+				if !included.contains(file) {
+					included.push(*file);
+					expand(included_source, defines, included, output)?;
+				}
+			}
+			"include" => (),
+			_ => return Err(format!("line {}: unknown directive #{name}", i + 1)),
+		}
+		output.push('\n');
+	}
+	if !branches.is_empty() {
+		return Err("#ifdef without #endif".to_string());
+	}
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Lines kept, the blank ones the directives leave out.
+	fn kept(source: &str, defines: &[&str]) -> Vec<String> {
+		let output = preprocess(source, defines).unwrap();
+		return output.lines().filter(|l| !l.is_empty()).map(str::to_owned).collect();
+	}
+
+	#[test]
+	fn ifdef_keeps_one_branch() {
+		let source = "#ifdef A\na\n#else\nnot a\n#endif\n#ifndef B\nnot b\n#endif";
+		assert_eq!(kept(source, &["A"]), ["a", "not b"]);
+		assert_eq!(kept(source, &["B"]), ["not a"]);
+	}
+
+	#[test]
+	fn nested_branches_need_every_parent() {
+		let source = "#ifdef A\n#ifdef B\nab\n#else\na\n#endif\n#endif";
+		assert_eq!(kept(source, &["A", "B"]), ["ab"]);
+		assert_eq!(kept(source, &["A"]), ["a"]);
+		assert!(kept(source, &["B"]).is_empty());
+	}
+
+	#[test]
+	fn directives_keep_line_numbers() {
+		let source = "#ifdef A\na\n#endif\nb";
+		assert_eq!(preprocess(source, &[]).unwrap().lines().count(), 4);
+	}
+
+	#[test]
+	fn includes_are_pasted_once() {
+		let once = kept("#include \"color.wgsl\"", &[]);
+		assert!(!once.is_empty());
+		assert_eq!(kept("#include \"color.wgsl\"\n#include \"color.wgsl\"", &[]), once);
+	}
+
+	#[test]
+	fn skipped_includes_are_not_checked() {
+		assert!(preprocess("#ifdef A\n#include \"missing.wgsl\"\n#endif", &[]).is_ok());
+		assert!(preprocess("#include \"missing.wgsl\"", &[]).is_err());
+	}
+
+	#[test]
+	fn unbalanced_directives_fail() {
+		assert!(preprocess("#ifdef A\na", &[]).is_err());
+		assert!(preprocess("#endif", &[]).is_err());
+		assert!(preprocess("#else", &[]).is_err());
+		assert!(preprocess("#ifdef A\n#else\n#else\n#endif", &[]).is_err());
+		assert!(preprocess("#ifdef\n#endif", &[]).is_err());
+		assert!(preprocess("#define A", &[]).is_err());
+	}
+}
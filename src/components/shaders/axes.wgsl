@@ -0,0 +1,50 @@
+struct AxesInput {
+	// In framebuffer pixels
+	center: vec2<f32>,
+	rays: u32,
+	// Radians clockwise from up of the first ray
+	angle: f32,
+	opacity: f32,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(0) @binding(0)
+var<uniform> axes: AxesInput;
+#else
+var<push_constant> axes: AxesInput;
+#endif
+
+@vertex
+fn vs_main(
+	@builtin(vertex_index) index: u32,
+) -> @builtin(position) vec4<f32> {
+	var pos = vec2<f32>(0., 0.);
+
+	if index % u32(2) == u32(1) {
+		pos.y = 1.;
+	}
+
+	if index == u32(0) || index >= u32(4) {
+		pos.x = 1.;
+	}
+
+	return vec4<f32>(2. * pos - 1., 1., 1.);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+	if axes.rays == u32(0) {
+		discard;
+	}
+	let d = frag.xy - axes.center;
+	let step = 6.28318530718 / f32(axes.rays);
+	let rel = atan2(d.x, -d.y) - axes.angle;
+	// Angle to the nearest ray
+	let off = rel - round(rel / step) * step;
+	if cos(off) <= 0. {
+		discard;
+	}
+	let dist = length(d) * abs(sin(off));
+	// White needs no sRGB conversion
+	return vec4<f32>(1., 1., 1., axes.opacity * (1. - smoothstep(0.5, 1.5, dist)));
+}
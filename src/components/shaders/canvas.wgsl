@@ -1,19 +1,120 @@
+// Rgba8 packed in r32uint, the format storage textures can be read and written in on GL
 @group(0) @binding(0)
-var tex: texture_storage_2d<rgba8unorm, read_write>;
+var tex: texture_storage_2d<r32uint, read_write>;
 
-var<push_constant> clear_color: vec3<f32>;
+struct ClearInput {
+	// Premultiplied, transparent layers are cleared to zero
+	color: vec4<f32>,
+	// Region of the texture cleared
+	origin: vec2<i32>,
+	size: vec2<u32>,
+	// Where the texture starts in the document
+	tile_origin: vec2<i32>,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(1) @binding(0)
+var<uniform> clear_in: ClearInput;
+#else
+var<push_constant> clear_in: ClearInput;
+#endif
 
 @compute
 @workgroup_size(8, 8, 1)
 fn clear(@builtin(global_invocation_id) gid: vec3<u32>) {
-	let pos = vec2<i32>(gid.xy);
+	let pos = vec2<i32>(gid.xy) + clear_in.origin;
+	let dims = textureDimensions(tex);
+
+	let end = min(dims, clear_in.origin + vec2<i32>(clear_in.size));
+	if pos.x >= end.x || pos.y >= end.y {
+		return;
+	}
+
+	textureStore(tex, pos, vec4<u32>(pack4x8unorm(clear_in.color)));
+}
+
+#include "selection.wgsl"
+
+// Selection the filters and selection fills are weighted by. Group 1 is bound to other layouts
+// by the other entry points, binding 3 keeps clear of their bindings
+@group(1) @binding(3)
+var<uniform> selection: SelectionMask;
+
+#ifdef UNIFORM_BUFFER
+@group(2) @binding(0)
+var<uniform> clear_selected_in: ClearInput;
+#else
+var<push_constant> clear_selected_in: ClearInput;
+#endif
+
+// Like `clear`, blending the color over as much of each pixel as is selected
+@compute
+@workgroup_size(8, 8, 1)
+fn clear_selected(@builtin(global_invocation_id) gid: vec3<u32>) {
+	let pos = vec2<i32>(gid.xy) + clear_selected_in.origin;
+	let dims = textureDimensions(tex);
+
+	let end = min(dims, clear_selected_in.origin + vec2<i32>(clear_selected_in.size));
+	if pos.x >= end.x || pos.y >= end.y {
+		return;
+	}
+
+	let w = selection_weight(selection, pos + clear_selected_in.tile_origin);
+	let color = mix(unpack4x8unorm(textureLoad(tex, pos).x), clear_selected_in.color, w);
+	textureStore(tex, pos, vec4<u32>(pack4x8unorm(color)));
+}
+
+struct OutlineInput {
+	color: vec4<f32>,
+	// Region of the texture outlined
+	origin: vec2<i32>,
+	size: vec2<u32>,
+	// Where the texture starts in the document
+	tile_origin: vec2<i32>,
+	width: f32,
+	_pad: f32,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(2) @binding(0)
+var<uniform> outline_in: OutlineInput;
+#else
+var<push_constant> outline_in: OutlineInput;
+#endif
+
+// Border `width` pixels wide centered on the selection edge, where the mask crosses one half. The
+// edge is found by searching the pixels around for the nearest one on its other side, it runs
+// half a pixel from that one.
+@compute
+@workgroup_size(8, 8, 1)
+fn stroke_selected(@builtin(global_invocation_id) gid: vec3<u32>) {
+	let pos = vec2<i32>(gid.xy) + outline_in.origin;
 	let dims = textureDimensions(tex);
 
-	if pos.x > dims.x || pos.y > dims.y {
+	let end = min(dims, outline_in.origin + vec2<i32>(outline_in.size));
+	if pos.x >= end.x || pos.y >= end.y {
 		return;
 	}
 
-	textureStore(tex, pos, vec4<f32>(clear_color, 1.));
+	let doc = pos + outline_in.tile_origin;
+	let inside = selection_weight(selection, doc) >= 0.5;
+	let radius = outline_in.width * 0.5;
+	let reach = i32(radius) + 1;
+	var nearest = radius + 1.;
+	for (var y = -reach; y <= reach; y++) {
+		for (var x = -reach; x <= reach; x++) {
+			let d = length(vec2<f32>(f32(x), f32(y)));
+			if d < nearest && (selection_weight(selection, doc + vec2<i32>(x, y)) >= 0.5) != inside {
+				nearest = d;
+			}
+		}
+	}
+
+	let coverage = clamp(radius + 1. - nearest, 0., 1.);
+	if coverage > 0. {
+		let color = mix(unpack4x8unorm(textureLoad(tex, pos).x), outline_in.color, coverage);
+		textureStore(tex, pos, vec4<u32>(pack4x8unorm(color)));
+	}
 }
 
 fn inside_circle(center: vec2<f32>, radius: f32, p: vec2<f32>) -> bool {
@@ -24,7 +125,12 @@ struct DrawInput {
 	mouse: vec2<i32>,
 	brush_rad: u32
 }
+#ifdef UNIFORM_BUFFER
+@group(1) @binding(0)
+var<uniform> point_in: DrawInput;
+#else
 var<push_constant> point_in: DrawInput;
+#endif
 
 @compute
 @workgroup_size(8, 8, 1)
@@ -36,8 +142,7 @@ fn draw_point(@builtin(global_invocation_id) gid: vec3<u32>) {
 	}
 
 	if inside_circle(vec2<f32>(point_in.mouse), f32(point_in.brush_rad), vec2<f32>(pos)) {
-		let color = vec4<f32>(1., 1., 1., 1.);
-		textureStore(tex, pos, color);
+		textureStore(tex, pos, vec4<u32>(0xffffffffu));
 	}
 }
 
@@ -67,40 +172,428 @@ struct LineInput {
 	line_start_index: u32,
 	line_end_index: u32,
 
-	brush_rad: u32,
+	// Where the bound texture starts in the document
+	tile_origin: vec2<i32>,
+
+	brush_rad: f32,
+	// Fraction of the radius painted fully opaque, the rest fades out
+	hardness: f32,
+	// Distance between dabs as a fraction of the brush size, 0 for a continuous stroke
+	spacing: f32,
+	// Premultiplied color packed as rgba8, the foreground or transparent when erasing
+	color: u32,
+	// How much the pressure scales the radius and the opacity, 0 to 1
+	pressure_size: f32,
+	pressure_opacity: f32,
+	// Most the stroke covers, and how much each pass adds up to it
+	opacity: f32,
+	flow: f32,
+	// Radians the tip is turned, and the STAMPED, ALPHA_LOCKED and SMUDGED bits
+	rotation: f32,
+	flags: u32,
 }
 
+// The tip is stamped instead of painting round dabs
+let STAMPED: u32 = 1u;
+// Only the color of covered pixels changes, how opaque they are stays
+let ALPHA_LOCKED: u32 = 2u;
+// Covered pixels take the color the stroke picked up instead of the brush color
+let SMUDGED: u32 = 4u;
+
+#ifdef UNIFORM_BUFFER
+@group(3) @binding(0)
+var<uniform> line_in: LineInput;
+#else
 var<push_constant> line_in: LineInput;
+#endif
+
+struct StrokePoint {
+	pos: vec2<i32>,
+	pressure: f32,
+	stroke: u32,
+	// Length of the stroke up to the point
+	distance: f32,
+	// Radians the tip is turned on top of the brush rotation, for symmetry copies
+	turn: f32,
+}
+
+// Set in the stroke of points from mirrored symmetry copies, above the id
+let MIRRORED_STROKE: u32 = 65536u;
+
+// Position along a -> b of the stroke center closest to p, from 0 to 1, snapped to the closest dab when the brush is spaced.
+fn stroke_position(a: vec2<f32>, b: vec2<f32>, p: vec2<f32>) -> f32 {
+	let len = length(b - a);
+	if len == 0. {
+		return 0.;
+	}
+
+	let proj = clamp(scalar_projection(a, b, p), 0., len);
+	let step = line_in.spacing * 2. * line_in.brush_rad;
+	if step < 1. {
+		return proj / len;
+	}
+
+	let dab = min(round(proj / step), floor(len / step));
+	return dab * step / len;
+}
+
+// Coverage of p by the segment between two stroke points, the pressure interpolated along it.
+fn segment_coverage(a: StrokePoint, b: StrokePoint, p: vec2<f32>) -> f32 {
+	let pa = vec2<f32>(a.pos);
+	let pb = vec2<f32>(b.pos);
+	let t = stroke_position(pa, pb, p);
+	let dist = distance(pa + (pb - pa) * t, p);
+	let pressure = mix(a.pressure, b.pressure, t);
+
+	let r = line_in.brush_rad * mix(1., pressure, line_in.pressure_size);
+	// At least a pixel of falloff so hard brushes still get an antialiased edge
+	let hard_r = r * line_in.hardness;
+	let coverage = clamp((r + 0.5 - dist) / max(r - hard_r, 1.), 0., 1.);
+	return coverage * mix(1., pressure, line_in.pressure_opacity);
+}
+
+// Coverage of the stamped tip, sampled in red. Bound with the stroke textures, the uniforms
+// take the last group
+@group(2) @binding(2)
+var tip: texture_2d<f32>;
+@group(2) @binding(3)
+var tip_sampler: sampler;
+
+// How each dab is randomly moved, resized, turned and tinted
+struct Dynamics {
+	// Most a dab is moved across the stroke either way, as a fraction of the brush size
+	scatter: f32,
+	// Most a dab shrinks, as a fraction of its size
+	size_jitter: f32,
+	// Most the hue turns either way, as a fraction of the hue wheel
+	hue_jitter: f32,
+	// Most the value changes either way
+	lightness_jitter: f32,
+	// Most radians the tip turns either way
+	rotation_jitter: f32,
+	// Same dabs for every stroke, 0 for different ones each stroke
+	seed: u32,
+	// Whether any of it is set, strokes are painted as separate dabs by the DABS variant then
+	jittered: u32,
+}
+
+@group(2) @binding(4)
+var<uniform> dynamics: Dynamics;
+
+// Premultiplied color a smudging stroke carries, and the stroke it's for
+struct Pickup {
+	color: vec4<f32>,
+	stroke: u32,
+}
+
+@group(2) @binding(5)
+var<storage, read_write> pickup: Pickup;
+
+fn hash(v: u32) -> u32 {
+	var h = v;
+	h = h ^ (h >> 16u);
+	h = h * 0x7feb352du;
+	h = h ^ (h >> 15u);
+	h = h * 0x846ca68bu;
+	return h ^ (h >> 16u);
+}
+
+fn unit(h: u32) -> f32 {
+	return f32(h >> 8u) / 16777216.;
+}
+
+// One dab of the stroke, after pressure and dynamics
+struct Dab {
+	center: vec2<f32>,
+	radius: f32,
+	// Radians the tip is turned, the brush rotation and its jitter
+	rotation: f32,
+	opacity: f32,
+	// Byte the color of the dab is jittered from, 0 to 1
+	tint: f32,
+}
+
+// The dab number `index` of a stroke placed at center, moved along normal by the scatter.
+fn make_dab(index: f32, stroke: u32, center: vec2<f32>, normal: vec2<f32>, pressure: f32) -> Dab {
+	let seed = select(dynamics.seed, hash(stroke & 65535u), dynamics.seed == 0u);
+	let r0 = hash(u32(index) ^ hash(seed));
+	let r1 = hash(r0);
+	let r2 = hash(r1);
+	let r3 = hash(r2);
+
+	var dab: Dab;
+	dab.center = center + normal * (unit(r0) * 2. - 1.) * dynamics.scatter * 2. * line_in.brush_rad;
+	dab.radius = line_in.brush_rad * mix(1., pressure, line_in.pressure_size) * (1. - dynamics.size_jitter * unit(r1));
+	dab.rotation = line_in.rotation + (unit(r2) * 2. - 1.) * dynamics.rotation_jitter;
+	dab.opacity = mix(1., pressure, line_in.pressure_opacity);
+	dab.tint = f32(r3 & 255u) / 255.;
+	return dab;
+}
+
+// Premultiplied `color` with its hue and value moved by the dynamics, by how much depending on tint.
+fn jitter_color(color: vec4<f32>, tint: f32) -> vec4<f32> {
+	if color.a <= 0. || (dynamics.hue_jitter <= 0. && dynamics.lightness_jitter <= 0.) {
+		return color;
+	}
+	let h = hash(u32(round(tint * 255.)) + 1u);
+	let c = color.rgb / color.a;
+
+	let v = max(c.r, max(c.g, c.b));
+	let d = v - min(c.r, min(c.g, c.b));
+	var hue = 0.;
+	if d > 0. {
+		if v == c.r {
+			hue = (c.g - c.b) / d;
+		} else if v == c.g {
+			hue = (c.b - c.r) / d + 2.;
+		} else {
+			hue = (c.r - c.g) / d + 4.;
+		}
+	}
+	let s = select(0., d / v, v > 0.);
+	hue = fract(hue / 6. + (unit(h) * 2. - 1.) * dynamics.hue_jitter + 1.);
+	let value = clamp(v + (unit(hash(h)) * 2. - 1.) * dynamics.lightness_jitter, 0., 1.);
+
+	let k = (vec3<f32>(5., 3., 1.) + hue * 6.) % 6.;
+	let rgb = value - value * s * clamp(min(k, 4. - k), vec3<f32>(0.), vec3<f32>(1.));
+	return vec4<f32>(rgb * color.a, color.a);
+}
+
+// Coverage of p by a round dab.
+fn round_coverage(dab: Dab, p: vec2<f32>) -> f32 {
+	let dist = distance(dab.center, p);
+	let hard_r = dab.radius * line_in.hardness;
+	return clamp((dab.radius + 0.5 - dist) / max(dab.radius - hard_r, 1.), 0., 1.) * dab.opacity;
+}
+
+// Coverage of p by the tip stamped as dab, turned and fitted in the dab diameter by its longer side.
+// Mirrored copies turn the other way and flip the tip.
+fn stamp_coverage(dab: Dab, turn: f32, mirrored: bool, p: vec2<f32>) -> f32 {
+	let r = dab.radius;
+	let d = p - dab.center;
+	let angle = select(dab.rotation + turn, turn - dab.rotation, mirrored);
+	let c = cos(angle);
+	let s = sin(angle);
+	var local = vec2<f32>(c * d.x + s * d.y, c * d.y - s * d.x);
+	if mirrored {
+		local.x = -local.x;
+	}
+
+	let dims = vec2<f32>(textureDimensions(tip));
+	let half_size = r * dims / max(dims.x, dims.y);
+	let uv = local / (2. * half_size) + 0.5;
+	if any(uv < vec2<f32>(0.)) || any(uv > vec2<f32>(1.)) {
+		return 0.;
+	}
+	return textureSampleLevel(tip, tip_sampler, uv, 0.).r * dab.opacity;
+}
+
+// Coverage of p by the dabs of the segment between two stroke points, and the tint of the one covering
+// it the most. Dabs are spaced along the whole stroke, each belonging to the segment it starts in.
+fn segment_dabs(a: StrokePoint, b: StrokePoint, p: vec2<f32>) -> vec2<f32> {
+	let pa = vec2<f32>(a.pos);
+	let pb = vec2<f32>(b.pos);
+	let len = b.distance - a.distance;
+	let step = line_in.spacing * 2. * line_in.brush_rad;
+	// Dabs further along the stroke than a tip corner, scattered, can't reach p
+	let corner = select(1., sqrt(2.), (line_in.flags & STAMPED) != 0u);
+	let reach = line_in.brush_rad * (corner + 2. * dynamics.scatter);
+
+	var along = a.distance;
+	if len > 0. {
+		along += clamp(scalar_projection(pa, pb, p), 0., len);
+	}
+	let first = max(ceil(a.distance / step), ceil((along - reach) / step));
+	let last = min(select(floor(a.distance / step), ceil(b.distance / step) - 1., len > 0.), floor((along + reach) / step));
+
+	let mirrored = (a.stroke & MIRRORED_STROKE) != 0u;
+	// Scattered the other way on mirrored copies, so they stay mirror images
+	var normal = vec2<f32>(0.);
+	if length(pb - pa) > 0. {
+		normal = normalize(vec2<f32>(pa.y - pb.y, pb.x - pa.x)) * select(1., -1., mirrored);
+	}
+	var best = vec2<f32>(0.);
+	for (var index = first; index <= last; index += 1.) {
+		let t = select(0., (index * step - a.distance) / len, len > 0.);
+		let dab = make_dab(index, a.stroke, pa + (pb - pa) * t, normal, mix(a.pressure, b.pressure, t));
+		var coverage = 0.;
+		if (line_in.flags & STAMPED) != 0u {
+			coverage = stamp_coverage(dab, a.turn, mirrored, p);
+		} else {
+			coverage = round_coverage(dab, p);
+		}
+		if coverage > best.x {
+			best = vec2<f32>(coverage, dab.tint);
+		}
+	}
+	return best;
+}
 
 @group(1) @binding(0)
-var<storage, read> points: array<vec2<i32>>;
+var<storage, read> points: array<StrokePoint>;
+
+// Layer pixels from before the stroke, and the coverage of the stroke with its id in green and blue
+@group(2) @binding(0)
+var stroke_base: texture_storage_2d<r32uint, read_write>;
+@group(2) @binding(1)
+var stroke_mask: texture_storage_2d<r32uint, read_write>;
 
 @compute
 @workgroup_size(8, 8, 1)
 fn draw_line(@builtin(global_invocation_id) gid: vec3<u32>) {
 	let pos = vec2<i32>(gid.xy) + line_in.reference_point;
+	let tex_pos = pos - line_in.tile_origin;
 	let dims = textureDimensions(tex);
-	if 0 > pos.x || pos.x > dims.x || 0 > pos.y || pos.y > dims.y {
+	if 0 > tex_pos.x || tex_pos.x >= dims.x || 0 > tex_pos.y || tex_pos.y >= dims.y {
 		return;
 	}
 
-	let r = f32(line_in.brush_rad);
+	var coverage = 0.;
+	var tint = 0.;
+	for (var i = line_in.line_start_index; i + u32(1) < line_in.line_end_index; i++) {
+#ifdef DABS
+		let dab = segment_dabs(points[i], points[i+u32(1)], vec2<f32>(pos));
+		if dab.x > coverage {
+			coverage = dab.x;
+			tint = dab.y;
+		}
+#else
+		coverage = max(coverage, segment_coverage(points[i], points[i+u32(1)], vec2<f32>(pos)));
+#endif
+	}
+	if coverage <= 0. {
+		return;
+	}
 
-	var i = line_in.line_start_index;
-	var flag = false;
-	while i < line_in.line_end_index - u32(1) {
-		let a = vec2<f32>(points[i]);
-		let b = vec2<f32>(points[i+u32(1)]);
-		flag = inside_circle(a, r, vec2<f32>(pos)) ||
-				inside_line(a, b, r, vec2<f32>(pos)) ||
-				inside_circle(b, r, vec2<f32>(pos));
-		if flag { break; }
-		i = i + u32(1);
+	// The stroke covers the layer as it was before it, going over itself doesn't darken it past the opacity
+	let stroke = points[line_in.line_start_index].stroke;
+	let id = vec2<f32>(f32((stroke >> 8u) & 255u), f32(stroke & 255u)) / 255.;
+	var mask = unpack4x8unorm(textureLoad(stroke_mask, tex_pos).x);
+	var base = unpack4x8unorm(textureLoad(stroke_base, tex_pos).x);
+	if any(round(mask.gb * 255.) != round(id * 255.)) {
+		base = unpack4x8unorm(textureLoad(tex, tex_pos).x);
+		textureStore(stroke_base, tex_pos, vec4<u32>(pack4x8unorm(base)));
+		mask = vec4<f32>(0., id, 0.);
+	}
+	// The dab covering the pixel the most picks its color, kept in alpha
+	if coverage >= mask.r {
+		mask.a = tint;
 	}
+	let built_up = mask.r + (1. - mask.r) * coverage * line_in.flow;
+	mask.r = select(built_up, max(mask.r, coverage), line_in.flow >= 1.);
+	textureStore(stroke_mask, tex_pos, vec4<u32>(pack4x8unorm(mask)));
 
-	if flag {
-		let color = vec4<f32>(1., 1., 1., 1.);
-		textureStore(tex, pos, color);
+	var color = jitter_color(unpack4x8unorm(line_in.color), mask.a);
+	if (line_in.flags & SMUDGED) != 0u {
+		color = pickup.color;
 	}
+	let amount = mask.r * line_in.opacity;
+	var painted = mix(base, color, amount);
+	if (line_in.flags & ALPHA_LOCKED) != 0u {
+		// Erasing leaves locked pixels as they are
+		let straight = select(vec3<f32>(0.), color.rgb / color.a, color.a > 0.);
+		painted = select(base, vec4<f32>(mix(base.rgb, straight * base.a, amount), base.a), color.a > 0.);
+	}
+	textureStore(tex, tex_pos, vec4<u32>(pack4x8unorm(painted)));
 }
 
+// Mixes the color under the first point of the bundle into what the stroke carries, more the further
+// the bundle goes. Strokes start carrying the brush color. Run on a single thread before draw_line.
+@compute
+@workgroup_size(1, 1, 1)
+fn pick_up() {
+	let first = points[line_in.line_start_index];
+	let tex_pos = first.pos - line_in.tile_origin;
+	let dims = textureDimensions(tex);
+	if 0 > tex_pos.x || tex_pos.x >= dims.x || 0 > tex_pos.y || tex_pos.y >= dims.y {
+		return;
+	}
+
+	if pickup.stroke != first.stroke {
+		pickup.color = unpack4x8unorm(line_in.color);
+		pickup.stroke = first.stroke;
+	}
+	let dragged = points[line_in.line_end_index - 1u].distance - first.distance;
+	// About two thirds of the color under it once dragged a brush size
+	let amount = 1. - exp(-dragged / max(2. * line_in.brush_rad, 1.));
+	pickup.color = mix(pickup.color, unpack4x8unorm(textureLoad(tex, tex_pos).x), amount);
+}
+
+struct BoundsInput {
+	background: vec3<f32>,
+	tile_origin: vec2<i32>,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(2) @binding(0)
+var<uniform> bounds_in: BoundsInput;
+#else
+var<push_constant> bounds_in: BoundsInput;
+#endif
+
+struct Bounds {
+	min_x: atomic<u32>,
+	min_y: atomic<u32>,
+	max_x: atomic<u32>,
+	max_y: atomic<u32>,
+}
+
+@group(1) @binding(1)
+var<storage, read_write> bounds: Bounds;
+
+@compute
+@workgroup_size(8, 8, 1)
+fn content_bounds(@builtin(global_invocation_id) gid: vec3<u32>) {
+	let tex_pos = vec2<i32>(gid.xy);
+	let dims = textureDimensions(tex);
+	if tex_pos.x >= dims.x || tex_pos.y >= dims.y {
+		return;
+	}
+
+	let color = unpack4x8unorm(textureLoad(tex, tex_pos).x).rgb;
+	if all(abs(color - bounds_in.background) < vec3<f32>(0.5 / 255.)) {
+		return;
+	}
+
+	let pos = vec2<u32>(tex_pos + bounds_in.tile_origin);
+	atomicMin(&bounds.min_x, pos.x);
+	atomicMin(&bounds.min_y, pos.y);
+	atomicMax(&bounds.max_x, pos.x);
+	atomicMax(&bounds.max_y, pos.y);
+}
+
+struct ReplaceInput {
+	source: vec3<f32>,
+	tolerance: f32,
+	replacement: vec3<f32>,
+	// Where the texture starts in the document
+	tile_origin: vec2<i32>,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(2) @binding(0)
+var<uniform> replace_in: ReplaceInput;
+#else
+var<push_constant> replace_in: ReplaceInput;
+#endif
+
+@compute
+@workgroup_size(8, 8, 1)
+fn replace_color(@builtin(global_invocation_id) gid: vec3<u32>) {
+	let tex_pos = vec2<i32>(gid.xy);
+	let dims = textureDimensions(tex);
+	if tex_pos.x >= dims.x || tex_pos.y >= dims.y {
+		return;
+	}
+
+	// Colors are premultiplied, compare them as painted
+	let color = unpack4x8unorm(textureLoad(tex, tex_pos).x);
+	if color.a <= 0. {
+		return;
+	}
+	if all(abs(color.rgb / color.a - replace_in.source) <= vec3<f32>(replace_in.tolerance)) {
+		let replaced = vec4<f32>(replace_in.replacement * color.a, color.a);
+		let w = selection_weight(selection, tex_pos + replace_in.tile_origin);
+		textureStore(tex, tex_pos, vec4<u32>(pack4x8unorm(mix(color, replaced, w))));
+	}
+}
@@ -0,0 +1,80 @@
+struct PickerInput {
+	// In framebuffer pixels
+	center: vec2<f32>,
+	// Outer radius of the hue ring, the square fits in its inner radius
+	radius: f32,
+	inner_radius: f32,
+	// Hue from 0 to 1, saturation and value of the picked color
+	hsv: vec3<f32>,
+	srgb_output: u32,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(0) @binding(0)
+var<uniform> picker: PickerInput;
+#else
+var<push_constant> picker: PickerInput;
+#endif
+
+@vertex
+fn vs_main(
+	@builtin(vertex_index) index: u32,
+) -> @builtin(position) vec4<f32> {
+	var pos = vec2<f32>(0., 0.);
+
+	if index % u32(2) == u32(1) {
+		pos.y = 1.;
+	}
+
+	if index == u32(0) || index >= u32(4) {
+		pos.x = 1.;
+	}
+
+	return vec4<f32>(2. * pos - 1., 1., 1.);
+}
+
+fn hsv_to_rgb(hsv: vec3<f32>) -> vec3<f32> {
+	let k = (vec3<f32>(5., 3., 1.) + hsv.x * 6.) % 6.;
+	return hsv.z - hsv.z * hsv.y * clamp(min(k, 4. - k), vec3<f32>(0.), vec3<f32>(1.));
+}
+
+#include "color.wgsl"
+
+// Opacity of a one pixel wide ring of radius r around the point d is from
+fn marker(d: vec2<f32>, r: f32) -> f32 {
+	return 1. - smoothstep(0.5, 1.5, abs(length(d) - r));
+}
+
+@fragment
+fn fs_main(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+	let d = frag.xy - picker.center;
+	let dist = length(d);
+	// Hue clockwise from the top
+	let hue = fract(atan2(d.x, -d.y) / 6.28318530718 + 1.);
+	let half_side = picker.inner_radius * 0.65;
+
+	var color = vec4<f32>(0.);
+	if dist >= picker.inner_radius && dist <= picker.radius {
+		let edge = min(dist - picker.inner_radius, picker.radius - dist);
+		color = vec4<f32>(hsv_to_rgb(vec3<f32>(hue, 1., 1.)), clamp(edge + 0.5, 0., 1.));
+	} else if all(abs(d) <= vec2<f32>(half_side)) {
+		let sv = vec2<f32>(d.x / half_side, -d.y / half_side) * 0.5 + 0.5;
+		color = vec4<f32>(hsv_to_rgb(vec3<f32>(picker.hsv.x, sv)), 1.);
+	}
+
+	// Markers on the picked hue and saturation and value, dark inside light
+	let ring_r = (picker.radius + picker.inner_radius) / 2.;
+	let angle = picker.hsv.x * 6.28318530718;
+	let hue_at = vec2<f32>(sin(angle), -cos(angle)) * ring_r;
+	let sv_at = vec2<f32>(picker.hsv.y * 2. - 1., 1. - picker.hsv.z * 2.) * half_side;
+	let ring_width = picker.radius - picker.inner_radius;
+	let light = max(marker(d - hue_at, ring_width / 2.), marker(d - sv_at, 5.));
+	let dark = max(marker(d - hue_at, ring_width / 2. - 1.5), marker(d - sv_at, 3.5));
+	color = mix(color, vec4<f32>(0., 0., 0., 1.), dark);
+	color = mix(color, vec4<f32>(1., 1., 1., 1.), light);
+
+	if picker.srgb_output == 1u {
+		color = vec4<f32>(srgb_to_linear(color.rgb), color.a);
+	}
+	return color;
+}
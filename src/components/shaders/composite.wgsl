@@ -0,0 +1,53 @@
+// Tiles hold rgba8 packed in r32uint, like those of canvas.wgsl
+@group(0) @binding(0)
+var composite: texture_storage_2d<r32uint, read_write>;
+
+@group(1) @binding(0)
+var layer: texture_storage_2d<r32uint, read_write>;
+
+#ifdef MASKED
+// How much of the layer shows, by its lightness
+@group(2) @binding(0)
+var mask: texture_storage_2d<r32uint, read_write>;
+#endif
+
+struct CompositeInput {
+	// First pixel of the damaged region, in tile coordinates
+	origin: vec2<i32>,
+	opacity: f32,
+	// The bottom layer replaces the composite instead of blending over it
+	first: u32,
+}
+
+// After the mask when there is one
+#ifdef UNIFORM_BUFFER
+#ifdef MASKED
+@group(3) @binding(0)
+#else
+@group(2) @binding(0)
+#endif
+var<uniform> composite_in: CompositeInput;
+#else
+var<push_constant> composite_in: CompositeInput;
+#endif
+
+// Layers hold premultiplied colors, blended bottom to top with the "over" operator.
+@compute
+@workgroup_size(8, 8, 1)
+fn composite_layer(@builtin(global_invocation_id) gid: vec3<u32>) {
+	let pos = vec2<i32>(gid.xy) + composite_in.origin;
+	let dims = textureDimensions(composite);
+	if pos.x >= dims.x || pos.y >= dims.y {
+		return;
+	}
+
+	var src = unpack4x8unorm(textureLoad(layer, pos).x) * composite_in.opacity;
+#ifdef MASKED
+	src *= dot(unpack4x8unorm(textureLoad(mask, pos).x).rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+#endif
+	var dst = vec4<f32>(0.);
+	if composite_in.first == 0u {
+		dst = unpack4x8unorm(textureLoad(composite, pos).x);
+	}
+	textureStore(composite, pos, vec4<u32>(pack4x8unorm(src + dst * (1. - src.a))));
+}
@@ -0,0 +1,141 @@
+// Rgba8 packed in r32uint, like the tiles of canvas.wgsl
+@group(0) @binding(0)
+var tex: texture_storage_2d<r32uint, read_write>;
+
+struct FillInput {
+	// Premultiplied color of the clicked pixel
+	seed_color: vec4<f32>,
+	// Where the bound texture starts in the document
+	tile_origin: vec2<i32>,
+	document: vec2<u32>,
+	tolerance: f32,
+	// Premultiplied fill color packed as rgba8
+	color: u32,
+	// Fill under the layer pixels: spread through everything less opaque than the tolerance allows, and go behind it
+	behind: u32,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(3) @binding(0)
+var<uniform> fill_in: FillInput;
+#else
+var<push_constant> fill_in: FillInput;
+#endif
+
+// One bit per document pixel, set where the fill reached, so the mask of the biggest documents
+// still fits in a storage buffer binding
+@group(1) @binding(0)
+var<storage, read_write> mask: array<atomic<u32>>;
+
+// Pixels the last sweeps added to the fill, no more sweeps needed once it stays at zero
+@group(1) @binding(1)
+var<storage, read_write> changed: atomic<u32>;
+
+#include "selection.wgsl"
+
+// The fill doesn't spread out of the selection, and fades out along its feathered edges
+@group(2) @binding(0)
+var<uniform> selection: SelectionMask;
+
+fn matches(pos: vec2<i32>) -> bool {
+	if selection_weight(selection, pos + fill_in.tile_origin) <= 0. {
+		return false;
+	}
+	if fill_in.behind != 0u {
+		return unpack4x8unorm(textureLoad(tex, pos).x).a < 1. - fill_in.tolerance;
+	}
+	return all(abs(unpack4x8unorm(textureLoad(tex, pos).x) - fill_in.seed_color) <= vec4<f32>(fill_in.tolerance));
+}
+
+fn mask_index(pos: vec2<i32>) -> u32 {
+	let doc = vec2<u32>(pos + fill_in.tile_origin);
+	return doc.y * fill_in.document.x + doc.x;
+}
+
+fn is_filled(i: u32) -> bool {
+	return (atomicLoad(&mask[i / 32u]) & (1u << (i % 32u))) != 0u;
+}
+
+// Whether the fill reached `pos` of a neighbouring tile, where a sweep coming from it starts. False past the document.
+fn filled_across(pos: vec2<i32>) -> bool {
+	let doc = pos + fill_in.tile_origin;
+	if any(doc < vec2<i32>(0)) || any(vec2<u32>(doc) >= fill_in.document) {
+		return false;
+	}
+	return is_filled(mask_index(pos));
+}
+
+// Grows the fill to `pos` if the pixel before it in the sweep is filled, returns if `pos` is filled.
+fn sweep(pos: vec2<i32>, previous: bool) -> bool {
+	let i = mask_index(pos);
+	if is_filled(i) {
+		return true;
+	}
+	if previous && matches(pos) {
+		// Other invocations set the other bits of the word
+		atomicOr(&mask[i / 32u], 1u << (i % 32u));
+		atomicAdd(&changed, 1u);
+		return true;
+	}
+	return false;
+}
+
+// Sweeps a row both ways, the fill spreads along it in one pass
+@compute
+@workgroup_size(64, 1, 1)
+fn fill_rows(@builtin(global_invocation_id) gid: vec3<u32>) {
+	let dims = textureDimensions(tex);
+	let y = i32(gid.x);
+	if y >= dims.y {
+		return;
+	}
+
+	var filled = filled_across(vec2<i32>(-1, y));
+	for (var x = 0; x < dims.x; x++) {
+		filled = sweep(vec2<i32>(x, y), filled);
+	}
+	filled = filled_across(vec2<i32>(dims.x, y));
+	for (var x = dims.x - 1; x >= 0; x--) {
+		filled = sweep(vec2<i32>(x, y), filled);
+	}
+}
+
+@compute
+@workgroup_size(64, 1, 1)
+fn fill_columns(@builtin(global_invocation_id) gid: vec3<u32>) {
+	let dims = textureDimensions(tex);
+	let x = i32(gid.x);
+	if x >= dims.x {
+		return;
+	}
+
+	var filled = filled_across(vec2<i32>(x, -1));
+	for (var y = 0; y < dims.y; y++) {
+		filled = sweep(vec2<i32>(x, y), filled);
+	}
+	filled = filled_across(vec2<i32>(x, dims.y));
+	for (var y = dims.y - 1; y >= 0; y--) {
+		filled = sweep(vec2<i32>(x, y), filled);
+	}
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn fill_apply(@builtin(global_invocation_id) gid: vec3<u32>) {
+	let pos = vec2<i32>(gid.xy);
+	let dims = textureDimensions(tex);
+	if pos.x >= dims.x || pos.y >= dims.y {
+		return;
+	}
+
+	if is_filled(mask_index(pos)) {
+		let color = unpack4x8unorm(fill_in.color);
+		let layer = unpack4x8unorm(textureLoad(tex, pos).x);
+		var filled = color;
+		if fill_in.behind != 0u {
+			filled = layer + color * (1. - layer.a);
+		}
+		let w = selection_weight(selection, pos + fill_in.tile_origin);
+		textureStore(tex, pos, vec4<u32>(pack4x8unorm(mix(layer, filled, w))));
+	}
+}
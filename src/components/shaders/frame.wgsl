@@ -0,0 +1,39 @@
+// What the window shows, kept between frames
+@group(0) @binding(1)
+var frame: texture_2d<f32>;
+
+// Linear, as it is written to the frame
+#ifdef UNIFORM_BUFFER
+@group(0) @binding(0)
+var<uniform> fill_color: vec4<f32>;
+#else
+var<push_constant> fill_color: vec4<f32>;
+#endif
+
+@vertex
+fn vs_main(
+	@builtin(vertex_index) index: u32,
+) -> @builtin(position) vec4<f32> {
+	var pos = vec2<f32>(0., 0.);
+
+	if index % u32(2) == u32(1) {
+		pos.y = 1.;
+	}
+
+	if index == u32(0) || index >= u32(4) {
+		pos.x = 1.;
+	}
+
+	return vec4<f32>(2. * pos - 1., 1., 1.);
+}
+
+// Same size as the surface, so each fragment copies its texel
+@fragment
+fn fs_copy(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+	return textureLoad(frame, vec2<i32>(frag.xy), 0);
+}
+
+@fragment
+fn fs_fill(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+	return fill_color;
+}
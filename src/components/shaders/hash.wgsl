@@ -0,0 +1,65 @@
+// Rgba8 packed in r32uint, like the tiles of canvas.wgsl
+@group(0) @binding(0)
+var tex: texture_storage_2d<r32uint, read_write>;
+
+@group(1) @binding(0)
+var<storage, read_write> hashes: array<atomic<u32>>;
+
+struct HashInput {
+	// Where the bound texture starts in the document
+	tile_origin: vec2<i32>,
+	// First pixel of the region of blocks being hashed, in the document
+	region_origin: vec2<i32>,
+	region_blocks: vec2<u32>,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(2) @binding(0)
+var<uniform> hash_in: HashInput;
+#else
+var<push_constant> hash_in: HashInput;
+#endif
+
+// Side of the square blocks hashed independently, matches canvas.rs
+let BLOCK: u32 = 64u;
+
+fn finalize(v: u32) -> u32 {
+	var h = v;
+	h = h ^ (h >> 16u);
+	h = h * 0x85ebca6bu;
+	h = h ^ (h >> 13u);
+	h = h * 0xc2b2ae35u;
+	return h ^ (h >> 16u);
+}
+
+// Every invocation hashes one pixel row of a block, rows are salted with their row in the
+// document and xor-ed together so the order they finish in doesn't matter.
+@compute
+@workgroup_size(8, 8, 1)
+fn block_hash(@builtin(global_invocation_id) gid: vec3<u32>) {
+	let block_x = gid.x;
+	let row = gid.y;
+	if block_x >= hash_in.region_blocks.x || row >= hash_in.region_blocks.y * BLOCK {
+		return;
+	}
+
+	let dims = textureDimensions(tex);
+	let y = hash_in.region_origin.y + i32(row) - hash_in.tile_origin.y;
+	if y < 0 || y >= dims.y {
+		return;
+	}
+
+	let start = hash_in.region_origin.x + i32(block_x * BLOCK) - hash_in.tile_origin.x;
+	let end = min(start + i32(BLOCK), dims.x);
+	if end <= max(start, 0) {
+		return;
+	}
+
+	var h = 2166136261u;
+	for (var x = max(start, 0); x < end; x++) {
+		h = (h ^ textureLoad(tex, vec2<i32>(x, y)).x) * 16777619u;
+	}
+
+	let index = (row / BLOCK) * hash_in.region_blocks.x + block_x;
+	atomicXor(&hashes[index], finalize(h ^ (u32(hash_in.region_origin.y + i32(row)) * 0x9e3779b9u)));
+}
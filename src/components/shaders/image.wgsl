@@ -28,11 +28,45 @@ fn vs_main(
 
 @group(0)
 @binding(0)
+#ifdef PACKED
+var tex: texture_storage_2d<r32uint, read>;
+#else
 var tex: texture_storage_2d<rgba8unorm, read>;
+#endif
+
+struct ImageInput {
+	// Texels drawn, the whole texture when the size is zero
+	source_origin: vec2<i32>,
+	source_size: vec2<u32>,
+	srgb_output: u32,
+	opacity: f32,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(1) @binding(0)
+var<uniform> image_in: ImageInput;
+#else
+var<push_constant> image_in: ImageInput;
+#endif
+
+#include "color.wgsl"
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-	let dim = textureDimensions(tex);
-	var pos = vec2<i32>(i32(in.pos.x * f32(dim.x)), i32((1. - in.pos.y) * f32(dim.y)));
-	return textureLoad(tex, pos);
+	var origin = vec2<i32>(0, 0);
+	var dim = textureDimensions(tex);
+	if image_in.source_size.x != 0u {
+		origin = image_in.source_origin;
+		dim = vec2<i32>(image_in.source_size);
+	}
+	var pos = origin + vec2<i32>(i32(in.pos.x * f32(dim.x)), i32((1. - in.pos.y) * f32(dim.y)));
+#ifdef PACKED
+	var color = unpack4x8unorm(textureLoad(tex, pos).x);
+#else
+	var color = textureLoad(tex, pos);
+#endif
+	if image_in.srgb_output == u32(1) {
+		color = vec4<f32>(srgb_to_linear(color.rgb), color.a);
+	}
+	return vec4<f32>(color.rgb, color.a * image_in.opacity);
 }
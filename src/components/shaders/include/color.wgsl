@@ -0,0 +1,5 @@
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+	let low = c / 12.92;
+	let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+	return select(high, low, c <= vec3<f32>(0.04045));
+}
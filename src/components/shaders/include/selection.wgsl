@@ -0,0 +1,23 @@
+// Region the selection tools act on in document pixels, and how far its edges fade in
+struct SelectionMask {
+	origin: vec2<i32>,
+	size: vec2<u32>,
+	feather: f32,
+	// 0 when nothing is selected, everything counts as selected then
+	selected: u32,
+	_pad: vec2<u32>,
+}
+
+// How much of the document pixel at `pos` is selected, 0 outside to 1 inside. Sharp edges take
+// the pixels they cover, feathered ones fade in over `feather` pixels from the border, smoothly
+// along each side and around the corners.
+fn selection_weight(selection: SelectionMask, pos: vec2<i32>) -> f32 {
+	if selection.selected == 0u {
+		return 1.;
+	}
+	let center = vec2<f32>(pos) + 0.5;
+	let start = vec2<f32>(selection.origin);
+	let inside = min(center - start, start + vec2<f32>(selection.size) - center) + 0.5;
+	let w = smoothstep(vec2<f32>(0.), vec2<f32>(max(selection.feather, 1.)), inside);
+	return w.x * w.y;
+}
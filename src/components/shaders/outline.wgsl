@@ -0,0 +1,55 @@
+struct OutlineInput {
+	color: vec4<f32>,
+	// x, y, w, h in framebuffer pixels
+	rect: vec4<f32>,
+	width: f32,
+	srgb_output: u32,
+	// Dash length, 0 for a solid border
+	dash: f32,
+	phase: f32,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(0) @binding(0)
+var<uniform> outline: OutlineInput;
+#else
+var<push_constant> outline: OutlineInput;
+#endif
+
+@vertex
+fn vs_main(
+	@builtin(vertex_index) index: u32,
+) -> @builtin(position) vec4<f32> {
+	var pos = vec2<f32>(0., 0.);
+
+	if index % u32(2) == u32(1) {
+		pos.y = 1.;
+	}
+
+	if index == u32(0) || index >= u32(4) {
+		pos.x = 1.;
+	}
+
+	return vec4<f32>(2. * pos - 1., 1., 1.);
+}
+
+#include "color.wgsl"
+
+@fragment
+fn fs_main(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+	let local = frag.xy - outline.rect.xy;
+	let w = outline.width;
+	if local.x >= w && local.y >= w && local.x < outline.rect.z - w && local.y < outline.rect.w - w {
+		discard;
+	}
+
+	var color = outline.color;
+	if outline.dash > 0. && fract((local.x + local.y + outline.phase) / (2. * outline.dash)) >= 0.5 {
+		color = vec4<f32>(1. - color.rgb, color.a);
+	}
+
+	if outline.srgb_output == u32(1) {
+		return vec4<f32>(srgb_to_linear(color.rgb), color.a);
+	}
+	return color;
+}
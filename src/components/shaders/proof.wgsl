@@ -0,0 +1,121 @@
+// Tiles hold rgba8 packed in r32uint, like those of canvas.wgsl
+@group(0) @binding(0)
+var composite: texture_storage_2d<r32uint, read_write>;
+
+@group(1) @binding(0)
+var proof: texture_storage_2d<r32uint, read_write>;
+
+struct ProofInput {
+	// First pixel of the damaged region, in tile coordinates
+	origin: vec2<i32>,
+	// Of the tile in the document, so dithering lines up across tiles
+	tile_pos: vec2<i32>,
+	// 0: 16 color palette, 1: 1-bit e-ink, 2: print
+	target_kind: u32,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(2) @binding(0)
+var<uniform> proof_in: ProofInput;
+#else
+var<push_constant> proof_in: ProofInput;
+#endif
+
+// The 16 colors of the PICO-8 palette
+let PALETTE_SIZE: u32 = 16u;
+var<private> PALETTE: array<vec3<f32>, 16> = array<vec3<f32>, 16>(
+	vec3<f32>(0., 0., 0.),
+	vec3<f32>(0.114, 0.169, 0.325),
+	vec3<f32>(0.494, 0.145, 0.325),
+	vec3<f32>(0., 0.529, 0.318),
+	vec3<f32>(0.671, 0.322, 0.212),
+	vec3<f32>(0.373, 0.341, 0.310),
+	vec3<f32>(0.761, 0.765, 0.780),
+	vec3<f32>(1., 0.945, 0.910),
+	vec3<f32>(1., 0., 0.302),
+	vec3<f32>(1., 0.639, 0.),
+	vec3<f32>(1., 0.925, 0.153),
+	vec3<f32>(0., 0.894, 0.212),
+	vec3<f32>(0.161, 0.678, 1.),
+	vec3<f32>(0.514, 0.463, 0.612),
+	vec3<f32>(1., 0.467, 0.659),
+	vec3<f32>(1., 0.8, 0.667),
+);
+
+// 4x4 ordered dithering thresholds, row major
+var<private> BAYER: array<f32, 16> = array<f32, 16>(
+	0., 8., 2., 10.,
+	12., 4., 14., 6.,
+	3., 11., 1., 9.,
+	15., 7., 13., 5.,
+);
+
+fn nearest_palette_color(color: vec3<f32>) -> vec3<f32> {
+	var best = PALETTE[0];
+	var best_distance = 4.;
+	for (var i = 0u; i < PALETTE_SIZE; i++) {
+		let d = color - PALETTE[i];
+		let distance = dot(d, d);
+		if distance < best_distance {
+			best = PALETTE[i];
+			best_distance = distance;
+		}
+	}
+	return best;
+}
+
+fn eink(color: vec3<f32>, pos: vec2<i32>) -> vec3<f32> {
+	let lightness = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+	let threshold = (BAYER[(pos.y & 3) * 4 + (pos.x & 3)] + 0.5) / 16.;
+	// Black ink on a grey panel
+	if lightness > threshold {
+		return vec3<f32>(0.86, 0.86, 0.82);
+	}
+	return vec3<f32>(0.16, 0.16, 0.17);
+}
+
+// Inks soak in and lose saturation, limited to what paper holds, then printed on off-white paper.
+fn print(color: vec3<f32>) -> vec3<f32> {
+	let k = 1. - max(color.r, max(color.g, color.b));
+	var cmy = vec3<f32>(0.);
+	if k < 1. {
+		cmy = (1. - color - k) / (1. - k);
+	}
+	// Total ink coverage above 280% is cut from the colored inks
+	let coverage = cmy.r + cmy.g + cmy.b + k;
+	if coverage > 2.8 {
+		cmy *= max(2.8 - k, 0.) / (coverage - k);
+	}
+	let grey = dot(cmy, vec3<f32>(1. / 3.));
+	cmy = mix(vec3<f32>(grey), cmy, 0.85);
+	let paper = vec3<f32>(0.96, 0.95, 0.91);
+	// Black ink doesn't get darker than this
+	return paper * (1. - cmy) * (1. - k * 0.92);
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn proof_tile(@builtin(global_invocation_id) gid: vec3<u32>) {
+	let pos = vec2<i32>(gid.xy) + proof_in.origin;
+	let dims = textureDimensions(composite);
+	if pos.x >= dims.x || pos.y >= dims.y {
+		return;
+	}
+
+	// Proofed over the paper or panel, so transparent parts show what they print as
+	let src = unpack4x8unorm(textureLoad(composite, pos).x);
+	let color = src.rgb + vec3<f32>(1. - src.a);
+	var proofed: vec3<f32>;
+	switch proof_in.target_kind {
+		case 0u: {
+			proofed = nearest_palette_color(color);
+		}
+		case 1u: {
+			proofed = eink(color, pos + proof_in.tile_pos);
+		}
+		default: {
+			proofed = print(color);
+		}
+	}
+	textureStore(proof, pos, vec4<u32>(pack4x8unorm(vec4<f32>(proofed, 1.))));
+}
@@ -0,0 +1,40 @@
+struct ShadowInput {
+	// x, y, w, h in framebuffer pixels of what casts the shadow, already offset
+	caster: vec4<f32>,
+	radius: f32,
+	opacity: f32,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(0) @binding(0)
+var<uniform> shadow: ShadowInput;
+#else
+var<push_constant> shadow: ShadowInput;
+#endif
+
+@vertex
+fn vs_main(
+	@builtin(vertex_index) index: u32,
+) -> @builtin(position) vec4<f32> {
+	var pos = vec2<f32>(0., 0.);
+
+	if index % u32(2) == u32(1) {
+		pos.y = 1.;
+	}
+
+	if index == u32(0) || index >= u32(4) {
+		pos.x = 1.;
+	}
+
+	return vec4<f32>(2. * pos - 1., 1., 1.);
+}
+
+@fragment
+fn fs_main(@builtin(position) frag: vec4<f32>) -> @location(0) vec4<f32> {
+	let half = shadow.caster.zw / 2.;
+	let center = shadow.caster.xy + half;
+	let dist = length(max(abs(frag.xy - center) - half, vec2<f32>(0.)));
+	let falloff = 1. - smoothstep(0., shadow.radius, dist);
+	// Black needs no sRGB conversion
+	return vec4<f32>(0., 0., 0., shadow.opacity * falloff * falloff);
+}
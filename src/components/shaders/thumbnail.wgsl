@@ -0,0 +1,47 @@
+// Rgba8 packed in r32uint, like the tiles of canvas.wgsl
+@group(0) @binding(0)
+var canvas: texture_storage_2d<r32uint, read_write>;
+
+@group(1) @binding(0)
+var thumbnail: texture_storage_2d<rgba8unorm, write>;
+
+struct ThumbnailInput {
+	tile_origin: vec2<i32>,
+	cell_origin: vec2<u32>,
+	cell_size: vec2<u32>,
+	scale: u32,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(2) @binding(0)
+var<uniform> input: ThumbnailInput;
+#else
+var<push_constant> input: ThumbnailInput;
+#endif
+
+// Box filter of `scale`² canvas pixels for every thumbnail pixel of the cell, limited to the
+// pixels of this tile.
+@compute @workgroup_size(8, 8)
+fn downscale(@builtin(global_invocation_id) id: vec3<u32>) {
+	if (id.x >= input.cell_size.x || id.y >= input.cell_size.y) {
+		return;
+	}
+
+	let t = input.cell_origin + id.xy;
+	let start = vec2<i32>(t * input.scale) - input.tile_origin;
+	let dims = vec2<i32>(textureDimensions(canvas));
+	if (any(start < vec2<i32>(0)) || any(start >= dims)) {
+		return;
+	}
+
+	let end = min(start + vec2<i32>(i32(input.scale)), dims);
+	var sum = vec4<f32>(0.);
+	for (var y = start.y; y < end.y; y++) {
+		for (var x = start.x; x < end.x; x++) {
+			sum += unpack4x8unorm(textureLoad(canvas, vec2<i32>(x, y)).x);
+		}
+	}
+
+	let count = f32((end.x - start.x) * (end.y - start.y));
+	textureStore(thumbnail, vec2<i32>(t), sum / count);
+}
@@ -0,0 +1,57 @@
+// Tiles hold rgba8 packed in r32uint, like those of canvas.wgsl
+@group(0) @binding(0)
+var tex: texture_storage_2d<r32uint, read_write>;
+
+// Packed too, so it is filtered here
+@group(1) @binding(0)
+var src: texture_2d<u32>;
+
+struct WarpInput {
+	// Maps document positions of the output back to where they come from
+	inverse: mat3x3<f32>,
+	src_origin: vec2<i32>,
+	tile_origin: vec2<i32>,
+}
+
+#ifdef UNIFORM_BUFFER
+@group(2) @binding(0)
+var<uniform> warp_in: WarpInput;
+#else
+var<push_constant> warp_in: WarpInput;
+#endif
+
+// Bilinear sample of `src` at `s` in texels, clamped to its edges like a sampler would.
+fn sample_bilinear(s: vec2<f32>) -> vec4<f32> {
+	let last = textureDimensions(src) - 1;
+	let t = s - 0.5;
+	let f = fract(t);
+	let p = vec2<i32>(floor(t));
+	let p0 = clamp(p, vec2<i32>(0), last);
+	let p1 = clamp(p + 1, vec2<i32>(0), last);
+	let top = mix(unpack4x8unorm(textureLoad(src, p0, 0).x), unpack4x8unorm(textureLoad(src, vec2<i32>(p1.x, p0.y), 0).x), f.x);
+	let bottom = mix(unpack4x8unorm(textureLoad(src, vec2<i32>(p0.x, p1.y), 0).x), unpack4x8unorm(textureLoad(src, p1, 0).x), f.x);
+	return mix(top, bottom, f.y);
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn warp(@builtin(global_invocation_id) gid: vec3<u32>) {
+	let tex_pos = vec2<i32>(gid.xy);
+	let dims = textureDimensions(tex);
+	if tex_pos.x >= dims.x || tex_pos.y >= dims.y {
+		return;
+	}
+
+	let p = warp_in.inverse * vec3<f32>(vec2<f32>(tex_pos + warp_in.tile_origin) + 0.5, 1.);
+	if p.z <= 0. {
+		return;
+	}
+
+	let src_dims = vec2<f32>(textureDimensions(src));
+	let s = p.xy / p.z - vec2<f32>(warp_in.src_origin);
+	if s.x < 0. || s.y < 0. || s.x >= src_dims.x || s.y >= src_dims.y {
+		return;
+	}
+
+	textureStore(tex, tex_pos, vec4<u32>(pack4x8unorm(sample_bilinear(s))));
+}
@@ -0,0 +1,124 @@
+use winit::event::VirtualKeyCode;
+
+use crate::components::{widget_focus_ring, widget_text_top, Context, Control, DrawnWidget, Event, Point, Rect, Size, Widget, WidgetImage, WIDGET_BACKGROUND, WIDGET_SCALE, WIDGET_TEXT};
+use crate::export::Pixels;
+use crate::font;
+
+const FILLED: [u8; 4] = [70, 70, 84, 255];
+const DRAGGED: [u8; 4] = [90, 90, 110, 255];
+
+/// Bar filled up to the value of its control, set by pressing and dragging along it, or with the
+/// arrow keys while focused. Emits `Event::ValueChanged` as it changes.
+pub struct Slider {
+	image: WidgetImage<(Size, f32, bool, bool)>,
+	control: Control,
+	value: f32,
+	dragging: bool,
+	focused: bool,
+}
+
+impl Slider {
+	pub fn set_control(&mut self, control: Control) {
+		self.control = control;
+		self.value = control.clamp(self.value);
+		self.image.invalidate();
+	}
+
+	/// Shows `value`, unless the slider is being dragged.
+	pub fn set_value(&mut self, value: f32) {
+		if !self.dragging {
+			self.value = self.control.clamp(value);
+		}
+	}
+
+	fn drag_to(&mut self, ctx: &mut Context, viewport: Rect, p: Point) -> bool {
+		let fraction = (p.x - viewport.pos.x) as f32 / viewport.size.w.max(1) as f32;
+		let value = self.control.value_at(fraction);
+		if value == self.value {
+			return false;
+		}
+		self.value = value;
+		ctx.emit(Event::ValueChanged(self.control, value));
+		return true;
+	}
+}
+
+impl Widget for Slider {
+	fn press(&mut self, ctx: &mut Context, viewport: Rect, p: Point) -> bool {
+		self.dragging = true;
+		self.drag_to(ctx, viewport, p);
+		return true;
+	}
+
+	fn drag(&mut self, ctx: &mut Context, viewport: Rect, p: Point) -> bool {
+		return self.drag_to(ctx, viewport, p);
+	}
+
+	fn release(&mut self, _: &mut Context) {
+		self.dragging = false;
+	}
+
+	fn focusable(&self) -> bool {
+		true
+	}
+
+	fn set_focused(&mut self, _: &mut Context, focused: bool) {
+		self.focused = focused;
+	}
+
+	/// Left and right move the value a step, Home and End to the ends of the range.
+	fn key_input(&mut self, ctx: &mut Context, key: VirtualKeyCode) -> bool {
+		let (min, max) = self.control.range();
+		let value = match key {
+			VirtualKeyCode::Left | VirtualKeyCode::Down => self.control.step(self.value, -1.),
+			VirtualKeyCode::Right | VirtualKeyCode::Up => self.control.step(self.value, 1.),
+			VirtualKeyCode::Home => min,
+			VirtualKeyCode::End => max,
+			_ => return false,
+		};
+		if value != self.value {
+			self.value = value;
+			ctx.emit(Event::ValueChanged(self.control, value));
+		}
+		return true;
+	}
+}
+
+impl DrawnWidget for Slider {
+	/// Size, value, dragging and focus state
+	type State = (Size, f32, bool, bool);
+	const LABEL: &'static str = "Slider(Texture)";
+
+	fn with_image(image: WidgetImage<Self::State>) -> Self {
+		let control = Control::BrushSize;
+		Self {
+			image,
+			control,
+			value: control.range().0,
+			dragging: false,
+			focused: false,
+		}
+	}
+
+	fn image(&mut self) -> &mut WidgetImage<Self::State> {
+		&mut self.image
+	}
+
+	fn state(&self, size: Size) -> Option<Self::State> {
+		return Some((size, self.value, self.dragging, self.focused));
+	}
+
+	fn draw(&self, size: Size, _: &Self::State) -> Pixels {
+		let mut pixels = Pixels::new(size);
+		font::fill(&mut pixels, 0, 0, size.w, size.h, WIDGET_BACKGROUND);
+		let filled = (self.control.fraction(self.value) * size.w as f32).round() as u32;
+		font::fill(&mut pixels, 0, 0, filled, size.h, if self.dragging { DRAGGED } else { FILLED });
+
+		let text = format!("{} {}", self.control.label(), self.control.format(self.value));
+		font::draw_text(&mut pixels, 2 * WIDGET_SCALE, widget_text_top(size.h), &text, WIDGET_TEXT, WIDGET_SCALE);
+		if self.focused {
+			widget_focus_ring(&mut pixels);
+		}
+		return pixels;
+	}
+}
@@ -0,0 +1,100 @@
+use crate::components::{ColorSlot, Context, DrawnWidget, Event, Point, Rect, Size, Widget, WidgetImage};
+use crate::export::Pixels;
+use crate::font;
+use crate::swatches::SwatchSet;
+
+/// Window pixels across a swatch, gap included
+pub const SWATCH_CELL: u32 = 20;
+const GAP: u32 = 2;
+const BACKGROUND: [u8; 4] = [32, 32, 36, 235];
+const CURRENT: [u8; 4] = [230, 230, 230, 255];
+
+/// Recent colors on the top row and pinned ones under them. Clicking one emits
+/// `Event::ColorChanged` for the foreground color.
+pub struct Swatches {
+	image: WidgetImage<Size>,
+	swatches: SwatchSet,
+	/// Outlined, the foreground color
+	current: Option<[u8; 3]>,
+}
+
+impl Swatches {
+	fn rows(&self) -> [&[[u8; 3]]; 2] {
+		[&self.swatches.recent, &self.swatches.pinned]
+	}
+
+	/// Window pixels the panel takes, nothing without swatches.
+	pub fn size(&self) -> Size {
+		let columns = self.rows().iter().map(|r| r.len()).max().unwrap_or(0) as u32;
+		let rows = self.rows().iter().filter(|r| !r.is_empty()).count() as u32;
+		return Size { w: columns * SWATCH_CELL + GAP, h: rows * SWATCH_CELL + GAP };
+	}
+
+	pub fn set_swatches(&mut self, swatches: &SwatchSet) {
+		if *swatches != self.swatches {
+			self.swatches = swatches.clone();
+			self.image.invalidate();
+		}
+	}
+
+	pub fn set_current(&mut self, color: [u8; 3]) {
+		if self.current != Some(color) {
+			self.current = Some(color);
+			self.image.invalidate();
+		}
+	}
+
+	fn color_at(&self, viewport: Rect, p: Point) -> Option<[u8; 3]> {
+		let (x, y) = (((p.x - viewport.pos.x) as u32).saturating_sub(GAP / 2) / SWATCH_CELL, ((p.y - viewport.pos.y) as u32).saturating_sub(GAP / 2) / SWATCH_CELL);
+		let row = self.rows().into_iter().filter(|r| !r.is_empty()).nth(y as usize)?;
+		return row.get(x as usize).copied();
+	}
+}
+
+impl Widget for Swatches {
+	fn press(&mut self, ctx: &mut Context, viewport: Rect, p: Point) -> bool {
+		if let Some(color) = self.color_at(viewport, p) {
+			ctx.emit(Event::ColorChanged(ColorSlot::Foreground, color.map(|c| c as f32 / 255.)));
+		}
+		return true;
+	}
+}
+
+impl DrawnWidget for Swatches {
+	/// Size of the panel, drawn again when the swatches change
+	type State = Size;
+	const LABEL: &'static str = "Swatches(Texture)";
+
+	fn with_image(image: WidgetImage<Self::State>) -> Self {
+		Self {
+			image,
+			swatches: SwatchSet::default(),
+			current: None,
+		}
+	}
+
+	fn image(&mut self) -> &mut WidgetImage<Self::State> {
+		&mut self.image
+	}
+
+	fn state(&self, _: Size) -> Option<Self::State> {
+		let size = self.size();
+		return (size.w > GAP && size.h > GAP).then_some(size);
+	}
+
+	fn draw(&self, _: Size, &size: &Size) -> Pixels {
+		let mut pixels = Pixels::new(size);
+		font::fill(&mut pixels, 0, 0, size.w, size.h, BACKGROUND);
+		for (y, row) in self.rows().into_iter().filter(|r| !r.is_empty()).enumerate() {
+			for (x, &[r, g, b]) in row.iter().enumerate() {
+				let (left, top) = (x as u32 * SWATCH_CELL + GAP, y as u32 * SWATCH_CELL + GAP);
+				let side = SWATCH_CELL - GAP;
+				if self.current == Some([r, g, b]) {
+					font::fill(&mut pixels, left - GAP / 2, top - GAP / 2, side + GAP, side + GAP, CURRENT);
+				}
+				font::fill(&mut pixels, left, top, side, side, [r, g, b, 255]);
+			}
+		}
+		return pixels;
+	}
+}
@@ -0,0 +1,141 @@
+use crate::components::Point;
+
+/// Most copies turned around the center
+pub const MAX_FOLDS: u32 = 16;
+
+/// Copies of every stroke turned around a center, and mirrored across an axis through it, painted along with it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Symmetry {
+	/// Document point the copies turn around
+	pub center: [f32; 2],
+	/// Copies evenly turned around the center, the stroke itself included
+	pub folds: u32,
+	/// Degrees clockwise from vertical of an axis each turned copy is mirrored across too, doubling them
+	pub mirror: Option<f32>,
+}
+
+/// How a copy of the stroke is placed, from the stroke itself
+#[derive(Copy, Clone, Debug)]
+pub struct SymmetryCopy {
+	center: [f32; 2],
+	matrix: [[f32; 2]; 2],
+	/// Radians stamped tips are turned by. Mirrored tips turn the other way before being flipped
+	pub turn: f32,
+	pub mirrored: bool,
+}
+
+impl Symmetry {
+	/// Turned copies and their mirror images, like the patterns of a kaleidoscope.
+	pub fn kaleidoscope(center: [f32; 2], folds: u32) -> Self {
+		Self { center, folds, mirror: Some(0.) }
+	}
+
+	/// The stroke mirrored left to right across a vertical axis.
+	pub fn horizontal(center: [f32; 2]) -> Self {
+		Self { center, folds: 1, mirror: Some(0.) }
+	}
+
+	/// The stroke mirrored top to bottom across a horizontal axis.
+	pub fn vertical(center: [f32; 2]) -> Self {
+		Self { center, folds: 1, mirror: Some(90.) }
+	}
+
+	/// Mirrored both ways, four copies in all.
+	pub fn both(center: [f32; 2]) -> Self {
+		Self { center, folds: 2, mirror: Some(0.) }
+	}
+
+	/// Copies turned around the center, none mirrored.
+	pub fn radial(center: [f32; 2], folds: u32) -> Self {
+		Self { center, folds, mirror: None }
+	}
+
+	/// Rays from the center along the axes, and the degrees clockwise from up of the first.
+	/// Mirrored copies flip across lines through the center, each drawn as two rays.
+	pub fn axes(&self) -> (u32, f32) {
+		let folds = self.folds.clamp(1, MAX_FOLDS);
+		return match self.mirror {
+			Some(axis) => (2 * folds, axis),
+			None if folds == 1 => (0, 0.),
+			None => (folds, 0.),
+		};
+	}
+
+	/// Every copy of the stroke, the stroke itself first.
+	pub fn copies(&self) -> Vec<SymmetryCopy> {
+		let folds = self.folds.clamp(1, MAX_FOLDS);
+		let mut copies = Vec::new();
+		for mirrored in [false, true] {
+			if mirrored && self.mirror.is_none() {
+				continue;
+			}
+			let axis = self.mirror.unwrap_or(0.).to_radians();
+			for k in 0..folds {
+				let angle = std::f32::consts::TAU * k as f32 / folds as f32;
+				let (s, c) = angle.sin_cos();
+				let rotation = [[c, -s], [s, c]];
+				let matrix = if mirrored {
+					// Turned after the reflection across the axis
+					let (s2, c2) = (2. * axis).sin_cos();
+					let reflection = [[-c2, -s2], [-s2, c2]];
+					[0, 1].map(|i| [0, 1].map(|j| rotation[i][0] * reflection[0][j] + rotation[i][1] * reflection[1][j]))
+				} else {
+					rotation
+				};
+				let turn = if mirrored { angle + 2. * axis } else { angle };
+				copies.push(SymmetryCopy { center: self.center, matrix, turn, mirrored });
+			}
+		}
+		return copies;
+	}
+}
+
+impl SymmetryCopy {
+	pub fn apply(&self, p: Point) -> Point {
+		let [cx, cy] = self.center;
+		let (dx, dy) = (p.x as f32 - cx, p.y as f32 - cy);
+		let [[a, b], [c, d]] = self.matrix;
+		return Point { x: (cx + a * dx + b * dy).round() as i32, y: (cy + c * dx + d * dy).round() as i32 };
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn applied(symmetry: Symmetry, p: Point) -> Vec<(i32, i32)> {
+		return symmetry.copies().iter().map(|c| c.apply(p)).map(|p| (p.x, p.y)).collect();
+	}
+
+	#[test]
+	fn horizontal_mirrors_across_the_center() {
+		let symmetry = Symmetry::horizontal([50., 50.]);
+		assert_eq!(applied(symmetry, Point { x: 40, y: 30 }), [(40, 30), (60, 30)]);
+		assert!(symmetry.copies()[1].mirrored);
+	}
+
+	#[test]
+	fn vertical_mirrors_top_to_bottom() {
+		assert_eq!(applied(Symmetry::vertical([50., 50.]), Point { x: 40, y: 30 }), [(40, 30), (40, 70)]);
+	}
+
+	#[test]
+	fn both_ways_makes_four_copies() {
+		let mut copies = applied(Symmetry::both([50., 50.]), Point { x: 40, y: 30 });
+		copies.sort();
+		assert_eq!(copies, [(40, 30), (40, 70), (60, 30), (60, 70)]);
+	}
+
+	#[test]
+	fn radial_turns_without_mirroring() {
+		let symmetry = Symmetry::radial([0., 0.], 4);
+		assert_eq!(applied(symmetry, Point { x: 10, y: 0 }), [(10, 0), (0, 10), (-10, 0), (0, -10)]);
+		assert!(symmetry.copies().iter().all(|c| !c.mirrored));
+	}
+
+	#[test]
+	fn folds_are_clamped() {
+		assert_eq!(Symmetry::radial([0., 0.], 0).copies().len(), 1);
+		assert_eq!(Symmetry::kaleidoscope([0., 0.], 100).copies().len(), 2 * MAX_FOLDS as usize);
+	}
+}
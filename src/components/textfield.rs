@@ -0,0 +1,114 @@
+use winit::event::VirtualKeyCode;
+
+use crate::components::{widget_focus_ring, widget_frame, widget_text_top, Context, DrawnWidget, Point, Rect, Size, Widget, WidgetImage, WIDGET_BORDER, WIDGET_FOCUSED, WIDGET_SCALE, WIDGET_TEXT};
+use crate::export::Pixels;
+use crate::font;
+
+const BACKGROUND: [u8; 4] = [24, 24, 28, 235];
+
+/// A line of text typed in with the keyboard once clicked on. Its owner reads `text` after handing
+/// it the input; Escape and Enter are left to the owner.
+pub struct TextField {
+	image: WidgetImage<(Size, String)>,
+	text: String,
+	/// Shown greyed out while nothing is typed
+	placeholder: &'static str,
+	focused: bool,
+}
+
+impl TextField {
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	pub fn set_text(&mut self, text: &str) {
+		self.text = text.to_string();
+	}
+
+	pub fn set_placeholder(&mut self, placeholder: &'static str) {
+		self.placeholder = placeholder;
+	}
+
+	fn shown_text(&self) -> String {
+		match (self.text.is_empty(), self.focused) {
+			(true, false) => self.placeholder.to_string(),
+			(true, true) => format!("_{}", self.placeholder),
+			(false, true) => format!("{}_", self.text),
+			(false, false) => self.text.clone(),
+		}
+	}
+}
+
+impl Widget for TextField {
+	fn press(&mut self, _: &mut Context, _: Rect, _: Point) -> bool {
+		return true;
+	}
+
+	fn focusable(&self) -> bool {
+		true
+	}
+
+	fn set_focused(&mut self, _: &mut Context, focused: bool) {
+		self.focused = focused;
+	}
+
+	fn text_input(&mut self, _: &mut Context, c: char) -> bool {
+		match c {
+			'\u{8}' => {
+				self.text.pop();
+			}
+			c if c.is_control() => return false,
+			c => self.text.push(c),
+		}
+		return true;
+	}
+
+	/// Keys typing characters are used up, so their bindings don't run while typing.
+	fn key_input(&mut self, _: &mut Context, key: VirtualKeyCode) -> bool {
+		return !matches!(key, VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter | VirtualKeyCode::Escape | VirtualKeyCode::Tab | VirtualKeyCode::Up | VirtualKeyCode::Down);
+	}
+}
+
+impl DrawnWidget for TextField {
+	/// Size and text
+	type State = (Size, String);
+	const LABEL: &'static str = "TextField(Texture)";
+
+	fn with_image(image: WidgetImage<Self::State>) -> Self {
+		Self {
+			image,
+			text: String::new(),
+			placeholder: "",
+			focused: false,
+		}
+	}
+
+	fn image(&mut self) -> &mut WidgetImage<Self::State> {
+		&mut self.image
+	}
+
+	fn state(&self, size: Size) -> Option<Self::State> {
+		return Some((size, self.shown_text()));
+	}
+
+	fn draw(&self, size: Size, (_, text): &Self::State) -> Pixels {
+		let mut pixels = widget_frame(size, BACKGROUND);
+		let x = 2 * WIDGET_SCALE;
+		// The end of text wider than the field stays in view
+		let shown: String = {
+			let fits = (size.w.saturating_sub(2 * x) / font::advance(WIDGET_SCALE)) as usize;
+			let skip = text.chars().count().saturating_sub(fits);
+			text.chars().skip(skip).collect()
+		};
+		let color = match (self.text.is_empty(), self.focused) {
+			(true, _) => WIDGET_BORDER,
+			(false, true) => WIDGET_FOCUSED,
+			(false, false) => WIDGET_TEXT,
+		};
+		font::draw_text(&mut pixels, x, widget_text_top(size.h), &shown, color, WIDGET_SCALE);
+		if self.focused {
+			widget_focus_ring(&mut pixels);
+		}
+		return pixels;
+	}
+}
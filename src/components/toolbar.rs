@@ -0,0 +1,130 @@
+use crate::components::{Context, DrawnWidget, Event, Point, Rect, Size, Widget, WidgetImage};
+use crate::export::Pixels;
+use crate::font;
+
+/// Window pixels across the bar, and each of its square buttons
+pub const TOOLBAR_WIDTH: u32 = 40;
+const ICON_RADIUS: f32 = 9.;
+const BACKGROUND: [u8; 4] = [32, 32, 36, 255];
+const HOVERED: [u8; 4] = [52, 52, 60, 255];
+const ACTIVE: [u8; 4] = [70, 70, 84, 255];
+const ICON: [u8; 4] = [230, 230, 230, 255];
+
+/// What the buttons switch to, top to bottom.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToolbarItem {
+	Brush,
+	Eraser,
+	Fill,
+	Select,
+}
+
+const ITEMS: [ToolbarItem; 4] = [ToolbarItem::Brush, ToolbarItem::Eraser, ToolbarItem::Fill, ToolbarItem::Select];
+
+/// Column of buttons along the side of the window, redrawn when what they show changes.
+pub struct Toolbar {
+	image: WidgetImage<(Size, Option<ToolbarItem>, Option<ToolbarItem>)>,
+	active: Option<ToolbarItem>,
+	hovered: Option<ToolbarItem>,
+}
+
+impl Toolbar {
+	/// Button at the window point `p` of the bar rendered in `viewport`.
+	fn item_at(&self, viewport: Rect, p: Point) -> Option<ToolbarItem> {
+		if !viewport.inside(p) || p.x >= viewport.pos.x + viewport.size.w as i32 {
+			return None;
+		}
+		return ITEMS.get(((p.y - viewport.pos.y) / TOOLBAR_WIDTH as i32) as usize).copied();
+	}
+
+	/// Highlights the button of what is in use.
+	pub fn set_active(&mut self, active: Option<ToolbarItem>) {
+		self.active = active;
+	}
+
+	/// Returns if the button under the pointer changed.
+	fn hover(&mut self, hovered: Option<ToolbarItem>) -> bool {
+		if hovered == self.hovered {
+			return false;
+		}
+		self.hovered = hovered;
+		return true;
+	}
+}
+
+/// Clicking a button emits `Event::ToolPicked` with it, the layout switches tools.
+impl Widget for Toolbar {
+	fn press(&mut self, ctx: &mut Context, viewport: Rect, p: Point) -> bool {
+		if let Some(item) = self.item_at(viewport, p) {
+			ctx.emit(Event::ToolPicked(item));
+		}
+		return true;
+	}
+
+	fn set_hovered(&mut self, hovered: bool) -> bool {
+		return !hovered && self.hover(None);
+	}
+
+	fn hover_at(&mut self, viewport: Rect, p: Point) -> bool {
+		let hovered = self.item_at(viewport, p);
+		return self.hover(hovered);
+	}
+}
+
+impl DrawnWidget for Toolbar {
+	/// Size, active and hovered button
+	type State = (Size, Option<ToolbarItem>, Option<ToolbarItem>);
+	const LABEL: &'static str = "Toolbar(Texture)";
+
+	fn with_image(image: WidgetImage<Self::State>) -> Self {
+		Self {
+			image,
+			active: None,
+			hovered: None,
+		}
+	}
+
+	fn image(&mut self) -> &mut WidgetImage<Self::State> {
+		&mut self.image
+	}
+
+	fn state(&self, size: Size) -> Option<Self::State> {
+		return Some((size, self.active, self.hovered));
+	}
+
+	fn draw(&self, size: Size, _: &Self::State) -> Pixels {
+		let mut pixels = Pixels::new(size);
+		font::fill(&mut pixels, 0, 0, size.w, size.h, BACKGROUND);
+		for (i, item) in ITEMS.iter().enumerate() {
+			let top = i as u32 * TOOLBAR_WIDTH;
+			if top + TOOLBAR_WIDTH > size.h {
+				break;
+			}
+			if Some(*item) == self.active {
+				font::fill(&mut pixels, 0, top, TOOLBAR_WIDTH, TOOLBAR_WIDTH, ACTIVE);
+			} else if Some(*item) == self.hovered {
+				font::fill(&mut pixels, 0, top, TOOLBAR_WIDTH, TOOLBAR_WIDTH, HOVERED);
+			}
+
+			let center = TOOLBAR_WIDTH as f32 / 2.;
+			for y in 0..TOOLBAR_WIDTH {
+				for x in 0..TOOLBAR_WIDTH {
+					let (dx, dy) = (x as f32 + 0.5 - center, y as f32 + 0.5 - center);
+					let distance = dx.hypot(dy);
+					let edge = dx.abs().max(dy.abs());
+					let drawn = match item {
+						ToolbarItem::Brush => distance <= ICON_RADIUS,
+						ToolbarItem::Eraser => (ICON_RADIUS - 2. ..=ICON_RADIUS).contains(&distance),
+						ToolbarItem::Fill => edge <= ICON_RADIUS * 0.8,
+						// Dashed like the selection outline
+						ToolbarItem::Select => (ICON_RADIUS - 1.5..=ICON_RADIUS).contains(&edge) && (x + y) / 3 % 2 == 0,
+					};
+					if drawn {
+						font::fill(&mut pixels, x, top + y, 1, 1, ICON);
+					}
+				}
+			}
+		}
+		return pixels;
+	}
+}
@@ -0,0 +1,219 @@
+use std::any::Any;
+
+use crate::components::{Component, Context, Rect, Size, Widget};
+
+/// Extent of a child along the direction of its container.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Length {
+	/// Window pixels
+	Fixed(u32),
+	/// Share of what the fixed children leave, by weight
+	Flex(u32),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+	/// Left to right
+	Row,
+	/// Top to bottom
+	Column,
+}
+
+/// Component a `Tree` owns, draws and hands the input of, through its `Widget` side.
+pub trait Element: Widget {
+	fn render(&mut self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, output: &wgpu::TextureView, viewport: Rect, clip_space: Option<Rect>);
+	fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Component + Widget + 'static> Element for T {
+	fn render(&mut self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, output: &wgpu::TextureView, viewport: Rect, clip_space: Option<Rect>) {
+		Component::render(self, encoder, ctx, output, viewport, clip_space);
+	}
+
+	fn as_any_mut(&mut self) -> &mut dyn Any {
+		self
+	}
+}
+
+pub enum Node<Id> {
+	/// Child the tree owns
+	Element(Id, Box<dyn Element>),
+	/// Room for a component owned elsewhere, its rect is looked up with `Tree::rect`
+	Slot(Id),
+	/// Children one after the other along `direction`, each as wide (rows) or tall (columns) as
+	/// the container
+	Container {
+		direction: Direction,
+		/// Between children
+		gap: u32,
+		/// Around them
+		padding: u32,
+		children: Vec<(Length, Node<Id>)>,
+	},
+}
+
+impl<Id: Copy> Node<Id> {
+	pub fn row(gap: u32, children: Vec<(Length, Node<Id>)>) -> Self {
+		Node::Container { direction: Direction::Row, gap, padding: 0, children }
+	}
+
+	pub fn column(gap: u32, children: Vec<(Length, Node<Id>)>) -> Self {
+		Node::Container { direction: Direction::Column, gap, padding: 0, children }
+	}
+
+	/// Sets the padding of a container, other nodes are left as they are.
+	pub fn padded(mut self, padding: u32) -> Self {
+		if let Node::Container { padding: p, .. } = &mut self {
+			*p = padding;
+		}
+		return self;
+	}
+
+	/// Smallest size the fixed lengths, gaps and padding take, flexible children taking none.
+	fn min_size(&self) -> Size {
+		let Node::Container { direction, gap, padding, children } = self else {
+			return Size { w: 0, h: 0 };
+		};
+		let (mut along, mut across) = (gap * children.len().saturating_sub(1) as u32, 0);
+		for (length, child) in children {
+			let size = child.min_size();
+			let (child_along, child_across) = match direction {
+				Direction::Row => (size.w, size.h),
+				Direction::Column => (size.h, size.w),
+			};
+			along += match length {
+				Length::Fixed(pixels) => (*pixels).max(child_along),
+				Length::Flex(_) => child_along,
+			};
+			across = across.max(child_across);
+		}
+		let (along, across) = (along + 2 * padding, across + 2 * padding);
+		return match direction {
+			Direction::Row => Size { w: along, h: across },
+			Direction::Column => Size { w: across, h: along },
+		};
+	}
+
+	/// Appends the rect of this node and the ones under it, laid out in `rect`, depth first.
+	fn layout(&self, rect: Rect, rects: &mut Vec<(Id, Rect)>) {
+		let (direction, gap, padding, children) = match self {
+			Node::Element(id, _) | Node::Slot(id) => {
+				rects.push((*id, rect));
+				return;
+			}
+			Node::Container { direction, gap, padding, children } => (*direction, *gap, *padding, children),
+		};
+		let inner = Rect::new(rect.pos.x + padding as i32, rect.pos.y + padding as i32, rect.size.w.saturating_sub(2 * padding), rect.size.h.saturating_sub(2 * padding));
+		let (extent, across) = match direction {
+			Direction::Row => (inner.size.w, inner.size.h),
+			Direction::Column => (inner.size.h, inner.size.w),
+		};
+		let fixed: u32 = children.iter().map(|(l, _)| match l {
+			Length::Fixed(pixels) => *pixels,
+			Length::Flex(_) => 0,
+		}).sum();
+		let weights: u32 = children.iter().map(|(l, _)| match l {
+			Length::Fixed(_) => 0,
+			Length::Flex(weight) => *weight,
+		}).sum();
+		let free = extent.saturating_sub(fixed + gap * children.len().saturating_sub(1) as u32);
+
+		let mut offset = 0;
+		let mut flex_given = 0;
+		let mut weights_given = 0;
+		for (length, child) in children {
+			let along = match length {
+				Length::Fixed(pixels) => *pixels,
+				// The last flexible child takes what rounding left over
+				Length::Flex(weight) => {
+					weights_given += weight;
+					let share = if weights_given == weights { free - flex_given } else { free * weight / weights.max(1) };
+					flex_given += share;
+					share
+				}
+			};
+			let child_rect = match direction {
+				Direction::Row => Rect::new(inner.pos.x + offset as i32, inner.pos.y, along, across),
+				Direction::Column => Rect::new(inner.pos.x, inner.pos.y + offset as i32, across, along),
+			};
+			child.layout(child_rect, rects);
+			offset += along + gap;
+		}
+	}
+
+	fn for_each_element<'a>(&'a mut self, f: &mut impl FnMut(Id, &'a mut dyn Element)) {
+		match self {
+			Node::Element(id, element) => f(*id, element.as_mut()),
+			Node::Slot(_) => (),
+			Node::Container { children, .. } => {
+				for (_, child) in children {
+					child.for_each_element(f);
+				}
+			}
+		}
+	}
+}
+
+/// Components placed by nested rows and columns, parents owning their children. The layout hands
+/// its widgets to the `WidgetRouter` along with the others.
+pub struct Tree<Id> {
+	root: Node<Id>,
+	/// Of every element and slot, from the last `layout`
+	rects: Vec<(Id, Rect)>,
+}
+
+impl<Id: Copy + PartialEq> Tree<Id> {
+	pub fn new(root: Node<Id>) -> Self {
+		Self { root, rects: Vec::new() }
+	}
+
+	/// Places the nodes in `bounds`.
+	pub fn layout(&mut self, bounds: Rect) {
+		self.rects.clear();
+		self.root.layout(bounds, &mut self.rects);
+	}
+
+	/// Smallest size the nodes fit in.
+	pub fn min_size(&self) -> Size {
+		self.root.min_size()
+	}
+
+	/// Rect of the element or slot `id`, zero sized before the first layout.
+	pub fn rect(&self, id: Id) -> Rect {
+		return self.rects.iter().find(|(i, _)| *i == id).map_or(Rect::new(0, 0, 0, 0), |(_, r)| *r);
+	}
+
+	/// The element `id`, when it is a `T`.
+	pub fn get_mut<T: 'static>(&mut self, id: Id) -> Option<&mut T> {
+		let mut found = None;
+		self.root.for_each_element(&mut |i, element| {
+			if i == id {
+				found = Some(element);
+			}
+		});
+		return found?.as_any_mut().downcast_mut();
+	}
+
+	/// Every element with where it takes input, in drawing order.
+	pub fn widgets(&mut self) -> Vec<(Id, Rect, &mut dyn Widget)> {
+		let rects = &self.rects;
+		let mut widgets: Vec<(Id, Rect, &mut dyn Widget)> = Vec::new();
+		self.root.for_each_element(&mut |id, element| {
+			let rect = rects.iter().find(|(i, _)| *i == id).map_or(Rect::new(0, 0, 0, 0), |(_, r)| *r);
+			widgets.push((id, element.bounds(rect), element as &mut dyn Widget));
+		});
+		return widgets;
+	}
+
+	/// Draws every element, clipped to `clip`.
+	pub fn render(&mut self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, output: &wgpu::TextureView, clip: Rect) {
+		let rects = &self.rects;
+		self.root.for_each_element(&mut |id, element| {
+			let rect = rects.iter().find(|(i, _)| *i == id).map_or(Rect::new(0, 0, 0, 0), |(_, r)| *r);
+			let rect = element.bounds(rect);
+			if let Some(clip) = rect.intersection(clip) {
+				element.render(encoder, ctx, output, rect, Some(clip));
+			}
+		});
+	}
+}
@@ -0,0 +1,115 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::components::Context;
+
+/// What shaders reading `Uniforms` are preprocessed with when they come from a uniform buffer.
+pub const UNIFORM_BUFFER: &str = "UNIFORM_BUFFER";
+
+/// A `T` a shader reads, as push constants when the adapter has them, otherwise from a uniform
+/// buffer at binding 0 of `group`, the group after the others of the pipeline. Shaders declare it
+/// both ways:
+///
+/// ```wgsl
+/// #ifdef UNIFORM_BUFFER
+/// @group(0) @binding(0)
+/// var<uniform> input: Input;
+/// #else
+/// var<push_constant> input: Input;
+/// #endif
+/// ```
+pub struct Uniforms<T> {
+	stages: wgpu::ShaderStages,
+	group: u32,
+	/// None with push constants
+	layout: Option<Arc<wgpu::BindGroupLayout>>,
+	_input: PhantomData<T>,
+}
+
+/// Values of one draw, from `Uniforms::bind`.
+pub enum BoundUniforms {
+	PushConstants(wgpu::ShaderStages, Vec<u8>),
+	/// Bind group of a chunk of the uniform pool, and where the values are in it
+	Buffer(u32, Arc<wgpu::BindGroup>, u32),
+}
+
+impl<T: bytemuck::Pod> Uniforms<T> {
+	pub fn new(ctx: &Context, stages: wgpu::ShaderStages, group: u32) -> Self {
+		let layout = (!ctx.device.features().contains(wgpu::Features::PUSH_CONSTANTS)).then(|| ctx.bind_group_layout("Uniforms(Binding Group Layout)", &[
+			wgpu::BindGroupLayoutEntry {
+				binding: 0,
+				visibility: stages,
+				ty: wgpu::BindingType::Buffer {
+					ty: wgpu::BufferBindingType::Uniform,
+					has_dynamic_offset: true,
+					min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<T>() as u64),
+				},
+				count: None,
+			}
+		]));
+		Self { stages, group, layout, _input: PhantomData }
+	}
+
+	/// Defines to preprocess the shader with.
+	pub fn defines(&self) -> &'static [&'static str] {
+		if self.layout.is_some() { &[UNIFORM_BUFFER] } else { &[] }
+	}
+
+	/// Of the pipeline layout, none with a uniform buffer.
+	pub fn push_constant_ranges(&self) -> Vec<wgpu::PushConstantRange> {
+		if self.layout.is_some() {
+			return Vec::new();
+		}
+		return vec![wgpu::PushConstantRange { stages: self.stages, range: 0..std::mem::size_of::<T>() as u32 }];
+	}
+
+	/// Of the pipeline layout at `group`, none with push constants.
+	pub fn bind_group_layout(&self) -> Option<&wgpu::BindGroupLayout> {
+		self.layout.as_deref()
+	}
+
+	/// Uploads `input` for one draw when it comes from a uniform buffer, valid until the uniform
+	/// pool is reset.
+	pub fn bind(&self, ctx: &mut Context, input: &T) -> BoundUniforms {
+		let bytes = bytemuck::bytes_of(input);
+		let Some(layout) = &self.layout else {
+			return BoundUniforms::PushConstants(self.stages, bytes.to_vec());
+		};
+		let size = wgpu::BufferSize::new(bytes.len() as u64).unwrap();
+		let slice = ctx.uniform_pool.alloc(&ctx.device, size);
+		ctx.queue.write_buffer(ctx.uniform_pool.buffer(&slice), slice.offset, bytes);
+		// Every draw of the chunk shares a bind group, told apart by the dynamic offset
+		let bind_group = ctx.bind_group("Uniforms(Binding Group)", &[slice.chunk() as u64, size.get(), self.stages.bits() as u64], || ctx.device.create_bind_group(&wgpu::BindGroupDescriptor {
+			label: Some("Uniforms(Binding Group)"),
+			layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+						buffer: ctx.uniform_pool.buffer(&slice),
+						offset: 0,
+						size: Some(size),
+					}),
+				}
+			],
+		}));
+		return BoundUniforms::Buffer(self.group, bind_group, slice.offset as u32);
+	}
+}
+
+impl BoundUniforms {
+	pub fn set<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+		match self {
+			BoundUniforms::PushConstants(stages, bytes) => render_pass.set_push_constants(*stages, 0, bytes),
+			BoundUniforms::Buffer(group, bind_group, offset) => render_pass.set_bind_group(*group, bind_group, &[*offset]),
+		}
+	}
+
+	/// Like `set`, for the dispatches of a compute pass.
+	pub fn set_compute<'a>(&'a self, compute_pass: &mut wgpu::ComputePass<'a>) {
+		match self {
+			BoundUniforms::PushConstants(_, bytes) => compute_pass.set_push_constants(0, bytes),
+			BoundUniforms::Buffer(group, bind_group, offset) => compute_pass.set_bind_group(*group, bind_group, &[*offset]),
+		}
+	}
+}
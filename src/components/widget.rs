@@ -0,0 +1,379 @@
+use winit::event::VirtualKeyCode;
+
+use crate::components::{Component, Context, Image, Pipelines, Point, Rect, Size, MAX_BRUSH_SIZE, MAX_CANVAS_SIDE, MAX_ZOOM, MIN_BRUSH_SIZE, MIN_ZOOM};
+use crate::export::Pixels;
+use crate::font;
+use crate::settings::preferences::{MAX_AUTOSAVE_INTERVAL, MAX_FPS, MIN_AUTOSAVE_INTERVAL, MIN_FPS};
+
+/// Font scale of widget labels
+pub const WIDGET_SCALE: u32 = 2;
+pub const WIDGET_BACKGROUND: [u8; 4] = [32, 32, 36, 235];
+pub const WIDGET_HOVERED: [u8; 4] = [52, 52, 60, 255];
+pub const WIDGET_BORDER: [u8; 4] = [70, 70, 84, 255];
+pub const WIDGET_TEXT: [u8; 4] = [230, 230, 230, 255];
+pub const WIDGET_FOCUSED: [u8; 4] = [240, 200, 90, 255];
+
+/// Pixels of a widget of `size`: a border around `fill`.
+pub fn widget_frame(size: Size, fill: [u8; 4]) -> Pixels {
+	let mut pixels = Pixels::new(size);
+	font::fill(&mut pixels, 0, 0, size.w, size.h, WIDGET_BORDER);
+	font::fill(&mut pixels, 1, 1, size.w.saturating_sub(2), size.h.saturating_sub(2), fill);
+	return pixels;
+}
+
+/// Draws the border of the widget with the keyboard over its own.
+pub fn widget_focus_ring(pixels: &mut Pixels) {
+	let size = pixels.size;
+	font::fill(pixels, 0, 0, size.w, 1, WIDGET_FOCUSED);
+	font::fill(pixels, 0, size.h.saturating_sub(1), size.w, 1, WIDGET_FOCUSED);
+	font::fill(pixels, 0, 0, 1, size.h, WIDGET_FOCUSED);
+	font::fill(pixels, size.w.saturating_sub(1), 0, 1, size.h, WIDGET_FOCUSED);
+}
+
+/// Top of a line of label text centered in `height`.
+pub fn widget_text_top(height: u32) -> u32 {
+	return height.saturating_sub(font::GLYPH_H * WIDGET_SCALE) / 2;
+}
+
+/// Image a `DrawnWidget` is rendered with, and the state it was last drawn in.
+pub struct WidgetImage<S> {
+	image: Box<Image>,
+	/// None when it needs drawing again
+	drawn: Option<S>,
+}
+
+impl<S> WidgetImage<S> {
+	/// Draws the widget again on its next render, for changes its state doesn't tell.
+	pub fn invalidate(&mut self) {
+		self.drawn = None;
+	}
+}
+
+/// Widget drawn on the CPU, uploaded again only when the state it is drawn in changes. It is a
+/// `Component` through it.
+pub trait DrawnWidget {
+	/// What the image shows, the size it is drawn at included
+	type State: PartialEq;
+	/// Of its texture
+	const LABEL: &'static str;
+
+	fn with_image(image: WidgetImage<Self::State>) -> Self;
+	fn image(&mut self) -> &mut WidgetImage<Self::State>;
+	/// State it is drawn in at `size`, None to leave it out.
+	fn state(&self, size: Size) -> Option<Self::State>;
+	fn draw(&self, size: Size, state: &Self::State) -> Pixels;
+}
+
+impl<T: DrawnWidget> Component for T {
+	fn generate_pipelines(_: &Context) -> Pipelines {
+		// Drawn by its image
+		Pipelines {
+			render: vec![],
+			compute: vec![],
+		}
+	}
+
+	fn new(ctx: &mut Context) -> Box<Self> {
+		Box::new(T::with_image(WidgetImage { image: Image::new(ctx), drawn: None }))
+	}
+
+	fn render(&mut self, encoder: &mut wgpu::CommandEncoder, ctx: &mut Context, output: &wgpu::TextureView, viewport: Rect, clip_space: Option<Rect>) {
+		if viewport.size.w == 0 || viewport.size.h == 0 {
+			return;
+		}
+		let Some(state) = self.state(viewport.size) else {
+			return;
+		};
+		if self.image().drawn.as_ref() != Some(&state) {
+			let tex = ctx.upload_texture(T::LABEL, &self.draw(viewport.size, &state), wgpu::TextureUsages::STORAGE_BINDING);
+			let image = self.image();
+			image.image.set_texture(ctx, tex);
+			image.drawn = Some(state);
+		}
+		self.image().image.render(encoder, ctx, output, viewport, clip_space);
+	}
+}
+
+/// Value a slider or numeric field edits, the layout applies the `Event::ValueChanged` they emit.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Control {
+	/// Diameter in pixels
+	BrushSize,
+	/// Percent
+	BrushOpacity,
+	/// Percent of the document size
+	Zoom,
+	/// Frames per second
+	FpsCap,
+	/// Of new documents, in pixels
+	CanvasWidth,
+	CanvasHeight,
+	/// Seconds
+	AutosaveInterval,
+}
+
+impl Control {
+	pub fn label(&self) -> &'static str {
+		match self {
+			Control::BrushSize => "Size",
+			Control::BrushOpacity => "Opacity",
+			Control::Zoom => "Zoom",
+			Control::FpsCap => "Frame rate cap",
+			Control::CanvasWidth => "Canvas width",
+			Control::CanvasHeight => "Canvas height",
+			Control::AutosaveInterval => "Autosave every",
+		}
+	}
+
+	pub fn range(&self) -> (f32, f32) {
+		match self {
+			Control::BrushSize => (MIN_BRUSH_SIZE as f32, MAX_BRUSH_SIZE as f32),
+			Control::BrushOpacity => (0., 100.),
+			Control::Zoom => (MIN_ZOOM * 100., MAX_ZOOM * 100.),
+			Control::FpsCap => (MIN_FPS as f32, MAX_FPS as f32),
+			Control::CanvasWidth | Control::CanvasHeight => (1., MAX_CANVAS_SIDE as f32),
+			Control::AutosaveInterval => (MIN_AUTOSAVE_INTERVAL.as_secs() as f32, MAX_AUTOSAVE_INTERVAL.as_secs() as f32),
+		}
+	}
+
+	/// Whether the slider spreads values by ratio instead of difference, for ranges spanning
+	/// orders of magnitude.
+	pub fn logarithmic(&self) -> bool {
+		matches!(self, Control::BrushSize | Control::Zoom | Control::CanvasWidth | Control::CanvasHeight | Control::AutosaveInterval)
+	}
+
+	/// `value` in range, rounded to what the control can hold.
+	pub fn clamp(&self, value: f32) -> f32 {
+		let (min, max) = self.range();
+		let value = value.clamp(min, max);
+		return match self {
+			Control::BrushSize | Control::FpsCap | Control::CanvasWidth | Control::CanvasHeight | Control::AutosaveInterval => value.round(),
+			Control::BrushOpacity | Control::Zoom => (value * 10.).round() / 10.,
+		};
+	}
+
+	/// Position of `value` along a slider, 0 to 1.
+	pub fn fraction(&self, value: f32) -> f32 {
+		let (min, max) = self.range();
+		let fraction = match self.logarithmic() {
+			true => (value.max(min) / min).ln() / (max / min).ln(),
+			false => (value - min) / (max - min),
+		};
+		return fraction.clamp(0., 1.);
+	}
+
+	/// Value at `fraction` of the way along a slider.
+	pub fn value_at(&self, fraction: f32) -> f32 {
+		let (min, max) = self.range();
+		let fraction = fraction.clamp(0., 1.);
+		let value = match self.logarithmic() {
+			true => min * (max / min).powf(fraction),
+			false => min + (max - min) * fraction,
+		};
+		return self.clamp(value);
+	}
+
+	/// `value` moved `steps` hundredths of the way along a slider, for the arrow keys.
+	pub fn step(&self, value: f32, steps: f32) -> f32 {
+		let stepped = self.value_at(self.fraction(value) + steps / 100.);
+		if stepped != value || steps == 0. {
+			return stepped;
+		}
+		// Too little to change a rounded value, it moves by what the control can hold instead
+		let unit = match self {
+			Control::BrushSize | Control::FpsCap | Control::CanvasWidth | Control::CanvasHeight | Control::AutosaveInterval => 1.,
+			Control::BrushOpacity | Control::Zoom => 0.1,
+		};
+		return self.clamp(value + unit * steps.signum());
+	}
+
+	pub fn format(&self, value: f32) -> String {
+		match self {
+			Control::BrushSize | Control::FpsCap => format!("{value:.0}"),
+			Control::BrushOpacity | Control::Zoom => format!("{value:.0}%"),
+			Control::CanvasWidth | Control::CanvasHeight => format!("{value:.0} px"),
+			Control::AutosaveInterval => format!("{value:.0} s"),
+		}
+	}
+}
+
+/// Pointer and keyboard input of a component, handed to it by a `WidgetRouter`. `viewport` is
+/// where the widget was last rendered, `p` is in window pixels.
+pub trait Widget {
+	/// Returns if the press was the widget's, it gets the drags and release of it then.
+	fn press(&mut self, ctx: &mut Context, viewport: Rect, p: Point) -> bool;
+
+	/// Returns if the widget changed.
+	fn drag(&mut self, _ctx: &mut Context, _viewport: Rect, _p: Point) -> bool {
+		false
+	}
+
+	fn release(&mut self, _ctx: &mut Context) {}
+
+	/// Where the widget is drawn and takes input when laid out at `rect`.
+	fn bounds(&self, rect: Rect) -> Rect {
+		rect
+	}
+
+	/// Returns if the widget looks different now that the pointer is over it, or isn't anymore.
+	fn set_hovered(&mut self, _hovered: bool) -> bool {
+		false
+	}
+
+	/// Pointer moved to `p` over the widget, for those showing what is under it. Returns if the
+	/// widget looks different.
+	fn hover_at(&mut self, _viewport: Rect, _p: Point) -> bool {
+		false
+	}
+
+	/// Whether pressing on the widget, or Tab, gives it the keyboard.
+	fn focusable(&self) -> bool {
+		false
+	}
+
+	fn set_focused(&mut self, _ctx: &mut Context, _focused: bool) {}
+
+	/// Character typed while focused. Returns if it was used.
+	fn text_input(&mut self, _ctx: &mut Context, _c: char) -> bool {
+		false
+	}
+
+	/// Key released while focused. Returns if it was used, its binding doesn't run then.
+	fn key_input(&mut self, _ctx: &mut Context, _key: VirtualKeyCode) -> bool {
+		false
+	}
+}
+
+/// Widgets identified by `Id`, with where they are on screen, topmost last.
+pub type Widgets<'a, Id> = [(Id, Rect, &'a mut dyn Widget)];
+
+/// Hands pointer input to the widget under it and keyboard input to the focused one, so the layout
+/// doesn't need to match each of them in turn.
+pub struct WidgetRouter<Id> {
+	/// Widget the left button went down on, it gets the input until its release
+	pressed: Option<Id>,
+	focused: Option<Id>,
+	hovered: Option<Id>,
+}
+
+impl<Id: Copy + PartialEq> WidgetRouter<Id> {
+	pub fn new() -> Self {
+		Self { pressed: None, focused: None, hovered: None }
+	}
+
+	pub fn focused(&self) -> Option<Id> {
+		self.focused
+	}
+
+	/// Moves the focus to the topmost widget under `p` when it takes it, or away from the focused
+	/// one when it doesn't. Returns if the press was a widget's.
+	pub fn press(&mut self, ctx: &mut Context, widgets: &mut Widgets<Id>, p: Point) -> bool {
+		let hit = widgets.iter().rposition(|(_, r, _)| r.inside(p));
+		let focus = hit.filter(|i| widgets[*i].2.focusable()).map(|i| widgets[i].0);
+		self.focus(ctx, widgets, focus);
+
+		let Some((id, rect, widget)) = hit.map(|i| &mut widgets[i]) else {
+			return false;
+		};
+		if !widget.press(ctx, *rect, p) {
+			return false;
+		}
+		self.pressed = Some(*id);
+		return true;
+	}
+
+	/// Tells the widgets the pointer at `p` entered and left. Returns if one of them needs drawing
+	/// again.
+	pub fn hover(&mut self, widgets: &mut Widgets<Id>, p: Point) -> bool {
+		let hovered = widgets.iter().rev().find(|(_, r, _)| r.inside(p)).map(|(id, _, _)| *id);
+		let mut changed = false;
+		for (id, rect, widget) in widgets.iter_mut() {
+			if Some(*id) == hovered {
+				changed |= (hovered != self.hovered && widget.set_hovered(true)) | widget.hover_at(*rect, p);
+			} else if Some(*id) == self.hovered {
+				changed |= widget.set_hovered(false);
+			}
+		}
+		self.hovered = hovered;
+		return changed;
+	}
+
+	/// Returns if the pointer is dragging a widget, the move is its then.
+	pub fn drag(&mut self, ctx: &mut Context, widgets: &mut Widgets<Id>, p: Point) -> bool {
+		if let Some((_, rect, widget)) = widgets.iter_mut().find(|(id, _, _)| Some(*id) == self.pressed) {
+			widget.drag(ctx, *rect, p);
+		}
+		return self.pressed.is_some();
+	}
+
+	/// Returns if the release was of a press on a widget.
+	pub fn release(&mut self, ctx: &mut Context, widgets: &mut Widgets<Id>) -> bool {
+		let Some(pressed) = self.pressed.take() else {
+			return false;
+		};
+		if let Some((_, _, widget)) = widgets.iter_mut().find(|(id, _, _)| *id == pressed) {
+			widget.release(ctx);
+		}
+		return true;
+	}
+
+	/// Gives the keyboard to the widget `focus`, or to none.
+	pub fn focus(&mut self, ctx: &mut Context, widgets: &mut Widgets<Id>, focus: Option<Id>) {
+		if focus == self.focused {
+			return;
+		}
+		for (id, _, widget) in widgets.iter_mut() {
+			if Some(*id) == self.focused {
+				widget.set_focused(ctx, false);
+			} else if Some(*id) == focus {
+				widget.set_focused(ctx, true);
+			}
+		}
+		self.focused = focus;
+	}
+
+	/// Returns if the focused widget used the character.
+	pub fn text_input(&mut self, ctx: &mut Context, widgets: &mut Widgets<Id>, c: char) -> bool {
+		return self.focused_widget(widgets).is_some_and(|w| w.text_input(ctx, c));
+	}
+
+	/// Returns if the focused widget used the key. Enter and Escape take the focus away from it,
+	/// Escape even when it didn't use it.
+	pub fn key_input(&mut self, ctx: &mut Context, widgets: &mut Widgets<Id>, key: VirtualKeyCode) -> bool {
+		let Some(widget) = self.focused_widget(widgets) else {
+			return false;
+		};
+		let used = widget.key_input(ctx, key);
+		if (used && matches!(key, VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter)) || key == VirtualKeyCode::Escape {
+			self.focus(ctx, widgets, None);
+			return true;
+		}
+		return used;
+	}
+
+	/// Whether one of `widgets` has the keyboard.
+	pub fn has_focus(&self, widgets: &mut Widgets<Id>) -> bool {
+		self.focused_widget(widgets).is_some()
+	}
+
+	/// Moves the focus to the next focusable widget, in drawing order and wrapping around, or the
+	/// previous one when `reverse`. Starts from the first or last one while none is focused.
+	pub fn cycle_focus(&mut self, ctx: &mut Context, widgets: &mut Widgets<Id>, reverse: bool) {
+		let focusable: Vec<Id> = widgets.iter().filter(|(_, _, w)| w.focusable()).map(|(id, _, _)| *id).collect();
+		if focusable.is_empty() {
+			return;
+		}
+		let next = match focusable.iter().position(|id| Some(*id) == self.focused) {
+			Some(i) if reverse => (i + focusable.len() - 1) % focusable.len(),
+			Some(i) => (i + 1) % focusable.len(),
+			None if reverse => focusable.len() - 1,
+			None => 0,
+		};
+		self.focus(ctx, widgets, Some(focusable[next]));
+	}
+
+	fn focused_widget<'w, 'a>(&self, widgets: &'w mut Widgets<'a, Id>) -> Option<&'w mut (dyn Widget + 'a)> {
+		let focused = self.focused?;
+		return widgets.iter_mut().find(|(id, _, _)| *id == focused).map(|(_, _, w)| &mut **w);
+	}
+}
@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+fn project_dirs() -> Option<directories::ProjectDirs> {
+	directories::ProjectDirs::from("", "", "pntr")
+}
+
+/// `pntr` inside the config directory of the platform, the XDG one on Linux. None without a home directory.
+pub fn config_dir() -> Option<PathBuf> {
+	return Some(project_dirs()?.config_dir().to_owned());
+}
+
+/// `pntr` inside the data directory of the platform, None without a home directory.
+pub fn data_dir() -> Option<PathBuf> {
+	return Some(project_dirs()?.data_dir().to_owned());
+}
+
+/// Text of `name` in the config directory, None when it can't be read.
+pub fn read_file(name: &str) -> Option<String> {
+	return std::fs::read_to_string(config_dir()?.join(name)).ok();
+}
+
+/// The lines of `name` in the config directory that aren't blank, parsed by `parse`, logging the ones it
+/// rejects as an invalid `what`. None when the file can't be read.
+pub fn read_lines<T>(name: &str, what: &str, mut parse: impl FnMut(&str) -> Option<T>) -> Option<Vec<T>> {
+	let text = read_file(name)?;
+	return Some(text.lines().filter(|l| !l.trim().is_empty()).filter_map(|line| {
+		let parsed = parse(line);
+		if parsed.is_none() {
+			log::warn!("Ignoring invalid {what} {line:?}");
+		}
+		parsed
+	}).collect());
+}
+
+/// Writes `text` to `name` in the config directory, making the directory first, logging what fails.
+pub fn write_file(name: &str, text: &str) {
+	let Some(dir) = config_dir() else {
+		return;
+	};
+	let path = dir.join(name);
+	if let Err(e) = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, text)) {
+		log::warn!("Could not save {}: {e}", path.display());
+	}
+}
+
+/// `name` in the config directory read as TOML, None when it can't be read or is invalid, logging why.
+pub fn read_toml<T: serde::de::DeserializeOwned>(name: &str) -> Option<T> {
+	let text = read_file(name)?;
+	return toml::from_str(&text).map_err(|e| log::warn!("Ignoring invalid {name}: {e}")).ok();
+}
+
+pub fn write_toml<T: serde::Serialize>(name: &str, value: &T) {
+	match toml::to_string(value) {
+		Ok(text) => write_file(name, &text),
+		Err(e) => log::warn!("Could not save {name}: {e}"),
+	}
+}
+
+/// `#rrggbb` as bytes.
+pub fn parse_hex_color(hex: &str) -> Option<[u8; 3]> {
+	let rgb = u32::from_str_radix(hex.strip_prefix('#')?, 16).ok().filter(|_| hex.len() == 7)?;
+	return Some([16, 8, 0].map(|shift| (rgb >> shift) as u8));
+}
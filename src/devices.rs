@@ -0,0 +1,108 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::config;
+use crate::pressure::{self, PressureCurve};
+
+pub const SETTINGS_FILE: &str = "input-devices";
+const PALM_REJECTION: Duration = Duration::from_millis(500);
+
+/// What an input event came from. winit doesn't name devices, so settings are kept per kind.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DeviceKind {
+	Mouse,
+	Touch,
+	/// Touch events reporting pressure
+	Stylus,
+}
+
+impl DeviceKind {
+	pub const ALL: [DeviceKind; 3] = [DeviceKind::Mouse, DeviceKind::Touch, DeviceKind::Stylus];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			DeviceKind::Mouse => "mouse",
+			DeviceKind::Touch => "touch",
+			DeviceKind::Stylus => "stylus",
+		}
+	}
+
+	fn from_name(name: &str) -> Option<Self> {
+		DeviceKind::ALL.into_iter().find(|k| k.name() == name)
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceSettings {
+	pub pressure_curve: PressureCurve,
+	/// Touches paint, otherwise they are left for navigating
+	pub touch_paints: bool,
+	/// The eraser end of the stylus switches to the eraser
+	pub eraser_end_erases: bool,
+	/// Touches this long after the stylus was last used are still taken for a resting palm and ignored, 0 to never ignore them
+	pub palm_rejection: Duration,
+}
+
+impl DeviceSettings {
+	fn default_for(kind: DeviceKind) -> Self {
+		Self {
+			pressure_curve: PressureCurve::default(),
+			touch_paints: kind != DeviceKind::Touch,
+			eraser_end_erases: true,
+			palm_rejection: PALM_REJECTION,
+		}
+	}
+}
+
+/// The pen of touch `pointer` touches with its eraser end. winit doesn't report it, but on Windows
+/// its touch ids are the pointer ids and events are handled while the pointer message is, so the
+/// pen state can still be asked for.
+#[cfg(windows)]
+pub fn eraser_end(pointer: u64) -> bool {
+	use windows::Win32::UI::{Input::Pointer::{GetPointerPenInfo, POINTER_PEN_INFO}, WindowsAndMessaging::{PEN_FLAG_ERASER, PEN_FLAG_INVERTED}};
+
+	let mut info = POINTER_PEN_INFO::default();
+	if unsafe { GetPointerPenInfo(pointer as u32, &mut info) }.is_err() {
+		return false;
+	}
+	return info.penFlags & (PEN_FLAG_ERASER | PEN_FLAG_INVERTED) != 0;
+}
+
+/// Other platforms don't report the pen tip
+#[cfg(not(windows))]
+pub fn eraser_end(_pointer: u64) -> bool {
+	false
+}
+
+/// Settings of every device kind, the pressure curves from their calibration file and the rest from
+/// `kind<TAB>touch_paints<TAB>eraser_end_erases<TAB>palm_rejection` lines, flags 0 or 1 and the delay in milliseconds.
+pub fn load_settings() -> HashMap<DeviceKind, DeviceSettings> {
+	let mut settings: HashMap<_, _> = DeviceKind::ALL.into_iter().map(|k| (k, DeviceSettings::default_for(k))).collect();
+
+	for (name, curve) in pressure::load_curves() {
+		if let Some(kind) = DeviceKind::from_name(&name) {
+			settings.get_mut(&kind).unwrap().pressure_curve = curve;
+		}
+	}
+
+	let lines = config::read_lines(SETTINGS_FILE, "device settings", |line| {
+		let fields: Vec<&str> = line.split('\t').collect();
+		let kind = fields.first().and_then(|n| DeviceKind::from_name(n))?;
+		let palm_rejection = fields.get(3).and_then(|ms| ms.parse().ok()).filter(|_| fields.len() == 4)?;
+		Some((kind, fields[1] == "1", fields[2] == "1", Duration::from_millis(palm_rejection)))
+	});
+	for (kind, touch_paints, eraser_end_erases, palm_rejection) in lines.unwrap_or_default() {
+		let entry = settings.get_mut(&kind).unwrap();
+		entry.touch_paints = touch_paints;
+		entry.eraser_end_erases = eraser_end_erases;
+		entry.palm_rejection = palm_rejection;
+	}
+	return settings;
+}
+
+pub fn save_settings(settings: &HashMap<DeviceKind, DeviceSettings>) {
+	let text: String = DeviceKind::ALL.iter().filter_map(|k| {
+		let s = settings.get(k)?;
+		Some(format!("{}\t{}\t{}\t{}\n", k.name(), s.touch_paints as u8, s.eraser_end_erases as u8, s.palm_rejection.as_millis()))
+	}).collect();
+	config::write_file(SETTINGS_FILE, &text);
+}
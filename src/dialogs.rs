@@ -0,0 +1,141 @@
+use std::{future::Future, path::{Path, PathBuf}, pin::Pin, task};
+
+use winit::window::Window;
+
+use crate::components::Rect;
+use crate::config;
+
+/// Directory the last file dialog picked a file in, offered first by the next one
+const LAST_DIRECTORY_FILE: &str = "last-directory";
+
+/// What a file dialog picks a file for.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FilePurpose {
+	/// A .pntr document or a PNG
+	Open,
+	SaveDocument,
+	ExportPng { verify: bool },
+	/// PNG of a document region drawn again from its strokes, `scale` times bigger
+	ExportRegion { region: Rect, scale: u32 },
+	/// PNGs of every recolored palette variant, numbered after the name picked
+	ExportPaletteVariants,
+	ExportSettings,
+	ImportSettings,
+	/// Image a new linked layer shows
+	LinkImage,
+}
+
+impl FilePurpose {
+	fn saves(self) -> bool {
+		!matches!(self, FilePurpose::Open | FilePurpose::ImportSettings | FilePurpose::LinkImage)
+	}
+
+	fn title(self) -> &'static str {
+		match self {
+			FilePurpose::Open => "Open",
+			FilePurpose::SaveDocument => "Save document",
+			FilePurpose::ExportPng { .. } => "Export image",
+			FilePurpose::ExportRegion { .. } => "Export region",
+			FilePurpose::ExportPaletteVariants => "Export palette variants",
+			FilePurpose::ExportSettings => "Export settings",
+			FilePurpose::ImportSettings => "Import settings",
+			FilePurpose::LinkImage => "Link image",
+		}
+	}
+
+	/// Name and extensions of the files shown
+	fn filter(self) -> (&'static str, &'static [&'static str]) {
+		match self {
+			FilePurpose::Open => ("Documents and images", &["pntr", "png", "jpg", "jpeg"]),
+			FilePurpose::SaveDocument => ("pntr documents", &["pntr"]),
+			FilePurpose::ExportPng { .. } | FilePurpose::ExportRegion { .. } | FilePurpose::LinkImage => ("Images", &["png", "jpg", "jpeg"]),
+			FilePurpose::ExportPaletteVariants => ("PNG images", &["png"]),
+			FilePurpose::ExportSettings | FilePurpose::ImportSettings => ("Settings bundles", &["zip"]),
+		}
+	}
+}
+
+/// Native file dialog, polled from the window update so the event loop keeps running while it's shown.
+pub struct FileDialog {
+	pub purpose: FilePurpose,
+	future: Pin<Box<dyn Future<Output = Option<rfd::FileHandle>>>>,
+	/// Runs the window update again once the dialog is answered
+	waker: task::Waker,
+}
+
+impl FileDialog {
+	/// Shows the dialog over `window`, saving dialogs suggest `file_name`.
+	pub fn show(purpose: FilePurpose, window: &Window, file_name: Option<&Path>, waker: task::Waker) -> Self {
+		let (name, extensions) = purpose.filter();
+		let mut dialog = rfd::AsyncFileDialog::new()
+			.set_title(purpose.title())
+			.add_filter(name, extensions)
+			.set_parent(window);
+		if let Some(dir) = load_last_directory() {
+			dialog = dialog.set_directory(dir);
+		}
+		if let Some(name) = file_name.and_then(|p| p.file_name()) {
+			dialog = dialog.set_file_name(name.to_string_lossy());
+		}
+
+		let future: Pin<Box<dyn Future<Output = Option<rfd::FileHandle>>>> = if purpose.saves() {
+			Box::pin(dialog.save_file())
+		} else {
+			Box::pin(dialog.pick_file())
+		};
+		return Self { purpose, future, waker };
+	}
+
+	/// Ready with the picked path once answered, None when cancelled.
+	pub fn poll(&mut self) -> task::Poll<Option<PathBuf>> {
+		let answer = self.future.as_mut().poll(&mut task::Context::from_waker(&self.waker));
+		return answer.map(|handle| {
+			let path = handle.map(|h| h.path().to_owned());
+			if let Some(dir) = path.as_deref().and_then(Path::parent) {
+				save_last_directory(dir);
+			}
+			path
+		});
+	}
+}
+
+/// Native dialog asking whether to save the changes to a document before its window closes, polled
+/// like `FileDialog`.
+pub struct SaveChangesDialog {
+	future: Pin<Box<dyn Future<Output = rfd::MessageDialogResult>>>,
+	waker: task::Waker,
+}
+
+impl SaveChangesDialog {
+	/// Shows the dialog over `window`, about the document called `name`.
+	pub fn show(window: &Window, name: &str, waker: task::Waker) -> Self {
+		let dialog = rfd::AsyncMessageDialog::new()
+			.set_level(rfd::MessageLevel::Warning)
+			.set_title("Unsaved changes")
+			.set_description(format!("Save the changes to {name} before closing?"))
+			.set_buttons(rfd::MessageButtons::YesNoCancel)
+			.set_parent(window);
+		return Self { future: Box::pin(dialog.show()), waker };
+	}
+
+	/// Ready with the button picked once answered.
+	pub fn poll(&mut self) -> task::Poll<rfd::MessageDialogResult> {
+		return self.future.as_mut().poll(&mut task::Context::from_waker(&self.waker));
+	}
+
+	/// Runs the window update again, for the file dialog shown after it.
+	pub fn waker(&self) -> task::Waker {
+		return self.waker.clone();
+	}
+}
+
+fn load_last_directory() -> Option<PathBuf> {
+	let text = config::read_file(LAST_DIRECTORY_FILE)?;
+	return Some(PathBuf::from(text.trim_end())).filter(|dir| dir.is_dir());
+}
+
+fn save_last_directory(dir: &Path) {
+	if let Some(text) = dir.to_str() {
+		config::write_file(LAST_DIRECTORY_FILE, text);
+	}
+}
@@ -0,0 +1,762 @@
+use std::{
+	fmt,
+	fs::File,
+	io::{self, Read, Seek, SeekFrom, Write},
+	path::{Path, PathBuf},
+	thread::JoinHandle,
+};
+
+use crate::components::{Brush, Context, LayerPixels, Logged, Point, Readback, SavedEdit, Size, Symmetry};
+use crate::export::{ExportError, Pixels};
+use crate::layers::{ColorTag, Layer, LinkedSource};
+
+// .pntr container, everything little endian:
+//    0  magic          "PNTR"
+//    4  version        u32
+//    8  width          u32
+//   12  height         u32
+//   16  layer_count    u32
+//   20  created        u64, unix seconds
+//   28  modified       u64, unix seconds
+//   36  preview_offset u32
+//   40  preview_len    u32
+//   44  active_seconds u64, tracked drawing time
+//   52  tag            u8, color the window is marked with, 0 before it was added
+//   53  flags          u8 (export annotations, stroke history)
+//   54  reserved up to HEADER_SIZE
+//   64  preview        downscaled PNG
+//       layer table    layer_count records, bottom layer first:
+//                        tag u8, flags u8 (lock pixels, lock position, background, linked, lock alpha,
+//                        annotation, grouped) since version 2,
+//                        name_len u16, name UTF-8,
+//                        link_len u16, link path UTF-8 when linked
+//                        group_len u16, group name UTF-8 when grouped, since version 4
+//                        (the cached raster is stored like any other layer's pixels)
+//       settings       brush size u32, hardness f32, spacing f32,
+//                      foreground and background colors 3 f32 each
+//       layer pixels   layer_count records, bottom layer first:
+//                        opacity f32, flags u8 (visible, masked), png_len u32, PNG with straight alpha,
+//                        mask_len u32 and grayscale PNG when masked
+//       history        with the stroke history flag, edit_count u32 edits oldest first:
+//                        kind u8 (stroke, clear), layer u32 index, flags u8 (mask, erasing, symmetric,
+//                        mirrored),
+//                        stroke: the brush fields in order, u32 or f32 like them, color 3 f32,
+//                        symmetry center 2 f32, folds u32 and mirror f32 when symmetric and mirrored,
+//                        point_count u32, points x i32, y i32, pressure f32
+//                        clear: premultiplied color 4 f32
+//
+// The header sits at a fixed offset so metadata and preview can be read without parsing the rest.
+//
+// Versions, by what each added. Files are read the way the version they were written with lays them out:
+//   1  header, preview and layer table of tags and names
+//   2  layer flags, link records, settings and layer pixels, the first that can be opened
+//   3  layer masks
+//   4  layer group records
+//   5  stroke history, which 3 and 4 files have too when their header flags say so
+// The header tag and flags were added to 3 and are zero in files written before them.
+
+const MAGIC: &[u8; 4] = b"PNTR";
+const VERSION: u32 = 5;
+/// First version with the layer flags and everything after the layer table
+const LAYER_FLAGS_VERSION: u32 = 2;
+const HEADER_SIZE: usize = 64;
+const PREVIEW_SIDE: u32 = 256;
+const LINKED_FLAG: u8 = 8;
+const GROUPED_FLAG: u8 = 64;
+/// Flags of the layer pixel records, versions before 3 only had the visibility
+const VISIBLE_FLAG: u8 = 1;
+const MASKED_FLAG: u8 = 2;
+/// Flags of the header
+const EXPORT_ANNOTATIONS_FLAG: u8 = 1;
+const HISTORY_FLAG: u8 = 2;
+/// Flags of the history edits
+const EDIT_MASK_FLAG: u8 = 1;
+const EDIT_ERASING_FLAG: u8 = 2;
+const EDIT_SYMMETRIC_FLAG: u8 = 4;
+const EDIT_MIRRORED_FLAG: u8 = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Metadata {
+	pub size: Size,
+	pub layer_count: u32,
+	pub created: u64,
+	pub modified: u64,
+	/// Time spent drawing, idle time excluded
+	pub active_seconds: u64,
+	/// Tells the windows of documents apart
+	pub tag: ColorTag,
+	/// Exports include the annotation layers
+	pub export_annotations: bool,
+	/// The strokes are saved with the pixels, so regions can be drawn again from them
+	pub save_history: bool,
+}
+
+/// Everything a .pntr file holds.
+pub struct Document {
+	pub metadata: Metadata,
+	pub layers: Vec<Layer>,
+	pub pixels: Vec<LayerPixels>,
+	pub brush: Brush,
+	/// Foreground and background
+	pub colors: [[f32; 3]; 2],
+	/// Saved with `Metadata::save_history`, empty otherwise
+	pub history: Vec<SavedEdit>,
+}
+
+#[derive(Debug)]
+pub enum DocumentError {
+	Io(io::Error),
+	Export(ExportError),
+	Format(&'static str),
+}
+
+impl fmt::Display for DocumentError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			DocumentError::Io(e) => write!(f, "{e}"),
+			DocumentError::Export(e) => write!(f, "{e}"),
+			DocumentError::Format(e) => write!(f, "not a valid pntr document: {e}"),
+		}
+	}
+}
+
+impl From<io::Error> for DocumentError {
+	fn from(e: io::Error) -> Self {
+		DocumentError::Io(e)
+	}
+}
+
+impl From<ExportError> for DocumentError {
+	fn from(e: ExportError) -> Self {
+		DocumentError::Export(e)
+	}
+}
+
+fn u32_at(header: &[u8; HEADER_SIZE], offset: usize) -> u32 {
+	u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+	let mut bytes = [0; 4];
+	r.read_exact(&mut bytes)?;
+	return Ok(u32::from_le_bytes(bytes));
+}
+
+fn read_f32(r: &mut impl Read) -> io::Result<f32> {
+	Ok(f32::from_bits(read_u32(r)?))
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+	let mut byte = [0];
+	r.read_exact(&mut byte)?;
+	return Ok(byte[0]);
+}
+
+/// Fails unless the file of `len` bytes has `count` more past where `r` is, so lengths taken from
+/// it can't make loading allocate more than it holds.
+fn check_left(r: &mut impl Seek, count: u64, len: u64) -> Result<(), DocumentError> {
+	if r.stream_position()?.saturating_add(count) > len {
+		return Err(DocumentError::Format("a length runs past the end of the file"));
+	}
+	return Ok(());
+}
+
+/// `len` bytes, after `check_left`.
+fn read_bytes(r: &mut (impl Read + Seek), len: usize, file_len: u64) -> Result<Vec<u8>, DocumentError> {
+	check_left(r, len as u64, file_len)?;
+	let mut bytes = vec![0; len];
+	r.read_exact(&mut bytes)?;
+	return Ok(bytes);
+}
+
+fn u64_at(header: &[u8; HEADER_SIZE], offset: usize) -> u64 {
+	u64::from_le_bytes(header[offset..offset + 8].try_into().unwrap())
+}
+
+/// Downscales the document to the size stored in the header.
+pub fn make_preview(pixels: &Pixels) -> Pixels {
+	let img = image::DynamicImage::ImageRgba8(pixels.to_image()).thumbnail(PREVIEW_SIDE, PREVIEW_SIDE);
+	return Pixels::from_image(img.into_rgba8());
+}
+
+/// Writes the header and preview, the document data follows right after.
+pub fn write_header(w: &mut impl Write, metadata: &Metadata, preview: &Pixels) -> Result<(), DocumentError> {
+	let png = preview.encode_png()?;
+
+	let mut header = [0u8; HEADER_SIZE];
+	header[0..4].copy_from_slice(MAGIC);
+	header[4..8].copy_from_slice(&VERSION.to_le_bytes());
+	header[8..12].copy_from_slice(&metadata.size.w.to_le_bytes());
+	header[12..16].copy_from_slice(&metadata.size.h.to_le_bytes());
+	header[16..20].copy_from_slice(&metadata.layer_count.to_le_bytes());
+	header[20..28].copy_from_slice(&metadata.created.to_le_bytes());
+	header[28..36].copy_from_slice(&metadata.modified.to_le_bytes());
+	header[36..40].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+	header[40..44].copy_from_slice(&(png.len() as u32).to_le_bytes());
+	header[44..52].copy_from_slice(&metadata.active_seconds.to_le_bytes());
+	header[52] = metadata.tag.to_u8();
+	header[53] = if metadata.export_annotations { EXPORT_ANNOTATIONS_FLAG } else { 0 } | if metadata.save_history { HISTORY_FLAG } else { 0 };
+
+	w.write_all(&header)?;
+	w.write_all(&png)?;
+	return Ok(());
+}
+
+fn read_header(r: &mut impl Read) -> Result<[u8; HEADER_SIZE], DocumentError> {
+	let mut header = [0; HEADER_SIZE];
+	r.read_exact(&mut header)?;
+
+	if &header[0..4] != MAGIC {
+		return Err(DocumentError::Format("wrong magic"));
+	}
+
+	if u32_at(&header, 4) > VERSION {
+		return Err(DocumentError::Format("made by a newer version of pntr"));
+	}
+
+	return Ok(header);
+}
+
+fn metadata_of(header: &[u8; HEADER_SIZE]) -> Result<Metadata, DocumentError> {
+	return Ok(Metadata {
+		size: Size { w: u32_at(header, 8), h: u32_at(header, 12) },
+		layer_count: u32_at(header, 16),
+		created: u64_at(header, 20),
+		modified: u64_at(header, 28),
+		active_seconds: u64_at(header, 44),
+		tag: ColorTag::from_u8(header[52]).ok_or(DocumentError::Format("unknown document tag"))?,
+		export_annotations: header[53] & EXPORT_ANNOTATIONS_FLAG != 0,
+		save_history: header[53] & HISTORY_FLAG != 0,
+	});
+}
+
+/// Reads only the embedded preview, without touching the rest of the document.
+pub fn read_preview(path: &Path) -> Result<Pixels, DocumentError> {
+	let mut file = File::open(path)?;
+	let header = read_header(&mut file)?;
+
+	let (offset, len) = (u32_at(&header, 36) as u64, u32_at(&header, 40) as u64);
+	if offset + len > file.metadata()?.len() {
+		return Err(DocumentError::Format("preview runs past the end of the file"));
+	}
+	file.seek(SeekFrom::Start(offset))?;
+	let mut png = vec![0; len as usize];
+	file.read_exact(&mut png)?;
+	return Ok(Pixels::decode_png(&png)?);
+}
+
+/// Names and tags of the layers, written right after the preview.
+pub fn write_layer_table(w: &mut impl Write, layers: &[Layer]) -> Result<(), DocumentError> {
+	for layer in layers {
+		let name = layer.name.as_bytes();
+		let len: u16 = name.len().try_into().map_err(|_| DocumentError::Format("layer name too long"))?;
+		w.write_all(&[layer.tag.to_u8(), layer.flags()])?;
+		w.write_all(&len.to_le_bytes())?;
+		w.write_all(name)?;
+
+		if let Some(link) = &layer.link {
+			let path = link.path.to_str().ok_or(DocumentError::Format("linked path isn't UTF-8"))?.as_bytes();
+			let len: u16 = path.len().try_into().map_err(|_| DocumentError::Format("linked path too long"))?;
+			w.write_all(&len.to_le_bytes())?;
+			w.write_all(path)?;
+		}
+		if let Some(group) = &layer.group {
+			let len: u16 = group.len().try_into().map_err(|_| DocumentError::Format("group name too long"))?;
+			w.write_all(&len.to_le_bytes())?;
+			w.write_all(group.as_bytes())?;
+		}
+	}
+	return Ok(());
+}
+
+/// The layer table of a file of `version`, `file_len` bytes long.
+pub fn read_layer_table(r: &mut (impl Read + Seek), layer_count: u32, version: u32, file_len: u64) -> Result<Vec<Layer>, DocumentError> {
+	let mut layers = Vec::new();
+	for _ in 0..layer_count {
+		let tag = ColorTag::from_u8(read_u8(r)?).ok_or(DocumentError::Format("unknown layer tag"))?;
+		let flags = if version >= LAYER_FLAGS_VERSION { read_u8(r)? } else { 0 };
+		let mut len = [0; 2];
+		r.read_exact(&mut len)?;
+		let name = read_bytes(r, u16::from_le_bytes(len) as usize, file_len)?;
+		let name = String::from_utf8(name).map_err(|_| DocumentError::Format("layer name isn't UTF-8"))?;
+
+		let mut layer = Layer::new(&name);
+		layer.tag = tag;
+		layer.set_flags(flags);
+
+		if flags & LINKED_FLAG != 0 {
+			let mut len = [0; 2];
+			r.read_exact(&mut len)?;
+			let path = read_bytes(r, u16::from_le_bytes(len) as usize, file_len)?;
+			let path = String::from_utf8(path).map_err(|_| DocumentError::Format("linked path isn't UTF-8"))?;
+			layer.link = Some(LinkedSource::new(path.into()));
+		}
+		if flags & GROUPED_FLAG != 0 {
+			let mut len = [0; 2];
+			r.read_exact(&mut len)?;
+			let group = read_bytes(r, u16::from_le_bytes(len) as usize, file_len)?;
+			layer.group = Some(String::from_utf8(group).map_err(|_| DocumentError::Format("group name isn't UTF-8"))?);
+		}
+		layers.push(layer);
+	}
+	return Ok(layers);
+}
+
+fn write_settings(w: &mut impl Write, brush: &Brush, colors: &[[f32; 3]; 2]) -> io::Result<()> {
+	w.write_all(&brush.size.to_le_bytes())?;
+	w.write_all(&brush.hardness.to_le_bytes())?;
+	w.write_all(&brush.spacing.to_le_bytes())?;
+	for c in colors.iter().flatten() {
+		w.write_all(&c.to_le_bytes())?;
+	}
+	return Ok(());
+}
+
+fn read_settings(r: &mut impl Read) -> io::Result<(Brush, [[f32; 3]; 2])> {
+	let brush = Brush {
+		size: read_u32(r)?,
+		hardness: read_f32(r)?,
+		spacing: read_f32(r)?,
+		..Brush::default()
+	};
+	let mut colors = [[0.; 3]; 2];
+	for c in colors.iter_mut().flatten() {
+		*c = read_f32(r)?;
+	}
+	return Ok((brush, colors));
+}
+
+fn write_brush(w: &mut impl Write, brush: &Brush) -> io::Result<()> {
+	let Brush { size, hardness, spacing, pressure_size, pressure_opacity, opacity, flow, stabilizer, rotation, scatter, size_jitter, hue_jitter, lightness_jitter, rotation_jitter, seed } = *brush;
+	w.write_all(&size.to_le_bytes())?;
+	for v in [hardness, spacing, pressure_size, pressure_opacity, opacity, flow] {
+		w.write_all(&v.to_le_bytes())?;
+	}
+	w.write_all(&stabilizer.to_le_bytes())?;
+	for v in [rotation, scatter, size_jitter, hue_jitter, lightness_jitter, rotation_jitter] {
+		w.write_all(&v.to_le_bytes())?;
+	}
+	w.write_all(&seed.to_le_bytes())?;
+	return Ok(());
+}
+
+fn read_brush(r: &mut impl Read) -> io::Result<Brush> {
+	return Ok(Brush {
+		size: read_u32(r)?,
+		hardness: read_f32(r)?,
+		spacing: read_f32(r)?,
+		pressure_size: read_f32(r)?,
+		pressure_opacity: read_f32(r)?,
+		opacity: read_f32(r)?,
+		flow: read_f32(r)?,
+		stabilizer: read_u32(r)?,
+		rotation: read_f32(r)?,
+		scatter: read_f32(r)?,
+		size_jitter: read_f32(r)?,
+		hue_jitter: read_f32(r)?,
+		lightness_jitter: read_f32(r)?,
+		rotation_jitter: read_f32(r)?,
+		seed: read_u32(r)?,
+	});
+}
+
+fn write_history(w: &mut impl Write, history: &[SavedEdit]) -> Result<(), DocumentError> {
+	let count: u32 = history.len().try_into().map_err(|_| DocumentError::Format("history too long"))?;
+	w.write_all(&count.to_le_bytes())?;
+	for edit in history {
+		let mask = if edit.mask { EDIT_MASK_FLAG } else { 0 };
+		let layer = edit.layer as u32;
+		match &edit.logged {
+			Logged::Stroke { points, brush, symmetry, color, erasing, .. } => {
+				let flags = mask | if *erasing { EDIT_ERASING_FLAG } else { 0 }
+					| if symmetry.is_some() { EDIT_SYMMETRIC_FLAG } else { 0 }
+					| if symmetry.is_some_and(|s| s.mirror.is_some()) { EDIT_MIRRORED_FLAG } else { 0 };
+				w.write_all(&[0])?;
+				w.write_all(&layer.to_le_bytes())?;
+				w.write_all(&[flags])?;
+				write_brush(w, brush)?;
+				for c in color {
+					w.write_all(&c.to_le_bytes())?;
+				}
+				if let Some(symmetry) = symmetry {
+					w.write_all(&symmetry.center[0].to_le_bytes())?;
+					w.write_all(&symmetry.center[1].to_le_bytes())?;
+					w.write_all(&symmetry.folds.to_le_bytes())?;
+					if let Some(mirror) = symmetry.mirror {
+						w.write_all(&mirror.to_le_bytes())?;
+					}
+				}
+				w.write_all(&(points.len() as u32).to_le_bytes())?;
+				for (p, pressure) in points {
+					w.write_all(&p.x.to_le_bytes())?;
+					w.write_all(&p.y.to_le_bytes())?;
+					w.write_all(&pressure.to_le_bytes())?;
+				}
+			}
+			Logged::Clear(color) => {
+				w.write_all(&[1])?;
+				w.write_all(&layer.to_le_bytes())?;
+				w.write_all(&[mask])?;
+				for c in color {
+					w.write_all(&c.to_le_bytes())?;
+				}
+			}
+		}
+	}
+	return Ok(());
+}
+
+/// Every record is at least this long, the number of edits is checked against it
+const MIN_EDIT_BYTES: u64 = 6;
+const POINT_BYTES: u64 = 12;
+
+fn read_history(r: &mut (impl Read + Seek), file_len: u64) -> Result<Vec<SavedEdit>, DocumentError> {
+	let count = read_u32(r)?;
+	check_left(r, count as u64 * MIN_EDIT_BYTES, file_len)?;
+	let mut history = Vec::with_capacity(count as usize);
+	for _ in 0..count {
+		let kind = read_u8(r)?;
+		let layer = read_u32(r)? as usize;
+		let flags = read_u8(r)?;
+		let logged = match kind {
+			0 => {
+				let brush = read_brush(r)?;
+				let color = [read_f32(r)?, read_f32(r)?, read_f32(r)?];
+				let symmetry = if flags & EDIT_SYMMETRIC_FLAG != 0 {
+					Some(Symmetry {
+						center: [read_f32(r)?, read_f32(r)?],
+						folds: read_u32(r)?,
+						mirror: if flags & EDIT_MIRRORED_FLAG != 0 { Some(read_f32(r)?) } else { None },
+					})
+				} else {
+					None
+				};
+				let point_count = read_u32(r)?;
+				check_left(r, point_count as u64 * POINT_BYTES, file_len)?;
+				let mut points = Vec::with_capacity(point_count as usize);
+				for _ in 0..point_count {
+					let p = Point { x: read_u32(r)? as i32, y: read_u32(r)? as i32 };
+					points.push((p, read_f32(r)?));
+				}
+				Logged::Stroke { points, brush, tip: None, symmetry, color, erasing: flags & EDIT_ERASING_FLAG != 0 }
+			}
+			1 => Logged::Clear([read_f32(r)?, read_f32(r)?, read_f32(r)?, read_f32(r)?]),
+			_ => return Err(DocumentError::Format("unknown history edit")),
+		};
+		history.push(SavedEdit { layer, mask: flags & EDIT_MASK_FLAG != 0, logged });
+	}
+	return Ok(history);
+}
+
+/// Writes next to `path` first and renames over it, so a failed save leaves the previous file alone.
+/// `composite` is what the layers look like together, downscaled for the preview.
+pub fn save(path: &Path, document: &Document, composite: &Pixels) -> Result<(), DocumentError> {
+	let tmp = path.with_extension("pntr.tmp");
+	let mut w = io::BufWriter::new(File::create(&tmp)?);
+	write_document(&mut w, document, composite)?;
+	w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+	std::fs::rename(&tmp, path)?;
+	return Ok(());
+}
+
+fn write_document(w: &mut impl Write, document: &Document, composite: &Pixels) -> Result<(), DocumentError> {
+	write_header(w, &document.metadata, &make_preview(composite))?;
+	write_layer_table(w, &document.layers)?;
+	write_settings(w, &document.brush, &document.colors)?;
+	for layer in &document.pixels {
+		let png = layer.pixels.encode_png()?;
+		w.write_all(&layer.opacity.to_le_bytes())?;
+		w.write_all(&[if layer.visible { VISIBLE_FLAG } else { 0 } | if layer.mask.is_some() { MASKED_FLAG } else { 0 }])?;
+		w.write_all(&(png.len() as u32).to_le_bytes())?;
+		w.write_all(&png)?;
+		if let Some(mask) = &layer.mask {
+			let png = mask.encode_png()?;
+			w.write_all(&(png.len() as u32).to_le_bytes())?;
+			w.write_all(&png)?;
+		}
+	}
+	if document.metadata.save_history {
+		write_history(w, &document.history)?;
+	}
+	return Ok(());
+}
+
+/// Layer pixels or mask, of the document `size`.
+fn read_png(r: &mut (impl Read + Seek), size: Size, file_len: u64) -> Result<Pixels, DocumentError> {
+	let len = read_u32(r)? as usize;
+	let png = read_bytes(r, len, file_len)?;
+	let pixels = Pixels::decode_png(&png)?;
+	if pixels.size != size {
+		return Err(DocumentError::Format("layer size doesn't match the document"));
+	}
+	return Ok(pixels);
+}
+
+pub fn load(path: &Path) -> Result<Document, DocumentError> {
+	let file = File::open(path)?;
+	let file_len = file.metadata()?.len();
+	return read_document(&mut io::BufReader::new(file), file_len);
+}
+
+/// The document of a file `file_len` bytes long.
+fn read_document(r: &mut (impl Read + Seek), file_len: u64) -> Result<Document, DocumentError> {
+	let header = read_header(r)?;
+	let version = u32_at(&header, 4);
+	if version < LAYER_FLAGS_VERSION {
+		return Err(DocumentError::Format("made before documents kept their layers"));
+	}
+	let metadata = metadata_of(&header)?;
+	r.seek(SeekFrom::Start(u32_at(&header, 36) as u64 + u32_at(&header, 40) as u64))?;
+
+	let layers = read_layer_table(r, metadata.layer_count, version, file_len)?;
+	let (brush, colors) = read_settings(r)?;
+
+	let mut pixels = Vec::new();
+	for _ in 0..metadata.layer_count {
+		let opacity = read_f32(r)?;
+		let flags = read_u8(r)?;
+		let layer = read_png(r, metadata.size, file_len)?;
+		let mask = if flags & MASKED_FLAG != 0 { Some(read_png(r, metadata.size, file_len)?) } else { None };
+		pixels.push(LayerPixels { pixels: layer, opacity, visible: flags & VISIBLE_FLAG != 0, mask });
+	}
+	let history = if metadata.save_history { read_history(r, file_len)? } else { Vec::new() };
+
+	return Ok(Document { metadata, layers, pixels, brush, colors, history });
+}
+
+/// Layer of a document on its way from the GPU.
+pub struct LayerReadback {
+	pub opacity: f32,
+	pub visible: bool,
+	pub pixels: Readback,
+	pub mask: Option<Readback>,
+}
+
+/// Document waiting for the pixels of its layers, everything else is taken when it starts.
+pub struct Snapshot {
+	document: Document,
+	layers: Vec<LayerReadback>,
+	/// What the layers look like together, for the preview
+	composite: Readback,
+}
+
+impl Snapshot {
+	/// `document` with its own pixels replaced by `layers` once they are read back.
+	pub fn new(document: Document, layers: Vec<LayerReadback>, composite: Readback) -> Self {
+		return Self { document, layers, composite };
+	}
+
+	/// What is saved besides the pixels.
+	pub fn document(&self) -> &Document {
+		return &self.document;
+	}
+
+	pub fn is_ready(&self, ctx: &Context) -> bool {
+		return self.composite.is_ready(ctx) && self.layers.iter().all(|l| l.pixels.is_ready(ctx) && l.mask.as_ref().is_none_or(|m| m.is_ready(ctx)));
+	}
+
+	/// Saves to `path` on a thread, once `is_ready`.
+	fn write(self, ctx: &Context, path: PathBuf) -> JoinHandle<Result<(), DocumentError>> {
+		let Snapshot { mut document, layers, composite } = self;
+		document.pixels = layers.iter().map(|l| LayerPixels {
+			pixels: l.pixels.try_finish(ctx).unwrap(),
+			opacity: l.opacity,
+			visible: l.visible,
+			mask: l.mask.as_ref().map(|m| m.try_finish(ctx).unwrap()),
+		}).collect();
+		let composite = composite.try_finish(ctx).unwrap();
+		return std::thread::spawn(move || {
+			if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+				std::fs::create_dir_all(dir)?;
+			}
+			return save(&path, &document, &composite);
+		});
+	}
+}
+
+/// Save of a document across frames, so drawing never waits for it: the layers are read back, then
+/// written by a thread.
+pub struct BackgroundSave {
+	pub path: PathBuf,
+	snapshot: Option<Snapshot>,
+	writing: Option<JoinHandle<Result<(), DocumentError>>>,
+}
+
+impl BackgroundSave {
+	pub fn start(path: PathBuf, snapshot: Snapshot) -> Self {
+		return Self { path, snapshot: Some(snapshot), writing: None };
+	}
+
+	/// How the save went once the file is written, None until then.
+	pub fn poll(&mut self, ctx: &Context) -> Option<Result<(), DocumentError>> {
+		if let Some(snapshot) = self.snapshot.take_if(|s| s.is_ready(ctx)) {
+			self.writing = Some(snapshot.write(ctx, self.path.clone()));
+		}
+		if !self.writing.as_ref().is_some_and(JoinHandle::is_finished) {
+			return None;
+		}
+		let result = self.writing.take().unwrap().join();
+		return Some(result.unwrap_or_else(|_| Err(io::Error::other("the saving thread panicked").into())));
+	}
+
+	/// Waits for the file to be written, if it is being. Readbacks still coming are dropped.
+	pub fn finish(self) {
+		if let Some(writing) = self.writing {
+			let _ = writing.join();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Cursor;
+
+	fn document(save_history: bool) -> Document {
+		let size = Size { w: 3, h: 2 };
+		let mut layers = vec![Layer::new("Background"), Layer::new("Ink"), Layer::new("Reference")];
+		layers[0].set_flags(4);
+		layers[1].tag = ColorTag::Green;
+		layers[1].lock_alpha = true;
+		layers[1].group = Some("Lines".to_owned());
+		layers[2].link = Some(LinkedSource::new("missing/reference.png".into()));
+
+		let mut pixels = Vec::new();
+		for i in 0..layers.len() {
+			let mut layer = Pixels::new(size);
+			layer.data.iter_mut().enumerate().for_each(|(j, b)| *b = (i * 40 + j) as u8);
+			let mask = (i == 1).then(|| Pixels { size, data: [0, 64, 128, 192, 255, 32].repeat(4) });
+			pixels.push(LayerPixels { pixels: layer, opacity: 1. / (i + 1) as f32, visible: i != 2, mask });
+		}
+
+		let stroke = Logged::Stroke {
+			points: vec![(Point { x: -1, y: 2 }, 0.5), (Point { x: 3, y: 4 }, 1.)],
+			brush: Brush { seed: 7, ..Brush::default() },
+			tip: None,
+			symmetry: Some(Symmetry { center: [1.5, 1.], folds: 3, mirror: Some(0.25) }),
+			color: [0.1, 0.2, 0.3],
+			erasing: true,
+		};
+		let history = vec![
+			SavedEdit { layer: 1, mask: true, logged: stroke },
+			SavedEdit { layer: 0, mask: false, logged: Logged::Clear([0., 0., 0., 1.]) },
+		];
+
+		return Document {
+			metadata: Metadata {
+				size,
+				layer_count: layers.len() as u32,
+				created: 1_700_000_000,
+				modified: 1_700_000_600,
+				active_seconds: 420,
+				tag: ColorTag::Blue,
+				export_annotations: true,
+				save_history,
+			},
+			layers,
+			pixels,
+			brush: Brush { size: 12, hardness: 0.5, spacing: 0.1, ..Brush::default() },
+			colors: [[1., 0.5, 0.], [0., 0., 1.]],
+			history,
+		};
+	}
+
+	fn written(document: &Document) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		write_document(&mut bytes, document, &document.pixels[0].pixels).unwrap();
+		return bytes;
+	}
+
+	fn read(bytes: &[u8]) -> Result<Document, DocumentError> {
+		return read_document(&mut Cursor::new(bytes), bytes.len() as u64);
+	}
+
+	#[test]
+	fn documents_read_back_as_written() {
+		let document = document(true);
+		let read = read(&written(&document)).unwrap();
+		assert_eq!(read.metadata, document.metadata);
+		assert_eq!(read.layers, document.layers);
+		assert_eq!((read.brush, read.colors), (document.brush, document.colors));
+		for (read, layer) in read.pixels.iter().zip(&document.pixels) {
+			assert_eq!((&read.pixels, read.opacity, read.visible, &read.mask), (&layer.pixels, layer.opacity, layer.visible, &layer.mask));
+		}
+
+		assert_eq!(read.history.len(), 2);
+		let SavedEdit { layer: 1, mask: true, logged: Logged::Stroke { points, brush, symmetry, color, erasing: true, .. } } = &read.history[0] else {
+			panic!("the stroke didn't read back");
+		};
+		assert_eq!(points, &[(Point { x: -1, y: 2 }, 0.5), (Point { x: 3, y: 4 }, 1.)]);
+		assert_eq!((brush.seed, *color), (7, [0.1, 0.2, 0.3]));
+		assert_eq!(*symmetry, Some(Symmetry { center: [1.5, 1.], folds: 3, mirror: Some(0.25) }));
+		assert!(matches!(read.history[1], SavedEdit { layer: 0, mask: false, logged: Logged::Clear([0., 0., 0., 1.]) }));
+	}
+
+	#[test]
+	fn history_is_only_kept_when_asked() {
+		assert!(read(&written(&document(false))).unwrap().history.is_empty());
+	}
+
+	#[test]
+	fn metadata_and_layer_table_read_back() {
+		let document = document(false);
+		let mut bytes = Vec::new();
+		write_header(&mut bytes, &document.metadata, &make_preview(&document.pixels[0].pixels)).unwrap();
+		write_layer_table(&mut bytes, &document.layers).unwrap();
+
+		let mut r = Cursor::new(&bytes);
+		let header = read_header(&mut r).unwrap();
+		assert_eq!(metadata_of(&header).unwrap(), document.metadata);
+		r.seek(SeekFrom::Start(u32_at(&header, 36) as u64 + u32_at(&header, 40) as u64)).unwrap();
+		let layers = read_layer_table(&mut r, document.metadata.layer_count, VERSION, bytes.len() as u64).unwrap();
+		assert_eq!(layers, document.layers);
+	}
+
+	#[test]
+	fn version_1_layer_tables_have_no_flags() {
+		let mut bytes = vec![ColorTag::Red.to_u8()];
+		bytes.extend(4u16.to_le_bytes());
+		bytes.extend(b"Inks");
+		let layers = read_layer_table(&mut Cursor::new(&bytes), 1, 1, bytes.len() as u64).unwrap();
+		assert_eq!((layers[0].name.as_str(), layers[0].tag, layers[0].flags()), ("Inks", ColorTag::Red, 0));
+	}
+
+	#[test]
+	fn documents_before_version_2_and_after_this_one_are_refused() {
+		let mut bytes = written(&document(false));
+		for version in [1, VERSION + 1] {
+			bytes[4..8].copy_from_slice(&u32::to_le_bytes(version));
+			assert!(matches!(read(&bytes), Err(DocumentError::Format(_))));
+		}
+	}
+
+	#[test]
+	fn truncated_documents_fail_to_read() {
+		let bytes = written(&document(true));
+		for len in 0..bytes.len() {
+			assert!(read(&bytes[..len]).is_err(), "{len} of {} bytes read", bytes.len());
+		}
+	}
+
+	#[test]
+	fn lengths_past_the_end_are_refused() {
+		let mut bytes = vec![0];
+		bytes.extend(u16::MAX.to_le_bytes());
+		let table = read_layer_table(&mut Cursor::new(&bytes), 1, 1, bytes.len() as u64);
+		assert!(matches!(table, Err(DocumentError::Format(_))));
+
+		let mut r = Cursor::new(u32::MAX.to_le_bytes().repeat(2));
+		assert!(matches!(read_png(&mut r, Size { w: 1, h: 1 }, 8), Err(DocumentError::Format(_))));
+
+		let mut r = Cursor::new(u32::MAX.to_le_bytes());
+		assert!(matches!(read_history(&mut r, 4), Err(DocumentError::Format(_))));
+
+		// One stroke that claims more points than there are bytes
+		let mut history = Vec::new();
+		write_history(&mut history, &document(true).history[..1]).unwrap();
+		let len = history.len() as u64;
+		history[len as usize - 2 * POINT_BYTES as usize - 4..][..4].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(matches!(read_history(&mut Cursor::new(&history), len), Err(DocumentError::Format(_))));
+	}
+}
@@ -0,0 +1,522 @@
+use std::{
+	fmt,
+	io,
+	path::{Path, PathBuf},
+	sync::Mutex,
+	thread::JoinHandle,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::components::{Context, Point, Readback, Size};
+use crate::config;
+
+pub const PRESETS_FILE: &str = "export-presets.toml";
+/// From 1 to 100
+const JPEG_QUALITY: u8 = 90;
+
+/// RGBA8 pixels, sRGB encoded like the canvas textures store them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pixels {
+	pub size: Size,
+	pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+	Image(image::ImageError),
+	/// The file read back doesn't match what was written, first differing byte.
+	Mismatch(usize),
+	/// The file read back isn't as big as what was written.
+	SizeMismatch { expected: Size, found: Size },
+}
+
+impl fmt::Display for ExportError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ExportError::Image(e) => write!(f, "{e}"),
+			ExportError::Mismatch(i) => write!(f, "exported file differs from the canvas at byte {i}"),
+			ExportError::SizeMismatch { expected, found } => write!(f, "exported file is {}x{} pixels instead of {}x{}", found.w, found.h, expected.w, expected.h),
+		}
+	}
+}
+
+impl From<image::ImageError> for ExportError {
+	fn from(e: image::ImageError) -> Self {
+		ExportError::Image(e)
+	}
+}
+
+/// Second and number of the last path `export_path` gave out
+static LAST_EXPORT: Mutex<(u64, u32)> = Mutex::new((0, 0));
+
+/// `pntr-<unix seconds>.<extension>` in `folder`, numbered `pntr-<unix seconds>-<n>` when an
+/// export was named in the same second or the file is there already.
+pub fn export_path(folder: &Path, extension: &str) -> PathBuf {
+	let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+	let mut last = LAST_EXPORT.lock().unwrap();
+	let mut n = if last.0 == secs { last.1 + 1 } else { 0 };
+	loop {
+		let name = match n {
+			0 => format!("pntr-{secs}.{extension}"),
+			n => format!("pntr-{secs}-{n}.{extension}"),
+		};
+		let path = folder.join(name);
+		if !path.exists() {
+			*last = (secs, n);
+			return path;
+		}
+		n += 1;
+	}
+}
+
+/// Name the export dialog suggests, a PNG in the working directory.
+pub fn default_export_path() -> PathBuf {
+	return export_path(Path::new(""), "png");
+}
+
+/// Export waiting for the canvas to be read back, see `Canvas::export`, then for its file to be
+/// written.
+pub struct PendingExport {
+	pub path: PathBuf,
+	readback: Readback,
+	/// Read the file back to check it
+	pub verify: bool,
+	pub preset: Option<ExportPreset>,
+	writing: Option<JoinHandle<Result<bool, ExportError>>>,
+}
+
+impl PendingExport {
+	pub fn new(path: PathBuf, readback: Readback) -> Self {
+		return Self { path, readback, verify: false, preset: None, writing: None };
+	}
+
+	/// How the export went once the file is written, whether it was verified when it was. None
+	/// until then.
+	pub fn poll(&mut self, ctx: &Context) -> Option<Result<bool, ExportError>> {
+		if self.writing.is_none() {
+			let pixels = self.readback.try_finish(ctx)?;
+			self.writing = Some(save_in_background(pixels, self.path.clone(), self.verify, self.preset.clone()));
+		}
+		if !self.writing.as_ref().is_some_and(JoinHandle::is_finished) {
+			return None;
+		}
+		let result = self.writing.take().unwrap().join();
+		return Some(result.unwrap_or_else(|_| Err(image::ImageError::IoError(io::Error::other("the exporting thread panicked")).into())));
+	}
+}
+
+/// Encodes and writes `pixels` on another thread in the format of the file extension, optionally
+/// reading the file back to check it. `preset` scales and flattens them first. The thread tells
+/// whether the file was verified, only PNGs are.
+pub fn save_in_background(pixels: Pixels, path: PathBuf, verify: bool, preset: Option<ExportPreset>) -> JoinHandle<Result<bool, ExportError>> {
+	return std::thread::spawn(move || {
+		let pixels = match preset {
+			Some(preset) => {
+				if let Err(e) = std::fs::create_dir_all(&preset.folder) {
+					log::error!("Could not create {}: {e}", preset.folder.display());
+					return Err(image::ImageError::IoError(e).into());
+				}
+				preset.apply(pixels)
+			}
+			None => pixels,
+		};
+		let format = ExportFormat::from_path(&path);
+		// JPEG is lossy, there is nothing to compare the file to
+		let verify = verify && format == ExportFormat::Png;
+		let result = pixels.save(&path, format).and_then(|()| if verify { pixels.verify_png(&path) } else { Ok(()) });
+		match &result {
+			Ok(()) if verify => log::info!("Exported and verified {}", path.display()),
+			Ok(()) => log::info!("Exported {}", path.display()),
+			Err(e) => log::error!("Could not export {}: {e}", path.display()),
+		}
+		return result.map(|()| verify);
+	});
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+	#[serde(rename = "png")]
+	Png,
+	/// Flattened over white, it has no transparency
+	#[serde(rename = "jpg")]
+	Jpeg,
+}
+
+impl ExportFormat {
+	pub fn extension(&self) -> &'static str {
+		match self {
+			ExportFormat::Png => "png",
+			ExportFormat::Jpeg => "jpg",
+		}
+	}
+
+	/// Format the extension of `path` names, PNG unless it's a JPEG one.
+	pub fn from_path(path: &Path) -> Self {
+		match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+			Some("jpg" | "jpeg") => ExportFormat::Jpeg,
+			_ => ExportFormat::Png,
+		}
+	}
+}
+
+/// Named way of exporting, run from its hotkey.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ExportPreset {
+	pub name: String,
+	pub format: ExportFormat,
+	/// Size of the exported image relative to the document
+	pub scale: f32,
+	/// Opaque color transparent pixels are flattened over, None to keep them transparent
+	pub background: Option<[u8; 3]>,
+	pub folder: PathBuf,
+}
+
+/// The export presets file, a `[[preset]]` table per preset.
+#[derive(Default, Serialize, Deserialize)]
+struct PresetsFile {
+	#[serde(default, rename = "preset")]
+	presets: Vec<ExportPreset>,
+}
+
+impl ExportPreset {
+	/// `pntr-<unix seconds>` in the preset folder, see `export_path`.
+	pub fn path(&self) -> PathBuf {
+		return export_path(&self.folder, self.format.extension());
+	}
+
+	pub fn apply(&self, mut pixels: Pixels) -> Pixels {
+		if self.scale != 1. {
+			let [w, h] = [pixels.size.w, pixels.size.h].map(|side| ((side as f32 * self.scale).round() as u32).max(1));
+			pixels = pixels.resized(Size { w, h });
+		}
+		if let Some(color) = self.background {
+			pixels.flatten(color);
+		}
+		return pixels;
+	}
+}
+
+/// Presets from the config file, in the order their hotkeys go. Those not scaling by a positive
+/// factor are left out.
+pub fn load_presets() -> Vec<ExportPreset> {
+	let file: PresetsFile = config::read_toml(PRESETS_FILE).unwrap_or_default();
+	return file.presets.into_iter().filter(|preset| {
+		if preset.scale <= 0. {
+			log::warn!("Ignoring export preset {:?} scaled by {}", preset.name, preset.scale);
+		}
+		preset.scale > 0.
+	}).collect();
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+	if c <= 0.04045 {
+		return c / 12.92;
+	}
+	return ((c + 0.055) / 1.055).powf(2.4);
+}
+
+impl Pixels {
+	pub fn new(size: Size) -> Self {
+		Self {
+			size,
+			data: vec![0; (size.w * size.h * 4) as usize],
+		}
+	}
+
+	pub fn from_image(img: image::RgbaImage) -> Self {
+		Self {
+			size: Size { w: img.width(), h: img.height() },
+			data: img.into_raw(),
+		}
+	}
+
+	pub fn premultiply(&mut self) {
+		for px in self.data.chunks_exact_mut(4) {
+			let a = px[3] as u32;
+			for c in &mut px[0..3] {
+				*c = ((*c as u32 * a + 127) / 255) as u8;
+			}
+		}
+	}
+
+	/// Canvas textures hold premultiplied colors, image files straight ones.
+	pub fn unpremultiply(&mut self) {
+		for px in self.data.chunks_exact_mut(4) {
+			let a = px[3] as u32;
+			if a != 0 && a != 255 {
+				for c in &mut px[0..3] {
+					*c = ((*c as u32 * 255 + a / 2) / a).min(255) as u8;
+				}
+			}
+		}
+	}
+
+	/// Copies `pixels` over these with their top left at `pos`, they have to fit.
+	pub fn paste(&mut self, pos: Point, pixels: &Pixels) {
+		let row = (pixels.size.w * 4) as usize;
+		for y in 0..pixels.size.h {
+			let src = (y * pixels.size.w * 4) as usize;
+			let dst = (((pos.y as u32 + y) * self.size.w + pos.x as u32) * 4) as usize;
+			self.data[dst..dst + row].copy_from_slice(&pixels.data[src..src + row]);
+		}
+	}
+
+	/// Scaled to `size`, averaging the pixels each one covers when shrinking.
+	pub fn resized(&self, size: Size) -> Pixels {
+		let mut resized = Pixels::new(size);
+		let span = |i: u32, from: u32, to: u32| {
+			let start = (i as u64 * from as u64 / to as u64) as u32;
+			let end = ((i as u64 + 1) * from as u64 / to as u64) as u32;
+			start..end.max(start + 1)
+		};
+		for y in 0..size.h {
+			for x in 0..size.w {
+				// Weighted by alpha, so transparent pixels don't darken the edges
+				let mut sum = [0u64; 4];
+				let mut count = 0;
+				for sy in span(y, self.size.h, size.h) {
+					for sx in span(x, self.size.w, size.w) {
+						let i = ((sy * self.size.w + sx) * 4) as usize;
+						let a = self.data[i + 3] as u64;
+						for (total, c) in sum.iter_mut().zip(&self.data[i..i + 3]) {
+							*total += *c as u64 * a;
+						}
+						sum[3] += a;
+						count += 1;
+					}
+				}
+				let i = ((y * size.w + x) * 4) as usize;
+				for (c, total) in resized.data[i..i + 3].iter_mut().zip(sum) {
+					*c = (total + sum[3] / 2).checked_div(sum[3]).unwrap_or(0) as u8;
+				}
+				resized.data[i + 3] = ((sum[3] + count / 2) / count) as u8;
+			}
+		}
+		return resized;
+	}
+
+	/// Blends every pixel over an opaque `color`.
+	pub fn flatten(&mut self, color: [u8; 3]) {
+		for px in self.data.chunks_exact_mut(4) {
+			let a = px[3] as u32;
+			for c in 0..3 {
+				px[c] = ((px[c] as u32 * a + color[c] as u32 * (255 - a) + 127) / 255) as u8;
+			}
+			px[3] = 255;
+		}
+	}
+
+	pub fn to_image(&self) -> image::RgbaImage {
+		image::RgbaImage::from_raw(self.size.w, self.size.h, self.data.clone()).expect("Pixel data doesn't match its size")
+	}
+
+	pub fn encode_png(&self) -> Result<Vec<u8>, ExportError> {
+		use image::ImageEncoder;
+		let mut bytes = Vec::new();
+		image::codecs::png::PngEncoder::new(&mut bytes).write_image(&self.data, self.size.w, self.size.h, image::ColorType::Rgba8)?;
+		return Ok(bytes);
+	}
+
+	pub fn decode_png(bytes: &[u8]) -> Result<Self, ExportError> {
+		let img = image::load_from_memory_with_format(bytes, image::ImageFormat::Png)?;
+		return Ok(Self::from_image(img.into_rgba8()));
+	}
+
+	pub fn save_png(&self, path: &Path) -> Result<(), ExportError> {
+		image::save_buffer_with_format(path, &self.data, self.size.w, self.size.h, image::ColorType::Rgba8, image::ImageFormat::Png)?;
+		return Ok(());
+	}
+
+	pub fn save(&self, path: &Path, format: ExportFormat) -> Result<(), ExportError> {
+		match format {
+			ExportFormat::Png => return self.save_png(path),
+			ExportFormat::Jpeg => {
+				let mut flat = self.clone();
+				flat.flatten([255, 255, 255]);
+				let rgb: Vec<u8> = flat.data.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect();
+				let file = std::io::BufWriter::new(std::fs::File::create(path).map_err(image::ImageError::IoError)?);
+				image::codecs::jpeg::JpegEncoder::new_with_quality(file, JPEG_QUALITY).encode(&rgb, self.size.w, self.size.h, image::ColorType::Rgb8)?;
+				return Ok(());
+			}
+		}
+	}
+
+	/// Decodes a PNG or JPEG file, whichever its contents are.
+	pub fn load_image(path: &Path) -> Result<Self, ExportError> {
+		return Ok(Self::from_image(image::open(path)?.into_rgba8()));
+	}
+
+	/// Reads `path` back and checks it matches these pixels byte by byte.
+	pub fn verify_png(&self, path: &Path) -> Result<(), ExportError> {
+		let read = Self::load_image(path)?;
+		if read.size != self.size {
+			return Err(ExportError::SizeMismatch { expected: self.size, found: read.size });
+		}
+
+		match self.data.iter().zip(read.data.iter()).position(|(a, b)| a != b) {
+			None => Ok(()),
+			Some(i) => Err(ExportError::Mismatch(i)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn temp_png(name: &str) -> std::path::PathBuf {
+		std::env::temp_dir().join(format!("pntr-{}-{name}.png", std::process::id()))
+	}
+
+	fn pattern(size: Size, f: impl Fn(u32, u32) -> [u8; 4]) -> Pixels {
+		let mut pixels = Pixels::new(size);
+		for y in 0..size.h {
+			for x in 0..size.w {
+				let i = ((y * size.w + x) * 4) as usize;
+				pixels.data[i..i + 4].copy_from_slice(&f(x, y));
+			}
+		}
+		return pixels;
+	}
+
+	fn round_trip(name: &str, pixels: &Pixels) {
+		let path = temp_png(name);
+		pixels.save_png(&path).unwrap();
+		let result = pixels.verify_png(&path);
+		std::fs::remove_file(&path).unwrap();
+		result.unwrap();
+	}
+
+	#[test]
+	fn gradient_round_trips() {
+		let size = Size { w: 256, h: 4 };
+		round_trip("gradient", &pattern(size, |x, y| [x as u8, 255 - x as u8, (x * y) as u8, 255]));
+	}
+
+	#[test]
+	fn checkerboard_with_alpha_round_trips() {
+		let size = Size { w: 33, h: 17 };
+		round_trip("checker", &pattern(size, |x, y| {
+			if (x + y) % 2 == 0 { [255, 255, 255, 255] } else { [0, 0, 0, (x * 7) as u8] }
+		}));
+	}
+
+	#[test]
+	fn srgb_to_linear_matches_reference_values() {
+		for (srgb, linear) in [(0., 0.), (0.04045, 0.0031308), (0.5, 0.21404), (1., 1.)] {
+			assert!((srgb_to_linear(srgb) - linear).abs() < 1e-5, "{srgb} gave {}", srgb_to_linear(srgb));
+		}
+	}
+
+	#[test]
+	fn srgb_to_linear_keeps_8_bit_values_apart() {
+		let linear: Vec<f32> = (0..=255u8).map(|v| srgb_to_linear(v as f32 / 255.)).collect();
+		assert!(linear.windows(2).all(|w| w[0] < w[1]));
+	}
+
+	#[test]
+	fn unpremultiply_restores_straight_colors() {
+		let mut pixels = Pixels { size: Size { w: 3, h: 1 }, data: vec![128, 64, 0, 128, 9, 9, 9, 0, 30, 20, 10, 255] };
+		pixels.unpremultiply();
+		assert_eq!(pixels.data, vec![255, 128, 0, 128, 9, 9, 9, 0, 30, 20, 10, 255]);
+	}
+
+	#[test]
+	fn modified_file_fails_verification() {
+		let size = Size { w: 8, h: 8 };
+		let pixels = pattern(size, |x, _| [x as u8, 0, 0, 255]);
+		let path = temp_png("mismatch");
+		pixels.save_png(&path).unwrap();
+
+		let mut other = pixels.clone();
+		other.data[5] = 42;
+		let result = other.verify_png(&path);
+		std::fs::remove_file(&path).unwrap();
+		assert!(matches!(result, Err(ExportError::Mismatch(5))));
+	}
+
+	#[test]
+	fn resized_file_fails_verification() {
+		let pixels = pattern(Size { w: 8, h: 8 }, |x, _| [x as u8, 0, 0, 255]);
+		let path = temp_png("resized");
+		pixels.save_png(&path).unwrap();
+
+		let wider = pattern(Size { w: 9, h: 8 }, |x, _| [x as u8, 0, 0, 255]);
+		let result = wider.verify_png(&path);
+		std::fs::remove_file(&path).unwrap();
+		assert!(matches!(result, Err(ExportError::SizeMismatch { expected: Size { w: 9, h: 8 }, found: Size { w: 8, h: 8 } })));
+	}
+
+	#[test]
+	fn jpeg_is_flattened_over_white() {
+		let path = temp_png("flattened").with_extension("jpg");
+		let pixels = pattern(Size { w: 16, h: 16 }, |x, _| if x < 8 { [0, 0, 0, 0] } else { [200, 40, 40, 255] });
+		pixels.save(&path, ExportFormat::from_path(&path)).unwrap();
+		let read = Pixels::load_image(&path);
+		std::fs::remove_file(&path).unwrap();
+		let read = read.unwrap();
+		let near = |i: usize, c: [u8; 4]| read.data[i..i + 4].iter().zip(c).all(|(a, b)| a.abs_diff(b) <= 8);
+		assert!(near(0, [255, 255, 255, 255]));
+		assert!(near(15 * 4, [200, 40, 40, 255]));
+	}
+
+	#[test]
+	fn export_paths_are_unique() {
+		let folder = std::env::temp_dir();
+		let first = export_path(&folder, "png");
+		std::fs::write(&first, b"").unwrap();
+		let second = export_path(&folder, "png");
+		let third = export_path(&folder, "png");
+		std::fs::remove_file(&first).unwrap();
+		assert_ne!(first, second);
+		assert_ne!(second, third);
+	}
+
+	#[test]
+	fn paste_copies_rows_into_place() {
+		let mut pixels = Pixels::new(Size { w: 3, h: 3 });
+		let patch = Pixels { size: Size { w: 2, h: 2 }, data: (1..=16).collect() };
+		pixels.paste(Point { x: 1, y: 1 }, &patch);
+		assert_eq!(pixels.data, vec![
+			0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+			0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8,
+			0, 0, 0, 0, 9, 10, 11, 12, 13, 14, 15, 16,
+		]);
+	}
+
+	#[test]
+	fn resize_repeats_and_averages_pixels() {
+		let pixels = pattern(Size { w: 2, h: 1 }, |x, _| [x as u8 * 200, 0, 0, 255]);
+		let doubled = pixels.resized(Size { w: 4, h: 2 });
+		assert_eq!(&doubled.data[4..12], &[0, 0, 0, 255, 200, 0, 0, 255]);
+		let halved = pixels.resized(Size { w: 1, h: 1 });
+		assert_eq!(halved.data, vec![100, 0, 0, 255]);
+	}
+
+	#[test]
+	fn presets_file_reads_tables() {
+		let file: PresetsFile = toml::from_str(r##"
+			[[preset]]
+			name = "Web"
+			format = "png"
+			scale = 0.5
+			background = [255, 128, 0]
+			folder = "exports"
+
+			[[preset]]
+			name = "Print"
+			format = "jpg"
+			scale = 2.0
+			folder = "."
+		"##).unwrap();
+		assert_eq!(file.presets.len(), 2);
+		assert_eq!(file.presets[0].background, Some([255, 128, 0]));
+		assert_eq!(file.presets[0].scale, 0.5);
+		assert_eq!(file.presets[0].folder, PathBuf::from("exports"));
+		assert_eq!(file.presets[1].format, ExportFormat::Jpeg);
+		assert_eq!(file.presets[1].background, None);
+		assert!(toml::from_str::<PresetsFile>("[[preset]]\nname = \"Web\"\nformat = \"gif\"\nscale = 1.0\nfolder = \".\"").is_err());
+	}
+}
@@ -0,0 +1,163 @@
+use crate::components::Size;
+use crate::export::Pixels;
+
+pub const GLYPH_W: u32 = 5;
+pub const GLYPH_H: u32 = 7;
+/// Space between glyphs and between lines, in font pixels
+const GAP: u32 = 1;
+
+/// 5x7 bitmap glyphs for ' ' to '_', one byte per row with the leftmost pixel in bit 4.
+/// Lowercase letters are drawn as uppercase, anything else as '?'.
+const GLYPHS: [[u8; 7]; 64] = [
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+	[0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // !
+	[0x0a, 0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00], // "
+	[0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a], // #
+	[0x04, 0x0f, 0x14, 0x0e, 0x05, 0x1e, 0x04], // $
+	[0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // %
+	[0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d], // &
+	[0x04, 0x04, 0x04, 0x00, 0x00, 0x00, 0x00], // '
+	[0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // (
+	[0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // )
+	[0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00], // *
+	[0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00], // +
+	[0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08], // ,
+	[0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00], // -
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c], // .
+	[0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // /
+	[0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e], // 0
+	[0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e], // 1
+	[0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f], // 2
+	[0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e], // 3
+	[0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02], // 4
+	[0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e], // 5
+	[0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e], // 6
+	[0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+	[0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e], // 8
+	[0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c], // 9
+	[0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00], // :
+	[0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x04, 0x08], // ;
+	[0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // <
+	[0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00], // =
+	[0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // >
+	[0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // ?
+	[0x0e, 0x11, 0x01, 0x0d, 0x15, 0x15, 0x0e], // @
+	[0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // A
+	[0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e], // B
+	[0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e], // C
+	[0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c], // D
+	[0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f], // E
+	[0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10], // F
+	[0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f], // G
+	[0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11], // H
+	[0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e], // I
+	[0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c], // J
+	[0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+	[0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f], // L
+	[0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+	[0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+	[0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // O
+	[0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10], // P
+	[0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d], // Q
+	[0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11], // R
+	[0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e], // S
+	[0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+	[0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e], // U
+	[0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04], // V
+	[0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a], // W
+	[0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11], // X
+	[0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04], // Y
+	[0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f], // Z
+	[0x0e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0e], // [
+	[0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // \
+	[0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0e], // ]
+	[0x04, 0x0a, 0x11, 0x00, 0x00, 0x00, 0x00], // ^
+	[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f], // _
+];
+
+fn glyph(c: char) -> &'static [u8; 7] {
+	let c = c.to_ascii_uppercase();
+	match c {
+		' '..='_' => &GLYPHS[c as usize - ' ' as usize],
+		_ => &GLYPHS['?' as usize - ' ' as usize],
+	}
+}
+
+/// Whether the font pixel at column `x`, row `y` of the glyph of `c` is drawn.
+pub fn glyph_pixel(c: char, x: u32, y: u32) -> bool {
+	if x >= GLYPH_W || y >= GLYPH_H {
+		return false;
+	}
+	return glyph(c)[y as usize] & (1 << (GLYPH_W - 1 - x)) != 0;
+}
+
+/// Pixels from one glyph to the next at `scale`.
+pub const fn advance(scale: u32) -> u32 {
+	(GLYPH_W + GAP) * scale
+}
+
+/// Size of `text` drawn at `scale`, lines split on '\n'.
+pub fn text_size(text: &str, scale: u32) -> Size {
+	let columns = text.lines().map(|l| l.chars().count() as u32).max().unwrap_or(0);
+	let lines = text.lines().count() as u32;
+	Size {
+		w: columns * (GLYPH_W + GAP) * scale,
+		h: lines * (GLYPH_H + GAP) * scale,
+	}
+}
+
+/// Draws `text` with its top left corner at `x`, `y`, clipped to the pixels.
+pub fn draw_text(pixels: &mut Pixels, x: u32, y: u32, text: &str, color: [u8; 4], scale: u32) {
+	for (line, text) in text.lines().enumerate() {
+		let top = y + line as u32 * (GLYPH_H + GAP) * scale;
+		for (column, c) in text.chars().enumerate() {
+			let left = x + column as u32 * (GLYPH_W + GAP) * scale;
+			for (row, bits) in glyph(c).iter().enumerate() {
+				for bit in 0..GLYPH_W {
+					if bits & (1 << (GLYPH_W - 1 - bit)) == 0 {
+						continue;
+					}
+					fill(pixels, left + bit * scale, top + row as u32 * scale, scale, scale, color);
+				}
+			}
+		}
+	}
+}
+
+/// Fills a rectangle, clipped to the pixels.
+pub fn fill(pixels: &mut Pixels, x: u32, y: u32, w: u32, h: u32, color: [u8; 4]) {
+	for py in y..(y + h).min(pixels.size.h) {
+		for px in x..(x + w).min(pixels.size.w) {
+			let i = ((py * pixels.size.w + px) * 4) as usize;
+			pixels.data[i..i + 4].copy_from_slice(&color);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn size_counts_the_longest_line() {
+		assert_eq!(text_size("ab\nabcd", 2), Size { w: 4 * advance(2), h: 2 * (GLYPH_H + GAP) * 2 });
+		assert_eq!(text_size("", 1), Size { w: 0, h: 0 });
+	}
+
+	#[test]
+	fn unknown_characters_draw_as_question_marks() {
+		for (x, y) in (0..GLYPH_W).flat_map(|x| (0..GLYPH_H).map(move |y| (x, y))) {
+			assert_eq!(glyph_pixel('é', x, y), glyph_pixel('?', x, y));
+		}
+		assert!(!glyph_pixel('A', GLYPH_W, 0));
+	}
+
+	#[test]
+	fn drawing_is_clipped() {
+		let mut pixels = Pixels::new(Size { w: 4, h: 4 });
+		draw_text(&mut pixels, 2, 2, "W", [255; 4], 2);
+		fill(&mut pixels, 3, 3, 10, 10, [9; 4]);
+		assert_eq!(pixels.data.len(), 4 * 4 * 4);
+		assert_eq!(pixels.data[(3 * 4 + 3) * 4..], [9; 4]);
+	}
+}
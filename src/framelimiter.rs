@@ -4,19 +4,38 @@ use winit::{
 };
 use core::cmp::Reverse;
 use std::{
-	time::{SystemTime, Duration}, collections::{BinaryHeap, HashMap}, sync::mpsc, thread,
+	time::{SystemTime, Duration}, collections::{BinaryHeap, HashMap, HashSet}, sync::mpsc, thread,
 };
 
 use crate::CustomEvents;
-use crate::FRAMETIME;
+use crate::settings::preferences;
+
+/// Time without input after which a window is idle
+const IDLE_AFTER: Duration = Duration::from_millis(750);
 
 pub struct FrameLimiter {
-	sender: mpsc::Sender<WindowId>,
+	sender: mpsc::Sender<Message>,
+}
+
+enum Message {
+	Redraw(WindowId),
+	/// Redraw once the time comes, for animations stepping slower than the frame rate
+	RedrawAt(WindowId, SystemTime),
+	/// The window got input
+	Active(WindowId),
+	/// The window has work for when it is idle
+	WantsIdle(WindowId),
+	/// Least time between two frames of a window
+	Frametime(Duration),
 }
 
 struct FrameSchedule {
 	last_scheduled_frametime: HashMap<WindowId, SystemTime>,
 	schedule_queue: BinaryHeap<Reverse<(SystemTime, WindowId)>>,
+	/// Windows to tell once they are idle
+	idle_waiting: HashSet<WindowId>,
+	last_input: HashMap<WindowId, SystemTime>,
+	frametime: Duration,
 	event_proxy: EventLoopProxy<CustomEvents>,
 }
 
@@ -26,21 +45,45 @@ fn now() -> SystemTime {
 }
 
 impl FrameSchedule {
-	pub fn new(event_proxy: EventLoopProxy<CustomEvents>) -> FrameSchedule {
+	pub fn new(event_proxy: EventLoopProxy<CustomEvents>, frametime: Duration) -> FrameSchedule {
 		FrameSchedule {
 			last_scheduled_frametime: HashMap::<WindowId, SystemTime>::new(),
 			schedule_queue: BinaryHeap::<Reverse::<(SystemTime, WindowId)>>::new(),
+			idle_waiting: HashSet::new(),
+			last_input: HashMap::new(),
+			frametime,
 			event_proxy
 		}
 
 	}
 
+	/// Until the next frame is due or a window waiting for it becomes idle.
 	pub fn time_to_next_frame(&self) -> Option<Duration> {
-		match	self.schedule_queue.peek() {
-			None => None,
-			Some(Reverse((time_of_next_frame, _))) => {
-				Some(time_of_next_frame.duration_since(now()).unwrap_or(Duration::ZERO))
+		let frame = self.schedule_queue.peek().map(|Reverse((time, _))| *time);
+		let idle = self.idle_waiting.iter().map(|wid| self.idle_time(wid)).min();
+		let next = match (frame, idle) {
+			(Some(frame), Some(idle)) => Some(frame.min(idle)),
+			(frame, idle) => frame.or(idle),
+		};
+		next.map(|time| time.duration_since(now()).unwrap_or(Duration::ZERO))
+	}
+
+	/// When `wid` is idle, unless it gets input before.
+	fn idle_time(&self, wid: &WindowId) -> SystemTime {
+		self.last_input.get(wid).map_or(SystemTime::UNIX_EPOCH, |time| *time + IDLE_AFTER)
+	}
+
+	fn receive(&mut self, message: Message) {
+		match message {
+			Message::Redraw(wid) => self.insert(wid),
+			Message::RedrawAt(wid, time) => self.schedule_queue.push(Reverse((time, wid))),
+			Message::Active(wid) => {
+				self.last_input.insert(wid, now());
+			}
+			Message::WantsIdle(wid) => {
+				self.idle_waiting.insert(wid);
 			}
+			Message::Frametime(frametime) => self.frametime = frametime,
 		}
 	}
 
@@ -50,10 +93,10 @@ impl FrameSchedule {
 				// next frame on this window is already scheduled, do nothing
 			}
 
-			Some(time) if *time + FRAMETIME > now()  => {
+			Some(time) if *time + self.frametime > now()  => {
 				// next frame on this window should be scheduled
 
-				let next_frame_time = *time + FRAMETIME;
+				let next_frame_time = *time + self.frametime;
 				self.last_scheduled_frametime.insert(wid, next_frame_time);
 				self.schedule_queue.push(Reverse((next_frame_time, wid)));
 			}
@@ -76,6 +119,12 @@ impl FrameSchedule {
 			self.send_redraw(wid);
 			self.schedule_queue.pop();
 		}
+
+		let idle: Vec<WindowId> = self.idle_waiting.iter().filter(|wid| self.idle_time(wid) <= now()).copied().collect();
+		for wid in idle {
+			self.idle_waiting.remove(&wid);
+			self.event_proxy.send_event(CustomEvents::Idle(wid)).unwrap();
+		}
 	}
 
 	fn send_redraw(&self, wid: &WindowId) {
@@ -86,24 +135,25 @@ impl FrameSchedule {
 impl FrameLimiter {
 	pub fn new(event_loop: &EventLoop<CustomEvents>) -> Self {
 
-		let (sender, receiver) = mpsc::channel::<WindowId>();
+		let (sender, receiver) = mpsc::channel::<Message>();
 		let event_proxy = event_loop.create_proxy();
+		let frametime = preferences::frametime(preferences::load().fps);
 
 		thread::spawn(move || {
 
-			let mut schedule = FrameSchedule::new(event_proxy);
+			let mut schedule = FrameSchedule::new(event_proxy, frametime);
 
 			loop {
 				match schedule.time_to_next_frame() {
 					None => {
-						let wid = receiver.recv().unwrap();
-						schedule.insert(wid);
+						let message = receiver.recv().unwrap();
+						schedule.receive(message);
 					}
 
 					Some(dur) => {
 						match receiver.recv_timeout(dur) {
-							Ok(wid) => {
-								schedule.insert(wid);
+							Ok(message) => {
+								schedule.receive(message);
 							}
 							Err(mpsc::RecvTimeoutError::Timeout) => {
 								schedule.process_due_frames();
@@ -123,6 +173,56 @@ impl FrameLimiter {
 	}
 
 	pub fn schedule_redraw(&self, wid: WindowId) {
-		self.sender.send(wid).unwrap();
+		self.sender.send(Message::Redraw(wid)).unwrap();
+	}
+
+	/// Draws windows at `fps` frames per second at most.
+	pub fn set_fps(&self, fps: u32) {
+		self.sender.send(Message::Frametime(preferences::frametime(fps))).unwrap();
+	}
+
+	/// Tells that `wid` got input, so it isn't idle.
+	pub fn mark_active(&self, wid: WindowId) {
+		self.sender.send(Message::Active(wid)).unwrap();
+	}
+
+	/// Sends `CustomEvents::Idle` once `wid` has gone `IDLE_AFTER` without input, right away when it
+	/// already has.
+	pub fn request_idle(&self, wid: WindowId) {
+		self.sender.send(Message::WantsIdle(wid)).unwrap();
+	}
+
+	/// Redraws `wid` after some time, from where there is no `FrameLimiter` at hand.
+	pub fn timer(&self, wid: WindowId) -> RedrawTimer {
+		return RedrawTimer { sender: self.sender.clone(), wid };
+	}
+
+	/// Schedules a redraw of `wid` when woken, for futures polled by the window.
+	pub fn waker(&self, wid: WindowId) -> std::task::Waker {
+		return std::sync::Arc::new(RedrawWaker { sender: std::sync::Mutex::new(self.sender.clone()), wid }).into();
+	}
+}
+
+struct RedrawWaker {
+	sender: std::sync::Mutex<mpsc::Sender<Message>>,
+	wid: WindowId,
+}
+
+pub struct RedrawTimer {
+	sender: mpsc::Sender<Message>,
+	wid: WindowId,
+}
+
+impl RedrawTimer {
+	pub fn redraw_in(&self, delay: Duration) {
+		// The event loop is gone, nothing to draw
+		let _ = self.sender.send(Message::RedrawAt(self.wid, now() + delay));
+	}
+}
+
+impl std::task::Wake for RedrawWaker {
+	fn wake(self: std::sync::Arc<Self>) {
+		// The event loop is gone, nothing to draw
+		let _ = self.sender.lock().unwrap().send(Message::Redraw(self.wid));
 	}
 }
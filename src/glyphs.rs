@@ -0,0 +1,103 @@
+use wgpu_glyph::{ab_glyph::FontArc, GlyphBrush, GlyphBrushBuilder, GlyphCruncher, Section, Text};
+
+use crate::components::{Context, Size};
+use crate::export::Pixels;
+
+/// Font typed text is drawn in, bundled so it looks the same everywhere. License in assets/DejaVuSans-LICENSE.txt
+const FONT: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Lays out text and draws its glyphs on the GPU with wgpu_glyph, into pixels the text tool
+/// previews and pastes.
+pub struct GlyphRenderer {
+	brush: GlyphBrush<()>,
+	staging: wgpu::util::StagingBelt,
+}
+
+impl GlyphRenderer {
+	pub fn new(ctx: &Context) -> Self {
+		let font = FontArc::try_from_slice(FONT).expect("The bundled font is valid");
+		return Self {
+			brush: GlyphBrushBuilder::using_font(font).build(&ctx.device, FORMAT),
+			staging: wgpu::util::StagingBelt::new(1024),
+		};
+	}
+
+	fn section(text: &str, size: f32, color: [f32; 4]) -> Section<'_> {
+		return Section::default().add_text(Text::new(text).with_scale(size).with_color(color));
+	}
+
+	/// Pixels `text` covers laid out `size` pixels tall, a line high even without glyphs.
+	pub fn measure(&mut self, ctx: &Context, text: &str, size: f32) -> Size {
+		let max = ctx.device.limits().max_texture_dimension_2d;
+		let (w, h) = self.brush.glyph_bounds(Self::section(text, size, [0.; 4])).map_or((1., size), |b| (b.max.x, b.max.y.max(size)));
+		return Size { w: (w.ceil() as u32).clamp(1, max), h: (h.ceil() as u32).clamp(1, max) };
+	}
+
+	/// `text` in straight alpha `color` over transparent pixels of `extent`, blocking until the GPU
+	/// drew it.
+	pub fn render(&mut self, ctx: &mut Context, text: &str, size: f32, color: [f32; 4], extent: Size) -> Pixels {
+		let texture = ctx.device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("Glyphs(Target)"),
+			size: wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: FORMAT,
+			usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+		});
+		let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+		let mut encoder = ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Glyphs(Encoder)"),
+		});
+		encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("Glyphs(Clear Pass)"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: &view,
+				resolve_target: None,
+				ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), store: true },
+			})],
+			depth_stencil_attachment: None,
+		});
+
+		// Blended over transparent pixels the glyphs come out premultiplied
+		self.brush.queue(Self::section(text, size, color));
+		self.brush.draw_queued(&ctx.device, &mut self.staging, &mut encoder, &view, extent.w, extent.h).expect("Could not draw the glyphs");
+		self.staging.finish();
+
+		let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+		let padded_row = (extent.w * 4).div_ceil(align) * align;
+		let readback = ctx.device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Glyphs(Readback)"),
+			size: (padded_row * extent.h) as u64,
+			usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+		encoder.copy_texture_to_buffer(
+			texture.as_image_copy(),
+			wgpu::ImageCopyBuffer {
+				buffer: &readback,
+				layout: wgpu::ImageDataLayout {
+					offset: 0,
+					bytes_per_row: std::num::NonZeroU32::new(padded_row),
+					rows_per_image: None,
+				},
+			},
+			wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+		);
+		ctx.queue.submit(std::iter::once(encoder.finish()));
+		self.staging.recall();
+
+		let slice = readback.slice(..);
+		slice.map_async(wgpu::MapMode::Read, |r| r.expect("Could not read back the glyphs"));
+		ctx.device.poll(wgpu::Maintain::Wait);
+		let mut pixels = Pixels::new(extent);
+		let data = slice.get_mapped_range();
+		let row = (extent.w * 4) as usize;
+		for y in 0..extent.h as usize {
+			pixels.data[y * row..(y + 1) * row].copy_from_slice(&data[y * padded_row as usize..y * padded_row as usize + row]);
+		}
+		pixels.unpremultiply();
+		return pixels;
+	}
+}
@@ -0,0 +1,79 @@
+use crate::components::{Rect, Size};
+use crate::config;
+
+pub const GUIDES_FILE: &str = "guides";
+
+/// Crop outlined over the document, helping fit the drawing to where it ends up. Never exported.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Guide {
+	/// Biggest centered rect of this width to height ratio
+	Aspect(u32, u32),
+	/// Centered rect this fraction of the document, like the title-safe area of video
+	Safe(f32),
+}
+
+impl Guide {
+	/// `aspect W:H` or `safe PERCENT`.
+	fn parse(line: &str) -> Option<Self> {
+		let (kind, value) = line.split_once(' ')?;
+		return match kind {
+			"aspect" => {
+				let (w, h) = value.split_once(':')?;
+				let (w, h) = (w.parse().ok()?, h.parse().ok()?);
+				(w > 0 && h > 0).then_some(Guide::Aspect(w, h))
+			}
+			"safe" => value.parse::<f32>().ok().filter(|p| *p > 0. && *p <= 100.).map(|p| Guide::Safe(p / 100.)),
+			_ => None,
+		};
+	}
+
+	pub fn rect(&self, document: Size) -> Rect {
+		let size = match *self {
+			Guide::Aspect(w, h) => {
+				let (dw, dh) = (document.w as u64, document.h as u64);
+				if dw * h as u64 > dh * w as u64 {
+					Size { w: (dh * w as u64 / h as u64) as u32, h: document.h }
+				} else {
+					Size { w: document.w, h: (dw * h as u64 / w as u64) as u32 }
+				}
+			}
+			Guide::Safe(fraction) => Size {
+				w: (document.w as f32 * fraction).round() as u32,
+				h: (document.h as f32 * fraction).round() as u32,
+			},
+		};
+		return Rect::new(((document.w - size.w) / 2) as i32, ((document.h - size.h) / 2) as i32, size.w, size.h);
+	}
+}
+
+/// Guides from the config file, one per line, or common video and social media crops without one.
+pub fn load_guides() -> Vec<Guide> {
+	let guides = config::read_lines(GUIDES_FILE, "guide", |line| Guide::parse(line.trim()));
+	return guides.unwrap_or_else(|| vec![Guide::Aspect(16, 9), Guide::Aspect(1, 1), Guide::Aspect(4, 5), Guide::Safe(0.9)]);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_aspect_and_safe_lines() {
+		assert_eq!(Guide::parse("aspect 16:9"), Some(Guide::Aspect(16, 9)));
+		assert_eq!(Guide::parse("safe 90"), Some(Guide::Safe(0.9)));
+	}
+
+	#[test]
+	fn rejects_invalid_lines() {
+		for line in ["aspect 16", "aspect 0:9", "aspect a:b", "safe 0", "safe 120", "crop 1:1", "aspect"] {
+			assert_eq!(Guide::parse(line), None, "{line}");
+		}
+	}
+
+	#[test]
+	fn aspect_rect_is_centered_and_fits() {
+		let document = Size { w: 200, h: 100 };
+		assert_eq!(Guide::Aspect(1, 1).rect(document), Rect::new(50, 0, 100, 100));
+		assert_eq!(Guide::Aspect(4, 1).rect(document), Rect::new(0, 25, 200, 50));
+		assert_eq!(Guide::Safe(0.5).rect(document), Rect::new(50, 25, 100, 50));
+	}
+}
@@ -0,0 +1,243 @@
+use std::collections::VecDeque;
+
+use crate::components::{self, Canvas, Context, DocumentTiles, Rect, RemovedLayer, SavedBlock, Size};
+use crate::layers::{Layer, LayerStack};
+
+/// Edits are dropped oldest first past this many, unless the preferences say otherwise
+pub const HISTORY_DEPTH: usize = 100;
+/// GPU memory the saved blocks may take, oldest edits are dropped past it, unless the preferences
+/// say otherwise
+pub const HISTORY_BUDGET: u64 = 512 << 20;
+/// Latest edits whose saved blocks stay on the GPU, older ones are packed while idle
+const UNPACKED_EDITS: usize = 8;
+
+/// A reversible change to the document.
+pub trait EditCommand {
+	fn name(&self) -> &'static str;
+	/// Returns the document area that changed, None if nothing did
+	fn undo(&mut self, canvas: &mut Canvas, layers: &mut LayerStack, ctx: &mut Context) -> Option<Rect>;
+	fn redo(&mut self, canvas: &mut Canvas, layers: &mut LayerStack, ctx: &mut Context) -> Option<Rect>;
+	/// GPU memory held, counted against the history budget
+	fn bytes(&self) -> u64;
+	/// Moves what it holds off the GPU, returns whether anything was
+	fn pack(&mut self, _: &Context) -> bool {
+		false
+	}
+}
+
+/// Pixels changed by strokes, clears and fills, as blocks from before and after the edit.
+pub struct BlockEdit {
+	pub name: &'static str,
+	pub before: Vec<SavedBlock>,
+	pub after: Vec<SavedBlock>,
+	/// Serials of the strokes and clears the canvas logged for it, left out of replays while undone
+	pub logged: Vec<u64>,
+}
+
+impl EditCommand for BlockEdit {
+	fn name(&self) -> &'static str {
+		self.name
+	}
+
+	fn undo(&mut self, canvas: &mut Canvas, _: &mut LayerStack, ctx: &mut Context) -> Option<Rect> {
+		canvas.set_logged_undone(&self.logged, true);
+		canvas.restore_blocks(ctx, &self.before)
+	}
+
+	fn redo(&mut self, canvas: &mut Canvas, _: &mut LayerStack, ctx: &mut Context) -> Option<Rect> {
+		canvas.set_logged_undone(&self.logged, false);
+		canvas.restore_blocks(ctx, &self.after)
+	}
+
+	fn bytes(&self) -> u64 {
+		self.before.iter().chain(self.after.iter()).map(SavedBlock::bytes).sum()
+	}
+
+	fn pack(&mut self, ctx: &Context) -> bool {
+		if self.before.iter().chain(self.after.iter()).all(SavedBlock::is_packed) {
+			return false;
+		}
+		components::pack_blocks(ctx, &mut self.before);
+		components::pack_blocks(ctx, &mut self.after);
+		return true;
+	}
+}
+
+/// A resize or crop, holding the whole document from the other side of it.
+pub struct ResizeEdit {
+	pub name: &'static str,
+	/// None only while it is being swapped
+	pub document: Option<DocumentTiles>,
+}
+
+impl ResizeEdit {
+	fn swap(&mut self, canvas: &mut Canvas, ctx: &mut Context) -> Option<Rect> {
+		let document = canvas.swap_document(ctx, self.document.take()?);
+		self.document = Some(document);
+		let size = canvas.size();
+		return Some(Rect::new(0, 0, size.w, size.h));
+	}
+}
+
+impl EditCommand for ResizeEdit {
+	fn name(&self) -> &'static str {
+		self.name
+	}
+
+	fn undo(&mut self, canvas: &mut Canvas, _: &mut LayerStack, ctx: &mut Context) -> Option<Rect> {
+		self.swap(canvas, ctx)
+	}
+
+	fn redo(&mut self, canvas: &mut Canvas, _: &mut LayerStack, ctx: &mut Context) -> Option<Rect> {
+		self.swap(canvas, ctx)
+	}
+
+	fn bytes(&self) -> u64 {
+		self.document.as_ref().map_or(0, DocumentTiles::bytes)
+	}
+}
+
+/// Layers deleted or merged, holding the ones from the other side of the edit with their pixels.
+pub struct LayersEdit {
+	pub name: &'static str,
+	/// Taken out of the stack, put back by swapping
+	pub held: Vec<(RemovedLayer, Layer)>,
+	/// Ids of the layers in the stack that swapping takes out
+	pub shown: Vec<u32>,
+}
+
+impl LayersEdit {
+	/// Takes the shown layers out first, so the held ones go back at the indices they had.
+	fn swap(&mut self, canvas: &mut Canvas, layers: &mut LayerStack) -> Option<Rect> {
+		let mut indices: Vec<usize> = self.shown.iter().filter_map(|id| canvas.layer_index(*id)).collect();
+		indices.sort_unstable();
+		let mut taken: Vec<(RemovedLayer, Layer)> = indices.into_iter().rev().map(|i| (canvas.take_any_layer(i), layers.remove(i))).collect();
+		taken.reverse();
+
+		self.shown = self.held.iter().map(|(removed, _)| removed.id()).collect();
+		for (removed, layer) in std::mem::replace(&mut self.held, taken) {
+			layers.insert(removed.index().min(layers.layers.len()), layer);
+			canvas.restore_layer(removed);
+		}
+		layers.select(canvas.active_layer());
+		let size = canvas.size();
+		return Some(Rect::new(0, 0, size.w, size.h));
+	}
+}
+
+impl EditCommand for LayersEdit {
+	fn name(&self) -> &'static str {
+		self.name
+	}
+
+	fn undo(&mut self, canvas: &mut Canvas, layers: &mut LayerStack, _: &mut Context) -> Option<Rect> {
+		self.swap(canvas, layers)
+	}
+
+	fn redo(&mut self, canvas: &mut Canvas, layers: &mut LayerStack, _: &mut Context) -> Option<Rect> {
+		self.swap(canvas, layers)
+	}
+
+	fn bytes(&self) -> u64 {
+		self.held.iter().map(|(removed, _)| removed.bytes()).sum()
+	}
+}
+
+pub struct History {
+	undo: VecDeque<Box<dyn EditCommand>>,
+	redo: Vec<Box<dyn EditCommand>>,
+	depth: usize,
+	budget: u64,
+	/// Document size the edits were recorded on, they don't apply to other sizes
+	document: Option<Size>,
+}
+
+impl History {
+	pub fn new(depth: usize, budget: u64) -> Self {
+		Self {
+			undo: VecDeque::new(),
+			redo: Vec::new(),
+			depth,
+			budget,
+			document: None,
+		}
+	}
+
+	/// GPU memory of the saved edits, undone or not.
+	pub fn bytes(&self) -> u64 {
+		self.undo.iter().chain(self.redo.iter()).map(|c| c.bytes()).sum()
+	}
+
+	pub fn push(&mut self, command: Box<dyn EditCommand>) {
+		log::debug!("Recorded {} ({} KiB)", command.name(), command.bytes() >> 10);
+		self.redo.clear();
+		self.undo.push_back(command);
+		self.trim();
+	}
+
+	/// Keeps at most `depth` edits, and drops the oldest past `budget` bytes.
+	pub fn set_limits(&mut self, depth: usize, budget: u64) {
+		(self.depth, self.budget) = (depth, budget);
+		self.trim();
+	}
+
+	fn trim(&mut self) {
+		while self.undo.len() > self.depth || (self.undo.len() > 1 && self.bytes() > self.budget) {
+			self.undo.pop_front();
+		}
+	}
+
+	/// Returns the document area that changed.
+	pub fn undo(&mut self, canvas: &mut Canvas, layers: &mut LayerStack, ctx: &mut Context) -> Option<Rect> {
+		let Some(mut command) = self.undo.pop_back() else {
+			log::info!("Nothing to undo");
+			return None;
+		};
+		log::info!("Undo {}", command.name());
+		let changed = command.undo(canvas, layers, ctx);
+		self.document = Some(canvas.size());
+		self.redo.push(command);
+		return changed;
+	}
+
+	/// Returns the document area that changed.
+	pub fn redo(&mut self, canvas: &mut Canvas, layers: &mut LayerStack, ctx: &mut Context) -> Option<Rect> {
+		let Some(mut command) = self.redo.pop() else {
+			log::info!("Nothing to redo");
+			return None;
+		};
+		log::info!("Redo {}", command.name());
+		let changed = command.redo(canvas, layers, ctx);
+		self.document = Some(canvas.size());
+		self.undo.push_back(command);
+		return changed;
+	}
+
+	/// Packs the saved blocks of the oldest edit still on the GPU, past the latest `UNPACKED_EDITS`.
+	/// Returns whether one was.
+	pub fn pack_oldest(&mut self, ctx: &Context) -> bool {
+		let old = self.undo.len().saturating_sub(UNPACKED_EDITS);
+		return self.undo.iter_mut().take(old).any(|command| command.pack(ctx));
+	}
+
+	/// Drops every edit, freeing the GPU memory they hold.
+	pub fn clear(&mut self) {
+		self.undo.clear();
+		self.redo.clear();
+	}
+
+	/// Records a resize or crop to `size`, the edits before it still apply once it's undone.
+	pub fn push_resize(&mut self, command: ResizeEdit, size: Size) {
+		self.push(Box::new(command));
+		self.document = Some(size);
+	}
+
+	/// Resizes and crops that weren't recorded change the tiles the edits refer to, so the history starts over.
+	pub fn check_document(&mut self, size: Size) {
+		if self.document.is_some_and(|s| s != size) && !(self.undo.is_empty() && self.redo.is_empty()) {
+			log::info!("Document resized, undo history cleared");
+			self.clear();
+		}
+		self.document = Some(size);
+	}
+}
@@ -0,0 +1,121 @@
+use winit::event::VirtualKeyCode;
+
+use crate::components::{Point, Size};
+use crate::export::Pixels;
+use crate::font;
+
+const SCALE: u32 = 2;
+const MARGIN: u32 = 12;
+const BACKGROUND: [u8; 4] = [24, 24, 28, 235];
+const TEXT: [u8; 4] = [230, 230, 230, 255];
+const FOCUSED: [u8; 4] = [240, 200, 90, 255];
+
+const FIELDS: [&str; 4] = ["X", "Y", "Scale %", "Angle"];
+const DEFAULTS: [f32; 4] = [0., 0., 100., 0.];
+
+/// Numeric fields of a transform, or of other fields given to `with_fields`, typed in with the
+/// keyboard and applied as they change. Tab goes to the next field, the first character typed in
+/// one replaces its value.
+pub struct TransformHud {
+	fields: &'static [&'static str],
+	/// Offset in pixels, scale in percent and rotation in degrees for a transform, in the order of `fields`
+	values: Vec<f32>,
+	field: usize,
+	/// Text typed in the focused field, None until something is typed in it
+	editing: Option<String>,
+}
+
+impl TransformHud {
+	pub fn new() -> Self {
+		Self::with_fields(&FIELDS, DEFAULTS.to_vec())
+	}
+
+	/// Fields named `fields`, starting at `values`.
+	pub fn with_fields(fields: &'static [&'static str], values: Vec<f32>) -> Self {
+		assert_eq!(fields.len(), values.len());
+		Self { fields, values, field: 0, editing: None }
+	}
+
+	pub fn value(&self, field: usize) -> f32 {
+		self.values[field]
+	}
+
+	/// Returns if the values changed.
+	pub fn input(&mut self, c: char) -> bool {
+		match c {
+			'\t' => {
+				self.field = (self.field + 1) % self.fields.len();
+				self.editing = None;
+				return false;
+			}
+			'\u{8}' => {
+				let text = self.editing.get_or_insert_with(String::new);
+				text.pop();
+			}
+			'0'..='9' | '.' | '-' => self.editing.get_or_insert_with(String::new).push(c),
+			_ => return false,
+		}
+
+		// Partial entries like "-" or "" leave the value as it was
+		let Some(value) = self.editing.as_deref().and_then(|t| t.parse::<f32>().ok()) else {
+			return false;
+		};
+		let changed = value != self.values[self.field];
+		self.values[self.field] = value;
+		return changed;
+	}
+
+	pub fn offset(&self) -> Point {
+		Point { x: self.values[0].round() as i32, y: self.values[1].round() as i32 }
+	}
+
+	/// 1 for the original size
+	pub fn scale(&self) -> f32 {
+		self.values[2] / 100.
+	}
+
+	pub fn angle_radians(&self) -> f32 {
+		self.values[3].to_radians()
+	}
+
+	fn lines(&self) -> Vec<String> {
+		let width = self.fields.iter().map(|f| f.len()).max().unwrap_or(0);
+		return self.fields.iter().enumerate().map(|(i, name)| {
+			let value = match &self.editing {
+				Some(text) if i == self.field => format!("{text}_"),
+				_ => format!("{}", self.values[i]),
+			};
+			format!("{name:width$}  {value}")
+		}).collect();
+	}
+
+	/// Text the HUD shows, redrawn when it changes.
+	pub fn text(&self) -> String {
+		return format!("{}:{}", self.field, self.lines().join("\n"));
+	}
+
+	pub fn render(&self) -> Pixels {
+		let lines = self.lines();
+		let line_h = font::text_size("A", SCALE).h;
+		let width = lines.iter().map(|l| font::text_size(l, SCALE).w).max().unwrap_or(0);
+		let size = Size { w: width + 2 * MARGIN, h: lines.len() as u32 * line_h + 2 * MARGIN };
+
+		let mut pixels = Pixels::new(size);
+		font::fill(&mut pixels, 0, 0, size.w, size.h, BACKGROUND);
+		for (i, line) in lines.iter().enumerate() {
+			let color = if i == self.field { FOCUSED } else { TEXT };
+			font::draw_text(&mut pixels, MARGIN, MARGIN + i as u32 * line_h, line, color, SCALE);
+		}
+		return pixels;
+	}
+}
+
+/// Keys typing into the HUD, their bindings don't run while it's shown.
+pub fn is_input_key(key: VirtualKeyCode) -> bool {
+	use VirtualKeyCode::*;
+	return matches!(key,
+		Key0 | Key1 | Key2 | Key3 | Key4 | Key5 | Key6 | Key7 | Key8 | Key9
+		| Numpad0 | Numpad1 | Numpad2 | Numpad3 | Numpad4 | Numpad5 | Numpad6 | Numpad7 | Numpad8 | Numpad9
+		| Minus | NumpadSubtract | Period | NumpadDecimal | Back | Tab
+	);
+}
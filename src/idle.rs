@@ -0,0 +1,55 @@
+use std::{collections::VecDeque, time::{Duration, Instant}};
+
+/// Time an idle slice may take, input waits for it to end
+pub const SLICE_BUDGET: Duration = Duration::from_millis(8);
+
+/// Work left for when the window is idle, so drawing never waits for it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IdleTask {
+	/// Moves the saved blocks of old edits off the GPU, deflated
+	CompressHistory,
+	/// Downscales the thumbnail cells strokes left dirty
+	RefreshThumbnails,
+	/// Frees the pooled buffers the busiest frame grew them to
+	TrimPools,
+	/// Creates the pipelines of the components shown on demand, before they are asked for
+	WarmPipelines,
+}
+
+/// Runs idle tasks in turns, each step of one as long as the slice has time left.
+pub struct IdleScheduler {
+	pending: VecDeque<IdleTask>,
+}
+
+impl IdleScheduler {
+	pub fn new() -> Self {
+		Self { pending: VecDeque::new() }
+	}
+
+	/// Queues `task` unless it already is.
+	pub fn schedule(&mut self, task: IdleTask) {
+		if !self.pending.contains(&task) {
+			self.pending.push_back(task);
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.pending.is_empty()
+	}
+
+	/// Runs steps of the queued tasks for up to `budget`, `step` returns whether its task has more
+	/// to do. A step started before the budget ran out finishes, so they should be short. Returns
+	/// whether any task is left.
+	pub fn run(&mut self, budget: Duration, mut step: impl FnMut(IdleTask) -> bool) -> bool {
+		let start = Instant::now();
+		while start.elapsed() < budget {
+			let Some(task) = self.pending.pop_front() else {
+				break;
+			};
+			if step(task) {
+				self.pending.push_back(task);
+			}
+		}
+		return !self.pending.is_empty();
+	}
+}
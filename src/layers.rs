@@ -0,0 +1,413 @@
+use std::{path::PathBuf, time::SystemTime};
+
+/// Color tags to group layers, or tell document windows apart, at a glance, like the label colors of other editors.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColorTag {
+	#[default]
+	None,
+	Red,
+	Orange,
+	Yellow,
+	Green,
+	Blue,
+	Violet,
+	Gray,
+}
+
+impl ColorTag {
+	pub const ALL: [ColorTag; 8] = {
+		use ColorTag::*;
+		[None, Red, Orange, Yellow, Green, Blue, Violet, Gray]
+	};
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			ColorTag::None => "none",
+			ColorTag::Red => "red",
+			ColorTag::Orange => "orange",
+			ColorTag::Yellow => "yellow",
+			ColorTag::Green => "green",
+			ColorTag::Blue => "blue",
+			ColorTag::Violet => "violet",
+			ColorTag::Gray => "gray",
+		}
+	}
+
+	/// sRGB swatch shown next to the layer name, and in the window icon of a tagged document
+	pub fn color(&self) -> Option<[f32; 3]> {
+		match self {
+			ColorTag::None => None,
+			ColorTag::Red => Some([0.86, 0.26, 0.26]),
+			ColorTag::Orange => Some([0.93, 0.55, 0.2]),
+			ColorTag::Yellow => Some([0.93, 0.82, 0.25]),
+			ColorTag::Green => Some([0.35, 0.72, 0.36]),
+			ColorTag::Blue => Some([0.3, 0.5, 0.88]),
+			ColorTag::Violet => Some([0.6, 0.4, 0.82]),
+			ColorTag::Gray => Some([0.55, 0.55, 0.55]),
+		}
+	}
+
+	/// Index stored in documents
+	pub fn to_u8(self) -> u8 {
+		ColorTag::ALL.iter().position(|t| *t == self).unwrap() as u8
+	}
+
+	pub fn from_u8(v: u8) -> Option<Self> {
+		ColorTag::ALL.get(v as usize).copied()
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LayerKind {
+	Normal,
+	/// Bottom opaque layer, it can't have alpha and starts with its position locked
+	Background,
+	/// Notes and markups shown in the viewport, left out of exports unless the document includes them
+	Annotation,
+}
+
+/// External image a smart object layer mirrors, its raster is cached in the document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkedSource {
+	pub path: PathBuf,
+	/// Modification time of the file when the cached raster was made
+	pub modified: Option<SystemTime>,
+}
+
+impl LinkedSource {
+	pub fn new(path: PathBuf) -> Self {
+		let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+		Self { path, modified }
+	}
+
+	/// Checks the file, returns true once per change so the layer can be re-rendered from it.
+	pub fn poll_changed(&mut self) -> bool {
+		let modified = std::fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+		if modified.is_none() || modified == self.modified {
+			return false;
+		}
+		self.modified = modified;
+		return true;
+	}
+
+	/// "Edit in place", opens the source with the application associated to it.
+	pub fn open_in_app(&self) -> std::io::Result<std::process::Child> {
+		#[cfg(target_os = "windows")]
+		let mut command = {
+			let mut c = std::process::Command::new("cmd");
+			c.args(["/C", "start", ""]);
+			c
+		};
+		#[cfg(target_os = "macos")]
+		let mut command = std::process::Command::new("open");
+		#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+		let mut command = std::process::Command::new("xdg-open");
+
+		return command.arg(&self.path).spawn();
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+	pub name: String,
+	pub tag: ColorTag,
+	pub kind: LayerKind,
+	/// Pixels can't be painted, cleared or recolored
+	pub lock_pixels: bool,
+	/// Content can't be moved or transformed
+	pub lock_position: bool,
+	/// Strokes only recolor the pixels, transparent ones stay transparent
+	pub lock_alpha: bool,
+	/// Smart object layers show an external file instead of being painted
+	pub link: Option<LinkedSource>,
+	/// Name of the group the layer is in, the layers panel nests it under the group
+	pub group: Option<String>,
+}
+
+impl Layer {
+	pub fn new(name: &str) -> Self {
+		Self {
+			name: name.to_owned(),
+			tag: ColorTag::None,
+			kind: LayerKind::Normal,
+			lock_pixels: false,
+			lock_position: false,
+			lock_alpha: false,
+			link: None,
+			group: None,
+		}
+	}
+
+	pub fn annotation(name: &str) -> Self {
+		Self { kind: LayerKind::Annotation, ..Self::new(name) }
+	}
+
+	pub fn background() -> Self {
+		Self {
+			name: "Background".to_owned(),
+			tag: ColorTag::None,
+			kind: LayerKind::Background,
+			lock_pixels: false,
+			lock_position: true,
+			lock_alpha: false,
+			link: None,
+			group: None,
+		}
+	}
+
+	pub fn can_paint(&self) -> bool {
+		!self.lock_pixels && self.link.is_none()
+	}
+
+	/// Transforms resample the pixels, so they need both locks off.
+	pub fn can_transform(&self) -> bool {
+		!self.lock_pixels && !self.lock_position
+	}
+
+	pub fn has_alpha(&self) -> bool {
+		self.kind != LayerKind::Background
+	}
+
+	/// Bit flags stored in documents, the link and group name are stored apart
+	pub fn flags(&self) -> u8 {
+		(self.lock_pixels as u8)
+			| (self.lock_position as u8) << 1
+			| ((self.kind == LayerKind::Background) as u8) << 2
+			| (self.link.is_some() as u8) << 3
+			| (self.lock_alpha as u8) << 4
+			| ((self.kind == LayerKind::Annotation) as u8) << 5
+			| (self.group.is_some() as u8) << 6
+	}
+
+	pub fn set_flags(&mut self, flags: u8) {
+		self.lock_pixels = flags & 1 != 0;
+		self.lock_position = flags & 2 != 0;
+		self.lock_alpha = flags & 16 != 0;
+		self.kind = if flags & 4 != 0 {
+			LayerKind::Background
+		} else if flags & 32 != 0 {
+			LayerKind::Annotation
+		} else {
+			LayerKind::Normal
+		};
+	}
+
+	/// Case insensitive match on the name or group, or the exact tag name.
+	pub fn matches(&self, query: &str) -> bool {
+		let query = query.trim().to_lowercase();
+		let group = self.group.as_ref().is_some_and(|g| g.to_lowercase().contains(&query));
+		return self.name.to_lowercase().contains(&query) || group || self.tag.name() == query;
+	}
+}
+
+/// Layers of a document, bottom first.
+pub struct LayerStack {
+	pub layers: Vec<Layer>,
+	pub active: usize,
+	/// Layers picked for bulk operations along with the active one, by index
+	selected: Vec<bool>,
+	/// Where range selections start from, the last layer clicked without Shift
+	anchor: Option<usize>,
+}
+
+impl LayerStack {
+	pub fn new() -> Self {
+		Self::from_layers(vec![Layer::background()])
+	}
+
+	pub fn from_layers(layers: Vec<Layer>) -> Self {
+		Self {
+			selected: vec![false; layers.len()],
+			layers,
+			active: 0,
+			anchor: None,
+		}
+	}
+
+	pub fn active(&self) -> &Layer {
+		&self.layers[self.active]
+	}
+
+	pub fn active_mut(&mut self) -> &mut Layer {
+		&mut self.layers[self.active]
+	}
+
+	pub fn rename(&mut self, index: usize, name: &str) {
+		self.layers[index].name = name.to_owned();
+	}
+
+	pub fn set_tag(&mut self, index: usize, tag: ColorTag) {
+		self.layers[index].tag = tag;
+	}
+
+	/// Adds `layer` at `index` and makes it active, mirroring `Canvas::add_layer`.
+	pub fn insert(&mut self, index: usize, layer: Layer) {
+		self.layers.insert(index, layer);
+		self.selected.insert(index, false);
+		self.active = index;
+		self.anchor = None;
+	}
+
+	pub fn remove(&mut self, index: usize) -> Layer {
+		self.selected.remove(index);
+		self.anchor = None;
+		if self.active >= index && self.active > 0 {
+			self.active -= 1;
+		}
+		return self.layers.remove(index);
+	}
+
+	pub fn reorder(&mut self, from: usize, to: usize) {
+		let layer = self.layers.remove(from);
+		self.layers.insert(to, layer);
+		let selected = self.selected.remove(from);
+		self.selected.insert(to, selected);
+		self.anchor = None;
+		if self.active == from {
+			self.active = to;
+		} else if from < self.active && self.active <= to {
+			self.active -= 1;
+		} else if to <= self.active && self.active < from {
+			self.active += 1;
+		}
+	}
+
+	/// Indices of the layers bulk operations apply to, bottom first. Always has the active layer.
+	pub fn selection(&self) -> Vec<usize> {
+		return (0..self.layers.len()).filter(|i| *i == self.active || self.selected[*i]).collect();
+	}
+
+	/// Activates `index` alone, like a plain click.
+	pub fn select(&mut self, index: usize) {
+		self.selected.fill(false);
+		self.active = index;
+		self.anchor = Some(index);
+	}
+
+	/// Adds `index` to the selection or takes it out, like a Ctrl click. The active layer moves to it
+	/// when added, or to another selected layer when it was the one taken out.
+	pub fn toggle_selected(&mut self, index: usize) {
+		self.anchor = Some(index);
+		if index != self.active {
+			self.selected[self.active] = true;
+			self.selected[index] = !self.selected[index];
+			if self.selected[index] {
+				self.active = index;
+			}
+			return;
+		}
+		let Some(other) = (0..self.layers.len()).find(|i| *i != index && self.selected[*i]) else {
+			return;
+		};
+		self.selected[index] = false;
+		self.active = other;
+	}
+
+	/// Selects every layer between the anchor and `index`, which becomes active, like a Shift click.
+	pub fn select_range(&mut self, index: usize) {
+		let anchor = *self.anchor.get_or_insert(self.active);
+		let (low, high) = (anchor.min(index), anchor.max(index));
+		self.selected.fill(false);
+		self.selected[low..=high].fill(true);
+		self.active = index;
+	}
+
+	/// Activates every layer of `group`, the topmost one active, like a click on its header.
+	pub fn select_group(&mut self, group: &str) {
+		let members: Vec<usize> = (0..self.layers.len()).filter(|i| self.layers[*i].group.as_deref() == Some(group)).collect();
+		let Some(top) = members.last() else {
+			return;
+		};
+		self.select(*top);
+		for i in members {
+			self.selected[i] = true;
+		}
+	}
+
+	/// First "Group N" no layer is in yet.
+	pub fn new_group_name(&self) -> String {
+		let taken = |name: &str| self.layers.iter().any(|l| l.group.as_deref() == Some(name));
+		return (1..).map(|n| format!("Group {n}")).find(|name| !taken(name)).unwrap();
+	}
+
+	/// Indices of the layers the layers panel shows for `query`, all of them when it's empty.
+	pub fn filter(&self, query: &str) -> Vec<usize> {
+		return (0..self.layers.len()).filter(|i| query.trim().is_empty() || self.layers[*i].matches(query)).collect();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn stack(layers: usize) -> LayerStack {
+		return LayerStack::from_layers((0..layers).map(|i| Layer::new(&format!("Layer {i}"))).collect());
+	}
+
+	#[test]
+	fn select_picks_one_layer() {
+		let mut stack = stack(4);
+		stack.select_range(3);
+		stack.select(1);
+		assert_eq!(stack.selection(), [1]);
+		assert_eq!(stack.active, 1);
+	}
+
+	#[test]
+	fn toggle_adds_and_takes_out() {
+		let mut stack = stack(4);
+		stack.select(0);
+		stack.toggle_selected(2);
+		assert_eq!((stack.selection(), stack.active), (vec![0, 2], 2));
+		// Taking the active one out moves it to another selected layer
+		stack.toggle_selected(2);
+		assert_eq!((stack.selection(), stack.active), (vec![0], 0));
+		// The last selected layer stays
+		stack.toggle_selected(0);
+		assert_eq!(stack.selection(), [0]);
+	}
+
+	#[test]
+	fn range_goes_from_the_anchor() {
+		let mut stack = stack(5);
+		stack.select(1);
+		stack.select_range(3);
+		assert_eq!((stack.selection(), stack.active), (vec![1, 2, 3], 3));
+		stack.select_range(0);
+		assert_eq!((stack.selection(), stack.active), (vec![0, 1], 0));
+	}
+
+	#[test]
+	fn group_selects_its_members_topmost_active() {
+		let mut stack = stack(4);
+		for i in [0, 2] {
+			stack.layers[i].group = Some("Group 1".to_owned());
+		}
+		stack.select_group("Group 1");
+		assert_eq!((stack.selection(), stack.active), (vec![0, 2], 2));
+		assert_eq!(stack.new_group_name(), "Group 2");
+	}
+
+	#[test]
+	fn filter_matches_names_groups_and_tags() {
+		let mut stack = stack(4);
+		stack.rename(0, "Sky");
+		stack.layers[1].group = Some("Inks".to_owned());
+		stack.set_tag(2, ColorTag::Red);
+		assert_eq!(stack.filter(" sky"), [0]);
+		assert_eq!(stack.filter("INK"), [1]);
+		assert_eq!(stack.filter("red"), [2]);
+		assert_eq!(stack.filter("layer"), [1, 2, 3]);
+		assert_eq!(stack.filter(""), [0, 1, 2, 3]);
+	}
+
+	#[test]
+	fn removing_keeps_the_selection_in_bounds() {
+		let mut stack = stack(3);
+		stack.select(2);
+		stack.toggle_selected(1);
+		stack.remove(2);
+		assert_eq!(stack.active, 1);
+		assert_eq!(stack.selection(), [1]);
+	}
+}
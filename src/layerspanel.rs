@@ -0,0 +1,110 @@
+use crate::components::{Rect, Size};
+use crate::export::Pixels;
+use crate::font;
+use crate::layers::LayerStack;
+
+const SCALE: u32 = 2;
+const MARGIN: u32 = 8;
+/// Color tag swatch left of the names
+const SWATCH: u32 = 6;
+/// Space between the thumbnails and the edges of their rows
+const THUMBNAIL_INSET: u32 = 1;
+/// Widest name shown, longer ones are cut
+const MAX_COLUMNS: usize = 24;
+const BACKGROUND: [u8; 4] = [24, 24, 28, 235];
+const SELECTED: [u8; 4] = [50, 62, 90, 255];
+const ACTIVE: [u8; 4] = [70, 96, 150, 255];
+const GROUP: [u8; 4] = [240, 200, 90, 255];
+const TEXT: [u8; 4] = [230, 230, 230, 255];
+
+/// What clicking a row picks.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RowTarget {
+	Layer(usize),
+	/// A group header, picking every layer in it
+	Group(String),
+}
+
+/// A line of the layers panel, topmost layer first, with a header above the layers of each group.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+	pub text: String,
+	pub target: RowTarget,
+	pub selected: bool,
+	pub active: bool,
+	pub tag: Option<[f32; 3]>,
+}
+
+/// Rows of the layers at the indices `shown`, with the headers of their groups.
+pub fn rows(stack: &LayerStack, shown: &[usize]) -> Vec<Row> {
+	let selection = stack.selection();
+	let mut rows = Vec::new();
+	let mut group = None;
+	for (i, layer) in stack.layers.iter().enumerate().rev().filter(|(i, _)| shown.contains(i)) {
+		if layer.group.is_some() && layer.group != group {
+			let name = layer.group.clone().unwrap();
+			rows.push(Row { text: format!("> {name}"), target: RowTarget::Group(name), selected: false, active: false, tag: None });
+		}
+		group = layer.group.clone();
+		let indent = if group.is_some() { "  " } else { "" };
+		let name: String = layer.name.chars().take(MAX_COLUMNS).collect();
+		rows.push(Row {
+			text: format!("{indent}{name}"),
+			target: RowTarget::Layer(i),
+			selected: selection.contains(&i),
+			active: i == stack.active,
+			tag: layer.tag.color(),
+		});
+	}
+	return rows;
+}
+
+fn row_height() -> u32 {
+	font::text_size("A", SCALE).h + MARGIN / 2
+}
+
+/// Side of the square the thumbnails are fitted in, right of the color tag.
+fn thumbnail_side() -> u32 {
+	row_height() - 2 * THUMBNAIL_INSET
+}
+
+/// Where the thumbnail of `size` of the layer in row `row` is drawn, centered in its square and
+/// relative to the panel.
+pub fn thumbnail_rect(row: usize, size: Size) -> Rect {
+	let side = thumbnail_side();
+	let longest = size.w.max(size.h).max(1);
+	let fitted = Size { w: (size.w * side / longest).max(1), h: (size.h * side / longest).max(1) };
+	let x = MARGIN + SWATCH + MARGIN / 2 + (side - fitted.w) / 2;
+	let y = MARGIN + row as u32 * row_height() + THUMBNAIL_INSET + (side - fitted.h) / 2;
+	return Rect::new(x as i32, y as i32, fitted.w, fitted.h);
+}
+
+/// The rows with a margin around them, at least `min_width` wide. The thumbnails are left for
+/// the layout to draw over it, at `thumbnail_rect`.
+pub fn render(rows: &[Row], min_width: u32) -> Pixels {
+	let text_x = MARGIN + SWATCH + MARGIN / 2 + thumbnail_side() + MARGIN / 2;
+	let width = rows.iter().map(|r| font::text_size(&r.text, SCALE).w).max().unwrap_or(0);
+	let size = Size { w: (text_x + width + MARGIN).max(min_width), h: rows.len() as u32 * row_height() + 2 * MARGIN };
+
+	let mut pixels = Pixels::new(size);
+	font::fill(&mut pixels, 0, 0, size.w, size.h, BACKGROUND);
+	for (i, row) in rows.iter().enumerate() {
+		let y = MARGIN + i as u32 * row_height();
+		if row.active || row.selected {
+			font::fill(&mut pixels, 0, y, size.w, row_height(), if row.active { ACTIVE } else { SELECTED });
+		}
+		if let Some(tag) = row.tag {
+			let [r, g, b] = tag.map(|c| (c * 255.) as u8);
+			font::fill(&mut pixels, MARGIN, y + MARGIN / 4, SWATCH, row_height() - MARGIN / 2, [r, g, b, 255]);
+		}
+		let color = if matches!(row.target, RowTarget::Group(_)) { GROUP } else { TEXT };
+		font::draw_text(&mut pixels, text_x, y + MARGIN / 4, &row.text, color, SCALE);
+	}
+	return pixels;
+}
+
+/// Index of the row at `y` pixels from the top of the panel.
+pub fn row_at(rows: &[Row], y: i32) -> Option<usize> {
+	let row = (y - MARGIN as i32).div_euclid(row_height() as i32);
+	return usize::try_from(row).ok().filter(|r| *r < rows.len());
+}
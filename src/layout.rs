@@ -1,7 +1,7 @@
-use crate::{components::{self, Component, Point, Rect}, CustomEvents, framelimiter::FrameLimiter};
+use crate::{actions::{Action, ActionRegistry, KeyBinding}, assets::{AssetKind, AssetLibrary}, autosave::Autosave, cheatsheet, clipboard::Clipboard, commandpalette::{CommandPalette, PaletteClick}, compare::{Compare, CompareMode}, devices::{self, DeviceKind, DeviceSettings}, dialogs::{FileDialog, FilePurpose, SaveChangesDialog}, components::{self, ColorSlot, Component, Control, Event, Length, Node, Point, Rect, Size, Tree, Widget, WidgetRouter}, CustomEvents, document, export::{self, Pixels}, framelimiter::FrameLimiter, memory, guides::{self, Guide}, notice::Notice, history::{BlockEdit, History, LayersEdit, ResizeEdit}, hud, idle::{self, IdleScheduler, IdleTask}, layers::{ColorTag, LayerKind, LayerStack, LinkedSource}, layerspanel::{self, RowTarget}, livepreview::{LivePreviewWindow, LiveSource}, palette, presets::{self, BrushPreset}, quickpicker::{Pick, QuickPicker}, radial::RadialMenu, session::{self, SessionTimer}, settings::{bundle, preferences::{self, Preferences}, window::PreferencesWindow}, swatches::{self, SwatchSet}, tools::{self, Tool}, tour::{self, Tour, TourEvent}, tween::Tween, workspace::{self, Panel, WorkspaceProfile}};
 use async_trait::async_trait;
-use std::sync::Arc;
-use winit::{event::WindowEvent, event_loop::EventLoopWindowTarget, window::Window};
+use std::{collections::HashMap, sync::{atomic::{AtomicBool, Ordering}, Arc}, time::{Duration, Instant}};
+use winit::{event::{DeviceId, ModifiersState, TouchPhase, VirtualKeyCode, WindowEvent}, event_loop::EventLoopWindowTarget, window::Window};
 
 pub enum WindowLifeStatus {
 	Alive,
@@ -10,12 +10,30 @@ pub enum WindowLifeStatus {
 
 #[derive(Default)]
 pub struct LayoutContext {
-	wgpu: Option<wgpu::Instance>,
+	pub wgpu: Option<wgpu::Instance>,
+	/// Image the first window opens, from the command line
+	pub open: Option<std::path::PathBuf>,
+	/// Image the opened document is compared with, from the command line
+	pub compare: Option<std::path::PathBuf>,
+	/// Recovery file the window restores, left by a session that didn't close
+	pub recover: Option<std::path::PathBuf>,
 }
 
 #[allow(unused)]
 pub struct InputHandler {
 	mouse_position: Option<Point>,
+	/// Kind of every device seen so far
+	devices: HashMap<DeviceId, DeviceKind>,
+	/// Device of the last pointer event
+	device: DeviceKind,
+	/// From 0 to 1, None when the device doesn't report it
+	pressure: Option<f32>,
+	/// Angle between the stylus and the tablet in radians, PI/2 when upright
+	tilt: Option<f32>,
+	/// Last stylus event, touches close to it are from the hand holding the stylus
+	last_stylus: Option<Instant>,
+	/// The stylus touches with its eraser end, only known on Windows (see `devices::eraser_end`)
+	eraser_end: bool,
 }
 
 #[allow(unused)]
@@ -24,20 +42,74 @@ impl InputHandler {
 		return &self.mouse_position;
 	}
 
-	fn get_mouse_relative(&self, r: Rect) -> Option<Point> {
+	/// Document point under the mouse of a canvas drawn in `r` through `view`.
+	fn get_mouse_relative(&self, r: Rect, view: components::ViewTransform) -> Option<Point> {
 		if let Some(p) = self.mouse_position {
 			if r.inside(p) {
-				return Some(p - r.pos);
+				return Some(view.to_document(p - r.pos));
 			}
 		}
 
 		return None;
 	}
 
+	fn device(&self) -> DeviceKind {
+		self.device
+	}
+
+	fn kind(&self, device: DeviceId) -> Option<DeviceKind> {
+		self.devices.get(&device).copied()
+	}
+
+	fn pressure(&self) -> Option<f32> {
+		self.pressure
+	}
+
+	fn tilt(&self) -> Option<f32> {
+		self.tilt
+	}
+
+	fn eraser_end(&self) -> bool {
+		self.eraser_end
+	}
+
+	/// The stylus was used within `window`, so touches should be ignored.
+	fn stylus_active(&self, window: Duration) -> bool {
+		self.last_stylus.is_some_and(|t| t.elapsed() < window)
+	}
+
 	fn handle_event(&mut self, event: &WindowEvent) {
+		match event {
+			WindowEvent::CursorMoved { device_id, .. } | WindowEvent::MouseInput { device_id, .. } => {
+				self.device = *self.devices.entry(*device_id).or_insert(DeviceKind::Mouse);
+				self.pressure = None;
+				self.tilt = None;
+				self.eraser_end = false;
+			}
+			// Touch devices that report pressure at least once are taken for styluses
+			WindowEvent::Touch(touch) => {
+				let kind = self.devices.entry(touch.device_id).or_insert(DeviceKind::Touch);
+				if touch.force.is_some() {
+					*kind = DeviceKind::Stylus;
+				}
+				if *kind == DeviceKind::Stylus {
+					self.last_stylus = Some(Instant::now());
+				}
+				self.device = *kind;
+				self.pressure = touch.force.map(|f| f.normalized() as f32);
+				self.tilt = match touch.force {
+					Some(winit::event::Force::Calibrated { altitude_angle, .. }) => altitude_angle.map(|a| a as f32),
+					_ => None,
+				};
+				let lifted = matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled);
+				self.eraser_end = *kind == DeviceKind::Stylus && !lifted && devices::eraser_end(touch.id);
+			}
+			_ => (),
+		}
+
 		match event {
 			WindowEvent::CursorMoved { position, .. } => {
-				self.mouse_position = Some(position.clone().into());
+				self.mouse_position = Some((*position).into());
 			}
 			WindowEvent::CursorLeft { .. } => {
 				self.mouse_position = None;
@@ -65,22 +137,599 @@ pub trait Layout {
 
 	/// Returns a life status and maybe another layout, notice that if the child layout uses the same window, the parent layout must pronounce itself as dead.
 	fn event_handler(&mut self, _: winit::event::WindowEvent, _: &FrameLimiter);
+	/// Does deferred work for a moment, after the window asked `FrameLimiter::request_idle` to.
+	fn idle(&mut self, _: &FrameLimiter);
+}
+
+/// Errors of a device that no error scope caught, raised by its error handler for the window to
+/// take care of on its next update.
+#[derive(Default)]
+pub struct GpuFaults {
+	/// The device was lost, after a driver reset or update, and it all needs connecting again
+	pub lost: AtomicBool,
+	/// Allocating a texture or buffer failed
+	pub out_of_memory: AtomicBool,
+}
+
+/// How long to wait before asking for an adapter again, there may be none for a moment while the
+/// driver resets. Doubled each time, up to `ADAPTER_RETRY_MAX`.
+const ADAPTER_RETRY: Duration = Duration::from_millis(250);
+const ADAPTER_RETRY_MAX: Duration = Duration::from_secs(4);
+
+/// Surface of the window and a device drawing to it, and the faults its error handler raises. None
+/// when there is no adapter.
+pub async fn connect_gpu(instance: &wgpu::Instance, window: &Window, size: winit::dpi::PhysicalSize<u32>) -> Option<(wgpu::Surface, wgpu::SurfaceConfiguration, components::Context, Arc<GpuFaults>)> {
+	let surface = unsafe { instance.create_surface(window) };
+
+	let adapter = instance
+		.request_adapter(&wgpu::RequestAdapterOptions {
+			power_preference: wgpu::PowerPreference::default(),
+			compatible_surface: Some(&surface),
+			force_fallback_adapter: false,
+		})
+		.await?;
+
+	let (device, queue, tier) = request_device(&adapter).await;
+
+	let formats = surface.get_supported_formats(&adapter);
+	let alpha_modes = surface.get_supported_alpha_modes(&adapter);
+	let format = choose_surface_format(&formats);
+	let alpha_mode = choose_alpha_mode(&alpha_modes, false);
+	log::info!("Surface format {:?} out of {:?}", format, formats);
+	log::info!("Surface alpha mode {:?} out of {:?}", alpha_mode, alpha_modes);
+
+	let config = wgpu::SurfaceConfiguration {
+		usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+		format,
+		width: size.width,
+		height: size.height,
+		present_mode: wgpu::PresentMode::AutoNoVsync,
+		alpha_mode,
+	};
+
+	surface.configure(&device, &config);
+
+	// Uncaptured errors panic by default, running out of memory and losing the device are recovered from instead
+	let faults = Arc::new(GpuFaults::default());
+	let raised = faults.clone();
+	device.on_uncaptured_error(move |e| {
+		if let wgpu::Error::OutOfMemory { .. } = e {
+			log::error!("GPU out of memory: {e}");
+			raised.out_of_memory.store(true, Ordering::Relaxed);
+			return;
+		}
+		if !is_device_lost(&e) {
+			panic!("Unhandled wgpu error: {e}");
+		}
+		log::error!("GPU device lost: {e}");
+		raised.lost.store(true, Ordering::Relaxed);
+	});
+
+	return Some((surface, config, components::Context::new(device, queue, format, tier), faults));
+}
+
+/// Why documents can't be painted with the adapter windows get, None when they can. Other windows
+/// work on any tier.
+pub async fn painting_unsupported(instance: &wgpu::Instance) -> Option<String> {
+	let Some(adapter) = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await else {
+		return Some("No graphics adapter was found.".to_string());
+	};
+	let tier = components::Tier::of(&adapter);
+	if tier.paints() {
+		return None;
+	}
+	let info = adapter.get_info();
+	return Some(format!("{} ({:?}) is a {} adapter, painting needs compute shaders.", info.name, info.backend, tier.name()));
+}
+
+/// Device with the features and limits the components need, as much of them as the adapter has.
+pub async fn request_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue, components::Tier) {
+	let tier = components::Tier::of(adapter);
+	let missing = components::Tier::FEATURES - adapter.features();
+	if !missing.is_empty() {
+		log::warn!("Adapter lacks {:?}", missing);
+	}
+	log::info!("GPU capability tier: {}", tier.name());
+	let (device, queue) = adapter
+		.request_device(
+			&wgpu::DeviceDescriptor {
+				features: components::Tier::FEATURES & adapter.features(),
+				limits: tier.limits(adapter),
+				label: None,
+			},
+			None,
+		)
+		.await
+		.expect("Could not get device-queue pair");
+	return (device, queue, tier);
+}
+
+/// Whether the error, or one it was caused by, is from the device being removed or reset.
+fn is_device_lost(e: &wgpu::Error) -> bool {
+	let mut source: Option<&(dyn std::error::Error + 'static)> = Some(e);
+	while let Some(e) = source {
+		if let Some(wgpu_core::device::DeviceError::Lost) = e.downcast_ref() {
+			return true;
+		}
+		source = e.source();
+	}
+	return false;
+}
+
+/// Prefers 8 bit sRGB formats, the Image shader takes care of encoding canvas colors for them.
+/// Falling back to whatever the surface prefers.
+fn choose_surface_format(formats: &[wgpu::TextureFormat]) -> wgpu::TextureFormat {
+	use wgpu::TextureFormat::*;
+	for preferred in [Bgra8UnormSrgb, Rgba8UnormSrgb] {
+		if formats.contains(&preferred) {
+			return preferred;
+		}
+	}
+	return *formats.first().expect("Surface is incompatible with the adapter");
+}
+
+/// Windows that want to show through their background need a (pre)multiplied alpha mode,
+/// every other window should be opaque so the compositor doesn't blend stray alpha values.
+fn choose_alpha_mode(modes: &[wgpu::CompositeAlphaMode], transparent: bool) -> wgpu::CompositeAlphaMode {
+	use wgpu::CompositeAlphaMode::*;
+	let preference: &[wgpu::CompositeAlphaMode] = if transparent {
+		&[PreMultiplied, PostMultiplied, Inherit]
+	} else {
+		&[Opaque, Inherit]
+	};
+
+	for preferred in preference {
+		if modes.contains(preferred) {
+			return *preferred;
+		}
+	}
+	return Auto;
+}
+
+/// wgpu doesn't expose partial presentation (VK_KHR_incremental_present, EGL_KHR_swap_buffers_with_damage, ...),
+/// so for now the window pixels drawn again are only reported and the whole surface is presented.
+fn present(output: wgpu::SurfaceTexture, damage: Option<Rect>) {
+	log::trace!("Presenting frame, damaged region: {:?}", damage);
+	output.present();
+}
+
+/// Color around the document from the config or `theme`, for a surface of `format`.
+pub fn load_workspace_color(format: wgpu::TextureFormat, theme: preferences::Theme) -> wgpu::Color {
+	let srgb = format.describe().srgb;
+	let [r, g, b] = workspace::load_background(theme).map(|c| {
+		let c = c as f32 / 255.;
+		(if srgb { export::srgb_to_linear(c) } else { c }) as f64
+	});
+	return wgpu::Color { r, g, b, a: 1. };
+}
+
+/// Top left corner of something of `size` centered on `p`.
+fn centered(p: Point, size: Size) -> Point {
+	return Point { x: p.x - size.w as i32 / 2, y: p.y - size.h as i32 / 2 };
+}
+
+/// Side of the window icon of tagged documents
+const TAG_ICON_SIDE: u32 = 32;
+
+/// Window icon filled with a tag `color`, inside a darker rim so it stands out on light and dark taskbars.
+fn tag_icon(color: [f32; 3]) -> winit::window::Icon {
+	let rim = 2;
+	let mut rgba = Vec::with_capacity((TAG_ICON_SIDE * TAG_ICON_SIDE * 4) as usize);
+	for y in 0..TAG_ICON_SIDE {
+		for x in 0..TAG_ICON_SIDE {
+			let edge = x < rim || y < rim || x >= TAG_ICON_SIDE - rim || y >= TAG_ICON_SIDE - rim;
+			let shade = if edge { 0.6 } else { 1. };
+			rgba.extend(color.map(|c| (c * shade * 255.).round() as u8));
+			rgba.push(255);
+		}
+	}
+	return winit::window::Icon::from_rgba(rgba, TAG_ICON_SIDE, TAG_ICON_SIDE).unwrap();
+}
+
+/// Zoom factor of one scroll wheel line
+const ZOOM_STEP: f32 = 1.25;
+/// Touchpads scroll by pixels, this many make a line
+const PIXELS_PER_SCROLL_LINE: f32 = 50.;
+
+/// Pixels the stabilizer string changes by
+const STABILIZER_STEP: i32 = 4;
+/// Pixels the feathered selection edges change by
+const FEATHER_STEP: f32 = 4.;
+const SELECTION_STROKE_WIDTH: u32 = 4;
+/// Widest border stroked along the selection, every pixel searches this far for the edge
+const MAX_SELECTION_STROKE_WIDTH: u32 = 64;
+/// Degrees the brush tip turns by
+const ROTATION_STEP: f32 = 15.;
+
+const FILL_TOLERANCE: f32 = 32. / 255.;
+
+/// Surface losses in a row after which the device is taken as lost
+const MAX_SURFACE_LOSSES: u32 = 3;
+/// How often the files of linked layers are checked for changes
+const LINK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const FILL_TOLERANCE_STEP: f32 = 8. / 255.;
+/// Pixels tall typed text is at first, how much each step changes it and the range it stays in
+const DEFAULT_TEXT_SIZE: f32 = 32.;
+const TEXT_SIZE_STEP: f32 = 2.;
+const TEXT_SIZES: std::ops::RangeInclusive<f32> = 6.0..=512.;
+
+/// Length of the marching ants dashes around the selection
+const ANTS_DASH: f32 = 4.;
+/// Pixels per second the ants march
+const ANTS_SPEED: f32 = 8.;
+/// Past when the next step of the ants is due the window is woken, so it isn't woken just before it
+const ANTS_WAKE_MARGIN: std::time::Duration = std::time::Duration::from_millis(2);
+
+/// Length of the guide dashes, set apart from the solid tool outlines
+const GUIDE_DASH: f32 = 8.;
+
+/// Window pixels across the handle the symmetry center is dragged by
+const SYMMETRY_HANDLE: u32 = 10;
+/// Copies around the center a new kaleidoscope starts with
+const KALEIDOSCOPE_FOLDS: u32 = 6;
+
+/// How long the area changed by undo or redo stays outlined
+const UNDO_HIGHLIGHT: Duration = Duration::from_millis(900);
+/// Times the outline pulses while it fades out
+const UNDO_PULSES: f32 = 3.;
+
+const PREVIEW_SIZE: Size = Size { w: 320, h: 96 };
+/// Space between the brush preview and the window edges
+const PREVIEW_MARGIN: u32 = 16;
+/// Points the preview stroke goes through
+const PREVIEW_SAMPLES: u32 = 48;
+
+/// Space between the HUD and the window edges
+const HUD_MARGIN: u32 = 16;
+
+const MIXER_SIDE: u32 = 192;
+/// Space between the mixer and the window edges
+const MIXER_MARGIN: u32 = 16;
+const PICKER_SIDE: u32 = 160;
+const SLIDER_WIDTH: u32 = 200;
+const FIELD_WIDTH: u32 = 72;
+const CONTROL_HEIGHT: u32 = 24;
+/// Space between the controls and around them
+const CONTROL_GAP: u32 = 4;
+const CONTROLS: [Control; 3] = [Control::BrushSize, Control::BrushOpacity, Control::Zoom];
+const SYMMETRY_OPTIONS: [&str; 5] = ["Off", "Left-right", "Top-bottom", "Both ways", "Radial"];
+
+/// GPU memory by category and buttons freeing some, shown by `Action::ToggleMemoryPanel`.
+struct MemoryPanel {
+	image: Box<components::Image>,
+	/// Shown by the image, drawn again when they change
+	lines: Vec<String>,
+	size: Size,
+	trim: Box<components::Button>,
+	clear_undo: Box<components::Button>,
+}
+
+/// Wide enough for both buttons
+const MEMORY_PANEL_WIDTH: u32 = 300;
+
+/// Layers top first under the headers of their groups, shown by `Action::ToggleLayersPanel`, under a
+/// field searching them.
+struct LayersPanel {
+	image: Box<components::Image>,
+	/// Shown by the image, drawn again when they change
+	rows: Vec<layerspanel::Row>,
+	size: Size,
+	/// Shows the layers matching what is typed in, or takes the new name of `renaming`
+	field: Box<components::TextField>,
+	/// Layer `Action::RenameLayer` was run on, until Enter or Escape
+	renaming: Option<usize>,
+}
+
+/// The rows of the layers panel. Clicking one emits `Event::Selected("layers.row", index)`, the
+/// layout picks its layers with the modifiers held.
+struct LayerRows<'a> {
+	rows: &'a [layerspanel::Row],
+}
+
+impl Widget for LayerRows<'_> {
+	fn press(&mut self, ctx: &mut components::Context, viewport: Rect, p: Point) -> bool {
+		if let Some(row) = layerspanel::row_at(self.rows, p.y - viewport.pos.y) {
+			ctx.emit(Event::Selected("layers.row", row));
+		}
+		return true;
+	}
+}
+
+const LAYERS_PANEL_WIDTH: u32 = 200;
+const LAYERS_SEARCH: &str = "Search layers";
+
+/// The color mixer, smeared with the canvas brush wet with the foreground color, or picked from into
+/// `pick`.
+struct MixerWidget<'a> {
+	mixer: &'a mut components::Canvas,
+	canvas: &'a components::Canvas,
+	pick: Option<ColorSlot>,
+	pressure: Option<f32>,
+}
+
+impl Widget for MixerWidget<'_> {
+	fn press(&mut self, ctx: &mut components::Context, viewport: Rect, p: Point) -> bool {
+		let p = self.mixer.view().to_document(p - viewport.pos);
+		if let Some(slot) = self.pick {
+			if let Some(pixel) = self.mixer.pixel_at(ctx, p) {
+				ctx.emit(Event::ColorChanged(slot, [0, 1, 2].map(|i| pixel[i] as f32 / 255.)));
+			}
+			return true;
+		}
+		self.mixer.set_brush(self.canvas.brush());
+		self.mixer.set_brush_tip(ctx, self.canvas.brush_tip());
+		self.mixer.set_color(ColorSlot::Foreground, self.canvas.get_color(ColorSlot::Foreground));
+		self.mixer.set_pressure_curve(self.canvas.pressure_curve().clone());
+		self.mixer.mouse_pos(p, self.pressure);
+		self.mixer.mouse_down();
+		return true;
+	}
+
+	fn drag(&mut self, _: &mut components::Context, viewport: Rect, p: Point) -> bool {
+		if !self.mixer.is_drawing() {
+			return false;
+		}
+		self.mixer.mouse_pos(self.mixer.view().to_document(p - viewport.pos), self.pressure);
+		return true;
+	}
+
+	fn release(&mut self, _: &mut components::Context) {
+		if self.mixer.is_drawing() {
+			self.mixer.mouse_up();
+		}
+	}
+}
+
+/// The compare divider, over the canvas rect the document is drawn in with `view`.
+struct CompareDivider<'a> {
+	compare: &'a mut Compare,
+	view: components::ViewTransform,
+	document: Size,
+}
+
+impl Widget for CompareDivider<'_> {
+	fn press(&mut self, _: &mut components::Context, viewport: Rect, p: Point) -> bool {
+		return self.compare.press(self.view.to_document(p - viewport.pos), self.view.scale);
+	}
+
+	fn drag(&mut self, _: &mut components::Context, viewport: Rect, p: Point) -> bool {
+		return self.compare.drag(self.view.to_document(p - viewport.pos), self.document);
+	}
+
+	fn release(&mut self, _: &mut components::Context) {
+		self.compare.release();
+	}
+}
+
+/// Handle of the symmetry center, dragging it sets `moved` to the document point under the pointer.
+struct SymmetryCenter<'a> {
+	view: components::ViewTransform,
+	/// Of the canvas rect
+	origin: Point,
+	moved: &'a mut Option<[f32; 2]>,
+}
+
+impl Widget for SymmetryCenter<'_> {
+	fn press(&mut self, _: &mut components::Context, _: Rect, _: Point) -> bool {
+		return true;
+	}
+
+	fn drag(&mut self, _: &mut components::Context, _: Rect, p: Point) -> bool {
+		let point = self.view.to_document(p - self.origin);
+		*self.moved = Some([point.x as f32, point.y as f32]);
+		return true;
+	}
+}
+
+/// Nodes of the component trees, and the widgets the pointer and keyboard input is routed to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum NodeId {
+	Toolbar,
+	Canvas,
+	ColorPicker,
+	Swatches,
+	Slider(Control),
+	Field(Control),
+	ActualSize,
+	Eraser,
+	Symmetry,
+	TrimCaches,
+	ClearUndo,
+	LayersPanel,
+	LayersField,
+	Mixer,
+	CompareDivider,
+	SymmetryCenter,
+	CommandPalette,
+	CommandPaletteField,
 }
 
 pub struct DrawingWindow {
 	window: Arc<Window>,
 	surface: wgpu::Surface,
-	queue: wgpu::Queue,
 	config: wgpu::SurfaceConfiguration,
 	size: winit::dpi::PhysicalSize<u32>,
+	/// The toolbar along the left of the window and the canvas taking the rest
+	window_tree: Tree<NodeId>,
 
 	ctx: components::Context,
 
 	canvas: Box<components::Canvas>,
+	layers: LayerStack,
+	/// Thumbnails of the layers for the layers panel, bottom first, made again when layers are added or removed
+	layer_thumbnails: Vec<components::LayerThumbnail>,
+	/// Of the thumbnails to the canvas invalidations
+	thumbnail_subscription: components::Subscription,
+	/// What the window shows, drawn again only where damaged
+	frame: Box<components::Frame>,
+	outline: Box<components::Outline>,
+	/// Under the document edges, so they stand out from the workspace
+	shadow: Box<components::Shadow>,
+	/// Guide along the symmetry axes
+	axes: Box<components::Axes>,
+	/// Buttons switching tools, left of the canvas
+	toolbar: Box<components::Toolbar>,
+	/// Which widget holds the press and the keyboard
+	widget_router: WidgetRouter<NodeId>,
+	/// Clear color around the document, linear for sRGB surfaces
+	workspace_color: wgpu::Color,
+	history: History,
+	/// Exports whose canvas readbacks are still being mapped
+	pending_exports: Vec<export::PendingExport>,
+	export_presets: Vec<export::ExportPreset>,
+	/// Edit started on the canvas, recorded in the history once the canvas drew all of it
+	pending_edit: Option<&'static str>,
+	/// Modal tool getting pointer input before the canvas
+	tool: Option<Box<dyn Tool>>,
+	/// Per channel tolerance of the fill tool
+	fill_tolerance: f32,
+	/// The fill tool paints under the layer pixels instead of over them
+	fill_behind: bool,
+	/// Pixels wide the border stroked along the selection is
+	selection_stroke_width: u32,
+	/// Pixels added between the glyphs of text along a path, and how far above the path they sit
+	text_spacing: i32,
+	text_baseline: i32,
+	/// Pixels tall the text tool types, apart from the brush size
+	text_size: f32,
+	/// Pixels copied or cut from the selection, with straight alpha
+	selection_buffer: Option<Pixels>,
+	/// Images copied to and pasted from other applications
+	clipboard: Clipboard,
+	/// When the window opened, animations are timed from it
+	epoch: std::time::Instant,
+	/// Screen rect and step the marching ants were last drawn at, they move a pixel each step. None
+	/// without a selection
+	ants: Option<(Rect, u32)>,
+	/// Wakes the window for the next step of slow animations, from the first event on
+	redraw_timer: Option<crate::framelimiter::RedrawTimer>,
+	/// Document area the last undo or redo changed, outlined until the tween finishes
+	undo_highlight: Option<(Rect, Tween)>,
+	follow_undo: bool,
+	/// Earlier version of the document shown over it
+	compare: Option<Compare>,
+	guides: Vec<Guide>,
+	guides_shown: bool,
+	/// Recolored variant shown over the canvas, and the image holding it
+	palette_preview: Option<(palette::SwapPreview, Box<components::Image>)>,
+	/// Where the palette variants are exported once the preview has the canvas read back
+	variant_export: Option<std::path::PathBuf>,
+	tour: Option<Tour>,
+	/// Shortcut overlay shown while F1 is held
+	cheatsheet: Option<(Size, Box<components::Image>)>,
+	/// Numeric fields of the active tool, the text they were drawn from and their image
+	hud: Option<(String, Size, Box<components::Image>)>,
+	/// Message shown at the bottom of the window until it expires, its size and image
+	notice: Option<(Notice, Size, Box<components::Image>)>,
+	/// Pixels of the tool preview and their texture
+	tool_preview: Option<(Pixels, Box<components::Image>)>,
+	/// Quick menu around the cursor while Tab or the right button is held, and the image showing it
+	radial: Option<(RadialMenu, Box<components::Image>)>,
+	/// Color picker summoned at the cursor, and the image showing it
+	quick_picker: Option<(QuickPicker, Box<components::Image>)>,
+	/// Actions searched by name, the field typing the search in and the image of the list
+	command_palette: Option<(CommandPalette, Box<components::TextField>, Box<components::Image>)>,
+	/// Stroke painted with the brush and foreground color it was last drawn with, while shown
+	brush_preview: Option<(components::Brush, [f32; 3], Box<components::Canvas>)>,
+	/// Scratch canvas to mix colors on, created when first shown and kept while hidden
+	mixer: Option<Box<components::Canvas>>,
+	mixer_shown: bool,
+	/// Picks the foreground color, while shown
+	color_picker: Option<Box<components::ColorPicker>>,
+	/// Colors painted with lately and pinned ones, saved between sessions
+	swatch_set: SwatchSet,
+	/// Shows the swatches to click on, while shown
+	swatches: Option<Box<components::Swatches>>,
+	/// Widgets shown by `Action::ToggleControls`, top right of the canvas
+	controls: Option<Tree<NodeId>>,
+	memory_panel: Option<MemoryPanel>,
+	layers_panel: Option<LayersPanel>,
+	/// Image in the brushes assets the brush tip was loaded from, round dabs without one
+	brush_tip: Option<std::path::PathBuf>,
+	/// Brushes saved under the number keys
+	brush_presets: std::collections::BTreeMap<u8, BrushPreset>,
+	/// Last pointer position while dragging the view around
+	panning: Option<Point>,
+	/// Space held, the left button pans instead of painting
+	space_held: bool,
+	/// Opaque panels and modals drawn over the canvas this frame, used to cull it.
+	occluders: Vec<Rect>,
+
+	actions: ActionRegistry,
+	/// Bindings of the keymap file, under those of the active profile
+	keymap: Vec<(KeyBinding, Action)>,
+	profiles: Vec<WorkspaceProfile>,
+	/// Active profile, None until one is picked
+	profile: Option<usize>,
+	/// None when there is no home directory to keep them in
+	assets: Option<AssetLibrary>,
+	modifiers: ModifiersState,
+	input: InputHandler,
+	/// By device kind, the settings of `input.device()` are the ones applied
+	device_settings: HashMap<DeviceKind, DeviceSettings>,
+	/// Eraser mode from before the stylus was flipped, while it is
+	erasing_before_eraser_end: Option<bool>,
+
+	session: SessionTimer,
+	/// Tracked time currently shown in the title, in minutes
+	title_minutes: Option<u64>,
+	/// Where the document is saved, None until it is saved or opened
+	document_path: Option<std::path::PathBuf>,
+	/// Unix seconds the document was first saved at
+	created: Option<u64>,
+	/// Shown in the title and window icon
+	document_tag: ColorTag,
+	/// Exports include the annotation layers
+	export_annotations: bool,
+	/// The document is saved with its strokes
+	save_history: bool,
+
+	/// Set after running out of GPU memory once, running out again closes the window
+	low_memory: bool,
+
+	/// Connects to the GPU again when the device is lost
+	instance: wgpu::Instance,
+	gpu_faults: Arc<GpuFaults>,
+	/// Surface losses in a row, the device is likely gone when they keep coming
+	surface_losses: u32,
+	/// When the files of the linked layers were last checked
+	links_polled: Instant,
+	/// When to ask for an adapter again after there was none to reconnect to, and how long to wait
+	/// if there is still none then
+	adapter_retry: Option<(Instant, Duration)>,
+	/// Shown in the title after coming back from a lost device, until the next edit
+	recovered: bool,
+	/// The document changed since it was last saved or opened, closing asks what to do with it
+	dirty: bool,
+	/// Closes the window once the save dialog shown when closing saved the document
+	close_after_save: bool,
+	/// Save of the document in progress
+	saving: Option<document::BackgroundSave>,
+	/// The document changed while it was being saved, so it still has unsaved changes after
+	edited_while_saving: bool,
+	autosave: Autosave,
+	preferences: Preferences,
+	/// Of the preferences loaded last, see `preferences::generation`
+	preferences_generation: u64,
+	/// Open the preferences window on the next update
+	open_preferences: bool,
+	/// Sends the composite to the live preview window while it is open
+	live_preview: Option<LiveSource>,
+	open_live_preview: bool,
+	/// Work done between bursts of input
+	idle: IdleScheduler,
 
 	//Events:
 	resized: bool,
 	close: bool,
+	/// Files dropped on the window since the last update, in the order they came
+	dropped: Vec<std::path::PathBuf>,
+	/// Dropped files still to open in windows of their own, one per update
+	open_in_windows: Vec<std::path::PathBuf>,
+	/// Shown until it is answered, one at a time
+	file_dialog: Option<FileDialog>,
+	/// Asks what to do with the unsaved changes when closing, until it is answered
+	close_dialog: Option<SaveChangesDialog>,
 }
 
 #[async_trait]
@@ -91,7 +740,9 @@ impl Layout for DrawingWindow {
 	{
 		LayoutContext {
 			wgpu: Some(wgpu::Instance::new(wgpu::Backends::all())),
-			..LayoutContext::default()
+			open: None,
+			compare: None,
+			recover: None,
 		}
 	}
 
@@ -99,61 +750,161 @@ impl Layout for DrawingWindow {
 		let size = window.inner_size();
 
 		let instance = layout_ctx.wgpu.expect("Generated with wrong context");
-		let surface = unsafe { instance.create_surface(window.as_ref()) };
-
-		let adapter = instance
-			.request_adapter(&wgpu::RequestAdapterOptions {
-				power_preference: wgpu::PowerPreference::default(),
-				compatible_surface: Some(&surface),
-				force_fallback_adapter: false,
-			})
-			.await
-			.expect("Could not get adapter");
-
-		let (device, queue) = adapter
-			.request_device(
-				&wgpu::DeviceDescriptor {
-					features: wgpu::Features::PUSH_CONSTANTS
-						| wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
-					limits: wgpu::Limits {
-						max_push_constant_size: 64,
-						..wgpu::Limits::default()
-					},
-					label: None,
-				},
-				None,
-			)
-			.await
-			.expect("Could not get device-queue pair");
-
-		let config = wgpu::SurfaceConfiguration {
-			usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-			format: surface.get_supported_formats(&adapter)[0],
-			width: size.width,
-			height: size.height,
-			present_mode: wgpu::PresentMode::AutoNoVsync,
-			alpha_mode: wgpu::CompositeAlphaMode::Auto,
-		};
-
-		surface.configure(&device, &config);
+		let (surface, config, mut ctx, gpu_faults) = connect_gpu(&instance, &window, size).await.expect("Could not get adapter");
 
-		let mut ctx = components::Context::new(device, config.format);
-
-		let canvas = components::Canvas::new(&mut ctx);
+		let device_settings = devices::load_settings();
+		let mut canvas = components::Canvas::new(&mut ctx);
+		let thumbnail_subscription = canvas.invalidations().subscribe();
+		canvas.set_pressure_curve(device_settings[&DeviceKind::Mouse].pressure_curve.clone());
+		let frame = components::Frame::new(&mut ctx);
+		let outline = components::Outline::new(&mut ctx);
+		let shadow = components::Shadow::new(&mut ctx);
+		let axes = components::Axes::new(&mut ctx);
+		let toolbar = components::Toolbar::new(&mut ctx);
+		let preferences = preferences::load();
+		let mut actions = ActionRegistry::new();
+		let keymap = actions.load_keymap();
+		actions.set_overrides(&keymap);
+		let profiles = workspace::load_profiles(&actions);
+		let workspace_color = load_workspace_color(config.format, preferences.theme);
+		let document = preferences.canvas_size;
+		if canvas.size() != document {
+			canvas.resize_document(&mut ctx, Rect::new(0, 0, document.w, document.h));
+			canvas.take_resize();
+		}
 
-		return Box::new(Self {
+		let mut layout = Box::new(Self {
 			window,
 			surface,
-			queue,
 			config,
 			size,
+			window_tree: Tree::new(Node::row(0, vec![(Length::Fixed(components::TOOLBAR_WIDTH), Node::Slot(NodeId::Toolbar)), (Length::Flex(1), Node::Slot(NodeId::Canvas))])),
 
 			ctx,
 			canvas,
+			layers: LayerStack::new(),
+			layer_thumbnails: Vec::new(),
+			thumbnail_subscription,
+			pending_exports: Vec::new(),
+			export_presets: export::load_presets(),
+			frame,
+			outline,
+			shadow,
+			axes,
+			toolbar,
+			widget_router: WidgetRouter::new(),
+			workspace_color,
+			history: History::new(preferences.history_depth, preferences.history_budget),
+			pending_edit: None,
+			tool: None,
+			fill_tolerance: FILL_TOLERANCE,
+			fill_behind: false,
+			selection_stroke_width: SELECTION_STROKE_WIDTH,
+			text_spacing: 0,
+			text_baseline: 0,
+			text_size: DEFAULT_TEXT_SIZE,
+			selection_buffer: None,
+			clipboard: Clipboard::new(),
+			epoch: std::time::Instant::now(),
+			ants: None,
+			redraw_timer: None,
+			undo_highlight: None,
+			follow_undo: true,
+			compare: None,
+			guides: guides::load_guides(),
+			guides_shown: false,
+			palette_preview: None,
+			variant_export: None,
+			tour: tour::is_first_run().then(Tour::new),
+			cheatsheet: None,
+			hud: None,
+			notice: None,
+			tool_preview: None,
+			radial: None,
+			quick_picker: None,
+			command_palette: None,
+			brush_preview: None,
+			mixer: None,
+			mixer_shown: false,
+			color_picker: None,
+			swatch_set: swatches::load_swatches(),
+			swatches: None,
+			controls: None,
+			memory_panel: None,
+			layers_panel: None,
+			brush_tip: None,
+			brush_presets: presets::load_presets(),
+			panning: None,
+			space_held: false,
+			occluders: Vec::new(),
+
+			actions,
+			keymap,
+			profiles,
+			profile: None,
+			assets: crate::assets::default_root().map(AssetLibrary::new),
+			modifiers: ModifiersState::empty(),
+			input: InputHandler { mouse_position: None, devices: HashMap::new(), device: DeviceKind::Mouse, pressure: None, tilt: None, last_stylus: None, eraser_end: false },
+			device_settings,
+			erasing_before_eraser_end: None,
+
+			session: SessionTimer::new(std::time::Duration::ZERO),
+			title_minutes: None,
+			document_path: None,
+			created: None,
+			document_tag: ColorTag::None,
+			export_annotations: false,
+			save_history: false,
+
+			low_memory: false,
+
+			instance,
+			gpu_faults,
+			surface_losses: 0,
+			links_polled: Instant::now(),
+			adapter_retry: None,
+			recovered: false,
+			dirty: false,
+			close_after_save: false,
+			saving: None,
+			edited_while_saving: false,
+			autosave: Autosave::new(preferences.autosave_interval),
+			preferences,
+			preferences_generation: preferences::generation(),
+			open_preferences: false,
+			live_preview: None,
+			open_live_preview: false,
+			idle: {
+				let mut idle = IdleScheduler::new();
+				idle.schedule(IdleTask::WarmPipelines);
+				idle
+			},
 
 			resized: false,
 			close: false,
+			dropped: Vec::new(),
+			open_in_windows: Vec::new(),
+			file_dialog: None,
+			close_dialog: None,
 		});
+
+		layout.layout_window();
+		match &layout_ctx.open {
+			Some(path) if path.extension().is_some_and(|e| e == "pntr") => layout.open_document(path),
+			Some(path) => layout.open_image(path),
+			None => (),
+		}
+		if let Some(path) = &layout_ctx.recover {
+			layout.open_document(path);
+			layout.autosave.adopt(path);
+			layout.document_path = None;
+			layout.mark_dirty();
+			layout.update_title();
+		}
+		if let Some(path) = &layout_ctx.compare {
+			layout.compare_with_file(path);
+		}
+		return layout;
 	}
 
 	fn window(&self) -> Arc<Window> {
@@ -161,12 +912,21 @@ impl Layout for DrawingWindow {
 	}
 
 	fn render(&mut self) {
+		self.handle_events();
 		match self.surface.get_current_texture() {
-			Err(wgpu::SurfaceError::Lost) => self.resized = true,
-			Err(wgpu::SurfaceError::OutOfMemory) => self.close = true,
+			Err(wgpu::SurfaceError::Lost) => {
+				self.surface_losses += 1;
+				if self.surface_losses > MAX_SURFACE_LOSSES {
+					log::error!("Surface lost {} times in a row, reconnecting to the GPU", self.surface_losses);
+					self.gpu_faults.lost.store(true, Ordering::Relaxed);
+				}
+				self.resized = true;
+			}
+			Err(wgpu::SurfaceError::OutOfMemory) => self.out_of_memory(),
 			Err(e) => eprintln!("{:?}", e),
 			Ok(output) => {
-				let view = output
+				self.surface_losses = 0;
+				let surface_view = output
 					.texture
 					.create_view(&wgpu::TextureViewDescriptor::default());
 
@@ -177,57 +937,655 @@ impl Layout for DrawingWindow {
 							label: Some("Render Encoder"),
 						});
 
-				self.canvas.render(
-					&mut encoder,
-					&mut self.ctx,
-					&view,
-					components::Rect::new(0, 0, self.size.width, self.size.height),
-					None,
-				);
+				let window_rect = Rect::new(0, 0, self.size.width, self.size.height);
+				self.frame.resize(&self.ctx, window_rect.size);
+				let view = self.frame.create_view().expect("Frame sized above");
+				let canvas_rect = self.canvas_rect();
+				let transform = self.canvas.view();
+				let document = transform.to_screen_rect(Rect { pos: Point { x: 0, y: 0 }, size: self.canvas.size() }) + canvas_rect.pos;
+
+				let mixer_rect = self.mixer_rect();
+				let picker_rect = self.picker_rect();
+				let swatches_rect = self.swatches_rect();
+				self.occluders.clear();
+				self.occluders.extend(mixer_rect);
+				self.occluders.extend(picker_rect);
+				self.occluders.extend(swatches_rect);
+				if let Some(controls) = &mut self.controls {
+					self.occluders.extend(controls.widgets().iter().map(|(_, r, _)| *r));
+				}
+
+				// Strokes stay queued in the canvas while it is hidden
+				let canvas_shown = !components::is_culled(canvas_rect, window_rect, &self.occluders);
+				if canvas_shown {
+					let transform = self.tool.as_ref().and_then(|t| t.transform_preview());
+					self.canvas.set_transform_preview(&mut self.ctx, &mut encoder, transform);
+					self.canvas.update(&mut encoder, &mut self.ctx);
+				}
+				// Strokes only damage where they landed, the rest is damaged by whatever scheduled the frame
+				let damage = self.canvas.take_damage();
+				if let Some(r) = damage.and_then(|d| (transform.to_screen_rect(d) + canvas_rect.pos).intersection(canvas_rect)) {
+					self.frame.damage(r);
+				}
+				self.refresh_memory_panel();
+				let memory_rects = self.memory_rects();
+				self.occluders.extend(memory_rects.map(|(panel, _, _)| panel));
+				self.refresh_layers_panel();
+				let (layers_rect, layers_field_rect) = (self.layers_panel_rect(), self.layers_field_rect());
+				self.occluders.extend(layers_rect);
+				self.occluders.extend(layers_field_rect);
+
+				// The ants only draw again when they march a step or the selection changes, the window is
+				// woken for the next step
+				let marched = self.epoch.elapsed().as_secs_f32() * ANTS_SPEED;
+				let ants = self.canvas.selection().map(|s| (transform.to_screen_rect(s) + canvas_rect.pos, marched as u32));
+				if ants != self.ants {
+					for (r, _) in self.ants.iter().chain(&ants) {
+						if let Some(r) = r.intersection(canvas_rect) {
+							self.frame.damage(r);
+						}
+					}
+					let stepped = ants.is_some_and(|(_, step)| self.ants.is_none_or(|(_, last)| last != step));
+					if let (true, Some(timer)) = (stepped, &self.redraw_timer) {
+						timer.redraw_in(ANTS_WAKE_MARGIN + std::time::Duration::from_secs_f32((marched.floor() + 1. - marched) / ANTS_SPEED));
+					}
+					self.ants = ants;
+				}
+
+				// Everything below is only drawn in there, nothing when the frame is as it was
+				let drawn = self.frame.take_damage();
+				let dirty = drawn.unwrap_or(Rect::new(0, 0, 0, 0));
+
+				// What was drawn there is blended over otherwise, see-through documents included
+				if dirty.intersects(window_rect) {
+					self.frame.fill(&mut encoder, &mut self.ctx, &view, dirty, self.workspace_color);
+					// Around the document when zoomed out or panned away from it
+					if !document.contains(canvas_rect) {
+						self.shadow.caster = document;
+						self.shadow.render(&mut encoder, &mut self.ctx, &view, window_rect, Some(dirty));
+					}
+				}
+
+				if let (true, Some(clip)) = (canvas_shown, canvas_rect.intersection(dirty)) {
+					self.canvas.render(
+						&mut encoder,
+						&mut self.ctx,
+						&view,
+						canvas_rect,
+						Some(clip),
+					);
+				}
+
+				if let Some((_, preview)) = &mut self.palette_preview {
+					if let Some(clip) = document.intersection(dirty) {
+						preview.render(&mut encoder, &mut self.ctx, &view, document, Some(clip));
+					}
+				}
+
+				if let Some(compare) = &mut self.compare {
+					compare.render(&mut encoder, &mut self.ctx, &view, document, self.canvas.size(), dirty);
+					if let Some(r) = compare.divider_rect(document, self.canvas.size()) {
+						if let Some(clip) = r.intersection(dirty) {
+							self.outline.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+						}
+					}
+				}
+
+				self.refresh_brush_preview();
+				if let Some((_, _, preview)) = &mut self.brush_preview {
+					let size = preview.size();
+					let r = Rect::new(canvas_rect.pos.x + PREVIEW_MARGIN as i32, PREVIEW_MARGIN as i32, size.w, size.h);
+					if let Some(clip) = r.intersection(dirty) {
+						preview.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+					}
+					preview.take_damage();
+				}
+
+				if let (Some(r), Some(mixer)) = (mixer_rect, &mut self.mixer) {
+					if let Some(clip) = r.intersection(dirty) {
+						mixer.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+					}
+					if let Some(clip) = window_rect.intersection(dirty) {
+						self.outline.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+					}
+					mixer.take_damage();
+				}
+
+				if let (Some(r), Some(picker)) = (picker_rect, &mut self.color_picker) {
+					picker.set_color(self.canvas.get_color(ColorSlot::Foreground));
+					if let Some(clip) = r.intersection(dirty) {
+						picker.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+					}
+				}
+
+				if let (Some(r), Some(swatches)) = (swatches_rect, &mut self.swatches) {
+					swatches.set_current(self.canvas.get_color(ColorSlot::Foreground).map(|c| (c * 255.).round() as u8));
+					if let Some(clip) = r.intersection(dirty) {
+						swatches.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+					}
+				}
+
+				let values = CONTROLS.map(|control| self.control_value(control));
+				let (erasing, symmetry) = (self.canvas.is_erasing(), self.symmetry_index());
+				if let Some(controls) = &mut self.controls {
+					for (control, value) in CONTROLS.iter().zip(values) {
+						if let Some(slider) = controls.get_mut::<components::Slider>(NodeId::Slider(*control)) {
+							slider.set_value(value);
+						}
+						if let Some(field) = controls.get_mut::<components::NumberField>(NodeId::Field(*control)) {
+							field.set_value(value);
+						}
+					}
+					if let Some(eraser) = controls.get_mut::<components::Checkbox>(NodeId::Eraser) {
+						eraser.set_checked(erasing);
+					}
+					if let Some(dropdown) = controls.get_mut::<components::Dropdown>(NodeId::Symmetry) {
+						dropdown.set_selected(symmetry);
+					}
+					controls.render(&mut encoder, &mut self.ctx, &view, dirty);
+				}
+
+				if let (Some((panel_rect, trim_rect, clear_rect)), Some(panel)) = (memory_rects, &mut self.memory_panel) {
+					panel.image.render(&mut encoder, &mut self.ctx, &view, panel_rect, panel_rect.intersection(dirty));
+					panel.trim.render(&mut encoder, &mut self.ctx, &view, trim_rect, trim_rect.intersection(dirty));
+					panel.clear_undo.render(&mut encoder, &mut self.ctx, &view, clear_rect, clear_rect.intersection(dirty));
+				}
+				if let (Some(rect), Some(field_rect), Some(panel)) = (layers_rect, layers_field_rect, &mut self.layers_panel) {
+					panel.image.render(&mut encoder, &mut self.ctx, &view, rect, rect.intersection(dirty));
+					for (i, row) in panel.rows.iter().enumerate() {
+						let RowTarget::Layer(layer) = row.target else {
+							continue;
+						};
+						let Some(thumbnail) = self.layer_thumbnails.get_mut(layer) else {
+							continue;
+						};
+						let thumbnail_rect = layerspanel::thumbnail_rect(i, thumbnail.size()) + rect.pos;
+						if let Some(clip) = thumbnail_rect.intersection(dirty) {
+							thumbnail.image().render(&mut encoder, &mut self.ctx, &view, thumbnail_rect, Some(clip));
+						}
+					}
+					panel.field.render(&mut encoder, &mut self.ctx, &view, field_rect, field_rect.intersection(dirty));
+				}
+
+				let toolbar_rect = self.toolbar_rect();
+				let active = self.toolbar_item();
+				self.toolbar.set_active(active);
+				if let Some(clip) = toolbar_rect.intersection(dirty) {
+					self.toolbar.render(&mut encoder, &mut self.ctx, &view, toolbar_rect, Some(clip));
+				}
+
+				if let Some(tour) = &self.tour {
+					let color = self.outline.color;
+					self.outline.color[3] *= tour.highlight_alpha();
+					if let Some(clip) = document.intersection(dirty) {
+						self.outline.render(&mut encoder, &mut self.ctx, &view, document, Some(clip));
+					}
+					self.outline.color = color;
+				}
+
+				self.refresh_hud();
+				if let Some((_, size, image)) = &mut self.hud {
+					let r = Rect::new(window_rect.size.w as i32 - (size.w + HUD_MARGIN) as i32, HUD_MARGIN as i32, size.w, size.h);
+					if let Some(clip) = r.intersection(dirty) {
+						image.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+					}
+				}
+
+				if let Some((_, size, image)) = &mut self.notice {
+					let r = Rect::new((window_rect.size.w as i32 - size.w as i32) / 2, window_rect.size.h as i32 - (size.h + HUD_MARGIN) as i32, size.w, size.h);
+					if let Some(clip) = r.intersection(dirty) {
+						image.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+					}
+				}
+
+				if let Some((menu, image)) = &mut self.radial {
+					let r = menu.rect();
+					if let Some(clip) = r.intersection(dirty) {
+						image.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+					}
+				}
+
+				if let Some((picker, image)) = &mut self.quick_picker {
+					let r = picker.rect();
+					if let Some(clip) = r.intersection(dirty) {
+						image.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+					}
+				}
+
+				if let Some((size, cheatsheet)) = &mut self.cheatsheet {
+					let pos = Point {
+						x: (window_rect.size.w as i32 - size.w as i32) / 2,
+						y: (window_rect.size.h as i32 - size.h as i32) / 2,
+					};
+					let r = Rect { pos, size: *size };
+					if let Some(clip) = r.intersection(dirty) {
+						cheatsheet.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+					}
+				}
+
+				if let Some((palette, field, list)) = &mut self.command_palette {
+					let pos = CommandPalette::position(window_rect.size);
+					let field_rect = CommandPalette::field_rect(pos);
+					if let Some(clip) = field_rect.intersection(dirty) {
+						field.render(&mut encoder, &mut self.ctx, &view, field_rect, Some(clip));
+					}
+					let list_rect = palette.list_rect(pos);
+					if let Some(clip) = list_rect.intersection(dirty) {
+						list.render(&mut encoder, &mut self.ctx, &view, list_rect, Some(clip));
+					}
+				}
+
+				if self.guides_shown {
+					let solid = self.outline.dash;
+					self.outline.dash = GUIDE_DASH;
+					for guide in &self.guides {
+						let r = transform.to_screen_rect(guide.rect(self.canvas.size())) + canvas_rect.pos;
+						if let Some(clip) = r.intersection(canvas_rect).and_then(|c| c.intersection(dirty)) {
+							self.outline.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+						}
+					}
+					self.outline.dash = solid;
+				}
+
+				if let (Some(symmetry), Some(r)) = (self.canvas.symmetry(), self.symmetry_handle()) {
+					let (rays, angle) = symmetry.axes();
+					let half = SYMMETRY_HANDLE as f32 / 2.;
+					self.axes.center = [r.pos.x as f32 + half, r.pos.y as f32 + half];
+					(self.axes.rays, self.axes.angle) = (rays, angle);
+					if let Some(clip) = canvas_rect.intersection(dirty) {
+						self.axes.render(&mut encoder, &mut self.ctx, &view, window_rect, Some(clip));
+					}
+					if let Some(clip) = r.intersection(canvas_rect).and_then(|c| c.intersection(dirty)) {
+						self.outline.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+					}
+				}
+
+				if let Some(selection) = self.canvas.selection() {
+					let r = transform.to_screen_rect(selection) + canvas_rect.pos;
+					if let Some(clip) = r.intersection(canvas_rect).and_then(|c| c.intersection(dirty)) {
+						let solid = (self.outline.dash, self.outline.phase);
+						self.outline.dash = ANTS_DASH;
+						self.outline.phase = self.ants.map_or(0, |(_, step)| step) as f32;
+						self.outline.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+						(self.outline.dash, self.outline.phase) = solid;
+					}
+				}
+
+				if let Some((changed, fade)) = &self.undo_highlight {
+					let r = transform.to_screen_rect(*changed) + canvas_rect.pos;
+					if let Some(clip) = r.intersection(canvas_rect).and_then(|c| c.intersection(dirty)) {
+						let color = self.outline.color;
+						let t = fade.value();
+						self.outline.color[3] *= t * (0.6 + 0.4 * (t * UNDO_PULSES * std::f32::consts::TAU).cos());
+						self.outline.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+						self.outline.color = color;
+					}
+					if fade.finished() {
+						self.undo_highlight = None;
+					}
+				}
+
+				self.refresh_tool_preview();
+				if let (Some((r, _)), Some((_, image))) = (self.tool.as_ref().and_then(|t| t.preview()), &mut self.tool_preview) {
+					let r = transform.to_screen_rect(r) + canvas_rect.pos;
+					if let Some(clip) = r.intersection(canvas_rect).and_then(|c| c.intersection(dirty)) {
+						image.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+					}
+				}
+
+				if let Some(tool) = &self.tool {
+					for r in tool.overlay() {
+						let r = transform.to_screen_rect(r) + canvas_rect.pos;
+						if let Some(clip) = r.intersection(canvas_rect).and_then(|c| c.intersection(dirty)) {
+							self.outline.render(&mut encoder, &mut self.ctx, &view, r, Some(clip));
+						}
+					}
+				}
+
+				if let Some(document) = self.canvas.take_resize() {
+					self.history.push_resize(ResizeEdit { name: "Resize canvas", document: Some(document) }, self.canvas.size());
+					self.mark_dirty();
+				}
+				self.history.check_document(self.canvas.size());
+				if self.pending_edit.is_some() && self.canvas.is_idle() {
+					let logged = self.canvas.take_logged();
+					if let Some((before, after)) = self.canvas.end_edit(&self.ctx, &mut encoder) {
+						self.history.push(Box::new(BlockEdit { name: self.pending_edit.unwrap(), before, after, logged }));
+						self.idle.schedule(IdleTask::CompressHistory);
+					}
+					self.idle.schedule(IdleTask::TrimPools);
+					self.pending_edit = None;
+				}
+
+				// What ending the edit damaged is drawn next frame
+				let damage = self.canvas.take_damage();
+				if let Some(r) = damage.and_then(|d| (transform.to_screen_rect(d) + canvas_rect.pos).intersection(canvas_rect)) {
+					self.frame.damage(r);
+				}
+				let canvas_size = self.canvas.size();
+				if self.layer_thumbnails.len() != self.canvas.layer_count() || self.layer_thumbnails.iter().any(|t| t.document() != canvas_size) {
+					self.layer_thumbnails = (0..self.canvas.layer_count()).map(|_| self.canvas.create_thumbnail(&mut self.ctx)).collect();
+				}
+				for (damaged, r) in self.canvas.invalidations().take(self.thumbnail_subscription) {
+					for (i, thumbnail) in self.layer_thumbnails.iter_mut().enumerate() {
+						if damaged.covers_layer(self.canvas.layer_id(i)) {
+							thumbnail.invalidate(r);
+						}
+					}
+				}
+				// Strokes leave their thumbnails for later
+				let mut downscaled = false;
+				for (i, thumbnail) in self.layer_thumbnails.iter_mut().enumerate() {
+					if thumbnail.is_dirty() && !self.canvas.is_drawing() {
+						self.canvas.update_thumbnail(&mut encoder, &mut self.ctx, i, thumbnail);
+						downscaled = true;
+					}
+				}
+				if downscaled {
+					self.damage_layer_thumbnails();
+				}
+
+				self.frame.render(&mut encoder, &mut self.ctx, &surface_view, window_rect, None);
 
 				self.ctx.staging_belt.finish();
-				self.queue.submit(std::iter::once(encoder.finish()));
+				self.ctx.queue.submit(std::iter::once(encoder.finish()));
 				self.ctx.staging_belt.recall();
-				output.present();
+				self.ctx.buffer_pool.reset();
+				self.ctx.uniform_pool.reset();
+				present(output, drawn);
+
+				// Exports are written on their own thread, the others are checked again next frame
+				let ctx = &self.ctx;
+				let mut written = Vec::new();
+				self.pending_exports.retain_mut(|export| match export.poll(ctx) {
+					Some(result) => {
+						written.push((export.path.clone(), result));
+						false
+					}
+					None => true,
+				});
+				for (path, result) in written {
+					self.finish_export(&path, result);
+				}
+				self.feed_live_preview();
+				if self.live_preview.as_ref().is_some_and(LiveSource::is_waiting) {
+					self.window.request_redraw();
+				}
+				self.refresh_palette_preview();
+				if self.palette_preview.as_ref().is_some_and(|(p, _)| p.is_waiting()) || self.saving.is_some() {
+					self.window.request_redraw();
+				}
+
+				let preview_drawing = self.brush_preview.as_ref().is_some_and(|(_, _, p)| !p.is_idle());
+				if !self.pending_exports.is_empty() || preview_drawing || self.undo_highlight.is_some() || self.tour.as_ref().is_some_and(Tour::is_animating) {
+					self.frame.damage_all();
+					self.window.request_redraw();
+				}
+
+				// Cells over budget are downscaled once idle
+				if self.layer_thumbnails.iter().any(|t| t.is_dirty()) {
+					self.idle.schedule(IdleTask::RefreshThumbnails);
+				}
 			}
 		}
 	}
 
 	fn update(
 		&mut self,
-		_: &EventLoopWindowTarget<CustomEvents>,
+		event_loop: &EventLoopWindowTarget<CustomEvents>,
 	) -> (WindowLifeStatus, Option<Box<dyn Layout>>) {
 		use WindowLifeStatus::*;
 
+		if self.gpu_faults.lost.load(Ordering::Relaxed) {
+			self.recover_device();
+		}
+		if self.gpu_faults.out_of_memory.swap(false, Ordering::Relaxed) {
+			self.out_of_memory();
+		}
+		if self.notice.as_ref().is_some_and(|(n, _, _)| n.expired()) {
+			self.notice = None;
+			self.frame.damage_all();
+			self.window.request_redraw();
+		}
+
 		if self.resized {
 			self.resized = false;
-			let new_size = self.window().inner_size();
-			if new_size.width <= 0 || new_size.height <= 0 {
-				return (Alive, None);
+			self.resize(self.window().inner_size());
+		}
+
+		if self.autosave.due() && self.pending_edit.is_none() && self.canvas.is_idle() {
+			self.start_autosave();
+		}
+		// Between strokes, rather than every frame of one
+		if self.canvas.is_idle() {
+			self.canvas.update_backup(&mut self.ctx);
+		}
+		if self.links_polled.elapsed() >= LINK_POLL_INTERVAL {
+			self.links_polled = Instant::now();
+			self.refresh_linked_layers(false);
+		}
+		self.autosave.poll(&self.ctx);
+		if let Some(result) = self.saving.as_mut().and_then(|s| s.poll(&self.ctx)) {
+			let path = self.saving.take().unwrap().path;
+			self.finish_save(&path, result);
+		}
+
+		if let Some(assets) = &mut self.assets {
+			for kind in assets.poll() {
+				log::info!("{} {} available", assets.files(kind).count(), kind.dir_name());
+			}
+		}
+
+		if self.preferences_generation != preferences::generation() {
+			self.apply_preferences();
+		}
+		if self.open_preferences {
+			self.open_preferences = false;
+			if PreferencesWindow::is_open() {
+				log::info!("The preferences are open already");
+			} else {
+				match Window::new(event_loop) {
+					Ok(window) => {
+						let layout: Box<dyn Layout> = pollster::block_on(PreferencesWindow::new(PreferencesWindow::init(), Arc::new(window)));
+						return (Alive, Some(layout));
+					}
+					Err(e) => log::error!("Could not open the preferences window: {e}"),
+				}
+			}
+		}
+		if self.open_live_preview {
+			self.open_live_preview = false;
+			if self.live_preview.as_ref().is_some_and(LiveSource::is_open) {
+				log::info!("The live preview is open already");
+			} else {
+				match Window::new(event_loop) {
+					Ok(window) => {
+						let (source, feed) = LiveSource::new(self.canvas.invalidations().subscribe());
+						self.live_preview = Some(source);
+						let layout: Box<dyn Layout> = pollster::block_on(LivePreviewWindow::open(feed, Arc::new(window)));
+						return (Alive, Some(layout));
+					}
+					Err(e) => log::error!("Could not open the live preview window: {e}"),
+				}
 			}
+		}
 
-			self.size = new_size;
-			self.config.width = new_size.width;
-			self.config.height = new_size.height;
-			self.surface.configure(&self.ctx.device, &self.config);
+		let minutes = self.session.total().as_secs() / 60;
+		if self.title_minutes != Some(minutes) {
+			self.title_minutes = Some(minutes);
+			self.update_title();
 		}
 
+		if let Some(dialog) = &mut self.close_dialog {
+			if let std::task::Poll::Ready(answer) = dialog.poll() {
+				let waker = dialog.waker();
+				self.close_dialog = None;
+				self.answer_close(answer, waker);
+			}
+		}
 		if self.close {
 			self.close = false;
 			return (Dead, None);
 		}
 
+		if !self.dropped.is_empty() {
+			self.place_dropped();
+		}
+		if let Some(dialog) = &mut self.file_dialog {
+			if let std::task::Poll::Ready(picked) = dialog.poll() {
+				let purpose = dialog.purpose;
+				self.file_dialog = None;
+				match picked {
+					Some(path) => self.use_picked_file(purpose, path),
+					None => self.close_after_save = false,
+				}
+			}
+		}
+		if !self.open_in_windows.is_empty() {
+			let path = self.open_in_windows.remove(0);
+			match Window::new(event_loop) {
+				Ok(window) => {
+					let mut layout_ctx = Self::init();
+					layout_ctx.open = Some(path);
+					let layout: Box<dyn Layout> = pollster::block_on(Self::new(layout_ctx, Arc::new(window)));
+					return (Alive, Some(layout));
+				}
+				Err(e) => log::error!("Could not open a window for {}: {e}", path.display()),
+			}
+		}
+
 		(Alive, None)
 	}
 
+	fn idle(&mut self, frame_limiter: &FrameLimiter) {
+		// Strokes still being drawn come first, the next input asks again
+		if self.pending_edit.is_some() || !self.canvas.is_idle() || self.idle.is_empty() {
+			return;
+		}
+		let mut scheduler = std::mem::replace(&mut self.idle, IdleScheduler::new());
+		let more = scheduler.run(idle::SLICE_BUDGET, |task| self.idle_step(task));
+		self.idle = scheduler;
+		// Shows what it freed
+		if self.memory_panel.is_some() {
+			self.window.request_redraw();
+		}
+		if more {
+			frame_limiter.request_idle(self.window.id());
+		}
+	}
+
 	fn event_handler(&mut self, event: winit::event::WindowEvent, frame_limiter: &FrameLimiter) {
 		use WindowEvent::*;
 
-		match event {
-			CloseRequested => self.close = true,
+		frame_limiter.request_idle(self.window.id());
+		if self.redraw_timer.is_none() {
+			self.redraw_timer = Some(frame_limiter.timer(self.window.id()));
+		}
+		let device = self.input.device();
+		self.input.handle_event(&event);
+		if self.input.device() != device {
+			self.switch_device(device);
+		}
+		if self.command_palette_input(&event, frame_limiter) {
+			return;
+		}
+		self.follow_eraser_end();
 
-			Resized(_) => {
-				self.resized = true;
+		match event {
+			KeyboardInput { .. } | MouseInput { .. } | MouseWheel { .. } | CursorMoved { .. } => self.session.activity(),
+			Focused(false) => self.session.pause(),
+			_ => (),
+		}
+
+		match event {
+			CloseRequested => self.request_close(frame_limiter),
+
+			// Reconfigure and redraw right away, otherwise the compositor stretches the old frame
+			// while the user drags the window edge
+			Resized(new_size) => {
+				self.resize(new_size);
+				self.render();
+			}
+
+			ScaleFactorChanged { new_inner_size, .. } => {
+				self.resize(*new_inner_size);
+				self.render();
+			}
+
+			DroppedFile(path) => self.dropped.push(path),
+
+			// Held rather than bound, so it's out of the registry
+			KeyboardInput {
+				input:
+					winit::event::KeyboardInput {
+						state,
+						virtual_keycode: Some(VirtualKeyCode::F1),
+						..
+					},
+				..
+			} if self.modifiers.is_empty() => {
+				self.show_cheatsheet(state == winit::event::ElementState::Pressed);
+				self.redraw(frame_limiter);
+			}
+
+			// Moves the focus between widgets once one has it, Shift+Tab gives it to the last one
+			KeyboardInput {
+				input:
+					winit::event::KeyboardInput {
+						state,
+						virtual_keycode: Some(VirtualKeyCode::Tab),
+						..
+					},
+				..
+			} if self.radial.is_none() && !self.hud_shown() && (self.modifiers == ModifiersState::SHIFT || self.route_widgets(|router, widgets, _| router.has_focus(widgets))) => {
+				if state == winit::event::ElementState::Pressed {
+					let reverse = self.modifiers.shift();
+					self.route_widgets(|router, widgets, ctx| router.cycle_focus(ctx, widgets, reverse));
+				}
+				self.redraw(frame_limiter);
+			}
+
+			// Held rather than bound, released over the action to run
+			KeyboardInput {
+				input:
+					winit::event::KeyboardInput {
+						state,
+						virtual_keycode: Some(VirtualKeyCode::Tab),
+						..
+					},
+				..
+			} if self.modifiers.is_empty() && !self.hud_shown() => {
+				self.hold_radial_menu(state == winit::event::ElementState::Pressed, frame_limiter);
+				self.redraw(frame_limiter);
+			}
+
+			MouseInput {
+				state,
+				button: winit::event::MouseButton::Right,
+				..
+			} => {
+				self.hold_radial_menu(state == winit::event::ElementState::Pressed, frame_limiter);
+				self.redraw(frame_limiter);
+			}
+
+			// Held rather than bound, dragging with it pans the view
+			KeyboardInput {
+				input:
+					winit::event::KeyboardInput {
+						state,
+						virtual_keycode: Some(VirtualKeyCode::Space),
+						..
+					},
+				..
+			} => {
+				self.space_held = state == winit::event::ElementState::Pressed;
+				if !self.space_held {
+					self.panning = None;
+					// Presses the focused button or checkbox too
+					if self.modifiers.is_empty() && self.route_widgets(|router, widgets, ctx| router.key_input(ctx, widgets, VirtualKeyCode::Space)) {
+						self.redraw(frame_limiter);
+					}
+				}
 			}
 
 			KeyboardInput {
@@ -239,32 +1597,216 @@ impl Layout for DrawingWindow {
 					},
 				..
 			} => {
-				let mut redraw = true;
-				match letter {
-					winit::event::VirtualKeyCode::C => self.canvas.clear(),
-					_ => redraw = false,
+				// Enter renames the layer the layers panel field is open for and leaves the field, Escape
+				// empties the field, the router takes the keyboard away from it
+				if self.widget_router.focused() == Some(NodeId::LayersField) && matches!(letter, VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter | VirtualKeyCode::Escape) {
+					let entered = letter != VirtualKeyCode::Escape;
+					self.end_layer_rename(entered);
+					if let Some(panel) = self.layers_panel.as_mut().filter(|_| !entered) {
+						panel.field.set_text("");
+					}
+					self.route_widgets(|router, widgets, ctx| router.focus(ctx, widgets, None));
+					self.redraw(frame_limiter);
+					return;
+				}
+				// Capitals are typed into it too
+				if self.widget_router.focused() == Some(NodeId::LayersField) && (self.modifiers - ModifiersState::SHIFT).is_empty() {
+					return;
+				}
+				if self.modifiers.is_empty() && self.route_widgets(|router, widgets, ctx| router.key_input(ctx, widgets, letter)) {
+					self.redraw(frame_limiter);
+					return;
+				}
+				// Typed into the HUD instead, see ReceivedCharacter
+				if self.hud_shown() && self.modifiers.is_empty() && hud::is_input_key(letter) {
+					return;
+				}
+				let typing = self.tool.as_ref().is_some_and(|t| t.captures_keys());
+				if typing && (self.modifiers - ModifiersState::SHIFT).is_empty() && !matches!(letter, VirtualKeyCode::Return | VirtualKeyCode::Escape) {
+					return;
+				}
+				if let Some(action) = self.actions.lookup(KeyBinding::new(letter, self.modifiers)) {
+					self.run_action(action, frame_limiter);
 				}
-				if redraw {
-					frame_limiter.schedule_redraw(self.window().id());
+			}
+
+			ModifiersChanged(modifiers) => self.modifiers = modifiers,
+
+			ReceivedCharacter(c) => {
+				if self.route_widgets(|router, widgets, ctx| router.text_input(ctx, widgets, c)) {
+					self.redraw(frame_limiter);
+					return;
+				}
+				if let Some(tool) = &mut self.tool {
+					if tool.text_input(c, &mut self.ctx) {
+						self.redraw(frame_limiter);
+					}
 				}
 			}
 
+			MouseInput {
+				state,
+				button: winit::event::MouseButton::Middle,
+				..
+			} => {
+				self.panning = (state == winit::event::ElementState::Pressed).then_some(*self.input.get_mouse_absolute()).flatten();
+			}
+
+			MouseInput {
+				state,
+				button: winit::event::MouseButton::Left,
+				..
+			} if self.quick_picker.is_some() => {
+				self.quick_picker_input(state, frame_limiter);
+				self.redraw(frame_limiter);
+			}
+
+			MouseInput {
+				state,
+				button: winit::event::MouseButton::Left,
+				..
+			} if self.space_held && !self.mixing() && !self.canvas.is_drawing() => {
+				self.panning = (state == winit::event::ElementState::Pressed).then_some(*self.input.get_mouse_absolute()).flatten();
+			}
+
+			MouseWheel { delta, .. } => {
+				use winit::event::MouseScrollDelta;
+				let lines = match delta {
+					MouseScrollDelta::LineDelta(_, y) => y,
+					MouseScrollDelta::PixelDelta(p) => p.y as f32 / PIXELS_PER_SCROLL_LINE,
+				};
+				let rect = self.canvas_rect();
+				let anchor = self.input.get_mouse_absolute().map_or(Point { x: rect.size.w as i32 / 2, y: rect.size.h as i32 / 2 }, |p| p - rect.pos);
+				let mut view = self.canvas.view();
+				view.zoom(ZOOM_STEP.powf(lines), anchor);
+				self.canvas.set_view(view);
+				self.redraw(frame_limiter);
+			}
+
 			MouseInput {
 				state,
 				button: winit::event::MouseButton::Left,
 				..
 			} => {
 				use winit::event::ElementState;
+				if self.widget_input(state) {
+					self.redraw(frame_limiter);
+					return;
+				}
+				let point = self.input.get_mouse_relative(self.canvas_rect(), self.canvas.view());
+				if let Some(tool) = &mut self.tool {
+					let used = match (state, point) {
+						(ElementState::Pressed, Some(p)) => tool.mouse_down(p, &mut self.canvas, &mut self.ctx),
+						(ElementState::Released, _) => {
+							if tool.edits_on_release() {
+								if self.layers.active().can_paint() {
+									self.pending_edit.get_or_insert(tool.name());
+									self.canvas.begin_edit();
+								} else {
+									log::warn!("Layer {} is locked, can't {}", self.layers.active().name, tool.name());
+									tool.cancel(&mut self.canvas);
+								}
+							}
+							tool.mouse_up(&mut self.canvas, &mut self.ctx);
+							false
+						}
+						_ => true,
+					};
+					let paints_through = tool.paints_through();
+					self.redraw(frame_limiter);
+					if used || !paints_through {
+						return;
+					}
+				}
+
 				match state {
-					ElementState::Pressed => self.canvas.mouse_down(),
+					ElementState::Pressed => {
+						// Released buttons always end the stroke, even once the layer is locked
+						if !self.layer_allows(self.layers.active().can_paint(), "paint") {
+							return;
+						}
+						self.tour_event(TourEvent::Stroke);
+						self.begin_edit("Stroke");
+						self.remember_color();
+						self.canvas.mouse_down();
+					}
 					ElementState::Released => self.canvas.mouse_up(),
 				}
+				self.redraw(frame_limiter);
+			}
+
+			Touch(touch) => {
+				if self.touch_rejected(touch.device_id) || !self.device_settings[&self.input.device()].touch_paints {
+					return;
+				}
+				if touch.phase == TouchPhase::Started {
+					if !self.layer_allows(self.layers.active().can_paint(), "paint") {
+						return;
+					}
+					self.tour_event(TourEvent::Stroke);
+					self.begin_edit("Stroke");
+					self.remember_color();
+					self.canvas.mouse_pos(self.document_point(touch.location.into()), self.input.pressure());
+					self.canvas.mouse_down();
+				}
+				self.canvas.mouse_pos(self.document_point(touch.location.into()), self.input.pressure());
+				if matches!(touch.phase, TouchPhase::Ended | TouchPhase::Cancelled) {
+					self.canvas.mouse_up();
+				}
+				// Only where the stroke lands is drawn again
 				frame_limiter.schedule_redraw(self.window().id());
 			}
 
 			CursorMoved { position, .. } => {
-				// TODO: Don't redraw window if no line was drawn
-				self.canvas.mouse_pos(position.into());
+				if let Some((menu, _)) = &mut self.radial {
+					if menu.hover(position.into()) {
+						self.refresh_radial_menu();
+						self.redraw(frame_limiter);
+					}
+					return;
+				}
+				if let Some((picker, _)) = &mut self.quick_picker {
+					let p = Point::from(position);
+					let color = picker.drag(p);
+					if let Some(color) = color {
+						self.canvas.set_color(ColorSlot::Foreground, color);
+					}
+					if picker.hover(p) || color.is_some() {
+						self.refresh_quick_picker();
+						self.redraw(frame_limiter);
+					}
+					return;
+				}
+				if let Some(last) = self.panning {
+					let p = Point::from(position);
+					let mut view = self.canvas.view();
+					view.pan(p - last);
+					self.canvas.set_view(view);
+					self.panning = Some(p);
+					self.redraw(frame_limiter);
+					return;
+				}
+				if self.route_widgets(|router, widgets, ctx| router.drag(ctx, widgets, position.into())) {
+					self.handle_events();
+					self.redraw(frame_limiter);
+					return;
+				}
+				if self.route_widgets(|router, widgets, _| router.hover(widgets, position.into())) {
+					self.redraw(frame_limiter);
+				}
+				let point = self.document_point(position.into());
+				if let Some(tool) = &mut self.tool {
+					if tool.mouse_move(point, &mut self.canvas, &mut self.ctx) {
+						self.redraw(frame_limiter);
+						return;
+					}
+					if !tool.paints_through() {
+						return;
+					}
+				}
+
+				// Only where the stroke lands is drawn again, nothing when no line was drawn
+				self.canvas.mouse_pos(point, self.input.pressure());
 				frame_limiter.schedule_redraw(self.window().id());
 			}
 
@@ -272,3 +1814,2514 @@ impl Layout for DrawingWindow {
 		}
 	}
 }
+
+impl DrawingWindow {
+	/// Does a bit of `task`, returns whether there is more.
+	fn idle_step(&mut self, task: IdleTask) -> bool {
+		match task {
+			IdleTask::CompressHistory => return self.history.pack_oldest(&self.ctx),
+			IdleTask::RefreshThumbnails => {
+				let mut encoder = self.ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+					label: Some("DrawingWindow(Thumbnail Encoder)"),
+				});
+				for (i, thumbnail) in self.layer_thumbnails.iter_mut().enumerate() {
+					if thumbnail.is_dirty() {
+						self.canvas.update_thumbnail(&mut encoder, &mut self.ctx, i, thumbnail);
+					}
+				}
+				self.ctx.queue.submit(std::iter::once(encoder.finish()));
+				// So the slice budget covers the GPU time too
+				self.ctx.device.poll(wgpu::Maintain::Wait);
+				self.damage_layer_thumbnails();
+				return self.layer_thumbnails.iter().any(|t| t.is_dirty());
+			}
+			IdleTask::TrimPools => {
+				self.ctx.trim_pools();
+				return false;
+			}
+			IdleTask::WarmPipelines => {
+				return self.ctx.warm_pipelines::<components::ColorPicker>()
+					|| self.ctx.warm_pipelines::<components::Swatches>()
+					|| self.ctx.warm_pipelines::<components::Image>();
+			}
+		}
+	}
+
+	/// Frees what can be made again and saves a recovery copy of the document, the first time. Running
+	/// out again closes the window, leaving the copy to be restored on the next start.
+	fn out_of_memory(&mut self) {
+		if self.low_memory {
+			log::error!("Out of GPU memory again, closing window");
+			self.close = true;
+			return;
+		}
+
+		log::error!("Out of GPU memory, releasing caches");
+		self.low_memory = true;
+		self.history.clear();
+		self.ctx.release_caches();
+		self.update_title();
+
+		let saving = self.autosave.is_enabled() && self.pending_edit.is_none() && self.canvas.is_idle();
+		if saving {
+			self.start_autosave();
+		}
+		self.notify(&format!(
+			"Running out of GPU memory, undo history was cleared.\n{}\nSave with Ctrl+Shift+S before doing more.",
+			if saving { "A recovery copy of the document is being saved." } else { "No recovery copy could be saved." },
+		));
+	}
+
+	/// Shows `text` at the bottom of the window for a while.
+	fn notify(&mut self, text: &str) {
+		let notice = Notice::new(text);
+		let pixels = notice.render();
+		let tex = self.ctx.upload_texture("Notice(Texture)", &pixels, wgpu::TextureUsages::STORAGE_BINDING);
+		let mut image = components::Image::new(&mut self.ctx);
+		image.set_texture(&self.ctx, tex);
+		self.notice = Some((notice, pixels.size, image));
+		self.frame.damage_all();
+		self.window.request_redraw();
+	}
+
+	fn update_title(&self) {
+		let mut title = String::from(if self.dirty { "*pntr" } else { "pntr" });
+		if self.document_tag != ColorTag::None {
+			title += &format!(" [{}]", self.document_tag.name());
+		}
+		if let Some(name) = self.document_path.as_ref().and_then(|p| p.file_name()) {
+			title += &format!(" - {}", name.to_string_lossy());
+		}
+		title += &format!(" - {} - {}", self.layers.active().name, session::format_duration(self.session.total()));
+		if let Some(tour) = &self.tour {
+			title += &format!(" - {}", tour.text());
+		}
+		if self.canvas.editing_mask() && self.canvas.has_layer_mask(self.canvas.active_layer()) {
+			title += " - Mask";
+		}
+		if self.low_memory {
+			title += " - Low on GPU memory";
+		}
+		if let Some(profile) = self.profile {
+			title += &format!(" - {} workspace", self.profiles[profile].name);
+		}
+		if self.recovered {
+			title += " - Recovered from a GPU reset";
+		}
+		self.window.set_title(&title);
+	}
+
+	/// Marks the window with `tag` in its title and icon, where the platform shows window icons.
+	fn set_document_tag(&mut self, tag: ColorTag) {
+		self.document_tag = tag;
+		self.window.set_window_icon(tag.color().map(tag_icon));
+		self.update_title();
+	}
+
+	/// Notes that the document changed since it was saved.
+	fn mark_dirty(&mut self) {
+		self.autosave.changed();
+		self.edited_while_saving |= self.saving.is_some();
+		if !std::mem::replace(&mut self.dirty, true) {
+			self.update_title();
+		}
+	}
+
+	/// Closes the window, once asked whether to save the document when it has unsaved changes.
+	fn request_close(&mut self, frame_limiter: &FrameLimiter) {
+		if !self.dirty {
+			self.autosave.discard();
+			self.close = true;
+			return;
+		}
+		if self.close_dialog.is_some() {
+			return;
+		}
+		let name = self.document_path.as_ref().and_then(|p| p.file_name()).map_or("the document".into(), |n| n.to_string_lossy());
+		self.close_dialog = Some(SaveChangesDialog::show(&self.window, &name, frame_limiter.waker(self.window.id())));
+	}
+
+	/// Saves, closes or keeps the window open, as answered in the dialog shown by `request_close`.
+	fn answer_close(&mut self, answer: rfd::MessageDialogResult, waker: std::task::Waker) {
+		match answer {
+			rfd::MessageDialogResult::Yes if self.document_path.is_some() => {
+				self.close_after_save = true;
+				self.save_document();
+			}
+			rfd::MessageDialogResult::Yes if self.file_dialog.is_none() => {
+				self.close_after_save = true;
+				self.file_dialog = Some(FileDialog::show(FilePurpose::SaveDocument, &self.window, Some(std::path::Path::new("untitled.pntr")), waker));
+			}
+			rfd::MessageDialogResult::No => self.close = true,
+			_ => (),
+		}
+		if self.close {
+			self.autosave.discard();
+		}
+	}
+
+	/// Schedules drawing all of the window again.
+	fn redraw(&mut self, frame_limiter: &FrameLimiter) {
+		self.frame.damage_all();
+		frame_limiter.schedule_redraw(self.window.id());
+	}
+
+	fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+		if new_size.width == 0 || new_size.height == 0 {
+			return;
+		}
+
+		self.size = new_size;
+		self.config.width = new_size.width;
+		self.config.height = new_size.height;
+		self.surface.configure(&self.ctx.device, &self.config);
+		self.layout_window();
+	}
+
+	/// Places the window tree in the window, and the panels in the canvas rect it gives.
+	fn layout_window(&mut self) {
+		self.window_tree.layout(Rect::new(0, 0, self.size.width, self.size.height));
+		self.layout_controls();
+	}
+
+	/// Where the canvas is drawn, right of the toolbar.
+	fn canvas_rect(&self) -> Rect {
+		return self.window_tree.rect(NodeId::Canvas);
+	}
+
+	fn toolbar_rect(&self) -> Rect {
+		return self.window_tree.rect(NodeId::Toolbar);
+	}
+
+	/// Toolbar button of what strokes and presses do now.
+	fn toolbar_item(&self) -> Option<components::ToolbarItem> {
+		use components::ToolbarItem;
+		return match self.tool.as_ref().map(|t| t.name()) {
+			None if self.canvas.is_erasing() => Some(ToolbarItem::Eraser),
+			None => Some(ToolbarItem::Brush),
+			Some("Fill") => Some(ToolbarItem::Fill),
+			Some("Select") => Some(ToolbarItem::Select),
+			Some(_) => None,
+		};
+	}
+
+	/// Switches to the tool of a toolbar button, like its action does.
+	fn pick_tool(&mut self, item: components::ToolbarItem) {
+		use components::ToolbarItem;
+		match item {
+			ToolbarItem::Brush | ToolbarItem::Eraser => {
+				self.tool = None;
+				self.canvas.set_erasing(item == ToolbarItem::Eraser);
+			}
+			ToolbarItem::Fill => self.toggle_fill(),
+			ToolbarItem::Select => self.toggle_tool(Box::new(tools::RectSelect::new())),
+		}
+	}
+
+	/// Document point shown at the window point `p`.
+	fn document_point(&self, p: Point) -> Point {
+		return self.canvas.view().to_document(p - self.canvas_rect().pos);
+	}
+
+	/// Outlines what an undo or redo changed, centering the view on it when none of it is shown.
+	fn highlight_change(&mut self, changed: Option<Rect>) {
+		let Some(changed) = changed else {
+			return;
+		};
+		self.undo_highlight = Some((changed, Tween::new(1., 0., UNDO_HIGHLIGHT)));
+
+		let viewport = self.canvas_rect();
+		let mut view = self.canvas.view();
+		let shown = Rect { pos: Point { x: 0, y: 0 }, size: viewport.size };
+		if self.follow_undo && !view.to_screen_rect(changed).intersects(shown) {
+			let center = [
+				(changed.pos.x as f32 + changed.size.w as f32 / 2., viewport.size.w as f32),
+				(changed.pos.y as f32 + changed.size.h as f32 / 2., viewport.size.h as f32),
+			];
+			view.offset = center.map(|(c, side)| side / 2. - c * view.scale);
+			self.canvas.set_view(view);
+		}
+	}
+
+	/// Where the mixer is drawn, in the bottom right corner. None while it's hidden.
+	fn mixer_rect(&self) -> Option<Rect> {
+		if !self.mixer_shown {
+			return None;
+		}
+		let offset = (MIXER_SIDE + MIXER_MARGIN) as i32;
+		return Some(Rect::new(self.size.width as i32 - offset, self.size.height as i32 - offset, MIXER_SIDE, MIXER_SIDE));
+	}
+
+	/// Bottom left of the canvas, None while hidden.
+	fn picker_rect(&self) -> Option<Rect> {
+		self.color_picker.as_ref()?;
+		let canvas = self.canvas_rect();
+		let y = canvas.size.h as i32 - (PICKER_SIDE + MIXER_MARGIN) as i32;
+		return Some(Rect::new(canvas.pos.x + MIXER_MARGIN as i32, y, PICKER_SIDE, PICKER_SIDE));
+	}
+
+	/// Bottom middle of the canvas, None while hidden or empty.
+	fn swatches_rect(&self) -> Option<Rect> {
+		let size = self.swatches.as_ref()?.size();
+		let canvas = self.canvas_rect();
+		if size.h == 0 {
+			return None;
+		}
+		let x = canvas.pos.x + (canvas.size.w as i32 - size.w as i32) / 2;
+		let y = canvas.size.h as i32 - (size.h + MIXER_MARGIN) as i32;
+		return Some(Rect::new(x, y, size.w, size.h));
+	}
+
+	/// Keeps the foreground color among the recent swatches, when painting with it.
+	fn remember_color(&mut self) {
+		if self.canvas.is_erasing() {
+			return;
+		}
+		let color = self.canvas.get_color(ColorSlot::Foreground).map(|c| (c * 255.).round() as u8);
+		if self.swatch_set.use_color(color) {
+			self.swatches_changed();
+		}
+	}
+
+	fn new_swatches(&mut self) -> Box<components::Swatches> {
+		let mut swatches = components::Swatches::new(&mut self.ctx);
+		swatches.set_swatches(&self.swatch_set);
+		return swatches;
+	}
+
+	/// Saves the swatches and shows them as they are now.
+	fn swatches_changed(&mut self) {
+		swatches::save_swatches(&self.swatch_set);
+		if let Some(swatches) = &mut self.swatches {
+			swatches.set_swatches(&self.swatch_set);
+		}
+	}
+
+	/// Puts the controls top right of the canvas, as tall as they need.
+	fn layout_controls(&mut self) {
+		let canvas = self.canvas_rect();
+		let Some(controls) = &mut self.controls else {
+			return;
+		};
+		let width = SLIDER_WIDTH + FIELD_WIDTH + 3 * CONTROL_GAP;
+		let height = controls.min_size().h;
+		controls.layout(Rect::new(canvas.pos.x + canvas.size.w as i32 - width as i32, 0, width, height));
+	}
+
+	/// A slider and the field next to it for each control, the actual size button and eraser
+	/// checkbox, then the symmetry dropdown.
+	fn new_controls(&mut self) -> Tree<NodeId> {
+		let mut rows: Vec<_> = CONTROLS.iter().map(|control| {
+			let (mut slider, mut field) = (components::Slider::new(&mut self.ctx), components::NumberField::new(&mut self.ctx));
+			slider.set_control(*control);
+			field.set_control(*control);
+			let row = Node::row(CONTROL_GAP, vec![
+				(Length::Flex(1), Node::Element(NodeId::Slider(*control), slider)),
+				(Length::Fixed(FIELD_WIDTH), Node::Element(NodeId::Field(*control), field)),
+			]);
+			(Length::Fixed(CONTROL_HEIGHT), row)
+		}).collect();
+		let mut actual_size = components::Button::new(&mut self.ctx);
+		actual_size.set_label("100%", "view.actual_size");
+		let mut eraser = components::Checkbox::new(&mut self.ctx);
+		eraser.set_label("Eraser", "brush.eraser");
+		rows.push((Length::Fixed(CONTROL_HEIGHT), Node::row(CONTROL_GAP, vec![
+			(Length::Flex(1), Node::Element(NodeId::ActualSize, actual_size)),
+			(Length::Flex(1), Node::Element(NodeId::Eraser, eraser)),
+		])));
+		let mut symmetry = components::Dropdown::new(&mut self.ctx);
+		symmetry.set_options(&SYMMETRY_OPTIONS, "symmetry.mode");
+		rows.push((Length::Fixed(CONTROL_HEIGHT), Node::Element(NodeId::Symmetry, symmetry)));
+		return Tree::new(Node::column(CONTROL_GAP, rows).padded(CONTROL_GAP));
+	}
+
+	fn memory_usage(&self) -> components::MemoryUsage {
+		let mut usage = self.canvas.memory();
+		usage.undo += self.history.bytes();
+		usage.thumbnails = self.layer_thumbnails.iter().map(components::LayerThumbnail::bytes).sum();
+		usage.atlases = self.ctx.atlas_bytes();
+		usage.scratch = self.mixer.iter().chain(self.brush_preview.as_ref().map(|(_, _, p)| p)).map(|c| c.memory().total()).sum();
+		return usage;
+	}
+
+	fn new_memory_panel(&mut self) -> MemoryPanel {
+		let mut trim = components::Button::new(&mut self.ctx);
+		trim.set_label("Trim caches", "memory.trim");
+		let mut clear_undo = components::Button::new(&mut self.ctx);
+		clear_undo.set_label("Clear undo", "memory.clear_undo");
+		return MemoryPanel { image: components::Image::new(&mut self.ctx), lines: Vec::new(), size: Size { w: 0, h: 0 }, trim, clear_undo };
+	}
+
+	/// Draws the memory panel again when the usage it shows changed.
+	fn refresh_memory_panel(&mut self) {
+		if self.memory_panel.is_none() {
+			return;
+		}
+		let lines = memory::lines(&self.memory_usage(), self.ctx.tier);
+		let Some(panel) = &mut self.memory_panel else {
+			return;
+		};
+		if lines == panel.lines {
+			return;
+		}
+		let pixels = memory::render(&lines, MEMORY_PANEL_WIDTH, CONTROL_HEIGHT + CONTROL_GAP);
+		let tex = self.ctx.upload_texture("MemoryPanel(Texture)", &pixels, wgpu::TextureUsages::STORAGE_BINDING);
+		panel.image.set_texture(&self.ctx, tex);
+		(panel.lines, panel.size) = (lines, pixels.size);
+		// Strokes change it too, not only what already damages everything
+		self.frame.damage_all();
+	}
+
+	/// Top middle of the canvas, the panel with the trim and clear undo buttons along its bottom.
+	fn memory_rects(&self) -> Option<(Rect, Rect, Rect)> {
+		let panel = self.memory_panel.as_ref()?;
+		let canvas = self.canvas_rect();
+		let rect = Rect::new(canvas.pos.x + (canvas.size.w as i32 - panel.size.w as i32) / 2, HUD_MARGIN as i32, panel.size.w, panel.size.h);
+		let half = panel.size.w.saturating_sub(3 * CONTROL_GAP) / 2;
+		let y = rect.pos.y + rect.size.h as i32 - (CONTROL_HEIGHT + CONTROL_GAP) as i32;
+		let trim = Rect::new(rect.pos.x + CONTROL_GAP as i32, y, half, CONTROL_HEIGHT);
+		let clear = Rect::new(rect.pos.x + (2 * CONTROL_GAP + half) as i32, y, half, CONTROL_HEIGHT);
+		return Some((rect, trim, clear));
+	}
+
+	fn new_layers_panel(&mut self) -> LayersPanel {
+		let mut field = components::TextField::new(&mut self.ctx);
+		field.set_placeholder(LAYERS_SEARCH);
+		return LayersPanel { image: components::Image::new(&mut self.ctx), rows: Vec::new(), size: Size { w: 0, h: 0 }, field, renaming: None };
+	}
+
+	/// Draws the layers panel again when the rows it shows changed. A rename is dropped once its field
+	/// lost the keyboard.
+	fn refresh_layers_panel(&mut self) {
+		if self.layers_panel.as_ref().is_some_and(|p| p.renaming.is_some()) && self.widget_router.focused() != Some(NodeId::LayersField) {
+			self.end_layer_rename(false);
+		}
+		let Some(panel) = &mut self.layers_panel else {
+			return;
+		};
+		let shown = self.layers.filter(if panel.renaming.is_some() { "" } else { panel.field.text() });
+		let rows = layerspanel::rows(&self.layers, &shown);
+		if rows == panel.rows {
+			return;
+		}
+		let pixels = layerspanel::render(&rows, LAYERS_PANEL_WIDTH);
+		let tex = self.ctx.upload_texture("LayersPanel(Texture)", &pixels, wgpu::TextureUsages::STORAGE_BINDING);
+		panel.image.set_texture(&self.ctx, tex);
+		(panel.rows, panel.size) = (rows, pixels.size);
+		self.frame.damage_all();
+	}
+
+	/// Draws the layers panel again next frame, to show thumbnails just downscaled.
+	fn damage_layer_thumbnails(&mut self) {
+		if let Some(rect) = self.layers_panel_rect() {
+			self.frame.damage(rect);
+			self.window.request_redraw();
+		}
+	}
+
+	/// Left middle of the canvas, above the color picker.
+	fn layers_panel_rect(&self) -> Option<Rect> {
+		let panel = self.layers_panel.as_ref()?;
+		let canvas = self.canvas_rect();
+		let y = canvas.pos.y + (canvas.size.h as i32 - panel.size.h as i32) / 2;
+		return Some(Rect::new(canvas.pos.x + HUD_MARGIN as i32, y, panel.size.w, panel.size.h));
+	}
+
+	/// Above the layers panel, as wide.
+	fn layers_field_rect(&self) -> Option<Rect> {
+		let panel = self.layers_panel_rect()?;
+		return Some(Rect::new(panel.pos.x, panel.pos.y - (CONTROL_HEIGHT + CONTROL_GAP) as i32, panel.size.w, CONTROL_HEIGHT));
+	}
+
+	/// Opens the layers panel with the name of the active layer in its field, renamed on Enter.
+	fn start_layer_rename(&mut self) {
+		if self.layers_panel.is_none() {
+			self.layers_panel = Some(self.new_layers_panel());
+		}
+		let index = self.layers.active;
+		let name = self.layers.active().name.clone();
+		let panel = self.layers_panel.as_mut().unwrap();
+		panel.renaming = Some(index);
+		panel.field.set_text(&name);
+		panel.field.set_placeholder("Layer name");
+		self.route_widgets(|router, widgets, ctx| router.focus(ctx, widgets, Some(NodeId::LayersField)));
+	}
+
+	/// Renames the layer the layers panel field was opened for to what was typed when `apply`, the
+	/// field searches again after.
+	fn end_layer_rename(&mut self, apply: bool) {
+		let Some(panel) = &mut self.layers_panel else {
+			return;
+		};
+		let Some(index) = panel.renaming.take() else {
+			return;
+		};
+		let name = panel.field.text().trim().to_owned();
+		panel.field.set_text("");
+		panel.field.set_placeholder(LAYERS_SEARCH);
+		// Deleted in the meantime
+		if !apply || name.is_empty() || index >= self.layers.layers.len() {
+			return;
+		}
+		log::info!("Layer {} renamed {name}", self.layers.layers[index].name);
+		self.layers.rename(index, &name);
+		self.mark_dirty();
+		self.update_title();
+	}
+
+	/// A click on the row `index` of the layers panel activates its layer, adding it to the selection or
+	/// taking it out with Ctrl and selecting the layers from the last one clicked with Shift, or picks
+	/// every layer of a group on its header.
+	fn pick_layers_row(&mut self, index: usize) {
+		let Some(target) = self.layers_panel.as_ref().and_then(|p| p.rows.get(index)).map(|r| r.target.clone()) else {
+			return;
+		};
+		match target {
+			RowTarget::Layer(index) if self.modifiers.ctrl() => self.layers.toggle_selected(index),
+			RowTarget::Layer(index) if self.modifiers.shift() => self.layers.select_range(index),
+			RowTarget::Layer(index) => self.layers.select(index),
+			RowTarget::Group(name) => self.layers.select_group(&name),
+		}
+		self.canvas.set_active(self.layers.active);
+		self.update_title();
+	}
+
+	/// What `Action::CycleSymmetry` goes through after no symmetry, around the current center.
+	fn symmetry_modes(&self) -> [components::Symmetry; 4] {
+		let size = self.canvas.size();
+		let center = self.canvas.symmetry().map_or([size.w as f32 / 2., size.h as f32 / 2.], |s| s.center);
+		return [
+			components::Symmetry::horizontal(center),
+			components::Symmetry::vertical(center),
+			components::Symmetry::both(center),
+			components::Symmetry::radial(center, KALEIDOSCOPE_FOLDS),
+		];
+	}
+
+	/// Index of the current symmetry in `SYMMETRY_OPTIONS`, the last one for other symmetries.
+	fn symmetry_index(&self) -> usize {
+		let Some(current) = self.canvas.symmetry() else {
+			return 0;
+		};
+		return self.symmetry_modes().iter().position(|m| *m == current).map_or(SYMMETRY_OPTIONS.len() - 1, |i| i + 1);
+	}
+
+	/// What `control` is set to now, in its units.
+	fn control_value(&self, control: Control) -> f32 {
+		match control {
+			Control::BrushSize => self.canvas.brush().size as f32,
+			Control::BrushOpacity => self.canvas.brush().opacity * 100.,
+			Control::Zoom => self.canvas.view().scale * 100.,
+			Control::FpsCap => self.preferences.fps as f32,
+			Control::CanvasWidth => self.preferences.canvas_size.w as f32,
+			Control::CanvasHeight => self.preferences.canvas_size.h as f32,
+			Control::AutosaveInterval => self.preferences.autosave_interval.as_secs() as f32,
+		}
+	}
+
+	/// Zooms around the middle of the canvas rect until the document is drawn at `scale`.
+	fn zoom_to(&mut self, scale: f32) {
+		let rect = self.canvas_rect();
+		let mut view = self.canvas.view();
+		view.zoom(scale / view.scale, Point { x: rect.size.w as i32 / 2, y: rect.size.h as i32 / 2 });
+		self.canvas.set_view(view);
+	}
+
+	/// Calls `f` with the widgets shown, where they are, topmost last. The command palette covers the
+	/// window while open.
+	fn route_widgets<R>(&mut self, f: impl FnOnce(&mut WidgetRouter<NodeId>, &mut components::Widgets<NodeId>, &mut components::Context) -> R) -> R {
+		let (picker_rect, swatches_rect, memory_rects) = (self.picker_rect(), self.swatches_rect(), self.memory_rects());
+		let (canvas_rect, toolbar_rect, mixer_rect, layers_rect) = (self.canvas_rect(), self.toolbar_rect(), self.mixer_rect(), self.layers_panel_rect());
+		let layers_field_rect = self.layers_field_rect();
+		let window_rect = Rect::new(0, 0, self.size.width, self.size.height);
+		let (view, document, handle) = (self.canvas.view(), self.canvas.size(), self.symmetry_handle());
+		let pick = self.tool.as_ref().and_then(|t| t.picks_color()).or(self.modifiers.alt().then_some(ColorSlot::Foreground));
+
+		let mut center = None;
+		let mut divider = self.compare.as_mut().map(|compare| CompareDivider { compare, view, document });
+		let mut symmetry = SymmetryCenter { view, origin: canvas_rect.pos, moved: &mut center };
+		let mut mixer = self.mixer.as_mut().map(|mixer| MixerWidget { mixer, canvas: &self.canvas, pick, pressure: self.input.pressure() });
+
+		let mut widgets: Vec<(NodeId, Rect, &mut dyn Widget)> = Vec::new();
+		if let Some(divider) = &mut divider {
+			widgets.push((NodeId::CompareDivider, canvas_rect, divider));
+		}
+		if let Some(handle) = handle {
+			widgets.push((NodeId::SymmetryCenter, handle, &mut symmetry));
+		}
+		if let (Some(r), Some(mixer)) = (mixer_rect, &mut mixer) {
+			widgets.push((NodeId::Mixer, r, mixer));
+		}
+		let mut layer_rows = self.layers_panel.as_mut().map(|panel| (LayerRows { rows: &panel.rows }, panel.field.as_mut()));
+		if let (Some(r), Some(field_rect), Some((rows, field))) = (layers_rect, layers_field_rect, &mut layer_rows) {
+			widgets.push((NodeId::LayersPanel, r, rows));
+			widgets.push((NodeId::LayersField, field_rect, *field));
+		}
+		widgets.push((NodeId::Toolbar, toolbar_rect, self.toolbar.as_mut()));
+		if let (Some(r), Some(picker)) = (picker_rect, &mut self.color_picker) {
+			widgets.push((NodeId::ColorPicker, r, picker.as_mut()));
+		}
+		if let (Some(r), Some(swatches)) = (swatches_rect, &mut self.swatches) {
+			widgets.push((NodeId::Swatches, r, swatches.as_mut()));
+		}
+		if let Some(controls) = &mut self.controls {
+			widgets.extend(controls.widgets());
+		}
+		if let (Some((_, trim_rect, clear_rect)), Some(panel)) = (memory_rects, &mut self.memory_panel) {
+			widgets.push((NodeId::TrimCaches, trim_rect, panel.trim.as_mut()));
+			widgets.push((NodeId::ClearUndo, clear_rect, panel.clear_undo.as_mut()));
+		}
+		if let Some((palette, field, _)) = &mut self.command_palette {
+			widgets.push((NodeId::CommandPalette, window_rect, palette));
+			widgets.push((NodeId::CommandPaletteField, CommandPalette::field_rect(CommandPalette::position(window_rect.size)), field.as_mut()));
+		}
+		let routed = f(&mut self.widget_router, &mut widgets, &mut self.ctx);
+
+		if let (Some(center), Some(mut symmetry)) = (center, self.canvas.symmetry()) {
+			symmetry.center = center;
+			self.canvas.set_symmetry(Some(symmetry));
+		}
+		return routed;
+	}
+
+	/// Returns if the press or release was a widget's, what they emitted is handled right away.
+	fn widget_input(&mut self, state: winit::event::ElementState) -> bool {
+		let p = *self.input.get_mouse_absolute();
+		let routed = self.route_widgets(|router, widgets, ctx| match state {
+			winit::event::ElementState::Pressed => p.is_some_and(|p| router.press(ctx, widgets, p)),
+			winit::event::ElementState::Released => router.release(ctx, widgets),
+		});
+		self.handle_events();
+		return routed;
+	}
+
+	/// Does what the widgets emitted, and hands it to the canvas.
+	fn handle_events(&mut self) {
+		for event in self.ctx.take_events() {
+			match event {
+				Event::ValueChanged(Control::Zoom, percent) => self.zoom_to(percent / 100.),
+				Event::Pressed("view.actual_size") => self.zoom_to(1.),
+				Event::Pressed("memory.trim") => {
+					let before = self.memory_usage().total();
+					self.ctx.release_caches();
+					self.canvas.release_caches();
+					log::info!("Trimmed {} KiB of caches", before.saturating_sub(self.memory_usage().total()) >> 10);
+				}
+				Event::Pressed("memory.clear_undo") => {
+					log::info!("Cleared {} KiB of undo history", self.history.bytes() >> 10);
+					self.history.clear();
+				}
+				Event::Toggled("brush.eraser", erasing) => self.canvas.set_erasing(erasing),
+				Event::Selected("layers.row", i) => self.pick_layers_row(i),
+				Event::ToolPicked(item) => self.pick_tool(item),
+				Event::Refused(why) => self.notify(why),
+				Event::Selected("symmetry.mode", i) => {
+					let modes = self.symmetry_modes();
+					self.canvas.set_symmetry(i.checked_sub(1).and_then(|i| modes.get(i).copied()));
+				}
+				_ => (),
+			}
+			self.canvas.handle_event(&event);
+		}
+	}
+
+	/// A stroke started on the mixer, it gets the pointer until released.
+	fn mixing(&self) -> bool {
+		return self.mixer.as_ref().is_some_and(|m| m.is_drawing());
+	}
+
+	fn toggle_mixer(&mut self) {
+		if let Some(mixer) = self.mixer.as_mut().filter(|m| m.is_drawing()) {
+			mixer.mouse_up();
+		}
+		self.mixer_shown = !self.mixer_shown;
+		if self.mixer_shown && self.mixer.is_none() {
+			let mut mixer = components::Canvas::with_size(&mut self.ctx, Size { w: MIXER_SIDE, h: MIXER_SIDE });
+			mixer.set_smudging(true);
+			self.mixer = Some(mixer);
+		}
+	}
+
+	/// Switches to `tool`, or back to plain drawing if it was already active.
+	fn toggle_tool(&mut self, tool: Box<dyn Tool>) {
+		let same = matches!(&self.tool, Some(t) if t.name() == tool.name());
+		self.tool = if same { None } else { Some(tool) };
+	}
+
+	/// Switches to flood filling, unless the layer is locked, or back to plain drawing.
+	fn toggle_fill(&mut self) {
+		let filling = self.tool.as_ref().is_some_and(|t| t.name() == "Fill");
+		if filling || self.layer_allows(self.layers.active().can_paint(), "fill") {
+			self.toggle_tool(Box::new(tools::Fill::new(self.fill_tolerance, self.fill_behind)));
+		}
+	}
+
+	/// Shows the palette preview over the canvas, reading the canvas back as it changes.
+	fn open_palette_preview(&mut self) {
+		let subscription = self.canvas.invalidations().subscribe();
+		self.palette_preview = Some((palette::SwapPreview::new(subscription), components::Image::new(&mut self.ctx)));
+	}
+
+	fn close_palette_preview(&mut self) {
+		if let Some((preview, _)) = self.palette_preview.take() {
+			self.canvas.invalidations().unsubscribe(preview.subscription());
+		}
+		self.variant_export = None;
+		self.frame.damage_all();
+		self.window.request_redraw();
+	}
+
+	/// Reads the canvas back for the palette preview once it changed, and shows the variant again
+	/// when the pixels arrived or it was remapped. Exports the variants once they are up to date.
+	fn refresh_palette_preview(&mut self) {
+		let Some((preview, _)) = &mut self.palette_preview else {
+			return;
+		};
+		if !self.canvas.invalidations().take(preview.subscription()).is_empty() {
+			preview.changed();
+		}
+		if !preview.poll(&self.ctx) {
+			self.close_palette_preview();
+			return;
+		}
+		if preview.due() {
+			let readback = self.export_readback();
+			if let Some((preview, _)) = &mut self.palette_preview {
+				preview.start(readback);
+			}
+		}
+
+		let Some((preview, image)) = &mut self.palette_preview else {
+			return;
+		};
+		if !preview.is_waiting() {
+			if let Some(path) = self.variant_export.take() {
+				export_palette_variants(preview, &path);
+			}
+		}
+		if let Some(pixels) = preview.take_recolored() {
+			let tex = self.ctx.upload_texture("Palette(Preview Texture)", &pixels, wgpu::TextureUsages::STORAGE_BINDING);
+			image.set_texture(&self.ctx, tex);
+			self.frame.damage_all();
+			self.window.request_redraw();
+		}
+	}
+
+	/// Starts reading back what exports save, without the annotation layers unless the document includes them.
+	fn export_readback(&mut self) -> components::Readback {
+		let excluded = self.excluded_from_export();
+		return self.canvas.composite_readback_without(&mut self.ctx, &excluded);
+	}
+
+	/// Starts exporting to `path` what `export_readback` reads back.
+	fn export(&mut self, path: std::path::PathBuf) -> export::PendingExport {
+		let excluded = self.excluded_from_export();
+		return self.canvas.export(&mut self.ctx, path, &excluded);
+	}
+
+	/// Indices of the layers exports leave out.
+	fn excluded_from_export(&self) -> Vec<usize> {
+		if self.export_annotations {
+			return Vec::new();
+		}
+		return self.layers.layers.iter().enumerate().filter(|(_, l)| l.kind == LayerKind::Annotation).map(|(i, _)| i).collect();
+	}
+
+	/// Reads the composite back for the live preview once the canvas changed, and sends it when it
+	/// arrives. Drops the source once the preview window closed.
+	fn feed_live_preview(&mut self) {
+		let Some(live) = &mut self.live_preview else {
+			return;
+		};
+		if !live.is_open() {
+			self.live_preview = None;
+			return;
+		}
+		if !self.canvas.invalidations().take(live.subscription()).is_empty() {
+			live.changed();
+		}
+		live.poll(&self.ctx);
+		if live.due() {
+			let readback = self.export_readback();
+			if let Some(live) = &mut self.live_preview {
+				live.start(readback);
+			}
+		}
+	}
+
+	/// `document`, with its pixels replaced by the layers once they are read back.
+	fn start_snapshot(&mut self, document: document::Document) -> document::Snapshot {
+		let layers = (0..self.canvas.layer_count()).map(|i| document::LayerReadback {
+			opacity: self.canvas.layer_opacity(i),
+			visible: self.canvas.layer_visible(i),
+			pixels: self.canvas.layer_readback(&mut self.ctx, i),
+			mask: self.canvas.layer_mask_readback(&mut self.ctx, i),
+		}).collect();
+		let composite = self.canvas.composite_readback(&mut self.ctx);
+		return document::Snapshot::new(document, layers, composite);
+	}
+
+	/// Starts reading back the document for the recovery file, written once it arrives.
+	fn start_autosave(&mut self) {
+		let document = self.document_without_pixels();
+		// Asked again on the next update until the hashes of what changed are read back
+		let Some(hash) = self.canvas.content_hash(&mut self.ctx) else {
+			return;
+		};
+		if self.autosave.skip_unchanged(hash, &document) {
+			log::debug!("Document unchanged since the last autosave");
+			return;
+		}
+		let snapshot = self.start_snapshot(document);
+		self.autosave.start(snapshot, hash);
+	}
+
+	/// Everything `snapshot` takes but the layer pixels, which stay empty.
+	fn document_without_pixels(&self) -> document::Document {
+		let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+		return document::Document {
+			metadata: document::Metadata {
+				size: self.canvas.size(),
+				layer_count: self.canvas.layer_count() as u32,
+				created: self.created.unwrap_or(now),
+				modified: now,
+				active_seconds: self.session.total().as_secs(),
+				tag: self.document_tag,
+				export_annotations: self.export_annotations,
+				save_history: self.save_history,
+			},
+			layers: self.layers.layers.clone(),
+			pixels: Vec::new(),
+			brush: self.canvas.brush(),
+			colors: [ColorSlot::Foreground, ColorSlot::Background].map(|slot| self.canvas.get_color(slot)),
+			history: if self.save_history { self.canvas.saved_log() } else { Vec::new() },
+		};
+	}
+
+	/// Shows a file dialog, unless one is already shown. The file it picks is used in `update`.
+	fn show_file_dialog(&mut self, purpose: FilePurpose, file_name: Option<&std::path::Path>, frame_limiter: &FrameLimiter) {
+		if self.file_dialog.is_some() {
+			log::info!("A file dialog is already open");
+			return;
+		}
+		let waker = frame_limiter.waker(self.window.id());
+		self.file_dialog = Some(FileDialog::show(purpose, &self.window, file_name, waker));
+	}
+
+	/// Does what the file dialog was shown for with the file it picked.
+	fn use_picked_file(&mut self, purpose: FilePurpose, path: std::path::PathBuf) {
+		match purpose {
+			FilePurpose::Open if path.extension().is_some_and(|e| e == "pntr") => self.open_document(&path),
+			FilePurpose::Open => {
+				self.open_image(&path);
+				self.document_path = None;
+				self.update_title();
+			}
+			FilePurpose::SaveDocument => {
+				self.document_path = Some(path);
+				self.save_document();
+			}
+			FilePurpose::ExportPng { verify } => {
+				let mut export = self.export(path);
+				export.verify = verify;
+				self.pending_exports.push(export);
+			}
+			FilePurpose::ExportRegion { region, scale } => {
+				let excluded = self.excluded_from_export();
+				let readback = self.canvas.render_region(&mut self.ctx, region, scale, &excluded);
+				self.pending_exports.push(export::PendingExport::new(path, readback));
+			}
+			FilePurpose::ExportPaletteVariants => {
+				if self.palette_preview.is_none() {
+					self.open_palette_preview();
+				}
+				self.variant_export = Some(path);
+			}
+			FilePurpose::LinkImage => self.link_image(path),
+			FilePurpose::ExportSettings => match bundle::export(&path) {
+				Ok(count) => log::info!("Exported {count} settings files to {}", path.display()),
+				Err(e) => log::error!("Could not export settings to {}: {e}", path.display()),
+			},
+			FilePurpose::ImportSettings => match bundle::import(&path) {
+				Ok(count) => {
+					self.reload_settings();
+					log::info!("Imported {count} settings files from {}", path.display());
+				}
+				Err(e) => log::error!("Could not import settings from {}: {e}", path.display()),
+			},
+		}
+		self.frame.damage_all();
+		self.window.request_redraw();
+	}
+
+	/// Starts saving the document, it is written in the background and `finish_save` tells how it
+	/// went.
+	fn save_document(&mut self) {
+		if self.saving.is_some() {
+			log::info!("The document is already being saved");
+			return;
+		}
+		let document = self.document_without_pixels();
+		let path = self.document_path.clone().unwrap_or_else(|| std::path::PathBuf::from(format!("pntr-{}.pntr", document.metadata.modified)));
+		self.created = Some(document.metadata.created);
+		self.document_path = Some(path.clone());
+		let snapshot = self.start_snapshot(document);
+		self.saving = Some(document::BackgroundSave::start(path, snapshot));
+		self.edited_while_saving = false;
+		self.window.request_redraw();
+	}
+
+	/// Once the save started by `save_document` is written, closes the window when it was saved on
+	/// closing.
+	fn finish_save(&mut self, path: &std::path::Path, result: Result<(), document::DocumentError>) {
+		let close = std::mem::take(&mut self.close_after_save);
+		if let Err(e) = result {
+			log::error!("Could not save {}: {e}", path.display());
+			self.notify(&format!("Could not save {}:\n{e}", path.display()));
+			return;
+		}
+		log::info!("Saved {}", path.display());
+		if !self.edited_while_saving {
+			self.dirty = false;
+			self.autosave.discard();
+		}
+		self.update_title();
+		self.close |= close;
+	}
+
+	/// Tells how an export went, when it was verified or failed.
+	fn finish_export(&mut self, path: &std::path::Path, result: Result<bool, export::ExportError>) {
+		match result {
+			Ok(true) => self.notify(&format!("Exported and verified {}", path.display())),
+			Ok(false) => (),
+			Err(e) => self.notify(&format!("Could not export {}:\n{e}", path.display())),
+		}
+	}
+
+	fn open_document(&mut self, path: &std::path::Path) {
+		let document = match document::load(path) {
+			Ok(document) => document,
+			Err(e) => {
+				log::error!("Could not open {}: {e}", path.display());
+				return;
+			}
+		};
+
+		let layer_count = document.metadata.layer_count;
+		self.apply_document(document);
+		// Linked files may have changed while the document was closed
+		self.refresh_linked_layers(true);
+		self.document_path = Some(path.to_owned());
+		self.dirty = false;
+		self.update_title();
+		log::info!("Opened {} ({} layers)", path.display(), layer_count);
+	}
+
+	/// Replaces the layers, brush and colors with the ones of `document`, dropping the history.
+	fn apply_document(&mut self, document: document::Document) {
+		self.canvas.set_layers(&mut self.ctx, document.metadata.size, &document.pixels);
+		self.canvas.set_brush(document.brush);
+		self.canvas.set_color(ColorSlot::Foreground, document.colors[0]);
+		self.canvas.set_color(ColorSlot::Background, document.colors[1]);
+		self.layers = LayerStack::from_layers(document.layers);
+		for (i, layer) in self.layers.layers.iter().enumerate() {
+			self.canvas.set_layer_alpha_locked(i, layer.lock_alpha);
+		}
+		self.session = SessionTimer::new(std::time::Duration::from_secs(document.metadata.active_seconds));
+		self.history.clear();
+		self.pending_edit = None;
+		self.created = Some(document.metadata.created);
+		self.set_document_tag(document.metadata.tag);
+		self.export_annotations = document.metadata.export_annotations;
+		self.save_history = document.metadata.save_history;
+		self.canvas.restore_log(document.history);
+	}
+
+	/// Connects to the GPU again after the device was lost, rebuilding what lived on it and restoring
+	/// the document from the copy of its layers in memory. Without one yet, from its last autosave
+	/// or the file it was opened from, and edits since are lost.
+	///
+	/// Asks for an adapter once per call. Without one the device stays lost, and a redraw is
+	/// scheduled to ask again after `adapter_retry`.
+	fn recover_device(&mut self) {
+		if matches!(self.adapter_retry, Some((at, _)) if Instant::now() < at) {
+			return;
+		}
+		log::warn!("GPU device lost, reconnecting");
+		let Some((surface, config, ctx, gpu_faults)) = pollster::block_on(connect_gpu(&self.instance, &self.window, self.size)) else {
+			let wait = self.adapter_retry.map_or(ADAPTER_RETRY, |(_, wait)| wait);
+			log::warn!("No adapter yet, asking again in {wait:?}");
+			if let Some(timer) = &self.redraw_timer {
+				timer.redraw_in(wait);
+			}
+			self.adapter_retry = Some((Instant::now() + wait, (wait * 2).min(ADAPTER_RETRY_MAX)));
+			return;
+		};
+		self.adapter_retry = None;
+		let backup = self.canvas.backup().map(|pixels| document::Document { pixels, ..self.document_without_pixels() });
+		(self.surface, self.config, self.ctx, self.gpu_faults) = (surface, config, ctx, gpu_faults);
+		self.surface_losses = 0;
+
+		let (view, brush, symmetry, erasing) = (self.canvas.view(), self.canvas.brush(), self.canvas.symmetry(), self.canvas.is_erasing());
+		let colors = [ColorSlot::Foreground, ColorSlot::Background].map(|slot| self.canvas.get_color(slot));
+		let pressure_curve = self.canvas.pressure_curve().clone();
+		self.canvas = components::Canvas::new(&mut self.ctx);
+		self.thumbnail_subscription = self.canvas.invalidations().subscribe();
+		if let Some(live) = &mut self.live_preview {
+			live.resubscribe(self.canvas.invalidations().subscribe());
+		}
+		self.frame = components::Frame::new(&mut self.ctx);
+		self.outline = components::Outline::new(&mut self.ctx);
+		self.shadow = components::Shadow::new(&mut self.ctx);
+		self.axes = components::Axes::new(&mut self.ctx);
+		self.toolbar = components::Toolbar::new(&mut self.ctx);
+		if self.color_picker.is_some() {
+			self.color_picker = Some(components::ColorPicker::new(&mut self.ctx));
+		}
+		if self.swatches.is_some() {
+			self.swatches = Some(self.new_swatches());
+		}
+		if self.controls.is_some() {
+			self.controls = Some(self.new_controls());
+			self.layout_controls();
+		}
+		if self.memory_panel.is_some() {
+			self.memory_panel = Some(self.new_memory_panel());
+		}
+		if self.layers_panel.is_some() {
+			self.layers_panel = Some(self.new_layers_panel());
+		}
+		self.layer_thumbnails.clear();
+		self.pending_exports.clear();
+		(self.compare, self.palette_preview, self.cheatsheet, self.hud, self.tool_preview, self.radial, self.quick_picker, self.command_palette) = (None, None, None, None, None, None, None, None);
+		(self.brush_preview, self.mixer, self.mixer_shown) = (None, None, false);
+		self.tool = None;
+
+		let autosaved = self.autosave.last_saved();
+		let (document, notice) = match (backup, autosaved) {
+			(Some(backup), _) => (Some(backup), "The GPU was reset, the document was restored."),
+			(None, Some(autosaved)) => (Some(autosaved), "The GPU was reset, the document was restored from its last autosave.\nEdits since are lost."),
+			(None, None) => {
+				let path = self.document_path.as_ref();
+				let opened = path.and_then(|path| document::load(path).map_err(|e| log::error!("Could not open {} again: {e}", path.display())).ok());
+				(opened, "The GPU was reset, the document was opened again from its file.\nUnsaved edits are lost.")
+			}
+		};
+		let notice = match document {
+			Some(document) => {
+				let session = self.session.total();
+				self.apply_document(document);
+				self.session = SessionTimer::new(session);
+				notice
+			}
+			None => "The GPU was reset before the document was autosaved, it couldn't be restored.",
+		};
+
+		self.canvas.set_view(view);
+		self.canvas.set_brush(brush);
+		self.canvas.set_symmetry(symmetry);
+		self.canvas.set_erasing(erasing);
+		self.canvas.set_pressure_curve(pressure_curve);
+		for (slot, color) in [ColorSlot::Foreground, ColorSlot::Background].into_iter().zip(colors) {
+			self.canvas.set_color(slot, color);
+		}
+		self.set_brush_tip(self.brush_tip.clone());
+
+		self.recovered = true;
+		self.update_title();
+		self.notify(notice);
+	}
+
+	/// Compares the document with a PNG, by swiping.
+	fn compare_with_file(&mut self, path: &std::path::Path) {
+		match Pixels::load_image(path) {
+			Ok(pixels) => {
+				self.compare = Some(Compare::new(&mut self.ctx, &pixels, self.canvas.size()));
+				log::info!("Comparing with {}", path.display());
+			}
+			Err(e) => log::error!("Could not open {}: {e}", path.display()),
+		}
+	}
+
+	/// Compares the document with itself as it is now, keeping the mode.
+	fn take_compare_snapshot(&mut self) {
+		let mode = self.compare.as_ref().map_or(CompareMode::Swipe, |c| c.mode);
+		let pixels = self.canvas.read_pixels(&mut self.ctx);
+		let mut compare = Compare::new(&mut self.ctx, &pixels, self.canvas.size());
+		compare.mode = mode;
+		self.compare = Some(compare);
+	}
+
+	/// Window rect of the handle dragging the symmetry center, None without symmetry.
+	fn symmetry_handle(&self) -> Option<Rect> {
+		let [x, y] = self.canvas.symmetry()?.center;
+		let center = self.canvas.view().to_screen(Point { x: x.round() as i32, y: y.round() as i32 }) + self.canvas_rect().pos;
+		let half = SYMMETRY_HANDLE as i32 / 2;
+		return Some(Rect::new(center.x - half, center.y - half, SYMMETRY_HANDLE, SYMMETRY_HANDLE));
+	}
+
+	/// Loads a PNG as the bottom layer, resizing the canvas to it.
+	fn open_image(&mut self, path: &std::path::Path) {
+		let pixels = match Pixels::load_image(path) {
+			Ok(pixels) => pixels,
+			Err(e) => {
+				log::error!("Could not open {}: {e}", path.display());
+				return;
+			}
+		};
+
+		self.canvas.load_image(&mut self.ctx, &pixels);
+		self.history.clear();
+		self.pending_edit = None;
+		log::info!("Opened {} ({}x{})", path.display(), pixels.size.w, pixels.size.h);
+	}
+
+	/// Draws the linked layers again from their files, those changed since they were last checked or
+	/// every one with `all`. Waits for the canvas to be done with edits first, the next poll draws
+	/// them then.
+	fn refresh_linked_layers(&mut self, all: bool) {
+		if self.pending_edit.is_some() || !self.canvas.is_idle() {
+			if all {
+				for link in self.layers.layers.iter_mut().filter_map(|l| l.link.as_mut()) {
+					link.modified = None;
+				}
+			}
+			return;
+		}
+		let active = self.canvas.active_layer();
+		let size = self.canvas.size();
+		let mut refreshed = false;
+		for index in 0..self.layers.layers.len() {
+			let Some(link) = &mut self.layers.layers[index].link else {
+				continue;
+			};
+			if !link.poll_changed() && !all {
+				continue;
+			}
+			let path = link.path.clone();
+			match Pixels::load_image(&path) {
+				Ok(pixels) => {
+					self.canvas.set_active(index);
+					self.canvas.clear_region(&mut self.ctx, Rect::new(0, 0, size.w, size.h));
+					self.canvas.write_region(&mut self.ctx, Point { x: 0, y: 0 }, &pixels);
+					log::info!("Layer {} drawn again from {}", self.layers.layers[index].name, path.display());
+					refreshed = true;
+				}
+				Err(e) => log::warn!("Could not draw layer {} from {}: {e}", self.layers.layers[index].name, path.display()),
+			}
+		}
+		self.canvas.set_active(active);
+		if refreshed {
+			if !all {
+				self.mark_dirty();
+			}
+			self.frame.damage_all();
+			self.window.request_redraw();
+		}
+	}
+
+	/// Adds a layer linked to the image at `path` above the active one, showing it.
+	fn link_image(&mut self, path: std::path::PathBuf) {
+		let index = self.canvas.add_layer(&mut self.ctx);
+		let name = path.file_stem().map_or("Linked".into(), |s| s.to_string_lossy().into_owned());
+		let mut layer = crate::layers::Layer::new(&name);
+		layer.link = Some(LinkedSource::new(path));
+		self.layers.insert(index, layer);
+		self.refresh_linked_layers(true);
+		self.mark_dirty();
+		self.update_title();
+	}
+
+	/// The first dropped image goes on a new layer centered where it was dropped, other files and
+	/// documents open in windows of their own.
+	fn place_dropped(&mut self) {
+		let mut dropped = std::mem::take(&mut self.dropped);
+		let first = dropped.iter().position(|p| p.extension().is_none_or(|e| e != "pntr"));
+		if let Some(path) = first.map(|i| dropped.remove(i)) {
+			match Pixels::load_image(&path) {
+				Ok(pixels) => {
+					let size = self.canvas.size();
+					let at = self.input.get_mouse_relative(self.canvas_rect(), self.canvas.view())
+						.unwrap_or(Point { x: size.w as i32 / 2, y: size.h as i32 / 2 });
+					self.paste_as_layer(&pixels, centered(at, pixels.size));
+					self.frame.damage_all();
+					self.window.request_redraw();
+				}
+				Err(e) => log::error!("Could not open {}: {e}", path.display()),
+			}
+		}
+		self.open_in_windows.extend(dropped);
+	}
+
+	/// Loads the settings from the config files again, after a bundle replaced them.
+	fn reload_settings(&mut self) {
+		self.export_presets = export::load_presets();
+		self.guides = guides::load_guides();
+		self.swatch_set = swatches::load_swatches();
+		self.brush_presets = presets::load_presets();
+		self.device_settings = devices::load_settings();
+		self.canvas.set_pressure_curve(self.device_settings[&self.input.device()].pressure_curve.clone());
+		// The active profile may be gone, back to none
+		self.keymap = self.actions.load_keymap();
+		self.profiles = workspace::load_profiles(&self.actions);
+		self.profile = None;
+		self.apply_keymap();
+		self.update_title();
+		self.apply_preferences();
+	}
+
+	/// Takes up the saved preferences, the canvas size is only for the next window.
+	fn apply_preferences(&mut self) {
+		self.preferences = preferences::load();
+		self.preferences_generation = preferences::generation();
+		self.autosave.set_interval(self.preferences.autosave_interval);
+		self.history.set_limits(self.preferences.history_depth, self.preferences.history_budget);
+		self.workspace_color = load_workspace_color(self.config.format, self.preferences.theme);
+		self.frame.damage_all();
+		self.window.request_redraw();
+	}
+
+	/// Keeps the curve the previous device ended up with (it may have been calibrated) and applies the new device settings.
+	fn switch_device(&mut self, previous: DeviceKind) {
+		if let Some(settings) = self.device_settings.get_mut(&previous) {
+			settings.pressure_curve = self.canvas.pressure_curve().clone();
+		}
+		let device = self.input.device();
+		self.canvas.set_pressure_curve(self.device_settings[&device].pressure_curve.clone());
+		log::info!("Input from {}", device.name());
+	}
+
+	/// Touches of a plain touch device right after stylus input are taken for the palm resting on the screen.
+	fn touch_rejected(&self, device: DeviceId) -> bool {
+		let window = self.device_settings[&DeviceKind::Stylus].palm_rejection;
+		return self.input.kind(device) == Some(DeviceKind::Touch) && self.input.stylus_active(window);
+	}
+
+	/// Erases while the stylus is flipped to its eraser end, then goes back to how it was painting before.
+	fn follow_eraser_end(&mut self) {
+		let flipped = self.input.eraser_end() && self.device_settings[&DeviceKind::Stylus].eraser_end_erases;
+		match (flipped, self.erasing_before_eraser_end) {
+			(true, None) => {
+				self.erasing_before_eraser_end = Some(self.canvas.is_erasing());
+				self.canvas.set_erasing(true);
+			}
+			(false, Some(erasing)) => {
+				self.erasing_before_eraser_end = None;
+				self.canvas.set_erasing(erasing);
+			}
+			_ => (),
+		}
+	}
+
+	/// Made again every time it's shown, so it lists the bindings of the current profile.
+	fn show_cheatsheet(&mut self, show: bool) {
+		if !show {
+			self.cheatsheet = None;
+			return;
+		}
+		if self.cheatsheet.is_some() {
+			return;
+		}
+
+		let pixels = cheatsheet::render(&self.actions, self.size.height);
+		let tex = self.ctx.upload_texture("Cheatsheet(Texture)", &pixels, wgpu::TextureUsages::STORAGE_BINDING);
+		let mut image = components::Image::new(&mut self.ctx);
+		image.set_texture(&self.ctx, tex);
+		self.cheatsheet = Some((pixels.size, image));
+	}
+
+	fn hud_shown(&self) -> bool {
+		return self.tool.as_ref().is_some_and(|t| t.hud().is_some());
+	}
+
+	/// Draws the HUD of the active tool again when its text changed.
+	fn refresh_hud(&mut self) {
+		let Some(hud) = self.tool.as_ref().and_then(|t| t.hud()) else {
+			self.hud = None;
+			return;
+		};
+		let text = hud.text();
+		if self.hud.as_ref().is_some_and(|(t, _, _)| *t == text) {
+			return;
+		}
+		let pixels = hud.render();
+		let tex = self.ctx.upload_texture("Hud(Texture)", &pixels, wgpu::TextureUsages::STORAGE_BINDING);
+		let mut image = components::Image::new(&mut self.ctx);
+		image.set_texture(&self.ctx, tex);
+		self.hud = Some((text, pixels.size, image));
+	}
+
+	/// Uploads the tool preview again when its pixels changed.
+	fn refresh_tool_preview(&mut self) {
+		let Some((_, pixels)) = self.tool.as_ref().and_then(|t| t.preview()) else {
+			self.tool_preview = None;
+			return;
+		};
+		if self.tool_preview.as_ref().is_some_and(|(p, _)| p == pixels) || pixels.size.w == 0 || pixels.size.h == 0 {
+			return;
+		}
+		let tex = self.ctx.upload_texture("Tool(Preview Texture)", pixels, wgpu::TextureUsages::STORAGE_BINDING);
+		let mut image = components::Image::new(&mut self.ctx);
+		image.set_texture(&self.ctx, tex);
+		self.tool_preview = Some((pixels.clone(), image));
+	}
+
+	/// Loads the tip at `path` for the canvas brush, going back to round dabs when it can't be.
+	fn set_brush_tip(&mut self, path: Option<std::path::PathBuf>) {
+		let tip = match path.as_deref().map(|p| self.ctx.brush_tip(p)) {
+			None => None,
+			Some(Ok(tip)) => Some(tip),
+			Some(Err(e)) => {
+				log::error!("Could not load brush tip {}: {e}", path.as_ref().unwrap().display());
+				self.set_brush_tip(None);
+				return;
+			}
+		};
+		log::info!("Brush tip {}", path.as_deref().map_or("round".into(), |p| p.display().to_string()));
+		self.canvas.set_brush_tip(&self.ctx, tip);
+		self.brush_tip = path;
+	}
+
+	/// Paints the preview stroke again when the brush, its tip or the foreground color changed since it was last painted.
+	fn refresh_brush_preview(&mut self) {
+		let Some((brush, color, preview)) = &mut self.brush_preview else {
+			return;
+		};
+		let (current, foreground, tip) = (self.canvas.brush(), self.canvas.get_color(ColorSlot::Foreground), self.canvas.brush_tip());
+		let same_tip = tip.as_ref().map(Arc::as_ptr) == preview.brush_tip().as_ref().map(Arc::as_ptr);
+		// Strokes still queued would land after the clear, wait for them
+		if ((*brush, *color) == (current, foreground) && same_tip) || !preview.is_idle() {
+			return;
+		}
+		(*brush, *color) = (current, foreground);
+
+		preview.set_brush(current);
+		preview.set_brush_tip(&self.ctx, tip);
+		preview.set_color(ColorSlot::Foreground, foreground);
+		preview.clear();
+
+		// An S curve, pressure rising and falling again along it
+		let size = preview.size();
+		let margin = current.size as f32 / 2. + 4.;
+		let (width, amplitude) = (size.w as f32 - 2. * margin, (size.h as f32 / 2. - margin).max(0.));
+		for i in 0..=PREVIEW_SAMPLES {
+			let t = i as f32 / PREVIEW_SAMPLES as f32;
+			let p = Point {
+				x: (margin + t * width) as i32,
+				y: (size.h as f32 / 2. - (t * std::f32::consts::TAU).sin() * amplitude) as i32,
+			};
+			preview.mouse_pos(p, Some((t * std::f32::consts::PI).sin()));
+			if i == 0 {
+				preview.mouse_down();
+			}
+		}
+		preview.mouse_up();
+	}
+
+	/// Opens the radial menu at the cursor, or closes it running the action it was released over.
+	fn hold_radial_menu(&mut self, held: bool, frame_limiter: &FrameLimiter) {
+		if !held {
+			if let Some(action) = self.radial.take().and_then(|(menu, _)| menu.action()) {
+				self.run_action(action, frame_limiter);
+			}
+			return;
+		}
+		if self.radial.is_some() || self.canvas.is_drawing() || self.mixing() {
+			return;
+		}
+		let Some(p) = *self.input.get_mouse_absolute() else {
+			return;
+		};
+		self.radial = Some((RadialMenu::new(p), components::Image::new(&mut self.ctx)));
+		self.refresh_radial_menu();
+	}
+
+	fn refresh_radial_menu(&mut self) {
+		let Some((menu, image)) = &mut self.radial else {
+			return;
+		};
+		let pixels = menu.render();
+		let tex = self.ctx.upload_texture("Radial Menu(Texture)", &pixels, wgpu::TextureUsages::STORAGE_BINDING);
+		image.set_texture(&self.ctx, tex);
+	}
+
+	/// Opens the command palette with an empty search, or closes it.
+	fn toggle_command_palette(&mut self) {
+		if self.command_palette.take().is_some() || self.canvas.is_drawing() {
+			return;
+		}
+		(self.radial, self.quick_picker) = (None, None);
+		let mut field = components::TextField::new(&mut self.ctx);
+		field.set_placeholder("Type a command");
+		// The router may still have the focus on the field of the last palette
+		field.set_focused(&mut self.ctx, true);
+		self.command_palette = Some((CommandPalette::new(&self.actions), field, components::Image::new(&mut self.ctx)));
+		self.route_widgets(|router, widgets, ctx| router.focus(ctx, widgets, Some(NodeId::CommandPaletteField)));
+		self.refresh_command_palette();
+	}
+
+	fn refresh_command_palette(&mut self) {
+		let Some((palette, _, list)) = &mut self.command_palette else {
+			return;
+		};
+		let pixels = palette.render();
+		let tex = self.ctx.upload_texture("Command Palette(Texture)", &pixels, wgpu::TextureUsages::STORAGE_BINDING);
+		list.set_texture(&self.ctx, tex);
+	}
+
+	/// Typing, the arrow keys and the pointer go to the command palette while it is open, the pointer
+	/// and typing through the widget router. Returns if it took `event`.
+	fn command_palette_input(&mut self, event: &WindowEvent, frame_limiter: &FrameLimiter) -> bool {
+		use winit::event::{ElementState, MouseScrollDelta};
+
+		if self.command_palette.is_none() {
+			return false;
+		}
+		let mut run = None;
+		match event {
+			WindowEvent::ReceivedCharacter(c) => {
+				if !self.route_widgets(|router, widgets, ctx| router.text_input(ctx, widgets, *c)) {
+					return true;
+				}
+				let (palette, field, _) = self.command_palette.as_mut().unwrap();
+				palette.set_query(field.text());
+			}
+			WindowEvent::KeyboardInput { input: winit::event::KeyboardInput { state, virtual_keycode: Some(key), .. }, .. } => {
+				let (palette, _, _) = self.command_palette.as_mut().unwrap();
+				match (state, key) {
+					(ElementState::Pressed, VirtualKeyCode::Up) => palette.move_selection(-1),
+					(ElementState::Pressed, VirtualKeyCode::Down) => palette.move_selection(1),
+					(ElementState::Released, VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter) => {
+						run = palette.selected();
+						self.command_palette = None;
+						true
+					}
+					(ElementState::Released, VirtualKeyCode::Escape) => {
+						self.command_palette = None;
+						true
+					}
+					(ElementState::Released, key) if self.actions.lookup(KeyBinding::new(*key, self.modifiers)) == Some(Action::CommandPalette) => {
+						self.command_palette = None;
+						true
+					}
+					_ => return true,
+				};
+			}
+			WindowEvent::CursorMoved { position, .. } => {
+				let p = Point::from(*position);
+				if !self.route_widgets(|router, widgets, ctx| router.drag(ctx, widgets, p) || router.hover(widgets, p)) {
+					return true;
+				}
+			}
+			WindowEvent::MouseWheel { delta, .. } => {
+				let lines = match delta {
+					MouseScrollDelta::LineDelta(_, y) => *y,
+					MouseScrollDelta::PixelDelta(p) => p.y as f32 / PIXELS_PER_SCROLL_LINE,
+				};
+				let (palette, _, _) = self.command_palette.as_mut().unwrap();
+				if !palette.move_selection(-lines.round() as i32) {
+					return true;
+				}
+			}
+			WindowEvent::MouseInput { state, button: winit::event::MouseButton::Left, .. } => {
+				self.widget_input(*state);
+				let (palette, _, _) = self.command_palette.as_mut().unwrap();
+				// A click outside of the field closes it
+				match palette.take_clicked() {
+					Some(PaletteClick::Run(action)) => run = Some(action),
+					Some(PaletteClick::Close) => (),
+					None => return true,
+				}
+				self.command_palette = None;
+			}
+			WindowEvent::MouseInput { .. } | WindowEvent::Touch(_) => return true,
+			_ => return false,
+		}
+		self.refresh_command_palette();
+		self.redraw(frame_limiter);
+		if let Some(action) = run {
+			self.run_action(action, frame_limiter);
+		}
+		return true;
+	}
+
+	/// Opens the quick color picker at the cursor, or closes it.
+	fn toggle_quick_picker(&mut self) {
+		if self.quick_picker.take().is_some() || self.canvas.is_drawing() || self.mixing() {
+			return;
+		}
+		let Some(p) = *self.input.get_mouse_absolute() else {
+			return;
+		};
+		let picker = QuickPicker::new(p, self.canvas.get_color(ColorSlot::Foreground), &self.swatch_set.recent);
+		self.quick_picker = Some((picker, components::Image::new(&mut self.ctx)));
+		self.refresh_quick_picker();
+	}
+
+	fn refresh_quick_picker(&mut self) {
+		let Some((picker, image)) = &mut self.quick_picker else {
+			return;
+		};
+		let pixels = picker.render();
+		let tex = self.ctx.upload_texture("Quick Picker(Texture)", &pixels, wgpu::TextureUsages::STORAGE_BINDING);
+		image.set_texture(&self.ctx, tex);
+	}
+
+	/// Presses go to the quick picker while it is open, those outside it close it.
+	fn quick_picker_input(&mut self, state: winit::event::ElementState, frame_limiter: &FrameLimiter) {
+		let Some((picker, _)) = &mut self.quick_picker else {
+			return;
+		};
+		if state == winit::event::ElementState::Released {
+			picker.release();
+			return;
+		}
+		let Some(p) = *self.input.get_mouse_absolute() else {
+			return;
+		};
+		match picker.press(p) {
+			Pick::Color(color) => {
+				self.canvas.set_color(ColorSlot::Foreground, color);
+				self.refresh_quick_picker();
+			}
+			Pick::Recent(color) => {
+				self.canvas.set_color(ColorSlot::Foreground, color);
+				self.quick_picker = None;
+			}
+			Pick::Eyedropper => {
+				self.quick_picker = None;
+				if !matches!(&self.tool, Some(t) if t.name() == tools::Eyedropper { slot: ColorSlot::Foreground }.name()) {
+					self.run_action(Action::EyedropperMode(ColorSlot::Foreground), frame_limiter);
+				}
+			}
+			Pick::Outside => self.quick_picker = None,
+		}
+	}
+
+	fn tour_event(&mut self, event: TourEvent) {
+		let Some(tour) = &mut self.tour else {
+			return;
+		};
+		if !tour.event(event) {
+			return;
+		}
+		if tour.is_finished() {
+			log::info!("Tour finished");
+			self.tour = None;
+			tour::mark_done();
+		}
+		self.update_title();
+	}
+
+	/// Edits on the canvas until it's idle again are recorded as one history entry.
+	fn begin_edit(&mut self, name: &'static str) {
+		if self.pending_edit.is_none() {
+			self.pending_edit = Some(name);
+		}
+		self.canvas.begin_edit();
+		self.mark_dirty();
+		if std::mem::replace(&mut self.recovered, false) {
+			self.update_title();
+		}
+	}
+
+	/// Adds a layer above the active one holding `pixels` with their top left corner at `pos`, and
+	/// starts transforming them into place.
+	fn paste_as_layer(&mut self, pixels: &Pixels, pos: Point) {
+		let size = self.canvas.size();
+		let Some(placed) = Rect { pos, size: pixels.size }.intersection(Rect::new(0, 0, size.w, size.h)) else {
+			return;
+		};
+
+		let index = self.canvas.add_layer(&mut self.ctx);
+		self.layers.insert(index, crate::layers::Layer::new("Pasted"));
+		self.begin_edit("Paste");
+		self.canvas.write_region(&mut self.ctx, pos, pixels);
+		self.canvas.set_selection(Some(placed));
+		self.tool = Some(Box::new(tools::Transform::new(placed)));
+		log::info!("Pasted a {}x{} image as a new layer", pixels.size.w, pixels.size.h);
+		self.update_title();
+	}
+
+	/// Sets up the brush, key bindings, panels, guides and tool of the profile at `index`.
+	fn switch_profile(&mut self, index: usize, frame_limiter: &FrameLimiter) {
+		let profile = &self.profiles[index];
+		self.profile = Some(index);
+		self.canvas.set_brush(profile.brush);
+		self.guides_shown = profile.guides;
+		let (name, panels, tool) = (profile.name.clone(), profile.panels.clone(), profile.tool.clone());
+		self.apply_keymap();
+		self.show_panels(&panels);
+		self.tool = None;
+		if let Some(tool) = tool {
+			self.perform(tool, frame_limiter);
+		}
+		log::info!("Workspace {name}");
+		self.notify(&format!("Workspace: {name}"));
+		self.update_title();
+		self.redraw(frame_limiter);
+	}
+
+	/// The bindings of the keymap file over the defaults, and those of the active profile over them.
+	fn apply_keymap(&mut self) {
+		let profile = self.profile.map_or(&[][..], |i| &self.profiles[i].keymap);
+		let overrides: Vec<(KeyBinding, Action)> = self.keymap.iter().chain(profile).cloned().collect();
+		self.actions.set_overrides(&overrides);
+	}
+
+	/// Shows the panels in `panels`, and hides the others.
+	fn show_panels(&mut self, panels: &[Panel]) {
+		if panels.contains(&Panel::Layers) != self.layers_panel.is_some() {
+			self.layers_panel = if self.layers_panel.is_some() { None } else { Some(self.new_layers_panel()) };
+		}
+		if panels.contains(&Panel::Swatches) != self.swatches.is_some() {
+			self.swatches = if self.swatches.is_some() { None } else { Some(self.new_swatches()) };
+		}
+		if panels.contains(&Panel::Controls) != self.controls.is_some() {
+			self.controls = if self.controls.is_some() { None } else { Some(self.new_controls()) };
+			self.layout_controls();
+		}
+		if panels.contains(&Panel::Memory) != self.memory_panel.is_some() {
+			self.memory_panel = if self.memory_panel.is_some() { None } else { Some(self.new_memory_panel()) };
+		}
+	}
+
+	/// Logs why the layer at `index` refuses an edit that would make it see-through.
+	fn alpha_allowed(&self, index: usize, what: &str) -> bool {
+		let layer = &self.layers.layers[index];
+		if !layer.has_alpha() {
+			log::warn!("Layer {} is a background layer, can't {what}", layer.name);
+		}
+		return layer.has_alpha();
+	}
+
+	/// Logs why the active layer refuses an edit.
+	fn layer_allows(&self, allowed: bool, what: &str) -> bool {
+		if !allowed {
+			log::warn!("Layer {} is locked, can't {what}", self.layers.active().name);
+		}
+		return allowed;
+	}
+
+	fn run_action(&mut self, action: Action, frame_limiter: &FrameLimiter) {
+		if let Some(info) = self.actions.info(&action) {
+			log::debug!("Running {} ({})", action.id(), info.description);
+		}
+		self.actions.record(&action);
+		self.tour_event(TourEvent::Action(action.id()));
+		if action.changes_document() {
+			self.mark_dirty();
+		}
+		self.perform(action, frame_limiter);
+	}
+
+	/// Does what `action` says, without recording it in a macro.
+	fn perform(&mut self, action: Action, frame_limiter: &FrameLimiter) {
+		match action {
+			// Waits for the edit in progress to be recorded first
+			Action::Undo | Action::Redo | Action::DeleteLayer | Action::MergeLayers if self.pending_edit.is_some() => (),
+
+			Action::Undo => {
+				let changed = self.history.undo(&mut self.canvas, &mut self.layers, &mut self.ctx);
+				if changed.is_some() {
+					self.mark_dirty();
+				}
+				self.highlight_change(changed);
+				self.redraw(frame_limiter);
+			}
+
+			Action::Redo => {
+				let changed = self.history.redo(&mut self.canvas, &mut self.layers, &mut self.ctx);
+				if changed.is_some() {
+					self.mark_dirty();
+				}
+				self.highlight_change(changed);
+				self.redraw(frame_limiter);
+			}
+
+			Action::CompareMode => {
+				match self.compare.as_ref().map(|c| c.mode) {
+					None => self.take_compare_snapshot(),
+					Some(CompareMode::Swipe) => self.compare.as_mut().unwrap().mode = CompareMode::Blend,
+					Some(CompareMode::Blend) => self.compare = None,
+				}
+				self.redraw(frame_limiter);
+			}
+
+			Action::CompareSnapshot => {
+				self.take_compare_snapshot();
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleGuides => {
+				self.guides_shown = !self.guides_shown;
+				self.redraw(frame_limiter);
+			}
+
+			Action::CycleProof => {
+				let targets = components::ProofTarget::ALL;
+				let next = match self.canvas.proof() {
+					None => 0,
+					Some(current) => targets.iter().position(|t| *t == current).map_or(targets.len(), |i| i + 1),
+				};
+				let target = targets.get(next).copied();
+				log::info!("Proofing {}", target.map_or("off", components::ProofTarget::name));
+				self.canvas.set_proof(&mut self.ctx, target);
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleFollowUndo => {
+				self.follow_undo = !self.follow_undo;
+				log::info!("Undo {} to changes out of view", if self.follow_undo { "scrolls" } else { "no longer scrolls" });
+			}
+
+			Action::ClearCanvas => {
+				if !self.layer_allows(self.layers.active().can_paint(), "clear") {
+					return;
+				}
+				self.begin_edit("Clear");
+				self.canvas.clear();
+				self.redraw(frame_limiter);
+			}
+
+			Action::OpenFile => self.show_file_dialog(FilePurpose::Open, None, frame_limiter),
+
+			Action::SaveDocument => match self.document_path {
+				Some(_) => {
+					self.save_document();
+				}
+				None => {
+					let name = std::path::PathBuf::from("untitled.pntr");
+					self.show_file_dialog(FilePurpose::SaveDocument, Some(&name), frame_limiter);
+				}
+			},
+
+			Action::ExportSettings => self.show_file_dialog(FilePurpose::ExportSettings, Some(&bundle::default_path()), frame_limiter),
+
+			Action::ImportSettings => self.show_file_dialog(FilePurpose::ImportSettings, None, frame_limiter),
+
+			Action::LinkImage => self.show_file_dialog(FilePurpose::LinkImage, None, frame_limiter),
+
+			Action::EditLinkedSource => {
+				let layer = self.layers.active();
+				match &layer.link {
+					Some(link) => match link.open_in_app() {
+						Ok(_) => log::info!("Opened {}, layer {} follows its changes", link.path.display(), layer.name),
+						Err(e) => log::error!("Could not open {}: {e}", link.path.display()),
+					},
+					None => log::info!("Layer {} isn't linked to a file", layer.name),
+				}
+			}
+			Action::ShowPreferences => self.open_preferences = true,
+			Action::LivePreview => self.open_live_preview = true,
+
+			Action::ExportPng { verify } => self.show_file_dialog(FilePurpose::ExportPng { verify }, Some(&export::default_export_path()), frame_limiter),
+
+			Action::RenderSelection(scale) => {
+				let Some(region) = self.canvas.selection() else {
+					log::info!("Select the region to render first");
+					return;
+				};
+				let scale = scale as u32;
+				let side = region.size.w.max(region.size.h) * scale;
+				if side > components::MAX_CANVAS_SIDE {
+					log::warn!("A {side} pixel render is too big, {} at most", components::MAX_CANVAS_SIDE);
+					return;
+				}
+				let name = std::path::PathBuf::from(format!("region-{scale}x.png"));
+				self.show_file_dialog(FilePurpose::ExportRegion { region, scale }, Some(&name), frame_limiter);
+			}
+
+			Action::ExportPreset(slot) => {
+				let Some(preset) = (slot as usize).checked_sub(1).and_then(|i| self.export_presets.get(i)).cloned() else {
+					log::warn!("No export preset {slot}, {} are set up", self.export_presets.len());
+					return;
+				};
+				log::info!("Exporting with {}", preset.name);
+				let mut export = self.export(preset.path());
+				export.preset = Some(preset);
+				self.pending_exports.push(export);
+				self.redraw(frame_limiter);
+			}
+
+			Action::AutocropCanvas => {
+				match self.canvas.autocrop(&mut self.ctx) {
+					None => log::info!("Nothing to crop"),
+					Some(r) => log::info!("Cropped canvas to {}x{}", r.size.w, r.size.h),
+				}
+				self.redraw(frame_limiter);
+			}
+
+			Action::CropToSelection => {
+				match self.canvas.crop_to_selection(&mut self.ctx) {
+					None => log::info!("Nothing selected to crop to"),
+					Some(r) => log::info!("Cropped canvas to {}x{}", r.size.w, r.size.h),
+				}
+				self.redraw(frame_limiter);
+			}
+
+			Action::ResizeCanvasMode => {
+				let size = self.canvas.size();
+				self.toggle_tool(Box::new(tools::CanvasResize::new(Rect::new(0, 0, size.w, size.h))));
+				self.redraw(frame_limiter);
+			}
+
+			Action::CanvasSizeMode => {
+				self.toggle_tool(Box::new(tools::CanvasSize::new(self.canvas.size())));
+				self.redraw(frame_limiter);
+			}
+
+			Action::NextWorkspace => {
+				let next = self.profile.map_or(0, |i| (i + 1) % self.profiles.len());
+				self.switch_profile(next, frame_limiter);
+			}
+
+			Action::EyedropperMode(slot) => {
+				self.toggle_tool(Box::new(tools::Eyedropper { slot }));
+				self.redraw(frame_limiter);
+			}
+
+			Action::SwapColors => {
+				self.canvas.swap_colors();
+				log::info!("Foreground color {:?}", self.canvas.get_color(components::ColorSlot::Foreground));
+			}
+
+			Action::PerspectiveMode => {
+				if !self.layer_allows(self.layers.active().can_transform(), "transform") {
+					return;
+				}
+				let size = self.canvas.size();
+				let source = self.canvas.selection().unwrap_or(Rect::new(0, 0, size.w, size.h));
+				self.toggle_tool(Box::new(tools::PerspectiveTransform::new(source)));
+				self.redraw(frame_limiter);
+			}
+
+			Action::TransformMode => {
+				if !self.layer_allows(self.layers.active().can_transform(), "transform") {
+					return;
+				}
+				let size = self.canvas.size();
+				let source = self.canvas.selection().unwrap_or(Rect::new(0, 0, size.w, size.h));
+				self.toggle_tool(Box::new(tools::Transform::new(source)));
+				self.redraw(frame_limiter);
+			}
+
+			Action::ReplaceColor(tolerance) => {
+				if !self.layer_allows(self.layers.active().can_paint(), "recolor") {
+					return;
+				}
+				let pixel = self.input.get_mouse_relative(self.canvas_rect(), self.canvas.view()).and_then(|p| self.canvas.pixel_at(&mut self.ctx, p));
+				if let Some(pixel) = pixel {
+					let from = [0, 1, 2].map(|i| pixel[i] as f32 / 255.);
+					let to = self.canvas.get_color(components::ColorSlot::Foreground);
+					self.begin_edit("Replace color");
+					self.canvas.replace_color(&mut self.ctx, from, to, tolerance);
+					self.redraw(frame_limiter);
+				}
+			}
+
+			Action::BrushSize(steps) => {
+				let mut brush = self.canvas.brush();
+				let step = std::cmp::max(1, brush.size / 10) as i32;
+				brush.size = (brush.size as i32 + steps * step).max(1) as u32;
+				self.canvas.set_brush(brush);
+				log::info!("Brush size {}", self.canvas.brush().size);
+			}
+
+			Action::BrushOpacity(steps) => {
+				let mut brush = self.canvas.brush();
+				brush.opacity = (brush.opacity + steps as f32 * 0.1).clamp(0.1, 1.);
+				self.canvas.set_brush(brush);
+				log::info!("Brush opacity {:.0}%", self.canvas.brush().opacity * 100.);
+			}
+
+			Action::BrushFlow(steps) => {
+				let mut brush = self.canvas.brush();
+				brush.flow = (brush.flow + steps as f32 * 0.1).clamp(0.1, 1.);
+				self.canvas.set_brush(brush);
+				log::info!("Brush flow {:.0}%", self.canvas.brush().flow * 100.);
+			}
+
+			Action::Stabilizer(steps) => {
+				let mut brush = self.canvas.brush();
+				brush.stabilizer = (brush.stabilizer as i32 + steps * STABILIZER_STEP).max(0) as u32;
+				self.canvas.set_brush(brush);
+				log::info!("Stabilizer string {} pixels", self.canvas.brush().stabilizer);
+			}
+
+			Action::BrushRotation(steps) => {
+				let mut brush = self.canvas.brush();
+				brush.rotation += steps as f32 * ROTATION_STEP;
+				self.canvas.set_brush(brush);
+				log::info!("Brush tip turned {}°", self.canvas.brush().rotation);
+			}
+
+			Action::ReseedBrush => {
+				let mut brush = self.canvas.brush();
+				// 0 would pick different dabs every stroke
+				brush.seed = rand::random::<u32>().max(1);
+				self.canvas.set_brush(brush);
+				log::info!("Brush dynamics seed {}", brush.seed);
+			}
+
+			Action::BrushPreset(slot) => {
+				let Some(preset) = self.brush_presets.get(&slot).cloned() else {
+					log::warn!("No brush preset {slot}, save one with Shift+{slot}");
+					return;
+				};
+				// Pressure settings follow the input device rather than the brush
+				let current = self.canvas.brush();
+				self.canvas.set_brush(components::Brush { pressure_size: current.pressure_size, pressure_opacity: current.pressure_opacity, ..preset.brush });
+				self.set_brush_tip(preset.tip);
+				log::info!("Brush preset {}", preset.name);
+				self.redraw(frame_limiter);
+			}
+
+			Action::SaveBrushPreset(slot) => {
+				let name = self.brush_presets.get(&slot).map_or(format!("Preset {slot}"), |p| p.name.clone());
+				log::info!("Saved the brush as preset {name}");
+				self.brush_presets.insert(slot, BrushPreset { name, brush: self.canvas.brush(), tip: self.brush_tip.clone() });
+				presets::save_presets(&self.brush_presets);
+			}
+
+			Action::NextBrushTip => {
+				let Some(assets) = &self.assets else {
+					log::warn!("No assets directory to load brush tips from");
+					return;
+				};
+				let tips: Vec<&std::path::Path> = assets.files(AssetKind::Brushes).filter(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("png"))).collect();
+				let next = match &self.brush_tip {
+					None => tips.first(),
+					Some(current) => tips.iter().position(|p| p == current).and_then(|i| tips.get(i + 1)),
+				}.map(|p| p.to_path_buf());
+				self.set_brush_tip(next);
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleLockPixels => {
+				let layer = self.layers.active_mut();
+				layer.lock_pixels = !layer.lock_pixels;
+				log::info!("Layer {} pixels {}", layer.name, if layer.lock_pixels { "locked" } else { "unlocked" });
+			}
+
+			Action::ToggleLockPosition => {
+				let layer = self.layers.active_mut();
+				layer.lock_position = !layer.lock_position;
+				log::info!("Layer {} position {}", layer.name, if layer.lock_position { "locked" } else { "unlocked" });
+			}
+
+			Action::ToggleLockAlpha => {
+				let layer = self.layers.active_mut();
+				layer.lock_alpha = !layer.lock_alpha;
+				log::info!("Layer {} transparency {}", layer.name, if layer.lock_alpha { "locked" } else { "unlocked" });
+				let locked = layer.lock_alpha;
+				self.canvas.set_layer_alpha_locked(self.canvas.active_layer(), locked);
+			}
+
+			Action::PressureCalibration => {
+				let tool = tools::PressureCalibration::new(self.input.device().name(), &self.canvas);
+				self.toggle_tool(Box::new(tool));
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleEraser => {
+				self.canvas.set_erasing(!self.canvas.is_erasing());
+				log::info!("{}", if self.canvas.is_erasing() { "Eraser" } else { "Brush" });
+			}
+
+			Action::ToggleTouchPainting => {
+				let settings = self.device_settings.get_mut(&DeviceKind::Touch).unwrap();
+				settings.touch_paints = !settings.touch_paints;
+				log::info!("Touch {}", if settings.touch_paints { "paints" } else { "doesn't paint" });
+				devices::save_settings(&self.device_settings);
+			}
+
+			Action::ToggleEraserEnd => {
+				let settings = self.device_settings.get_mut(&DeviceKind::Stylus).unwrap();
+				settings.eraser_end_erases = !settings.eraser_end_erases;
+				log::info!("Stylus eraser end {}", if settings.eraser_end_erases { "erases" } else { "draws" });
+				devices::save_settings(&self.device_settings);
+			}
+
+			Action::NewLayer => {
+				let index = self.canvas.add_layer(&mut self.ctx);
+				let name = format!("Layer {}", self.layers.layers.len());
+				self.layers.insert(index, crate::layers::Layer::new(&name));
+				self.update_title();
+				self.redraw(frame_limiter);
+			}
+
+			Action::NewAnnotationLayer => {
+				let index = self.canvas.add_layer(&mut self.ctx);
+				let count = self.layers.layers.iter().filter(|l| l.kind == LayerKind::Annotation).count();
+				self.layers.insert(index, crate::layers::Layer::annotation(&format!("Notes {}", count + 1)));
+				self.update_title();
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleAnnotationExport => {
+				self.export_annotations = !self.export_annotations;
+				log::info!("Exports {} the annotation layers", if self.export_annotations { "include" } else { "leave out" });
+			}
+
+			Action::ToggleSaveHistory => {
+				self.save_history = !self.save_history;
+				log::info!("The document is saved {} its strokes", if self.save_history { "with" } else { "without" });
+			}
+
+			Action::DeleteLayer => {
+				let mut held = Vec::new();
+				for index in self.layers.selection().into_iter().rev() {
+					if let Some(removed) = self.canvas.take_layer(index) {
+						held.push((removed, self.layers.remove(index)));
+					}
+				}
+				if !held.is_empty() {
+					held.reverse();
+					self.history.push(Box::new(LayersEdit { name: "Delete layers", held, shown: Vec::new() }));
+					self.layers.select(self.canvas.active_layer());
+					self.update_title();
+					self.redraw(frame_limiter);
+				}
+			}
+
+			Action::SelectLayer(steps) => {
+				let index = (self.canvas.active_layer() as i32 + steps).clamp(0, self.canvas.layer_count() as i32 - 1) as usize;
+				self.canvas.set_active(index);
+				self.layers.select(index);
+				self.update_title();
+				self.redraw(frame_limiter);
+			}
+
+			Action::ExtendLayerSelection(steps) => {
+				let index = (self.canvas.active_layer() as i32 + steps).clamp(0, self.canvas.layer_count() as i32 - 1) as usize;
+				self.canvas.set_active(index);
+				self.layers.select_range(index);
+				log::info!("{} layers selected", self.layers.selection().len());
+				self.update_title();
+				self.redraw(frame_limiter);
+			}
+
+			Action::MoveLayer(steps) => {
+				let selection = self.layers.selection();
+				if !selection.iter().all(|i| self.alpha_allowed(*i, "move in the stack")) {
+					return;
+				}
+				// Nothing goes under the background layer, which has nothing to show through it
+				let bottom = self.layers.layers.first().is_some_and(|l| !l.has_alpha()) as i32;
+				let (low, high) = (selection[0] as i32, *selection.last().unwrap() as i32);
+				let steps = steps.clamp(bottom - low, self.canvas.layer_count() as i32 - 1 - high);
+				if steps != 0 {
+					// The one in front goes first, so the selected layers never pass each other
+					let order: Vec<usize> = if steps > 0 { selection.into_iter().rev().collect() } else { selection };
+					for from in order {
+						let to = (from as i32 + steps) as usize;
+						self.canvas.reorder(from, to);
+						self.layers.reorder(from, to);
+					}
+					self.redraw(frame_limiter);
+				}
+			}
+
+			Action::MergeLayers => {
+				let selection = self.layers.selection();
+				// A single layer merges down into the one below it
+				let indices = match selection[..] {
+					[index] => vec![index.saturating_sub(1), index],
+					_ => selection,
+				};
+				let Some(bottom) = self.canvas.merge_target(&indices) else {
+					log::warn!("Nothing to merge, it takes two visible layers");
+					return;
+				};
+				let layer = &self.layers.layers[bottom];
+				if !layer.can_paint() {
+					log::warn!("Layer {} is locked, can't merge into it", layer.name);
+					return;
+				}
+				// The merged layer takes the place of the bottom one, which keeps its name and locks
+				let mut held = Vec::new();
+				for removed in self.canvas.merge_layers(&mut self.ctx, &indices).into_iter().rev() {
+					let layer = if removed.index() == bottom { self.layers.layers[bottom].clone() } else { self.layers.remove(removed.index()) };
+					held.push((removed, layer));
+				}
+				held.reverse();
+				let merged = held.len();
+				self.history.push(Box::new(LayersEdit { name: "Merge layers", held, shown: vec![self.canvas.layer_id(bottom)] }));
+				self.layers.select(self.canvas.active_layer());
+				log::info!("Merged {merged} layers into {}", self.layers.layers[bottom].name);
+				self.update_title();
+				self.redraw(frame_limiter);
+			}
+
+			Action::TagDocument => {
+				let tags = &ColorTag::ALL;
+				let current = tags.iter().position(|t| *t == self.document_tag).unwrap();
+				let tag = tags[(current + 1) % tags.len()];
+				self.set_document_tag(tag);
+				log::info!("Document tagged {}", tag.name());
+			}
+
+			Action::GroupLayers => {
+				let selection = self.layers.selection();
+				if !selection.iter().all(|i| self.alpha_allowed(*i, "move into a group")) {
+					return;
+				}
+				// Gathered under the topmost one, the one in front first so they never pass each other
+				let top = *selection.last().unwrap();
+				for (k, from) in selection.iter().rev().enumerate() {
+					self.canvas.reorder(*from, top - k);
+					self.layers.reorder(*from, top - k);
+				}
+				let name = self.layers.new_group_name();
+				for index in top + 1 - selection.len()..=top {
+					self.layers.layers[index].group = Some(name.clone());
+				}
+				log::info!("{} layers grouped in {name}", selection.len());
+				self.redraw(frame_limiter);
+			}
+
+			Action::TagLayer => {
+				let tags = &ColorTag::ALL;
+				let current = tags.iter().position(|t| *t == self.layers.active().tag).unwrap();
+				let tag = tags[(current + 1) % tags.len()];
+				for index in self.layers.selection() {
+					self.layers.set_tag(index, tag);
+				}
+				log::info!("Layer {} tagged {}", self.layers.active().name, tag.name());
+				self.redraw(frame_limiter);
+			}
+
+			Action::RenameLayer => {
+				self.start_layer_rename();
+				self.redraw(frame_limiter);
+			}
+
+			Action::UngroupLayers => {
+				let selection = self.layers.selection();
+				for index in &selection {
+					self.layers.layers[*index].group = None;
+				}
+				log::info!("{} layers taken out of their groups", selection.len());
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleLayerVisibility => {
+				let visible = !self.canvas.layer_visible(self.canvas.active_layer());
+				for index in self.layers.selection() {
+					self.canvas.set_layer_visible(index, visible);
+				}
+				log::info!("Layer {} {}", self.layers.active().name, if visible { "shown" } else { "hidden" });
+				self.redraw(frame_limiter);
+			}
+
+			Action::LayerOpacity(steps) => {
+				let changed: Vec<usize> = self.layers.selection().into_iter().filter(|i| self.alpha_allowed(*i, "change its opacity")).collect();
+				for index in changed {
+					self.canvas.set_layer_opacity(index, self.canvas.layer_opacity(index) + steps as f32 * 0.1);
+				}
+				let index = self.canvas.active_layer();
+				log::info!("Layer {} opacity {:.0}%", self.layers.active().name, self.canvas.layer_opacity(index) * 100.);
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleLayerMask => {
+				let add = !self.canvas.has_layer_mask(self.canvas.active_layer());
+				for index in self.layers.selection() {
+					if add && !self.alpha_allowed(index, "have a mask") {
+						continue;
+					}
+					if add {
+						self.canvas.add_layer_mask(&mut self.ctx, index);
+					} else {
+						self.canvas.remove_layer_mask(index);
+					}
+				}
+				log::info!("Layer {} mask {}", self.layers.active().name, if add { "added" } else { "removed" });
+				self.update_title();
+				self.redraw(frame_limiter);
+			}
+
+			Action::EditLayerMask => {
+				let editing = !self.canvas.editing_mask();
+				self.canvas.set_editing_mask(editing);
+				if editing && !self.canvas.has_layer_mask(self.canvas.active_layer()) {
+					log::info!("Painting masks, layer {} has none", self.layers.active().name);
+				} else {
+					log::info!("Painting {}", if editing { "layer masks" } else { "layer pixels" });
+				}
+				self.update_title();
+			}
+
+			Action::Zoom(steps) => {
+				let rect = self.canvas_rect();
+				let mut view = self.canvas.view();
+				view.zoom(ZOOM_STEP.powi(steps), Point { x: rect.size.w as i32 / 2, y: rect.size.h as i32 / 2 });
+				self.canvas.set_view(view);
+				self.redraw(frame_limiter);
+			}
+
+			Action::FillMode => {
+				self.toggle_fill();
+				self.redraw(frame_limiter);
+			}
+
+			Action::TextMode => {
+				if !self.layer_allows(self.layers.active().can_paint(), "type") {
+					return;
+				}
+				self.toggle_tool(Box::new(tools::Text::new(self.text_size)));
+				self.redraw(frame_limiter);
+			}
+
+			Action::TextPathMode => {
+				if !self.layer_allows(self.layers.active().can_paint(), "type") {
+					return;
+				}
+				self.toggle_tool(Box::new(tools::TextPath::new(self.text_spacing, self.text_baseline)));
+				self.redraw(frame_limiter);
+			}
+
+			Action::TextSpacing(steps) | Action::TextBaseline(steps) => {
+				if matches!(action, Action::TextSpacing(_)) {
+					self.text_spacing += steps;
+				} else {
+					self.text_baseline += steps;
+				}
+				log::info!("Text spacing {}, baseline {}", self.text_spacing, self.text_baseline);
+				if self.tool.as_mut().is_some_and(|t| t.set_text_offsets(self.text_spacing, self.text_baseline)) {
+					self.redraw(frame_limiter);
+				}
+			}
+
+			Action::TextSize(steps) => {
+				self.text_size = (self.text_size + steps as f32 * TEXT_SIZE_STEP).clamp(*TEXT_SIZES.start(), *TEXT_SIZES.end());
+				log::info!("Text size {}px", self.text_size);
+				if self.tool.as_mut().is_some_and(|t| t.set_text_size(self.text_size, &mut self.ctx)) {
+					self.redraw(frame_limiter);
+				}
+			}
+
+			Action::FillTolerance(steps) => {
+				self.fill_tolerance = (self.fill_tolerance + steps as f32 * FILL_TOLERANCE_STEP).clamp(0., 1.);
+				log::info!("Fill tolerance {:.0}/255", self.fill_tolerance * 255.);
+				if self.tool.as_ref().is_some_and(|t| t.name() == "Fill") {
+					self.tool = Some(Box::new(tools::Fill::new(self.fill_tolerance, self.fill_behind)));
+				}
+			}
+
+			Action::ToggleFillBehind => {
+				self.fill_behind = !self.fill_behind;
+				log::info!("Fill {}", if self.fill_behind { "behind the layer pixels" } else { "over the layer pixels" });
+				if self.tool.as_ref().is_some_and(|t| t.name() == "Fill") {
+					self.tool = Some(Box::new(tools::Fill::new(self.fill_tolerance, self.fill_behind)));
+				}
+			}
+
+			Action::SelectMode => {
+				self.toggle_tool(Box::new(tools::RectSelect::new()));
+				self.redraw(frame_limiter);
+			}
+
+			Action::Deselect => {
+				self.canvas.set_selection(None);
+				self.redraw(frame_limiter);
+			}
+
+			Action::CopySelection => {
+				match self.canvas.read_selection(&mut self.ctx) {
+					Some(pixels) => {
+						self.clipboard.copy_image(&pixels);
+						self.selection_buffer = Some(pixels);
+					}
+					// Pasted back as a new layer, the clipboard no longer holds the selection buffer
+					None => self.clipboard.copy_image(&self.canvas.read_pixels(&mut self.ctx)),
+				}
+			}
+
+			Action::CutSelection | Action::DeleteSelection => {
+				if self.canvas.selection().is_none() {
+					return;
+				}
+				if !self.layer_allows(self.layers.active().can_paint(), "clear pixels") {
+					return;
+				}
+				if let Some(pixels) = self.canvas.read_selection(&mut self.ctx).filter(|_| action == Action::CutSelection) {
+					self.clipboard.copy_image(&pixels);
+					self.selection_buffer = Some(pixels);
+				}
+				self.begin_edit(if action == Action::CutSelection { "Cut" } else { "Delete" });
+				self.canvas.fill_selection(&mut self.ctx, None);
+				self.redraw(frame_limiter);
+			}
+
+			Action::FillSelection | Action::StrokeSelection => {
+				if self.canvas.selection().is_none() {
+					return;
+				}
+				if !self.layer_allows(self.layers.active().can_paint(), "paint") {
+					return;
+				}
+				let foreground = self.canvas.get_color(ColorSlot::Foreground);
+				if action == Action::FillSelection {
+					self.begin_edit("Fill Selection");
+					self.canvas.fill_selection(&mut self.ctx, Some(foreground));
+				} else {
+					self.begin_edit("Stroke Selection");
+					self.canvas.stroke_selection(&mut self.ctx, foreground, self.selection_stroke_width as f32);
+				}
+				self.redraw(frame_limiter);
+			}
+
+			Action::SelectionStrokeWidth(steps) => {
+				self.selection_stroke_width = (self.selection_stroke_width as i32 + steps).clamp(1, MAX_SELECTION_STROKE_WIDTH as i32) as u32;
+				log::info!("Selection stroke {} pixels wide", self.selection_stroke_width);
+			}
+
+			Action::FeatherSelection(steps) => {
+				self.canvas.set_selection_feather(self.canvas.selection_feather() + steps as f32 * FEATHER_STEP);
+				log::info!("Selection feathered by {} pixels", self.canvas.selection_feather());
+			}
+
+			Action::PasteSelection => {
+				// Images copied elsewhere float on a layer of their own
+				if let Some(pixels) = self.clipboard.paste_image().filter(|p| self.selection_buffer.as_ref() != Some(p)) {
+					let size = self.canvas.size();
+					let center = Point { x: size.w as i32 / 2, y: size.h as i32 / 2 };
+					let pos = self.canvas.selection().map_or_else(|| centered(center, pixels.size), |s| s.pos);
+					self.paste_as_layer(&pixels, pos);
+					self.redraw(frame_limiter);
+					return;
+				}
+				let Some(pixels) = self.selection_buffer.clone() else {
+					return;
+				};
+				if !self.layer_allows(self.layers.active().can_paint(), "paste") {
+					return;
+				}
+				let pos = self.canvas.selection().map_or(Point { x: 0, y: 0 }, |s| s.pos);
+				self.begin_edit("Paste");
+				self.canvas.write_region(&mut self.ctx, pos, &pixels);
+				self.canvas.set_selection(Some(Rect { pos, size: pixels.size }));
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleZoomSmoothing => {
+				let smoothing = !self.canvas.zoom_smoothing();
+				self.canvas.set_zoom_smoothing(smoothing);
+				log::info!("Strokes smoothed {}", if smoothing { "by the zoom" } else { "the same at every zoom" });
+			}
+
+			Action::ToggleBrushPreview => {
+				self.brush_preview = match self.brush_preview {
+					Some(_) => None,
+					None => {
+						let preview = components::Canvas::with_size(&mut self.ctx, PREVIEW_SIZE);
+						// Never equal to the canvas brush, so the first frame paints it
+						Some((components::Brush { size: 0, ..components::Brush::default() }, [-1.; 3], preview))
+					}
+				};
+				self.redraw(frame_limiter);
+			}
+
+			Action::Kaleidoscope => {
+				let symmetry = match self.canvas.symmetry() {
+					Some(_) => None,
+					None => {
+						let size = self.canvas.size();
+						Some(components::Symmetry::kaleidoscope([size.w as f32 / 2., size.h as f32 / 2.], KALEIDOSCOPE_FOLDS))
+					}
+				};
+				log::info!("Kaleidoscope {}", if symmetry.is_some() { "on" } else { "off" });
+				self.canvas.set_symmetry(symmetry);
+				self.redraw(frame_limiter);
+			}
+
+			Action::CycleSymmetry => {
+				let modes = self.symmetry_modes();
+				let next = match self.canvas.symmetry() {
+					None => 0,
+					Some(current) => modes.iter().position(|m| *m == current).map_or(modes.len(), |i| i + 1),
+				};
+				let names = ["left to right", "top to bottom", "both ways", "turned around the center"];
+				log::info!("Symmetry {}", names.get(next).unwrap_or(&"off"));
+				self.canvas.set_symmetry(modes.get(next).copied());
+				self.redraw(frame_limiter);
+			}
+
+			Action::SymmetryFolds(steps) => {
+				let Some(mut symmetry) = self.canvas.symmetry() else {
+					return;
+				};
+				symmetry.folds = (symmetry.folds as i32 + steps).clamp(1, components::MAX_FOLDS as i32) as u32;
+				log::info!("Symmetry with {} folds", symmetry.folds);
+				self.canvas.set_symmetry(Some(symmetry));
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleMixer => {
+				self.toggle_mixer();
+				self.redraw(frame_limiter);
+			}
+
+			Action::CommandPalette => {
+				self.toggle_command_palette();
+				self.redraw(frame_limiter);
+			}
+
+			Action::QuickColorPicker => {
+				self.toggle_quick_picker();
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleColorPicker => {
+				self.color_picker = match self.color_picker {
+					Some(_) => None,
+					None => Some(components::ColorPicker::new(&mut self.ctx)),
+				};
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleMemoryPanel => {
+				self.memory_panel = match self.memory_panel {
+					Some(_) => None,
+					None => Some(self.new_memory_panel()),
+				};
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleLayersPanel => {
+				self.end_layer_rename(false);
+				self.layers_panel = match self.layers_panel {
+					Some(_) => None,
+					None => Some(self.new_layers_panel()),
+				};
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleControls => {
+				self.controls = match self.controls {
+					Some(_) => None,
+					None => Some(self.new_controls()),
+				};
+				self.layout_controls();
+				self.redraw(frame_limiter);
+			}
+
+			Action::ToggleSwatches => {
+				self.swatches = match self.swatches {
+					Some(_) => None,
+					None => Some(self.new_swatches()),
+				};
+				self.redraw(frame_limiter);
+			}
+
+			Action::PinColor => {
+				let color = self.canvas.get_color(ColorSlot::Foreground).map(|c| (c * 255.).round() as u8);
+				let pinned = self.swatch_set.toggle_pin(color);
+				log::info!("{} the foreground color", if pinned { "Pinned" } else { "Unpinned" });
+				self.swatches_changed();
+				self.redraw(frame_limiter);
+			}
+
+			Action::ImportPalettes => {
+				let Some(assets) = &self.assets else {
+					log::warn!("No assets directory to import palettes from");
+					return;
+				};
+				let palettes: Vec<std::path::PathBuf> = assets.files(AssetKind::Palettes).filter(|p| p.extension().is_some_and(|e| e.eq_ignore_ascii_case("gpl"))).map(|p| p.to_path_buf()).collect();
+				let mut pinned = 0;
+				for path in &palettes {
+					if let Some(colors) = swatches::load_gpl(path) {
+						pinned += self.swatch_set.pin_all(&colors);
+					}
+				}
+				log::info!("Pinned {pinned} colors from {} palettes", palettes.len());
+				self.swatches_changed();
+				self.redraw(frame_limiter);
+			}
+
+			Action::PaletteSwapPreview => {
+				match &mut self.palette_preview {
+					None => self.open_palette_preview(),
+					Some((preview, _)) => {
+						if !preview.next() {
+							self.close_palette_preview();
+						}
+					}
+				}
+				self.redraw(frame_limiter);
+			}
+
+			Action::RemapPaletteEntry => {
+				let p = self.input.get_mouse_relative(self.canvas_rect(), self.canvas.view());
+				let to = self.canvas.get_color(ColorSlot::Foreground).map(|c| (c * 255.).round() as u8);
+				let remapped = self.palette_preview.as_mut().zip(p).and_then(|((preview, _), p)| preview.remap(p, to));
+				match remapped {
+					Some(from) => log::info!("Palette entry {from:?} remapped to {to:?}"),
+					None => log::info!("Remapping palette entries needs the palette preview and a cursor over the canvas"),
+				}
+				self.redraw(frame_limiter);
+			}
+
+			Action::ExportPaletteVariants => self.show_file_dialog(FilePurpose::ExportPaletteVariants, Some(std::path::Path::new("variant.png")), frame_limiter),
+
+			Action::Confirm => {
+				if let Some(tool) = &self.tool {
+					self.begin_edit(tool.name());
+				}
+				if let Some(tool) = &mut self.tool {
+					if tool.confirm(&mut self.canvas, &mut self.ctx) {
+						self.tool = None;
+					}
+				}
+				self.redraw(frame_limiter);
+			}
+
+			Action::StartTour => {
+				self.tour = Some(Tour::new());
+				self.update_title();
+				self.redraw(frame_limiter);
+			}
+
+			Action::Cancel if self.tour.is_some() => {
+				self.tour = None;
+				tour::mark_done();
+				self.update_title();
+				self.redraw(frame_limiter);
+			}
+
+			Action::Cancel => {
+				if self.quick_picker.take().is_some() {
+					self.redraw(frame_limiter);
+					return;
+				}
+				if let Some(tool) = &mut self.tool {
+					tool.cancel(&mut self.canvas);
+				}
+				self.tool = None;
+				self.redraw(frame_limiter);
+			}
+
+			Action::RecordMacro(slot) => self.actions.toggle_recording(slot),
+
+			Action::PlayMacro(slot) => {
+				for recorded in self.actions.macro_actions(slot) {
+					self.run_action(recorded, frame_limiter);
+				}
+			}
+		}
+	}
+}
+
+/// Writes every variant of `preview` next to `path`, numbered after its name.
+fn export_palette_variants(preview: &palette::SwapPreview, path: &std::path::Path) {
+	let Some(variants) = preview.variants() else {
+		return;
+	};
+	let stem = path.file_stem().map_or("variant".into(), |s| s.to_string_lossy());
+	for (i, pixels) in variants.into_iter().enumerate() {
+		let path = path.with_file_name(format!("{stem}-{}.png", i + 1));
+		export::save_in_background(pixels, path, false, None);
+	}
+}
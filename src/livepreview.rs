@@ -0,0 +1,304 @@
+use std::{
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use winit::{event::{VirtualKeyCode, WindowEvent}, event_loop::EventLoopWindowTarget, window::Window};
+
+use crate::components::{self, Component, Rect, Readback, Size, Subscription};
+use crate::export::Pixels;
+use crate::framelimiter::FrameLimiter;
+use crate::layout::{self, Layout, LayoutContext, WindowLifeStatus};
+use crate::settings::preferences;
+use crate::CustomEvents;
+
+/// Least time between two frames sent to a live preview, each one reads the composite back
+const LIVE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Each window has a GPU device of its own, so the composite goes from one to the other through
+/// the CPU: the drawing window publishes it here and wakes the preview up.
+pub struct LiveFeed {
+	/// Frames published so far, and the last one
+	frame: Mutex<(u64, Option<Arc<Pixels>>)>,
+	/// Redrawn when a frame is published, None once the preview closed
+	preview: Mutex<Option<Arc<Window>>>,
+}
+
+impl LiveFeed {
+	fn new() -> Self {
+		Self { frame: Mutex::new((0, None)), preview: Mutex::new(None) }
+	}
+
+	fn publish(&self, pixels: Pixels) {
+		{
+			let mut frame = self.frame.lock().unwrap();
+			*frame = (frame.0 + 1, Some(Arc::new(pixels)));
+		}
+		if let Some(window) = &*self.preview.lock().unwrap() {
+			window.request_redraw();
+		}
+	}
+
+	/// The last frame and its number, when it is newer than `seen`.
+	fn latest(&self, seen: u64) -> Option<(u64, Arc<Pixels>)> {
+		let frame = self.frame.lock().unwrap();
+		return frame.1.clone().filter(|_| frame.0 != seen).map(|p| (frame.0, p));
+	}
+}
+
+/// The drawing window's end of a live preview: reads the composite back after the canvas notified
+/// changes, at most every `LIVE_INTERVAL`.
+pub struct LiveSource {
+	feed: Arc<LiveFeed>,
+	subscription: Subscription,
+	/// Changed since the last frame was read back
+	stale: bool,
+	readback: Option<Readback>,
+	taken: Instant,
+}
+
+impl LiveSource {
+	/// A source taking the canvas changes of `subscription`, and the feed of its preview.
+	pub fn new(subscription: Subscription) -> (Self, Arc<LiveFeed>) {
+		let feed = Arc::new(LiveFeed::new());
+		let source = Self { feed: feed.clone(), subscription, stale: true, readback: None, taken: Instant::now() };
+		return (source, feed);
+	}
+
+	/// Whether the preview window is still open.
+	pub fn is_open(&self) -> bool {
+		Arc::strong_count(&self.feed) > 1
+	}
+
+	pub fn subscription(&self) -> Subscription {
+		self.subscription
+	}
+
+	/// For a canvas made again, which tells its changes to new subscriptions.
+	pub fn resubscribe(&mut self, subscription: Subscription) {
+		(self.subscription, self.stale, self.readback) = (subscription, true, None);
+	}
+
+	pub fn changed(&mut self) {
+		self.stale = true;
+	}
+
+	/// Whether a readback should be started, none is in progress.
+	pub fn due(&self) -> bool {
+		return self.stale && self.readback.is_none() && self.taken.elapsed() >= LIVE_INTERVAL;
+	}
+
+	pub fn start(&mut self, readback: Readback) {
+		(self.readback, self.stale, self.taken) = (Some(readback), false, Instant::now());
+	}
+
+	/// Publishes the frame once read back.
+	pub fn poll(&mut self, ctx: &components::Context) {
+		let Some(pixels) = self.readback.as_ref().and_then(|r| r.try_finish(ctx)) else {
+			return;
+		};
+		self.readback = None;
+		self.feed.publish(pixels);
+	}
+
+	/// Whether a frame is on its way or due, the window should keep drawing until it is sent.
+	pub fn is_waiting(&self) -> bool {
+		self.stale || self.readback.is_some()
+	}
+}
+
+/// Read-only window showing the document of a drawing window as it is painted, for a second
+/// monitor facing a client or a stream capture. H flips it, F fits it to the window or shows it at
+/// actual size.
+pub struct LivePreviewWindow {
+	window: Arc<Window>,
+	surface: wgpu::Surface,
+	config: wgpu::SurfaceConfiguration,
+	ctx: components::Context,
+	feed: Arc<LiveFeed>,
+	/// Number of the frame shown
+	seen: u64,
+	frame: Option<Arc<Pixels>>,
+	image: Box<components::Image>,
+	flipped: bool,
+	fit: bool,
+	theme: preferences::Theme,
+	close: bool,
+}
+
+impl LivePreviewWindow {
+	pub async fn open(feed: Arc<LiveFeed>, window: Arc<Window>) -> Box<Self> {
+		let mut layout = Self::new(Self::init(), window).await;
+		*feed.preview.lock().unwrap() = Some(layout.window.clone());
+		layout.feed = feed;
+		layout.update_title();
+		return layout;
+	}
+
+	fn update_title(&self) {
+		let mut title = String::from("pntr live preview");
+		title += if self.fit { " - Fit" } else { " - Actual size" };
+		if self.flipped {
+			title += " - Flipped";
+		}
+		self.window.set_title(&title);
+	}
+
+	/// Uploads the frame, mirrored left to right when flipped.
+	fn upload(&mut self) {
+		let Some(frame) = &self.frame else {
+			return;
+		};
+		let tex = if self.flipped {
+			let mut flipped = Pixels::new(frame.size);
+			let row = (frame.size.w * 4) as usize;
+			for (src, dst) in frame.data.chunks_exact(row).zip(flipped.data.chunks_exact_mut(row)) {
+				for (s, d) in src.chunks_exact(4).rev().zip(dst.chunks_exact_mut(4)) {
+					d.copy_from_slice(s);
+				}
+			}
+			self.ctx.upload_texture("Live Preview(Texture)", &flipped, wgpu::TextureUsages::STORAGE_BINDING)
+		} else {
+			self.ctx.upload_texture("Live Preview(Texture)", frame, wgpu::TextureUsages::STORAGE_BINDING)
+		};
+		self.image.set_texture(&self.ctx, tex);
+	}
+
+	/// Where the frame is drawn in the window and the part of it shown: all of it scaled to fit,
+	/// or what fits of it at actual size, centered.
+	fn placement(&self, document: Size) -> Option<(Rect, Rect)> {
+		let window = Size { w: self.config.width, h: self.config.height };
+		if document.w == 0 || document.h == 0 {
+			return None;
+		}
+		if self.fit {
+			let scale = (window.w as f32 / document.w as f32).min(window.h as f32 / document.h as f32);
+			let size = Size { w: ((document.w as f32 * scale) as u32).max(1), h: ((document.h as f32 * scale) as u32).max(1) };
+			let r = Rect::new((window.w as i32 - size.w as i32) / 2, (window.h as i32 - size.h as i32) / 2, size.w, size.h);
+			return Some((r, Rect::new(0, 0, document.w, document.h)));
+		}
+		let placed = Rect::new((window.w as i32 - document.w as i32) / 2, (window.h as i32 - document.h as i32) / 2, document.w, document.h);
+		let shown = placed.intersection(Rect::new(0, 0, window.w, window.h))?;
+		let source = Rect::new(shown.pos.x - placed.pos.x, shown.pos.y - placed.pos.y, shown.size.w, shown.size.h);
+		return Some((shown, source));
+	}
+}
+
+#[async_trait]
+impl Layout for LivePreviewWindow {
+	fn init() -> LayoutContext {
+		LayoutContext { wgpu: Some(wgpu::Instance::new(wgpu::Backends::all())), ..Default::default() }
+	}
+
+	async fn new(layout_ctx: LayoutContext, window: Arc<Window>) -> Box<Self> {
+		let instance = layout_ctx.wgpu.expect("Generated with wrong context");
+		let size = window.inner_size();
+		let (surface, config, mut ctx, _) = layout::connect_gpu(&instance, &window, size).await.expect("Could not get adapter");
+		let image = components::Image::new(&mut ctx);
+		return Box::new(Self {
+			window,
+			surface,
+			config,
+			ctx,
+			// Replaced by `open`
+			feed: Arc::new(LiveFeed::new()),
+			seen: 0,
+			frame: None,
+			image,
+			flipped: false,
+			fit: true,
+			theme: preferences::load().theme,
+			close: false,
+		});
+	}
+
+	fn window(&self) -> Arc<Window> {
+		self.window.clone()
+	}
+
+	fn render(&mut self) {
+		if let Some((generation, pixels)) = self.feed.latest(self.seen) {
+			(self.seen, self.frame) = (generation, Some(pixels));
+			self.upload();
+		}
+
+		let output = match self.surface.get_current_texture() {
+			Ok(output) => output,
+			Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+				self.surface.configure(&self.ctx.device, &self.config);
+				self.window.request_redraw();
+				return;
+			}
+			Err(e) => {
+				log::warn!("Could not draw the live preview: {e:?}");
+				return;
+			}
+		};
+		let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+		let mut encoder = self.ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Live Preview(Render Encoder)"),
+		});
+		encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("Live Preview(Clear Pass)"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: &view,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Clear(layout::load_workspace_color(self.config.format, self.theme)),
+					store: true,
+				}
+			})],
+			depth_stencil_attachment: None,
+		});
+
+		if let Some((r, source)) = self.frame.as_ref().and_then(|f| self.placement(f.size)) {
+			self.image.set_source(Some(source));
+			self.image.render(&mut encoder, &mut self.ctx, &view, r, None);
+		}
+
+		self.ctx.staging_belt.finish();
+		self.ctx.queue.submit(std::iter::once(encoder.finish()));
+		self.ctx.staging_belt.recall();
+		self.ctx.buffer_pool.reset();
+		self.ctx.uniform_pool.reset();
+		output.present();
+	}
+
+	fn update(&mut self, _: &EventLoopWindowTarget<CustomEvents>) -> (WindowLifeStatus, Option<Box<dyn Layout>>) {
+		if self.close {
+			// The drawing window stops reading back once the feed is only its own
+			*self.feed.preview.lock().unwrap() = None;
+			return (WindowLifeStatus::Dead, None);
+		}
+		return (WindowLifeStatus::Alive, None);
+	}
+
+	fn event_handler(&mut self, event: WindowEvent, frame_limiter: &FrameLimiter) {
+		use winit::event::ElementState;
+
+		match event {
+			WindowEvent::CloseRequested => self.close = true,
+			WindowEvent::Resized(size) if size.width > 0 && size.height > 0 => {
+				(self.config.width, self.config.height) = (size.width, size.height);
+				self.surface.configure(&self.ctx.device, &self.config);
+			}
+			WindowEvent::KeyboardInput { input: winit::event::KeyboardInput { state: ElementState::Released, virtual_keycode: Some(key), .. }, .. } => {
+				match key {
+					VirtualKeyCode::Escape => self.close = true,
+					VirtualKeyCode::H => {
+						self.flipped = !self.flipped;
+						self.upload();
+					}
+					VirtualKeyCode::F => self.fit = !self.fit,
+					_ => return,
+				}
+				self.update_title();
+			}
+			_ => return,
+		}
+		frame_limiter.schedule_redraw(self.window.id());
+	}
+
+	fn idle(&mut self, _: &FrameLimiter) {}
+}
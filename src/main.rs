@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return)]
+
 use winit::{
 	event::Event,
 	event_loop::EventLoopBuilder,
@@ -6,13 +8,51 @@ use winit::{
 
 use std::{
 	collections::HashMap,
+	panic::AssertUnwindSafe,
 	sync::Arc,
-	time::Duration,
 };
 
+mod actions;
+mod assets;
+mod autosave;
+mod bench;
+mod cheatsheet;
+mod clipboard;
+mod commandpalette;
+mod compare;
 mod components;
+mod config;
+mod devices;
+mod dialogs;
+mod document;
+mod export;
+mod font;
+mod glyphs;
+mod layers;
+mod layerspanel;
 mod layout;
+mod livepreview;
+mod math;
+mod memory;
+mod notice;
 mod framelimiter;
+mod guides;
+mod history;
+mod hud;
+mod idle;
+mod palette;
+mod presets;
+mod pressure;
+mod quickpicker;
+mod radial;
+mod session;
+mod settings;
+mod swatches;
+mod thumbnail;
+mod tools;
+mod tour;
+mod tween;
+mod workspace;
 use framelimiter::FrameLimiter;
 use layout::Layout;
 use layout::WindowLifeStatus;
@@ -22,21 +62,88 @@ type InitialLayout = layout::DrawingWindow;
 #[derive(Debug)]
 pub enum CustomEvents {
 	ShouldRedraw(WindowId),
+	/// The window went without input for a while, see `FrameLimiter::request_idle`
+	Idle(WindowId),
+}
+
+/// What happens after a layout panicked.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum PanicPolicy {
+	/// Closes the window of the layout, the others stay open
+	CloseWindow,
+	Exit,
+}
+
+/// Picks the policy from the panic message and how many windows are open, the crashed one included.
+type PanicHook = fn(&str, usize) -> PanicPolicy;
+
+/// Keeps the other documents open, unless `PNTR_PANIC=exit` asks to stop at the first panic.
+fn default_panic_policy(_: &str, _: usize) -> PanicPolicy {
+	if std::env::var_os("PNTR_PANIC").is_some_and(|v| v == "exit") {
+		return PanicPolicy::Exit;
+	}
+	return PanicPolicy::CloseWindow;
+}
+
+/// Runs `f`, returning the message it panicked with. The message was already printed by the panic hook.
+fn isolated(f: impl FnOnce()) -> Result<(), String> {
+	return std::panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+		payload.downcast_ref::<&str>().map(|s| s.to_string())
+			.or_else(|| payload.downcast_ref::<String>().cloned())
+			.unwrap_or_else(|| "unknown error".to_string())
+	});
 }
 
-const FPS: i16 = 144;
-const FRAMETIME: Duration = Duration::from_nanos(1_000_000_000 / (FPS as u64));
+/// Tells about a crashed window, blocking until the dialog is dismissed.
+fn show_crash_dialog(message: &str, policy: PanicPolicy) {
+	let consequence = match policy {
+		PanicPolicy::CloseWindow => "Its window was closed, other documents are still open.",
+		PanicPolicy::Exit => "pntr will exit.",
+	};
+	rfd::MessageDialog::new()
+		.set_level(rfd::MessageLevel::Error)
+		.set_title("pntr crashed")
+		.set_description(format!("{message}\n\n{consequence}"))
+		.set_buttons(rfd::MessageButtons::Ok)
+		.show();
+}
+
+/// Tells why no document can be opened, blocking until the dialog is dismissed.
+fn show_unsupported_dialog(reason: &str) {
+	rfd::MessageDialog::new()
+		.set_level(rfd::MessageLevel::Error)
+		.set_title("pntr can't paint on this GPU")
+		.set_description(reason)
+		.set_buttons(rfd::MessageButtons::Ok)
+		.show();
+}
 
-async fn run() {
+async fn run(open: Option<std::path::PathBuf>, compare: Option<std::path::PathBuf>) {
 	env_logger::init();
 
+	let mut ctx = InitialLayout::init();
+	if let Some(reason) = layout::painting_unsupported(ctx.wgpu.as_ref().expect("Generated with wrong context")).await {
+		log::error!("{reason}");
+		show_unsupported_dialog(&reason);
+		return;
+	}
+
 	let event_loop = EventLoopBuilder::<CustomEvents>::with_user_event().build();
 
 	let mut window_map = HashMap::<WindowId, Box<dyn Layout>>::new();
 	let frame_limiter = FrameLimiter::new(&event_loop);
+	let panic_hook: PanicHook = default_panic_policy;
+
+	// Documents of a session that crashed come back in windows of their own, the first one in the
+	// initial window unless it opens a file
+	let mut restored = autosave::ask_restore();
 
 	// Start initial layout
-	let ctx = InitialLayout::init();
+	if open.is_none() && !restored.is_empty() {
+		ctx.recover = Some(restored.remove(0));
+	}
+	ctx.open = open;
+	ctx.compare = compare;
 
 	let window = Arc::new(Window::new(&event_loop).expect("Could not create window"));
 
@@ -45,28 +152,49 @@ async fn run() {
 
 	window_map.insert(initial_layout.window().id(), initial_layout);
 
+	for path in restored {
+		let mut ctx = InitialLayout::init();
+		ctx.recover = Some(path);
+		let window = Arc::new(Window::new(&event_loop).expect("Could not create window"));
+		let mut layout = InitialLayout::new(ctx, window).await;
+		layout.render();
+		window_map.insert(layout.window().id(), layout);
+	}
+
 	event_loop.run(move |event, event_loop, control_flow| {
 		control_flow.set_wait();
 
+		// Windows whose layout panicked, and the message it panicked with
+		let mut crashed: Vec<(WindowId, String)> = Vec::new();
+
 		match event {
 			Event::WindowEvent { window_id, event } => {
-				match window_map.get_mut(&window_id) {
+				let layout = match window_map.get_mut(&window_id) {
 					None => {
 						println!("Ignoring event to invalid window: {:?}", window_id);
 						return;
 					}
 					Some(r) => r,
+				};
+				frame_limiter.mark_active(window_id);
+				if let Err(message) = isolated(|| layout.event_handler(event, &frame_limiter)) {
+					crashed.push((window_id, message));
 				}
-				.event_handler(event, &frame_limiter);
 			}
 			Event::MainEventsCleared => {
 				let mut should_remove: Vec<WindowId> = Vec::new();
 				let mut should_add: Vec<Box<dyn Layout>> = Vec::new();
 				window_map.values_mut().for_each(|layout| {
-					let (window_state, child) = layout.update(event_loop);
+					let id = layout.window().id();
+					let mut update = None;
+					if let Err(message) = isolated(|| update = Some(layout.update(event_loop))) {
+						crashed.push((id, message));
+						return;
+					}
+					let (window_state, child) = update.unwrap();
 
 					if let WindowLifeStatus::Dead = window_state {
-						should_remove.push(layout.window().id());
+						should_remove.push(id);
 					}
 
 					if let Some(child_layout) = child {
@@ -95,16 +223,62 @@ async fn run() {
 			Event::RedrawRequested(wid) |
 			Event::UserEvent(CustomEvents::ShouldRedraw(wid)) => {
 				if let Some(layout) = window_map.get_mut(&wid) {
-					layout.render();
+					if let Err(message) = isolated(|| layout.render()) {
+						crashed.push((wid, message));
+					}
+				}
+			}
+
+			Event::UserEvent(CustomEvents::Idle(wid)) => {
+				if let Some(layout) = window_map.get_mut(&wid) {
+					if let Err(message) = isolated(|| layout.idle(&frame_limiter)) {
+						crashed.push((wid, message));
+					}
 				}
 			}
 
 
 			_ => (),
 		}
+
+		for (id, message) in crashed {
+			let policy = panic_hook(&message, window_map.len());
+			log::error!("Window {id:?} crashed: {message}");
+			show_crash_dialog(&message, policy);
+			match policy {
+				PanicPolicy::CloseWindow => {
+					window_map.remove(&id);
+					if window_map.is_empty() {
+						control_flow.set_exit_with_code(1);
+					}
+				}
+				PanicPolicy::Exit => control_flow.set_exit_with_code(1),
+			}
+		}
 	})
 }
 
 fn main() {
-	pollster::block_on(run());
+	let args: Vec<String> = std::env::args().collect();
+
+	if args.get(1).map(String::as_str) == Some("--thumbnail") {
+		if let Err(e) = thumbnail::run(&args[2..]) {
+			eprintln!("{e}");
+			std::process::exit(1);
+		}
+		return;
+	}
+	if args.get(1).map(String::as_str) == Some("--bench") {
+		env_logger::init();
+		if let Err(e) = bench::run(&args[2..]) {
+			eprintln!("{e}");
+			std::process::exit(1);
+		}
+		return;
+	}
+
+	// pntr [file] [--compare image]
+	let compare = args.iter().position(|a| a == "--compare").and_then(|i| args.get(i + 1)).map(std::path::PathBuf::from);
+	let open = args.get(1).filter(|a| *a != "--compare").map(std::path::PathBuf::from);
+	pollster::block_on(run(open, compare));
 }
@@ -0,0 +1,88 @@
+use core::ops;
+
+/// Row major 3x3 matrix for 2D projective transforms of homogeneous points.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat3(pub [[f32; 3]; 3]);
+
+impl Mat3 {
+	pub fn scale(x: f32, y: f32) -> Mat3 {
+		Mat3([[x, 0., 0.], [0., y, 0.], [0., 0., 1.]])
+	}
+
+	pub fn translate(x: f32, y: f32) -> Mat3 {
+		Mat3([[1., 0., x], [0., 1., y], [0., 0., 1.]])
+	}
+
+	/// Clockwise on screen, where y goes down.
+	pub fn rotate(radians: f32) -> Mat3 {
+		let (sin, cos) = radians.sin_cos();
+		Mat3([[cos, -sin, 0.], [sin, cos, 0.], [0., 0., 1.]])
+	}
+
+	/// Where the point `p` lands.
+	pub fn apply(&self, p: [f32; 2]) -> [f32; 2] {
+		let m = &self.0;
+		let [x, y, w] = [0, 1, 2].map(|r| m[r][0] * p[0] + m[r][1] * p[1] + m[r][2]);
+		return [x / w, y / w];
+	}
+
+	/// Maps the unit square corners (0,0), (1,0), (1,1), (0,1) to `quad`, in that order.
+	/// None if the quad is degenerate.
+	pub fn square_to_quad(quad: [[f32; 2]; 4]) -> Option<Mat3> {
+		let [[x0, y0], [x1, y1], [x2, y2], [x3, y3]] = quad;
+
+		let (dx1, dx2, dy1, dy2) = (x1 - x2, x3 - x2, y1 - y2, y3 - y2);
+		let (sx, sy) = (x0 - x1 + x2 - x3, y0 - y1 + y2 - y3);
+
+		let det = dx1 * dy2 - dx2 * dy1;
+		if det.abs() < f32::EPSILON {
+			return None;
+		}
+
+		let g = (sx * dy2 - dx2 * sy) / det;
+		let h = (dx1 * sy - sx * dy1) / det;
+
+		return Some(Mat3([
+			[x1 - x0 + g * x1, x3 - x0 + h * x3, x0],
+			[y1 - y0 + g * y1, y3 - y0 + h * y3, y0],
+			[g, h, 1.],
+		]));
+	}
+
+	pub fn inverse(&self) -> Option<Mat3> {
+		let m = &self.0;
+		let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+
+		let adj = [
+			[cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2)],
+			[-cofactor(1, 2, 0, 2), cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2)],
+			[cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1)],
+		];
+
+		let det = m[0][0] * adj[0][0] + m[0][1] * adj[1][0] + m[0][2] * adj[2][0];
+		if det == 0. || !det.is_finite() {
+			return None;
+		}
+
+		return Some(Mat3(adj.map(|row| row.map(|v| v / det))));
+	}
+
+	/// Column major with each column padded to a vec4, the layout of a WGSL `mat3x3<f32>`.
+	pub fn wgsl_columns(&self) -> [[f32; 4]; 3] {
+		let m = &self.0;
+		return [0, 1, 2].map(|c| [m[0][c], m[1][c], m[2][c], 0.]);
+	}
+}
+
+impl ops::Mul for Mat3 {
+	type Output = Mat3;
+	fn mul(self, rhs: Mat3) -> Mat3 {
+		let mut r = [[0.; 3]; 3];
+		for (i, row) in r.iter_mut().enumerate() {
+			for (j, v) in row.iter_mut().enumerate() {
+				*v = (0..3).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+			}
+		}
+		Mat3(r)
+	}
+}
@@ -0,0 +1,45 @@
+use crate::components::{MemoryUsage, Size, Tier};
+use crate::export::Pixels;
+use crate::font;
+
+const SCALE: u32 = 2;
+const MARGIN: u32 = 12;
+const BACKGROUND: [u8; 4] = [24, 24, 28, 235];
+const HEADING: [u8; 4] = [240, 200, 90, 255];
+const TEXT: [u8; 4] = [230, 230, 230, 255];
+
+fn format_bytes(bytes: u64) -> String {
+	return format!("{:.1} MiB", bytes as f64 / (1 << 20) as f64);
+}
+
+/// Lines of the memory panel, the total first and the capability tier of the GPU last.
+pub fn lines(usage: &MemoryUsage, tier: Tier) -> Vec<String> {
+	let rows = [
+		("Total", usage.total()),
+		("Layers", usage.layers),
+		("Tiles", usage.tiles),
+		("Undo", usage.undo),
+		("Thumbnails", usage.thumbnails),
+		("Atlases", usage.atlases),
+		("Scratch", usage.scratch),
+	];
+	let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+	let mut lines: Vec<String> = rows.iter().map(|(name, bytes)| format!("{name:width$}  {:>10}", format_bytes(*bytes))).collect();
+	lines.push(format!("{:width$}  {:>10}", "GPU tier", tier.name()));
+	return lines;
+}
+
+/// The lines with a margin around them, at least `min_width` wide and with `footer` pixels left
+/// free at the bottom for the buttons.
+pub fn render(lines: &[String], min_width: u32, footer: u32) -> Pixels {
+	let line_h = font::text_size("A", SCALE).h;
+	let width = lines.iter().map(|l| font::text_size(l, SCALE).w).max().unwrap_or(0);
+	let size = Size { w: (width + 2 * MARGIN).max(min_width), h: lines.len() as u32 * line_h + 2 * MARGIN + footer };
+
+	let mut pixels = Pixels::new(size);
+	font::fill(&mut pixels, 0, 0, size.w, size.h, BACKGROUND);
+	for (i, line) in lines.iter().enumerate() {
+		font::draw_text(&mut pixels, MARGIN, MARGIN + i as u32 * line_h, line, if i == 0 { HEADING } else { TEXT }, SCALE);
+	}
+	return pixels;
+}
@@ -0,0 +1,41 @@
+use std::time::{Duration, Instant};
+
+use crate::components::Size;
+use crate::export::Pixels;
+use crate::font;
+
+const SCALE: u32 = 2;
+const MARGIN: u32 = 12;
+const BACKGROUND: [u8; 4] = [120, 30, 30, 240];
+const TEXT: [u8; 4] = [245, 240, 235, 255];
+
+/// How long a notice stays up
+const SHOWN_FOR: Duration = Duration::from_secs(10);
+
+/// Message shown over the canvas for a while, for what the user should know about but doesn't
+/// have to answer, like running low on GPU memory.
+pub struct Notice {
+	text: String,
+	shown: Instant,
+}
+
+impl Notice {
+	/// A notice of `text`, lines split on '\n'.
+	pub fn new(text: &str) -> Self {
+		return Self { text: text.to_string(), shown: Instant::now() };
+	}
+
+	pub fn expired(&self) -> bool {
+		self.shown.elapsed() >= SHOWN_FOR
+	}
+
+	pub fn render(&self) -> Pixels {
+		let text = font::text_size(&self.text, SCALE);
+		let size = Size { w: text.w + 2 * MARGIN, h: text.h + 2 * MARGIN };
+
+		let mut pixels = Pixels::new(size);
+		font::fill(&mut pixels, 0, 0, size.w, size.h, BACKGROUND);
+		font::draw_text(&mut pixels, MARGIN, MARGIN, &self.text, TEXT, SCALE);
+		return pixels;
+	}
+}
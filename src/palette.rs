@@ -0,0 +1,213 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::components::{self, Point, Readback, Subscription};
+use crate::export::Pixels;
+
+/// Past this many colors the art isn't treated as palette based.
+pub const MAX_PALETTE: usize = 256;
+/// Recolored variants previewed and exported in one batch.
+pub const VARIANT_COUNT: usize = 5;
+
+/// Distinct colors of `pixels`, None if there are more than `max` (not palette based art).
+pub fn extract_palette(pixels: &Pixels, max: usize) -> Option<Vec<[u8; 3]>> {
+	let mut seen = HashSet::new();
+	let mut palette = Vec::new();
+	for px in pixels.data.chunks_exact(4) {
+		let color = [px[0], px[1], px[2]];
+		if seen.insert(color) {
+			if palette.len() == max {
+				return None;
+			}
+			palette.push(color);
+		}
+	}
+	return Some(palette);
+}
+
+pub fn rgb_to_hsv(c: [f32; 3]) -> [f32; 3] {
+	let max = c[0].max(c[1]).max(c[2]);
+	let min = c[0].min(c[1]).min(c[2]);
+	let delta = max - min;
+
+	let h = if delta == 0. {
+		0.
+	} else if max == c[0] {
+		60. * ((c[1] - c[2]) / delta).rem_euclid(6.)
+	} else if max == c[1] {
+		60. * ((c[2] - c[0]) / delta + 2.)
+	} else {
+		60. * ((c[0] - c[1]) / delta + 4.)
+	};
+
+	let s = if max == 0. { 0. } else { delta / max };
+	return [h, s, max];
+}
+
+pub fn hsv_to_rgb(hsv: [f32; 3]) -> [f32; 3] {
+	let [h, s, v] = hsv;
+	let c = v * s;
+	let x = c * (1. - ((h / 60.).rem_euclid(2.) - 1.).abs());
+	let m = v - c;
+
+	let (r, g, b) = match (h.rem_euclid(360.) / 60.) as u32 {
+		0 => (c, x, 0.),
+		1 => (x, c, 0.),
+		2 => (0., c, x),
+		3 => (0., x, c),
+		4 => (x, 0., c),
+		_ => (c, 0., x),
+	};
+	return [r + m, g + m, b + m];
+}
+
+/// Remaps palette entries to new colors, alpha is kept.
+#[derive(Clone, Default)]
+pub struct PaletteMap {
+	map: HashMap<[u8; 3], [u8; 3]>,
+}
+
+impl PaletteMap {
+	pub fn set(&mut self, from: [u8; 3], to: [u8; 3]) {
+		self.map.insert(from, to);
+	}
+
+	/// Keeps the entries of the colors still in `palette` and drops the others. The new colors are
+	/// rotated around the hue wheel by `degrees`, a quick way to get recolored variants.
+	pub fn follow(&mut self, palette: &[[u8; 3]], degrees: f32) {
+		self.map.retain(|from, _| palette.contains(from));
+		for c in palette {
+			self.map.entry(*c).or_insert_with(|| {
+				let mut hsv = rgb_to_hsv(c.map(|v| v as f32 / 255.));
+				hsv[0] += degrees;
+				hsv_to_rgb(hsv).map(|v| (v * 255.).round() as u8)
+			});
+		}
+	}
+
+	pub fn apply(&self, pixels: &Pixels) -> Pixels {
+		let mut out = pixels.clone();
+		for px in out.data.chunks_exact_mut(4) {
+			if let Some(to) = self.map.get(&[px[0], px[1], px[2]]) {
+				px[0..3].copy_from_slice(to);
+			}
+		}
+		return out;
+	}
+}
+
+/// Recolored variants of the canvas palette, one of them shown over the canvas. The canvas is read
+/// back once it changed, the variants are recolored from that copy, so remapping an entry or
+/// showing another variant doesn't wait for the GPU.
+pub struct SwapPreview {
+	/// Variant shown
+	index: usize,
+	/// `VARIANT_COUNT` of them, spread evenly around the hue wheel until entries are remapped
+	variants: Vec<PaletteMap>,
+	/// Colors of `pixels`
+	palette: Vec<[u8; 3]>,
+	/// Canvas as last read back
+	pixels: Option<Pixels>,
+	subscription: Subscription,
+	/// The canvas changed since the last readback started
+	stale: bool,
+	readback: Option<Readback>,
+	/// The shown variant changed since it was last taken
+	recolored: bool,
+}
+
+impl SwapPreview {
+	/// A preview of the first variant, taking the canvas changes of `subscription`.
+	pub fn new(subscription: Subscription) -> Self {
+		return Self {
+			index: 0,
+			variants: vec![PaletteMap::default(); VARIANT_COUNT],
+			palette: Vec::new(),
+			pixels: None,
+			subscription,
+			stale: true,
+			readback: None,
+			recolored: false,
+		};
+	}
+
+	pub fn subscription(&self) -> Subscription {
+		self.subscription
+	}
+
+	pub fn changed(&mut self) {
+		self.stale = true;
+	}
+
+	/// Whether a readback should be started, none is in progress.
+	pub fn due(&self) -> bool {
+		self.stale && self.readback.is_none()
+	}
+
+	pub fn start(&mut self, readback: Readback) {
+		(self.readback, self.stale) = (Some(readback), false);
+	}
+
+	/// Takes the canvas pixels once read back. The variants follow their palette when it changed.
+	/// False when the canvas isn't palette based, the preview can't show it.
+	pub fn poll(&mut self, ctx: &components::Context) -> bool {
+		let Some(pixels) = self.readback.as_ref().and_then(|r| r.try_finish(ctx)) else {
+			return true;
+		};
+		self.readback = None;
+		let Some(palette) = extract_palette(&pixels, MAX_PALETTE) else {
+			log::warn!("Canvas has more than {MAX_PALETTE} colors, not palette based");
+			return false;
+		};
+		if palette != self.palette {
+			let step = 360. / (VARIANT_COUNT + 1) as f32;
+			for (k, variant) in self.variants.iter_mut().enumerate() {
+				variant.follow(&palette, (k + 1) as f32 * step);
+			}
+			self.palette = palette;
+		}
+		(self.pixels, self.recolored) = (Some(pixels), true);
+		return true;
+	}
+
+	/// Whether a readback is due or on its way, the window should keep drawing until it arrives.
+	pub fn is_waiting(&self) -> bool {
+		self.stale || self.readback.is_some()
+	}
+
+	/// Shows the next variant, false past the last one.
+	pub fn next(&mut self) -> bool {
+		if self.index + 1 >= VARIANT_COUNT {
+			return false;
+		}
+		(self.index, self.recolored) = (self.index + 1, true);
+		return true;
+	}
+
+	/// Remaps the palette entry at document point `p` to `to` in the shown variant, returns the
+	/// entry. None outside the canvas or before it was read back.
+	pub fn remap(&mut self, p: Point, to: [u8; 3]) -> Option<[u8; 3]> {
+		let pixels = self.pixels.as_ref()?;
+		if p.x < 0 || p.y < 0 || p.x as u32 >= pixels.size.w || p.y as u32 >= pixels.size.h {
+			return None;
+		}
+		let i = ((p.y as u32 * pixels.size.w + p.x as u32) * 4) as usize;
+		let from = [pixels.data[i], pixels.data[i + 1], pixels.data[i + 2]];
+		self.variants[self.index].set(from, to);
+		self.recolored = true;
+		return Some(from);
+	}
+
+	/// The canvas in the shown variant, when it changed since the last call.
+	pub fn take_recolored(&mut self) -> Option<Pixels> {
+		if !std::mem::take(&mut self.recolored) {
+			return None;
+		}
+		return self.pixels.as_ref().map(|p| self.variants[self.index].apply(p));
+	}
+
+	/// The canvas in every variant, None until it was read back.
+	pub fn variants(&self) -> Option<Vec<Pixels>> {
+		let pixels = self.pixels.as_ref()?;
+		return Some(self.variants.iter().map(|v| v.apply(pixels)).collect());
+	}
+}
@@ -0,0 +1,77 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::components::Brush;
+use crate::config;
+
+pub const PRESETS_FILE: &str = "brush-presets.toml";
+
+/// Brush settings saved under a name, with the image the tip is stamped from.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BrushPreset {
+	pub name: String,
+	/// Round dabs without one
+	pub tip: Option<PathBuf>,
+	/// Settings left out of the file keep their defaults
+	pub brush: Brush,
+}
+
+/// A `[[preset]]` table of the presets file, with the number key it is under.
+#[derive(Serialize, Deserialize)]
+struct SavedPreset {
+	slot: u8,
+	#[serde(flatten)]
+	preset: BrushPreset,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct PresetsFile {
+	#[serde(default, rename = "preset")]
+	presets: Vec<SavedPreset>,
+}
+
+/// Presets from the config file by the number key they are under.
+pub fn load_presets() -> BTreeMap<u8, BrushPreset> {
+	let file: PresetsFile = config::read_toml(PRESETS_FILE).unwrap_or_default();
+	return file.presets.into_iter().map(|saved| (saved.slot, saved.preset)).collect();
+}
+
+pub fn save_presets(presets: &BTreeMap<u8, BrushPreset>) {
+	let presets = presets.iter().map(|(slot, preset)| SavedPreset { slot: *slot, preset: preset.clone() }).collect();
+	config::write_toml(PRESETS_FILE, &PresetsFile { presets });
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn presets_file_round_trips() {
+		let preset = BrushPreset {
+			name: "Ink".to_owned(),
+			tip: Some(PathBuf::from("brushes/ink.png")),
+			brush: Brush { size: 12, hardness: 0.5, ..Brush::default() },
+		};
+		let file = PresetsFile { presets: vec![SavedPreset { slot: 3, preset: preset.clone() }] };
+		let read: PresetsFile = toml::from_str(&toml::to_string(&file).unwrap()).unwrap();
+		assert_eq!(read.presets.len(), 1);
+		assert_eq!(read.presets[0].slot, 3);
+		assert_eq!(read.presets[0].preset, preset);
+	}
+
+	#[test]
+	fn missing_brush_settings_keep_their_defaults() {
+		let text = "[[preset]]\nslot = 1\nname = \"Soft\"\n\n[preset.brush]\nsize = 40\n";
+		let read: PresetsFile = toml::from_str(text).unwrap();
+		let preset = &read.presets[0].preset;
+		assert_eq!((preset.name.as_str(), preset.tip.as_ref()), ("Soft", None));
+		assert_eq!(preset.brush, Brush { size: 40, ..Brush::default() });
+	}
+
+	#[test]
+	fn empty_file_has_no_presets() {
+		let read: PresetsFile = toml::from_str("").unwrap();
+		assert!(read.presets.is_empty());
+	}
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use crate::config;
+
+pub const CURVES_FILE: &str = "pressure-curves";
+/// Most control points the calibration editor lets the user add
+pub const MAX_POINTS: usize = 8;
+
+/// Maps raw stylus pressure to the pressure the brush uses, piecewise linear through its control points.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PressureCurve {
+	/// Sorted by input, the first at 0 and the last at 1
+	points: Vec<[f32; 2]>,
+}
+
+impl Default for PressureCurve {
+	fn default() -> Self {
+		Self { points: vec![[0., 0.], [1., 1.]] }
+	}
+}
+
+impl PressureCurve {
+	pub fn points(&self) -> &[[f32; 2]] {
+		&self.points
+	}
+
+	pub fn apply(&self, pressure: f32) -> f32 {
+		let p = pressure.clamp(0., 1.);
+		for pair in self.points.windows(2) {
+			let ([x0, y0], [x1, y1]) = (pair[0], pair[1]);
+			if p <= x1 {
+				let t = if x1 > x0 { (p - x0) / (x1 - x0) } else { 1. };
+				return y0 + (y1 - y0) * t;
+			}
+		}
+		return self.points.last().unwrap()[1];
+	}
+
+	/// Moves a control point, the end points only vertically and the others between their neighbours.
+	pub fn move_point(&mut self, index: usize, point: [f32; 2]) {
+		let last = self.points.len() - 1;
+		let x = match index {
+			0 => 0.,
+			i if i == last => 1.,
+			i => point[0].clamp(self.points[i - 1][0], self.points[i + 1][0]),
+		};
+		self.points[index] = [x, point[1].clamp(0., 1.)];
+	}
+
+	/// Adds a control point between the end points, returns its index.
+	pub fn add_point(&mut self, point: [f32; 2]) -> Option<usize> {
+		if self.points.len() >= MAX_POINTS || point[0] <= 0. || point[0] >= 1. {
+			return None;
+		}
+		let index = self.points.iter().position(|p| p[0] > point[0]).unwrap();
+		self.points.insert(index, [point[0], point[1].clamp(0., 1.)]);
+		return Some(index);
+	}
+
+	/// Space separated `input:output` pairs.
+	pub fn parse(text: &str) -> Option<Self> {
+		let mut points = Vec::new();
+		for pair in text.split_whitespace() {
+			let (x, y) = pair.split_once(':')?;
+			points.push([x.parse().ok()?, y.parse().ok()?]);
+		}
+
+		let sorted = points.windows(2).all(|w: &[[f32; 2]]| w[0][0] <= w[1][0]);
+		if points.len() < 2 || !sorted || points[0][0] != 0. || points.last().unwrap()[0] != 1. {
+			return None;
+		}
+		return Some(Self { points });
+	}
+
+	pub fn format(&self) -> String {
+		self.points.iter().map(|[x, y]| format!("{x:.3}:{y:.3}")).collect::<Vec<_>>().join(" ")
+	}
+}
+
+/// Calibrated curves by input device name, one `device<TAB>curve` line each in the config directory.
+pub fn load_curves() -> HashMap<String, PressureCurve> {
+	let curves = config::read_lines(CURVES_FILE, "pressure curve", |line| {
+		let (device, curve) = line.split_once('\t')?;
+		Some((device.to_owned(), PressureCurve::parse(curve)?))
+	});
+	return curves.unwrap_or_default().into_iter().collect();
+}
+
+pub fn save_curve(device: &str, curve: &PressureCurve) {
+	let mut curves = load_curves();
+	curves.insert(device.to_owned(), curve.clone());
+	let mut devices: Vec<_> = curves.keys().cloned().collect();
+	devices.sort();
+	let text: String = devices.iter().map(|d| format!("{d}\t{}\n", curves[d].format())).collect();
+	config::write_file(CURVES_FILE, &text);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn applies_piecewise_linearly() {
+		let curve = PressureCurve::parse("0:0 0.5:0.8 1:1").unwrap();
+		assert_eq!(curve.apply(0.25), 0.4);
+		assert!((curve.apply(0.75) - 0.9).abs() < 1e-6);
+		assert_eq!(curve.apply(2.), 1.);
+		assert_eq!(PressureCurve::default().apply(0.3), 0.3);
+	}
+
+	#[test]
+	fn parse_needs_sorted_points_from_0_to_1() {
+		for text in ["", "0:0", "0:0 0.5:1", "0.1:0 1:1", "0:0 0.6:1 0.4:0 1:1", "0:0 a:b 1:1", "0-0 1-1"] {
+			assert_eq!(PressureCurve::parse(text), None, "{text}");
+		}
+	}
+
+	#[test]
+	fn format_round_trips() {
+		let curve = PressureCurve::parse("0:0.1 0.25:0.5 1:0.9").unwrap();
+		assert_eq!(PressureCurve::parse(&curve.format()), Some(curve));
+	}
+
+	#[test]
+	fn points_stay_between_their_neighbours() {
+		let mut curve = PressureCurve::default();
+		assert_eq!(curve.add_point([0.5, 2.]), Some(1));
+		assert_eq!(curve.points()[1], [0.5, 1.]);
+		curve.move_point(1, [1.5, 0.2]);
+		assert_eq!(curve.points()[1], [1., 0.2]);
+		curve.move_point(0, [0.3, 0.3]);
+		assert_eq!(curve.points()[0], [0., 0.3]);
+		assert_eq!(curve.add_point([0., 0.5]), None);
+	}
+
+	#[test]
+	fn control_points_are_limited() {
+		let mut curve = PressureCurve::default();
+		for i in 1..MAX_POINTS - 1 {
+			assert!(curve.add_point([i as f32 / MAX_POINTS as f32, 0.5]).is_some());
+		}
+		assert_eq!(curve.add_point([0.99, 0.5]), None);
+	}
+}
@@ -0,0 +1,230 @@
+use crate::components::{Point, Rect, Size};
+use crate::export::Pixels;
+use crate::font;
+use crate::palette;
+
+const RADIUS: u32 = 110;
+/// Inner radius of the hue ring, as a fraction of the outer one
+const RING_INNER: f32 = 0.8;
+/// Half the side of the saturation and value square, as a fraction of the ring inner radius
+const SQUARE_HALF: f32 = 0.65;
+/// Recent colors shown under the ring, newest first
+const RECENT_SHOWN: usize = 8;
+const CELL: u32 = 18;
+const CELL_GAP: u32 = 4;
+/// From the ring to the row of recent colors
+const ROW_GAP: u32 = 8;
+/// The eyedropper button after the recent colors is as wide as this many cells
+const PICK_CELLS: u32 = 2;
+const BACKGROUND: [u8; 4] = [24, 24, 28, 235];
+const HOVERED: [u8; 4] = [230, 230, 230, 255];
+const BUTTON: [u8; 4] = [50, 50, 58, 245];
+const TEXT: [u8; 4] = [230, 230, 230, 255];
+const MARKER: [u8; 4] = [255, 255, 255, 255];
+const MARKER_OUTLINE: [u8; 4] = [0, 0, 0, 255];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Part {
+	Ring,
+	Square,
+	Recent(usize),
+	Eyedropper,
+}
+
+/// What a press on the quick picker asks for.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Pick {
+	/// Dragging on the ring or square changed the foreground color, the picker stays open
+	Color([f32; 3]),
+	/// A recent color was clicked, the picker closes
+	Recent([f32; 3]),
+	/// Pick the foreground color from the canvas instead
+	Eyedropper,
+	/// Pressed outside, the picker closes
+	Outside,
+}
+
+/// Hue ring, recent colors and the eyedropper around the cursor, so the foreground color changes
+/// without reaching for a panel.
+pub struct QuickPicker {
+	/// Of the ring, where the picker was summoned
+	center: Point,
+	/// Hue in degrees, saturation and value from 0 to 1
+	hsv: [f32; 3],
+	recent: Vec<[u8; 3]>,
+	hovered: Option<Part>,
+	/// What the press started on, the pointer moves it until released
+	dragging: Option<Part>,
+}
+
+impl QuickPicker {
+	pub fn new(center: Point, color: [f32; 3], recent: &[[u8; 3]]) -> Self {
+		Self {
+			center,
+			hsv: palette::rgb_to_hsv(color),
+			recent: recent.iter().take(RECENT_SHOWN).copied().collect(),
+			hovered: None,
+			dragging: None,
+		}
+	}
+
+	fn size() -> Size {
+		return Size { w: 2 * RADIUS, h: 2 * RADIUS + ROW_GAP + CELL };
+	}
+
+	pub fn rect(&self) -> Rect {
+		let size = Self::size();
+		return Rect::new(self.center.x - RADIUS as i32, self.center.y - RADIUS as i32, size.w, size.h);
+	}
+
+	/// Left edge of a cell of the bottom row and its width, the eyedropper after the recent colors.
+	fn cell(part: Part) -> (u32, u32) {
+		let step = CELL + CELL_GAP;
+		let row = (RECENT_SHOWN as u32 + PICK_CELLS) * step - CELL_GAP;
+		let left = RADIUS - row / 2;
+		return match part {
+			Part::Recent(i) => (left + i as u32 * step, CELL),
+			_ => (left + RECENT_SHOWN as u32 * step, PICK_CELLS * step - CELL_GAP),
+		};
+	}
+
+	/// Part at `offset` from the top left corner of the picker.
+	fn part_at(&self, offset: [f32; 2]) -> Option<Part> {
+		let [x, y] = offset;
+		let (dx, dy) = (x - RADIUS as f32, y - RADIUS as f32);
+		let radius = RADIUS as f32;
+		let half = radius * RING_INNER * SQUARE_HALF;
+		let distance = dx.hypot(dy);
+		if (radius * RING_INNER..=radius).contains(&distance) {
+			return Some(Part::Ring);
+		}
+		if dx.abs() <= half && dy.abs() <= half {
+			return Some(Part::Square);
+		}
+		let top = (2 * RADIUS + ROW_GAP) as f32;
+		if !(top..top + CELL as f32).contains(&y) {
+			return None;
+		}
+		let mut parts = (0..self.recent.len()).map(Part::Recent).chain(std::iter::once(Part::Eyedropper));
+		return parts.find(|part| {
+			let (left, w) = Self::cell(*part);
+			(left as f32..(left + w) as f32).contains(&x)
+		});
+	}
+
+	fn offset(&self, p: Point) -> [f32; 2] {
+		let r = self.rect();
+		return [(p.x - r.pos.x) as f32, (p.y - r.pos.y) as f32];
+	}
+
+	/// Returns if the hovered part changed.
+	pub fn hover(&mut self, p: Point) -> bool {
+		let hovered = self.part_at(self.offset(p));
+		let changed = hovered != self.hovered;
+		self.hovered = hovered;
+		return changed;
+	}
+
+	pub fn press(&mut self, p: Point) -> Pick {
+		let part = self.part_at(self.offset(p));
+		return match part {
+			Some(Part::Ring | Part::Square) => {
+				self.dragging = part;
+				self.drag(p).map_or(Pick::Color(palette::hsv_to_rgb(self.hsv)), Pick::Color)
+			}
+			Some(Part::Recent(i)) => Pick::Recent(self.recent[i].map(|c| c as f32 / 255.)),
+			Some(Part::Eyedropper) => Pick::Eyedropper,
+			None => Pick::Outside,
+		};
+	}
+
+	/// The new foreground color when dragging changed it.
+	pub fn drag(&mut self, p: Point) -> Option<[f32; 3]> {
+		let part = self.dragging?;
+		let [x, y] = self.offset(p);
+		let (dx, dy) = (x - RADIUS as f32, y - RADIUS as f32);
+		let hsv = match part {
+			Part::Ring => [dx.atan2(-dy).to_degrees().rem_euclid(360.), self.hsv[1], self.hsv[2]],
+			_ => {
+				let half = RADIUS as f32 * RING_INNER * SQUARE_HALF;
+				[self.hsv[0], (dx / half * 0.5 + 0.5).clamp(0., 1.), (0.5 - dy / half * 0.5).clamp(0., 1.)]
+			}
+		};
+		if hsv == self.hsv {
+			return None;
+		}
+		self.hsv = hsv;
+		return Some(palette::hsv_to_rgb(hsv));
+	}
+
+	pub fn release(&mut self) {
+		self.dragging = None;
+	}
+
+	fn rgba(rgb: [f32; 3]) -> [u8; 4] {
+		let [r, g, b] = rgb.map(|c| (c * 255.).round() as u8);
+		return [r, g, b, 255];
+	}
+
+	/// Small ring around `x`, `y` showing the picked hue or saturation and value.
+	fn marker(pixels: &mut Pixels, x: f32, y: f32) {
+		for py in (y - 6.).max(0.) as u32..(y + 6.) as u32 {
+			for px in (x - 6.).max(0.) as u32..(x + 6.) as u32 {
+				let d = (px as f32 + 0.5 - x).hypot(py as f32 + 0.5 - y);
+				let color = match d {
+					d if (3.5..4.5).contains(&d) => MARKER,
+					d if (2.5..5.5).contains(&d) => MARKER_OUTLINE,
+					_ => continue,
+				};
+				font::fill(pixels, px, py, 1, 1, color);
+			}
+		}
+	}
+
+	pub fn render(&self) -> Pixels {
+		let size = Self::size();
+		let mut pixels = Pixels::new(size);
+		let radius = RADIUS as f32;
+		let inner = radius * RING_INNER;
+		let half = inner * SQUARE_HALF;
+
+		for y in 0..2 * RADIUS {
+			for x in 0..2 * RADIUS {
+				let (dx, dy) = (x as f32 + 0.5 - radius, y as f32 + 0.5 - radius);
+				let distance = dx.hypot(dy);
+				let color = if (inner..=radius).contains(&distance) {
+					Self::rgba(palette::hsv_to_rgb([dx.atan2(-dy).to_degrees().rem_euclid(360.), 1., 1.]))
+				} else if dx.abs() <= half && dy.abs() <= half {
+					Self::rgba(palette::hsv_to_rgb([self.hsv[0], dx / half * 0.5 + 0.5, 0.5 - dy / half * 0.5]))
+				} else if distance < inner {
+					BACKGROUND
+				} else {
+					continue;
+				};
+				font::fill(&mut pixels, x, y, 1, 1, color);
+			}
+		}
+
+		let hue = self.hsv[0].to_radians();
+		let ring = (inner + radius) / 2.;
+		Self::marker(&mut pixels, radius + hue.sin() * ring, radius - hue.cos() * ring);
+		Self::marker(&mut pixels, radius + (self.hsv[1] * 2. - 1.) * half, radius + (1. - self.hsv[2] * 2.) * half);
+
+		let top = 2 * RADIUS + ROW_GAP;
+		for (i, color) in self.recent.iter().enumerate() {
+			let (left, w) = Self::cell(Part::Recent(i));
+			if self.hovered == Some(Part::Recent(i)) {
+				font::fill(&mut pixels, left - 1, top - 1, w + 2, CELL + 2, HOVERED);
+			}
+			let [r, g, b] = *color;
+			font::fill(&mut pixels, left, top, w, CELL, [r, g, b, 255]);
+		}
+		let (left, w) = Self::cell(Part::Eyedropper);
+		let button = if self.hovered == Some(Part::Eyedropper) { HOVERED } else { BUTTON };
+		font::fill(&mut pixels, left, top, w, CELL, button);
+		let text = if self.hovered == Some(Part::Eyedropper) { BACKGROUND } else { TEXT };
+		let label = font::text_size("Pick", 1);
+		font::draw_text(&mut pixels, left + (w - label.w) / 2, top + (CELL - label.h) / 2 + 1, "Pick", text, 1);
+		return pixels;
+	}
+}
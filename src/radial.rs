@@ -0,0 +1,107 @@
+use std::f32::consts::TAU;
+
+use crate::actions::Action;
+use crate::components::{ColorSlot, Point, Rect, Size};
+use crate::export::Pixels;
+use crate::font;
+
+const RADIUS: u32 = 140;
+/// Releasing this close to the center runs nothing
+const DEAD_ZONE: f32 = 36.;
+const LABEL_RADIUS: f32 = 92.;
+/// Width of the gaps between sectors, in pixels
+const GAP: f32 = 3.;
+const SCALE: u32 = 2;
+const BACKGROUND: [u8; 4] = [24, 24, 28, 235];
+const HOVERED: [u8; 4] = [70, 70, 84, 245];
+const TEXT: [u8; 4] = [230, 230, 230, 255];
+
+/// Sectors clockwise from the top, with their label.
+const ENTRIES: [(&str, Action); 8] = [
+	("Undo", Action::Undo),
+	("Redo", Action::Redo),
+	("Zoom+", Action::Zoom(1)),
+	("Eraser", Action::ToggleEraser),
+	("Swap", Action::SwapColors),
+	("Zoom-", Action::Zoom(-1)),
+	("Pick", Action::EyedropperMode(ColorSlot::Foreground)),
+	("Mixer", Action::ToggleMixer),
+];
+
+/// Menu of common actions around the cursor, picked by releasing the key or button over one.
+pub struct RadialMenu {
+	center: Point,
+	hovered: Option<usize>,
+}
+
+impl RadialMenu {
+	pub fn new(center: Point) -> Self {
+		Self { center, hovered: None }
+	}
+
+	pub fn rect(&self) -> Rect {
+		let r = RADIUS as i32;
+		return Rect::new(self.center.x - r, self.center.y - r, 2 * RADIUS, 2 * RADIUS);
+	}
+
+	/// Sector `offset` from the center is in, None in the dead zone. Picking doesn't stop at
+	/// the outer edge so quick flicks still land.
+	fn sector_at(offset: [f32; 2]) -> Option<usize> {
+		let [x, y] = offset;
+		if x.hypot(y) < DEAD_ZONE {
+			return None;
+		}
+		let sector = TAU / ENTRIES.len() as f32;
+		let angle = (x.atan2(-y) + sector / 2.).rem_euclid(TAU);
+		return Some((angle / sector) as usize % ENTRIES.len());
+	}
+
+	/// Returns if the hovered sector changed.
+	pub fn hover(&mut self, p: Point) -> bool {
+		let hovered = Self::sector_at([(p.x - self.center.x) as f32, (p.y - self.center.y) as f32]);
+		let changed = hovered != self.hovered;
+		self.hovered = hovered;
+		return changed;
+	}
+
+	pub fn action(&self) -> Option<Action> {
+		return self.hovered.map(|i| ENTRIES[i].1.clone());
+	}
+
+	pub fn render(&self) -> Pixels {
+		let size = Size { w: 2 * RADIUS, h: 2 * RADIUS };
+		let mut pixels = Pixels::new(size);
+		let center = RADIUS as f32;
+		let sector = TAU / ENTRIES.len() as f32;
+
+		for y in 0..size.h {
+			for x in 0..size.w {
+				let offset = [x as f32 + 0.5 - center, y as f32 + 0.5 - center];
+				let distance = offset[0].hypot(offset[1]);
+				if distance > RADIUS as f32 {
+					continue;
+				}
+				let Some(i) = Self::sector_at(offset) else {
+					continue;
+				};
+
+				// Distance to the closest sector edge, along the arc
+				let angle = (offset[0].atan2(-offset[1]) + sector / 2.).rem_euclid(sector);
+				if angle.min(sector - angle) * distance < GAP / 2. {
+					continue;
+				}
+				let color = if Some(i) == self.hovered { HOVERED } else { BACKGROUND };
+				font::fill(&mut pixels, x, y, 1, 1, color);
+			}
+		}
+
+		for (i, (label, _)) in ENTRIES.iter().enumerate() {
+			let angle = i as f32 * sector;
+			let text = font::text_size(label, SCALE);
+			let x = center + angle.sin() * LABEL_RADIUS - text.w as f32 / 2.;
+			let y = center - angle.cos() * LABEL_RADIUS - text.h as f32 / 2.;
+			font::draw_text(&mut pixels, x as u32, y as u32, label, TEXT, SCALE);
+		}
+		return pixels;
+	}
+}
@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+
+/// Gaps between input events longer than this are idle time and aren't tracked.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Active time spent on a document, for artists billing by tracked hours.
+pub struct SessionTimer {
+	/// Tracked in earlier sessions, read from the document
+	previous: Duration,
+	tracked: Duration,
+	last_activity: Option<Instant>,
+}
+
+impl SessionTimer {
+	pub fn new(previous: Duration) -> Self {
+		Self {
+			previous,
+			tracked: Duration::ZERO,
+			last_activity: None,
+		}
+	}
+
+	/// Called on user input, the time since the previous input counts unless it was idle.
+	pub fn activity(&mut self) {
+		let now = Instant::now();
+		if let Some(last) = self.last_activity {
+			let gap = now - last;
+			if gap < IDLE_TIMEOUT {
+				self.tracked += gap;
+			}
+		}
+		self.last_activity = Some(now);
+	}
+
+	/// Stops tracking until the next input, when the window loses focus.
+	pub fn pause(&mut self) {
+		self.last_activity = None;
+	}
+
+	pub fn total(&self) -> Duration {
+		self.previous + self.tracked
+	}
+}
+
+/// Hours and minutes, like commissions are billed.
+pub fn format_duration(d: Duration) -> String {
+	let minutes = d.as_secs() / 60;
+	format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
@@ -0,0 +1,111 @@
+use std::{
+	fmt,
+	fs::File,
+	io::{self, Read, Write},
+	path::{Path, PathBuf},
+};
+
+use crate::{actions, config, devices, export, guides, presets, pressure, swatches, workspace};
+use crate::settings::preferences;
+
+/// Config files a bundle carries: brushes, palettes, the workspace color and profiles, key
+/// bindings, export presets, input devices, pressure curves, guides and preferences.
+const FILES: [&str; 10] = [
+	presets::PRESETS_FILE,
+	swatches::SWATCHES_FILE,
+	workspace::BACKGROUND_FILE,
+	workspace::PROFILES_FILE,
+	actions::KEYMAP_FILE,
+	export::PRESETS_FILE,
+	devices::SETTINGS_FILE,
+	pressure::CURVES_FILE,
+	guides::GUIDES_FILE,
+	preferences::PREFERENCES_FILE,
+];
+
+#[derive(Debug)]
+pub enum BundleError {
+	Io(io::Error),
+	Zip(zip::result::ZipError),
+	/// There is no home directory to keep the settings in
+	NoConfigDir,
+}
+
+impl fmt::Display for BundleError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			BundleError::Io(e) => write!(f, "{e}"),
+			BundleError::Zip(e) => write!(f, "not a valid settings bundle: {e}"),
+			BundleError::NoConfigDir => write!(f, "no config directory"),
+		}
+	}
+}
+
+impl From<io::Error> for BundleError {
+	fn from(e: io::Error) -> Self {
+		BundleError::Io(e)
+	}
+}
+
+impl From<zip::result::ZipError> for BundleError {
+	fn from(e: zip::result::ZipError) -> Self {
+		BundleError::Zip(e)
+	}
+}
+
+/// Where bundles are exported to and imported from.
+pub fn default_path() -> PathBuf {
+	PathBuf::from("pntr-settings.zip")
+}
+
+/// Zips the config files that exist into `path`, returns how many there were.
+pub fn export(path: &Path) -> Result<usize, BundleError> {
+	let dir = config::config_dir().ok_or(BundleError::NoConfigDir)?;
+	let mut zip = zip::ZipWriter::new(File::create(path)?);
+	let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+	let mut count = 0;
+	for name in FILES {
+		let text = match std::fs::read(dir.join(name)) {
+			Ok(text) => text,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+			Err(e) => return Err(e.into()),
+		};
+		zip.start_file(name, options)?;
+		zip.write_all(&text)?;
+		count += 1;
+	}
+	zip.finish()?.sync_all()?;
+	return Ok(count);
+}
+
+/// Replaces the config files with those in the bundle at `path`, leaving the others alone.
+/// Returns how many were replaced, they apply once loaded again.
+pub fn import(path: &Path) -> Result<usize, BundleError> {
+	let dir = config::config_dir().ok_or(BundleError::NoConfigDir)?;
+	let mut zip = zip::ZipArchive::new(File::open(path)?)?;
+
+	// Read everything first, so a broken bundle doesn't leave the settings half replaced
+	let mut files = Vec::new();
+	for name in FILES {
+		let mut file = match zip.by_name(name) {
+			Ok(file) => file,
+			Err(zip::result::ZipError::FileNotFound) => continue,
+			Err(e) => return Err(e.into()),
+		};
+		let mut text = Vec::new();
+		file.read_to_end(&mut text)?;
+		files.push((name, text));
+	}
+	if files.len() < zip.len() {
+		log::warn!("Skipped {} unknown files in {}", zip.len() - files.len(), path.display());
+	}
+
+	std::fs::create_dir_all(&dir)?;
+	for (name, text) in &files {
+		let tmp = dir.join(format!("{name}.tmp"));
+		std::fs::write(&tmp, text)?;
+		std::fs::rename(&tmp, dir.join(name))?;
+	}
+	return Ok(files.len());
+}
@@ -0,0 +1,153 @@
+use std::{
+	sync::atomic::{AtomicU64, Ordering},
+	time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::components::{Size, MAX_CANVAS_SIDE};
+use crate::config;
+use crate::history;
+
+pub const PREFERENCES_FILE: &str = "preferences.toml";
+pub const MIN_FPS: u32 = 10;
+pub const MAX_FPS: u32 = 480;
+pub const MIN_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(10);
+pub const MAX_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(3600);
+pub const MAX_HISTORY_DEPTH: usize = 1000;
+/// In MiB, how the preferences file has it
+pub const MIN_HISTORY_BUDGET: u64 = 16;
+pub const MAX_HISTORY_BUDGET: u64 = 16 << 10;
+
+/// Times preferences were saved by this process, windows reload them when it changes
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Colors of the workspace around the document.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+	Dark,
+	Grey,
+	Light,
+}
+
+impl Theme {
+	pub const ALL: [Theme; 3] = [Theme::Dark, Theme::Grey, Theme::Light];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Theme::Dark => "Dark",
+			Theme::Grey => "Grey",
+			Theme::Light => "Light",
+		}
+	}
+
+	/// Around the document, unless the workspace background file sets a color.
+	pub fn background(&self) -> [u8; 3] {
+		match self {
+			Theme::Dark => [26, 26, 26],
+			Theme::Grey => [96, 96, 100],
+			Theme::Light => [208, 208, 212],
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Preferences {
+	/// Frames per second windows are drawn at, at most
+	pub fps: u32,
+	/// Of the document new windows start with
+	pub canvas_size: Size,
+	pub theme: Theme,
+	/// Between autosaves of a document that keeps changing
+	pub autosave_interval: Duration,
+	/// Edits that can be undone, at most
+	pub history_depth: usize,
+	/// GPU memory in bytes the undo history may take
+	pub history_budget: u64,
+}
+
+impl Default for Preferences {
+	fn default() -> Self {
+		Self {
+			fps: 144,
+			canvas_size: Size { w: 2000, h: 2000 },
+			theme: Theme::Dark,
+			autosave_interval: Duration::from_secs(60),
+			history_depth: history::HISTORY_DEPTH,
+			history_budget: history::HISTORY_BUDGET,
+		}
+	}
+}
+
+/// What the preferences file holds, settings left out of it keep their defaults.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct PreferencesFile {
+	fps: u32,
+	canvas_width: u32,
+	canvas_height: u32,
+	theme: Theme,
+	autosave_seconds: u64,
+	history_depth: usize,
+	history_budget_mib: u64,
+}
+
+impl Default for PreferencesFile {
+	fn default() -> Self {
+		Self::from(&Preferences::default())
+	}
+}
+
+impl From<&Preferences> for PreferencesFile {
+	fn from(p: &Preferences) -> Self {
+		Self {
+			fps: p.fps,
+			canvas_width: p.canvas_size.w,
+			canvas_height: p.canvas_size.h,
+			theme: p.theme,
+			autosave_seconds: p.autosave_interval.as_secs(),
+			history_depth: p.history_depth,
+			history_budget_mib: p.history_budget >> 20,
+		}
+	}
+}
+
+/// `value` when it's `valid`, the default otherwise.
+fn checked<T: std::fmt::Debug>(name: &str, value: T, valid: bool, default: T) -> T {
+	if !valid {
+		log::warn!("Ignoring invalid preference {name} = {value:?}");
+		return default;
+	}
+	return value;
+}
+
+/// Least time between two frames at `fps`.
+pub fn frametime(fps: u32) -> Duration {
+	Duration::from_nanos(1_000_000_000 / fps.clamp(MIN_FPS, MAX_FPS) as u64)
+}
+
+/// Bumped every time preferences are saved.
+pub fn generation() -> u64 {
+	GENERATION.load(Ordering::Relaxed)
+}
+
+/// Preferences from the TOML file in the config directory, missing and out of range ones keep their default.
+pub fn load() -> Preferences {
+	let defaults = Preferences::default();
+	let file: PreferencesFile = config::read_toml(PREFERENCES_FILE).unwrap_or_default();
+	let sides = (1..=MAX_CANVAS_SIDE).contains(&file.canvas_width) && (1..=MAX_CANVAS_SIDE).contains(&file.canvas_height);
+	let autosave = Duration::from_secs(file.autosave_seconds);
+	return Preferences {
+		fps: checked("fps", file.fps, (MIN_FPS..=MAX_FPS).contains(&file.fps), defaults.fps),
+		canvas_size: checked("canvas size", Size { w: file.canvas_width, h: file.canvas_height }, sides, defaults.canvas_size),
+		theme: file.theme,
+		autosave_interval: checked("autosave interval", autosave, (MIN_AUTOSAVE_INTERVAL..=MAX_AUTOSAVE_INTERVAL).contains(&autosave), defaults.autosave_interval),
+		history_depth: checked("history depth", file.history_depth, (1..=MAX_HISTORY_DEPTH).contains(&file.history_depth), defaults.history_depth),
+		history_budget: checked("history budget", file.history_budget_mib, (MIN_HISTORY_BUDGET..=MAX_HISTORY_BUDGET).contains(&file.history_budget_mib), defaults.history_budget >> 20) << 20,
+	};
+}
+
+pub fn save(preferences: &Preferences) {
+	config::write_toml(PREFERENCES_FILE, &PreferencesFile::from(preferences));
+	GENERATION.fetch_add(1, Ordering::Relaxed);
+}
@@ -0,0 +1,274 @@
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+
+use async_trait::async_trait;
+use winit::{event::{ModifiersState, VirtualKeyCode, WindowEvent}, event_loop::EventLoopWindowTarget, window::Window};
+
+use crate::components::{self, widget_text_top, Component, Control, Event, Point, Rect, Size, Widget, WidgetRouter, WIDGET_SCALE, WIDGET_TEXT};
+use crate::export::Pixels;
+use crate::font;
+use crate::framelimiter::FrameLimiter;
+use crate::layout::{self, Layout, LayoutContext, WindowLifeStatus};
+use crate::settings::preferences::{self, Preferences, Theme};
+use crate::CustomEvents;
+
+const FIELDS: [Control; 4] = [Control::FpsCap, Control::CanvasWidth, Control::CanvasHeight, Control::AutosaveInterval];
+const MARGIN: u32 = 16;
+const LABEL_WIDTH: u32 = 240;
+const FIELD_WIDTH: u32 = 140;
+const ROW_HEIGHT: u32 = 24;
+const ROW_GAP: u32 = 8;
+const THEME_MESSAGE: &str = "preferences.theme";
+
+/// A preferences window is open, there is only one
+static OPEN: AtomicBool = AtomicBool::new(false);
+
+/// Row of a control, the theme dropdown after the number fields.
+type Row = usize;
+
+/// Edits the preferences, saving them on every change. Windows take them up on their next update,
+/// the frame limiter right away.
+pub struct PreferencesWindow {
+	window: Arc<Window>,
+	surface: wgpu::Surface,
+	config: wgpu::SurfaceConfiguration,
+	ctx: components::Context,
+	preferences: Preferences,
+	fields: Vec<components::NumberField>,
+	theme: Box<components::Dropdown>,
+	/// Of the fields and then the theme dropdown
+	labels: Vec<components::Image>,
+	router: WidgetRouter<Row>,
+	mouse: Option<Point>,
+	modifiers: ModifiersState,
+	close: bool,
+}
+
+impl PreferencesWindow {
+	pub fn is_open() -> bool {
+		OPEN.load(Ordering::Relaxed)
+	}
+
+	fn row_rect(row: Row) -> Rect {
+		let top = MARGIN + row as u32 * (ROW_HEIGHT + ROW_GAP);
+		return Rect::new((MARGIN + LABEL_WIDTH) as i32, top as i32, FIELD_WIDTH, ROW_HEIGHT);
+	}
+
+	/// Room for every row, and the theme options below the last one.
+	fn window_size() -> Size {
+		let rows = FIELDS.len() as u32 + 1 + Theme::ALL.len() as u32;
+		return Size { w: 2 * MARGIN + LABEL_WIDTH + FIELD_WIDTH, h: 2 * MARGIN + rows * (ROW_HEIGHT + ROW_GAP) };
+	}
+
+	fn value(&self, control: Control) -> f32 {
+		match control {
+			Control::FpsCap => self.preferences.fps as f32,
+			Control::CanvasWidth => self.preferences.canvas_size.w as f32,
+			Control::CanvasHeight => self.preferences.canvas_size.h as f32,
+			Control::AutosaveInterval => self.preferences.autosave_interval.as_secs() as f32,
+			Control::BrushSize | Control::BrushOpacity | Control::Zoom => 0.,
+		}
+	}
+
+	/// Calls `f` with the widgets, topmost last.
+	fn route_widgets<R>(&mut self, f: impl FnOnce(&mut WidgetRouter<Row>, &mut components::Widgets<Row>, &mut components::Context) -> R) -> R {
+		let theme_rect = self.theme.rect(Self::row_rect(FIELDS.len()));
+		let mut widgets: Vec<(Row, Rect, &mut dyn Widget)> = Vec::new();
+		for (row, field) in self.fields.iter_mut().enumerate() {
+			widgets.push((row, Self::row_rect(row), field));
+		}
+		widgets.push((FIELDS.len(), theme_rect, self.theme.as_mut()));
+		return f(&mut self.router, &mut widgets, &mut self.ctx);
+	}
+
+	/// Saves what the widgets changed.
+	fn apply_events(&mut self, frame_limiter: &FrameLimiter) {
+		let before = self.preferences;
+		for event in self.ctx.take_events() {
+			match event {
+				Event::ValueChanged(Control::FpsCap, fps) => self.preferences.fps = fps as u32,
+				Event::ValueChanged(Control::CanvasWidth, w) => self.preferences.canvas_size.w = w as u32,
+				Event::ValueChanged(Control::CanvasHeight, h) => self.preferences.canvas_size.h = h as u32,
+				Event::ValueChanged(Control::AutosaveInterval, s) => self.preferences.autosave_interval = std::time::Duration::from_secs(s as u64),
+				Event::Selected(THEME_MESSAGE, i) => self.preferences.theme = Theme::ALL[i],
+				_ => (),
+			}
+		}
+		if self.preferences == before {
+			return;
+		}
+		if self.preferences.fps != before.fps {
+			frame_limiter.set_fps(self.preferences.fps);
+		}
+		preferences::save(&self.preferences);
+		let values: Vec<f32> = FIELDS.iter().map(|c| self.value(*c)).collect();
+		for (field, value) in self.fields.iter_mut().zip(values) {
+			field.set_value(value);
+		}
+	}
+
+	fn label(ctx: &mut components::Context, text: &str) -> components::Image {
+		let mut pixels = Pixels::new(Size { w: LABEL_WIDTH, h: ROW_HEIGHT });
+		font::draw_text(&mut pixels, 0, widget_text_top(ROW_HEIGHT), text, WIDGET_TEXT, WIDGET_SCALE);
+		let mut image = components::Image::new(ctx);
+		let tex = ctx.upload_texture("Preferences(Label Texture)", &pixels, wgpu::TextureUsages::STORAGE_BINDING);
+		image.set_texture(ctx, tex);
+		return *image;
+	}
+}
+
+#[async_trait]
+impl Layout for PreferencesWindow {
+	fn init() -> LayoutContext {
+		LayoutContext { wgpu: Some(wgpu::Instance::new(wgpu::Backends::all())), ..Default::default() }
+	}
+
+	async fn new(layout_ctx: LayoutContext, window: Arc<Window>) -> Box<Self> {
+		let size = Self::window_size();
+		window.set_title("pntr preferences");
+		window.set_inner_size(winit::dpi::PhysicalSize::new(size.w, size.h));
+		window.set_resizable(false);
+		let instance = layout_ctx.wgpu.expect("Generated with wrong context");
+		let (surface, config, mut ctx, _) = layout::connect_gpu(&instance, &window, winit::dpi::PhysicalSize::new(size.w, size.h)).await.expect("Could not get adapter");
+
+		let mut labels: Vec<components::Image> = FIELDS.iter().map(|c| Self::label(&mut ctx, c.label())).collect();
+		labels.push(Self::label(&mut ctx, "Theme"));
+		let mut layout = Box::new(Self {
+			window,
+			surface,
+			config,
+			preferences: preferences::load(),
+			fields: FIELDS.iter().map(|_| *components::NumberField::new(&mut ctx)).collect(),
+			theme: components::Dropdown::new(&mut ctx),
+			labels,
+			ctx,
+			router: WidgetRouter::new(),
+			mouse: None,
+			modifiers: ModifiersState::empty(),
+			close: false,
+		});
+		for (i, control) in FIELDS.iter().enumerate() {
+			let value = layout.value(*control);
+			layout.fields[i].set_control(*control);
+			layout.fields[i].set_value(value);
+		}
+		let names: Vec<&str> = Theme::ALL.iter().map(Theme::name).collect();
+		layout.theme.set_options(&names, THEME_MESSAGE);
+		layout.theme.set_selected(Theme::ALL.iter().position(|t| *t == layout.preferences.theme).unwrap_or(0));
+		OPEN.store(true, Ordering::Relaxed);
+		return layout;
+	}
+
+	fn window(&self) -> Arc<Window> {
+		self.window.clone()
+	}
+
+	fn render(&mut self) {
+		let output = match self.surface.get_current_texture() {
+			Ok(output) => output,
+			Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+				self.surface.configure(&self.ctx.device, &self.config);
+				self.window.request_redraw();
+				return;
+			}
+			Err(e) => {
+				log::warn!("Could not draw the preferences: {e:?}");
+				return;
+			}
+		};
+		let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+		let mut encoder = self.ctx.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Preferences(Render Encoder)"),
+		});
+		encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("Preferences(Clear Pass)"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: &view,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Clear(layout::load_workspace_color(self.config.format, self.preferences.theme)),
+					store: true,
+				}
+			})],
+			depth_stencil_attachment: None,
+		});
+
+		for (row, label) in self.labels.iter_mut().enumerate() {
+			let r = Self::row_rect(row);
+			label.render(&mut encoder, &mut self.ctx, &view, Rect::new(MARGIN as i32, r.pos.y, LABEL_WIDTH, ROW_HEIGHT), None);
+		}
+		for (row, field) in self.fields.iter_mut().enumerate() {
+			field.render(&mut encoder, &mut self.ctx, &view, Self::row_rect(row), None);
+		}
+		// Last, its options go over what is below
+		let theme_rect = self.theme.rect(Self::row_rect(FIELDS.len()));
+		self.theme.render(&mut encoder, &mut self.ctx, &view, theme_rect, None);
+
+		self.ctx.staging_belt.finish();
+		self.ctx.queue.submit(std::iter::once(encoder.finish()));
+		self.ctx.staging_belt.recall();
+		self.ctx.buffer_pool.reset();
+		self.ctx.uniform_pool.reset();
+		output.present();
+	}
+
+	fn update(&mut self, _: &EventLoopWindowTarget<CustomEvents>) -> (WindowLifeStatus, Option<Box<dyn Layout>>) {
+		if self.close {
+			OPEN.store(false, Ordering::Relaxed);
+			return (WindowLifeStatus::Dead, None);
+		}
+		return (WindowLifeStatus::Alive, None);
+	}
+
+	fn event_handler(&mut self, event: WindowEvent, frame_limiter: &FrameLimiter) {
+		use winit::event::ElementState;
+
+		match event {
+			WindowEvent::CloseRequested => {
+				// Commits what is being typed
+				self.route_widgets(|router, widgets, ctx| router.focus(ctx, widgets, None));
+				self.close = true;
+			}
+			WindowEvent::Resized(size) if size.width > 0 && size.height > 0 => {
+				(self.config.width, self.config.height) = (size.width, size.height);
+				self.surface.configure(&self.ctx.device, &self.config);
+			}
+			WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers,
+			WindowEvent::CursorMoved { position, .. } => {
+				let p: Point = position.into();
+				self.mouse = Some(p);
+				self.route_widgets(|router, widgets, ctx| router.drag(ctx, widgets, p) | router.hover(widgets, p));
+			}
+			WindowEvent::CursorLeft { .. } => self.mouse = None,
+			WindowEvent::MouseInput { state, button: winit::event::MouseButton::Left, .. } => {
+				let p = self.mouse;
+				self.route_widgets(|router, widgets, ctx| match state {
+					ElementState::Pressed => p.is_some_and(|p| router.press(ctx, widgets, p)),
+					ElementState::Released => router.release(ctx, widgets),
+				});
+			}
+			WindowEvent::ReceivedCharacter(c) => {
+				self.route_widgets(|router, widgets, ctx| router.text_input(ctx, widgets, c));
+			}
+			WindowEvent::KeyboardInput { input: winit::event::KeyboardInput { state, virtual_keycode: Some(key), .. }, .. } => {
+				match (key, state) {
+					(VirtualKeyCode::Tab, ElementState::Pressed) => {
+						let reverse = self.modifiers.shift();
+						self.route_widgets(|router, widgets, ctx| router.cycle_focus(ctx, widgets, reverse));
+					}
+					(VirtualKeyCode::Tab, ElementState::Released) | (_, ElementState::Pressed) => (),
+					(key, ElementState::Released) => {
+						let used = self.route_widgets(|router, widgets, ctx| router.key_input(ctx, widgets, key));
+						if !used && key == VirtualKeyCode::Escape {
+							self.close = true;
+						}
+					}
+				}
+			}
+			_ => return,
+		}
+		self.apply_events(frame_limiter);
+		frame_limiter.schedule_redraw(self.window.id());
+	}
+
+	fn idle(&mut self, _: &FrameLimiter) {}
+}
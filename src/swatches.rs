@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use crate::config;
+
+pub const SWATCHES_FILE: &str = "swatches";
+/// Recent colors kept, the oldest goes first
+pub const MAX_RECENT: usize = 12;
+pub const MAX_PINNED: usize = 32;
+
+/// Colors painted with lately, and the ones kept at hand on purpose.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SwatchSet {
+	/// Newest first
+	pub recent: Vec<[u8; 3]>,
+	pub pinned: Vec<[u8; 3]>,
+}
+
+impl SwatchSet {
+	/// Moves `color` to the front of the recent colors. Returns if they changed.
+	pub fn use_color(&mut self, color: [u8; 3]) -> bool {
+		if self.recent.first() == Some(&color) {
+			return false;
+		}
+		self.recent.retain(|c| *c != color);
+		self.recent.insert(0, color);
+		self.recent.truncate(MAX_RECENT);
+		return true;
+	}
+
+	/// Pins `color`, or unpins it when it already is. Returns if it is pinned now.
+	pub fn toggle_pin(&mut self, color: [u8; 3]) -> bool {
+		if let Some(i) = self.pinned.iter().position(|c| *c == color) {
+			self.pinned.remove(i);
+			return false;
+		}
+		if self.pinned.len() >= MAX_PINNED {
+			log::warn!("Already {MAX_PINNED} pinned colors, unpin one first");
+			return false;
+		}
+		self.pinned.push(color);
+		return true;
+	}
+
+	/// Pins the colors not pinned yet, as many as there is room for. Returns how many were.
+	pub fn pin_all(&mut self, colors: &[[u8; 3]]) -> usize {
+		let before = self.pinned.len();
+		for color in colors {
+			if self.pinned.len() < MAX_PINNED && !self.pinned.contains(color) {
+				self.pinned.push(*color);
+			}
+		}
+		return self.pinned.len() - before;
+	}
+}
+
+/// Colors of a GIMP palette, `R G B name` lines after the `GIMP Palette` header. Names, the
+/// `Name:` and `Columns:` lines and `#` comments are skipped.
+pub fn parse_gpl(text: &str) -> Option<Vec<[u8; 3]>> {
+	let mut lines = text.lines();
+	if lines.next()?.trim() != "GIMP Palette" {
+		return None;
+	}
+	let mut colors = Vec::new();
+	for line in lines.map(str::trim) {
+		if line.is_empty() || line.starts_with('#') || line.starts_with("Name:") || line.starts_with("Columns:") {
+			continue;
+		}
+		let mut fields = line.split_whitespace();
+		let mut channel = || fields.next()?.parse::<u8>().ok();
+		let (Some(r), Some(g), Some(b)) = (channel(), channel(), channel()) else {
+			log::warn!("Ignoring invalid palette line {line:?}");
+			continue;
+		};
+		colors.push([r, g, b]);
+	}
+	return Some(colors);
+}
+
+pub fn load_gpl(path: &Path) -> Option<Vec<[u8; 3]>> {
+	let text = match std::fs::read_to_string(path) {
+		Ok(text) => text,
+		Err(e) => {
+			log::warn!("Could not read {}: {e}", path.display());
+			return None;
+		}
+	};
+	let colors = parse_gpl(&text);
+	if colors.is_none() {
+		log::warn!("{} isn't a GIMP palette", path.display());
+	}
+	return colors;
+}
+
+/// Swatches from the config file, `recent<TAB>#rrggbb` and `pinned<TAB>#rrggbb` lines in order.
+pub fn load_swatches() -> SwatchSet {
+	let lines = config::read_lines(SWATCHES_FILE, "swatch", |line| {
+		let (kind, hex) = line.split_once('\t').filter(|(kind, _)| ["recent", "pinned"].contains(kind))?;
+		return Some((kind == "pinned", config::parse_hex_color(hex)?));
+	});
+	let mut swatches = SwatchSet::default();
+	for (pinned, color) in lines.unwrap_or_default() {
+		match pinned {
+			false if swatches.recent.len() < MAX_RECENT => swatches.recent.push(color),
+			true if swatches.pinned.len() < MAX_PINNED => swatches.pinned.push(color),
+			_ => log::warn!("Ignoring swatch #{:02x}{:02x}{:02x} past the most kept", color[0], color[1], color[2]),
+		}
+	}
+	return swatches;
+}
+
+pub fn save_swatches(swatches: &SwatchSet) {
+	let line = |kind: &str, [r, g, b]: [u8; 3]| format!("{kind}\t#{r:02x}{g:02x}{b:02x}\n");
+	let text: String = swatches.recent.iter().map(|c| line("recent", *c)).chain(swatches.pinned.iter().map(|c| line("pinned", *c))).collect();
+	config::write_file(SWATCHES_FILE, &text);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reads_colors_after_the_header() {
+		let text = "GIMP Palette\nName: Test\nColumns: 4\n# A comment\n\n255 0 0 Red\n  0 128 255\tBlue\n";
+		assert_eq!(parse_gpl(text), Some(vec![[255, 0, 0], [0, 128, 255]]));
+	}
+
+	#[test]
+	fn needs_the_header() {
+		assert_eq!(parse_gpl("255 0 0 Red"), None);
+		assert_eq!(parse_gpl(""), None);
+	}
+
+	#[test]
+	fn skips_invalid_lines() {
+		assert_eq!(parse_gpl("GIMP Palette\n300 0 0\n1 2\n1 2 3"), Some(vec![[1, 2, 3]]));
+	}
+}
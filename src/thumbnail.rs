@@ -0,0 +1,24 @@
+use std::path::Path;
+
+use crate::document;
+use crate::export::Pixels;
+
+const DEFAULT_SIZE: u32 = 128;
+const USAGE: &str = "usage: pntr --thumbnail <input.pntr> <output.png> [size]";
+
+/// `pntr --thumbnail <input.pntr> <output.png> [size]`, the freedesktop thumbnailer entry point.
+pub fn run(args: &[String]) -> Result<(), String> {
+	let (input, output) = match args {
+		[input, output, ..] => (Path::new(input), Path::new(output)),
+		_ => return Err(USAGE.to_string()),
+	};
+	let size = match args.get(2) {
+		Some(size) => size.parse::<u32>().ok().filter(|s| *s > 0).ok_or_else(|| format!("invalid size {size:?}, {USAGE}"))?,
+		None => DEFAULT_SIZE,
+	};
+
+	let preview = document::read_preview(input).map_err(|e| format!("{}: {e}", input.display()))?;
+	let thumbnail = image::DynamicImage::ImageRgba8(preview.to_image()).thumbnail(size, size);
+	Pixels::from_image(thumbnail.into_rgba8()).save_png(output).map_err(|e| format!("{}: {e}", output.display()))?;
+	return Ok(());
+}
@@ -0,0 +1,1028 @@
+use crate::components::{anchored_rect, transformed_bounds, Canvas, ColorSlot, Context, Point, Rect, Size, MAX_CANVAS_SIDE};
+use crate::export::Pixels;
+use crate::font;
+use crate::glyphs::GlyphRenderer;
+use crate::hud::TransformHud;
+use crate::math::Mat3;
+use crate::pressure::{self, PressureCurve};
+
+const HANDLE_DISTANCE: i32 = 8;
+/// How far above the top edge of the transform box its rotation handle is
+const ROTATE_HANDLE_DISTANCE: f32 = 24.;
+/// Smallest scale the transform tool shrinks to, so it can still be inverted
+const MIN_TRANSFORM_SCALE: f32 = 0.01;
+
+/// Modal interaction over the canvas, gets pointer input before the canvas does.
+pub trait Tool {
+	fn name(&self) -> &'static str;
+
+	/// Returns if the press was used by the tool.
+	fn mouse_down(&mut self, _: Point, _: &mut Canvas, _: &mut Context) -> bool;
+
+	/// Returns if the window should be redrawn.
+	fn mouse_move(&mut self, _: Point, _: &mut Canvas, _: &mut Context) -> bool;
+
+	fn mouse_up(&mut self, _: &mut Canvas, _: &mut Context);
+
+	/// Called on Enter, returns if the tool is done.
+	fn confirm(&mut self, _: &mut Canvas, _: &mut Context) -> bool {
+		true
+	}
+
+	/// Called on Escape, before the tool is dropped.
+	fn cancel(&mut self, _: &mut Canvas) {}
+
+	/// Presses and moves the tool doesn't use go on to paint the canvas.
+	fn paints_through(&self) -> bool {
+		false
+	}
+
+	/// The coming release changes pixels, recorded in the history under the tool name.
+	fn edits_on_release(&self) -> bool {
+		false
+	}
+
+	/// Typed characters, returns if the window should be redrawn.
+	fn text_input(&mut self, _: char, _: &mut Context) -> bool {
+		false
+	}
+
+	/// Numeric fields shown while the tool is active.
+	fn hud(&self) -> Option<&TransformHud> {
+		None
+	}
+
+	/// Slot presses set to the color under them, for tools picking colors.
+	fn picks_color(&self) -> Option<ColorSlot> {
+		None
+	}
+
+	/// Keys without modifiers other than Shift type into the tool, their bindings don't run.
+	/// Enter and Escape still confirm and cancel.
+	fn captures_keys(&self) -> bool {
+		false
+	}
+
+	/// Pixels shown over the document rect until the tool applies them.
+	fn preview(&self) -> Option<(Rect, &Pixels)> {
+		None
+	}
+
+	/// Region of the active layer and the transform it is shown through, until the tool applies it.
+	fn transform_preview(&self) -> Option<(Rect, Mat3)> {
+		None
+	}
+
+	/// Pixels added between glyphs and how far above the line glyphs sit, for tools laying out text.
+	/// Returns if the window should be redrawn.
+	fn set_text_offsets(&mut self, _spacing: i32, _baseline: i32) -> bool {
+		false
+	}
+
+	/// Pixels tall typed text is, for tools typing it. Returns if the window should be redrawn.
+	fn set_text_size(&mut self, _: f32, _: &mut Context) -> bool {
+		false
+	}
+
+	/// Rects outlined over the canvas.
+	fn overlay(&self) -> Vec<Rect>;
+}
+
+fn handle_rect(p: Point) -> Rect {
+	Rect::new(p.x - HANDLE_DISTANCE, p.y - HANDLE_DISTANCE, 2 * HANDLE_DISTANCE as u32, 2 * HANDLE_DISTANCE as u32)
+}
+
+#[derive(Copy, Clone, Default)]
+struct Edges {
+	left: bool,
+	right: bool,
+	top: bool,
+	bottom: bool,
+}
+
+/// Interactive canvas resizing: drag the document edges (or corners) to grow or shrink it.
+pub struct CanvasResize {
+	document: Rect,
+	preview: Rect,
+	dragging: Option<(Edges, Point)>,
+}
+
+impl CanvasResize {
+	pub fn new(document: Rect) -> Self {
+		Self {
+			document,
+			preview: document,
+			dragging: None,
+		}
+	}
+}
+
+impl Tool for CanvasResize {
+	fn name(&self) -> &'static str {
+		"Resize canvas"
+	}
+
+	fn mouse_down(&mut self, p: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		let d = self.document;
+		let near = |a: i32, b: i32| (a - b).abs() <= HANDLE_DISTANCE;
+		let within = |v: i32, start: i32, len: u32| start - HANDLE_DISTANCE <= v && v <= start + len as i32 + HANDLE_DISTANCE;
+
+		let edges = Edges {
+			left: near(p.x, d.pos.x) && within(p.y, d.pos.y, d.size.h),
+			right: near(p.x, d.pos.x + d.size.w as i32) && within(p.y, d.pos.y, d.size.h),
+			top: near(p.y, d.pos.y) && within(p.x, d.pos.x, d.size.w),
+			bottom: near(p.y, d.pos.y + d.size.h as i32) && within(p.x, d.pos.x, d.size.w),
+		};
+
+		if !(edges.left || edges.right || edges.top || edges.bottom) {
+			return false;
+		}
+
+		self.dragging = Some((edges, p));
+		return true;
+	}
+
+	fn mouse_move(&mut self, p: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		let Some((edges, start)) = self.dragging else {
+			return false;
+		};
+
+		let delta = p - start;
+		let d = self.document;
+		let mut left = d.pos.x;
+		let mut top = d.pos.y;
+		let mut right = d.pos.x + d.size.w as i32;
+		let mut bottom = d.pos.y + d.size.h as i32;
+
+		if edges.left { left = std::cmp::min(left + delta.x, right - 1); }
+		if edges.right { right = std::cmp::max(right + delta.x, left + 1); }
+		if edges.top { top = std::cmp::min(top + delta.y, bottom - 1); }
+		if edges.bottom { bottom = std::cmp::max(bottom + delta.y, top + 1); }
+
+		self.preview = Rect::new(left, top, (right - left) as u32, (bottom - top) as u32);
+		return true;
+	}
+
+	fn mouse_up(&mut self, canvas: &mut Canvas, ctx: &mut Context) {
+		if self.dragging.take().is_none() || self.preview == self.document {
+			return;
+		}
+
+		let r = Rect { pos: self.preview.pos - self.document.pos, size: self.preview.size };
+		canvas.resize_document(ctx, r);
+		self.document = Rect { pos: self.document.pos, size: self.preview.size };
+		self.preview = self.document;
+	}
+
+	fn overlay(&self) -> Vec<Rect> {
+		vec![self.preview]
+	}
+}
+
+const CANVAS_SIZE_FIELDS: [&str; 3] = ["Width", "Height", "Anchor"];
+
+/// Width and height of the canvas typed in the HUD, Enter resizes it. The anchor is where the
+/// old content stays, as laid out on the numpad: 7 is the top left corner, 5 the center.
+pub struct CanvasSize {
+	document: Size,
+	hud: TransformHud,
+}
+
+impl CanvasSize {
+	pub fn new(document: Size) -> Self {
+		Self {
+			document,
+			hud: TransformHud::with_fields(&CANVAS_SIZE_FIELDS, vec![document.w as f32, document.h as f32, 5.]),
+		}
+	}
+
+	fn size(&self) -> Size {
+		let [w, h] = [0, 1].map(|i| self.hud.value(i).round().clamp(1., MAX_CANVAS_SIDE as f32) as u32);
+		return Size { w, h };
+	}
+
+	/// 0 to 1 from the top left
+	fn anchor(&self) -> [f32; 2] {
+		let key = self.hud.value(2).round().clamp(1., 9.) as u32 - 1;
+		return [(key % 3) as f32 / 2., 1. - (key / 3) as f32 / 2.];
+	}
+}
+
+impl Tool for CanvasSize {
+	fn name(&self) -> &'static str {
+		"Canvas size"
+	}
+
+	fn mouse_down(&mut self, _: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		false
+	}
+
+	fn mouse_move(&mut self, _: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		false
+	}
+
+	fn mouse_up(&mut self, _: &mut Canvas, _: &mut Context) {}
+
+	fn confirm(&mut self, canvas: &mut Canvas, ctx: &mut Context) -> bool {
+		let size = self.size();
+		if size != self.document {
+			canvas.resize_canvas(ctx, size, self.anchor());
+			log::info!("Resized canvas to {}x{}", size.w, size.h);
+		}
+		return true;
+	}
+
+	fn text_input(&mut self, c: char, _: &mut Context) -> bool {
+		self.hud.input(c);
+		return true;
+	}
+
+	fn hud(&self) -> Option<&TransformHud> {
+		Some(&self.hud)
+	}
+
+	/// Where the new document edges fall against the current ones.
+	fn overlay(&self) -> Vec<Rect> {
+		return vec![anchored_rect(self.document, self.size(), self.anchor())];
+	}
+}
+
+/// Drags the four corners of the selection, or of the document without one, around. Enter maps
+/// what they enclosed onto the new quad.
+/// Typing in the HUD moves, scales and rotates the quad exactly, from where its corners were last dragged.
+pub struct PerspectiveTransform {
+	source: Rect,
+	/// Top left, top right, bottom right, bottom left, where the HUD moves the corners from. The
+	/// source at first, and where they were dragged to after
+	quad: [Point; 4],
+	corners: [Point; 4],
+	dragging: Option<usize>,
+	hud: TransformHud,
+}
+
+impl PerspectiveTransform {
+	pub fn new(source: Rect) -> Self {
+		let (x0, y0) = (source.pos.x, source.pos.y);
+		let (x1, y1) = (x0 + source.size.w as i32, y0 + source.size.h as i32);
+		let mut tool = Self {
+			source,
+			quad: [Point { x: x0, y: y0 }, Point { x: x1, y: y0 }, Point { x: x1, y: y1 }, Point { x: x0, y: y1 }],
+			corners: [Point { x: 0, y: 0 }; 4],
+			dragging: None,
+			hud: TransformHud::new(),
+		};
+		tool.apply_hud();
+		return tool;
+	}
+
+	/// Corners of the quad moved, scaled and rotated around its center as the HUD says.
+	fn apply_hud(&mut self) {
+		let center = [0, 1].map(|k| self.quad.iter().map(|c| [c.x, c.y][k] as f32).sum::<f32>() / 4.);
+		let offset = self.hud.offset();
+		let scale = self.hud.scale();
+		let (sin, cos) = self.hud.angle_radians().sin_cos();
+
+		self.corners = self.quad.map(|c| {
+			let (x, y) = ((c.x as f32 - center[0]) * scale, (c.y as f32 - center[1]) * scale);
+			Point {
+				x: (center[0] + offset.x as f32 + x * cos - y * sin).round() as i32,
+				y: (center[1] + offset.y as f32 + x * sin + y * cos).round() as i32,
+			}
+		});
+	}
+}
+
+impl Tool for PerspectiveTransform {
+	fn name(&self) -> &'static str {
+		"Perspective transform"
+	}
+
+	fn mouse_down(&mut self, p: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		self.dragging = self.corners.iter().position(|c| handle_rect(*c).inside(p));
+		if self.dragging.is_some() {
+			// Typing afterwards goes from the dragged corners instead of putting them back
+			self.quad = self.corners;
+			self.hud = TransformHud::new();
+		}
+		return self.dragging.is_some();
+	}
+
+	fn mouse_move(&mut self, p: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		let Some(i) = self.dragging else {
+			return false;
+		};
+
+		self.corners[i] = p;
+		self.quad[i] = p;
+		return true;
+	}
+
+	fn mouse_up(&mut self, _: &mut Canvas, _: &mut Context) {
+		self.dragging = None;
+	}
+
+	fn confirm(&mut self, canvas: &mut Canvas, ctx: &mut Context) -> bool {
+		if !canvas.warp_perspective(ctx, self.source, self.corners) {
+			log::warn!("Corners don't make a valid quad, keep dragging");
+			return false;
+		}
+		return true;
+	}
+
+	fn text_input(&mut self, c: char, _: &mut Context) -> bool {
+		if self.hud.input(c) {
+			self.apply_hud();
+		}
+		return true;
+	}
+
+	fn hud(&self) -> Option<&TransformHud> {
+		Some(&self.hud)
+	}
+
+	fn overlay(&self) -> Vec<Rect> {
+		self.corners.iter().map(|c| handle_rect(*c)).collect()
+	}
+}
+
+#[derive(Copy, Clone)]
+enum TransformDrag {
+	Move,
+	/// Around the center, along the sides of the box
+	Scale,
+	Rotate,
+}
+
+/// Moves, scales and rotates the selection, or the whole active layer without one, shown on the canvas as it
+/// changes. Dragging inside the box moves it, its corners scale it and the handle above it rotates it.
+/// Enter resamples the pixels where they are shown.
+pub struct Transform {
+	source: Rect,
+	placement: Placement,
+	/// What is dragged, where from, and the placement when it started
+	dragging: Option<(TransformDrag, Point, Placement)>,
+}
+
+/// Where the transform tool shows its source, around the source center.
+#[derive(Copy, Clone)]
+struct Placement {
+	offset: [f32; 2],
+	scale: [f32; 2],
+	/// Radians, clockwise
+	angle: f32,
+}
+
+impl Transform {
+	pub fn new(source: Rect) -> Self {
+		Self {
+			source,
+			placement: Placement { offset: [0., 0.], scale: [1., 1.], angle: 0. },
+			dragging: None,
+		}
+	}
+
+	/// Where the center of the source is shown.
+	fn center(&self) -> [f32; 2] {
+		let s = self.source;
+		return [s.pos.x as f32 + s.size.w as f32 / 2. + self.placement.offset[0], s.pos.y as f32 + s.size.h as f32 / 2. + self.placement.offset[1]];
+	}
+
+	/// Maps document positions of the source to where they are shown.
+	fn matrix(&self) -> Mat3 {
+		let [cx, cy] = self.center();
+		let (sx, sy) = (self.source.pos.x as f32 + self.source.size.w as f32 / 2., self.source.pos.y as f32 + self.source.size.h as f32 / 2.);
+		return Mat3::translate(cx, cy) * Mat3::rotate(self.placement.angle) * Mat3::scale(self.placement.scale[0], self.placement.scale[1]) * Mat3::translate(-sx, -sy);
+	}
+
+	/// Top left, top right, bottom right, bottom left of the box.
+	fn corners(&self) -> [[f32; 2]; 4] {
+		let s = self.source;
+		let (x0, y0) = (s.pos.x as f32, s.pos.y as f32);
+		let (x1, y1) = (x0 + s.size.w as f32, y0 + s.size.h as f32);
+		let m = self.matrix();
+		return [[x0, y0], [x1, y0], [x1, y1], [x0, y1]].map(|p| m.apply(p));
+	}
+
+	fn rotate_handle(&self) -> [f32; 2] {
+		let [a, b, _, _] = self.corners();
+		let (sin, cos) = self.placement.angle.sin_cos();
+		return [(a[0] + b[0]) / 2. + sin * ROTATE_HANDLE_DISTANCE, (a[1] + b[1]) / 2. - cos * ROTATE_HANDLE_DISTANCE];
+	}
+}
+
+fn to_point(p: [f32; 2]) -> Point {
+	Point { x: p[0].round() as i32, y: p[1].round() as i32 }
+}
+
+impl Tool for Transform {
+	fn name(&self) -> &'static str {
+		"Transform"
+	}
+
+	fn mouse_down(&mut self, p: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		let inside = self.matrix().inverse().is_some_and(|inverse| {
+			let [x, y] = inverse.apply([p.x as f32, p.y as f32]);
+			let s = self.source;
+			s.pos.x as f32 <= x && x <= (s.pos.x + s.size.w as i32) as f32 && s.pos.y as f32 <= y && y <= (s.pos.y + s.size.h as i32) as f32
+		});
+		let drag = if handle_rect(to_point(self.rotate_handle())).inside(p) {
+			TransformDrag::Rotate
+		} else if self.corners().iter().any(|c| handle_rect(to_point(*c)).inside(p)) {
+			TransformDrag::Scale
+		} else if inside {
+			TransformDrag::Move
+		} else {
+			return false;
+		};
+		self.dragging = Some((drag, p, self.placement));
+		return true;
+	}
+
+	fn mouse_move(&mut self, p: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		let Some((drag, start, started)) = self.dragging else {
+			return false;
+		};
+		let [cx, cy] = self.center();
+		match drag {
+			TransformDrag::Move => self.placement.offset = [started.offset[0] + (p.x - start.x) as f32, started.offset[1] + (p.y - start.y) as f32],
+			TransformDrag::Scale => {
+				// In the axes of the box, turned with it
+				let (dx, dy) = (p.x as f32 - cx, p.y as f32 - cy);
+				let (sin, cos) = self.placement.angle.sin_cos();
+				let local = [dx * cos + dy * sin, dy * cos - dx * sin];
+				let half = [self.source.size.w as f32 / 2., self.source.size.h as f32 / 2.];
+				self.placement.scale = [0, 1].map(|k| (local[k].abs() / half[k]).max(MIN_TRANSFORM_SCALE));
+			}
+			TransformDrag::Rotate => {
+				let turn = |q: Point| (q.y as f32 - cy).atan2(q.x as f32 - cx);
+				self.placement.angle = started.angle + turn(p) - turn(start);
+			}
+		}
+		return true;
+	}
+
+	fn mouse_up(&mut self, _: &mut Canvas, _: &mut Context) {
+		self.dragging = None;
+	}
+
+	fn confirm(&mut self, canvas: &mut Canvas, ctx: &mut Context) -> bool {
+		if !canvas.transform_region(ctx, self.source, self.matrix()) {
+			log::warn!("Transformed region is off the canvas, keep dragging");
+			return false;
+		}
+		return true;
+	}
+
+	fn cancel(&mut self, _: &mut Canvas) {
+		self.dragging = None;
+	}
+
+	fn transform_preview(&self) -> Option<(Rect, Mat3)> {
+		Some((self.source, self.matrix()))
+	}
+
+	fn overlay(&self) -> Vec<Rect> {
+		let mut overlay = vec![transformed_bounds(self.source, self.matrix()), handle_rect(to_point(self.rotate_handle()))];
+		overlay.extend(self.corners().iter().map(|c| handle_rect(to_point(*c))));
+		return overlay;
+	}
+}
+
+/// Drags out the canvas selection, dragging from inside it moves the selected pixels instead.
+pub struct RectSelect {
+	/// Where the drag started and where it is now
+	dragging: Option<(Point, Point)>,
+	/// Selection being moved, None while selecting
+	moving: Option<Rect>,
+}
+
+impl RectSelect {
+	pub fn new() -> Self {
+		Self { dragging: None, moving: None }
+	}
+
+	/// Rect between the drag ends, at least a pixel wide
+	fn marquee(a: Point, b: Point) -> Rect {
+		let (x, y) = (a.x.min(b.x), a.y.min(b.y));
+		return Rect::new(x, y, (a.x - b.x).unsigned_abs().max(1), (a.y - b.y).unsigned_abs().max(1));
+	}
+}
+
+impl Tool for RectSelect {
+	fn name(&self) -> &'static str {
+		"Select"
+	}
+
+	fn mouse_down(&mut self, p: Point, canvas: &mut Canvas, _: &mut Context) -> bool {
+		self.moving = canvas.selection().filter(|s| s.inside(p));
+		self.dragging = Some((p, p));
+		return true;
+	}
+
+	fn mouse_move(&mut self, p: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		let Some((start, _)) = self.dragging else {
+			return false;
+		};
+		self.dragging = Some((start, p));
+		return true;
+	}
+
+	fn mouse_up(&mut self, canvas: &mut Canvas, ctx: &mut Context) {
+		let Some((start, end)) = self.dragging.take() else {
+			return;
+		};
+		if self.moving.take().is_some() {
+			if end != start {
+				canvas.move_selection(ctx, end - start);
+			}
+			return;
+		}
+		// A click without dragging deselects
+		canvas.set_selection((end != start).then(|| Self::marquee(start, end)));
+	}
+
+	fn cancel(&mut self, _: &mut Canvas) {
+		self.dragging = None;
+		self.moving = None;
+	}
+
+	fn edits_on_release(&self) -> bool {
+		self.moving.is_some()
+	}
+
+	fn overlay(&self) -> Vec<Rect> {
+		let Some((start, end)) = self.dragging else {
+			return Vec::new();
+		};
+		match self.moving {
+			Some(selection) => vec![selection + (end - start)],
+			None => vec![Self::marquee(start, end)],
+		}
+	}
+}
+
+/// Click to flood fill the area around the cursor with the foreground color.
+pub struct Fill {
+	/// Per channel, from 0 to 1
+	tolerance: f32,
+	/// Fills under what is painted already, see `Canvas::flood_fill`
+	behind: bool,
+	pressed: Option<Point>,
+}
+
+impl Fill {
+	pub fn new(tolerance: f32, behind: bool) -> Self {
+		Self { tolerance, behind, pressed: None }
+	}
+}
+
+impl Tool for Fill {
+	fn name(&self) -> &'static str {
+		"Fill"
+	}
+
+	fn mouse_down(&mut self, p: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		self.pressed = Some(p);
+		return true;
+	}
+
+	fn mouse_move(&mut self, _: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		false
+	}
+
+	// Filled on release, so the history records it like the other edits
+	fn mouse_up(&mut self, canvas: &mut Canvas, ctx: &mut Context) {
+		if let Some(p) = self.pressed.take() {
+			canvas.flood_fill(ctx, p, self.tolerance, self.behind);
+		}
+	}
+
+	fn cancel(&mut self, _: &mut Canvas) {
+		self.pressed = None;
+	}
+
+	fn edits_on_release(&self) -> bool {
+		self.pressed.is_some()
+	}
+
+	fn overlay(&self) -> Vec<Rect> {
+		Vec::new()
+	}
+}
+
+/// Click over the canvas to pick the color under the cursor into `slot`.
+pub struct Eyedropper {
+	pub slot: ColorSlot,
+}
+
+impl Tool for Eyedropper {
+	fn name(&self) -> &'static str {
+		"Eyedropper"
+	}
+
+	fn mouse_down(&mut self, p: Point, canvas: &mut Canvas, ctx: &mut Context) -> bool {
+		let Some(pixel) = canvas.pixel_at(ctx, p) else {
+			return false;
+		};
+		canvas.set_color(self.slot, [0, 1, 2].map(|i| pixel[i] as f32 / 255.));
+		return true;
+	}
+
+	fn mouse_move(&mut self, _: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		false
+	}
+
+	fn mouse_up(&mut self, _: &mut Canvas, _: &mut Context) {}
+
+	fn picks_color(&self) -> Option<ColorSlot> {
+		Some(self.slot)
+	}
+
+	fn overlay(&self) -> Vec<Rect> {
+		Vec::new()
+	}
+}
+
+/// Side of the curve editor drawn over the top left of the canvas
+const CURVE_EDITOR_SIDE: u32 = 200;
+const CURVE_SAMPLES: u32 = 24;
+
+/// Editor for the pressure curve of `device`, strokes outside it test the curve being edited.
+pub struct PressureCalibration {
+	device: String,
+	curve: PressureCurve,
+	/// Curve in use before, restored when cancelled
+	original: PressureCurve,
+	editor: Rect,
+	dragging: Option<usize>,
+}
+
+impl PressureCalibration {
+	pub fn new(device: &str, canvas: &Canvas) -> Self {
+		let side = CURVE_EDITOR_SIDE;
+		Self {
+			device: device.to_owned(),
+			curve: canvas.pressure_curve().clone(),
+			original: canvas.pressure_curve().clone(),
+			editor: Rect::new(HANDLE_DISTANCE * 2, HANDLE_DISTANCE * 2, side, side),
+			dragging: None,
+		}
+	}
+
+	/// Input pressure to the right, output up.
+	fn editor_point(&self, [x, y]: [f32; 2]) -> Point {
+		let side = self.editor.size.w as f32;
+		Point {
+			x: self.editor.pos.x + (x * side) as i32,
+			y: self.editor.pos.y + ((1. - y) * side) as i32,
+		}
+	}
+
+	fn curve_point(&self, p: Point) -> [f32; 2] {
+		let side = self.editor.size.w as f32;
+		[(p.x - self.editor.pos.x) as f32 / side, 1. - (p.y - self.editor.pos.y) as f32 / side]
+	}
+}
+
+impl Tool for PressureCalibration {
+	fn name(&self) -> &'static str {
+		"Pressure calibration"
+	}
+
+	fn mouse_down(&mut self, p: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		let near = self.curve.points().iter().position(|c| handle_rect(self.editor_point(*c)).contains(Rect::new(p.x, p.y, 1, 1)));
+		if near.is_some() {
+			self.dragging = near;
+			return true;
+		}
+		if !self.editor.contains(Rect::new(p.x, p.y, 1, 1)) {
+			return false;
+		}
+		self.dragging = self.curve.add_point(self.curve_point(p));
+		return true;
+	}
+
+	fn mouse_move(&mut self, p: Point, canvas: &mut Canvas, _: &mut Context) -> bool {
+		let Some(index) = self.dragging else {
+			return false;
+		};
+		self.curve.move_point(index, self.curve_point(p));
+		canvas.set_pressure_curve(self.curve.clone());
+		return true;
+	}
+
+	fn mouse_up(&mut self, _: &mut Canvas, _: &mut Context) {
+		self.dragging = None;
+	}
+
+	fn confirm(&mut self, canvas: &mut Canvas, _: &mut Context) -> bool {
+		canvas.set_pressure_curve(self.curve.clone());
+		pressure::save_curve(&self.device, &self.curve);
+		log::info!("Saved pressure curve of {}: {}", self.device, self.curve.format());
+		return true;
+	}
+
+	fn cancel(&mut self, canvas: &mut Canvas) {
+		canvas.set_pressure_curve(self.original.clone());
+	}
+
+	fn paints_through(&self) -> bool {
+		true
+	}
+
+	fn overlay(&self) -> Vec<Rect> {
+		let mut rects = vec![self.editor];
+		for i in 0..=CURVE_SAMPLES {
+			let x = i as f32 / CURVE_SAMPLES as f32;
+			let p = self.editor_point([x, self.curve.apply(x)]);
+			rects.push(Rect::new(p.x - 1, p.y - 1, 2, 2));
+		}
+		rects.extend(self.curve.points().iter().map(|c| handle_rect(self.editor_point(*c))));
+		return rects;
+	}
+}
+
+/// Writes the opaque pixels of `source` over the canvas region `r`, starting `offset` in.
+fn paste_opaque(canvas: &mut Canvas, ctx: &mut Context, r: Rect, offset: Point, source: &Pixels) {
+	let mut pixels = canvas.read_region(ctx, r);
+	for y in 0..r.size.h {
+		for x in 0..r.size.w {
+			let src = (((y + offset.y as u32) * source.size.w + x + offset.x as u32) * 4) as usize;
+			if source.data[src + 3] != 0 {
+				let dst = ((y * r.size.w + x) * 4) as usize;
+				pixels.data[dst..dst + 4].copy_from_slice(&source.data[src..src + 4]);
+			}
+		}
+	}
+	canvas.write_region(ctx, r.pos, &pixels);
+}
+
+/// Types text where the canvas is clicked, in the foreground color and its own size. Confirming
+/// draws it on the active layer.
+pub struct Text {
+	origin: Option<Point>,
+	text: String,
+	/// Pixels from the top of a line to the next
+	size: f32,
+	color: [f32; 4],
+	/// Made on the first click, with the context it needs
+	glyphs: Option<GlyphRenderer>,
+	/// Laid out text with room for the caret past the last character
+	extent: Size,
+	/// The text drawn on a transparent background
+	pixels: Pixels,
+}
+
+impl Text {
+	pub fn new(size: f32) -> Self {
+		Self { origin: None, text: String::new(), size, color: [0., 0., 0., 1.], glyphs: None, extent: Size { w: 0, h: 0 }, pixels: Pixels::new(Size { w: 0, h: 0 }) }
+	}
+
+	fn rect(&self) -> Option<Rect> {
+		return Some(Rect { pos: self.origin?, size: self.extent });
+	}
+
+	fn draw(&mut self, ctx: &mut Context) {
+		if self.origin.is_none() {
+			return;
+		}
+		let glyphs = self.glyphs.get_or_insert_with(|| GlyphRenderer::new(ctx));
+		self.extent = glyphs.measure(ctx, &format!("{}_", self.text), self.size);
+		self.pixels = match self.text.is_empty() {
+			true => Pixels::new(self.extent),
+			false => glyphs.render(ctx, &self.text, self.size, self.color, self.extent),
+		};
+	}
+}
+
+impl Tool for Text {
+	fn name(&self) -> &'static str {
+		"Text"
+	}
+
+	fn mouse_down(&mut self, p: Point, canvas: &mut Canvas, ctx: &mut Context) -> bool {
+		self.origin = Some(p);
+		let [r, g, b] = canvas.get_color(ColorSlot::Foreground);
+		self.color = [r, g, b, 1.];
+		self.draw(ctx);
+		return true;
+	}
+
+	fn mouse_move(&mut self, _: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		false
+	}
+
+	fn mouse_up(&mut self, _: &mut Canvas, _: &mut Context) {}
+
+	fn confirm(&mut self, canvas: &mut Canvas, ctx: &mut Context) -> bool {
+		let document = Rect { pos: Point { x: 0, y: 0 }, size: canvas.size() };
+		let Some(r) = self.rect().and_then(|r| r.intersection(document)).filter(|_| !self.text.is_empty()) else {
+			return true;
+		};
+		paste_opaque(canvas, ctx, r, r.pos - self.origin.unwrap(), &self.pixels);
+		return true;
+	}
+
+	fn text_input(&mut self, c: char, ctx: &mut Context) -> bool {
+		if self.origin.is_none() {
+			return false;
+		}
+		match c {
+			'\u{8}' => {
+				self.text.pop();
+			}
+			c if c.is_control() => return false,
+			c => self.text.push(c),
+		}
+		self.draw(ctx);
+		return true;
+	}
+
+	fn set_text_size(&mut self, size: f32, ctx: &mut Context) -> bool {
+		self.size = size;
+		self.draw(ctx);
+		return self.origin.is_some();
+	}
+
+	fn captures_keys(&self) -> bool {
+		self.origin.is_some()
+	}
+
+	fn preview(&self) -> Option<(Rect, &Pixels)> {
+		return Some((self.rect()?, &self.pixels));
+	}
+
+	fn overlay(&self) -> Vec<Rect> {
+		self.rect().into_iter().collect()
+	}
+}
+
+/// Document pixels between the recorded points of a text path
+const PATH_STEP: f32 = 4.;
+
+/// Text laid out along a path dragged on the canvas, each glyph turned with the path under it.
+/// Glyphs past the end of the path are left out.
+pub struct TextPath {
+	path: Vec<Point>,
+	/// The path is being dragged, typing waits for the release
+	dragging: bool,
+	text: String,
+	scale: u32,
+	color: [u8; 4],
+	spacing: i32,
+	baseline: i32,
+	/// Bounds of the glyphs in the document, and the glyphs drawn on a transparent background
+	rendered: Option<(Rect, Pixels)>,
+}
+
+impl TextPath {
+	pub fn new(spacing: i32, baseline: i32) -> Self {
+		Self { path: Vec::new(), dragging: false, text: String::new(), scale: 1, color: [0, 0, 0, 255], spacing, baseline, rendered: None }
+	}
+
+	/// Position and direction of the path `distance` pixels along it, None past its end.
+	fn along(&self, distance: f32) -> Option<([f32; 2], [f32; 2])> {
+		let mut left = distance;
+		for pair in self.path.windows(2) {
+			let (a, b) = ([pair[0].x as f32, pair[0].y as f32], [pair[1].x as f32, pair[1].y as f32]);
+			let len = (b[0] - a[0]).hypot(b[1] - a[1]);
+			if len == 0. {
+				continue;
+			}
+			if left <= len {
+				let dir = [(b[0] - a[0]) / len, (b[1] - a[1]) / len];
+				return Some(([a[0] + dir[0] * left, a[1] + dir[1] * left], dir));
+			}
+			left -= len;
+		}
+		return None;
+	}
+
+	fn draw(&mut self) {
+		self.rendered = None;
+		if self.path.len() < 2 || self.text.is_empty() {
+			return;
+		}
+		let width = (font::GLYPH_W * self.scale) as f32;
+		let height = (font::GLYPH_H * self.scale) as f32;
+		let advance = (font::advance(self.scale) as i32 + self.spacing).max(1) as f32;
+		let base = self.baseline as f32;
+
+		// Center, direction and document bounds of every glyph on the path
+		let mut glyphs = Vec::new();
+		for (i, c) in self.text.chars().enumerate() {
+			let Some((center, dir)) = self.along(i as f32 * advance + width / 2.) else {
+				break;
+			};
+			let up = [dir[1], -dir[0]];
+			let corners = [(-0.5, base), (0.5, base), (-0.5, base + height), (0.5, base + height)].map(|(u, h)| {
+				[center[0] + dir[0] * u * width + up[0] * h, center[1] + dir[1] * u * width + up[1] * h]
+			});
+			let min = [0, 1].map(|k| corners.iter().map(|p| p[k]).fold(f32::MAX, f32::min).floor() as i32);
+			let max = [0, 1].map(|k| corners.iter().map(|p| p[k]).fold(f32::MIN, f32::max).ceil() as i32);
+			glyphs.push((c, center, dir, min, max));
+		}
+		if glyphs.is_empty() {
+			return;
+		}
+		let min = [0, 1].map(|k| glyphs.iter().map(|g| g.3[k]).min().unwrap());
+		let max = [0, 1].map(|k| glyphs.iter().map(|g| g.4[k]).max().unwrap());
+		let r = Rect::new(min[0], min[1], (max[0] - min[0]) as u32 + 1, (max[1] - min[1]) as u32 + 1);
+
+		let mut pixels = Pixels::new(r.size);
+		for (c, center, dir, gmin, gmax) in &glyphs {
+			for y in gmin[1]..=gmax[1] {
+				for x in gmin[0]..=gmax[0] {
+					let d = [x as f32 + 0.5 - center[0], y as f32 + 0.5 - center[1]];
+					let u = d[0] * dir[0] + d[1] * dir[1] + width / 2.;
+					let h = d[0] * dir[1] - d[1] * dir[0] - base;
+					if u < 0. || h < 0. || h > height {
+						continue;
+					}
+					let (column, row) = ((u / self.scale as f32) as u32, ((height - h) / self.scale as f32) as u32);
+					if font::glyph_pixel(*c, column, row) {
+						font::fill(&mut pixels, (x - r.pos.x) as u32, (y - r.pos.y) as u32, 1, 1, self.color);
+					}
+				}
+			}
+		}
+		self.rendered = Some((r, pixels));
+	}
+}
+
+impl Tool for TextPath {
+	fn name(&self) -> &'static str {
+		"Text path"
+	}
+
+	fn mouse_down(&mut self, p: Point, canvas: &mut Canvas, _: &mut Context) -> bool {
+		self.path = vec![p];
+		self.dragging = true;
+		self.scale = ((canvas.brush().size as f32 / font::GLYPH_H as f32).round() as u32).max(1);
+		let [r, g, b] = canvas.get_color(ColorSlot::Foreground).map(|c| (c * 255.).round() as u8);
+		self.color = [r, g, b, 255];
+		self.draw();
+		return true;
+	}
+
+	fn mouse_move(&mut self, p: Point, _: &mut Canvas, _: &mut Context) -> bool {
+		let Some(last) = self.path.last().filter(|_| self.dragging) else {
+			return false;
+		};
+		if ((p.x - last.x) as f32).hypot((p.y - last.y) as f32) < PATH_STEP {
+			return false;
+		}
+		self.path.push(p);
+		self.draw();
+		return true;
+	}
+
+	fn mouse_up(&mut self, _: &mut Canvas, _: &mut Context) {
+		self.dragging = false;
+	}
+
+	fn confirm(&mut self, canvas: &mut Canvas, ctx: &mut Context) -> bool {
+		let document = Rect { pos: Point { x: 0, y: 0 }, size: canvas.size() };
+		if let Some((rect, pixels)) = &self.rendered {
+			if let Some(r) = rect.intersection(document) {
+				paste_opaque(canvas, ctx, r, r.pos - rect.pos, pixels);
+			}
+		}
+		return true;
+	}
+
+	fn text_input(&mut self, c: char, _: &mut Context) -> bool {
+		if self.path.len() < 2 || self.dragging {
+			return false;
+		}
+		match c {
+			'\u{8}' => {
+				self.text.pop();
+			}
+			c if c.is_control() => return false,
+			c => self.text.push(c),
+		}
+		self.draw();
+		return true;
+	}
+
+	fn captures_keys(&self) -> bool {
+		self.path.len() >= 2
+	}
+
+	fn set_text_offsets(&mut self, spacing: i32, baseline: i32) -> bool {
+		(self.spacing, self.baseline) = (spacing, baseline);
+		self.draw();
+		return true;
+	}
+
+	fn preview(&self) -> Option<(Rect, &Pixels)> {
+		return self.rendered.as_ref().map(|(r, pixels)| (*r, pixels));
+	}
+
+	fn overlay(&self) -> Vec<Rect> {
+		// Each point of the path, and the text bounds
+		let dots = self.path.iter().map(|p| Rect::new(p.x - 1, p.y - 1, 2, 2));
+		return dots.chain(self.rendered.as_ref().map(|(r, _)| *r)).collect();
+	}
+}
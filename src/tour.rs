@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use crate::{config, tween::Tween};
+
+/// Empty, kept once the tour was finished or dismissed
+const DONE_FILE: &str = "tour-done";
+const FADE_IN: Duration = Duration::from_millis(600);
+
+/// What the user does that moves the tour forward.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TourEvent {
+	Stroke,
+	/// An action ran, by id
+	Action(&'static str),
+}
+
+struct TourStep {
+	text: &'static str,
+	done_on: TourEvent,
+}
+
+const STEPS: &[TourStep] = &[
+	TourStep { text: "Drag on the canvas to draw", done_on: TourEvent::Stroke },
+	TourStep { text: "Press Ctrl+Z to undo the stroke", done_on: TourEvent::Action("history.undo") },
+	TourStep { text: "Press Ctrl+S to export the canvas as PNG", done_on: TourEvent::Action("file.export") },
+];
+
+/// First-run guided tour, every step highlights where to act and waits for it.
+pub struct Tour {
+	step: usize,
+	fade: Tween,
+}
+
+impl Tour {
+	pub fn new() -> Self {
+		Self {
+			step: 0,
+			fade: Tween::new(0., 1., FADE_IN),
+		}
+	}
+
+	/// Callout for the current step
+	pub fn text(&self) -> String {
+		format!("Tour {}/{}: {} (Esc to skip)", self.step + 1, STEPS.len(), STEPS[self.step].text)
+	}
+
+	/// Returns if the tour moved to another step or finished.
+	pub fn event(&mut self, event: TourEvent) -> bool {
+		if self.is_finished() || STEPS[self.step].done_on != event {
+			return false;
+		}
+		self.step += 1;
+		self.fade = Tween::new(0., 1., FADE_IN);
+		return true;
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.step >= STEPS.len()
+	}
+
+	/// Opacity of the highlight, fading in at every step.
+	pub fn highlight_alpha(&self) -> f32 {
+		self.fade.value()
+	}
+
+	pub fn is_animating(&self) -> bool {
+		!self.fade.finished()
+	}
+}
+
+pub fn is_first_run() -> bool {
+	config::config_dir().is_some_and(|dir| !dir.join(DONE_FILE).exists())
+}
+
+/// Remembers the tour was finished or dismissed, so it isn't started again.
+pub fn mark_done() {
+	config::write_file(DONE_FILE, "");
+}
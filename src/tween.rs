@@ -0,0 +1,39 @@
+use std::time::{Duration, Instant};
+
+/// Eased interpolation from `from` to `to` over `duration`, starting when created.
+#[derive(Copy, Clone, Debug)]
+pub struct Tween {
+	start: Instant,
+	duration: Duration,
+	from: f32,
+	to: f32,
+}
+
+impl Tween {
+	pub fn new(from: f32, to: f32, duration: Duration) -> Self {
+		Self {
+			start: Instant::now(),
+			duration,
+			from,
+			to,
+		}
+	}
+
+	fn progress(&self) -> f32 {
+		if self.duration.is_zero() {
+			return 1.;
+		}
+		return (self.start.elapsed().as_secs_f32() / self.duration.as_secs_f32()).min(1.);
+	}
+
+	/// Smoothstep eased value
+	pub fn value(&self) -> f32 {
+		let t = self.progress();
+		let eased = t * t * (3. - 2. * t);
+		return self.from + (self.to - self.from) * eased;
+	}
+
+	pub fn finished(&self) -> bool {
+		self.progress() >= 1.
+	}
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::actions::{Action, ActionRegistry, KeyBinding};
+use crate::components::Brush;
+use crate::config;
+use crate::settings::preferences::Theme;
+
+pub const BACKGROUND_FILE: &str = "workspace-background";
+pub const PROFILES_FILE: &str = "workspaces.toml";
+
+/// Profiles used without a profiles file, in its format.
+const BUILTIN_PROFILES: &str = r#"
+[[profile]]
+name = "Sketching"
+panels = ["layers", "controls"]
+brush = { size = 12, hardness = 0.4, spacing = 0.0 }
+
+[[profile]]
+name = "Pixel art"
+panels = ["layers", "swatches"]
+guides = true
+brush = { size = 1, hardness = 1.0, spacing = 0.0 }
+
+[profile.keys]
+# Palettes are exact, replace only the same color
+"Shift+R" = "canvas.replace_color 0"
+"E" = "color.eyedropper_mode foreground"
+
+[[profile]]
+name = "Animation"
+panels = ["layers"]
+brush = { size = 4, hardness = 0.8, spacing = 0.0 }
+"#;
+
+/// Panel a profile can show.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Panel {
+	Layers,
+	Swatches,
+	Controls,
+	Memory,
+}
+
+/// Named preset of how the editor is set up for a kind of work.
+pub struct WorkspaceProfile {
+	pub name: String,
+	/// Brush selected when switching to the profile
+	pub brush: Brush,
+	/// Bindings replacing the defaults while the profile is active
+	pub keymap: Vec<(KeyBinding, Action)>,
+	/// Shown when switching to the profile, the others are hidden
+	pub panels: Vec<Panel>,
+	/// Picks the tool to start with, the brush without one
+	pub tool: Option<Action>,
+	pub guides: bool,
+}
+
+/// A `[[profile]]` table of the profiles file, settings left out of it keep their defaults.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SavedProfile {
+	name: String,
+	brush: Brush,
+	panels: Vec<Panel>,
+	/// Id of the action picking the tool
+	tool: Option<String>,
+	guides: bool,
+	/// Bindings like `Shift+R` to actions like `canvas.replace_color 0`
+	keys: BTreeMap<String, String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ProfilesFile {
+	#[serde(default, rename = "profile")]
+	profiles: Vec<SavedProfile>,
+}
+
+/// Profiles from the config file, the built in ones without any there.
+pub fn load_profiles(actions: &ActionRegistry) -> Vec<WorkspaceProfile> {
+	let file = config::read_toml::<ProfilesFile>(PROFILES_FILE)
+		.filter(|f| !f.profiles.is_empty())
+		.unwrap_or_else(|| toml::from_str(BUILTIN_PROFILES).expect("Invalid built in profiles"));
+	return file.profiles.into_iter().map(|saved| {
+		let tool = saved.tool.and_then(|tool| {
+			let action = actions.parse_action(&tool);
+			if action.is_none() {
+				log::warn!("Ignoring invalid tool {tool:?} of workspace {}", saved.name);
+			}
+			return action;
+		});
+		return WorkspaceProfile {
+			keymap: actions.parse_keymap(&saved.keys),
+			name: saved.name,
+			brush: saved.brush,
+			panels: saved.panels,
+			tool,
+			guides: saved.guides,
+		};
+	}).collect();
+}
+
+/// Color around the document, `#rrggbb` from the config file, that of `theme` without one.
+pub fn load_background(theme: Theme) -> [u8; 3] {
+	let Some(text) = config::read_file(BACKGROUND_FILE) else {
+		return theme.background();
+	};
+	return config::parse_hex_color(text.trim()).unwrap_or_else(|| {
+		log::warn!("Ignoring invalid workspace background {:?}", text.trim());
+		theme.background()
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn builtin_profiles_are_valid() {
+		let actions = ActionRegistry::new();
+		let file: ProfilesFile = toml::from_str(BUILTIN_PROFILES).unwrap();
+		assert_eq!(file.profiles.len(), 3);
+		for saved in &file.profiles {
+			assert_eq!(actions.parse_keymap(&saved.keys).len(), saved.keys.len());
+		}
+		let pixel_art = &file.profiles[1];
+		assert_eq!((pixel_art.brush.size, pixel_art.guides), (1, true));
+		assert_eq!(pixel_art.panels, vec![Panel::Layers, Panel::Swatches]);
+	}
+
+	#[test]
+	fn missing_profile_settings_keep_their_defaults() {
+		let text = "[[profile]]\nname = \"Inking\"\ntool = \"fill.mode\"\n";
+		let read: ProfilesFile = toml::from_str(text).unwrap();
+		let saved = &read.profiles[0];
+		assert_eq!((saved.name.as_str(), saved.tool.as_deref()), ("Inking", Some("fill.mode")));
+		assert_eq!(saved.brush, Brush::default());
+		assert!(saved.panels.is_empty() && saved.keys.is_empty() && !saved.guides);
+	}
+}
@@ -0,0 +1,189 @@
+// Explorer asks the DLL for a thumbnail provider, gives it the file as a stream and asks it for a
+// bitmap no larger than a size. The bitmap is made from the preview embedded in the document, the
+// same one the freedesktop thumbnailer (`pntr --thumbnail`) uses.
+
+#![allow(clippy::needless_return)]
+
+use image::RgbaImage;
+
+/// Header layout of the .pntr container, as written by src/document.rs
+const MAGIC: &[u8; 4] = b"PNTR";
+const HEADER_SIZE: usize = 64;
+const PREVIEW_OFFSET: usize = 36;
+const PREVIEW_LEN: usize = 40;
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+	u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// The preview of a .pntr document, downscaled to fit in `side` by `side`. None when the bytes
+/// aren't one.
+pub fn preview(document: &[u8], side: u32) -> Option<RgbaImage> {
+	if document.len() < HEADER_SIZE || &document[0..4] != MAGIC {
+		return None;
+	}
+	let offset = u32_at(document, PREVIEW_OFFSET) as usize;
+	let len = u32_at(document, PREVIEW_LEN) as usize;
+	let png = document.get(offset..offset.checked_add(len)?)?;
+	let image = image::load_from_memory_with_format(png, image::ImageFormat::Png).ok()?;
+	return Some(image.thumbnail(side, side).into_rgba8());
+}
+
+#[cfg(windows)]
+mod provider {
+	use std::{cell::RefCell, ffi::c_void};
+
+	use windows::{
+		core::{implement, ComInterface, IUnknown, Result, GUID, HRESULT},
+		Win32::{
+			Foundation::{BOOL, CLASS_E_CLASSNOTAVAILABLE, CLASS_E_NOAGGREGATION, E_FAIL, E_UNEXPECTED, S_FALSE},
+			Graphics::Gdi::{CreateDIBSection, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, HBITMAP},
+			System::Com::{IClassFactory, IClassFactory_Impl, IStream, STREAM_SEEK_END, STREAM_SEEK_SET},
+			UI::Shell::{IThumbnailProvider, IThumbnailProvider_Impl, WTSAT_ARGB, WTS_ALPHATYPE},
+			UI::Shell::PropertiesSystem::{IInitializeWithStream, IInitializeWithStream_Impl},
+		},
+	};
+
+	/// Class id the thumbnail provider is registered under, in assets/pntr.reg
+	pub const CLSID_THUMBNAILER: GUID = GUID::from_u128(0x6d1c_8f2e_3b4a_4f0e_9c7d_2a51_e8b3_f406);
+
+	/// Documents larger than this aren't read, their thumbnail is left to the default icon
+	const MAX_DOCUMENT_BYTES: u64 = 1 << 30;
+
+	#[implement(IInitializeWithStream, IThumbnailProvider)]
+	struct Thumbnailer {
+		stream: RefCell<Option<IStream>>,
+	}
+
+	impl IInitializeWithStream_Impl for Thumbnailer {
+		fn Initialize(&self, stream: Option<&IStream>, _mode: u32) -> Result<()> {
+			*self.stream.borrow_mut() = stream.cloned();
+			return Ok(());
+		}
+	}
+
+	impl IThumbnailProvider_Impl for Thumbnailer {
+		fn GetThumbnail(&self, side: u32, bitmap: *mut HBITMAP, alpha: *mut WTS_ALPHATYPE) -> Result<()> {
+			let stream = self.stream.borrow().clone().ok_or(windows::core::Error::from(E_UNEXPECTED))?;
+			let bytes = read_all(&stream)?;
+			let preview = super::preview(&bytes, side).ok_or(windows::core::Error::from(E_FAIL))?;
+			unsafe {
+				*bitmap = to_bitmap(&preview)?;
+				*alpha = WTSAT_ARGB;
+			}
+			return Ok(());
+		}
+	}
+
+	fn read_all(stream: &IStream) -> Result<Vec<u8>> {
+		let mut len = 0;
+		unsafe {
+			stream.Seek(0, STREAM_SEEK_END, Some(&mut len))?;
+			stream.Seek(0, STREAM_SEEK_SET, None)?;
+		}
+		if len > MAX_DOCUMENT_BYTES {
+			return Err(E_FAIL.into());
+		}
+		let mut bytes = vec![0u8; len as usize];
+		let mut filled = 0;
+		while filled < bytes.len() {
+			let mut read = 0;
+			let chunk = (bytes.len() - filled).min(u32::MAX as usize) as u32;
+			unsafe { stream.Read(bytes[filled..].as_mut_ptr() as *mut c_void, chunk, Some(&mut read)).ok()? };
+			if read == 0 {
+				break;
+			}
+			filled += read as usize;
+		}
+		bytes.truncate(filled);
+		return Ok(bytes);
+	}
+
+	/// Top-down 32 bit bitmap with premultiplied BGRA pixels, what Explorer takes with WTSAT_ARGB.
+	unsafe fn to_bitmap(image: &image::RgbaImage) -> Result<HBITMAP> {
+		let info = BITMAPINFO {
+			bmiHeader: BITMAPINFOHEADER {
+				biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+				biWidth: image.width() as i32,
+				biHeight: -(image.height() as i32),
+				biPlanes: 1,
+				biBitCount: 32,
+				biCompression: BI_RGB.0,
+				..Default::default()
+			},
+			..Default::default()
+		};
+		let mut bits: *mut c_void = std::ptr::null_mut();
+		let bitmap = CreateDIBSection(None, &info, DIB_RGB_COLORS, &mut bits, None, 0)?;
+		let dst = std::slice::from_raw_parts_mut(bits as *mut u8, (image.width() * image.height() * 4) as usize);
+		for (d, s) in dst.chunks_exact_mut(4).zip(image.pixels()) {
+			let [r, g, b, a] = s.0;
+			let premultiply = |c: u8| ((c as u32 * a as u32 + 127) / 255) as u8;
+			d.copy_from_slice(&[premultiply(b), premultiply(g), premultiply(r), a]);
+		}
+		return Ok(bitmap);
+	}
+
+	#[implement(IClassFactory)]
+	struct Factory;
+
+	impl IClassFactory_Impl for Factory {
+		fn CreateInstance(&self, outer: Option<&IUnknown>, iid: *const GUID, object: *mut *mut c_void) -> Result<()> {
+			if outer.is_some() {
+				return Err(CLASS_E_NOAGGREGATION.into());
+			}
+			let thumbnailer: IUnknown = Thumbnailer { stream: RefCell::new(None) }.into();
+			return unsafe { thumbnailer.query(iid, object).ok() };
+		}
+
+		fn LockServer(&self, _lock: BOOL) -> Result<()> {
+			return Ok(());
+		}
+	}
+
+	#[no_mangle]
+	extern "system" fn DllGetClassObject(clsid: *const GUID, iid: *const GUID, object: *mut *mut c_void) -> HRESULT {
+		if unsafe { *clsid } != CLSID_THUMBNAILER {
+			return CLASS_E_CLASSNOTAVAILABLE;
+		}
+		let factory: IClassFactory = Factory.into();
+		return unsafe { factory.query(iid, object) };
+	}
+
+	/// Explorer keeps the DLL loaded, providers are short lived
+	#[no_mangle]
+	extern "system" fn DllCanUnloadNow() -> HRESULT {
+		return S_FALSE;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn document(preview: &RgbaImage) -> Vec<u8> {
+		let mut png = Vec::new();
+		preview.write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png).unwrap();
+		let mut bytes = vec![0; HEADER_SIZE];
+		bytes[0..4].copy_from_slice(MAGIC);
+		bytes[PREVIEW_OFFSET..PREVIEW_OFFSET + 4].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+		bytes[PREVIEW_LEN..PREVIEW_LEN + 4].copy_from_slice(&(png.len() as u32).to_le_bytes());
+		bytes.extend_from_slice(&png);
+		return bytes;
+	}
+
+	#[test]
+	fn preview_fits_the_side() {
+		let bytes = document(&RgbaImage::from_pixel(64, 32, image::Rgba([255, 0, 0, 255])));
+		let preview = preview(&bytes, 16).unwrap();
+		assert_eq!((preview.width(), preview.height()), (16, 8));
+	}
+
+	#[test]
+	fn truncated_preview_is_rejected() {
+		let mut bytes = document(&RgbaImage::new(8, 8));
+		bytes.truncate(bytes.len() - 1);
+		assert!(preview(&bytes, 16).is_none());
+		assert!(preview(b"PNG", 16).is_none());
+	}
+}